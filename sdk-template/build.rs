@@ -44,18 +44,31 @@ fn main() {
     writeln!(f, "  RAM (rwx) : ORIGIN = 0x0400, LENGTH = 0x1BFF").unwrap();
     writeln!(f, "  ZP (rw) : ORIGIN = 0x0040, LENGTH = 0x00C0").unwrap();
     writeln!(f, "  SCR (w) : ORIGIN = 0x2000, LENGTH = 0x0008").unwrap();
-    writeln!(f, "  FIXED_FLASH (rx) : ORIGIN = 0x0C000, LENGTH = 0x3FFA").unwrap();
+    writeln!(f, "  FIXED_FLASH (rx) : ORIGIN = 0x0C000, LENGTH = 0x3F7A").unwrap();
+    writeln!(f, "  ROM_INFO (r) : ORIGIN = 0x0FF7A, LENGTH = 0x80").unwrap();
     writeln!(f, "  VECTOR_TABLE (rw) : ORIGIN = 0x0FFFA, LENGTH = 6").unwrap();
     writeln!(f, "}}").unwrap();
 
     writeln!(f, "SECTIONS {{").unwrap();
+    // `.auto` sections go in the first bank with room; every bank lists them so
+    // the linker can spill to the next one (--enable-non-contiguous-regions).
+    // Bank 63 holds saves (see gametank::save), so they skip it.
     for bank in 0..=126 {
-        writeln!(f, "  .text.bank{0} : {{ KEEP(*(.text.bank{0})) KEEP(*(.text.bank{0}.*)) }} > BANK{0} = 0xFF", bank).unwrap();
-        writeln!(f, "  .rodata.bank{0} : {{ KEEP(*(.rodata.bank{0})) KEEP(*(.rodata.bank{0}.*)) }} > BANK{0}", bank).unwrap();
+        let (auto_text, auto_rodata) = match bank {
+            63 => ("", ""),
+            _ => (" *(.text.auto .text.auto.*)", " *(.rodata.auto .rodata.auto.*)"),
+        };
+        writeln!(f, "  .text.bank{0} : {{ KEEP(*(.text.bank{0} .text.bank{0}.* .bank{0}.text .bank{0}.text.*)){1} }} > BANK{0} = 0xFF", bank, auto_text).unwrap();
+        writeln!(f, "  .rodata.bank{0} : {{ KEEP(*(.rodata.bank{0} .rodata.bank{0}.* .bank{0}.rodata .bank{0}.rodata.*)){1} }} > BANK{0}", bank, auto_rodata).unwrap();
     }
 
     writeln!(f, "  .text : {{ *(.text*) }} > FIXED_FLASH = 0xFF").unwrap();
     writeln!(f, "  .rodata : {{ *(.rodata*) }} > FIXED_FLASH").unwrap();
+    // Filled in by gtrom after linking, see gametank::banking
+    writeln!(f, "  .bank_table : {{ KEEP(*(.bank_table .bank_table.*)) }} > FIXED_FLASH").unwrap();
+
+    // Written by gametank_rom!, see gametank::rom_info
+    writeln!(f, "  .rom_info : {{ KEEP(*(.rom_info)) }} > ROM_INFO").unwrap();
 
     // writeln!(f, "  .init : {{ KEEP(*(.init)) }} > FIXED_FLASH").unwrap();
 
@@ -99,6 +112,7 @@ fn main() {
 
     // Hook up the linker script
     println!("cargo:rustc-link-arg=-T{}", link_path.display());
+    println!("cargo:rustc-link-arg=-Wl,--enable-non-contiguous-regions");
 
     // Preserve static asm lib - use absolute path for container compatibility
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
@@ -0,0 +1,161 @@
+.global audio_irq
+.global audio_nmi
+.section .text
+
+; Memory map (4KB = $0000 - $0FFF) as a Markdown table:
+; | Range        | Size         | Purpose                        | Notes                                      |
+; |--------------|--------------|--------------------------------|--------------------------------------------|
+; | $0000-$0040  | $0041 (65)   | Zero Page (Reserved)           | Fast addressing; pointers & small vars     |
+; | $0041-$0050  | $0010 (16)   | Voices (2 × 8 bytes)           | VOICE_BASE = $0041, VOICE_SIZE = 8         |
+; | $0051-$0053  | $0003 (3)    | Stream / IRQ state             | STREAM_AVAIL, TICK, MIX                    |
+; | $0100-$01FF  | $0100 (256)  | CPU Stack                      | CPU stack                                  |
+; | $0200-$05FF  | $0400 (1KB)  | Stream ring buffer (4 × 256)   | RING_START_PAGE = $02, RING_END_PAGE = $06 |
+; | $0600-$0DFF  | $0800 (2KB)  | One-shot sample memory         | Loaded by the main CPU                     |
+; | $0E00-$0FF9  | $01FA (506)  | Code                           | ARAM region for program code               |
+; | $0FFA-$0FFF  | $0006 (6)    | Vector table                   | NMI, RESET, IRQ vectors                    |
+;
+; Addresses are little-endian, and ranges are inclusive.
+;
+; Samples are 8-bit unsigned, centered on $80. Each voice reads from PTR and
+; advances it by STEP (8.8 fixed point, $0100 = one sample per IRQ), so the
+; step sets the pitch. A one-shot voice stops when PTR reaches END.
+;
+; A streaming voice ignores END and loops over the ring buffer instead. The
+; main CPU fills pages of the ring and sends an NMI for each one; every NMI
+; adds a page to STREAM_AVAIL, and every page the voice finishes takes one
+; away. With no pages left the voice holds still until the next NMI.
+
+; Define the base address for the voices (zero page)
+.set VOICE_BASE, 0x0041   ; zero-page base for voice control registers
+.set VOICE_SIZE, 8        ; Each voice occupies 8 bytes
+.set VOICE_COUNT, 2
+
+; Voice flags
+.set FLAG_PLAY, 0x80      ; bit 7: voice is playing
+.set FLAG_STREAM, 0x40    ; bit 6: voice reads from the ring buffer
+
+; Stream and IRQ state
+.set STREAM_AVAIL, 0x0051 ; ring pages filled by the CPU and not yet played
+.set TICK, 0x0052         ; counts samples, so the CPU can tell we're running
+.set MIX, 0x0053          ; running mix accumulator
+
+; Ring buffer pages
+.set RING_START_PAGE, 0x02
+.set RING_END_PAGE, 0x06
+
+; DAC output
+.set DAC, 0x8040
+
+; Macro to define offsets for a voice
+.macro DEFINE_VOICE voice_index
+    .set VOICE_\voice_index\()_BASE, (VOICE_BASE + (VOICE_SIZE * \voice_index))
+    .set VOICE_\voice_index\()_FRAC, (VOICE_\voice_index\()_BASE + 0)
+    .set VOICE_\voice_index\()_PTR_L, (VOICE_\voice_index\()_BASE + 1)
+    .set VOICE_\voice_index\()_PTR_H, (VOICE_\voice_index\()_BASE + 2)
+    .set VOICE_\voice_index\()_END_L, (VOICE_\voice_index\()_BASE + 3)
+    .set VOICE_\voice_index\()_END_H, (VOICE_\voice_index\()_BASE + 4)
+    .set VOICE_\voice_index\()_STEP_L, (VOICE_\voice_index\()_BASE + 5)
+    .set VOICE_\voice_index\()_STEP_H, (VOICE_\voice_index\()_BASE + 6)
+    .set VOICE_\voice_index\()_FLAGS, (VOICE_\voice_index\()_BASE + 7)
+.endm
+
+DEFINE_VOICE 0
+DEFINE_VOICE 1
+
+; Macro to play a single voice into MIX and advance it
+.macro PROCESS_VOICE n
+    bit VOICE_\n\()_FLAGS      ; N = playing, V = streaming
+    bpl .Ldone_\n
+    bvc .Lplay_\n
+    lda STREAM_AVAIL           ; stream waiting on the CPU: hold still
+    beq .Ldone_\n
+
+.Lplay_\n:
+    ; Halve the sample and mix it in around the center
+    lda (VOICE_\n\()_PTR_L)
+    lsr a                      ; 0..127
+    clc
+    adc #0xC0                  ; -64..63
+    clc
+    adc MIX
+    sta MIX
+
+    ; PTR.FRAC += STEP
+    clc
+    lda VOICE_\n\()_FRAC
+    adc VOICE_\n\()_STEP_L
+    sta VOICE_\n\()_FRAC
+    lda VOICE_\n\()_PTR_L
+    adc VOICE_\n\()_STEP_H
+    sta VOICE_\n\()_PTR_L
+    bcc .Lcheck_\n
+    inc VOICE_\n\()_PTR_H
+
+    ; Crossed a page. For a stream, that page is used up.
+    bit VOICE_\n\()_FLAGS
+    bvc .Lcheck_\n
+    dec STREAM_AVAIL
+    lda VOICE_\n\()_PTR_H
+    cmp #RING_END_PAGE
+    bne .Ldone_\n
+    lda #RING_START_PAGE
+    sta VOICE_\n\()_PTR_H
+    bra .Ldone_\n
+
+.Lcheck_\n:
+    ; Streams never end; one-shots stop at PTR >= END
+    bit VOICE_\n\()_FLAGS
+    bvs .Ldone_\n
+    lda VOICE_\n\()_PTR_L
+    cmp VOICE_\n\()_END_L
+    lda VOICE_\n\()_PTR_H
+    sbc VOICE_\n\()_END_H
+    bcc .Ldone_\n
+    stz VOICE_\n\()_FLAGS
+
+.Ldone_\n:
+.endm
+
+audio_irq:
+    ; Silence = 0x80, voices mix in around it
+    lda #0x80
+    sta MIX
+
+    PROCESS_VOICE 0
+    PROCESS_VOICE 1
+
+    ; Output final mixed sample
+    lda MIX
+    sta DAC
+    inc TICK
+
+    rti                    ; return from interrupt
+
+audio_nmi:
+    ; The CPU filled another ring page
+    inc STREAM_AVAIL
+    rti
+
+; Simple main function that just waits
+.section .text
+.global _start
+_start:
+    sei                    ; disable interrupts during setup
+    cld                    ; clear decimal mode
+
+    ; Initialize stack pointer
+    ldx #0xff
+    txs
+
+    ; Enable interrupts
+    cli
+
+main_loop:
+    wai                    ; wait for interrupt
+    jmp main_loop          ; loop forever
+
+; Vector table (must be at $FFFA-$FFFF)
+.section .vector_table, "a"
+    .word audio_nmi        ; NMI vector ($FFFA-$FFFB)
+    .word _start           ; RESET vector ($FFFC-$FFFD)
+    .word audio_irq        ; IRQ/BRK vector ($FFFE-$FFFF)
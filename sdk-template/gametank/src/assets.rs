@@ -0,0 +1,100 @@
+//! # Bundled Assets
+//!
+//! Assets listed in a project's `assets.toml` are converted by `gtrom build`,
+//! packed into ROM banks, and declared in a generated `src/assets_gen.rs`
+//! as constants of the types below:
+//!
+//! ```ignore
+//! mod assets_gen;
+//! use assets_gen::{JUMP, PLAYER_SPRITE};
+//!
+//! // Sprite sheets load like a SpriteSheet, one quadrant at a time
+//! PLAYER_SPRITE.load(console, 0);
+//!
+//! // Everything else is a BankedSlice
+//! JUMP.data.with(|data| Sample::load(console, 0, data));
+//! ```
+//!
+//! The bank each asset went into is fixed when `gtrom build` generates the
+//! file, so there's nothing to look up at runtime.
+
+use crate::banking::BankedSlice;
+use crate::blitter::SpriteQuadrant;
+use crate::compress::unpack_with;
+use crate::console::Console;
+use crate::gfx::SpriteSheet;
+
+/// A sprite sheet, split into 128×128 quadrants that each sit in one ROM
+/// bank. Rows below the image are left out, so small sheets share banks.
+pub struct SpriteAsset {
+    /// Source image width in pixels
+    pub width: u16,
+    /// Source image height in pixels
+    pub height: u16,
+    /// Each quadrant is packed for [`compress`](crate::compress)
+    pub compressed: bool,
+    /// Quadrants in reading order, like [`SpriteSheet`] lays them out
+    pub quadrants: &'static [BankedSlice<u8>],
+}
+
+impl SpriteAsset {
+    /// Copy the sheet into sprite RAM, starting at the top-left of `page`.
+    /// The ROM bank selected beforehand is restored afterwards. Returns
+    /// `false`, having stopped, as [`SpriteSheet::load`] does.
+    pub fn load(&self, console: &mut Console, page: u8) -> bool {
+        let mut quadrant = SpriteQuadrant::One;
+        let mut page = page;
+
+        for chunk in self.quadrants {
+            let loaded = if self.compressed {
+                let previous_page = console.bank_flags.sprite_page();
+                console.set_sprite_page(page);
+                let loaded = unpack_quadrant(console, quadrant, chunk);
+                console.set_sprite_page(previous_page);
+                loaded
+            } else {
+                chunk.with(|data| SpriteSheet::new(data, page).at(quadrant, 0).load(console))
+            };
+            if !loaded {
+                return false;
+            }
+            match quadrant.next() {
+                Some(next) => quadrant = next,
+                None => {
+                    quadrant = SpriteQuadrant::One;
+                    page += 1;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Unpack `chunk` into `quadrant` of the selected sprite page
+fn unpack_quadrant(console: &mut Console, quadrant: SpriteQuadrant, chunk: &BankedSlice<u8>) -> bool {
+    let Some(mut blitter) = console.blitter() else { return false };
+    blitter.set_vram_quad(quadrant);
+    drop(blitter);
+    let Some(mut sm) = console.dma.sprite_mem(&mut console.video_flags) else { return false };
+    let bytes = sm.bytes();
+    let mut i = 0;
+    chunk.with(|packed| unpack_with(packed, |b| {
+        bytes[i] = b;
+        i += 1;
+    }));
+    true
+}
+
+/// An 8-bit unsigned sample, centered on 0x80
+pub struct SampleAsset {
+    /// Playback rate in Hz
+    pub rate: u32,
+    pub data: BankedSlice<u8>,
+}
+
+/// A single-cycle waveform for a wavetable slot
+pub struct WavetableAsset {
+    /// Pitch detected in the source recording, in Hz
+    pub source_hz: u16,
+    pub data: BankedSlice<u8>,
+}
@@ -0,0 +1,284 @@
+//! # Envelopes and Instruments
+//!
+//! Shapes each note's volume over time instead of switching it on and off.
+//! An [`Instrument`] is a wavetable, a volume, an ADSR [`Envelope`] and an
+//! optional vibrato, declared once; a [`Player`] plays notes on it and moves
+//! every voice's envelope along once a frame.
+//!
+//! ```rust,ignore
+//! use rom::sdk::audio::envelope::{Envelope, Instrument, Player};
+//! use rom::sdk::audio::MidiNote;
+//!
+//! // quick attack, then dies away to nothing
+//! static PLUCK: Instrument = Instrument::new(0).with_envelope(Envelope::new(0, 12, 0, 0));
+//! // swells in, holds, and fades out over most of a second after release
+//! static PAD: Instrument = Instrument::new(2)
+//!     .with_volume(40)
+//!     .with_envelope(Envelope::new(30, 10, 192, 45))
+//!     .with_vibrato(6, 8);
+//!
+//! let mut synth = Player::new();
+//! synth.note_on(6, &PAD, MidiNote::C4);
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     synth.tick();
+//!
+//!     if jumped {
+//!         synth.note_on(7, &PLUCK, MidiNote::G5);
+//!     }
+//!     if level_over {
+//!         synth.note_off(6);
+//!     }
+//! }
+//! ```
+//!
+//! ## The Envelope
+//!
+//! Times are in frames. A note rises from silence to its instrument's volume
+//! over the attack, falls to the sustain level over the decay, and holds
+//! there until [`note_off`](Player::note_off), then falls to silence over
+//! the release. The sustain level is a fraction of the volume, 255 being
+//! all of it; with a sustain of 0 the note ends after its decay. A time of
+//! 0 skips straight to the end of that stage.
+//!
+//! ## Sharing Voices
+//!
+//! [`tick`](Player::tick) writes the volume and pitch of each voice playing
+//! a note, and leaves the others alone. The [`sequencer`](super::sequencer)
+//! and [`music`](super::music) write every voice each frame, so with a song
+//! playing, give the player voices the song doesn't use.
+
+use crate::audio::pitch_table::{midi_inc, MidiNote};
+use crate::audio::sequencer::lfo;
+use crate::audio::{voices, MAX_VOLUME, VOICE_COUNT, WAVETABLE};
+
+/// Volume over the life of a note. See the [module docs](self#the-envelope).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    /// Frames to rise from silence to full volume
+    pub attack: u8,
+    /// Frames to fall from full volume to the sustain level
+    pub decay: u8,
+    /// Level held until the note is released, out of 255
+    pub sustain: u8,
+    /// Frames to fall to silence after the note is released
+    pub release: u8,
+}
+
+impl Envelope {
+    /// Full volume from the start until the note is released
+    pub const HOLD: Self = Self::new(0, 0, 255, 0);
+
+    pub const fn new(attack: u8, decay: u8, sustain: u8, release: u8) -> Self {
+        Self { attack, decay, sustain, release }
+    }
+}
+
+/// Everything about how a note sounds, but its pitch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instrument {
+    /// Slot in [`WAVETABLE`]
+    pub wavetable: usize,
+    /// Volume at the top of the envelope, up to [`MAX_VOLUME`]
+    pub volume: u8,
+    pub envelope: Envelope,
+    /// Vibrato size, in frequency increments at its widest; 0 for none
+    pub vibrato_depth: u8,
+    /// How far through the vibrato's cycle each frame moves, out of 256
+    pub vibrato_speed: u8,
+}
+
+impl Instrument {
+    /// Wavetable slot `wavetable` at full volume, with [`Envelope::HOLD`]
+    /// and no vibrato
+    pub const fn new(wavetable: usize) -> Self {
+        Self {
+            wavetable,
+            volume: MAX_VOLUME,
+            envelope: Envelope::HOLD,
+            vibrato_depth: 0,
+            vibrato_speed: 0,
+        }
+    }
+
+    pub const fn with_volume(mut self, volume: u8) -> Self {
+        self.volume = if volume > MAX_VOLUME { MAX_VOLUME } else { volume };
+        self
+    }
+
+    pub const fn with_envelope(mut self, envelope: Envelope) -> Self {
+        self.envelope = envelope;
+        self
+    }
+
+    pub const fn with_vibrato(mut self, depth: u8, speed: u8) -> Self {
+        self.vibrato_depth = depth;
+        self.vibrato_speed = speed;
+        self
+    }
+}
+
+/// Where a voice's note is in its envelope
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    /// Not playing, or finished its release
+    Off,
+}
+
+/// A voice's note and how far through its envelope it is
+#[derive(Clone, Copy)]
+struct Note {
+    instrument: Option<&'static Instrument>,
+    frequency: u16,
+    stage: Stage,
+    /// Envelope level, 8.8 fixed point out of 255
+    level: u16,
+    /// Added to or taken from `level` each frame of this stage
+    step: u16,
+    vibrato_phase: u8,
+}
+
+impl Note {
+    const OFF: Self = Self {
+        instrument: None,
+        frequency: 0,
+        stage: Stage::Off,
+        level: 0,
+        step: 0,
+        vibrato_phase: 0,
+    };
+
+    /// Start `stage`, working out the step that gets from the current level
+    /// to `target` in `frames`
+    fn enter(&mut self, stage: Stage, target: u8, frames: u8) {
+        self.stage = stage;
+        let target = (target as u16) << 8;
+        let span = self.level.abs_diff(target);
+        self.step = match frames {
+            0 => span,
+            n => span.div_ceil(n as u16),
+        };
+    }
+
+    /// Move the envelope on a frame, into the next stage at the end of this one
+    fn advance(&mut self, envelope: &Envelope) {
+        match self.stage {
+            Stage::Attack => {
+                self.level = self.level.saturating_add(self.step).min(0xFF00);
+                if self.level == 0xFF00 {
+                    self.enter(Stage::Decay, envelope.sustain, envelope.decay);
+                }
+            }
+            Stage::Decay => {
+                let sustain = (envelope.sustain as u16) << 8;
+                self.level = self.level.saturating_sub(self.step).max(sustain);
+                if self.level == sustain {
+                    // nothing left to hold
+                    self.stage = if sustain == 0 { Stage::Off } else { Stage::Sustain };
+                }
+            }
+            Stage::Release => {
+                self.level = self.level.saturating_sub(self.step);
+                if self.level == 0 {
+                    self.stage = Stage::Off;
+                }
+            }
+            Stage::Sustain | Stage::Off => {}
+        }
+    }
+}
+
+/// Plays [`Instrument`] notes on the voices. See the [module docs](self).
+pub struct Player {
+    notes: [Note; VOICE_COUNT],
+}
+
+impl Player {
+    pub const fn new() -> Self {
+        Self { notes: [Note::OFF; VOICE_COUNT] }
+    }
+
+    /// Start `note` on `voice` with `instrument`, from the start of its
+    /// envelope, cutting off whatever the voice was playing
+    pub fn note_on(&mut self, voice: usize, instrument: &'static Instrument, note: MidiNote) {
+        let Some(state) = self.notes.get_mut(voice) else { return };
+
+        if let Some(&wavetable) = WAVETABLE.get(instrument.wavetable) {
+            voices()[voice].set_wavetable(wavetable);
+        }
+        *state = Note { instrument: Some(instrument), frequency: midi_inc(note), ..Note::OFF };
+        state.enter(Stage::Attack, 255, instrument.envelope.attack);
+        // a 0 frame attack is at full volume this very frame
+        if instrument.envelope.attack == 0 {
+            state.advance(&instrument.envelope);
+        }
+    }
+
+    /// Release the note on `voice`, so it fades out over its release
+    pub fn note_off(&mut self, voice: usize) {
+        let Some(state) = self.notes.get_mut(voice) else { return };
+        let Some(instrument) = state.instrument else { return };
+        if state.stage != Stage::Off {
+            state.enter(Stage::Release, 0, instrument.envelope.release);
+        }
+    }
+
+    /// Silence `voice` straight away, skipping the release
+    pub fn cut(&mut self, voice: usize) {
+        if let Some(state) = self.notes.get_mut(voice) {
+            if state.instrument.take().is_some() {
+                voices()[voice].mute();
+            }
+            *state = Note::OFF;
+        }
+    }
+
+    /// Where `voice`'s note is in its envelope
+    pub fn stage(&self, voice: usize) -> Stage {
+        self.notes.get(voice).map_or(Stage::Off, |n| n.stage)
+    }
+
+    /// Whether `voice` is still sounding a note, release included
+    pub fn is_playing(&self, voice: usize) -> bool {
+        self.stage(voice) != Stage::Off
+    }
+
+    /// Move every note's envelope and vibrato on a frame and write them to
+    /// the voices. Call once a frame.
+    pub fn tick(&mut self) {
+        let v = voices();
+
+        for (i, note) in self.notes.iter_mut().enumerate() {
+            let Some(instrument) = note.instrument else { continue };
+
+            note.advance(&instrument.envelope);
+            if note.stage == Stage::Off {
+                // mute once, then leave the voice to others
+                note.instrument = None;
+                v[i].mute();
+                continue;
+            }
+
+            let mut frequency = note.frequency;
+            if instrument.vibrato_depth > 0 {
+                note.vibrato_phase = note.vibrato_phase.wrapping_add(instrument.vibrato_speed);
+                frequency = frequency.wrapping_add_signed(lfo(note.vibrato_phase, instrument.vibrato_depth));
+            }
+
+            let volume = (((note.level >> 8) + 1) * instrument.volume as u16) >> 8;
+            v[i].set_frequency(frequency);
+            v[i].set_volume(volume as u8);
+        }
+    }
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -6,12 +6,10 @@
 //! ## Quick Start
 //!
 //! ```rust,ignore
-//! use rom::sdk::audio::{FIRMWARE, voices, MidiNote, WAVETABLE};
+//! use rom::sdk::audio::{init_audio, voices, MidiNote, WAVETABLE};
 //!
-//! // Initialize audio (do once at startup)
-//! console.sc.set_audio(0);                    // Disable while loading
-//! console.audio.copy_from_slice(FIRMWARE);    // Load firmware
-//! console.sc.set_audio(0xFF);                 // Enable at ~14kHz
+//! // Load and start the firmware (do once at startup)
+//! init_audio(console);
 //!
 //! // Play a note
 //! let v = voices();
@@ -49,14 +47,39 @@
 //! console.audio[0x400..0x500].copy_from_slice(&waveform);
 //! ```
 //!
+//! `gtrom convert lead.wav` makes one from a recording: it detects the pitch,
+//! takes a single cycle, and writes `lead.bin` plus a `lead.rs` module that
+//! includes it. `--wav sample` keeps the whole recording instead, resampled to
+//! the ACP rate.
+//!
 //! ## Audio Firmware
 //!
+//! [`init_audio`] stops the ACP, copies [`FIRMWARE`] into audio RAM, starts
+//! it at [`SAMPLE_RATE`], and waits until the firmware is mixing samples.
+//! Voices written before that are overwritten by the copy.
+//!
 //! Enable a firmware via Cargo features:
 //! - `audio-wavetable-8ch` - 8-channel wavetable synth (default, recommended)
 //! - `audio-wavetable-7ch-linear` - 7-channel wavetable synth with linear volume (16 levels)
+//! - `audio-pcm` - 2-voice sample player for recorded sound effects, see [`pcm`]
 //!
 //! The firmware runs on the Audio Coprocessor at ~14kHz sample rate,
 //! with about 660 CPU cycles available per sample for synthesis.
+//!
+//! ## Instruments
+//!
+//! [`envelope`] plays notes through ADSR volume envelopes and vibrato,
+//! described once as an [`Instrument`](envelope::Instrument) instead of
+//! poking each voice's volume every frame.
+//!
+//! ## Tracker Songs
+//!
+//! Songs exported from the `gtgo` tracker can be played back with the
+//! [`sequencer`] module, or by [`music`], which ticks a sequencer from the
+//! vblank interrupt so the game loop doesn't have to.
+
+#[cfg(any(feature = "audio-wavetable-8ch", feature = "audio-wavetable-7ch-linear", feature = "audio-pcm"))]
+use crate::console::Console;
 
 // Audio firmware binary - selected via Cargo.toml features
 #[cfg(feature = "audio-wavetable-8ch")]
@@ -65,6 +88,9 @@ pub static FIRMWARE: &[u8; 4096] = include_bytes!("../../audiofw/wavetable-8ch.b
 #[cfg(feature = "audio-wavetable-7ch-linear")]
 pub static FIRMWARE: &[u8; 4096] = include_bytes!("../../audiofw/wavetable-7ch-linear.bin");
 
+#[cfg(feature = "audio-pcm")]
+pub static FIRMWARE: &[u8; 4096] = include_bytes!("../../audiofw/pcm.bin");
+
 // Audio interface modules - selected via Cargo.toml features
 #[cfg(feature = "audio-wavetable-8ch")]
 pub mod wavetable_8ch;
@@ -76,7 +102,66 @@ pub mod wavetable_7ch_linear;
 #[cfg(feature = "audio-wavetable-7ch-linear")]
 pub use wavetable_7ch_linear::*;
 
+#[cfg(feature = "audio-pcm")]
+pub mod pcm;
+#[cfg(feature = "audio-pcm")]
+pub use pcm::*;
+
 // Shared
 pub mod pitch_table;
+#[cfg(any(feature = "audio-wavetable-8ch", feature = "audio-wavetable-7ch-linear"))]
+pub mod sequencer;
+#[cfg(any(feature = "audio-wavetable-8ch", feature = "audio-wavetable-7ch-linear"))]
+pub mod music;
+#[cfg(any(feature = "audio-wavetable-8ch", feature = "audio-wavetable-7ch-linear"))]
+pub mod envelope;
 pub use pitch_table::MidiNote;
 
+/// Sample rate register value for ~14kHz: the ACP enable bit plus the
+/// slowest IRQ interval
+pub const SAMPLE_RATE: u8 = 0xFF;
+
+/// How many times [`init_audio`] polls for the firmware before giving up
+#[cfg(any(feature = "audio-wavetable-8ch", feature = "audio-wavetable-7ch-linear", feature = "audio-pcm"))]
+const STARTUP_POLLS: u16 = 0x2000;
+
+/// Load [`FIRMWARE`] into audio RAM, restart the ACP, and wait for it to start
+/// producing samples.
+///
+/// Returns `false` if the firmware never started (the ACP is missing or
+/// disabled). [`voices`] only make sound after this returns `true`.
+#[cfg(any(feature = "audio-wavetable-8ch", feature = "audio-wavetable-7ch-linear", feature = "audio-pcm"))]
+pub fn init_audio(console: &mut Console) -> bool {
+    let audio = &mut console.audio;
+
+    // the ACP can't run while its RAM is being replaced
+    audio.set_sample_rate(0);
+    for (dst, src) in audio.aram.iter_mut().zip(FIRMWARE.iter()) {
+        unsafe { core::ptr::write_volatile(dst, *src) };
+    }
+    audio.set_sample_rate(SAMPLE_RATE);
+    audio.reset();
+
+    wait_for_firmware()
+}
+
+/// Both wavetable firmwares start a voice with its phase then its frequency.
+/// Give voice 0 the smallest step and watch its phase move once per sample.
+#[cfg(any(feature = "audio-wavetable-8ch", feature = "audio-wavetable-7ch-linear"))]
+fn wait_for_firmware() -> bool {
+    let phase = VOICE_BASE as *const u8;
+    let frequency = (VOICE_BASE + 2) as *mut u8;
+    unsafe {
+        core::ptr::write_volatile(frequency, 1);
+        let before = core::ptr::read_volatile(phase);
+        let mut started = false;
+        for _ in 0..STARTUP_POLLS {
+            if core::ptr::read_volatile(phase) != before {
+                started = true;
+                break;
+            }
+        }
+        core::ptr::write_volatile(frequency, 0);
+        started
+    }
+}
@@ -0,0 +1,177 @@
+//! # Background Music
+//!
+//! Plays a tracker song from the vblank interrupt, so it keeps time no
+//! matter what the game loop is doing. The first [`play`] installs a
+//! [vblank handler](crate::irq::on_vblank) that ticks a
+//! [`Sequencer`] once per frame.
+//!
+//! ```rust,ignore
+//! use rom::sdk::audio::music;
+//!
+//! static TITLE: &[u8] = include_bytes!("../assets/title.gts");
+//!
+//! init_audio(&mut console);
+//! music::play(TITLE);
+//!
+//! // quieter under dialogue, fading over 15 frames
+//! music::duck(96, 15);
+//! show_dialogue();
+//! music::unduck(15);
+//! ```
+//!
+//! The song is read from the interrupt, so it has to stay mapped: keep it in
+//! the fixed bank at `$C000-$FFFF` or in RAM, not a switched ROM bank.
+//! [`play_song`] takes a [`Song`] compiled by `gtrom build` instead, which
+//! can be in any bank: the handler switches to it and back each frame. A
+//! frame that interrupts one of the game's own bank switches is played a
+//! frame late instead, so the two don't get mixed up.
+//! Voices belong to the music while it plays; sound effects on the same
+//! voices are overwritten at the next frame.
+
+use crate::audio::mute_all;
+use crate::audio::sequencer::{Sequencer, Song};
+use crate::irq;
+
+/// Full volume for [`set_volume`] and [`duck`]
+pub const FULL_VOLUME: u8 = 255;
+
+static mut PLAYER: Option<Sequencer<'static>> = None;
+/// Set while the game changes [`PLAYER`], so the handler skips that frame
+static mut BUSY: bool = false;
+static mut HOOKED: bool = false;
+
+/// Volume being faded towards, and how much to move each frame
+static mut FADE_TARGET: u8 = FULL_VOLUME;
+static mut FADE_STEP: u8 = 0;
+
+fn tick() {
+    unsafe {
+        if core::ptr::read_volatile(&raw const BUSY) {
+            return;
+        }
+        let Some(player) = PLAYER.as_mut() else { return };
+
+        if FADE_STEP > 0 {
+            let volume = player.master_volume();
+            let next = if volume < FADE_TARGET {
+                volume.saturating_add(FADE_STEP).min(FADE_TARGET)
+            } else {
+                volume.saturating_sub(FADE_STEP).max(FADE_TARGET)
+            };
+            player.set_master_volume(next);
+            if next == FADE_TARGET {
+                FADE_STEP = 0;
+            }
+        }
+
+        player.tick();
+    }
+}
+
+/// Run `f` on the player with the handler held off
+fn with_player<R>(f: impl FnOnce(&mut Option<Sequencer<'static>>) -> R) -> R {
+    unsafe {
+        core::ptr::write_volatile(&raw mut BUSY, true);
+        let result = f(&mut PLAYER);
+        core::ptr::write_volatile(&raw mut BUSY, false);
+        result
+    }
+}
+
+/// Start `song` from the beginning, replacing whatever was playing. The
+/// volume carries over.
+///
+/// Returns `false` if the vblank handler couldn't be installed because
+/// [`irq::MAX_HANDLERS`] are already in use.
+pub fn play(song: &'static [u8]) -> bool {
+    start(Sequencer::new(song))
+}
+
+/// Start a compiled `song` from the beginning, as [`play`] does, after
+/// loading its wavetables.
+pub fn play_song(song: &Song) -> bool {
+    start(Sequencer::for_song(song))
+}
+
+fn start(mut sequencer: Sequencer<'static>) -> bool {
+    unsafe {
+        if !HOOKED {
+            if !irq::on_vblank(tick) {
+                return false;
+            }
+            HOOKED = true;
+        }
+    }
+
+    with_player(|player| {
+        let volume = player.as_ref().map_or(FULL_VOLUME, Sequencer::master_volume);
+        sequencer.set_master_volume(volume);
+        sequencer.play();
+        *player = Some(sequencer);
+    });
+    true
+}
+
+/// Stop the song and silence every voice. [`resume`] starts it over.
+pub fn stop() {
+    with_player(|player| {
+        if let Some(player) = player {
+            player.stop();
+            player.rewind();
+        }
+    });
+}
+
+/// Pause the song where it is and silence every voice.
+pub fn pause() {
+    with_player(|player| {
+        if let Some(player) = player {
+            player.pause();
+            mute_all();
+        }
+    });
+}
+
+/// Carry on after [`pause`] or [`stop`].
+pub fn resume() {
+    with_player(|player| {
+        if let Some(player) = player {
+            player.play();
+        }
+    });
+}
+
+pub fn is_playing() -> bool {
+    with_player(|player| player.as_ref().is_some_and(Sequencer::is_playing))
+}
+
+/// Set the music volume right away, from 0 (silent) to [`FULL_VOLUME`].
+pub fn set_volume(volume: u8) {
+    with_player(|player| {
+        unsafe { FADE_STEP = 0 };
+        if let Some(player) = player {
+            player.set_master_volume(volume);
+        }
+    });
+}
+
+/// Fade the music down (or up) to `volume` over about `frames` frames,
+/// e.g. under dialogue or a jingle.
+pub fn duck(volume: u8, frames: u8) {
+    with_player(|player| {
+        let Some(player) = player else { return };
+        let distance = player.master_volume().abs_diff(volume);
+        unsafe {
+            FADE_TARGET = volume;
+            FADE_STEP = match frames {
+                0 => distance.max(1),
+                _ => distance.div_ceil(frames).max(1),
+            };
+        }
+    });
+}
+
+/// Fade back to full volume over about `frames` frames.
+pub fn unduck(frames: u8) {
+    duck(FULL_VOLUME, frames);
+}
@@ -0,0 +1,296 @@
+//! # PCM Sample Player
+//!
+//! This firmware plays recorded sounds instead of synthesizing them. It has
+//! 2 voices, each of which can play:
+//! - **One-shots** - a [`Sample`] loaded into the 2KB of sample memory
+//! - **A stream** - data fed from ROM a page at a time, for sounds that
+//!   don't fit in audio RAM
+//!
+//! Samples are 8-bit unsigned, centered on 0x80 (what `gtrom convert
+//! --wav sample` writes). Each voice plays at half amplitude so the two can
+//! never clip.
+//!
+//! ## Quick Start
+//!
+//! ```rust,ignore
+//! use rom::sdk::audio::{init_audio, semitone_step, voices, Sample, NATIVE_STEP};
+//!
+//! init_audio(console);
+//! let jump = Sample::load(console, 0, JUMP).unwrap();
+//!
+//! // At the rate it was converted at
+//! voices()[0].play(&jump, NATIVE_STEP);
+//!
+//! // A fifth higher
+//! voices()[0].play(&jump, semitone_step(7));
+//! ```
+//!
+//! ## Streaming
+//!
+//! A [`Stream`] plays through a 1KB ring buffer in audio RAM. Keep it topped
+//! up from the main loop; each page tells the ACP it's ready with an NMI:
+//!
+//! ```rust,ignore
+//! let mut rest: &[u8] = &INTRO_MUSIC;
+//! let mut stream = Stream::start(1, NATIVE_STEP);
+//!
+//! loop {
+//!     stream.fill(console, &mut rest);
+//!     // ...
+//! }
+//! ```
+//!
+//! At ~14kHz a voice plays about one page per frame, so the four pages of
+//! the ring last a few frames. If the ring runs dry the voice waits in
+//! silence until the next page arrives.
+
+use crate::console::Console;
+
+/// Base address for voice registers (CPU-side address, ACP RAM at 0x3000)
+pub const VOICE_BASE: usize = 0x3041;
+/// Number of bytes per voice
+pub const VOICE_SIZE: usize = 8;
+/// Number of voices
+pub const VOICE_COUNT: usize = 2;
+
+/// Start of one-shot sample memory (ACP-side)
+pub const SAMPLE_BASE: u16 = 0x0600;
+/// Bytes of one-shot sample memory
+pub const SAMPLE_MEMORY_SIZE: usize = 0x0800;
+
+/// Step that plays a sample at the rate it was converted at
+pub const NATIVE_STEP: u16 = 0x0100;
+
+/// Ring pages filled by the CPU and not yet played
+const STREAM_AVAIL: *mut u8 = 0x3051 as *mut u8;
+/// Counts samples while the firmware runs
+const TICK: *const u8 = 0x3052 as *const u8;
+
+/// First page of the stream ring buffer (ACP-side)
+const RING_START_PAGE: u8 = 0x02;
+/// Pages in the stream ring buffer
+const RING_PAGES: u8 = 4;
+const PAGE_SIZE: usize = 256;
+
+const FLAG_PLAY: u8 = 0x80;
+const FLAG_STREAM: u8 = 0x40;
+
+/// Steps for 0-11 semitones up (256 * 2^(n/12))
+const SEMITONE_STEPS: [u16; 12] = [256, 271, 287, 304, 323, 342, 362, 384, 406, 431, 456, 483];
+
+/// The step that plays a sample `semitones` higher (or lower, if negative)
+/// than its native pitch.
+pub const fn semitone_step(semitones: i8) -> u16 {
+    let octave = semitones.div_euclid(12);
+    let step = SEMITONE_STEPS[semitones.rem_euclid(12) as usize];
+    if octave >= 0 {
+        step << octave
+    } else {
+        step >> -octave
+    }
+}
+
+/// A sound in sample memory, ready to play.
+#[derive(Clone, Copy)]
+pub struct Sample {
+    addr: u16,
+    len: u16,
+}
+
+impl Sample {
+    /// Copy `data` into sample memory, `offset` bytes in.
+    ///
+    /// Returns `None` if it doesn't fit. Stop any voice playing from that
+    /// part of sample memory first.
+    pub fn load(console: &mut Console, offset: u16, data: &[u8]) -> Option<Sample> {
+        let start = SAMPLE_BASE as usize + offset as usize;
+        let end = start + data.len();
+        if end > SAMPLE_BASE as usize + SAMPLE_MEMORY_SIZE {
+            return None;
+        }
+
+        for (dst, src) in console.audio.aram[start..end].iter_mut().zip(data) {
+            unsafe { core::ptr::write_volatile(dst, *src) };
+        }
+
+        Some(Sample { addr: start as u16, len: data.len() as u16 })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// A single sample playback voice.
+///
+/// This struct is laid out to match the ACP firmware's memory layout exactly.
+/// Addresses and steps are split into bytes so they can be written in order.
+#[repr(C)]
+pub struct Voice {
+    /// Fractional part of the read position
+    frac: u8,
+    /// Read position in ACP RAM
+    ptr: [u8; 2],
+    /// One-shots stop when `ptr` gets here
+    end: [u8; 2],
+    /// Added to `ptr.frac` every sample (8.8 fixed point)
+    step: [u8; 2],
+    /// Bit 7: playing, bit 6: streaming
+    flags: u8,
+}
+
+impl Voice {
+    #[inline(always)]
+    fn write(field: &mut u8, value: u8) {
+        unsafe { core::ptr::write_volatile(field, value) };
+    }
+
+    /// Stop the voice, set it up, and start it again. The ACP only looks at
+    /// the other fields while the play flag is set, so that goes last.
+    fn start(&mut self, ptr: u16, end: u16, step: u16, flags: u8) {
+        Self::write(&mut self.flags, 0);
+        Self::write(&mut self.frac, 0);
+        Self::write(&mut self.ptr[0], ptr as u8);
+        Self::write(&mut self.ptr[1], (ptr >> 8) as u8);
+        Self::write(&mut self.end[0], end as u8);
+        Self::write(&mut self.end[1], (end >> 8) as u8);
+        Self::write(&mut self.step[0], step as u8);
+        Self::write(&mut self.step[1], (step >> 8) as u8);
+        Self::write(&mut self.flags, flags);
+    }
+
+    /// Play `sample` once from the start, replacing whatever this voice was
+    /// playing. `step` sets the pitch: [`NATIVE_STEP`] for the original.
+    pub fn play(&mut self, sample: &Sample, step: u16) {
+        self.start(sample.addr, sample.addr + sample.len, step, FLAG_PLAY);
+    }
+
+    /// Change the pitch of whatever is playing.
+    ///
+    /// The two bytes land separately, so the ACP may play one sample at a
+    /// step between the old and new ones.
+    #[inline]
+    pub fn set_step(&mut self, step: u16) {
+        Self::write(&mut self.step[0], step as u8);
+        Self::write(&mut self.step[1], (step >> 8) as u8);
+    }
+
+    /// Silence this voice immediately.
+    #[inline]
+    pub fn stop(&mut self) {
+        Self::write(&mut self.flags, 0);
+    }
+
+    /// Whether the voice is still playing. One-shots stop on their own at
+    /// the end of the sample.
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        unsafe { core::ptr::read_volatile(&self.flags) & FLAG_PLAY != 0 }
+    }
+}
+
+/// Get a mutable reference to both voices.
+///
+/// # Safety
+/// This function creates a mutable reference to memory-mapped hardware.
+/// The caller must ensure exclusive access to the voice registers.
+#[inline]
+pub fn voices() -> &'static mut [Voice; VOICE_COUNT] {
+    unsafe { &mut *(VOICE_BASE as *mut [Voice; VOICE_COUNT]) }
+}
+
+/// Get a mutable reference to a single voice by index (0-1).
+///
+/// # Panics
+/// Panics if `index >= 2`.
+#[inline]
+pub fn voice(index: usize) -> &'static mut Voice {
+    assert!(index < VOICE_COUNT, "voice index out of range");
+    unsafe { &mut *((VOICE_BASE + index * VOICE_SIZE) as *mut Voice) }
+}
+
+/// Silence all voices.
+#[inline]
+pub fn mute_all() {
+    for voice in voices().iter_mut() {
+        voice.stop();
+    }
+}
+
+/// A voice playing from the stream ring buffer. Only one stream can play
+/// at a time.
+pub struct Stream {
+    voice: usize,
+    /// Ring page the next [`push_page`](Stream::push_page) writes to
+    write_page: u8,
+}
+
+impl Stream {
+    /// Start streaming on `voice`, at `step` like [`Voice::play`]. The voice
+    /// stays silent until the first page arrives.
+    pub fn start(voice_index: usize, step: u16) -> Self {
+        let voice = voice(voice_index);
+        voice.stop();
+        unsafe { core::ptr::write_volatile(STREAM_AVAIL, 0) };
+
+        let ring = (RING_START_PAGE as u16) << 8;
+        voice.start(ring, 0, step, FLAG_PLAY | FLAG_STREAM);
+
+        Self { voice: voice_index, write_page: 0 }
+    }
+
+    /// Pages that can be pushed without overwriting ones still to play
+    pub fn free_pages(&self) -> u8 {
+        RING_PAGES - unsafe { core::ptr::read_volatile(STREAM_AVAIL) }
+    }
+
+    /// Copy up to one page of samples into the ring and hand it to the ACP.
+    /// A short page is padded with silence. Returns `false` if the ring is
+    /// full.
+    pub fn push_page(&mut self, console: &mut Console, data: &[u8]) -> bool {
+        if self.free_pages() == 0 {
+            return false;
+        }
+
+        let start = (RING_START_PAGE + self.write_page) as usize * PAGE_SIZE;
+        let page = &mut console.audio.aram[start..start + PAGE_SIZE];
+        for (i, dst) in page.iter_mut().enumerate() {
+            let sample = data.get(i).copied().unwrap_or(0x80);
+            unsafe { core::ptr::write_volatile(dst, sample) };
+        }
+
+        self.write_page = (self.write_page + 1) % RING_PAGES;
+        console.audio.nmi();
+        true
+    }
+
+    /// Push pages from the front of `data` until the ring is full, advancing
+    /// `data` past what was sent.
+    pub fn fill(&mut self, console: &mut Console, data: &mut &[u8]) {
+        while !data.is_empty() && self.free_pages() > 0 {
+            let len = data.len().min(PAGE_SIZE);
+            self.push_page(console, &data[..len]);
+            *data = &data[len..];
+        }
+    }
+
+    /// Whether every pushed page has played
+    pub fn is_drained(&self) -> bool {
+        self.free_pages() == RING_PAGES
+    }
+
+    /// Stop the stream's voice.
+    pub fn stop(self) {
+        voice(self.voice).stop();
+    }
+}
+
+/// Wait for the sample counter to move.
+pub(super) fn wait_for_firmware() -> bool {
+    let before = unsafe { core::ptr::read_volatile(TICK) };
+    (0..super::STARTUP_POLLS).any(|_| unsafe { core::ptr::read_volatile(TICK) } != before)
+}
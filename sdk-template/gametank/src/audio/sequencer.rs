@@ -0,0 +1,597 @@
+//! # Sequencer
+//!
+//! Plays songs exported from the `gtgo` tracker. The sequencer runs on the
+//! main CPU: call [`Sequencer::tick`] once per frame and it writes notes,
+//! volumes, and effects into the [`voices`] of whichever firmware is enabled.
+//!
+//! ```rust,ignore
+//! use rom::sdk::audio::sequencer::Sequencer;
+//!
+//! static SONG: &[u8] = include_bytes!("../assets/song.gts");
+//!
+//! let mut seq = Sequencer::new(SONG);
+//! seq.play();
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     seq.tick();
+//! }
+//! ```
+//!
+//! ## Song Format
+//!
+//! All multi-byte values are little-endian.
+//!
+//! | offset | size    | field                                   |
+//! |--------|---------|-----------------------------------------|
+//! | 0      | 4       | magic, `GTS` followed by version `2`    |
+//! | 4      | 1       | initial tempo (rows per minute)         |
+//! | 5      | 1       | pattern count `P`                       |
+//! | 6      | 1       | order list length `L`                   |
+//! | 7      | 1       | loop start (order list index)           |
+//! | 8      | `2 * L` | order list (pattern, row count pairs)   |
+//! | 8 + 2L | `2 * P` | offset of each pattern from song start  |
+//!
+//! An order entry plays its pattern for its row count (1-64), so shorter
+//! sections can reuse the start of a pattern. After the last entry, playback
+//! goes back to the loop start.
+//!
+//! Each pattern is a stream of commands. Row events come first, followed by
+//! [`op::WAIT`] with the number of rows until the next event, and the
+//! stream ends with [`op::END`]. Channel commands keep the channel number in
+//! the low nibble of the opcode.
+//!
+//! Channels the current firmware doesn't have (e.g. channel 7 on the 7-voice
+//! firmware) are ignored.
+//!
+//! ## Compiled Songs
+//!
+//! `gtrom build` compiles `.gtt` tracker projects in the asset directories
+//! straight into the ROM, each as a [`Song`] in whichever bank has room.
+//! The `.wav` files in a `<name>.wavetables` directory next to `<name>.gtt`
+//! go with it, in name order, and are copied into wavetable slots 0 and up
+//! before it plays:
+//!
+//! ```rust,ignore
+//! include!("../target/assets/assets.rs");
+//!
+//! // assets/music/title.gtt, with assets/music/title.wavetables/*.wav
+//! let mut seq = Sequencer::for_song(&music_title::TITLE);
+//! seq.play();
+//! ```
+//!
+//! A sequencer playing a [`Song`] selects its bank whenever it reads the
+//! song, and puts back the one that was selected.
+
+use crate::audio::pitch_table::MIDI_INCREMENTS;
+use crate::audio::{voices, MAX_VOLUME, VOICE_COUNT, WAVETABLE};
+use crate::banking::BankEntry;
+use crate::via::Via;
+
+/// Song header magic (including the version byte)
+pub const MAGIC: [u8; 4] = *b"GTS\x02";
+/// Size of the fixed song header
+pub const HEADER_SIZE: usize = 8;
+/// Tracker channels, regardless of how many voices the firmware has
+pub const CHANNELS: usize = 8;
+/// Rows in a pattern
+pub const ROWS: u8 = 64;
+/// Bytes in each of a [`Song`]'s wavetables
+pub const WAVETABLE_LEN: usize = 256;
+
+/// Opcodes used in pattern streams
+pub mod op {
+    /// End of pattern, continue with the next order entry
+    pub const END: u8 = 0x00;
+    /// `WAIT rows` - wait before reading the next event
+    pub const WAIT: u8 = 0x01;
+
+    /// `NOTE|ch note`
+    pub const NOTE: u8 = 0x10;
+    /// `VOLUME|ch volume`
+    pub const VOLUME: u8 = 0x20;
+    /// `WAVETABLE|ch lo hi` - ACP-side wavetable address
+    pub const WAVETABLE: u8 = 0x30;
+    /// `PHASE|ch lo hi`
+    pub const PHASE: u8 = 0x40;
+    /// `TREMOLO|ch depth speed`
+    pub const TREMOLO: u8 = 0x50;
+    /// `VIBRATO|ch depth speed`
+    pub const VIBRATO: u8 = 0x60;
+    /// `SLIDE_VOL|ch rows lo hi` - add a signed delta to volume every row
+    pub const SLIDE_VOL: u8 = 0x70;
+    /// `STOP_VSLIDE|ch`
+    pub const STOP_VSLIDE: u8 = 0x80;
+    /// `SLIDE_PITCH|ch rows lo hi` - add a signed delta to frequency every row
+    pub const SLIDE_PITCH: u8 = 0x90;
+    /// `STOP_PSLIDE|ch`
+    pub const STOP_PSLIDE: u8 = 0xA0;
+
+    /// `TEMPO bpm`
+    pub const TEMPO: u8 = 0xF1;
+    /// `LOAD slot lo hi` - copy 256 bytes from a CPU address into a wavetable slot
+    pub const LOAD: u8 = 0xF2;
+    /// `PATTERN n` - jump to row 0 of pattern `n`
+    pub const PATTERN: u8 = 0xF3;
+    /// `BEAT row` - jump to a row in the current pattern
+    pub const BEAT: u8 = 0xF4;
+    /// `ADVANCE` - jump to the next order entry
+    pub const ADVANCE: u8 = 0xF5;
+    /// `STOP` - stop playback
+    pub const STOP: u8 = 0xF6;
+}
+
+#[derive(Clone, Copy, Default)]
+struct Channel {
+    frequency: u16,
+    volume: u8,
+    vol_slide: i16,
+    vol_slide_rows: u8,
+    pitch_slide: i16,
+    pitch_slide_rows: u8,
+    tremolo_depth: u8,
+    tremolo_speed: u8,
+    tremolo_phase: u8,
+    vibrato_depth: u8,
+    vibrato_speed: u8,
+    vibrato_phase: u8,
+}
+
+/// Triangle LFO in -64..=63, scaled by depth / 64. Also drives
+/// [`envelope`](super::envelope)'s vibrato.
+pub(super) fn lfo(phase: u8, depth: u8) -> i16 {
+    let tri = if phase < 128 { phase } else { 255 - phase } as i16;
+    ((tri - 64) * depth as i16) >> 6
+}
+
+/// A song compiled from a tracker project by `gtrom build`, with its
+/// wavetables. See [Compiled Songs](self#compiled-songs).
+#[derive(Clone, Copy)]
+pub struct Song {
+    bank: &'static BankEntry,
+    data: &'static [u8],
+    len: usize,
+}
+
+impl Song {
+    /// A song whose first `len` bytes of `data` are the song, followed by
+    /// its wavetables.
+    ///
+    /// # Safety
+    /// `data` has to be linked into the bank `bank` is filled in with.
+    pub const unsafe fn new(bank: &'static BankEntry, data: &'static [u8], len: usize) -> Self {
+        Self { bank, data, len }
+    }
+
+    /// The ROM bank the song is in
+    pub fn bank(&self) -> u8 {
+        self.bank.get()
+    }
+
+    /// Wavetables loaded before it plays
+    pub fn wavetable_count(&self) -> usize {
+        (self.data.len() - self.len) / WAVETABLE_LEN
+    }
+
+    /// Copy the song's wavetables into slots 0 and up, as many as the
+    /// firmware has.
+    pub fn load_wavetables(&self) {
+        with_bank(Some(self.bank()), || {
+            for (table, addr) in self.data[self.len..].chunks_exact(WAVETABLE_LEN).zip(WAVETABLE) {
+                let dst = 0x3000 + addr as usize;
+                unsafe {
+                    core::ptr::copy_nonoverlapping(table.as_ptr(), dst as *mut u8, WAVETABLE_LEN);
+                }
+            }
+        })
+    }
+}
+
+/// Select ROM bank `bank`, if there is one, run `f`, then put back the bank
+/// that was selected before.
+fn with_bank<R>(bank: Option<u8>, f: impl FnOnce() -> R) -> R {
+    let Some(bank) = bank else { return f() };
+    let via = unsafe { Via::new() };
+    let previous = via.rom_bank();
+    via.change_rom_bank(bank);
+
+    let result = f();
+
+    let via = unsafe { Via::new() };
+    via.change_rom_bank(previous);
+    result
+}
+
+/// Song player
+pub struct Sequencer<'a> {
+    song: &'a [u8],
+    playing: bool,
+    order_pos: u8,
+    /// Rows the current order entry plays for
+    order_rows: u8,
+    /// Row of the pattern that plays next
+    row: u8,
+    pattern_start: usize,
+    cursor: usize,
+    wait_rows: u8,
+    frames_per_row: u8,
+    frame: u8,
+    /// Scales every channel's volume; 255 is full
+    master_volume: u8,
+    channels: [Channel; CHANNELS],
+    /// The bank the song is in, for a [`Song`]
+    bank: Option<u8>,
+}
+
+impl<'a> Sequencer<'a> {
+    /// Create a player for an exported song. Playback starts stopped.
+    ///
+    /// Songs with a bad header are accepted but never play.
+    pub fn new(song: &'a [u8]) -> Self {
+        Self::new_in(song, None)
+    }
+
+    /// Create a player for a compiled song, and load its wavetables.
+    /// Playback starts stopped.
+    pub fn for_song(song: &Song) -> Sequencer<'static> {
+        song.load_wavetables();
+        Sequencer::new_in(&song.data[..song.len], Some(song.bank()))
+    }
+
+    fn new_in(song: &'a [u8], bank: Option<u8>) -> Self {
+        let mut seq = Self {
+            song,
+            playing: false,
+            order_pos: 0,
+            order_rows: ROWS,
+            row: 0,
+            pattern_start: 0,
+            cursor: 0,
+            wait_rows: 0,
+            frames_per_row: 1,
+            frame: 0,
+            master_volume: 255,
+            channels: [Channel::default(); CHANNELS],
+            bank,
+        };
+        seq.rewind();
+        seq
+    }
+
+    fn is_valid(&self) -> bool {
+        self.song.len() >= HEADER_SIZE
+            && self.song[0..4] == MAGIC
+            && self.byte(5) > 0
+            && self.byte(6) > 0
+    }
+
+    fn byte(&self, offset: usize) -> u8 {
+        self.song.get(offset).copied().unwrap_or(op::END)
+    }
+
+    fn word(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.byte(offset), self.byte(offset + 1)])
+    }
+
+    fn next(&mut self) -> u8 {
+        let b = self.byte(self.cursor);
+        self.cursor += 1;
+        b
+    }
+
+    fn next_word(&mut self) -> u16 {
+        let w = self.word(self.cursor);
+        self.cursor += 2;
+        w
+    }
+
+    fn set_tempo(&mut self, bpm: u8) {
+        // one row per beat, 60 frames per second
+        self.frames_per_row = (3600 / bpm.max(15) as u16) as u8;
+    }
+
+    fn order_len(&self) -> u8 {
+        self.byte(6)
+    }
+
+    fn load_pattern(&mut self, pattern: u8) {
+        let pattern_count = self.byte(5);
+        let table = HEADER_SIZE + self.order_len() as usize * 2;
+        let pattern = if pattern < pattern_count { pattern } else { 0 };
+        self.pattern_start = self.word(table + pattern as usize * 2) as usize;
+        self.cursor = self.pattern_start;
+        self.wait_rows = 0;
+        self.row = 0;
+    }
+
+    /// Play order entry `pos`, or the loop start past the end
+    fn load_order(&mut self, pos: u8) {
+        let loop_start = self.byte(7);
+        self.order_pos = if pos < self.order_len() {
+            pos
+        } else if loop_start < self.order_len() {
+            loop_start
+        } else {
+            0
+        };
+
+        let entry = HEADER_SIZE + self.order_pos as usize * 2;
+        let rows = self.byte(entry + 1);
+        self.order_rows = if rows == 0 || rows > ROWS { ROWS } else { rows };
+        self.load_pattern(self.byte(entry));
+    }
+
+    /// Jump to the start of the song and reset all channel state
+    pub fn rewind(&mut self) {
+        self.channels = [Channel::default(); CHANNELS];
+        self.frame = 0;
+        with_bank(self.bank, || {
+            if self.is_valid() {
+                self.set_tempo(self.byte(4));
+                self.load_order(0);
+            }
+        })
+    }
+
+    /// Start (or resume) playback
+    pub fn play(&mut self) {
+        self.playing = with_bank(self.bank, || self.is_valid());
+    }
+
+    /// Pause playback. Voices keep whatever they were last set to.
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Stop playback and silence all voices
+    pub fn stop(&mut self) {
+        self.playing = false;
+        for v in voices().iter_mut() {
+            v.mute();
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Scale the song's volumes, from 0 (silent) to 255 (as written).
+    /// Applies from the next [`tick`](Self::tick).
+    pub fn set_master_volume(&mut self, volume: u8) {
+        self.master_volume = volume;
+    }
+
+    pub fn master_volume(&self) -> u8 {
+        self.master_volume
+    }
+
+    /// Index into the order list of the pattern that's currently playing
+    pub fn order_position(&self) -> u8 {
+        self.order_pos
+    }
+
+    /// Advance by one frame. Call once per vblank.
+    ///
+    /// A banked song's rows are read with its bank switched in. If the tick
+    /// interrupts the game halfway through a bank switch of its own, the
+    /// frame is put off until the next tick rather than garble both.
+    pub fn tick(&mut self) {
+        if !self.playing {
+            return;
+        }
+
+        if self.frame == 0 {
+            if self.bank.is_some() && unsafe { Via::new() }.is_switching_bank() {
+                return;
+            }
+            with_bank(self.bank, || self.step_row());
+        }
+
+        self.frame += 1;
+        if self.frame >= self.frames_per_row {
+            self.frame = 0;
+        }
+
+        self.update_voices();
+    }
+
+    fn step_row(&mut self) {
+        for ch in self.channels.iter_mut() {
+            if ch.vol_slide_rows > 0 {
+                ch.vol_slide_rows -= 1;
+                let vol = ch.volume as i16 + ch.vol_slide;
+                ch.volume = vol.clamp(0, MAX_VOLUME as i16) as u8;
+            }
+            if ch.pitch_slide_rows > 0 {
+                ch.pitch_slide_rows -= 1;
+                ch.frequency = ch.frequency.wrapping_add_signed(ch.pitch_slide);
+            }
+        }
+
+        // a short order entry cuts its pattern off
+        if self.row >= self.order_rows {
+            self.load_order(self.order_pos.wrapping_add(1));
+        }
+
+        if self.wait_rows == 0 {
+            self.run_events();
+        }
+
+        self.wait_rows = self.wait_rows.saturating_sub(1);
+        self.row = self.row.saturating_add(1);
+    }
+
+    /// Execute events until the next WAIT
+    fn run_events(&mut self) {
+        // bail out of malformed songs, like a BEAT back to its own row,
+        // instead of spinning forever
+        let mut jumps = 0;
+
+        while self.playing {
+            let opcode = self.next();
+
+            match opcode {
+                op::END | op::ADVANCE => {
+                    jumps += 1;
+                    if jumps > 2 {
+                        self.playing = false;
+                        return;
+                    }
+                    self.load_order(self.order_pos.wrapping_add(1));
+                }
+                op::WAIT => {
+                    self.wait_rows = self.next();
+                    return;
+                }
+                op::TEMPO => {
+                    let bpm = self.next();
+                    self.set_tempo(bpm);
+                }
+                op::LOAD => {
+                    let slot = self.next() as usize;
+                    let src = self.next_word() as usize;
+                    if slot < WAVETABLE.len() {
+                        let dst = 0x3000 + WAVETABLE[slot] as usize;
+                        unsafe {
+                            core::ptr::copy_nonoverlapping(src as *const u8, dst as *mut u8, 256);
+                        }
+                    }
+                }
+                op::PATTERN => {
+                    jumps += 1;
+                    if jumps > 2 {
+                        self.playing = false;
+                        return;
+                    }
+                    let pattern = self.next();
+                    self.load_pattern(pattern);
+                }
+                op::BEAT => {
+                    jumps += 1;
+                    if jumps > 2 {
+                        self.playing = false;
+                        return;
+                    }
+                    let row = self.next();
+                    self.seek_row(row);
+                    self.row = row;
+                    if self.wait_rows > 0 {
+                        return;
+                    }
+                }
+                op::STOP => {
+                    self.stop();
+                    return;
+                }
+                _ => self.channel_event(opcode),
+            }
+        }
+    }
+
+    fn channel_event(&mut self, opcode: u8) {
+        let ch = (opcode & 0x0F) as usize;
+        let kind = opcode & 0xF0;
+
+        // read operands first so the cursor stays in sync for ignored channels
+        let (a, b) = match kind {
+            op::NOTE | op::VOLUME => (self.next(), 0),
+            op::WAVETABLE | op::PHASE => (0, self.next_word()),
+            op::TREMOLO | op::VIBRATO => (self.next(), self.next() as u16),
+            op::SLIDE_VOL | op::SLIDE_PITCH => (self.next(), self.next_word()),
+            _ => (0, 0),
+        };
+
+        if ch >= CHANNELS {
+            return;
+        }
+        let state = &mut self.channels[ch];
+
+        match kind {
+            op::NOTE => {
+                state.frequency = MIDI_INCREMENTS[(a & 0x7F) as usize];
+                state.pitch_slide_rows = 0;
+            }
+            op::VOLUME => state.volume = a.min(MAX_VOLUME),
+            op::WAVETABLE if ch < VOICE_COUNT => voices()[ch].set_wavetable(b),
+            op::PHASE if ch < VOICE_COUNT => voices()[ch].set_phase(b),
+            op::TREMOLO => {
+                state.tremolo_depth = a;
+                state.tremolo_speed = b as u8;
+            }
+            op::VIBRATO => {
+                state.vibrato_depth = a;
+                state.vibrato_speed = b as u8;
+            }
+            op::SLIDE_VOL => {
+                state.vol_slide_rows = a;
+                state.vol_slide = b as i16;
+            }
+            op::STOP_VSLIDE => state.vol_slide_rows = 0,
+            op::SLIDE_PITCH => {
+                state.pitch_slide_rows = a;
+                state.pitch_slide = b as i16;
+            }
+            op::STOP_PSLIDE => state.pitch_slide_rows = 0,
+            _ => {}
+        }
+    }
+
+    /// Move the cursor to `row` of the current pattern
+    fn seek_row(&mut self, row: u8) {
+        self.cursor = self.pattern_start;
+        self.wait_rows = 0;
+
+        let mut current: u8 = 0;
+        while current < row {
+            match self.next() {
+                op::END => {
+                    self.cursor -= 1;
+                    return;
+                }
+                op::WAIT => {
+                    current = current.saturating_add(self.next());
+                }
+                op::TEMPO | op::PATTERN | op::BEAT => self.cursor += 1,
+                op::LOAD => self.cursor += 3,
+                op::ADVANCE | op::STOP => {}
+                opcode => {
+                    self.cursor += match opcode & 0xF0 {
+                        op::NOTE | op::VOLUME => 1,
+                        op::WAVETABLE | op::PHASE | op::TREMOLO | op::VIBRATO => 2,
+                        op::SLIDE_VOL | op::SLIDE_PITCH => 3,
+                        _ => 0,
+                    };
+                }
+            }
+        }
+
+        // landed in the middle of a wait
+        self.wait_rows = current - row;
+    }
+
+    fn update_voices(&mut self) {
+        let v = voices();
+
+        for (i, ch) in self.channels.iter_mut().enumerate().take(VOICE_COUNT) {
+            let mut frequency = ch.frequency;
+            let mut volume = ch.volume;
+
+            if ch.vibrato_depth > 0 {
+                ch.vibrato_phase = ch.vibrato_phase.wrapping_add(ch.vibrato_speed);
+                frequency = frequency.wrapping_add_signed(lfo(ch.vibrato_phase, ch.vibrato_depth));
+            }
+
+            if ch.tremolo_depth > 0 {
+                ch.tremolo_phase = ch.tremolo_phase.wrapping_add(ch.tremolo_speed);
+                let vol = volume as i16 + lfo(ch.tremolo_phase, ch.tremolo_depth);
+                volume = vol.clamp(0, MAX_VOLUME as i16) as u8;
+            }
+            if self.master_volume < 255 {
+                volume = ((volume as u16 * (self.master_volume as u16 + 1)) >> 8) as u8;
+            }
+
+            v[i].set_frequency(frequency);
+            v[i].set_volume(volume);
+        }
+    }
+}
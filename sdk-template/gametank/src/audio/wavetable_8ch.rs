@@ -50,6 +50,8 @@ pub const VOICE_BASE: usize = 0x3041;
 pub const VOICE_SIZE: usize = 7;
 /// Number of voices
 pub const VOICE_COUNT: usize = 8;
+/// Loudest volume level accepted by [`Voice::set_volume`]
+pub const MAX_VOLUME: u8 = 63;
 
 /// Base address for wavetables in ACP RAM (CPU-side)
 pub const WAVETABLE_BASE: usize = 0x3300;
@@ -125,6 +127,12 @@ impl Voice {
         self.phase = 0;
     }
 
+    /// Set the phase accumulator directly.
+    #[inline]
+    pub fn set_phase(&mut self, phase: u16) {
+        self.phase = phase;
+    }
+
     /// Get the current volume level.
     #[inline]
     pub fn get_volume(&self) -> u8 {
@@ -0,0 +1,249 @@
+//! # ROM Banking
+//!
+//! Data and code can go in a fixed bank with a link section, or let the
+//! linker pick one:
+//!
+//! ```ignore
+//! // Always bank 10 (`.bank10.rodata` works too)
+//! #[unsafe(link_section = ".rodata.bank10")]
+//! static TITLE_SCREEN: [u8; 8192] = *include_bytes!("title.bin");
+//!
+//! // The first bank with room left
+//! #[unsafe(no_mangle)]
+//! #[unsafe(link_section = ".rodata.auto.level1")]
+//! static LEVEL1: [u8; 12000] = *include_bytes!("level1.bin");
+//! ```
+//!
+//! Give every auto-banked item its own section name (`.rodata.auto.<name>`
+//! or `.text.auto.<name>`). The linker moves whole sections, so items that
+//! share one always end up in the same bank. When the banks are full, auto
+//! sections spill into the fixed bank.
+//!
+//! ## Bank Table
+//!
+//! To find out where an auto-banked item went, declare a [`BankEntry`] for it
+//! with [`bank_table!`]. The entry lives in the fixed bank and `gtrom` fills
+//! it in after linking:
+//!
+//! ```ignore
+//! gametank::bank_table!(LEVEL1_BANK => LEVEL1);
+//!
+//! console.set_rom_bank(LEVEL1_BANK.get());
+//! let first_byte = LEVEL1[0];
+//! ```
+//!
+//! The item has to be `#[unsafe(no_mangle)]` (or an asm label) so `gtrom` can
+//! find it by name. Asm can add its own entries: a byte named `__bank_of_<label>`
+//! in the `.bank_table` section.
+//!
+//! ## Safe Access
+//!
+//! A reference into `$8000-$BFFF` is only valid while its bank is selected.
+//! [`BankedSlice`] keeps the data behind a closure that switches to the right
+//! bank and switches back afterwards:
+//!
+//! ```ignore
+//! let level = unsafe { BankedSlice::new(LEVEL1_BANK.get(), &LEVEL1) };
+//!
+//! let first_byte = level.with(|data| data[0]);
+//! ```
+//!
+//! The closure has to run from the fixed bank (the default for code), and
+//! an interrupt handler that changes banks must put the old one back.
+//!
+//! ## RAM Banks
+//!
+//! There are 32KB of RAM in four 8KB banks, but switching banks swaps all of
+//! `$0000-$1FFF`: zero page, the stacks and every variable. So the program
+//! always runs in bank 0, and banks 1-3 are extra storage reached through
+//! [`RamBanks`], which copies to and from them a byte at a time:
+//!
+//! ```ignore
+//! let mut save = console.ram_bank(1).unwrap();
+//! save.write(0, &level_state);
+//! // later
+//! save.read(0, &mut level_state);
+//! ```
+//!
+//! Each bank has [`RAM_WINDOW_LEN`] bytes to use, from `$0200`; its zero page
+//! and stack page are kept for the copy code and the NMI handler. While a
+//! [`RamWindow`] is out, the [`Console`](crate::console::Console)'s bank flags
+//! can't change, so the copies always put back the flags the game had set.
+//! Copies run with IRQs off, and shouldn't be used from interrupt handlers.
+
+use crate::scr::BankFlags;
+use crate::via::Via;
+
+/// The ROM bank an item was linked into, filled in by `gtrom`.
+#[repr(transparent)]
+pub struct BankEntry(u8);
+
+impl BankEntry {
+    /// Placeholder until the ROM is built
+    pub const UNASSIGNED: Self = Self(0xFF);
+
+    /// The bank number, ready for [`Console::set_rom_bank`](crate::console::Console::set_rom_bank).
+    /// Items in the fixed bank report 127.
+    #[inline(always)]
+    pub fn get(&self) -> u8 {
+        // read through memory: the compiler only ever sees the placeholder
+        unsafe { core::ptr::read_volatile(&self.0) }
+    }
+}
+
+/// Declare a [`BankEntry`] for a `#[no_mangle]` item.
+///
+/// `bank_table!(LEVEL1_BANK => LEVEL1)` creates `static LEVEL1_BANK: BankEntry`
+/// holding the bank `LEVEL1` was linked into.
+#[macro_export]
+macro_rules! bank_table {
+    ($($vis:vis $entry:ident => $item:ident),+ $(,)?) => {
+        $(
+            #[unsafe(export_name = concat!("__bank_of_", stringify!($item)))]
+            #[unsafe(link_section = ".bank_table")]
+            $vis static $entry: $crate::banking::BankEntry = $crate::banking::BankEntry::UNASSIGNED;
+        )+
+    };
+}
+
+/// A slice in a ROM bank, only reachable while that bank is selected.
+pub struct BankedSlice<T: 'static> {
+    bank: u8,
+    data: &'static [T],
+}
+
+impl<T> BankedSlice<T> {
+    /// # Safety
+    /// `data` has to be linked into ROM bank `bank`.
+    pub const unsafe fn new(bank: u8, data: &'static [T]) -> Self {
+        Self { bank, data }
+    }
+
+    pub fn bank(&self) -> u8 {
+        self.bank
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Select the slice's bank, run `f` on the data, then restore the bank
+    /// that was selected before.
+    pub fn with<R>(&self, f: impl FnOnce(&[T]) -> R) -> R {
+        let via = unsafe { Via::new() };
+        let previous = via.rom_bank();
+        via.change_rom_bank(self.bank);
+
+        let result = f(self.data);
+
+        let via = unsafe { Via::new() };
+        via.change_rom_bank(previous);
+        result
+    }
+}
+
+/// Bytes usable in each of RAM banks 1-3
+pub const RAM_WINDOW_LEN: u16 = 0x1E00;
+
+/// Where the usable part of a RAM bank starts
+const RAM_WINDOW_START: u16 = 0x0200;
+
+/// Arguments for `ram_bank_copy` in `ram-banks.asm`
+#[repr(C)]
+struct RamCopy {
+    home_flags: u8,
+    other_flags: u8,
+    home: *mut u8,
+    other: *mut u8,
+    len: u16,
+    to_other: bool,
+}
+
+unsafe extern "C" {
+    fn ram_bank_copy(args: *const RamCopy);
+}
+
+/// Hands out access to RAM banks 1-3. See the [module docs](self).
+pub struct RamBanks {
+    /// The bank the last window was for
+    selected: u8,
+}
+
+impl RamBanks {
+    pub(crate) const fn new() -> Self {
+        Self { selected: 0 }
+    }
+
+    /// Access to bank `bank` (1-3), using `flags` for everything but the
+    /// bank bits. `None` for bank 0, which is the program's own RAM.
+    pub fn window<'a>(&'a mut self, flags: &'a BankFlags, bank: u8) -> Option<RamWindow<'a>> {
+        if !(1..=3).contains(&bank) {
+            return None;
+        }
+        self.selected = bank;
+        Some(RamWindow { flags, bank, _banks: self })
+    }
+
+    /// The bank the last [`window`](Self::window) was for, or 0 if none.
+    pub fn selected(&self) -> u8 {
+        self.selected
+    }
+}
+
+/// The usable part of one RAM bank, from [`RamBanks::window`]. Offsets are
+/// from the start of the window, up to [`RAM_WINDOW_LEN`].
+pub struct RamWindow<'a> {
+    flags: &'a BankFlags,
+    bank: u8,
+    _banks: &'a mut RamBanks,
+}
+
+impl RamWindow<'_> {
+    pub fn bank(&self) -> u8 {
+        self.bank
+    }
+
+    fn copy(&self, offset: u16, home: *mut u8, len: usize, to_other: bool) -> bool {
+        if (offset as usize).checked_add(len).is_none_or(|end| end > RAM_WINDOW_LEN as usize) {
+            return false;
+        }
+        let home_flags = self.flags.with_ram_bank(0);
+        let args = RamCopy {
+            home_flags: home_flags.bits(),
+            other_flags: home_flags.with_ram_bank(self.bank).bits(),
+            home,
+            other: (RAM_WINDOW_START + offset) as *mut u8,
+            len: len as u16,
+            to_other,
+        };
+        unsafe { ram_bank_copy(&args) };
+        true
+    }
+
+    /// Copy `data` into the bank at `offset`. Returns `false`, copying
+    /// nothing, if it would run past the end of the window.
+    pub fn write(&mut self, offset: u16, data: &[u8]) -> bool {
+        self.copy(offset, data.as_ptr() as *mut u8, data.len(), true)
+    }
+
+    /// Fill `buf` from the bank at `offset`. Returns `false`, copying
+    /// nothing, if it would run past the end of the window.
+    pub fn read(&self, offset: u16, buf: &mut [u8]) -> bool {
+        self.copy(offset, buf.as_mut_ptr(), buf.len(), false)
+    }
+
+    /// One byte from the bank, or `None` past the end of the window.
+    pub fn read_byte(&self, offset: u16) -> Option<u8> {
+        let mut byte = [0];
+        self.read(offset, &mut byte).then_some(byte[0])
+    }
+
+    /// Write one byte to the bank. Returns `false` past the end of the window.
+    pub fn write_byte(&mut self, offset: u16, value: u8) -> bool {
+        self.write(offset, &[value])
+    }
+}
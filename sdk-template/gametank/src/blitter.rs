@@ -100,6 +100,7 @@ pub enum BlitterFillMode {
 /// └───────────┴───────────┘
 ///   X=0-127     X=128-255
 /// ```
+#[derive(Clone, Copy, PartialEq)]
 pub enum SpriteQuadrant {
     /// Top-left (X: 0-127, Y: 0-127)
     One,
@@ -129,4 +130,15 @@ impl SpriteQuadrant {
             Self::Three | Self::Four => 128,
         }
     }
+
+    /// The quadrant after this one, in reading order. `None` after [`Four`](Self::Four).
+    #[inline(always)]
+    pub fn next(&self) -> Option<Self> {
+        match self {
+            Self::One => Some(Self::Two),
+            Self::Two => Some(Self::Three),
+            Self::Three => Some(Self::Four),
+            Self::Four => None,
+        }
+    }
 }
@@ -2,7 +2,7 @@
 use core::panic::PanicInfo;
 use core::ptr;
 
-use crate::{blitter::SpriteQuadrant, console::Console};
+use crate::{blitter::SpriteQuadrant, console::Console, rom_info};
 
 unsafe extern "Rust" {
     unsafe fn main(console: &mut Console);
@@ -15,15 +15,56 @@ fn panic(_panic: &PanicInfo<'_>) -> ! {
 }
 
 #[unsafe(link_section = ".data.zp")]
+#[unsafe(no_mangle)]
 pub static mut VBLANK: bool = false;
 
+/// Put statics in zero page, where the 6502 reaches them faster and in
+/// fewer bytes. Zero page is small: `gtrom build` lists what's in it, and
+/// fails past the `zp` limit in `gtrom.toml`.
+///
+/// ```ignore
+/// gametank::zeropage! {
+///     static mut PLAYER_X: u8 = 0;
+///     pub static mut SCROLL: u16 = 0;
+/// }
+/// ```
+///
+/// Crt0 copies their initial values in before `main`.
+#[macro_export]
+macro_rules! zeropage {
+    () => {};
+    ($(#[$meta:meta])* $vis:vis static mut $name:ident : $ty:ty = $init:expr; $($rest:tt)*) => {
+        $(#[$meta])*
+        #[unsafe(link_section = ".data.zp")]
+        $vis static mut $name: $ty = $init;
+        $crate::zeropage!($($rest)*);
+    };
+    ($(#[$meta:meta])* $vis:vis static $name:ident : $ty:ty = $init:expr; $($rest:tt)*) => {
+        $(#[$meta])*
+        #[unsafe(link_section = ".data.zp")]
+        $vis static $name: $ty = $init;
+        $crate::zeropage!($($rest)*);
+    };
+}
+
 unsafe extern "C" {
     pub unsafe fn return_from_interrupt();
 
+    /// The NMI handler, in `interrupts.asm`. Sets [`VBLANK`], then runs the
+    /// [`irq::on_vblank`](crate::irq::on_vblank) handlers if there are any.
+    unsafe fn vblank_nmi();
+
+    /// The IRQ/BRK handler, in `interrupts.asm`. Runs the
+    /// [`irq::on_irq`](crate::irq::on_irq) handlers if there are any.
+    unsafe fn irq_handler();
+
     pub unsafe fn wait();
 
     pub unsafe fn enable_irq_handler();
 
+    /// Mark RAM banks 1-3 for the NMI handler, in `ram-banks.asm`
+    unsafe fn init_ram_banks();
+
     pub unsafe fn disable_irq_handler();
 
     /// Set the overflow (V) flag. Used by llvm-mos for certain operations.
@@ -79,20 +120,12 @@ unsafe fn init_data_and_bss() {
     }
 }
 
-#[unsafe(no_mangle)]
-extern "C" fn vblank_nmi() {
-    unsafe {
-        VBLANK = true;
-        return_from_interrupt();
-    }
-}
-
 #[unsafe(link_section = ".vector_table")]
 #[unsafe(no_mangle)]
 pub static _VECTOR_TABLE: [unsafe extern "C" fn(); 3] = [
     vblank_nmi,            // Non-Maskable Interrupt vector
     __boot,                // Reset vector
-    return_from_interrupt, // IRQ/BRK vector
+    irq_handler,           // IRQ/BRK vector
 ];
 
 
@@ -107,6 +140,11 @@ fn call_main() {
         blitter.set_vram_quad(SpriteQuadrant::One);
     }
 
+    // see gametank_rom!
+    if let Some(splash) = rom_info::boot_splash() {
+        splash(console);
+    }
+
     unsafe { main(console) };
 }
 
@@ -115,6 +153,7 @@ unsafe extern "C" fn __boot() {
     unsafe {
         reset_banking_register();
         init_data_and_bss();
+        init_ram_banks();
         init_stack();
 
         // IMPORTANT: we can't initialize Console in __boot,
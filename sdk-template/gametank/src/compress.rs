@@ -0,0 +1,98 @@
+//! # Compressed Data
+//!
+//! Big tilemaps and title screens can be stored compressed in ROM and
+//! unpacked at load time. `gtrom` compresses assets listed in `assets.toml`
+//! with `compress = true`; this module unpacks them.
+//!
+//! ```ignore
+//! use rom::sdk::compress;
+//!
+//! // Into RAM
+//! let mut level = [0u8; 1024];
+//! LEVEL1.with(|packed| compress::unpack(packed, &mut level));
+//!
+//! // Straight into sprite RAM, which can't be read back to copy matches from
+//! let mut sm = console.dma.sprite_mem(&mut console.video_flags).unwrap();
+//! let bytes = sm.bytes();
+//! let mut i = 0;
+//! TITLE.with(|packed| compress::unpack_with(packed, |b| { bytes[i] = b; i += 1; }));
+//! ```
+//!
+//! ## Format
+//!
+//! A little-endian `u16` with the unpacked length, then tokens:
+//!
+//! - `0nnnnnnn`: the next `n + 1` bytes are copied as they are
+//! - `1nnnnnnn dddddddd`: `n + 3` bytes are copied from `d + 1` bytes back
+//!   in the output, which may overlap what's being written
+//!
+//! Every field is a whole byte and matches reach at most 256 bytes back, so
+//! unpacking needs no bit shifting and only a 256-byte window.
+
+/// Set in a token that copies from earlier output
+const MATCH_FLAG: u8 = 0x80;
+
+/// Shortest match a token can hold
+const MIN_MATCH: usize = 3;
+
+/// Length of the unpacked data, from its header
+pub fn unpacked_len(packed: &[u8]) -> usize {
+    match packed {
+        [lo, hi, ..] => u16::from_le_bytes([*lo, *hi]) as usize,
+        _ => 0,
+    }
+}
+
+/// Unpack into `out`, returning the number of bytes written, or `None`
+/// (writing nothing) if `out` is too small.
+pub fn unpack(packed: &[u8], out: &mut [u8]) -> Option<usize> {
+    let len = unpacked_len(packed);
+    if len > out.len() {
+        return None;
+    }
+    let mut i = 0;
+    unpack_with(packed, |b| {
+        out[i] = b;
+        i += 1;
+    });
+    Some(i)
+}
+
+/// Unpack a byte at a time, for writing somewhere that can't be read back,
+/// like sprite RAM. Stops early if the data is cut short.
+pub fn unpack_with(packed: &[u8], mut out: impl FnMut(u8)) {
+    // the last 256 bytes written, for matches to copy from
+    let mut window = [0u8; 256];
+    let mut pos: u8 = 0;
+    let mut remaining = unpacked_len(packed);
+    let mut input = packed.get(2..).unwrap_or(&[]).iter().copied();
+
+    while remaining > 0 {
+        let Some(token) = input.next() else { return };
+        let (count, mut from) = if token & MATCH_FLAG == 0 {
+            (token as usize + 1, None)
+        } else {
+            let Some(distance) = input.next() else { return };
+            let count = (token & !MATCH_FLAG) as usize + MIN_MATCH;
+            (count, Some(pos.wrapping_sub(distance).wrapping_sub(1)))
+        };
+
+        for _ in 0..count.min(remaining) {
+            let b = match from.as_mut() {
+                Some(src) => {
+                    let b = window[*src as usize];
+                    *src = src.wrapping_add(1);
+                    b
+                }
+                None => match input.next() {
+                    Some(b) => b,
+                    None => return,
+                },
+            };
+            window[pos as usize] = b;
+            pos = pos.wrapping_add(1);
+            out(b);
+        }
+        remaining = remaining.saturating_sub(count);
+    }
+}
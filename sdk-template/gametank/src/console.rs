@@ -1,4 +1,4 @@
-use crate::{input::GenesisGamepad, scr::{BankFlags, VideoFlags}, via::Via, video_dma::{DmaManager, VideoDma, blitter::BlitterGuard, spritemem::SpriteMem}};
+use crate::{banking::{RamBanks, RamWindow}, input::GenesisGamepad, scr::{BankFlags, VideoFlags}, via::Via, video_dma::{DmaManager, VideoDma, blitter::BlitterGuard, spritemem::SpriteMem}};
 
 /// Write-only register at $2005
 const BANK_REG: *mut u8 = 0x2005 as *mut u8;
@@ -13,7 +13,24 @@ pub struct AudioManager {
 }
 
 impl AudioManager {
-    
+    /// Write the sample rate register at `$2006`. Bit 7 enables the ACP and
+    /// the low 7 bits set the time between sample IRQs; 0 stops it.
+    #[inline(always)]
+    pub fn set_sample_rate(&mut self, rate: u8) {
+        unsafe { core::ptr::write_volatile(self.audio_freq, rate); }
+    }
+
+    /// Restart the ACP from its reset vector.
+    #[inline(always)]
+    pub fn reset(&mut self) {
+        unsafe { core::ptr::write_volatile(self.audio_reset, 0); }
+    }
+
+    /// Send the ACP an NMI.
+    #[inline(always)]
+    pub fn nmi(&mut self) {
+        unsafe { core::ptr::write_volatile(self.audio_nmi, 0); }
+    }
 }
 
 
@@ -26,6 +43,8 @@ pub struct Console {
     pub dma: DmaManager,
     pub audio: AudioManager,
     pub via: &'static mut Via,
+    /// RAM banks 1-3, see [`ram_bank`](Self::ram_bank)
+    pub ram_banks: RamBanks,
 }
 
 impl Console {
@@ -51,6 +70,7 @@ impl Console {
                 audio_freq: unsafe { &mut *(0x2006 as *mut u8) },
             },
             via: unsafe { Via::new() },
+            ram_banks: RamBanks::new(),
         };
         console
     }
@@ -75,6 +95,13 @@ impl Console {
         self.write_video_flags();
     }
 
+    /// Select the sprite RAM page (0-7) used by the blitter and sprite RAM access.
+    #[inline(always)]
+    pub fn set_sprite_page(&mut self, page: u8) {
+        self.bank_flags.set_sprite_page(page);
+        self.write_bank_flags();
+    }
+
     pub fn genesis_gamepads(&self) -> (GenesisGamepad<1>, GenesisGamepad<2>) {
         (GenesisGamepad::new(), GenesisGamepad::new())
     }
@@ -83,8 +110,18 @@ impl Console {
         self.via.change_rom_bank(bank);
     }
 
+    pub fn rom_bank(&self) -> u8 {
+        self.via.rom_bank()
+    }
+
+    /// Copy to and from RAM bank `bank` (1-3) while the window is held.
+    /// See [`banking`](crate::banking#ram-banks).
+    pub fn ram_bank(&mut self, bank: u8) -> Option<RamWindow<'_>> {
+        self.ram_banks.window(&self.bank_flags, bank)
+    }
+
     pub fn blitter(&mut self) -> Option<BlitterGuard<'_>> {
         self.video_flags.set(VideoFlags::DMA_COLORFILL, false);
-        self.dma.blitter(&mut self.video_flags)
+        self.dma.blitter_with_banks(&mut self.video_flags, &mut self.bank_flags)
     }
 }
\ No newline at end of file
@@ -0,0 +1,45 @@
+//! # Debug Output
+//!
+//! [`log!`] prints a line to the emulator, taking the same arguments as
+//! `println!`. gte and the libretro core write it to their log, gtgo's
+//! emulator shows it beside the screen, and `gtrom test` prints it as the
+//! test runs.
+//!
+//! ```ignore
+//! use rom::sdk::debug::log;
+//!
+//! log!("player at {}, {}", player.x, player.y);
+//! ```
+//!
+//! The text goes a byte at a time to [`DEBUG_PORT`], a system control
+//! address the console doesn't use, so on hardware it goes nowhere. The
+//! formatting still costs cycles and ROM, so keep it out of tight frames.
+
+use core::{fmt, ptr};
+
+/// Where the emulator collects what's written
+pub const DEBUG_PORT: *mut u8 = 0x2002 as *mut u8;
+
+/// Writes text to [`DEBUG_PORT`]. A newline ends each message.
+pub struct DebugPort;
+
+impl fmt::Write for DebugPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            unsafe { ptr::write_volatile(DEBUG_PORT, byte) };
+        }
+        Ok(())
+    }
+}
+
+/// Print a line to the emulator, like `println!`. See [`debug`](crate::debug).
+#[macro_export]
+macro_rules! debug_log {
+    ($($arg:tt)*) => {{
+        use core::fmt::Write as _;
+        let _ = writeln!($crate::debug::DebugPort, $($arg)*);
+    }};
+}
+
+#[doc(inline)]
+pub use crate::debug_log as log;
@@ -0,0 +1,331 @@
+//! # Animation
+//!
+//! `gtrom convert hero.aseprite` (or an Aseprite file in `assets/`) packs
+//! every frame into sprite RAM and generates:
+//!
+//! - `HERO_FRAMES`, a [`Frames`] saying where each frame is
+//! - `HERO_ANIM`, an [`Animation`] of every frame in order
+//! - `HERO_ANIM_<TAG>` for each tag, with the tag's direction and repeat
+//!   count, and frame durations rounded to vblanks
+//!
+//! An [`AnimPlayer`] steps through an animation, one [`tick`] per frame:
+//!
+//! ```ignore
+//! use rom::sdk::gfx::{SpriteSheet, anim::AnimPlayer};
+//!
+//! SpriteSheet::new(hero::HERO, 2).load(&mut console);
+//! let mut player = AnimPlayer::new(&hero::HERO_ANIM_IDLE);
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     console.flip_framebuffers();
+//!
+//!     // play() only restarts if it's a different animation
+//!     player.play(if walking { &hero::HERO_ANIM_WALK } else { &hero::HERO_ANIM_IDLE });
+//!     player.tick();
+//!
+//!     let mut blitter = console.blitter().unwrap();
+//!     hero::HERO_FRAMES.draw(&mut blitter, 2, player.frame(), x, y);
+//!     blitter.wait_blit();
+//! }
+//! ```
+//!
+//! ## Play Modes
+//!
+//! An [`Animation`] loops forever by default. [`PlayMode::Once`] stops on
+//! the last step, [`PlayMode::PingPong`] plays to the end and back, and
+//! [`Animation::repeat`] stops a looping one after some number of plays.
+//! [`AnimPlayer::is_finished`] says when it's done.
+//!
+//! ## Hand-Written Tables
+//!
+//! Nothing here needs `gtrom convert`. A step's `frame` is just an index,
+//! so it can pick from any table, like metasprites:
+//!
+//! ```ignore
+//! use rom::sdk::gfx::{MetaSprite, anim::{AnimFrame, AnimPlayer, Animation, PlayMode}};
+//!
+//! static BOSS_POSES: [MetaSprite; 3] = [...];
+//! static BOSS_ROAR: Animation = Animation::new(&[
+//!     AnimFrame { frame: 0, duration: 10 },
+//!     AnimFrame { frame: 1, duration: 4 },
+//!     AnimFrame { frame: 2, duration: 20 },
+//! ]).mode(PlayMode::Once);
+//!
+//! let mut player = AnimPlayer::new(&BOSS_ROAR);
+//! // every frame
+//! player.tick();
+//! if let Some(pose) = player.current(&BOSS_POSES) {
+//!     blitter.draw_metasprite(pose, x, y);
+//! }
+//! ```
+//!
+//! [`tick`]: AnimPlayer::tick
+
+use core::ptr;
+
+use crate::{math::Rect8, video_dma::blitter::BlitterGuard};
+
+/// Where one frame is in sprite RAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Pages past the one the sheet was loaded into
+    pub page: u8,
+    /// Blitter source X, quadrant included
+    pub sx: u8,
+    /// Blitter source Y, quadrant included
+    pub sy: u8,
+}
+
+/// Every frame of a sprite, all the same size.
+#[derive(Clone, Copy, Debug)]
+pub struct Frames {
+    width: u8,
+    height: u8,
+    frames: &'static [Frame],
+}
+
+impl Frames {
+    pub const fn new(width: u8, height: u8, frames: &'static [Frame]) -> Self {
+        Self { width, height, frames }
+    }
+
+    #[inline(always)]
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    #[inline(always)]
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    #[inline(always)]
+    pub fn frames(&self) -> &'static [Frame] {
+        self.frames
+    }
+
+    /// Sprite page and blitter source rectangle of frame `index`, for a
+    /// sheet loaded at the top-left of `page`.
+    pub fn source(&self, page: u8, index: u8) -> Option<(u8, Rect8)> {
+        let frame = self.frames.get(index as usize)?;
+        Some((page + frame.page, Rect8::new(frame.sx, frame.sy, self.width, self.height)))
+    }
+
+    /// Draw frame `index` at `(x, y)`, from a sheet loaded at the top-left
+    /// of `page`.
+    ///
+    /// Selects the frame's sprite page, which needs a guard from
+    /// [`Console::blitter`](crate::console::Console::blitter); with any
+    /// other guard, select it beforehand. Wait for the blit as with
+    /// [`draw_sprite`](BlitterGuard::draw_sprite).
+    pub fn draw(&self, blitter: &mut BlitterGuard, page: u8, index: u8, x: u8, y: u8) {
+        let Some((page, rect)) = self.source(page, index) else { return };
+        if blitter.sprite_page().is_some_and(|p| p != page) {
+            blitter.set_sprite_page(page);
+        }
+        blitter.draw_sprite(rect.x, rect.y, x, y, rect.w, rect.h);
+    }
+}
+
+/// One step of an [`Animation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnimFrame {
+    /// Index into the sprite's [`Frames`], or whatever table of frames the
+    /// animation is for
+    pub frame: u8,
+    /// Vblanks to show it for
+    pub duration: u8,
+}
+
+/// How an [`Animation`] plays through its steps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayMode {
+    /// Start to end, then from the start again
+    Loop,
+    /// Start to end, then hold the last step
+    Once,
+    /// Start to end and back, without showing the end steps twice
+    PingPong,
+}
+
+/// A sequence of frames and how to play them.
+///
+/// ```ignore
+/// static BLINK: Animation = Animation::new(&[
+///     AnimFrame { frame: 0, duration: 90 },
+///     AnimFrame { frame: 1, duration: 6 },
+/// ]);
+/// static SWING: Animation = Animation::new(&[...]).mode(PlayMode::PingPong).repeat(3);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Animation {
+    steps: &'static [AnimFrame],
+    mode: PlayMode,
+    repeat: u8,
+}
+
+impl Animation {
+    /// An animation that loops forever.
+    pub const fn new(steps: &'static [AnimFrame]) -> Self {
+        Self { steps, mode: PlayMode::Loop, repeat: 0 }
+    }
+
+    pub const fn mode(mut self, mode: PlayMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Stop after playing through `times` times (there and back for
+    /// [`PlayMode::PingPong`]), or never for 0. Ignored by
+    /// [`PlayMode::Once`].
+    pub const fn repeat(mut self, times: u8) -> Self {
+        self.repeat = times;
+        self
+    }
+
+    #[inline(always)]
+    pub fn steps(&self) -> &'static [AnimFrame] {
+        self.steps
+    }
+
+    #[inline(always)]
+    pub fn play_mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    /// Times it plays through before stopping, 0 for forever.
+    pub fn plays(&self) -> u8 {
+        match self.mode {
+            PlayMode::Once => 1,
+            _ => self.repeat,
+        }
+    }
+
+    /// Vblanks to play through once.
+    pub fn duration(&self) -> u16 {
+        let once: u16 = self.steps.iter().map(|s| s.duration as u16).sum();
+        match (self.mode, self.steps) {
+            (PlayMode::PingPong, [first, .., last]) => {
+                let inner = once - first.duration as u16 - last.duration as u16;
+                once + inner
+            }
+            _ => once,
+        }
+    }
+}
+
+/// Plays an [`Animation`]. See the [module docs](self).
+#[derive(Clone, Copy, Debug)]
+pub struct AnimPlayer {
+    animation: &'static Animation,
+    step: u8,
+    /// Vblanks left on the current step
+    timer: u8,
+    /// Times played through so far
+    plays: u8,
+    /// Heading back to the start, in [`PlayMode::PingPong`]
+    backward: bool,
+    finished: bool,
+}
+
+impl AnimPlayer {
+    pub fn new(animation: &'static Animation) -> Self {
+        let mut player = Self { animation, step: 0, timer: 0, plays: 0, backward: false, finished: false };
+        player.restart();
+        player
+    }
+
+    #[inline(always)]
+    pub fn animation(&self) -> &'static Animation {
+        self.animation
+    }
+
+    /// Switch to `animation`, from its start. Does nothing if it's already
+    /// playing, so it can be called every frame.
+    pub fn play(&mut self, animation: &'static Animation) {
+        if !ptr::eq(self.animation, animation) {
+            self.animation = animation;
+            self.restart();
+        }
+    }
+
+    /// Play the current animation again from its first step.
+    pub fn restart(&mut self) {
+        self.step = 0;
+        self.plays = 0;
+        self.backward = false;
+        self.finished = self.animation.steps.is_empty();
+        self.timer = self.animation.steps.first().map_or(0, |s| s.duration);
+    }
+
+    /// Count one play through. Returns `true` if that was the last.
+    fn played(&mut self) -> bool {
+        self.plays = self.plays.saturating_add(1);
+        let plays = self.animation.plays();
+        self.finished = plays != 0 && self.plays >= plays;
+        self.finished
+    }
+
+    /// Advance by one vblank.
+    pub fn tick(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+
+        let steps = self.animation.steps;
+        let last = steps.len() as u8 - 1;
+        match self.animation.mode {
+            PlayMode::PingPong if last > 0 => {
+                if !self.backward {
+                    self.step += 1;
+                    self.backward = self.step == last;
+                } else {
+                    self.step -= 1;
+                    if self.step == 0 {
+                        self.backward = false;
+                        if self.played() {
+                            return;
+                        }
+                    }
+                }
+            }
+            _ if self.step < last => self.step += 1,
+            _ => {
+                if self.played() {
+                    return;
+                }
+                self.step = 0;
+            }
+        }
+        self.timer = steps[self.step as usize].duration;
+    }
+
+    /// The current step of the animation.
+    pub fn step(&self) -> Option<AnimFrame> {
+        self.animation.steps.get(self.step as usize).copied()
+    }
+
+    /// The frame to draw, an index into the sprite's [`Frames`].
+    pub fn frame(&self) -> u8 {
+        self.step().map_or(0, |s| s.frame)
+    }
+
+    /// The entry for the current frame in a table of frames, e.g. a
+    /// hand-written `[MetaSprite; N]` drawn with
+    /// [`draw_metasprite`](BlitterGuard::draw_metasprite).
+    pub fn current<'a, T>(&self, table: &'a [T]) -> Option<&'a T> {
+        table.get(self.frame() as usize)
+    }
+
+    /// Whether an animation that doesn't play forever has played out. It
+    /// stays on its last step ([`PlayMode::PingPong`] ends on its first).
+    #[inline(always)]
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
@@ -0,0 +1,200 @@
+//! # Cameras
+//!
+//! A [`Camera`] is the top-left corner of the screen in a level bigger than
+//! it. It follows a target, usually the player, scrolling only once the
+//! target leaves a deadzone in the middle of the screen, eases towards where
+//! it's going rather than jumping, and never shows past the level's edges.
+//!
+//! ```ignore
+//! use rom::sdk::gfx::Camera;
+//!
+//! let mut camera = Camera::for_tilemap(&map).with_smoothing(2);
+//! camera.center_on(player.x, player.y);
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     console.flip_framebuffers();
+//!
+//!     camera.follow(player.x, player.y);
+//!     camera.apply(&mut map);
+//!
+//!     let mut blitter = console.blitter().unwrap();
+//!     map.draw(&mut blitter);
+//!     if let Some((x, y)) = camera.to_screen(player.x - 8, player.y - 8, 16, 16) {
+//!         blitter.draw_sprite(sx, sy, x, y, 16, 16);
+//!         blitter.wait_blit();
+//!         map.mark_dirty(x, y, 16, 16);
+//!     }
+//! }
+//! ```
+//!
+//! ## Following
+//!
+//! The deadzone is a rectangle of the screen the target can move around in
+//! without the camera moving; by default it's 32×32 in the middle. A
+//! sidescroller might make it tall, so small jumps don't scroll, and a
+//! 0×0 deadzone keeps the target in one spot. Set one with
+//! [`with_deadzone`](Camera::with_deadzone).
+//!
+//! With [`with_smoothing`](Camera::with_smoothing), each frame moves the
+//! camera `1 / 2^n` of the way to where it should be, at least a pixel, so
+//! it speeds up and settles gently. With none (the default) it keeps up
+//! exactly.
+//!
+//! ## Drawing
+//!
+//! [`to_screen`](Camera::to_screen) turns a rectangle in the level into
+//! blitter coordinates, or `None` if none of it's on screen. Rectangles
+//! partly off the left or top come back wrapped around, like the tiles
+//! [`Tilemap::draw`] cuts off at the edges: draw them with the `CLIP_X`
+//! and `CLIP_Y` bank flags set, which the tilemap leaves as it found them.
+
+use super::tilemap::{Tilemap, TILE_SIZE};
+
+/// Width and height of the screen in pixels.
+const SCREEN_SIZE: u16 = 128;
+
+/// Follows a position around a level. See the [module docs](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Camera {
+    x: u16,
+    y: u16,
+    /// The largest `x` and `y` that keep the screen inside the level
+    max_x: u16,
+    max_y: u16,
+    /// Screen rectangle the target stays in, edges included
+    deadzone_left: u8,
+    deadzone_top: u8,
+    deadzone_right: u8,
+    deadzone_bottom: u8,
+    smoothing: u8,
+}
+
+/// Where the camera has to be for `target` to be within `low..=high` of it
+fn chase(camera: u16, target: u16, low: u8, high: u8) -> u16 {
+    if target < camera.saturating_add(low as u16) {
+        target.saturating_sub(low as u16)
+    } else if target > camera.saturating_add(high as u16) {
+        target - high as u16
+    } else {
+        camera
+    }
+}
+
+/// `current` moved `1 / 2^shift` of the way to `target`, at least a pixel
+fn approach(current: u16, target: u16, shift: u8) -> u16 {
+    if target > current {
+        current + ((target - current) >> shift).max(1)
+    } else if target < current {
+        current - ((current - target) >> shift).max(1)
+    } else {
+        current
+    }
+}
+
+/// Where a span at `start` of `size` pixels is drawn on a screen at
+/// `camera`, wrapped if it starts off screen
+fn screen_axis(start: u16, size: u8, camera: u16) -> Option<u8> {
+    let end = start.saturating_add(size as u16);
+    if size == 0 || end <= camera || start >= camera.saturating_add(SCREEN_SIZE) {
+        return None;
+    }
+    Some(start.wrapping_sub(camera) as u8)
+}
+
+impl Camera {
+    /// A camera at the top-left of a level `width` by `height` pixels.
+    pub const fn new(width: u16, height: u16) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            max_x: width.saturating_sub(SCREEN_SIZE),
+            max_y: height.saturating_sub(SCREEN_SIZE),
+            deadzone_left: 48,
+            deadzone_top: 48,
+            deadzone_right: 80,
+            deadzone_bottom: 80,
+            smoothing: 0,
+        }
+    }
+
+    /// A camera for a level the size of `map`.
+    pub const fn for_tilemap<const W: usize, const H: usize>(_map: &Tilemap<W, H>) -> Self {
+        Self::new((W * TILE_SIZE as usize) as u16, (H * TILE_SIZE as usize) as u16)
+    }
+
+    /// Keep the target within a `width` by `height` rectangle of the screen
+    /// whose top-left is `x`, `y`.
+    pub const fn with_deadzone(mut self, x: u8, y: u8, width: u8, height: u8) -> Self {
+        let right = x.saturating_add(width);
+        let bottom = y.saturating_add(height);
+        self.deadzone_left = x;
+        self.deadzone_top = y;
+        self.deadzone_right = if right > 127 { 127 } else { right };
+        self.deadzone_bottom = if bottom > 127 { 127 } else { bottom };
+        self
+    }
+
+    /// Move `1 / 2^shift` of the way each frame instead of keeping up
+    /// exactly; 0 turns it off.
+    pub const fn with_smoothing(mut self, shift: u8) -> Self {
+        self.smoothing = if shift > 15 { 15 } else { shift };
+        self
+    }
+
+    /// Change the level's size, for the next level, keeping the camera
+    /// inside it.
+    pub fn set_bounds(&mut self, width: u16, height: u16) {
+        self.max_x = width.saturating_sub(SCREEN_SIZE);
+        self.max_y = height.saturating_sub(SCREEN_SIZE);
+        self.set_position(self.x, self.y);
+    }
+
+    /// The top-left corner of the screen, in pixels from the top-left of
+    /// the level.
+    #[inline(always)]
+    pub fn position(&self) -> (u16, u16) {
+        (self.x, self.y)
+    }
+
+    /// Jump to a position, kept inside the level.
+    pub fn set_position(&mut self, x: u16, y: u16) {
+        self.x = x.min(self.max_x);
+        self.y = y.min(self.max_y);
+    }
+
+    /// Jump to put `x`, `y` in the middle of the screen, or as near as the
+    /// level's edges allow. For the start of a level, or after a teleport.
+    pub fn center_on(&mut self, x: u16, y: u16) {
+        let half = SCREEN_SIZE / 2;
+        self.set_position(x.saturating_sub(half), y.saturating_sub(half));
+    }
+
+    /// Move towards keeping `x`, `y` (a position in the level) inside the
+    /// deadzone. Call once a frame.
+    pub fn follow(&mut self, x: u16, y: u16) {
+        let want_x = chase(self.x, x, self.deadzone_left, self.deadzone_right).min(self.max_x);
+        let want_y = chase(self.y, y, self.deadzone_top, self.deadzone_bottom).min(self.max_y);
+        self.x = approach(self.x, want_x, self.smoothing);
+        self.y = approach(self.y, want_y, self.smoothing);
+    }
+
+    /// Scroll `map` to the camera.
+    #[inline]
+    pub fn apply<const W: usize, const H: usize>(&self, map: &mut Tilemap<W, H>) {
+        map.set_scroll(self.x, self.y);
+    }
+
+    /// Where a `width` by `height` rectangle at `x`, `y` in the level is on
+    /// screen, or `None` if it's all off screen. See
+    /// [Drawing](self#drawing).
+    pub fn to_screen(&self, x: u16, y: u16, width: u8, height: u8) -> Option<(u8, u8)> {
+        Some((screen_axis(x, width, self.x)?, screen_axis(y, height, self.y)?))
+    }
+
+    /// Where the point `x`, `y` in the level is relative to the top-left of
+    /// the screen, on screen or not.
+    pub fn offset(&self, x: u16, y: u16) -> (i16, i16) {
+        (x.wrapping_sub(self.x) as i16, y.wrapping_sub(self.y) as i16)
+    }
+}
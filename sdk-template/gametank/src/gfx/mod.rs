@@ -0,0 +1,277 @@
+//! # Sprite Sheets
+//!
+//! Loading graphics into sprite RAM by hand means juggling the page bits in
+//! [`BankFlags`](crate::scr::BankFlags), the quadrant selected through the
+//! blitter, and the 16KB window at `$4000`. [`SpriteSheet`] does all of that
+//! for you.
+//!
+//! ```ignore
+//! use rom::sdk::{blitter::SpriteQuadrant, gfx::SpriteSheet};
+//!
+//! // Produced by `gtrom convert hero.png -f rs`
+//! static HERO: &[u8] = include_bytes!("hero.bin");
+//!
+//! // Put the sheet in page 2, starting at the top-left quadrant
+//! let sheet = SpriteSheet::new(HERO, 2);
+//! sheet.load(&mut console);
+//!
+//! // Later, select the page and draw from it
+//! console.set_sprite_page(sheet.page());
+//! let mut blitter = console.blitter().unwrap();
+//! blitter.draw_sprite(sheet.gx(), sheet.gy(), 10, 10, 16, 16);
+//! blitter.wait_blit();
+//! ```
+//!
+//! ## Layout
+//!
+//! Data is written in 128×128 quadrant-sized chunks (16KB each). A sheet
+//! larger than one quadrant spills into the next quadrant in reading order
+//! (top-left, top-right, bottom-left, bottom-right), then into quadrant one of
+//! the next page. This matches the output of `gtrom convert`.
+//!
+//! An `offset` lets several small sheets share a quadrant: it's a byte offset
+//! into the first quadrant, i.e. `y * 128 + x`.
+//!
+//! ## Metasprites
+//!
+//! A character bigger than one blit, or assembled from pieces scattered
+//! around sprite RAM, is a [`MetaSprite`]: a list of [`SpritePart`]s, each
+//! drawn at an offset from the sprite's origin. Declare one with
+//! [`metasprite!`](crate::metasprite) and draw it with
+//! [`BlitterGuard::draw_metasprite`](crate::video_dma::blitter::BlitterGuard::draw_metasprite):
+//!
+//! ```ignore
+//! use rom::sdk::gfx::MetaSprite;
+//!
+//! // page, source x/y, offset x/y, size
+//! static KNIGHT: MetaSprite = gametank::metasprite![
+//!     (1, 0, 0, 0, 0, 16, 16),    // helmet
+//!     (1, 0, 16, 0, 16, 16, 16),  // body
+//!     (2, 32, 0, -8, 4, 8, 16),   // shield, from another page
+//! ];
+//!
+//! let mut blitter = console.blitter().unwrap();
+//! blitter.draw_metasprite(&KNIGHT, knight_x, knight_y);
+//! blitter.wait_blit();
+//! ```
+//!
+//! Source coordinates are blitter coordinates, quadrant included, like
+//! [`SpriteSheet::gx`] and [`SpriteSheet::gy`] return.
+//!
+//! ## Text
+//!
+//! [`text::Font`] draws a built-in font, at normal or double height. Menus
+//! and text boxes built on it are in [`ui`](crate::ui).
+//!
+//! ## Tilemaps
+//!
+//! Backgrounds made of 16×16 tiles, with scrolling, are in [`tilemap`].
+//!
+//! ## Cameras
+//!
+//! For a level bigger than the screen, a [`Camera`] follows the player with
+//! a deadzone and smoothing, stays inside the level, scrolls the tilemap,
+//! and turns level positions into screen positions for sprites. See
+//! [`camera`].
+//!
+//! ## Animation
+//!
+//! Frames and tagged animations converted from Aseprite files are played
+//! with [`anim`].
+//!
+//! ## Streaming
+//!
+//! To load sheets a little each frame instead of all at once, e.g. the next
+//! level's tiles while this one plays, queue them on a [`Streamer`]. See
+//! [`stream`].
+//!
+//! ## ROM Banks
+//!
+//! If the asset lives in a banked section, switch to that ROM bank before
+//! calling [`SpriteSheet::load`], or load it from inside
+//! [`BankedSlice::with`](crate::banking::BankedSlice::with).
+
+use crate::{blitter::SpriteQuadrant, console::Console, mem};
+
+pub mod anim;
+pub mod camera;
+pub mod stream;
+pub mod text;
+pub mod tilemap;
+
+pub use camera::Camera;
+pub use stream::Streamer;
+
+/// Size of one CPU-visible sprite RAM quadrant.
+pub const QUADRANT_SIZE: usize = 0x4000;
+
+/// Width of a quadrant in pixels (also its row stride in bytes).
+const QUADRANT_WIDTH: u16 = 128;
+
+/// Number of sprite RAM pages.
+pub const SPRITE_PAGES: u8 = 8;
+
+/// Graphics data and where it goes in sprite RAM.
+pub struct SpriteSheet<'a> {
+    data: &'a [u8],
+    page: u8,
+    quadrant: SpriteQuadrant,
+    offset: u16,
+}
+
+impl<'a> SpriteSheet<'a> {
+    /// A sheet that starts at the top-left of `page` (0-7).
+    pub const fn new(data: &'a [u8], page: u8) -> Self {
+        Self {
+            data,
+            page,
+            quadrant: SpriteQuadrant::One,
+            offset: 0,
+        }
+    }
+
+    /// Start the sheet at `offset` bytes into `quadrant` instead.
+    pub const fn at(mut self, quadrant: SpriteQuadrant, offset: u16) -> Self {
+        self.quadrant = quadrant;
+        self.offset = offset;
+        self
+    }
+
+    /// The sprite RAM page the sheet starts in.
+    #[inline(always)]
+    pub fn page(&self) -> u8 {
+        self.page
+    }
+
+    /// Blitter source X of the sheet's first pixel.
+    #[inline(always)]
+    pub fn gx(&self) -> u8 {
+        self.quadrant.value_gx() + (self.offset % QUADRANT_WIDTH) as u8
+    }
+
+    /// Blitter source Y of the sheet's first pixel.
+    #[inline(always)]
+    pub fn gy(&self) -> u8 {
+        self.quadrant.value_gy() + (self.offset / QUADRANT_WIDTH) as u8
+    }
+
+    /// Copy the sheet into sprite RAM.
+    ///
+    /// The sprite page selected beforehand is restored afterwards. Data that
+    /// would run past page 7 is dropped. Returns `false` if the video DMA is
+    /// held elsewhere, by a blitter or another guard: loading stops at the
+    /// first chunk it can't copy, leaving the sheet partly loaded.
+    pub fn load(&self, console: &mut Console) -> bool {
+        let previous_page = console.bank_flags.sprite_page();
+
+        let mut page = self.page;
+        let mut quadrant = self.quadrant;
+        let mut offset = (self.offset as usize).min(QUADRANT_SIZE);
+        let mut remaining = self.data;
+        let mut loaded = true;
+
+        while !remaining.is_empty() && page < SPRITE_PAGES {
+            let len = remaining.len().min(QUADRANT_SIZE - offset);
+            let (chunk, rest) = remaining.split_at(len);
+
+            console.set_sprite_page(page);
+            let Some(mut blitter) = console.blitter() else { loaded = false; break };
+            blitter.set_vram_quad(quadrant);
+            drop(blitter);
+            let Some(mut sm) = console.dma.sprite_mem(&mut console.video_flags) else { loaded = false; break };
+            mem::copy_to_sprite_mem(&mut sm, offset as u16, chunk);
+
+            remaining = rest;
+            offset = 0;
+            match quadrant.next() {
+                Some(next) => quadrant = next,
+                None => {
+                    quadrant = SpriteQuadrant::One;
+                    page += 1;
+                }
+            }
+        }
+
+        console.set_sprite_page(previous_page);
+        loaded
+    }
+}
+
+/// One blit of a [`MetaSprite`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpritePart {
+    /// Sprite RAM page (0-7) the pixels are in
+    pub page: u8,
+    /// Blitter source X, quadrant included
+    pub sx: u8,
+    /// Blitter source Y, quadrant included
+    pub sy: u8,
+    /// Where to draw it, relative to the metasprite's origin
+    pub dx: i8,
+    pub dy: i8,
+    pub width: u8,
+    pub height: u8,
+}
+
+/// A sprite drawn as several blits. See the [module docs](self#metasprites).
+#[derive(Clone, Copy, Debug)]
+pub struct MetaSprite {
+    parts: &'static [SpritePart],
+}
+
+impl MetaSprite {
+    /// A metasprite from its parts, drawn in order (later parts on top).
+    pub const fn new(parts: &'static [SpritePart]) -> Self {
+        Self { parts }
+    }
+
+    #[inline(always)]
+    pub fn parts(&self) -> &'static [SpritePart] {
+        self.parts
+    }
+
+    /// The box around every part, as `(dx, dy, width, height)` from the
+    /// origin. Handy for hit boxes.
+    pub fn bounds(&self) -> (i8, i8, u8, u8) {
+        let Some(first) = self.parts.first() else {
+            return (0, 0, 0, 0);
+        };
+        let (mut left, mut top) = (first.dx as i16, first.dy as i16);
+        let (mut right, mut bottom) = (left, top);
+        for part in self.parts {
+            left = left.min(part.dx as i16);
+            top = top.min(part.dy as i16);
+            right = right.max(part.dx as i16 + part.width as i16);
+            bottom = bottom.max(part.dy as i16 + part.height as i16);
+        }
+        (left as i8, top as i8, (right - left) as u8, (bottom - top) as u8)
+    }
+}
+
+/// Declare a [`MetaSprite`](crate::gfx::MetaSprite) from
+/// `(page, sx, sy, dx, dy, width, height)` tuples.
+///
+/// ```ignore
+/// static BOSS: MetaSprite = gametank::metasprite![
+///     (0, 0, 0, 0, 0, 32, 32),
+///     (0, 32, 0, 32, 0, 32, 32),
+/// ];
+/// ```
+#[macro_export]
+macro_rules! metasprite {
+    ($(($page:expr, $sx:expr, $sy:expr, $dx:expr, $dy:expr, $width:expr, $height:expr)),* $(,)?) => {
+        $crate::gfx::MetaSprite::new(&[
+            $(
+                $crate::gfx::SpritePart {
+                    page: $page,
+                    sx: $sx,
+                    sy: $sy,
+                    dx: $dx,
+                    dy: $dy,
+                    width: $width,
+                    height: $height,
+                },
+            )*
+        ])
+    };
+}
@@ -0,0 +1,247 @@
+//! # Sprite Streaming
+//!
+//! Loading a whole tile set with [`SpriteSheet::load`] stops the game for
+//! as long as the copy takes, several frames for a full page. A [`Streamer`]
+//! queues the loads instead, and copies a little at a time at the start of
+//! each frame, before anything is drawn, picking up where it left off the
+//! next frame:
+//!
+//! ```ignore
+//! use rom::sdk::gfx::{SpriteSheet, Streamer};
+//!
+//! let mut streamer: Streamer<4> = Streamer::new();
+//!
+//! // Level 2's tiles go into pages 2 and 3 while level 1 plays on page 1
+//! streamer.queue(LEVEL2_TILES_BANK.get(), SpriteSheet::new(&LEVEL2_TILES, 2));
+//! streamer.queue(LEVEL2_ENEMIES_BANK.get(), SpriteSheet::new(&LEVEL2_ENEMIES, 3));
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     streamer.start_frame();
+//!     streamer.run(&mut console);
+//!
+//!     if level_over && streamer.is_empty() {
+//!         // everything's in sprite RAM
+//!     }
+//!     // ...
+//! }
+//! ```
+//!
+//! ## Timing
+//!
+//! The CPU and the blitter share sprite RAM, so copies have to happen while
+//! nothing is being drawn. [`run`](Streamer::run) copies in chunks of up to
+//! [`CHUNK_SIZE`] bytes, and only starts a chunk if the time the last one
+//! took still fits before the deadline. Time is counted with the VIA's frame
+//! timer from [`start_frame`](Streamer::start_frame), like the
+//! [`scheduler`](crate::scheduler) (with either of those or the profiler,
+//! only one needs to start the frame). The deadline is [`DEFAULT_DEADLINE`],
+//! roughly the vblank, unless [`set_deadline`](Streamer::set_deadline)
+//! moves it. The first chunk of a frame only needs to start before the
+//! deadline, so a queue always drains.
+//!
+//! ## ROM Banks
+//!
+//! Each load names the ROM bank its data is in, usually from a
+//! [`bank_table!`](crate::bank_table) entry, or 127 for data in the fixed
+//! bank. `run` switches to it for each chunk and puts back the bank and
+//! sprite page that were selected, so it has to be called from the fixed
+//! bank (the default for code).
+
+use crate::{
+    blitter::SpriteQuadrant,
+    console::Console,
+    mem,
+    scheduler::CYCLES_PER_SCANLINE,
+    via::{Via, FRAME_CYCLES},
+};
+
+use super::{SpriteSheet, QUADRANT_SIZE, SPRITE_PAGES};
+
+/// Most bytes [`Streamer::run`] copies at once
+pub const CHUNK_SIZE: usize = 256;
+
+/// Cycles into the frame that chunks stop at by default: about the vblank
+pub const DEFAULT_DEADLINE: u16 = 20 * CYCLES_PER_SCANLINE;
+
+/// Bytes in a sprite RAM page
+const PAGE_SIZE: u32 = 4 * QUADRANT_SIZE as u32;
+
+/// A queued load
+struct Upload {
+    sheet: SpriteSheet<'static>,
+    bank: u8,
+}
+
+impl Upload {
+    /// Where the sheet starts, as a byte offset into all of sprite RAM
+    fn start(&self) -> u32 {
+        let quadrant = match self.sheet.quadrant {
+            SpriteQuadrant::One => 0,
+            SpriteQuadrant::Two => 1,
+            SpriteQuadrant::Three => 2,
+            SpriteQuadrant::Four => 3,
+        };
+        self.sheet.page as u32 * PAGE_SIZE
+            + quadrant * QUADRANT_SIZE as u32
+            + (self.sheet.offset as usize).min(QUADRANT_SIZE) as u32
+    }
+}
+
+fn quadrant_at(position: u32) -> SpriteQuadrant {
+    match (position / QUADRANT_SIZE as u32) % 4 {
+        0 => SpriteQuadrant::One,
+        1 => SpriteQuadrant::Two,
+        2 => SpriteQuadrant::Three,
+        _ => SpriteQuadrant::Four,
+    }
+}
+
+/// Up to `N` queued sprite RAM loads, copied a chunk at a time. See the
+/// [module docs](self).
+pub struct Streamer<const N: usize> {
+    queue: [Option<Upload>; N],
+    /// Index of the load being copied
+    head: usize,
+    len: usize,
+    /// Bytes of the head load copied so far
+    copied: usize,
+    deadline: u16,
+    /// Cycles the last chunk took, to guess whether the next will fit
+    cost: u16,
+}
+
+impl<const N: usize> Streamer<N> {
+    pub const fn new() -> Self {
+        Self {
+            queue: [const { None }; N],
+            head: 0,
+            len: 0,
+            copied: 0,
+            deadline: DEFAULT_DEADLINE,
+            cost: 0,
+        }
+    }
+
+    /// Queue `sheet`, whose data is in ROM bank `bank`, after the loads
+    /// already queued. Returns `false` if there are already `N`.
+    pub fn queue(&mut self, bank: u8, sheet: SpriteSheet<'static>) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.queue[(self.head + self.len) % N] = Some(Upload { sheet, bank });
+        self.len += 1;
+        true
+    }
+
+    /// Drop every queued load, including one that's partly copied
+    pub fn clear(&mut self) {
+        self.queue = [const { None }; N];
+        self.head = 0;
+        self.len = 0;
+        self.copied = 0;
+    }
+
+    /// Loads not yet finished, the one being copied included
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether everything queued is in sprite RAM
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes still to copy, across the whole queue
+    pub fn remaining(&self) -> usize {
+        let queued: usize = (0..self.len)
+            .filter_map(|i| self.queue[(self.head + i) % N].as_ref())
+            .map(|upload| upload.sheet.data.len())
+            .sum();
+        queued - self.copied
+    }
+
+    /// Stop starting chunks `cycles` into the frame
+    pub fn set_deadline(&mut self, cycles: u16) {
+        self.deadline = cycles.min(FRAME_CYCLES);
+    }
+
+    /// Restart the frame timer. Call right after waiting for vblank.
+    pub fn start_frame(&mut self) {
+        unsafe { Via::new() }.start_frame_timer();
+    }
+
+    /// Copy chunks until the deadline is near or the queue is empty. Call
+    /// once a frame, before drawing anything.
+    pub fn run(&mut self, console: &mut Console) {
+        if self.is_empty() {
+            return;
+        }
+
+        let via = unsafe { Via::new() };
+        let previous_page = console.bank_flags.sprite_page();
+        let previous_bank = console.rom_bank();
+
+        let mut first = true;
+        loop {
+            let now = via.frame_cycles();
+            if now >= self.deadline || (!first && now.saturating_add(self.cost) > self.deadline) {
+                break;
+            }
+            first = false;
+
+            let started = via.frame_cycles();
+            let more = self.copy_chunk(console);
+            self.cost = via.frame_cycles().saturating_sub(started);
+            if !more {
+                break;
+            }
+        }
+
+        console.set_rom_bank(previous_bank);
+        console.set_sprite_page(previous_page);
+    }
+
+    /// Copy the next chunk of the head load. Returns whether there's more
+    /// queued.
+    fn copy_chunk(&mut self, console: &mut Console) -> bool {
+        let Some(upload) = self.queue[self.head].as_ref() else {
+            return false;
+        };
+
+        let data = upload.sheet.data;
+        let position = upload.start() + self.copied as u32;
+        let page = (position / PAGE_SIZE) as u8;
+        let offset = position as usize % QUADRANT_SIZE;
+        let len = (data.len() - self.copied).min(CHUNK_SIZE).min(QUADRANT_SIZE - offset);
+
+        if page < SPRITE_PAGES && len > 0 {
+            console.set_sprite_page(page);
+            if let Some(mut blitter) = console.blitter() {
+                blitter.set_vram_quad(quadrant_at(position));
+            }
+            console.set_rom_bank(upload.bank);
+            if let Some(mut sm) = console.dma.sprite_mem(&mut console.video_flags) {
+                mem::copy_to_sprite_mem(&mut sm, offset as u16, &data[self.copied..self.copied + len]);
+            }
+            self.copied += len;
+        }
+
+        // done, or running past page 7, where the rest is dropped like `load`
+        if page >= SPRITE_PAGES || self.copied == data.len() {
+            self.queue[self.head] = None;
+            self.head = (self.head + 1) % N;
+            self.len -= 1;
+            self.copied = 0;
+        }
+        !self.is_empty()
+    }
+}
+
+impl<const N: usize> Default for Streamer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,298 @@
+//! # Text
+//!
+//! A built-in 3×5 pixel font, drawn by the blitter from sprite RAM. [`Font::load`]
+//! renders it there in one color, twice: at its normal size, in 4×6 cells
+//! (32 characters across the screen), and at double height, in 4×12 cells,
+//! for titles and menus that have to read well on a TV.
+//!
+//! ```ignore
+//! use rom::sdk::gfx::text::{Font, TextSize};
+//!
+//! // The font takes the top 36 rows of page 7's first quadrant
+//! const FONT: Font = Font::new(7);
+//! FONT.load(&mut console, !WHITE);
+//!
+//! let mut blitter = console.blitter().unwrap();
+//! let x = FONT.draw(&mut blitter, 8, 8, "SCORE ", TextSize::Normal);
+//! blitter.wait_blit();
+//! FONT.draw_number(&mut blitter, x, 8, score, TextSize::Normal);
+//! blitter.wait_blit();
+//! FONT.draw(&mut blitter, 24, 40, "PAUSED", TextSize::Tall);
+//! blitter.wait_blit();
+//! ```
+//!
+//! The font has ASCII's printable characters up to `_`. Lower case is drawn
+//! as upper case, and anything else as `?`. Glyphs are drawn transparent, so
+//! what's behind them shows through.
+
+use crate::{blitter::SpriteQuadrant, console::Console, mem, video_dma::blitter::{BlitterGuard, DrawOptions}};
+
+/// Width of a character cell in pixels, including the space after it.
+pub const GLYPH_WIDTH: u8 = 4;
+
+/// The first character in the font, a space.
+const FIRST: u8 = b' ';
+
+/// Glyphs per row of the font in sprite RAM.
+const GLYPHS_PER_ROW: u8 = 32;
+
+/// Rows of sprite RAM the font takes: two rows of glyphs at each size.
+pub const FONT_HEIGHT: u8 = 2 * 6 + 2 * 12;
+
+/// Each glyph from ` ` to `_`, five rows of three pixels, leftmost pixel in
+/// bit 2.
+static GLYPHS: [[u8; 5]; 64] = [
+    [0b000, 0b000, 0b000, 0b000, 0b000], // space
+    [0b010, 0b010, 0b010, 0b000, 0b010], // !
+    [0b101, 0b101, 0b000, 0b000, 0b000], // "
+    [0b101, 0b111, 0b101, 0b111, 0b101], // #
+    [0b011, 0b110, 0b010, 0b011, 0b110], // $
+    [0b101, 0b001, 0b010, 0b100, 0b101], // %
+    [0b010, 0b101, 0b010, 0b101, 0b011], // &
+    [0b010, 0b010, 0b000, 0b000, 0b000], // '
+    [0b001, 0b010, 0b010, 0b010, 0b001], // (
+    [0b100, 0b010, 0b010, 0b010, 0b100], // )
+    [0b000, 0b101, 0b010, 0b101, 0b000], // *
+    [0b000, 0b010, 0b111, 0b010, 0b000], // +
+    [0b000, 0b000, 0b000, 0b010, 0b100], // ,
+    [0b000, 0b000, 0b111, 0b000, 0b000], // -
+    [0b000, 0b000, 0b000, 0b000, 0b010], // .
+    [0b001, 0b001, 0b010, 0b100, 0b100], // /
+    [0b111, 0b101, 0b101, 0b101, 0b111], // 0
+    [0b010, 0b110, 0b010, 0b010, 0b111], // 1
+    [0b110, 0b001, 0b010, 0b100, 0b111], // 2
+    [0b110, 0b001, 0b010, 0b001, 0b110], // 3
+    [0b101, 0b101, 0b111, 0b001, 0b001], // 4
+    [0b111, 0b100, 0b110, 0b001, 0b110], // 5
+    [0b011, 0b100, 0b111, 0b101, 0b111], // 6
+    [0b111, 0b001, 0b010, 0b010, 0b010], // 7
+    [0b111, 0b101, 0b111, 0b101, 0b111], // 8
+    [0b111, 0b101, 0b111, 0b001, 0b110], // 9
+    [0b000, 0b010, 0b000, 0b010, 0b000], // :
+    [0b000, 0b010, 0b000, 0b010, 0b100], // ;
+    [0b001, 0b010, 0b100, 0b010, 0b001], // <
+    [0b000, 0b111, 0b000, 0b111, 0b000], // =
+    [0b100, 0b010, 0b001, 0b010, 0b100], // >
+    [0b110, 0b001, 0b010, 0b000, 0b010], // ?
+    [0b010, 0b101, 0b111, 0b100, 0b011], // @
+    [0b010, 0b101, 0b111, 0b101, 0b101], // A
+    [0b110, 0b101, 0b110, 0b101, 0b110], // B
+    [0b011, 0b100, 0b100, 0b100, 0b011], // C
+    [0b110, 0b101, 0b101, 0b101, 0b110], // D
+    [0b111, 0b100, 0b110, 0b100, 0b111], // E
+    [0b111, 0b100, 0b110, 0b100, 0b100], // F
+    [0b011, 0b100, 0b101, 0b101, 0b011], // G
+    [0b101, 0b101, 0b111, 0b101, 0b101], // H
+    [0b111, 0b010, 0b010, 0b010, 0b111], // I
+    [0b001, 0b001, 0b001, 0b101, 0b010], // J
+    [0b101, 0b101, 0b110, 0b101, 0b101], // K
+    [0b100, 0b100, 0b100, 0b100, 0b111], // L
+    [0b101, 0b111, 0b111, 0b101, 0b101], // M
+    [0b110, 0b101, 0b101, 0b101, 0b101], // N
+    [0b010, 0b101, 0b101, 0b101, 0b010], // O
+    [0b110, 0b101, 0b110, 0b100, 0b100], // P
+    [0b010, 0b101, 0b101, 0b111, 0b011], // Q
+    [0b110, 0b101, 0b110, 0b101, 0b101], // R
+    [0b011, 0b100, 0b010, 0b001, 0b110], // S
+    [0b111, 0b010, 0b010, 0b010, 0b010], // T
+    [0b101, 0b101, 0b101, 0b101, 0b111], // U
+    [0b101, 0b101, 0b101, 0b101, 0b010], // V
+    [0b101, 0b101, 0b111, 0b111, 0b101], // W
+    [0b101, 0b101, 0b010, 0b101, 0b101], // X
+    [0b101, 0b101, 0b010, 0b010, 0b010], // Y
+    [0b111, 0b001, 0b010, 0b100, 0b111], // Z
+    [0b110, 0b100, 0b100, 0b100, 0b110], // [
+    [0b100, 0b100, 0b010, 0b001, 0b001], // \
+    [0b011, 0b001, 0b001, 0b001, 0b011], // ]
+    [0b010, 0b101, 0b000, 0b000, 0b000], // ^
+    [0b000, 0b000, 0b000, 0b000, 0b111], // _
+];
+
+/// How tall text is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextSize {
+    /// 4×6 cells
+    Normal,
+    /// Double height, 4×12 cells
+    Tall,
+}
+
+impl TextSize {
+    /// Height of a character cell in pixels, including the space below it.
+    pub const fn height(self) -> u8 {
+        match self {
+            TextSize::Normal => 6,
+            TextSize::Tall => 12,
+        }
+    }
+
+    /// Where this size starts in the font, in rows from its top
+    const fn offset(self) -> u8 {
+        match self {
+            TextSize::Normal => 0,
+            TextSize::Tall => 2 * 6,
+        }
+    }
+
+    /// Font rows per glyph row: each row is drawn twice at double height
+    const fn scale(self) -> u8 {
+        match self {
+            TextSize::Normal => 1,
+            TextSize::Tall => 2,
+        }
+    }
+}
+
+/// Glyph number of `c` in [`GLYPHS`]
+#[inline(always)]
+fn glyph(c: u8) -> u8 {
+    match c {
+        b' '..=b'_' => c - FIRST,
+        b'a'..=b'z' => c - b'a' + b'A' - FIRST,
+        _ => b'?' - FIRST,
+    }
+}
+
+/// Width of `text` in pixels when drawn, at [`GLYPH_WIDTH`] a character.
+pub const fn text_width(text: &str) -> u8 {
+    let len = text.len();
+    if len > 255 / GLYPH_WIDTH as usize { 255 } else { len as u8 * GLYPH_WIDTH }
+}
+
+/// Where the font is in sprite RAM: [`FONT_HEIGHT`] rows of a quadrant,
+/// the full 128 pixels wide.
+#[derive(Clone, Copy)]
+pub struct Font {
+    page: u8,
+    quadrant: SpriteQuadrant,
+    y: u8,
+}
+
+impl Font {
+    /// A font at the top-left of `page` (0-7).
+    pub const fn new(page: u8) -> Self {
+        Self { page, quadrant: SpriteQuadrant::One, y: 0 }
+    }
+
+    /// Put it `y` rows down `quadrant` instead, at most `128 - FONT_HEIGHT`.
+    pub const fn at(mut self, quadrant: SpriteQuadrant, y: u8) -> Self {
+        self.quadrant = quadrant;
+        self.y = y;
+        self
+    }
+
+    #[inline(always)]
+    pub fn page(&self) -> u8 {
+        self.page
+    }
+
+    /// Render the font into sprite RAM in `color`, given as for
+    /// [`draw_square`](BlitterGuard::draw_square) (`!WHITE`). The pixels
+    /// around each glyph are 0, which transparent blits skip, so `color`
+    /// can't be 0.
+    ///
+    /// Like [`SpriteSheet::load`](super::SpriteSheet::load), the sprite
+    /// page selected beforehand is restored afterwards.
+    pub fn load(&self, console: &mut Console, color: u8) {
+        let previous_page = console.bank_flags.sprite_page();
+        console.set_sprite_page(self.page);
+        if let Some(mut blitter) = console.blitter() {
+            blitter.set_vram_quad(self.quadrant);
+        }
+
+        let mut line = [0u8; 128];
+        for size in [TextSize::Normal, TextSize::Tall] {
+            for row in 0..2 * size.height() {
+                let (glyph_row, pixel_row) = (row / size.height(), row % size.height() / size.scale());
+                for (i, cell) in line.chunks_exact_mut(GLYPH_WIDTH as usize).enumerate() {
+                    let bits = match GLYPHS[(glyph_row * GLYPHS_PER_ROW) as usize + i].get(pixel_row as usize) {
+                        Some(&bits) => bits,
+                        None => 0,
+                    };
+                    for (x, pixel) in cell.iter_mut().enumerate() {
+                        *pixel = if x < 3 && bits & (0b100 >> x) != 0 { color } else { 0 };
+                    }
+                }
+
+                let y = self.y as u16 + size.offset() as u16 + row as u16;
+                if let Some(mut sm) = console.dma.sprite_mem(&mut console.video_flags) {
+                    mem::copy_to_sprite_mem(&mut sm, y * 128, &line);
+                }
+            }
+        }
+
+        console.set_sprite_page(previous_page);
+    }
+
+    /// Blitter source of character `c` at `size`
+    #[inline(always)]
+    fn source(&self, c: u8, size: TextSize) -> (u8, u8) {
+        let glyph = glyph(c);
+        let sx = self.quadrant.value_gx() + (glyph % GLYPHS_PER_ROW) * GLYPH_WIDTH;
+        let sy = self.quadrant.value_gy() + self.y + size.offset() + (glyph / GLYPHS_PER_ROW) * size.height();
+        (sx, sy)
+    }
+
+    /// Draw `text` from `(x, y)`, starting a new line at `x` after each
+    /// `\n`. Returns the x just past the last character, where more text
+    /// would go.
+    ///
+    /// Waits between characters, so the caller only has to wait for the
+    /// last one, as with [`draw_metasprite`](BlitterGuard::draw_metasprite).
+    /// Characters that would run off the right or bottom of the screen are
+    /// skipped. The sprite page is switched to the font's and back (for
+    /// guards from [`Console::blitter`]), and the blitter is left drawing transparent, as
+    /// [`draw_sprite_with`](BlitterGuard::draw_sprite_with) leaves it.
+    pub fn draw(&self, blitter: &mut BlitterGuard, x: u8, y: u8, text: &str, size: TextSize) -> u8 {
+        let start_page = blitter.sprite_page();
+        let switch = start_page.is_some_and(|page| page != self.page);
+        if switch {
+            blitter.set_sprite_page(self.page);
+        }
+
+        let height = size.height();
+        let (mut cx, mut cy) = (x, y);
+        let mut drawn = false;
+        for c in text.bytes() {
+            if c == b'\n' {
+                cx = x;
+                cy = cy.saturating_add(height);
+                continue;
+            }
+            let fits = cx <= 128 - GLYPH_WIDTH && cy <= 128 - height;
+            if fits && c != b' ' {
+                if drawn {
+                    blitter.wait_blit();
+                }
+                let (sx, sy) = self.source(c, size);
+                blitter.draw_sprite_with(sx, sy, cx, cy, GLYPH_WIDTH, height, DrawOptions::DEFAULT.transparent());
+                drawn = true;
+            }
+            cx = cx.saturating_add(GLYPH_WIDTH);
+        }
+
+        if let Some(start_page) = start_page.filter(|_| switch) {
+            blitter.wait_blit();
+            blitter.set_sprite_page(start_page);
+        }
+        cx
+    }
+
+    /// Draw `value` in decimal, as [`draw`](Self::draw) does.
+    pub fn draw_number(&self, blitter: &mut BlitterGuard, x: u8, y: u8, value: u16, size: TextSize) -> u8 {
+        let mut digits = [0u8; 5];
+        let mut start = digits.len();
+        let mut value = value;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (value % 10) as u8;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+        // only ever ASCII digits
+        let text = core::str::from_utf8(&digits[start..]).unwrap_or("");
+        self.draw(blitter, x, y, text, size)
+    }
+}
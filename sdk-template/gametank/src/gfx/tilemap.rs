@@ -0,0 +1,319 @@
+//! # Tilemaps
+//!
+//! A [`Tilemap`] is a grid of tile indices in RAM, drawn from a [`Tileset`]
+//! in sprite RAM with one 16×16 blit per tile. Scrolling moves a 128×128
+//! view over the map; tiles cut off at the screen edges are drawn at
+//! negative positions with the `CLIP_X`/`CLIP_Y` bank flags set, so they
+//! don't wrap around to the other side.
+//!
+//! ```ignore
+//! use rom::sdk::gfx::{SpriteSheet, tilemap::{Tilemap, Tileset}};
+//!
+//! static TILES: &[u8] = include_bytes!("tiles.bin"); // a 128px wide sheet of 16x16 tiles
+//! static LEVEL: [[u8; 8]; 32] = [...];
+//!
+//! SpriteSheet::new(TILES, 3).load(&mut console);
+//! let mut map = Tilemap::from_tiles(Tileset::new(3), LEVEL);
+//! map.set_scroll(0, map.max_scroll().1); // start at the bottom
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     console.flip_framebuffers();
+//!
+//!     map.scroll_by(0, -1);
+//!     let mut blitter = console.blitter().unwrap();
+//!     map.draw(&mut blitter);
+//!     // draw sprites on top, then tell the map what they covered
+//!     blitter.draw_sprite(sx, sy, x, y, 16, 16);
+//!     blitter.wait_blit();
+//!     map.mark_dirty(x, y, 16, 16);
+//! }
+//! ```
+//!
+//! ## What Gets Redrawn
+//!
+//! The GameTank can't move what's already in a framebuffer, so after a
+//! scroll every visible tile is blitted again: 64 to 81 blits, about a third
+//! of the blitter's time per frame. While the view holds still, [`draw`]
+//! only blits tiles changed with [`set_tile`] or covered by something
+//! passed to [`mark_dirty`].
+//!
+//! Both framebuffers are tracked separately, since with double buffering
+//! each one is drawn every other frame.
+//!
+//! ## Tiled Maps
+//!
+//! `gtrom convert level.tmx` turns a [Tiled](https://www.mapeditor.org/) map
+//! into a tileset for sprite RAM, a `[[u8; W]; H]` for each tile layer, and
+//! a table of [`Spawn`]s from its object layers.
+//!
+//! [`draw`]: Tilemap::draw
+//! [`set_tile`]: Tilemap::set_tile
+//! [`mark_dirty`]: Tilemap::mark_dirty
+
+use crate::{blitter::SpriteQuadrant, scr::BankFlags, video_dma::blitter::BlitterGuard};
+
+/// Width and height of a tile in pixels.
+pub const TILE_SIZE: u8 = 16;
+
+/// Tiles across (and down) the screen, plus one for a partly scrolled-in tile.
+const VIEW_TILES: usize = 128 / TILE_SIZE as usize + 1;
+
+/// Tiles per row of a quadrant.
+const QUADRANT_TILES: u8 = 128 / TILE_SIZE;
+
+/// Where a map's tiles are in sprite RAM.
+///
+/// Tiles are numbered in reading order from a 128 pixel wide sheet loaded at
+/// the top-left of `page` by [`SpriteSheet`](super::SpriteSheet), which is
+/// what `gtrom convert` makes from a 128 pixel wide PNG. A full page holds
+/// 256 tiles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tileset {
+    page: u8,
+}
+
+impl Tileset {
+    pub const fn new(page: u8) -> Self {
+        Self { page }
+    }
+
+    #[inline(always)]
+    pub fn page(&self) -> u8 {
+        self.page
+    }
+
+    /// Blitter source X and Y of `tile`.
+    pub fn source(&self, tile: u8) -> (u8, u8) {
+        let per_quadrant = QUADRANT_TILES * QUADRANT_TILES;
+        let quadrant = match tile / per_quadrant {
+            0 => SpriteQuadrant::One,
+            1 => SpriteQuadrant::Two,
+            2 => SpriteQuadrant::Three,
+            _ => SpriteQuadrant::Four,
+        };
+        let index = tile % per_quadrant;
+        (
+            quadrant.value_gx() + (index % QUADRANT_TILES) * TILE_SIZE,
+            quadrant.value_gy() + (index / QUADRANT_TILES) * TILE_SIZE,
+        )
+    }
+}
+
+/// An object from a Tiled map's object layers, as `gtrom convert` writes
+/// them for a `.tmx`. `kind` is one of the `<MAP>_KIND_*` constants
+/// generated alongside, from the object's class (or name).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Spawn {
+    pub kind: u8,
+    /// Pixels from the left of the map
+    pub x: u16,
+    /// Pixels from the top of the map
+    pub y: u16,
+}
+
+/// A `W`×`H` grid of tiles with a scrollable view. See the
+/// [module docs](self).
+pub struct Tilemap<const W: usize, const H: usize> {
+    tiles: [[u8; W]; H],
+    tileset: Tileset,
+    scroll_x: u16,
+    scroll_y: u16,
+    /// Whether each framebuffer holds the map at the current scroll
+    drawn: [bool; 2],
+    /// Visible tiles to redraw in each framebuffer: a bit per column, for
+    /// each row, counted from the top-left visible tile
+    dirty: [[u16; VIEW_TILES]; 2],
+}
+
+impl<const W: usize, const H: usize> Tilemap<W, H> {
+    /// A map filled with tile 0.
+    pub const fn new(tileset: Tileset) -> Self {
+        Self::from_tiles(tileset, [[0; W]; H])
+    }
+
+    /// A map from rows of tile indices.
+    pub const fn from_tiles(tileset: Tileset, tiles: [[u8; W]; H]) -> Self {
+        Self {
+            tiles,
+            tileset,
+            scroll_x: 0,
+            scroll_y: 0,
+            drawn: [false; 2],
+            dirty: [[0; VIEW_TILES]; 2],
+        }
+    }
+
+    #[inline(always)]
+    pub fn tileset(&self) -> Tileset {
+        self.tileset
+    }
+
+    /// The tile at column `x`, row `y`.
+    #[inline(always)]
+    pub fn tile(&self, x: usize, y: usize) -> u8 {
+        self.tiles[y][x]
+    }
+
+    /// Change a tile. It's redrawn by the next [`draw`](Self::draw) of
+    /// each framebuffer if it's on screen.
+    pub fn set_tile(&mut self, x: usize, y: usize, tile: u8) {
+        if self.tiles[y][x] == tile {
+            return;
+        }
+        self.tiles[y][x] = tile;
+
+        let (first_col, first_row) = self.first_tile();
+        let (Some(col), Some(row)) = (x.checked_sub(first_col), y.checked_sub(first_row)) else {
+            return;
+        };
+        if col < VIEW_TILES && row < VIEW_TILES {
+            for dirty in &mut self.dirty {
+                dirty[row] |= 1 << col;
+            }
+        }
+    }
+
+    /// Redraw the tiles under a screen rectangle, e.g. where a sprite was
+    /// drawn over the map.
+    pub fn mark_dirty(&mut self, x: u8, y: u8, width: u8, height: u8) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (fine_x, fine_y) = self.fine_scroll();
+        let tile_at = |pos: u8, fine: u8| ((pos as usize + fine as usize) / TILE_SIZE as usize).min(VIEW_TILES - 1);
+
+        let (left, right) = (tile_at(x, fine_x), tile_at(x.saturating_add(width - 1), fine_x));
+        let (top, bottom) = (tile_at(y, fine_y), tile_at(y.saturating_add(height - 1), fine_y));
+        let columns = ((1u16 << (right + 1)) - 1) & !((1u16 << left) - 1);
+
+        for dirty in &mut self.dirty {
+            for row in &mut dirty[top..=bottom] {
+                *row |= columns;
+            }
+        }
+    }
+
+    /// Redraw the whole view in both framebuffers.
+    pub fn invalidate(&mut self) {
+        self.drawn = [false; 2];
+    }
+
+    /// The top-left corner of the view, in pixels from the top-left of the map.
+    #[inline(always)]
+    pub fn scroll(&self) -> (u16, u16) {
+        (self.scroll_x, self.scroll_y)
+    }
+
+    /// The largest scroll that keeps the view inside the map.
+    pub fn max_scroll(&self) -> (u16, u16) {
+        let size = |tiles: usize| (tiles as u16 * TILE_SIZE as u16).saturating_sub(128);
+        (size(W), size(H))
+    }
+
+    /// Move the view, keeping it inside the map.
+    pub fn set_scroll(&mut self, x: u16, y: u16) {
+        let (max_x, max_y) = self.max_scroll();
+        let (x, y) = (x.min(max_x), y.min(max_y));
+        if (x, y) != (self.scroll_x, self.scroll_y) {
+            self.scroll_x = x;
+            self.scroll_y = y;
+            self.invalidate();
+        }
+    }
+
+    /// Move the view by some pixels, keeping it inside the map.
+    pub fn scroll_by(&mut self, dx: i16, dy: i16) {
+        let x = self.scroll_x.saturating_add_signed(dx);
+        let y = self.scroll_y.saturating_add_signed(dy);
+        self.set_scroll(x, y);
+    }
+
+    /// Column and row of the top-left visible tile.
+    fn first_tile(&self) -> (usize, usize) {
+        (
+            (self.scroll_x / TILE_SIZE as u16) as usize,
+            (self.scroll_y / TILE_SIZE as u16) as usize,
+        )
+    }
+
+    /// How many pixels of the top-left visible tile are scrolled off screen.
+    fn fine_scroll(&self) -> (u8, u8) {
+        (
+            (self.scroll_x % TILE_SIZE as u16) as u8,
+            (self.scroll_y % TILE_SIZE as u16) as u8,
+        )
+    }
+
+    /// Blit the tiles the framebuffer being drawn to is missing, and wait
+    /// for the last one.
+    ///
+    /// Use a guard from [`Console::blitter`](crate::console::Console::blitter):
+    /// it knows which framebuffer is being drawn and can set the sprite page
+    /// and clipping. With any other guard, the whole view is drawn every
+    /// time, from whichever page is selected, and tiles cut off at the
+    /// edges wrap around.
+    pub fn draw(&mut self, blitter: &mut BlitterGuard) {
+        let previous = blitter.bank_flags.as_deref().copied();
+        let framebuffer = previous.map(|flags| flags.contains(BankFlags::FRAMEBUFFER_SELECT) as usize);
+
+        let (full, dirty) = match framebuffer {
+            Some(fb) => (!self.drawn[fb], self.dirty[fb]),
+            None => (true, [0; VIEW_TILES]),
+        };
+        if !full && dirty.iter().all(|&row| row == 0) {
+            return;
+        }
+
+        blitter.set_sprite_page(self.tileset.page);
+        blitter.set_clip(true, true);
+
+        let (first_col, first_row) = self.first_tile();
+        let (fine_x, fine_y) = self.fine_scroll();
+        let visible = |index: usize, fine: u8| index * (TILE_SIZE as usize) < 128 + fine as usize;
+        let mut drawn = false;
+
+        for (row, &dirty_row) in dirty.iter().enumerate() {
+            let map_y = first_row + row;
+            if map_y >= H || !visible(row, fine_y) {
+                break;
+            }
+            let columns = if full { u16::MAX } else { dirty_row };
+            if columns == 0 {
+                continue;
+            }
+            let fb_y = (row as u8 * TILE_SIZE).wrapping_sub(fine_y);
+
+            for col in 0..VIEW_TILES {
+                let map_x = first_col + col;
+                if map_x >= W || !visible(col, fine_x) {
+                    break;
+                }
+                if columns & (1 << col) == 0 {
+                    continue;
+                }
+                let fb_x = (col as u8 * TILE_SIZE).wrapping_sub(fine_x);
+                let (sx, sy) = self.tileset.source(self.tiles[map_y][map_x]);
+
+                if drawn {
+                    blitter.wait_blit();
+                }
+                blitter.draw_sprite(sx, sy, fb_x, fb_y, TILE_SIZE, TILE_SIZE);
+                drawn = true;
+            }
+        }
+
+        if drawn {
+            blitter.wait_blit();
+        }
+
+        if let Some(previous) = previous {
+            blitter.set_clip(previous.contains(BankFlags::CLIP_X), previous.contains(BankFlags::CLIP_Y));
+            blitter.set_sprite_page(previous.sprite_page());
+        }
+        if let Some(fb) = framebuffer {
+            self.drawn[fb] = true;
+            self.dirty[fb] = [0; VIEW_TILES];
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! # Heap
+//!
+//! With the `alloc` feature, the SDK installs a `#[global_allocator]` so
+//! games can use `alloc::vec::Vec`, `Box` and friends. It's off by default:
+//! every allocation costs a walk of the free list, and there's only 8KB of
+//! RAM to share with statics and the stack.
+//!
+//! Give the allocator some RAM before the first allocation:
+//!
+//! ```ignore
+//! extern crate alloc;
+//! use alloc::vec::Vec;
+//! use rom::sdk::heap;
+//!
+//! // everything between the statics and the stack, keeping 1KB for the stack
+//! unsafe { heap::init_free_ram(0x400) };
+//!
+//! let mut enemies = Vec::new();
+//! enemies.push(Enemy::new(10, 20));
+//! ```
+//!
+//! Or hand it a fixed region with [`init`]. Out of memory is a panic, like
+//! any other allocation failure in `no_std`.
+//!
+//! ## How It Works
+//!
+//! Fresh memory comes off the top of a bump pointer. Freed blocks go on a
+//! free list sorted by address, merging with their neighbours, and the next
+//! allocation takes the first block that fits before bumping again. Freeing
+//! the most recent allocation moves the bump pointer back down, so
+//! stack-like use never fragments.
+//!
+//! ## RAM Banks
+//!
+//! The RAM bank bits in [`BankFlags`](crate::scr::BankFlags) swap the whole
+//! `$0000-$1FFF` window, so the heap only exists while the bank that was
+//! selected at [`init`] is. Switch back before touching anything allocated,
+//! and don't allocate from interrupt handlers.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::Cell;
+use core::ptr;
+
+unsafe extern "C" {
+    unsafe static mut __data_end: u8;
+    unsafe static mut __bss_end: u8;
+}
+
+/// First byte the heap may use: above the zero page and the hardware stack
+const RAM_START: usize = 0x0200;
+/// End of the RAM window. The soft stack grows down from here.
+const RAM_END: usize = 0x2000;
+
+/// Header written into every freed block
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+/// Smallest block the free list can track. Smaller allocations round up.
+const MIN_BLOCK: usize = size_of::<FreeBlock>();
+const BLOCK_ALIGN: usize = align_of::<FreeBlock>();
+
+const fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+fn block_size(layout: Layout) -> usize {
+    align_up(layout.size().max(MIN_BLOCK), BLOCK_ALIGN)
+}
+
+/// Bump allocator with a free list, see the [module docs](self).
+struct Heap {
+    /// First byte that has never been handed out
+    next: Cell<usize>,
+    end: Cell<usize>,
+    /// Freed blocks below `next`, sorted by address
+    free: Cell<*mut FreeBlock>,
+}
+
+// One CPU, and the allocator isn't used from interrupts
+unsafe impl Sync for Heap {}
+
+#[global_allocator]
+static HEAP: Heap = Heap::empty();
+
+impl Heap {
+    /// A heap with no memory. Every allocation fails until [`init`].
+    const fn empty() -> Self {
+        Self {
+            next: Cell::new(0),
+            end: Cell::new(0),
+            free: Cell::new(ptr::null_mut()),
+        }
+    }
+
+    /// Bytes that haven't been handed out, counting the free list
+    fn available(&self) -> usize {
+        let mut total = self.end.get() - self.next.get();
+        let mut block = self.free.get();
+        while !block.is_null() {
+            unsafe {
+                total += (*block).size;
+                block = (*block).next;
+            }
+        }
+        total
+    }
+
+    /// Take `size` bytes at `align` from the first free block with room.
+    /// The part before the aligned address stays on the list; the part after
+    /// goes back on it.
+    unsafe fn take_free(&self, size: usize, align: usize) -> *mut u8 {
+        unsafe {
+            let mut link = self.free.as_ptr();
+            let mut block = *link;
+
+            while !block.is_null() {
+                let start = block as usize;
+                let addr = align_up(start, align);
+                let pad = addr - start;
+                let fits = pad + size <= (*block).size;
+
+                // leftovers too small for a header would be lost for good
+                let rest = (*block).size.saturating_sub(pad + size);
+                if fits && (pad == 0 || pad >= MIN_BLOCK) && (rest == 0 || rest >= MIN_BLOCK) {
+                    let after = if rest > 0 {
+                        let remainder = (addr + size) as *mut FreeBlock;
+                        remainder.write(FreeBlock { size: rest, next: (*block).next });
+                        remainder
+                    } else {
+                        (*block).next
+                    };
+
+                    if pad > 0 {
+                        (*block).size = pad;
+                        (*block).next = after;
+                    } else {
+                        *link = after;
+                    }
+                    return addr as *mut u8;
+                }
+
+                link = &raw mut (*block).next;
+                block = *link;
+            }
+
+            ptr::null_mut()
+        }
+    }
+
+    /// Put `size` bytes at `start` back, merging with the blocks around it.
+    /// A block that ends up touching the bump pointer moves it back instead.
+    unsafe fn release(&self, start: usize, size: usize) {
+        unsafe {
+            // find where it goes, remembering the link to the block before it
+            let mut prev_link: *mut *mut FreeBlock = ptr::null_mut();
+            let mut link = self.free.as_ptr();
+            let mut next = *link;
+            while !next.is_null() && (next as usize) < start {
+                prev_link = link;
+                link = &raw mut (*next).next;
+                next = *link;
+            }
+
+            let mut start = start;
+            let mut size = size;
+
+            if !next.is_null() && start + size == next as usize {
+                size += (*next).size;
+                next = (*next).next;
+            }
+
+            if !prev_link.is_null() {
+                let prev = *prev_link;
+                if prev as usize + (*prev).size == start {
+                    start = prev as usize;
+                    size += (*prev).size;
+                    link = prev_link;
+                }
+            }
+
+            if start + size == self.next.get() {
+                self.next.set(start);
+                *link = next;
+            } else {
+                let block = start as *mut FreeBlock;
+                block.write(FreeBlock { size, next });
+                *link = block;
+            }
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let size = block_size(layout);
+        let align = layout.align().max(BLOCK_ALIGN);
+
+        let reused = unsafe { self.take_free(size, align) };
+        if !reused.is_null() {
+            return reused;
+        }
+
+        let next = self.next.get();
+        let addr = align_up(next, align);
+        if addr + size > self.end.get() {
+            return ptr::null_mut();
+        }
+
+        self.next.set(addr + size);
+        if addr - next >= MIN_BLOCK {
+            unsafe { self.release(next, addr - next) };
+        }
+        addr as *mut u8
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.release(ptr as usize, block_size(layout)) };
+    }
+}
+
+/// Give the allocator `size` bytes of RAM starting at `start`. Call it once,
+/// before the first allocation.
+///
+/// # Safety
+/// Nothing else may use that RAM: not statics, not the soft stack.
+///
+/// # Panics
+/// If the region isn't inside `$0200-$1FFF`.
+pub unsafe fn init(start: usize, size: usize) {
+    let start = align_up(start, BLOCK_ALIGN);
+    let end = start.saturating_add(size);
+    assert!(start >= RAM_START && end <= RAM_END, "heap must be inside $0200-$1FFF");
+
+    HEAP.next.set(start);
+    HEAP.end.set(end);
+    HEAP.free.set(ptr::null_mut());
+}
+
+/// Give the allocator all the RAM between the end of the statics and the
+/// soft stack, leaving `stack_size` bytes for the stack.
+///
+/// # Safety
+/// The stack must never grow past `stack_size`, or it will overwrite heap
+/// memory.
+///
+/// # Panics
+/// If the statics and the stack leave no room.
+pub unsafe fn init_free_ram(stack_size: usize) {
+    let statics_end = (&raw const __data_end as usize).max(&raw const __bss_end as usize);
+    let stack_bottom = RAM_END.saturating_sub(stack_size);
+    assert!(statics_end < stack_bottom, "no RAM left for the heap");
+
+    unsafe { init(statics_end, stack_bottom - statics_end) };
+}
+
+/// Bytes left to allocate. They may be split between several free blocks,
+/// so one allocation this big can still fail.
+pub fn available() -> usize {
+    HEAP.available()
+}
@@ -1,5 +1,45 @@
+//! # Controllers
+//!
+//! [`GenesisGamepad`] reads a controller when asked, usually once a frame
+//! after `wait()`. A game whose frames sometimes run long reads less often
+//! than once per vblank, and a tap that starts and ends between two reads is
+//! lost.
+//!
+//! [`poll_in_vblank`] reads both ports in the vblank NMI instead, every
+//! frame however long the game's frames take, keeping the last
+//! [`BUFFER_FRAMES`] states. A [`BufferedGamepad`] takes what was polled
+//! since its last [`update`](BufferedGamepad::update), so `just_pressed` sees
+//! every press, and can look back for one made a few frames early, the
+//! input buffering that lets a jump pressed just before landing still count:
+//!
+//! ```ignore
+//! use rom::sdk::input::{poll_in_vblank, BufferedGamepad, Buttons};
+//!
+//! poll_in_vblank();
+//! let mut pad = BufferedGamepad::<1>::new();
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     pad.update();
+//!
+//!     // pressed up to 5 frames ago counts, but only once
+//!     if player.on_ground && pad.pressed_within(Buttons::A, 5) {
+//!         pad.consume(Buttons::A);
+//!         player.jump();
+//!     }
+//! }
+//! ```
+//!
+//! While polling, don't also call [`GenesisGamepad::read`]: the NMI can land
+//! between its reads and leave the controller's select line out of step.
+//! Polling pauses while [`save`](crate::save) has the NMI off.
+
+use core::ptr;
+
 use bit_field::BitField;
 
+use crate::irq;
+
 const GPR1: *const u8 = 0x2008 as *const u8;
 const GPR2: *const u8 = 0x2009 as *const u8;
 
@@ -27,7 +67,7 @@ pub enum Buttons {
 
 impl Buttons {
     /// good shit, const brained
-    const fn idx(&self) -> usize {
+    pub(crate) const fn idx(&self) -> usize {
         match self {
             Buttons::Start => 7,
             Buttons::A => 6,
@@ -63,18 +103,35 @@ impl<const PORT: u8> GenesisGamepad<PORT> {
     }
 }
 
+/// Port 1's buttons, one bit each as [`Buttons`] numbers them
+#[inline(always)]
+fn read_port1() -> u8 {
+    // Reset select by reading GPR2, then read GPR1 twice
+    let _ = read_gpr2();
+    let byte0 = read_gpr1();
+    let byte1 = read_gpr1();
+
+    // bits: start, a | c, b, up, down, left, right
+    ((!byte0 << 2) & 0b1100_0000) | (!byte1 & 0b0011_1111)
+}
+
+/// Port 2's buttons, one bit each as [`Buttons`] numbers them
+#[inline(always)]
+fn read_port2() -> u8 {
+    // Reset select by reading GPR1, then read GPR2 twice
+    let _ = read_gpr1();
+    let byte0 = read_gpr2();
+    let byte1 = read_gpr2();
+
+    ((!byte0 << 2) & 0b1100_0000) | (!byte1 & 0b0011_1111)
+}
+
 impl GenesisGamepad<1> {
     /// Read port 1 controller state.
     #[inline(always)]
     pub fn read(&mut self) {
-        // Reset select by reading GPR2, then read GPR1 twice
-        let _ = read_gpr2();
-        let byte0 = read_gpr1();
-        let byte1 = read_gpr1();
-
         self.buttons_last = self.buttons;
-        // bits: start, a | c, b, up, down, left, right
-        self.buttons = ((!byte0 << 2) & 0b1100_0000) | (!byte1 & 0b0011_1111);
+        self.buttons = read_port1();
     }
 }
 
@@ -82,14 +139,8 @@ impl GenesisGamepad<2> {
     /// Read port 2 controller state.
     #[inline(always)]
     pub fn read(&mut self) {
-        // Reset select by reading GPR1, then read GPR2 twice
-        let _ = read_gpr1();
-        let byte0 = read_gpr2();
-        let byte1 = read_gpr2();
-
         self.buttons_last = self.buttons;
-        // bits: start, a | c, b, up, down, left, right
-        self.buttons = ((!byte0 << 2) & 0b1100_0000) | (!byte1 & 0b0011_1111);
+        self.buttons = read_port2();
     }
 }
 
@@ -116,3 +167,176 @@ impl<const PORT: u8> GenesisGamepad<PORT> {
         !self.is_pressed(button) && self.was_pressed(button)
     }
 }
+
+/// Vblanks of controller states [`poll_in_vblank`] keeps. A power of two.
+pub const BUFFER_FRAMES: usize = 16;
+
+/// Furthest back a [`BufferedGamepad`] looks: the oldest state can be
+/// overwritten by the next NMI, and a press needs the state before it too
+const HISTORY: u8 = BUFFER_FRAMES as u8 - 2;
+
+/// What the NMI polled
+struct Polled {
+    /// Vblanks polled, wrapping. The newest state is at `polls - 1`.
+    polls: u8,
+    /// Both ports' buttons, by `poll % BUFFER_FRAMES`
+    states: [[u8; 2]; BUFFER_FRAMES],
+}
+
+static mut POLLED: Polled = Polled { polls: 0, states: [[0; 2]; BUFFER_FRAMES] };
+
+/// The vblank handler: read both ports into the next slot
+fn poll() {
+    let state = [read_port1(), read_port2()];
+    unsafe {
+        let polled = &raw mut POLLED;
+        let polls = (*polled).polls;
+        (*polled).states[polls as usize % BUFFER_FRAMES] = state;
+        // only counted once it's all written
+        ptr::write_volatile(&raw mut (*polled).polls, polls.wrapping_add(1));
+    }
+}
+
+/// Vblanks polled so far, wrapping
+#[inline]
+fn polls() -> u8 {
+    unsafe { ptr::read_volatile(&raw const POLLED.polls) }
+}
+
+/// `port`'s buttons at poll number `poll`. Ports other than 1 and 2 have
+/// nothing plugged in, so nothing held.
+#[inline]
+fn polled_state(poll: u8, port: u8) -> u8 {
+    let index = match port {
+        1 | 2 => port as usize - 1,
+        _ => return 0,
+    };
+    unsafe { ptr::read_volatile(&raw const POLLED.states[poll as usize % BUFFER_FRAMES][index]) }
+}
+
+/// Read both controllers at every vblank, for [`BufferedGamepad`]. Returns
+/// `false` if the [`irq::on_vblank`] table is full.
+pub fn poll_in_vblank() -> bool {
+    // don't poll twice a vblank if it's called again
+    irq::remove_vblank(poll);
+    irq::on_vblank(poll)
+}
+
+/// Stop polling at vblank. Returns `false` if it wasn't on.
+pub fn stop_polling() -> bool {
+    irq::remove_vblank(poll)
+}
+
+/// A controller read from what [`poll_in_vblank`] buffered. See the
+/// [module docs](self).
+pub struct BufferedGamepad<const PORT: u8> {
+    /// Buttons held at the latest poll
+    pub buttons: u8,
+    /// Buttons that went down at any poll since the last update
+    pub pressed: u8,
+    /// Buttons that came up at any poll since the last update
+    pub released: u8,
+    /// The poll count at the last update
+    seen: u8,
+    /// Per button, the poll count when it was [`consume`](Self::consume)d
+    consumed: [u8; 8],
+    /// Buttons with a `consumed` recent enough to matter
+    consumed_mask: u8,
+}
+
+impl<const PORT: u8> BufferedGamepad<PORT> {
+    /// Start from the latest poll; earlier presses aren't seen
+    pub fn new() -> Self {
+        let seen = polls();
+        Self {
+            buttons: polled_state(seen.wrapping_sub(1), PORT),
+            pressed: 0,
+            released: 0,
+            seen,
+            consumed: [0; 8],
+            consumed_mask: 0,
+        }
+    }
+
+    /// Take in everything polled since the last update. Call once a frame.
+    pub fn update(&mut self) {
+        let polls = polls();
+        let behind = polls.wrapping_sub(self.seen);
+        let new = behind.min(HISTORY);
+
+        // further behind than the buffer goes: start from the oldest state kept
+        let mut last = if behind > HISTORY { polled_state(polls.wrapping_sub(new + 1), PORT) } else { self.buttons };
+        self.pressed = 0;
+        self.released = 0;
+        for back in (1..=new).rev() {
+            let state = polled_state(polls.wrapping_sub(back), PORT);
+            self.pressed |= state & !last;
+            self.released |= last & !state;
+            last = state;
+        }
+
+        self.buttons = last;
+        self.seen = polls;
+
+        // a consume only hides presses still in the buffer
+        for (i, consumed) in self.consumed.iter().enumerate() {
+            if polls.wrapping_sub(*consumed) > HISTORY {
+                self.consumed_mask.set_bit(i, false);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_pressed(&self, button: Buttons) -> bool {
+        self.buttons.get_bit(button.idx())
+    }
+
+    /// Whether the button went down since the last update, however briefly
+    #[inline]
+    pub fn just_pressed(&self, button: Buttons) -> bool {
+        self.pressed.get_bit(button.idx())
+    }
+
+    /// Whether the button came up since the last update
+    #[inline]
+    pub fn just_released(&self, button: Buttons) -> bool {
+        self.released.get_bit(button.idx())
+    }
+
+    /// Whether the button went down in the last `frames` polls (at most
+    /// `BUFFER_FRAMES - 2`), and hasn't been consumed since
+    pub fn pressed_within(&self, button: Buttons, frames: u8) -> bool {
+        let idx = button.idx();
+        let mut frames = frames.min(HISTORY);
+        if self.consumed_mask.get_bit(idx) {
+            frames = frames.min(self.seen.wrapping_sub(self.consumed[idx]));
+        }
+
+        (1..=frames).any(|back| {
+            let poll = self.seen.wrapping_sub(back);
+            polled_state(poll, PORT).get_bit(idx) && !polled_state(poll.wrapping_sub(1), PORT).get_bit(idx)
+        })
+    }
+
+    /// Use up the button's buffered presses, so [`pressed_within`](Self::pressed_within)
+    /// doesn't act on the same one twice
+    pub fn consume(&mut self, button: Buttons) {
+        let idx = button.idx();
+        self.consumed[idx] = self.seen;
+        self.consumed_mask.set_bit(idx, true);
+    }
+
+    /// Polls in a row the button has been held for, up to `BUFFER_FRAMES - 2`
+    pub fn held_frames(&self, button: Buttons) -> u8 {
+        let idx = button.idx();
+        (1..=HISTORY)
+            .take_while(|&back| polled_state(self.seen.wrapping_sub(back), PORT).get_bit(idx))
+            .count() as u8
+    }
+}
+
+impl<const PORT: u8> Default for BufferedGamepad<PORT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
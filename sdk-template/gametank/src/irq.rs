@@ -0,0 +1,133 @@
+//! # Interrupt Hooks
+//!
+//! The vector table is fixed when the SDK is built, so games and SDK
+//! services (like [`audio::music`](crate::audio)) add their interrupt code
+//! here instead of editing the boot code. Handlers are plain functions, kept
+//! in zero page and called from the NMI and IRQ vectors in the order they
+//! were added.
+//!
+//! ```ignore
+//! use rom::sdk::irq;
+//!
+//! static mut FRAMES: u16 = 0;
+//!
+//! fn count_frame() {
+//!     unsafe { FRAMES = FRAMES.wrapping_add(1) };
+//! }
+//!
+//! irq::on_vblank(count_frame);
+//! ```
+//!
+//! ## Vblank
+//!
+//! [`on_vblank`] handlers run from the NMI at the start of every vblank,
+//! right after [`VBLANK`](crate::boot::VBLANK) is set, so `wait()` still
+//! works alongside them.
+//!
+//! ## IRQ
+//!
+//! [`on_irq`] handlers run from the IRQ vector, e.g. for VIA timers. Every
+//! handler runs on every IRQ, so each should check its own source and
+//! acknowledge it; an IRQ nobody acknowledges fires again straight away.
+//! IRQs also need enabling with
+//! [`enable_irq_handler`](crate::boot::enable_irq_handler). A `BRK` goes
+//! through the same vector.
+//!
+//! Handlers interrupt whatever the game is doing, so they should be short
+//! and only touch state that the game loop leaves alone while they might
+//! run. They see the ROM and RAM banks the game had selected. Registers are
+//! only saved when there are handlers, so an empty table costs a few cycles.
+
+use core::ptr;
+
+/// Most handlers each of [`on_vblank`] and [`on_irq`] can hold
+pub const MAX_HANDLERS: usize = 4;
+
+fn no_handler() {}
+
+/// Handlers for one vector. `interrupts.asm` reads `count` at offset 0.
+#[repr(C)]
+struct Handlers {
+    /// How many of `handlers` run. Handlers are only ever added or removed
+    /// past this count, so the interrupt never sees one half-written.
+    count: u8,
+    handlers: [fn(); MAX_HANDLERS],
+}
+
+impl Handlers {
+    const fn new() -> Self {
+        Self { count: 0, handlers: [no_handler; MAX_HANDLERS] }
+    }
+
+    fn run(&self) {
+        let count = unsafe { ptr::read_volatile(&self.count) } as usize;
+        for handler in &self.handlers[..count] {
+            handler();
+        }
+    }
+
+    fn add(&mut self, handler: fn()) -> bool {
+        let count = self.count as usize;
+        if count >= MAX_HANDLERS {
+            return false;
+        }
+        self.handlers[count] = handler;
+        unsafe { ptr::write_volatile(&mut self.count, count as u8 + 1) };
+        true
+    }
+
+    fn remove(&mut self, handler: fn()) -> bool {
+        let count = self.count as usize;
+        let Some(index) = self.handlers[..count].iter().position(|&h| ptr::fn_addr_eq(h, handler)) else {
+            return false;
+        };
+
+        // hide the handlers being moved from the interrupt, then bring them back
+        unsafe { ptr::write_volatile(&mut self.count, index as u8) };
+        self.handlers.copy_within(index + 1..count, index);
+        unsafe { ptr::write_volatile(&mut self.count, count as u8 - 1) };
+        true
+    }
+}
+
+#[unsafe(link_section = ".data.zp")]
+#[unsafe(no_mangle)]
+static mut VBLANK_HANDLERS: Handlers = Handlers::new();
+
+#[unsafe(link_section = ".data.zp")]
+#[unsafe(no_mangle)]
+static mut IRQ_HANDLERS: Handlers = Handlers::new();
+
+/// Called by the NMI in `interrupts.asm`, with registers saved
+#[unsafe(no_mangle)]
+extern "C" fn run_vblank_handlers() {
+    unsafe { VBLANK_HANDLERS.run() }
+}
+
+/// Called by the IRQ handler in `interrupts.asm`, with registers saved
+#[unsafe(no_mangle)]
+extern "C" fn run_irq_handlers() {
+    unsafe { IRQ_HANDLERS.run() }
+}
+
+/// Run `handler` at the start of every vblank until it's removed. Returns
+/// `false` if [`MAX_HANDLERS`] are already installed.
+pub fn on_vblank(handler: fn()) -> bool {
+    unsafe { VBLANK_HANDLERS.add(handler) }
+}
+
+/// Stop running `handler` at vblank. Returns `false` if it wasn't installed.
+pub fn remove_vblank(handler: fn()) -> bool {
+    unsafe { VBLANK_HANDLERS.remove(handler) }
+}
+
+/// Run `handler` on every IRQ until it's removed. Returns `false` if
+/// [`MAX_HANDLERS`] are already installed.
+pub fn on_irq(handler: fn()) -> bool {
+    unsafe { IRQ_HANDLERS.add(handler) }
+}
+
+/// Stop running `handler` on IRQs. Returns `false` if it wasn't installed.
+pub fn remove_irq(handler: fn()) -> bool {
+    unsafe { IRQ_HANDLERS.remove(handler) }
+}
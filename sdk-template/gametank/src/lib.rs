@@ -75,6 +75,9 @@
 //! // Copy a sprite from sprite RAM to the screen
 //! blitter.draw_sprite(&mut console.sc, src_x, src_y, dst_x, dst_y, width, height);
 //!
+//! // Or flipped, transparent, or tiled, with DrawOptions
+//! blitter.draw_sprite_with(src_x, src_y, dst_x, dst_y, width, height, DrawOptions::DEFAULT.flip_x());
+//!
 //! // IMPORTANT: Wait before starting another blit or accessing video memory
 //! blitter.wait_blit();
 //! ```
@@ -114,17 +117,20 @@
 //! sprite_mem.bytes()[..SPRITES.len()].copy_from_slice(SPRITES);
 //! ```
 //!
+//! For anything bigger than one quadrant, or outside page 0, use
+//! [`SpriteSheet`](gfx::SpriteSheet) to handle page and quadrant selection.
+//! [`gfx::Streamer`] loads sheets a chunk per frame instead, so a level's
+//! tiles can come in without stopping the game.
+//!
 //! ## Audio
 //!
 //! The GameTank has a dedicated audio coprocessor. Initialize it with firmware:
 //!
 //! ```ignore
-//! use rom::sdk::audio::{FIRMWARE, voices, MidiNote, WAVETABLE};
+//! use rom::sdk::audio::{init_audio, voices, MidiNote, WAVETABLE};
 //!
-//! // Load audio firmware (do this once at startup)
-//! console.sc.set_audio(0);  // Disable while loading
-//! console.audio.copy_from_slice(FIRMWARE);
-//! console.sc.set_audio(0xFF);  // Enable at ~14kHz
+//! // Load and start the audio firmware (do this once at startup)
+//! init_audio(console);
 //!
 //! // Play notes using the wavetable synth
 //! let v = voices();
@@ -148,6 +154,107 @@
 //! // Now LEVEL_DATA is accessible at its address
 //! ```
 //!
+//! Sections named `.rodata.auto.<name>` go in whichever bank has room; see
+//! [`banking`] for looking up where they landed.
+//!
+//! Assets listed in `assets.toml` are packed into banks by `gtrom build`, which
+//! generates `src/assets_gen.rs` with a constant for each; see [`assets`].
+//! Big title screens and maps can be stored compressed and unpacked at load
+//! time with [`compress`].
+//!
+//! ## Copying Memory
+//!
+//! [`mem`] copies and fills RAM, sprite RAM and the framebuffer faster than
+//! `copy_from_slice` and `fill`, a page at a time.
+//!
+//! ## Saving
+//!
+//! [`save`] keeps versioned, checksummed saves in the cartridge's flash,
+//! spreading the writes so it wears evenly:
+//!
+//! ```ignore
+//! let mut saves = SaveBank::open();
+//! let progress: Progress = saves.load_or_default(0);
+//! saves.save(&mut console, 0, &progress).ok();
+//! ```
+//!
+//! ## Collision
+//!
+//! [`math`] has 8-bit [`Rect8`](math::Rect8) and [`Point8`](math::Point8)
+//! types for hit boxes, and [`Fx8`](math::Fx8) 8.8 fixed point for sub-pixel
+//! movement, all written to stay small on the 6502. [`math::trig`] turns
+//! angles into velocities and back, for aiming and rotation.
+//!
+//! ## Objects
+//!
+//! [`pool::Pool`] keeps a fixed number of bullets or enemies without the
+//! heap, handing out handles that go stale when their object is removed.
+//!
+//! ## Spreading Work Across Frames
+//!
+//! [`scheduler::Scheduler`] steps tasks like decompression or pathfinding
+//! in whatever time each frame has left, by priority, carrying unfinished
+//! work over to the next frame.
+//!
+//! ## Testing
+//!
+//! `gtrom test` runs the ROM headless and fails the run if it crashes. Use
+//! [`testing`] to report pass or fail from the ROM itself, and to check
+//! frames against golden checksums.
+//!
+//! ## Expansion Port
+//!
+//! [`peripherals`] has drivers for link cables and other add-ons, and a
+//! trait for writing your own.
+//!
+//! ## Debug Output
+//!
+//! [`debug::log!`] prints a line to the emulator, like `println!`, and
+//! costs nothing on hardware beyond the formatting.
+//!
+//! ## Profiling
+//!
+//! Enable the `profiler` feature to time parts of each frame in CPU cycles
+//! and draw them as a bar on screen. See `profiler`.
+//!
+//! The `watchdog` feature catches frames that miss their vblank, flashing a
+//! border on screen and counting them for `gtrom test`. See `watchdog`.
+//!
+//! ## Menus
+//!
+//! [`ui`] has pause menus with toggles and choices, and dialogue boxes,
+//! drawn with the built-in font in [`gfx::text`], with double-height titles.
+//!
+//! ## Controllers
+//!
+//! [`input::GenesisGamepad`] reads a controller when asked. Games whose
+//! frames can run long can [`poll`](input::poll_in_vblank) in the vblank
+//! NMI instead, and read presses back with [`input::BufferedGamepad`],
+//! which never misses one and can look a few frames back for input
+//! buffering.
+//!
+//! ## Interrupts
+//!
+//! Add functions to run at every vblank or IRQ with [`irq::on_vblank`] and
+//! [`irq::on_irq`], rather than changing the vector table.
+//!
+//! ## Zero Page
+//!
+//! Statics declared in [`zeropage!`] live in zero page, which is quicker to
+//! reach. There's little of it, so `gtrom build` lists what's there and
+//! fails when it's over budget.
+//!
+//! ## Heap
+//!
+//! Enable the `alloc` feature to use `Vec` and `Box`. See `heap` for setting
+//! aside RAM for it.
+//!
+//! ## ROM Info
+//!
+//! [`gametank_rom!`] records the game's title, author and version in the
+//! ROM, for the flasher and emulator to show, and can put them on a splash
+//! screen before `main`. See [`rom_info`].
+//!
 //! ## Hardware Overview
 //!
 //! | Feature | Spec |
@@ -166,6 +273,27 @@ pub mod via;
 pub mod video_dma;
 pub mod audio;
 pub mod boot;
+pub mod irq;
 pub mod input;
 pub mod console;
+pub mod gfx;
+pub mod banking;
+pub mod assets;
+pub mod compress;
+pub mod mem;
+pub mod math;
+pub mod pool;
+pub mod scheduler;
+pub mod testing;
+pub mod save;
+pub mod peripherals;
+pub mod ui;
+pub mod rom_info;
+pub mod debug;
+#[cfg(feature = "alloc")]
+pub mod heap;
+#[cfg(feature = "profiler")]
+pub mod profiler;
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
 
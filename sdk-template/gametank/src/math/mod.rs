@@ -0,0 +1,329 @@
+//! # Math
+//!
+//! Points, rectangles and fixed-point numbers for 8-bit coordinates.
+//!
+//! The 6502 has no multiply and 8-bit registers, so every bit of width costs
+//! code. Naive `i32` math or `x + w > other.x` on `u16`s balloons into long
+//! add-with-carry chains; everything here stays in `u8`, with 16 bits only
+//! where the value itself needs them.
+//!
+//! ```ignore
+//! use rom::sdk::math::{Fx8, Point8, Rect8};
+//!
+//! let mut x = Fx8::from_int(20);
+//! let speed = Fx8::from_bits(0x0180); // 1.5 pixels per frame
+//! x += speed;
+//!
+//! let player = Rect8::new(x.int() as u8, 40, 8, 16);
+//! let coin = Rect8::new(30, 44, 4, 4);
+//! if player.overlaps(&coin) {
+//!     // ...
+//! }
+//! ```
+//!
+//! Rectangles may not wrap past 255: `x + w` has to fit in a `u8` for the
+//! tests to be right.
+//!
+//! [`trig`] has sine tables and angles for rotating and aiming.
+
+pub mod trig;
+
+use core::ops::{Add, AddAssign, Neg, Shr, Sub, SubAssign};
+
+/// A point in 8-bit screen space.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Point8 {
+    pub x: u8,
+    pub y: u8,
+}
+
+impl Point8 {
+    pub const fn new(x: u8, y: u8) -> Self {
+        Self { x, y }
+    }
+
+    /// Move by a signed amount, wrapping at the edges of the 256x256 space.
+    #[inline]
+    pub const fn offset(self, dx: i8, dy: i8) -> Self {
+        Self {
+            x: self.x.wrapping_add_signed(dx),
+            y: self.y.wrapping_add_signed(dy),
+        }
+    }
+
+    /// `|dx| + |dy|`, capped at 255.
+    #[inline]
+    pub const fn manhattan(self, other: Point8) -> u8 {
+        self.x.abs_diff(other.x).saturating_add(self.y.abs_diff(other.y))
+    }
+
+    /// The larger of `|dx|` and `|dy|`: how many 8-way steps apart the
+    /// points are.
+    #[inline]
+    pub const fn chebyshev(self, other: Point8) -> u8 {
+        let dx = self.x.abs_diff(other.x);
+        let dy = self.y.abs_diff(other.y);
+        if dx > dy { dx } else { dy }
+    }
+
+    /// Straight-line distance, approximated as `max + 3/8 * min` (within
+    /// about 7%), capped at 255. Shifts and adds only.
+    #[inline]
+    pub const fn distance(self, other: Point8) -> u8 {
+        let dx = self.x.abs_diff(other.x);
+        let dy = self.y.abs_diff(other.y);
+        let (max, min) = if dx > dy { (dx, dy) } else { (dy, dx) };
+        max.saturating_add((min >> 2) + (min >> 3))
+    }
+
+    /// Whether `other` is within `radius` of this point, exactly.
+    ///
+    /// Squares the deltas, which costs two software multiplies; prefer
+    /// [`distance`](Self::distance) where close enough is fine.
+    #[inline]
+    pub fn within(self, other: Point8, radius: u8) -> bool {
+        let dx = self.x.abs_diff(other.x);
+        let dy = self.y.abs_diff(other.y);
+        // cheap rejection before multiplying
+        if dx > radius || dy > radius {
+            return false;
+        }
+        let square = |v: u8| v as u16 * v as u16;
+        square(dx).saturating_add(square(dy)) <= square(radius)
+    }
+}
+
+/// An axis-aligned rectangle in 8-bit screen space.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect8 {
+    pub x: u8,
+    pub y: u8,
+    pub w: u8,
+    pub h: u8,
+}
+
+impl Rect8 {
+    pub const fn new(x: u8, y: u8, w: u8, h: u8) -> Self {
+        Self { x, y, w, h }
+    }
+
+    /// A `w` by `h` rectangle with its top-left corner at `at`.
+    pub const fn at(at: Point8, w: u8, h: u8) -> Self {
+        Self { x: at.x, y: at.y, w, h }
+    }
+
+    #[inline]
+    pub const fn position(&self) -> Point8 {
+        Point8 { x: self.x, y: self.y }
+    }
+
+    /// First column past the right edge.
+    #[inline]
+    pub const fn right(&self) -> u8 {
+        self.x.saturating_add(self.w)
+    }
+
+    /// First row past the bottom edge.
+    #[inline]
+    pub const fn bottom(&self) -> u8 {
+        self.y.saturating_add(self.h)
+    }
+
+    /// The middle, rounded toward the top-left.
+    #[inline]
+    pub const fn center(&self) -> Point8 {
+        Point8 { x: self.x + self.w / 2, y: self.y + self.h / 2 }
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.w == 0 || self.h == 0
+    }
+
+    /// Move by a signed amount, wrapping at the edges of the 256x256 space.
+    #[inline]
+    pub const fn offset(self, dx: i8, dy: i8) -> Self {
+        Self {
+            x: self.x.wrapping_add_signed(dx),
+            y: self.y.wrapping_add_signed(dy),
+            ..self
+        }
+    }
+
+    /// Whether `p` is inside. The right and bottom edges are outside.
+    #[inline]
+    pub const fn contains(&self, p: Point8) -> bool {
+        // one wrapping subtract and compare per axis: points to the left
+        // wrap around to big numbers
+        p.x.wrapping_sub(self.x) < self.w && p.y.wrapping_sub(self.y) < self.h
+    }
+
+    /// Whether the two rectangles share any pixels. A zero-size rectangle
+    /// still counts if it starts inside the other.
+    #[inline]
+    pub const fn overlaps(&self, other: &Rect8) -> bool {
+        Self::spans_overlap(self.x, self.w, other.x, other.w)
+            && Self::spans_overlap(self.y, self.h, other.y, other.h)
+    }
+
+    /// Whether `[a, a + a_len)` and `[b, b + b_len)` intersect: one of them
+    /// starts inside the other.
+    #[inline(always)]
+    const fn spans_overlap(a: u8, a_len: u8, b: u8, b_len: u8) -> bool {
+        b.wrapping_sub(a) < a_len || a.wrapping_sub(b) < b_len
+    }
+
+    /// The overlapping area, if there is one.
+    pub fn intersection(&self, other: &Rect8) -> Option<Rect8> {
+        if self.is_empty() || other.is_empty() || !self.overlaps(other) {
+            return None;
+        }
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        Some(Rect8 {
+            x,
+            y,
+            w: self.right().min(other.right()) - x,
+            h: self.bottom().min(other.bottom()) - y,
+        })
+    }
+}
+
+/// A signed 8.8 fixed-point number: -128 to just under 128, in steps of
+/// 1/256. Sub-pixel positions and velocities fit in one of these, and the
+/// whole-pixel part is the high byte.
+///
+/// Arithmetic wraps rather than panicking. There's no general multiply,
+/// which would need a 32-bit product; [`mul_frac`](Self::mul_frac) covers
+/// scaling by a fraction, as in friction and damping.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct Fx8(i16);
+
+impl Fx8 {
+    pub const ZERO: Fx8 = Fx8(0);
+    pub const ONE: Fx8 = Fx8(0x0100);
+    pub const HALF: Fx8 = Fx8(0x0080);
+    pub const MIN: Fx8 = Fx8(i16::MIN);
+    pub const MAX: Fx8 = Fx8(i16::MAX);
+
+    /// From the raw 8.8 value, e.g. `0x0180` for 1.5.
+    #[inline(always)]
+    pub const fn from_bits(bits: i16) -> Self {
+        Self(bits)
+    }
+
+    #[inline(always)]
+    pub const fn to_bits(self) -> i16 {
+        self.0
+    }
+
+    #[inline(always)]
+    pub const fn from_int(n: i8) -> Self {
+        Self((n as i16) << 8)
+    }
+
+    /// The whole part, rounded down (toward negative infinity).
+    #[inline(always)]
+    pub const fn int(self) -> i8 {
+        (self.0 >> 8) as i8
+    }
+
+    /// The fractional part, in 256ths.
+    #[inline(always)]
+    pub const fn frac(self) -> u8 {
+        self.0 as u8
+    }
+
+    /// The nearest whole number, halves rounding up.
+    #[inline]
+    pub const fn round(self) -> i8 {
+        (self.0.wrapping_add(0x80) >> 8) as i8
+    }
+
+    #[inline]
+    pub const fn abs(self) -> Self {
+        Self(self.0.wrapping_abs())
+    }
+
+    #[inline]
+    pub const fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    /// Multiply by `f / 256`, e.g. `mul_frac(240)` to lose 1/16 of a
+    /// velocity each frame. Rounds toward zero.
+    ///
+    /// Uses two 8x8 multiplies on the magnitude, so nothing is wider than
+    /// 16 bits.
+    #[inline]
+    pub fn mul_frac(self, f: u8) -> Self {
+        let magnitude = self.0.unsigned_abs();
+        let hi = (magnitude >> 8) as u8;
+        let lo = magnitude as u8;
+        let scaled = (hi as u16 * f as u16) + ((lo as u16 * f as u16) >> 8);
+        if self.0 < 0 {
+            Self((scaled as i16).wrapping_neg())
+        } else {
+            Self(scaled as i16)
+        }
+    }
+}
+
+impl From<i8> for Fx8 {
+    #[inline(always)]
+    fn from(n: i8) -> Self {
+        Self::from_int(n)
+    }
+}
+
+impl Add for Fx8 {
+    type Output = Fx8;
+
+    #[inline(always)]
+    fn add(self, rhs: Fx8) -> Fx8 {
+        Fx8(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl Sub for Fx8 {
+    type Output = Fx8;
+
+    #[inline(always)]
+    fn sub(self, rhs: Fx8) -> Fx8 {
+        Fx8(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl Neg for Fx8 {
+    type Output = Fx8;
+
+    #[inline(always)]
+    fn neg(self) -> Fx8 {
+        Fx8(self.0.wrapping_neg())
+    }
+}
+
+impl AddAssign for Fx8 {
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: Fx8) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Fx8 {
+    #[inline(always)]
+    fn sub_assign(&mut self, rhs: Fx8) {
+        *self = *self - rhs;
+    }
+}
+
+/// Divide by a power of two, rounding down.
+impl Shr<u8> for Fx8 {
+    type Output = Fx8;
+
+    #[inline(always)]
+    fn shr(self, rhs: u8) -> Fx8 {
+        Fx8(self.0 >> rhs)
+    }
+}
@@ -0,0 +1,132 @@
+//! # Trigonometry
+//!
+//! Sine tables and angles for rotating and aiming, without floats.
+//!
+//! Angles are a `u8`, 256 to the turn, so they wrap for free: 64 is a
+//! quarter turn, 128 a half. Angle 0 points right, and since screen `y`
+//! grows downward, 64 points down and increasing angles turn clockwise.
+//!
+//! [`SIN`] is built at compile time, 127 standing for 1.0; cosine reads it
+//! a quarter turn ahead rather than taking another 256 bytes.
+//!
+//! ```ignore
+//! use rom::sdk::math::{trig, Fx8};
+//!
+//! // fire at the player, 1.5 pixels per frame
+//! let angle = trig::atan2_8(player_y - y, player_x - x);
+//! let (vx, vy) = trig::polar(Fx8::from_bits(0x0180), angle);
+//!
+//! // spin a star around the middle of the screen
+//! let (sx, sy) = trig::rotate_point(star_x, star_y, frame);
+//! ```
+//!
+//! Scaling by the table is two 8x8 multiplies, through
+//! [`Fx8::mul_frac`], so lengths come out about 1% short.
+
+use super::Fx8;
+
+/// `sin` of each angle, times 127
+pub static SIN: [i8; 256] = build_sine();
+
+/// `atan(i / 64)` in angle units, for the first octant
+static ATAN: [u8; 65] = [
+    0, 1, 1, 2, 3, 3, 4, 4, 5, 6, 6, 7, 8, 8, 9, 9,
+    10, 11, 11, 12, 12, 13, 13, 14, 15, 15, 16, 16, 17, 17, 18, 18,
+    19, 19, 20, 20, 21, 21, 22, 22, 23, 23, 24, 24, 25, 25, 25, 26,
+    26, 27, 27, 27, 28, 28, 29, 29, 29, 30, 30, 30, 31, 31, 31, 32,
+    32,
+];
+
+/// `sin(i / 256 turns) * 127` for `i` up to a quarter turn, from a Taylor
+/// series in 2.30 fixed point
+const fn quarter_sine(i: i64) -> i8 {
+    const ONE: i64 = 1 << 30;
+    const PI: i64 = 3_373_259_426;
+
+    let x = i * PI / 128;
+    let mut term = x;
+    let mut sum = x;
+    let mut k = 1;
+    while k < 7 {
+        term = -(term * x / ONE) * x / ONE / ((2 * k) * (2 * k + 1));
+        sum += term;
+        k += 1;
+    }
+    ((sum * 127 + ONE / 2) / ONE) as i8
+}
+
+const fn build_sine() -> [i8; 256] {
+    let mut table = [0i8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let half = i % 128;
+        let quarter = if half <= 64 { half } else { 128 - half };
+        let s = quarter_sine(quarter as i64);
+        table[i] = if i < 128 { s } else { -s };
+        i += 1;
+    }
+    table
+}
+
+/// `sin(angle)`, times 127
+#[inline(always)]
+pub fn sin(angle: u8) -> i8 {
+    SIN[angle as usize]
+}
+
+/// `cos(angle)`, times 127
+#[inline(always)]
+pub fn cos(angle: u8) -> i8 {
+    SIN[angle.wrapping_add(64) as usize]
+}
+
+/// `v * s / 128`
+#[inline]
+fn scale(v: Fx8, s: i8) -> Fx8 {
+    let scaled = v.mul_frac(s.unsigned_abs() << 1);
+    if s < 0 { -scaled } else { scaled }
+}
+
+/// The vector `length` long pointing at `angle`, e.g. a bullet's velocity.
+#[inline]
+pub fn polar(length: Fx8, angle: u8) -> (Fx8, Fx8) {
+    (scale(length, cos(angle)), scale(length, sin(angle)))
+}
+
+/// Rotate `(x, y)` about the origin by `angle`, clockwise on screen.
+/// Offset by a center first to rotate around something else.
+pub fn rotate_point(x: Fx8, y: Fx8, angle: u8) -> (Fx8, Fx8) {
+    let (s, c) = (sin(angle), cos(angle));
+    (scale(x, c) - scale(y, s), scale(x, s) + scale(y, c))
+}
+
+/// The angle from the origin to `(x, y)`, to within a step or so. Like
+/// `f32::atan2`, `y` comes first; `(0, 0)` gives 0.
+///
+/// Works for screen deltas as well as the bits of [`Fx8`]s, since only the
+/// ratio matters. One 16-by-8 divide.
+pub fn atan2_8(y: i16, x: i16) -> u8 {
+    let (mut ax, mut ay) = (x.unsigned_abs(), y.unsigned_abs());
+    if ax == 0 && ay == 0 {
+        return 0;
+    }
+    // only the ratio matters, so bring both into a byte
+    while ax > 0xFF || ay > 0xFF {
+        ax >>= 1;
+        ay >>= 1;
+    }
+
+    // within the first quadrant, from the octant on either side of 45°
+    let angle = if ay <= ax {
+        ATAN[(ay * 64 / ax) as usize]
+    } else {
+        64 - ATAN[(ax * 64 / ay) as usize]
+    };
+
+    match (x < 0, y < 0) {
+        (false, false) => angle,
+        (true, false) => 128 - angle,
+        (true, true) => 128 + angle,
+        (false, true) => angle.wrapping_neg(),
+    }
+}
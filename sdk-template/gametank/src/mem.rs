@@ -0,0 +1,139 @@
+//! # Copying and Filling Memory
+//!
+//! Fast [`copy`] and [`fill`] for RAM, and versions for video memory that
+//! take its guard, so they can't run while the blitter has the bus.
+//!
+//! `copy_from_slice` and `fill` on mos go through the general-purpose
+//! `memcpy` and `memset`, which count and step their pointers in 16 bits for
+//! every byte. These work a 256-byte page at a time with an 8-bit index
+//! instead, eight bytes per trip around the loop, and don't modify their own
+//! code, so they run from ROM.
+//!
+//! ```ignore
+//! use rom::sdk::mem;
+//!
+//! // Clear a tile buffer
+//! mem::fill(&mut tiles, 0);
+//!
+//! // Load sprites
+//! if let Some(mut sm) = console.dma.sprite_mem(&mut console.video_flags) {
+//!     mem::copy_to_sprite_mem(&mut sm, 0, &MY_SPRITES);
+//! }
+//!
+//! // Clear the screen without the blitter
+//! if let Some(mut fb) = console.dma.framebuffers(&mut console.video_flags) {
+//!     mem::fill_framebuffer(&mut fb, !BLACK);
+//! }
+//! ```
+
+use crate::video_dma::{framebuffers::FramebuffersGuard, spritemem::SpriteMemGuard};
+
+/// Repeat a statement for offsets 0 to 7
+macro_rules! unroll8 {
+    ($k:ident => $body:expr) => {{
+        { let $k = 0u8; $body; }
+        { let $k = 1u8; $body; }
+        { let $k = 2u8; $body; }
+        { let $k = 3u8; $body; }
+        { let $k = 4u8; $body; }
+        { let $k = 5u8; $body; }
+        { let $k = 6u8; $body; }
+        { let $k = 7u8; $body; }
+    }};
+}
+
+/// Copy `len` bytes, at most 256 (0 meaning 256)
+///
+/// # Safety
+///
+/// `src` and `dst` must each have `len` bytes, and not overlap.
+#[inline(always)]
+unsafe fn copy_run(dst: *mut u8, src: *const u8, len: u8) {
+    let mut i = 0u8;
+    // eight at a time, then what's left; `len` 0 is a whole page, where `i`
+    // wraps back to 0 at the end
+    while len == 0 || len - i >= 8 {
+        unroll8!(k => unsafe { *dst.add((i + k) as usize) = *src.add((i + k) as usize) });
+        i = i.wrapping_add(8);
+        if i == 0 || i == len {
+            return;
+        }
+    }
+    while i != len {
+        unsafe { *dst.add(i as usize) = *src.add(i as usize) };
+        i += 1;
+    }
+}
+
+/// Fill `len` bytes, at most 256 (0 meaning 256)
+///
+/// # Safety
+///
+/// `dst` must have `len` bytes.
+#[inline(always)]
+unsafe fn fill_run(dst: *mut u8, value: u8, len: u8) {
+    let mut i = 0u8;
+    while len == 0 || len - i >= 8 {
+        unroll8!(k => unsafe { *dst.add((i + k) as usize) = value });
+        i = i.wrapping_add(8);
+        if i == 0 || i == len {
+            return;
+        }
+    }
+    while i != len {
+        unsafe { *dst.add(i as usize) = value };
+        i += 1;
+    }
+}
+
+/// Copy all of `src` into `dst`, which must be the same length (like
+/// `copy_from_slice`)
+pub fn copy(dst: &mut [u8], src: &[u8]) {
+    assert!(dst.len() == src.len(), "mem::copy needs slices of the same length");
+
+    let (dst, src, len) = (dst.as_mut_ptr(), src.as_ptr(), src.len());
+    let pages = len / 256;
+    for page in 0..pages {
+        // a whole page
+        unsafe { copy_run(dst.add(page * 256), src.add(page * 256), 0) };
+    }
+    if len % 256 != 0 {
+        unsafe { copy_run(dst.add(pages * 256), src.add(pages * 256), len as u8) };
+    }
+}
+
+/// Set every byte of `dst` to `value`
+pub fn fill(dst: &mut [u8], value: u8) {
+    let (dst, len) = (dst.as_mut_ptr(), dst.len());
+    let pages = len / 256;
+    for page in 0..pages {
+        unsafe { fill_run(dst.add(page * 256), value, 0) };
+    }
+    if len % 256 != 0 {
+        unsafe { fill_run(dst.add(pages * 256), value, len as u8) };
+    }
+}
+
+/// Copy `src` into the framebuffer being drawn to, starting `offset` bytes
+/// in (`y * 128 + x`). Panics if it runs past the end.
+pub fn copy_to_framebuffer(fb: &mut FramebuffersGuard, offset: u16, src: &[u8]) {
+    let offset = offset as usize;
+    copy(&mut fb.bytes()[offset..offset + src.len()], src);
+}
+
+/// Set the whole framebuffer being drawn to to `color`
+pub fn fill_framebuffer(fb: &mut FramebuffersGuard, color: u8) {
+    fill(fb.bytes(), color);
+}
+
+/// Copy `src` into the selected sprite RAM quadrant, starting `offset` bytes
+/// in. Panics if it runs past the end.
+pub fn copy_to_sprite_mem(sm: &mut SpriteMemGuard, offset: u16, src: &[u8]) {
+    let offset = offset as usize;
+    copy(&mut sm.bytes()[offset..offset + src.len()], src);
+}
+
+/// Set the whole selected sprite RAM quadrant to `color`
+pub fn fill_sprite_mem(sm: &mut SpriteMemGuard, color: u8) {
+    fill(sm.bytes(), color);
+}
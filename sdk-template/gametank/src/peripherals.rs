@@ -0,0 +1,299 @@
+//! # Peripherals
+//!
+//! Drivers for add-ons on the expansion port, so a link cable, serial
+//! adapter or rumble pack plugs into a game the same way.
+//!
+//! The expansion port carries the VIA's port B and its CB1/CB2 lines: eight
+//! pins that are each an input or an output, an input that flags edges, and
+//! an output that can pulse whenever port B is written. [`ExpansionPort`]
+//! wraps those registers, and a driver builds on it by implementing
+//! [`Peripheral`], plus [`Link`] if it moves bytes.
+//!
+//! ```ignore
+//! use rom::sdk::peripherals::{ExpansionPort, Link, ParallelLink};
+//!
+//! let mut link = ParallelLink::new(unsafe { ExpansionPort::new() });
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     link.send(my_buttons);
+//!     if let Some(theirs) = link.recv() {
+//!         other_player.buttons = theirs;
+//!     }
+//! }
+//! ```
+//!
+//! Write game code against `impl Link` and it can be handed a [`Loopback`]
+//! instead, to test without a second console or cable.
+//!
+//! ## Writing a Driver
+//!
+//! A driver owns the port, sets up its pins in its constructor, and does
+//! any per-frame work in [`Peripheral::poll`]. The registers and bits it
+//! needs are in [`reg`]. [`Via::profiler_start`] and
+//! [`Via::profiler_end`] write port B too, so don't mix them with a
+//! peripheral.
+
+use crate::via::Via;
+
+/// Register bits for the expansion port's side of the VIA
+pub mod reg {
+    /// ACR: hold port B's inputs from the last CB1 edge until it's read
+    pub const ACR_PB_LATCH: u8 = 0b0000_0010;
+    /// ACR: the shift register's mode
+    pub const ACR_SHIFT_MASK: u8 = 0b0001_1100;
+    /// ACR: shift in, clocked by CB1
+    pub const ACR_SHIFT_IN_CB1: u8 = 0b0000_1100;
+    /// ACR: shift out on CB2 at the CPU clock
+    pub const ACR_SHIFT_OUT_PHI2: u8 = 0b0001_1000;
+    /// ACR: shift out on CB2, clocked by CB1
+    pub const ACR_SHIFT_OUT_CB1: u8 = 0b0001_1100;
+
+    /// PCR: flag CB1's rising edges rather than falling
+    pub const PCR_CB1_RISING: u8 = 0b0001_0000;
+    /// PCR: CB2's mode
+    pub const PCR_CB2_MASK: u8 = 0b1110_0000;
+    /// PCR: CB2 is an input
+    pub const PCR_CB2_INPUT: u8 = 0b0000_0000;
+    /// PCR: CB2 goes low for a cycle after each port B write
+    pub const PCR_CB2_PULSE: u8 = 0b1010_0000;
+    /// PCR: CB2 held low
+    pub const PCR_CB2_LOW: u8 = 0b1100_0000;
+    /// PCR: CB2 held high
+    pub const PCR_CB2_HIGH: u8 = 0b1110_0000;
+
+    /// IFR/IER: the shift register finished a byte
+    pub const IRQ_SR: u8 = 0b0000_0100;
+    /// IFR/IER: CB2 saw an edge, while it's an input
+    pub const IRQ_CB2: u8 = 0b0000_1000;
+    /// IFR/IER: CB1 saw an edge. Reading or writing port B clears it.
+    pub const IRQ_CB1: u8 = 0b0001_0000;
+}
+
+/// The VIA's port B and CB1/CB2, as wired to the expansion port
+pub struct ExpansionPort {
+    via: &'static mut Via,
+}
+
+impl ExpansionPort {
+    /// # Safety
+    ///
+    /// Only one driver should have the port at a time. ROM banking uses
+    /// port A and the profiler uses timer 1, so they don't get in the way.
+    pub unsafe fn new() -> Self {
+        Self { via: unsafe { Via::new() } }
+    }
+
+    /// Drive the pins set in `outputs`, and read the rest
+    #[inline(always)]
+    pub fn set_outputs(&mut self, outputs: u8) {
+        unsafe { self.via.ddrb.write(outputs) };
+    }
+
+    /// Set the output pins. Pulses CB2 in [`reg::PCR_CB2_PULSE`] mode.
+    #[inline(always)]
+    pub fn write(&mut self, value: u8) {
+        unsafe { self.via.iorb.write(value) };
+    }
+
+    /// The input pins, or what they were at the last CB1 edge when
+    /// latching, along with the output pins' values
+    #[inline(always)]
+    pub fn read(&mut self) -> u8 {
+        self.via.iorb.read()
+    }
+
+    /// Set CB2's mode, one of the `reg::PCR_CB2_*` values
+    pub fn set_cb2(&mut self, mode: u8) {
+        unsafe { self.via.pcr.modify(|pcr| (pcr & !reg::PCR_CB2_MASK) | (mode & reg::PCR_CB2_MASK)) };
+    }
+
+    /// Whether CB1 flags rising edges rather than falling
+    pub fn set_cb1_rising(&mut self, rising: bool) {
+        unsafe {
+            self.via.pcr.modify(|pcr| if rising { pcr | reg::PCR_CB1_RISING } else { pcr & !reg::PCR_CB1_RISING });
+        }
+    }
+
+    /// Whether port B's inputs are latched by CB1 edges
+    pub fn set_latch(&mut self, latch: bool) {
+        unsafe {
+            self.via.acr.modify(|acr| if latch { acr | reg::ACR_PB_LATCH } else { acr & !reg::ACR_PB_LATCH });
+        }
+    }
+
+    /// The interrupt flags among `bits` that are set
+    #[inline(always)]
+    pub fn flags(&self, bits: u8) -> u8 {
+        self.via.ifr.read() & bits
+    }
+
+    /// Clear the interrupt flags in `bits`
+    #[inline(always)]
+    pub fn clear_flags(&mut self, bits: u8) {
+        unsafe { self.via.ifr.write(bits & 0x7F) };
+    }
+}
+
+/// A device on the expansion port
+pub trait Peripheral {
+    /// Call once a frame, for devices that need tending. Does nothing by
+    /// default.
+    fn poll(&mut self) {}
+}
+
+/// A peripheral that moves bytes, like a link cable or serial adapter
+pub trait Link: Peripheral {
+    /// Send a byte. What happens if the other end hasn't read the last one
+    /// is up to the device; assume it's lost.
+    fn send(&mut self, byte: u8);
+
+    /// The next byte received, if there is one
+    fn recv(&mut self) -> Option<u8>;
+}
+
+/// A cable joining two consoles' expansion ports, port B to port B, with
+/// each side's CB2 going to the other's CB1.
+///
+/// Sending drives port B just long enough to write it, which pulses CB2 so
+/// the other side latches the byte. Each side holds one byte until it's
+/// received, so trade a byte a frame, or take turns: both sides sending at
+/// once garbles the bytes.
+///
+/// gte emulates the cable as netplay: start one with `--link-host` and the
+/// other with `--link-join`.
+pub struct ParallelLink {
+    port: ExpansionPort,
+    received: Option<u8>,
+}
+
+impl ParallelLink {
+    pub fn new(mut port: ExpansionPort) -> Self {
+        port.set_outputs(0);
+        port.set_cb2(reg::PCR_CB2_PULSE);
+        port.set_cb1_rising(false);
+        port.set_latch(true);
+        port.clear_flags(reg::IRQ_CB1);
+        Self { port, received: None }
+    }
+}
+
+impl Peripheral for ParallelLink {
+    /// Pick up a byte the other side sent, so it isn't lost to a send
+    fn poll(&mut self) {
+        if self.port.flags(reg::IRQ_CB1) != 0 {
+            // reading clears the flag
+            self.received = Some(self.port.read());
+        }
+    }
+}
+
+impl Link for ParallelLink {
+    fn send(&mut self, byte: u8) {
+        // writing port B clears CB1's flag, so collect anything waiting first
+        self.poll();
+        self.port.set_outputs(0xFF);
+        self.port.write(byte);
+        self.port.set_outputs(0);
+    }
+
+    fn recv(&mut self) -> Option<u8> {
+        self.poll();
+        self.received.take()
+    }
+}
+
+/// A motor on one port B pin, like a rumble pack
+pub struct Rumble {
+    port: ExpansionPort,
+    pin: u8,
+    frames: u8,
+}
+
+impl Rumble {
+    /// A motor switched by the pins in `pin`, which start off
+    pub fn new(mut port: ExpansionPort, pin: u8) -> Self {
+        let value = port.read() & !pin;
+        port.write(value);
+        port.set_outputs(pin);
+        Self { port, pin, frames: 0 }
+    }
+
+    /// Run the motor for `frames` frames, counting down in [`poll`](Peripheral::poll)
+    pub fn pulse(&mut self, frames: u8) {
+        self.frames = frames;
+        self.set(frames > 0);
+    }
+
+    pub fn stop(&mut self) {
+        self.pulse(0);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.frames > 0
+    }
+
+    fn set(&mut self, on: bool) {
+        let value = self.port.read();
+        self.port.write(if on { value | self.pin } else { value & !self.pin });
+    }
+}
+
+impl Peripheral for Rumble {
+    fn poll(&mut self) {
+        if self.frames > 0 {
+            self.frames -= 1;
+            if self.frames == 0 {
+                self.set(false);
+            }
+        }
+    }
+}
+
+/// Bytes a [`Loopback`] holds before dropping the oldest
+pub const LOOPBACK_SIZE: usize = 16;
+
+/// A [`Link`] with no hardware: whatever's sent is received back, in order.
+/// For running link code in tests, or single player.
+pub struct Loopback {
+    buf: [u8; LOOPBACK_SIZE],
+    head: u8,
+    len: u8,
+}
+
+impl Loopback {
+    pub const fn new() -> Self {
+        Self { buf: [0; LOOPBACK_SIZE], head: 0, len: 0 }
+    }
+}
+
+impl Default for Loopback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Peripheral for Loopback {}
+
+impl Link for Loopback {
+    fn send(&mut self, byte: u8) {
+        let tail = (self.head as usize + self.len as usize) % LOOPBACK_SIZE;
+        self.buf[tail] = byte;
+        if (self.len as usize) < LOOPBACK_SIZE {
+            self.len += 1;
+        } else {
+            // full, so the oldest goes
+            self.head = ((self.head as usize + 1) % LOOPBACK_SIZE) as u8;
+        }
+    }
+
+    fn recv(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buf[self.head as usize];
+        self.head = ((self.head as usize + 1) % LOOPBACK_SIZE) as u8;
+        self.len -= 1;
+        Some(byte)
+    }
+}
@@ -0,0 +1,242 @@
+//! # Object Pools
+//!
+//! [`Pool`] holds up to `N` bullets, enemies or particles in a fixed array,
+//! so it works without the heap and can live in a `static`. Adding one gives
+//! back a [`Handle`], which stops working once that object is removed, even
+//! if something new takes its place:
+//!
+//! ```ignore
+//! use rom::sdk::pool::{Handle, Pool};
+//!
+//! struct Bullet { x: u8, y: u8 }
+//!
+//! let mut bullets: Pool<Bullet, 16> = Pool::new();
+//! let _ = bullets.insert(Bullet { x: 60, y: 120 });
+//!
+//! // Move everything, dropping bullets that left the screen
+//! bullets.retain(|_, b| {
+//!     b.y = b.y.wrapping_sub(2);
+//!     b.y < 128
+//! });
+//!
+//! for (_, b) in bullets.iter() {
+//!     blitter.draw_square(&mut console.sc, b.x, b.y, 1, 2, !WHITE);
+//! }
+//!
+//! // An enemy can hold on to its target safely
+//! let target: Option<Handle> = None;
+//! if let Some(b) = target.and_then(|h| bullets.get(h)) {
+//!     // ...
+//! }
+//! ```
+//!
+//! Indices and generations are single bytes, so `N` can be at most 255, and
+//! a handle kept through 256 reuses of its slot could match again.
+
+use core::iter::Enumerate;
+use core::slice;
+
+/// Refers to an object in a [`Pool`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    index: u8,
+    generation: u8,
+}
+
+impl Handle {
+    /// The slot it's in, for keeping per-object data in a parallel array
+    #[inline]
+    pub const fn index(self) -> u8 {
+        self.index
+    }
+}
+
+struct Slot<T> {
+    /// Bumped each time the slot is emptied
+    generation: u8,
+    value: Option<T>,
+    /// The next empty slot, while this one is empty
+    next_free: u8,
+}
+
+/// A fixed-capacity set of `T`s, addressed by [`Handle`]s
+pub struct Pool<T, const N: usize> {
+    slots: [Slot<T>; N],
+    /// First empty slot, or `N` when full
+    free: u8,
+    len: u8,
+}
+
+impl<T, const N: usize> Pool<T, N> {
+    const FITS: () = assert!(N <= 255, "a Pool holds at most 255 objects");
+
+    pub const fn new() -> Self {
+        let () = Self::FITS;
+        let mut slots = [const { Slot { generation: 0, value: None, next_free: 0 } }; N];
+        let mut i = 0;
+        while i < N {
+            slots[i].next_free = (i + 1) as u8;
+            i += 1;
+        }
+        Self { slots, free: 0, len: 0 }
+    }
+
+    /// Add `value`, or give it back if the pool is full
+    pub fn insert(&mut self, value: T) -> Result<Handle, T> {
+        let index = self.free;
+        let Some(slot) = self.slots.get_mut(index as usize) else {
+            return Err(value);
+        };
+        self.free = slot.next_free;
+        slot.value = Some(value);
+        self.len += 1;
+        Ok(Handle { index, generation: slot.generation })
+    }
+
+    /// Take the object out, if `handle` still refers to it
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let value = slot.value.take()?;
+        self.release(handle.index);
+        Some(value)
+    }
+
+    /// Put an emptied slot back on the free list
+    fn release(&mut self, index: u8) {
+        let slot = &mut self.slots[index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.next_free = self.free;
+        self.free = index;
+        self.len -= 1;
+    }
+
+    #[inline]
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        let slot = self.slots.get(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.value.as_ref()
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.value.as_mut()
+    }
+
+    /// Whether `handle`'s object is still in the pool
+    #[inline]
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.get(handle).is_some()
+    }
+
+    #[inline]
+    pub const fn len(&self) -> u8 {
+        self.len
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub const fn is_full(&self) -> bool {
+        self.len as usize == N
+    }
+
+    #[inline]
+    pub const fn capacity(&self) -> u8 {
+        N as u8
+    }
+
+    /// Objects in slot order, with their handles
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { slots: self.slots.iter().enumerate() }
+    }
+
+    /// Objects in slot order, with their handles
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { slots: self.slots.iter_mut().enumerate() }
+    }
+
+    /// Keep only the objects `f` returns `true` for. `f` can change them as
+    /// it goes, so updating and removing take one pass.
+    pub fn retain(&mut self, mut f: impl FnMut(Handle, &mut T) -> bool) {
+        for index in 0..N as u8 {
+            let slot = &mut self.slots[index as usize];
+            let handle = Handle { index, generation: slot.generation };
+            let Some(value) = &mut slot.value else { continue };
+            if !f(handle, value) {
+                slot.value = None;
+                self.release(index);
+            }
+        }
+    }
+
+    /// Remove everything. Handles to what was there stop working.
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+}
+
+impl<T, const N: usize> Default for Pool<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Iter<'a, T> {
+    slots: Enumerate<slice::Iter<'a, Slot<T>>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Handle, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.find_map(|(index, slot)| {
+            let handle = Handle { index: index as u8, generation: slot.generation };
+            slot.value.as_ref().map(|value| (handle, value))
+        })
+    }
+}
+
+pub struct IterMut<'a, T> {
+    slots: Enumerate<slice::IterMut<'a, Slot<T>>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (Handle, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.find_map(|(index, slot)| {
+            let handle = Handle { index: index as u8, generation: slot.generation };
+            slot.value.as_mut().map(|value| (handle, value))
+        })
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a Pool<T, N> {
+    type Item = (Handle, &'a T);
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut Pool<T, N> {
+    type Item = (Handle, &'a mut T);
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
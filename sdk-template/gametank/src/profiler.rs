@@ -0,0 +1,215 @@
+//! # Frame Profiler
+//!
+//! Times parts of each frame in CPU cycles, to see how much of the vblank
+//! budget they take. The GameTank has no scanline counter to read, so the
+//! profiler runs the VIA's timer 1 from the start of each frame instead: at
+//! ~3.58 MHz, a 60 Hz frame is [`FRAME_CYCLES`] cycles.
+//!
+//! ```ignore
+//! use rom::sdk::profiler::{Profiler, Section};
+//!
+//! let mut profiler = Profiler::new();
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     profiler.start_frame();
+//!     console.flip_framebuffers();
+//!
+//!     {
+//!         let _update = profiler.scope(Section::Update);
+//!         update_game_logic();
+//!     }
+//!     {
+//!         let _draw = profiler.scope(Section::Draw);
+//!         draw_everything(&mut console);
+//!     }
+//!
+//!     profiler.end_frame();
+//!     let mut blitter = console.blitter().unwrap();
+//!     profiler.draw_overlay(&mut blitter, 124);
+//! }
+//! ```
+//!
+//! Totals are for the last whole frame, so reading them (or drawing the
+//! overlay) doesn't disturb the frame being measured.
+//!
+//! ## The Overlay
+//!
+//! [`draw_overlay`](Profiler::draw_overlay) draws a bar across the screen
+//! where the full width is one frame: a segment per [`Section`] in its
+//! color, then gray for time spent outside any section. When the frame
+//! went over budget, the bar's right end is red.
+//!
+//! Timer 1 is 16 bits, so a frame that runs longer than 65535 cycles
+//! (about 1.1 frames) reads as 65535.
+
+// colors are written hue_saturation_luminance
+#![allow(clippy::unusual_byte_groupings)]
+
+use crate::{via::Via, video_dma::blitter::BlitterGuard};
+
+pub use crate::via::FRAME_CYCLES;
+
+/// Cycles per pixel of the overlay bar, so a whole frame fits across the screen.
+const CYCLES_PER_PIXEL: u16 = FRAME_CYCLES.div_ceil(128);
+
+/// Height of the overlay bar in pixels.
+const BAR_HEIGHT: u8 = 4;
+
+/// Overlay colors, before inverting for the blitter
+const UNTIMED_COLOR: u8 = 0b000_00_100;
+const OVER_BUDGET_COLOR: u8 = 0b010_11_100;
+
+/// A part of the frame to time. Time spent in no section still counts
+/// toward the frame total.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Section {
+    Update = 0,
+    Draw = 1,
+    Audio = 2,
+}
+
+/// Number of [`Section`]s.
+pub const SECTIONS: usize = 3;
+
+impl Section {
+    pub const ALL: [Section; SECTIONS] = [Section::Update, Section::Draw, Section::Audio];
+
+    /// The section's color in the overlay.
+    pub fn color(self) -> u8 {
+        match self {
+            Section::Update => 0b111_11_100, // green
+            Section::Draw => 0b101_11_100,   // blue
+            Section::Audio => 0b001_11_100,
+        }
+    }
+}
+
+/// Cycles since [`Profiler::start_frame`] restarted timer 1.
+#[inline(always)]
+fn elapsed() -> u16 {
+    unsafe { Via::new() }.timer1_elapsed()
+}
+
+/// Per-section cycle counts for the current and the last frame. See the
+/// [module docs](self).
+pub struct Profiler {
+    /// When each running section began, in cycles into the frame
+    started: [u16; SECTIONS],
+    /// Cycles spent in each section so far this frame
+    running: [u16; SECTIONS],
+    /// Cycles spent in each section last frame
+    totals: [u16; SECTIONS],
+    /// Cycles from `start_frame` to `end_frame` last frame
+    frame: u16,
+}
+
+impl Profiler {
+    pub const fn new() -> Self {
+        Self {
+            started: [0; SECTIONS],
+            running: [0; SECTIONS],
+            totals: [0; SECTIONS],
+            frame: 0,
+        }
+    }
+
+    /// Restart the timer. Call right after waiting for vblank.
+    pub fn start_frame(&mut self) {
+        unsafe { Via::new() }.start_frame_timer();
+        self.running = [0; SECTIONS];
+    }
+
+    /// Finish the frame's totals. Call just before waiting for vblank,
+    /// after the last section has ended.
+    pub fn end_frame(&mut self) {
+        self.frame = unsafe { Via::new() }.frame_cycles();
+        self.totals = self.running;
+    }
+
+    /// Start timing `section`.
+    #[inline(always)]
+    pub fn begin(&mut self, section: Section) {
+        self.started[section as usize] = elapsed();
+    }
+
+    /// Stop timing `section`, adding the time since [`begin`](Self::begin)
+    /// to this frame's total.
+    #[inline(always)]
+    pub fn end(&mut self, section: Section) {
+        let index = section as usize;
+        let cycles = elapsed().wrapping_sub(self.started[index]);
+        self.running[index] = self.running[index].saturating_add(cycles);
+    }
+
+    /// Time `section` until the returned guard is dropped.
+    pub fn scope(&mut self, section: Section) -> Scope<'_> {
+        self.begin(section);
+        Scope { profiler: self, section }
+    }
+
+    /// Cycles spent in `section` last frame.
+    #[inline(always)]
+    pub fn cycles(&self, section: Section) -> u16 {
+        self.totals[section as usize]
+    }
+
+    /// Cycles from [`start_frame`](Self::start_frame) to
+    /// [`end_frame`](Self::end_frame) last frame.
+    #[inline(always)]
+    pub fn frame_cycles(&self) -> u16 {
+        self.frame
+    }
+
+    /// Whether last frame's work took longer than a frame.
+    #[inline(always)]
+    pub fn over_budget(&self) -> bool {
+        self.frame > FRAME_CYCLES
+    }
+
+    /// Draw last frame's costs as a bar across the screen at row `y`, and
+    /// wait for the last blit.
+    pub fn draw_overlay(&self, blitter: &mut BlitterGuard, y: u8) {
+        let width = |cycles: u16| (cycles / CYCLES_PER_PIXEL) as u8;
+        let frame = width(self.frame.min(FRAME_CYCLES));
+        let mut x = 0u8;
+
+        for section in Section::ALL {
+            let w = width(self.cycles(section)).min(frame - x);
+            if w > 0 {
+                blitter.draw_square(x, y, w, BAR_HEIGHT, !section.color());
+                blitter.wait_blit();
+                x += w;
+            }
+        }
+
+        if frame > x {
+            blitter.draw_square(x, y, frame - x, BAR_HEIGHT, !UNTIMED_COLOR);
+            blitter.wait_blit();
+        }
+        if self.over_budget() {
+            // the last few pixels, so the bar still shows where the time went
+            blitter.draw_square(124, y, 4, BAR_HEIGHT, !OVER_BUDGET_COLOR);
+            blitter.wait_blit();
+        }
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Times a [`Section`] from [`Profiler::scope`] until dropped.
+pub struct Scope<'a> {
+    profiler: &'a mut Profiler,
+    section: Section,
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        self.profiler.end(self.section);
+    }
+}
@@ -0,0 +1,187 @@
+//! # ROM Info
+//!
+//! [`gametank_rom!`](crate::gametank_rom) stamps the game's title, author
+//! and version into the 128 bytes just below the vector table, laid out
+//! like the header of a `.gtr` file. `gtrom build` copies them into the
+//! header it writes, and the flasher and emulator read them back, even from
+//! a bare `.bin`, to show what a ROM is.
+//!
+//! ```ignore
+//! gametank::gametank_rom! {
+//!     title: "SPACE BLASTER",
+//!     author: "DWBRITE",
+//!     version: "1.0",
+//!     splash: true,
+//! }
+//! ```
+//!
+//! The title and author can be up to 32 bytes and the version up to 16;
+//! longer ones fail to compile.
+//!
+//! ## Boot Splash
+//!
+//! With `splash: true`, the title, version and author are shown in the
+//! built-in font for [`SPLASH_FRAMES`], or until a button is pressed, before
+//! `main` runs. Both framebuffers are cleared to black afterwards. The font
+//! is loaded into the top [`FONT_HEIGHT`](crate::gfx::text::FONT_HEIGHT)
+//! rows of sprite page 7's first quadrant, which the game is free to load
+//! over.
+
+use core::ptr;
+
+use crate::{
+    boot::wait,
+    console::Console,
+    gfx::text::{text_width, Font, TextSize},
+    video_dma::blitter::BlitterGuard,
+};
+
+/// Where the record is linked, by the `ROM_INFO` region in `build.rs`
+pub const INFO_ADDRESS: u16 = 0xFF7A;
+
+/// `.gtr` magic, which also marks a ROM as having a record
+pub const MAGIC: [u8; 4] = *b"GTR\x1A";
+
+/// Version of the `.gtr` header layout
+pub const VERSION: u8 = 1;
+
+/// Bytes of the record before the splash address
+pub const HEADER_LEN: usize = 96;
+
+const TITLE_OFFSET: usize = 16;
+const AUTHOR_OFFSET: usize = 48;
+const TEXT_LEN: usize = 32;
+const VERSION_OFFSET: usize = 80;
+const VERSION_LEN: usize = 16;
+
+/// Frames the splash stays up without a button press: two seconds
+pub const SPLASH_FRAMES: u8 = 120;
+
+/// Draws the splash, on page 7
+const SPLASH_FONT: Font = Font::new(7);
+
+/// Inverted, as for `draw_square`
+const BLACK: u8 = !0u8;
+/// White, `0b000_00_111`, inverted
+const WHITE: u8 = !0x07;
+
+/// What [`gametank_rom!`](crate::gametank_rom) links at [`INFO_ADDRESS`]
+#[repr(C)]
+pub struct RomInfo {
+    header: [u8; HEADER_LEN],
+    splash: Option<fn(&mut Console)>,
+    reserved: [u8; 128 - HEADER_LEN - size_of::<Option<fn(&mut Console)>>()],
+}
+
+impl RomInfo {
+    pub const fn new(title: &str, author: &str, version: &str) -> Self {
+        let mut header = [0u8; HEADER_LEN];
+        copy_text(&mut header, 0, 4, &MAGIC);
+        header[4] = VERSION;
+        assert!(title.len() <= TEXT_LEN, "gametank_rom! title is over 32 bytes");
+        assert!(author.len() <= TEXT_LEN, "gametank_rom! author is over 32 bytes");
+        assert!(version.len() <= VERSION_LEN, "gametank_rom! version is over 16 bytes");
+        copy_text(&mut header, TITLE_OFFSET, TEXT_LEN, title.as_bytes());
+        copy_text(&mut header, AUTHOR_OFFSET, TEXT_LEN, author.as_bytes());
+        copy_text(&mut header, VERSION_OFFSET, VERSION_LEN, version.as_bytes());
+
+        Self {
+            header,
+            splash: None,
+            reserved: [0; 128 - HEADER_LEN - size_of::<Option<fn(&mut Console)>>()],
+        }
+    }
+
+    /// Run `splash` before `main`, if `show`
+    pub const fn with_splash(mut self, show: bool, splash: fn(&mut Console)) -> Self {
+        if show {
+            self.splash = Some(splash);
+        }
+        self
+    }
+}
+
+const fn copy_text(out: &mut [u8; HEADER_LEN], offset: usize, len: usize, text: &[u8]) {
+    let mut i = 0;
+    while i < text.len() && i < len {
+        out[offset + i] = text[i];
+        i += 1;
+    }
+}
+
+/// Stamp the ROM's title, author and version into it, and optionally show
+/// them on a splash screen before `main`. See [`rom_info`](crate::rom_info).
+#[macro_export]
+macro_rules! gametank_rom {
+    (title: $title:expr, author: $author:expr, version: $version:expr $(, splash: $splash:expr)? $(,)?) => {
+        #[used]
+        #[unsafe(link_section = ".rom_info")]
+        static __GAMETANK_ROM_INFO: $crate::rom_info::RomInfo =
+            $crate::rom_info::RomInfo::new($title, $author, $version)
+            $(.with_splash($splash, |console| $crate::rom_info::splash(console, $title, $author, $version)))?;
+    };
+}
+
+/// The splash `gametank_rom!` asked for, if this ROM has a record
+pub(crate) fn boot_splash() -> Option<fn(&mut Console)> {
+    let info = INFO_ADDRESS as *const RomInfo;
+    unsafe {
+        if ptr::read_volatile((&raw const (*info).header).cast::<[u8; 4]>()) != MAGIC {
+            return None;
+        }
+        ptr::read_volatile(&raw const (*info).splash)
+    }
+}
+
+/// Show `title`, `version` and `author` centered on a black screen, until
+/// [`SPLASH_FRAMES`] pass or a button is pressed.
+pub fn splash(console: &mut Console, title: &str, author: &str, version: &str) {
+    SPLASH_FONT.load(console, WHITE);
+
+    // the same picture in both framebuffers, so it holds while flipping
+    for _ in 0..2 {
+        if let Some(mut blitter) = console.blitter() {
+            clear(&mut blitter);
+            SPLASH_FONT.draw(&mut blitter, centered(title), 44, title, TextSize::Tall);
+            blitter.wait_blit();
+            SPLASH_FONT.draw(&mut blitter, centered(version), 60, version, TextSize::Normal);
+            blitter.wait_blit();
+            SPLASH_FONT.draw(&mut blitter, centered(author), 100, author, TextSize::Normal);
+            blitter.wait_blit();
+        }
+        unsafe { wait() };
+        console.flip_framebuffers();
+    }
+
+    // a button held since power-on doesn't skip it
+    let (mut gamepad, _) = console.genesis_gamepads();
+    gamepad.read();
+    for _ in 0..SPLASH_FRAMES {
+        unsafe { wait() };
+        gamepad.read();
+        if gamepad.buttons & !gamepad.buttons_last != 0 {
+            break;
+        }
+    }
+
+    for _ in 0..2 {
+        if let Some(mut blitter) = console.blitter() {
+            clear(&mut blitter);
+        }
+        unsafe { wait() };
+        console.flip_framebuffers();
+    }
+}
+
+fn centered(text: &str) -> u8 {
+    (128 - text_width(text).min(128)) / 2
+}
+
+/// Fill the framebuffer being drawn to with black, a quarter at a time, as
+/// a blit is at most 127 pixels across
+fn clear(blitter: &mut BlitterGuard) {
+    for (x, y) in [(0, 0), (64, 0), (0, 64), (64, 64)] {
+        blitter.draw_square(x, y, 64, 64, BLACK);
+        blitter.wait_blit();
+    }
+}
@@ -0,0 +1,584 @@
+//! # Saving
+//!
+//! The flash cartridge keeps saves in ROM bank [`SAVE_BANK`], the only bank
+//! made of small (8KB) erase blocks. Flash bits can only be cleared, and a
+//! block only erased a limited number of times, so saves are appended to a
+//! log in one block. Once it fills up, the newest record of each slot is
+//! copied to the other block and the old one is erased, so the two blocks
+//! wear evenly and a save interrupted by a power cut leaves the previous one
+//! in place.
+//!
+//! Anything [`Savable`] can be saved in one of [`SLOTS`] slots. The
+//! [`savable!`](crate::savable) macro declares a struct with a fixed layout:
+//!
+//! ```ignore
+//! use rom::sdk::{savable, save::SaveBank};
+//!
+//! savable! {
+//!     version 1;
+//!     #[derive(Default)]
+//!     pub struct Progress {
+//!         pub level: u8,
+//!         pub best_times: [u16; 8],
+//!         pub unlocked: bool,
+//!     }
+//! }
+//!
+//! let mut saves = SaveBank::open();
+//! let mut progress: Progress = saves.load_or_default(0);
+//! // later
+//! progress.level += 1;
+//! saves.save(&mut console, 0, &progress).ok();
+//! ```
+//!
+//! Each record carries the layout's version and a checksum. A slot whose
+//! newest record is damaged loads the one before it; one saved with a
+//! different version comes back as [`LoadError::Version`], so the game can
+//! convert it or start over.
+//!
+//! Saving turns off the vblank NMI while the flash is busy: programming is
+//! quick, but erasing a block takes a frame or two, so save between scenes
+//! rather than mid-action. The save bank must not hold code or data; `.auto`
+//! sections already skip it.
+
+use crate::console::Console;
+use crate::scr::VideoFlags;
+use crate::via::Via;
+
+/// The ROM bank saves live in
+pub const SAVE_BANK: u8 = 63;
+
+/// Save slots, numbered from 0
+pub const SLOTS: u8 = 16;
+
+/// Bytes in each of the save bank's two erase blocks
+const AREA_LEN: u16 = 0x2000;
+
+/// `"GS"` and a generation count, written once a block is ready
+const AREA_HEADER_LEN: u16 = 3;
+const MAGIC: [u8; 2] = *b"GS";
+
+/// Slot, version and length before the data, checksum after
+const RECORD_HEADER_LEN: u16 = 4;
+const RECORD_OVERHEAD: u16 = RECORD_HEADER_LEN + 2;
+
+/// What's in a byte of flash that's been erased
+const ERASED: u8 = 0xFF;
+
+/// Arguments for `flash_program` in `flash.asm`
+#[repr(C)]
+struct FlashWrite {
+    dest: *mut u8,
+    src: *const u8,
+    len: u16,
+}
+
+unsafe extern "C" {
+    fn flash_program(args: *const FlashWrite);
+    fn flash_erase(block: *mut u8);
+}
+
+/// A value with a fixed-size layout in the save bank.
+///
+/// Implemented for integers, `bool` and arrays; use [`savable!`](crate::savable)
+/// for structs.
+pub trait Savable: Sized {
+    /// Bytes [`save`](Self::save) writes, always
+    const SIZE: u16;
+    /// Bump this when the layout changes
+    const VERSION: u8 = 0;
+
+    fn save(&self, w: &mut SaveWriter);
+    /// `None` if the bytes aren't a valid value
+    fn load(r: &mut SaveReader) -> Option<Self>;
+}
+
+/// Why a slot didn't load
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// Nothing saved in the slot, or it was cleared
+    Empty,
+    /// Every record for the slot failed its checksum or didn't decode
+    Corrupt,
+    /// Saved with another version of the layout
+    Version(u8),
+}
+
+/// Why a save failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveError {
+    /// The slot number is [`SLOTS`] or more
+    NoSuchSlot,
+    /// The other slots' saves leave no room for this one
+    Full,
+    /// Reading it back didn't match, so the flash is worn out or missing
+    Verify,
+}
+
+/// Fletcher-16, which never comes out as erased flash
+#[derive(Clone, Copy)]
+struct Checksum {
+    a: u8,
+    b: u8,
+}
+
+impl Checksum {
+    const fn new() -> Self {
+        Self { a: 0, b: 0 }
+    }
+
+    fn add(&mut self, byte: u8) {
+        self.a = add_mod255(self.a, byte);
+        self.b = add_mod255(self.b, self.a);
+    }
+
+    fn bytes(&self) -> [u8; 2] {
+        [self.a, self.b]
+    }
+}
+
+fn add_mod255(a: u8, b: u8) -> u8 {
+    let (sum, carry) = a.overflowing_add(b);
+    let sum = sum.wrapping_add(carry as u8);
+    if sum == 0xFF { 0 } else { sum }
+}
+
+/// Where the save bank shows up
+fn flash_at(offset: u16) -> *mut u8 {
+    (0x8000 + offset) as *mut u8
+}
+
+fn read_flash(offset: u16) -> u8 {
+    unsafe { core::ptr::read_volatile(flash_at(offset)) }
+}
+
+fn read_flash_u16(offset: u16) -> u16 {
+    u16::from_le_bytes([read_flash(offset), read_flash(offset + 1)])
+}
+
+fn program(offset: u16, bytes: &[u8]) {
+    let args = FlashWrite { dest: flash_at(offset), src: bytes.as_ptr(), len: bytes.len() as u16 };
+    unsafe { flash_program(&args) };
+}
+
+/// Copy flash to flash, within the save bank
+fn program_from_flash(offset: u16, from: u16, len: u16) {
+    let args = FlashWrite { dest: flash_at(offset), src: flash_at(from), len };
+    unsafe { flash_program(&args) };
+}
+
+/// Select the save bank for `f`, then put back the bank that was selected.
+fn with_save_bank<R>(f: impl FnOnce() -> R) -> R {
+    let via = unsafe { Via::new() };
+    let previous = via.rom_bank();
+    via.change_rom_bank(SAVE_BANK);
+    let result = f();
+    let via = unsafe { Via::new() };
+    via.change_rom_bank(previous);
+    result
+}
+
+/// Writes a [`Savable`] into the save bank, a byte at a time.
+pub struct SaveWriter {
+    offset: u16,
+    len: u16,
+    written: u16,
+    sum: Checksum,
+}
+
+impl SaveWriter {
+    /// Bytes past the value's [`SIZE`](Savable::SIZE) are counted, but
+    /// not written, and the save fails.
+    pub fn u8(&mut self, value: u8) {
+        if self.written < self.len {
+            program(self.offset + self.written, &[value]);
+            self.sum.add(value);
+        }
+        self.written = self.written.saturating_add(1);
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.u8(b);
+        }
+    }
+}
+
+/// Reads a [`Savable`] back out of the save bank.
+pub struct SaveReader {
+    offset: u16,
+    end: u16,
+}
+
+impl SaveReader {
+    /// The next byte, or `None` past the end of the record
+    pub fn u8(&mut self) -> Option<u8> {
+        if self.offset >= self.end {
+            return None;
+        }
+        let value = read_flash(self.offset);
+        self.offset += 1;
+        Some(value)
+    }
+
+    pub fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let mut out = [0; N];
+        for b in &mut out {
+            *b = self.u8()?;
+        }
+        Some(out)
+    }
+}
+
+macro_rules! savable_int {
+    ($($t:ty),*) => {$(
+        impl Savable for $t {
+            const SIZE: u16 = core::mem::size_of::<$t>() as u16;
+
+            fn save(&self, w: &mut SaveWriter) {
+                w.bytes(&self.to_le_bytes());
+            }
+
+            fn load(r: &mut SaveReader) -> Option<Self> {
+                Some(<$t>::from_le_bytes(r.bytes()?))
+            }
+        }
+    )*};
+}
+
+savable_int!(u8, i8, u16, i16, u32, i32);
+
+impl Savable for bool {
+    const SIZE: u16 = 1;
+
+    fn save(&self, w: &mut SaveWriter) {
+        w.u8(*self as u8);
+    }
+
+    fn load(r: &mut SaveReader) -> Option<Self> {
+        match r.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Savable + Copy + Default, const N: usize> Savable for [T; N] {
+    const SIZE: u16 = T::SIZE * N as u16;
+
+    fn save(&self, w: &mut SaveWriter) {
+        for item in self {
+            item.save(w);
+        }
+    }
+
+    fn load(r: &mut SaveReader) -> Option<Self> {
+        let mut out = [T::default(); N];
+        for item in &mut out {
+            *item = T::load(r)?;
+        }
+        Some(out)
+    }
+}
+
+/// Declare a struct and implement [`Savable`] for it, field by field in
+/// order. Every field has to be [`Savable`] too. An optional `version N;`
+/// sets [`Savable::VERSION`].
+///
+/// ```ignore
+/// savable! {
+///     version 2;
+///     #[derive(Default, Clone, Copy)]
+///     pub struct Options {
+///         pub music: u8,
+///         pub sfx: u8,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! savable {
+    (
+        @struct $version:literal;
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty),*
+        }
+
+        impl $crate::save::Savable for $name {
+            const SIZE: u16 = 0 $(+ <$ty as $crate::save::Savable>::SIZE)*;
+            const VERSION: u8 = $version;
+
+            fn save(&self, w: &mut $crate::save::SaveWriter) {
+                $($crate::save::Savable::save(&self.$field, w);)*
+            }
+
+            fn load(r: &mut $crate::save::SaveReader) -> Option<Self> {
+                Some(Self {
+                    $($field: <$ty as $crate::save::Savable>::load(r)?),*
+                })
+            }
+        }
+    };
+    (version $version:literal; $($rest:tt)*) => {
+        $crate::savable!(@struct $version; $($rest)*);
+    };
+    ($($rest:tt)*) => {
+        $crate::savable!(@struct 0; $($rest)*);
+    };
+}
+
+/// The newest record for a slot
+#[derive(Clone, Copy)]
+struct Record {
+    offset: u16,
+    version: u8,
+    len: u16,
+}
+
+/// The save bank, as found by [`open`](Self::open). See the
+/// [module docs](self).
+pub struct SaveBank {
+    /// Offset of the block in use, `None` until the first save
+    area: Option<u16>,
+    generation: u8,
+    /// Where the next record goes, from the start of the bank
+    end: u16,
+}
+
+impl SaveBank {
+    /// Find the block in use and the end of its log.
+    pub fn open() -> Self {
+        with_save_bank(|| {
+            let headers = [0, AREA_LEN].map(|area| {
+                // a generation that's still erased means the header was cut short
+                let generation = read_flash(area + 2);
+                let valid = read_flash(area) == MAGIC[0] && read_flash(area + 1) == MAGIC[1] && generation != ERASED;
+                valid.then_some((area, generation))
+            });
+            let current = match headers {
+                // an interrupted copy leaves both; the newer one is complete
+                [Some(a), Some(b)] => if b.1.wrapping_sub(a.1) as i8 > 0 { Some(b) } else { Some(a) },
+                [a, b] => a.or(b),
+            };
+
+            match current {
+                Some((area, generation)) => Self { area: Some(area), generation, end: Self::log_end(area) },
+                None => Self { area: None, generation: 0, end: 0 },
+            }
+        })
+    }
+
+    /// Where the log in `area` ends. A record whose header was cut short
+    /// can't be stepped over, so the block counts as full from there.
+    fn log_end(area: u16) -> u16 {
+        let limit = area + AREA_LEN;
+        let mut offset = area + AREA_HEADER_LEN;
+        while offset + RECORD_OVERHEAD <= limit {
+            if read_flash(offset) == ERASED {
+                return offset;
+            }
+            let len = read_flash_u16(offset + 2);
+            if len > limit - offset - RECORD_OVERHEAD {
+                return limit;
+            }
+            offset += RECORD_OVERHEAD + len;
+        }
+        limit
+    }
+
+    /// Whether the record at `offset` is whole
+    fn is_intact(offset: u16, len: u16) -> bool {
+        let mut sum = Checksum::new();
+        for i in 0..RECORD_HEADER_LEN + len {
+            sum.add(read_flash(offset + i));
+        }
+        let end = offset + RECORD_HEADER_LEN + len;
+        sum.bytes() == [read_flash(end), read_flash(end + 1)]
+    }
+
+    /// The newest intact record for each slot, and whether any damaged
+    /// ones came after it
+    fn scan(&self) -> ([Option<Record>; SLOTS as usize], [bool; SLOTS as usize]) {
+        let mut records = [None; SLOTS as usize];
+        let mut damaged = [false; SLOTS as usize];
+        let Some(area) = self.area else { return (records, damaged) };
+
+        let mut offset = area + AREA_HEADER_LEN;
+        while offset + RECORD_OVERHEAD <= self.end {
+            let slot = read_flash(offset) as usize;
+            let len = read_flash_u16(offset + 2);
+            if len > self.end - offset - RECORD_OVERHEAD {
+                break;
+            }
+            if slot < SLOTS as usize {
+                if Self::is_intact(offset, len) {
+                    records[slot] = Some(Record { offset, version: read_flash(offset + 1), len });
+                    damaged[slot] = false;
+                } else {
+                    damaged[slot] = true;
+                }
+            }
+            offset += RECORD_OVERHEAD + len;
+        }
+        (records, damaged)
+    }
+
+    /// Load slot `slot`.
+    pub fn load<T: Savable>(&self, slot: u8) -> Result<T, LoadError> {
+        if slot >= SLOTS {
+            return Err(LoadError::Empty);
+        }
+        with_save_bank(|| {
+            let (records, damaged) = self.scan();
+            let record = match records[slot as usize] {
+                Some(r) if r.len > 0 => r,
+                Some(_) => return Err(LoadError::Empty),
+                None if damaged[slot as usize] => return Err(LoadError::Corrupt),
+                None => return Err(LoadError::Empty),
+            };
+            if record.version != T::VERSION {
+                return Err(LoadError::Version(record.version));
+            }
+            if record.len != T::SIZE {
+                return Err(LoadError::Corrupt);
+            }
+
+            let start = record.offset + RECORD_HEADER_LEN;
+            let mut r = SaveReader { offset: start, end: start + record.len };
+            T::load(&mut r).ok_or(LoadError::Corrupt)
+        })
+    }
+
+    /// Load slot `slot`, or the default if it's empty, damaged or from
+    /// another version.
+    pub fn load_or_default<T: Savable + Default>(&self, slot: u8) -> T {
+        self.load(slot).unwrap_or_default()
+    }
+
+    /// Whether anything is saved in `slot`, without checking it loads.
+    pub fn is_used(&self, slot: u8) -> bool {
+        slot < SLOTS && with_save_bank(|| {
+            matches!(self.scan().0[slot as usize], Some(r) if r.len > 0)
+        })
+    }
+
+    /// Save `value` in `slot`. The vblank NMI is off until it's done.
+    pub fn save<T: Savable>(&mut self, console: &mut Console, slot: u8, value: &T) -> Result<(), SaveError> {
+        self.write(console, slot, T::VERSION, T::SIZE, |w| value.save(w))
+    }
+
+    /// Empty `slot`, so it loads as [`LoadError::Empty`].
+    pub fn clear(&mut self, console: &mut Console, slot: u8) -> Result<(), SaveError> {
+        self.write(console, slot, 0, 0, |_| {})
+    }
+
+    fn write(
+        &mut self,
+        console: &mut Console,
+        slot: u8,
+        version: u8,
+        len: u16,
+        data: impl FnOnce(&mut SaveWriter),
+    ) -> Result<(), SaveError> {
+        if slot >= SLOTS {
+            return Err(SaveError::NoSuchSlot);
+        }
+        if len > AREA_LEN - AREA_HEADER_LEN - RECORD_OVERHEAD {
+            return Err(SaveError::Full);
+        }
+
+        let nmi = console.video_flags.contains(VideoFlags::DMA_NMI);
+        console.video_flags.remove(VideoFlags::DMA_NMI);
+        console.write_video_flags();
+
+        let result = with_save_bank(|| {
+            let fits = |bank: &Self| bank.area.is_some_and(|area| bank.end + RECORD_OVERHEAD + len <= area + AREA_LEN);
+            if !fits(self) {
+                self.compact(slot, len);
+                if !fits(self) {
+                    return Err(SaveError::Full);
+                }
+            }
+            self.append(slot, version, len, data)
+        });
+
+        console.video_flags.set(VideoFlags::DMA_NMI, nmi);
+        console.write_video_flags();
+        result
+    }
+
+    fn append(&mut self, slot: u8, version: u8, len: u16, data: impl FnOnce(&mut SaveWriter)) -> Result<(), SaveError> {
+        let offset = self.end;
+        let [len_lo, len_hi] = len.to_le_bytes();
+        let header = [slot, version, len_lo, len_hi];
+        program(offset, &header);
+        // the log now reaches past this record, whatever happens to it
+        self.end = offset + RECORD_OVERHEAD + len;
+
+        let mut w = SaveWriter { offset: offset + RECORD_HEADER_LEN, len, written: 0, sum: Checksum::new() };
+        for b in header {
+            w.sum.add(b);
+        }
+        data(&mut w);
+        // pad a Savable that wrote less than its SIZE, so the log stays walkable
+        while w.written < len {
+            w.u8(0);
+        }
+
+        program(offset + RECORD_HEADER_LEN + len, &w.sum.bytes());
+        if w.written != len || !Self::is_intact(offset, len) {
+            return Err(SaveError::Verify);
+        }
+        Ok(())
+    }
+
+    /// Copy the newest record of every slot into the other block, mark it
+    /// as in use, then erase the old one. The old record for `slot` is left
+    /// out if keeping it wouldn't leave room for a new one `len` long.
+    fn compact(&mut self, slot: u8, len: u16) {
+        let (records, _) = self.scan();
+        let total: u16 = records.iter().flatten()
+            .filter(|r| r.len > 0)
+            .map(|r| RECORD_OVERHEAD + r.len)
+            .sum();
+        let skip = total + RECORD_OVERHEAD + len > AREA_LEN - AREA_HEADER_LEN;
+
+        let old = self.area;
+        let area = match old {
+            Some(0) => AREA_LEN,
+            _ => 0,
+        };
+
+        unsafe { flash_erase(flash_at(area)) };
+        let mut offset = area + AREA_HEADER_LEN;
+        for (i, record) in records.iter().enumerate() {
+            match record {
+                Some(r) if r.len > 0 && !(skip && i as u8 == slot) => {
+                    program_from_flash(offset, r.offset, RECORD_OVERHEAD + r.len);
+                    offset += RECORD_OVERHEAD + r.len;
+                }
+                _ => {}
+            }
+        }
+
+        let generation = match self.generation.wrapping_add(1) {
+            _ if old.is_none() => 0,
+            ERASED => 0,
+            next => next,
+        };
+        program(area, &[MAGIC[0], MAGIC[1], generation]);
+        if let Some(old) = old {
+            unsafe { flash_erase(flash_at(old)) };
+        }
+
+        self.area = Some(area);
+        self.generation = generation;
+        self.end = offset;
+    }
+}
@@ -0,0 +1,189 @@
+//! # Task Scheduler
+//!
+//! Spreads heavy work (decompressing a level, pathfinding, building a
+//! tilemap) across frames, so it doesn't push a frame past vblank. A task is
+//! a function that does a small step of its job and says whether there's
+//! more. Each frame, [`Scheduler::run`] steps tasks until the frame's
+//! deadline is near, and whatever's left carries over to the next frame.
+//!
+//! ```ignore
+//! use rom::sdk::scheduler::{Scheduler, Step};
+//!
+//! struct World { unpacked: u16, path: PathSearch }
+//!
+//! fn unpack_level(world: &mut World) -> Step {
+//!     unpack_chunk(world.unpacked);
+//!     world.unpacked += 64;
+//!     if world.unpacked < LEVEL_SIZE { Step::Pending } else { Step::Done }
+//! }
+//!
+//! fn find_path(world: &mut World) -> Step {
+//!     if world.path.expand(8) { Step::Done } else { Step::Pending }
+//! }
+//!
+//! let mut tasks: Scheduler<World, 4> = Scheduler::new();
+//! let _ = tasks.spawn(2, unpack_level);
+//! let _ = tasks.spawn(1, find_path);
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     tasks.start_frame();
+//!     update_game_logic(&mut world);
+//!     draw_everything(&mut console);
+//!     // whatever time is left goes to the tasks
+//!     tasks.run(&mut world);
+//! }
+//! ```
+//!
+//! ## Priorities
+//!
+//! Higher priorities go first. A lower priority task only gets a step when
+//! every higher one is done, or has a next step too big to fit before the
+//! deadline, so a high priority task that never finishes starves the rest.
+//! Tasks of the same priority take turns, a step each.
+//!
+//! ## The Deadline
+//!
+//! Time is counted in CPU cycles from [`start_frame`](Scheduler::start_frame),
+//! using the VIA's timer 1 like the profiler (so with the profiler on, call
+//! one or the other's `start_frame`). A step is only started if the time its
+//! task's last step took still fits before the deadline, which is
+//! [`DEFAULT_DEADLINE`] unless [`set_deadline`](Scheduler::set_deadline) or
+//! [`set_deadline_scanline`](Scheduler::set_deadline_scanline) says
+//! otherwise. Keep steps short: a step that runs long still runs to the end.
+
+use crate::pool::{Handle, Pool};
+use crate::via::{Via, FRAME_CYCLES};
+
+/// CPU cycles per scanline, 262 of them a frame
+pub const CYCLES_PER_SCANLINE: u16 = 228;
+
+/// Cycles into the frame that steps stop at by default, leaving ~10
+/// scanlines for the rest of the loop to reach `wait()`
+pub const DEFAULT_DEADLINE: u16 = FRAME_CYCLES - 10 * CYCLES_PER_SCANLINE;
+
+/// What a task's step reports back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// There's more to do; step again when there's time
+    Pending,
+    /// Finished, so the task is removed
+    Done,
+}
+
+struct Task<C> {
+    step: fn(&mut C) -> Step,
+    priority: u8,
+    /// Cycles the last step took, to guess whether the next will fit
+    cost: u16,
+    /// Steps run this frame, so tasks of one priority take turns
+    steps: u8,
+}
+
+/// Up to `N` tasks, each stepped with a `&mut C` shared by all of them.
+/// See the [module docs](self).
+pub struct Scheduler<C, const N: usize> {
+    tasks: Pool<Task<C>, N>,
+    deadline: u16,
+}
+
+impl<C, const N: usize> Scheduler<C, N> {
+    pub const fn new() -> Self {
+        Self { tasks: Pool::new(), deadline: DEFAULT_DEADLINE }
+    }
+
+    /// Add a task at `priority` (higher goes first), or `None` if there are
+    /// already `N`. The handle can [`cancel`](Self::cancel) it.
+    pub fn spawn(&mut self, priority: u8, step: fn(&mut C) -> Step) -> Option<Handle> {
+        self.tasks.insert(Task { step, priority, cost: 0, steps: 0 }).ok()
+    }
+
+    /// Stop a task before it's done. Returns whether it was still running.
+    pub fn cancel(&mut self, handle: Handle) -> bool {
+        self.tasks.remove(handle).is_some()
+    }
+
+    /// Whether `handle`'s task still has work to do
+    #[inline]
+    pub fn is_running(&self, handle: Handle) -> bool {
+        self.tasks.contains(handle)
+    }
+
+    #[inline]
+    pub fn len(&self) -> u8 {
+        self.tasks.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Stop starting steps `cycles` into the frame
+    pub fn set_deadline(&mut self, cycles: u16) {
+        self.deadline = cycles.min(FRAME_CYCLES);
+    }
+
+    /// Stop starting steps at scanline `line`, counting from the vblank
+    /// that `wait()` returns at
+    pub fn set_deadline_scanline(&mut self, line: u16) {
+        self.set_deadline(line.saturating_mul(CYCLES_PER_SCANLINE));
+    }
+
+    /// Restart the frame timer. Call right after waiting for vblank.
+    pub fn start_frame(&mut self) {
+        unsafe { Via::new() }.start_frame_timer();
+    }
+
+    /// Step tasks until the deadline is near or they're all done. Call once
+    /// a frame, after the frame's other work.
+    pub fn run(&mut self, context: &mut C) {
+        let via = unsafe { Via::new() };
+        for (_, task) in self.tasks.iter_mut() {
+            task.steps = 0;
+        }
+
+        loop {
+            let now = via.frame_cycles();
+            if now >= self.deadline {
+                return;
+            }
+            let Some(handle) = self.next(now) else { return };
+            let Some(task) = self.tasks.get_mut(handle) else { return };
+
+            let started = via.frame_cycles();
+            let step = (task.step)(context);
+            task.cost = via.frame_cycles().saturating_sub(started);
+            task.steps = task.steps.saturating_add(1);
+
+            if step == Step::Done {
+                self.tasks.remove(handle);
+            }
+        }
+    }
+
+    /// The task to step next: the highest priority one whose step fits,
+    /// taking turns within a priority
+    fn next(&self, now: u16) -> Option<Handle> {
+        let mut best: Option<(Handle, &Task<C>)> = None;
+        for (handle, task) in self.tasks.iter() {
+            if now.saturating_add(task.cost) > self.deadline {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some((_, b)) => task.priority > b.priority || (task.priority == b.priority && task.steps < b.steps),
+            };
+            if better {
+                best = Some((handle, task));
+            }
+        }
+        best.map(|(handle, _)| handle)
+    }
+}
+
+impl<C, const N: usize> Default for Scheduler<C, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
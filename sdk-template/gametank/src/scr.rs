@@ -16,7 +16,8 @@
 //!
 //! ## BankFlags (`$2005`)
 //!
-//! Controls sprite RAM page, framebuffer selection, and clipping.
+//! Controls sprite RAM page, framebuffer selection, clipping, and which RAM
+//! bank is at `$0000-$1FFF`.
 
 bitflags::bitflags! {
     /// Video/Blitter control flags at `$2007`.
@@ -76,3 +77,37 @@ bitflags::bitflags! {
         const RAM_BANK_3          = 0b1100_0000;
     }
 }
+
+impl BankFlags {
+    /// Mask of the sprite RAM page bits.
+    pub const SPRITE_PAGE_MASK: u8 = 0b0000_0111;
+
+    /// The selected sprite RAM page (0-7).
+    #[inline(always)]
+    pub fn sprite_page(&self) -> u8 {
+        self.bits() & Self::SPRITE_PAGE_MASK
+    }
+
+    /// Select a sprite RAM page (0-7), leaving the other bits untouched.
+    #[inline(always)]
+    pub fn set_sprite_page(&mut self, page: u8) {
+        let bits = (self.bits() & !Self::SPRITE_PAGE_MASK) | (page & Self::SPRITE_PAGE_MASK);
+        *self = Self::from_bits_retain(bits);
+    }
+
+    /// Mask of the RAM bank bits.
+    pub const RAM_BANK_MASK: u8 = 0b1100_0000;
+
+    /// The selected RAM bank (0-3). The program itself always runs in bank
+    /// 0; see [`RamBanks`](crate::banking::RamBanks) for the others.
+    #[inline(always)]
+    pub fn ram_bank(&self) -> u8 {
+        self.bits() >> 6
+    }
+
+    /// These flags with RAM bank `bank` (0-3) selected instead.
+    #[inline(always)]
+    pub fn with_ram_bank(self, bank: u8) -> Self {
+        Self::from_bits_retain((self.bits() & !Self::RAM_BANK_MASK) | (bank << 6))
+    }
+}
@@ -0,0 +1,77 @@
+//! Hooks for `gtrom test`
+//!
+//! `gtrom test` runs the ROM in a headless emulator and watches a few bytes
+//! of RAM that this module writes. The bytes are found by symbol name, so
+//! they cost nothing on hardware beyond three bytes of RAM; a ROM that never
+//! calls these just runs for the frame limit and passes if it doesn't crash.
+//!
+//! ```ignore
+//! use rom::sdk::testing;
+//!
+//! let score = add_points(40, 2);
+//! testing::assert(score == 42, 1);
+//!
+//! draw_title_screen(&mut console);
+//! testing::check_frame(1); // compared against tests/golden/frame-1.crc
+//!
+//! testing::pass();
+//! ```
+//!
+//! Failure codes are yours to pick; `gtrom test` prints them so you can
+//! tell which check failed.
+
+/// Still running (the initial value)
+pub const RUNNING: u8 = 0;
+/// [`pass`] was called
+pub const PASSED: u8 = 1;
+/// [`fail`] was called
+pub const FAILED: u8 = 2;
+
+/// One of [`RUNNING`], [`PASSED`] or [`FAILED`]
+#[unsafe(no_mangle)]
+#[used]
+static mut GT_TEST_STATUS: u8 = RUNNING;
+
+/// The code given to [`fail`]
+#[unsafe(no_mangle)]
+#[used]
+static mut GT_TEST_CODE: u8 = 0;
+
+/// Nonzero asks for the displayed frame to be checked at the next vblank.
+/// The test runner clears it once it has.
+#[unsafe(no_mangle)]
+#[used]
+static mut GT_TEST_FRAME: u8 = 0;
+
+/// End the test run as passed
+#[inline(never)]
+pub fn pass() {
+    unsafe { core::ptr::write_volatile(&raw mut GT_TEST_STATUS, PASSED); }
+}
+
+/// End the test run as failed, reporting `code`
+#[inline(never)]
+pub fn fail(code: u8) {
+    unsafe {
+        core::ptr::write_volatile(&raw mut GT_TEST_CODE, code);
+        core::ptr::write_volatile(&raw mut GT_TEST_STATUS, FAILED);
+    }
+}
+
+/// [`fail`] with `code` unless `cond` holds
+#[inline]
+pub fn assert(cond: bool, code: u8) {
+    if !cond {
+        fail(code);
+    }
+}
+
+/// Compare the frame on screen at the next vblank against the golden
+/// checksum `tests/golden/frame-<id>.crc`. `id` must not be 0.
+///
+/// Call this after flipping to the frame you want checked, then wait for
+/// vblank before drawing over it.
+#[inline(never)]
+pub fn check_frame(id: u8) {
+    unsafe { core::ptr::write_volatile(&raw mut GT_TEST_FRAME, id); }
+}
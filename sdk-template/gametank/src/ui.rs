@@ -0,0 +1,565 @@
+//! # Menus and Text Boxes
+//!
+//! Pause menus, option screens and dialogue, drawn with the built-in
+//! [`Font`]. [`NavInput`] turns a controller into menu moves, repeating a
+//! held direction; [`Menu`] is a list of actions, toggles and choices under
+//! an optional double-height title; [`TextBox`] shows text a page at a
+//! time, revealing it a few characters a frame.
+//!
+//! ```ignore
+//! use rom::sdk::{gfx::text::Font, ui::{Item, Menu, MenuEvent, NavInput}};
+//!
+//! const FONT: Font = Font::new(7);
+//! FONT.load(&mut console, !WHITE);
+//!
+//! let mut nav = NavInput::new();
+//! let mut pause = Menu::new([
+//!     Item::Action("RESUME"),
+//!     Item::Toggle("MUSIC", true),
+//!     Item::Choice("SPEED", &["SLOW", "FAST"], 0),
+//!     Item::Action("QUIT"),
+//! ]).with_title("PAUSED").at(24, 30, 80);
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     console.flip_framebuffers();
+//!     gamepad.read();
+//!
+//!     match pause.update(nav.update(gamepad.buttons)) {
+//!         Some(MenuEvent::Selected(0)) | Some(MenuEvent::Back) => break,
+//!         Some(MenuEvent::Changed(1)) => set_music(pause.is_on(1)),
+//!         Some(MenuEvent::Selected(3)) => quit(),
+//!         _ => {}
+//!     }
+//!
+//!     let mut blitter = console.blitter().unwrap();
+//!     if pause.draw(&mut blitter, &FONT) {
+//!         blitter.wait_blit();
+//!     }
+//! }
+//! ```
+//!
+//! ## Drawing
+//!
+//! Each blit of a character waits for the one before, so a full menu takes
+//! a good part of a frame. Menus and text boxes only draw what changed
+//! since they were last drawn to the framebuffer being drawn to (the two
+//! are tracked separately, as with double buffering each is drawn every
+//! other frame): a menu redraws when it changes, and a text box draws just
+//! the characters revealed since. After drawing anything over them, call
+//! `invalidate` to have them drawn whole again.
+//!
+//! Like [`Tilemap`](crate::gfx::tilemap::Tilemap), this needs a guard from
+//! [`Console::blitter`](crate::console::Console::blitter), which knows
+//! which framebuffer is being drawn to; with any other guard they draw in
+//! full every time.
+
+use bit_field::BitField;
+
+use crate::{
+    gfx::text::{text_width, Font, TextSize, GLYPH_WIDTH},
+    input::Buttons,
+    scr::BankFlags,
+    video_dma::blitter::BlitterGuard,
+};
+
+/// Frames a direction is held before it starts repeating.
+pub const REPEAT_DELAY: u8 = 20;
+
+/// Frames between repeats after that.
+pub const REPEAT_RATE: u8 = 5;
+
+/// Space around a menu's or text box's contents, in pixels
+const PADDING: u8 = 3;
+
+/// Default box color, inverted as for `draw_square`
+const BLACK: u8 = !0u8;
+
+/// Default cursor bar color: dark blue, `0b101_10_010`, inverted
+const DARK_BLUE: u8 = !0xB2;
+
+/// Height of a menu row
+const ROW_HEIGHT: u8 = TextSize::Normal.height() + 2;
+
+/// A move through a menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nav {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// A or Start
+    Confirm,
+    /// B
+    Back,
+}
+
+/// Turns a controller's buttons into [`Nav`]s: one per press, and for a
+/// held direction, another after [`REPEAT_DELAY`] frames and every
+/// [`REPEAT_RATE`] after that.
+pub struct NavInput {
+    last: u8,
+    held: u8,
+}
+
+impl NavInput {
+    pub const fn new() -> Self {
+        Self { last: 0, held: 0 }
+    }
+
+    /// Call once a frame with the buttons down, e.g. `gamepad.buttons` from
+    /// a [`GenesisGamepad`](crate::input::GenesisGamepad) or
+    /// [`BufferedGamepad`](crate::input::BufferedGamepad).
+    pub fn update(&mut self, buttons: u8) -> Option<Nav> {
+        let pressed = buttons & !self.last;
+        self.last = buttons;
+
+        for (button, nav) in [(Buttons::A, Nav::Confirm), (Buttons::Start, Nav::Confirm), (Buttons::B, Nav::Back)] {
+            if pressed.get_bit(button.idx()) {
+                return Some(nav);
+            }
+        }
+
+        let directions = [(Buttons::Up, Nav::Up), (Buttons::Down, Nav::Down), (Buttons::Left, Nav::Left), (Buttons::Right, Nav::Right)];
+        let Some(&(button, nav)) = directions.iter().find(|(button, _)| buttons.get_bit(button.idx())) else {
+            self.held = 0;
+            return None;
+        };
+
+        if pressed.get_bit(button.idx()) {
+            self.held = 0;
+            return Some(nav);
+        }
+        self.held += 1;
+        if self.held >= REPEAT_DELAY {
+            self.held = REPEAT_DELAY - REPEAT_RATE;
+            return Some(nav);
+        }
+        None
+    }
+}
+
+impl Default for NavInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Which framebuffer `blitter` draws to, if it can tell
+fn framebuffer(blitter: &BlitterGuard) -> Option<usize> {
+    blitter.bank_flags.as_deref().map(|flags| flags.contains(BankFlags::FRAMEBUFFER_SELECT) as usize)
+}
+
+/// One line of a [`Menu`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Item {
+    /// Reports [`MenuEvent::Selected`] when confirmed
+    Action(&'static str),
+    /// On or off, flipped by confirm, left or right
+    Toggle(&'static str, bool),
+    /// The selected one of the options, stepped through with left and right
+    Choice(&'static str, &'static [&'static str], u8),
+}
+
+impl Item {
+    fn label(&self) -> &'static str {
+        match *self {
+            Item::Action(label) | Item::Toggle(label, _) | Item::Choice(label, _, _) => label,
+        }
+    }
+
+    /// What's shown on the right
+    fn value(&self) -> Option<&'static str> {
+        match *self {
+            Item::Action(_) => None,
+            Item::Toggle(_, on) => Some(if on { "ON" } else { "OFF" }),
+            Item::Choice(_, options, selected) => options.get(selected as usize).copied(),
+        }
+    }
+}
+
+/// What [`Menu::update`] did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuEvent {
+    /// The action at this index was confirmed
+    Selected(u8),
+    /// The toggle or choice at this index changed
+    Changed(u8),
+    /// B was pressed
+    Back,
+}
+
+/// A vertical menu with a cursor. See the [module docs](self).
+pub struct Menu<const N: usize> {
+    items: [Item; N],
+    title: Option<&'static str>,
+    cursor: u8,
+    x: u8,
+    y: u8,
+    width: u8,
+    /// Box color, given as for `draw_square`
+    background: u8,
+    /// Bar behind the selected item
+    highlight: u8,
+    /// Whether each framebuffer shows the menu as it is
+    drawn: [bool; 2],
+}
+
+impl<const N: usize> Menu<N> {
+    /// A menu of `items` with the cursor on the first, at the top-left of
+    /// the screen, black with a dark blue cursor bar.
+    pub const fn new(items: [Item; N]) -> Self {
+        Self {
+            items,
+            title: None,
+            cursor: 0,
+            x: 0,
+            y: 0,
+            width: 128,
+            background: BLACK,
+            highlight: DARK_BLUE,
+            drawn: [false; 2],
+        }
+    }
+
+    /// Draw it at `(x, y)`, `width` pixels wide.
+    pub const fn at(mut self, x: u8, y: u8, width: u8) -> Self {
+        self.x = x;
+        self.y = y;
+        self.width = width;
+        self
+    }
+
+    /// Put `title` above the items in double-height text.
+    pub const fn with_title(mut self, title: &'static str) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    /// Colors of the box and the cursor bar, given as for `draw_square`.
+    pub const fn colors(mut self, background: u8, highlight: u8) -> Self {
+        self.background = background;
+        self.highlight = highlight;
+        self
+    }
+
+    #[inline(always)]
+    pub fn cursor(&self) -> u8 {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, index: u8) {
+        if (index as usize) < N && index != self.cursor {
+            self.cursor = index;
+            self.invalidate();
+        }
+    }
+
+    #[inline(always)]
+    pub fn item(&self, index: u8) -> &Item {
+        &self.items[index as usize]
+    }
+
+    /// Change an item, e.g. to set a toggle from saved settings.
+    pub fn set_item(&mut self, index: u8, item: Item) {
+        self.items[index as usize] = item;
+        self.invalidate();
+    }
+
+    /// Whether the toggle at `index` is on; false for other items.
+    pub fn is_on(&self, index: u8) -> bool {
+        matches!(self.items[index as usize], Item::Toggle(_, true))
+    }
+
+    /// The option selected by the choice at `index`; 0 for other items.
+    pub fn choice(&self, index: u8) -> u8 {
+        match self.items[index as usize] {
+            Item::Choice(_, _, selected) => selected,
+            _ => 0,
+        }
+    }
+
+    /// Height of the whole menu in pixels.
+    pub const fn height(&self) -> u8 {
+        let title = match self.title {
+            Some(_) => TextSize::Tall.height() + 2,
+            None => 0,
+        };
+        2 * PADDING + title + N as u8 * ROW_HEIGHT
+    }
+
+    /// Draw it whole next time, to both framebuffers.
+    pub fn invalidate(&mut self) {
+        self.drawn = [false; 2];
+    }
+
+    /// Move the cursor or change the selected item with this frame's
+    /// [`Nav`], if any. The cursor wraps from one end to the other.
+    pub fn update(&mut self, nav: Option<Nav>) -> Option<MenuEvent> {
+        let nav = nav?;
+        let index = self.cursor;
+        let item = &mut self.items[index as usize];
+
+        let event = match (nav, item) {
+            (Nav::Up, _) => {
+                self.cursor = if self.cursor == 0 { N as u8 - 1 } else { self.cursor - 1 };
+                None
+            }
+            (Nav::Down, _) => {
+                self.cursor = if self.cursor as usize + 1 >= N { 0 } else { self.cursor + 1 };
+                None
+            }
+            (Nav::Back, _) => return Some(MenuEvent::Back),
+            (Nav::Confirm, Item::Action(_)) => return Some(MenuEvent::Selected(index)),
+            (Nav::Left | Nav::Right | Nav::Confirm, Item::Toggle(_, on)) => {
+                *on = !*on;
+                Some(MenuEvent::Changed(index))
+            }
+            (Nav::Left, Item::Choice(_, options, selected)) if !options.is_empty() => {
+                *selected = if *selected == 0 { options.len() as u8 - 1 } else { *selected - 1 };
+                Some(MenuEvent::Changed(index))
+            }
+            (Nav::Right | Nav::Confirm, Item::Choice(_, options, selected)) if !options.is_empty() => {
+                *selected = if *selected as usize + 1 >= options.len() { 0 } else { *selected + 1 };
+                Some(MenuEvent::Changed(index))
+            }
+            _ => return None,
+        };
+
+        self.invalidate();
+        event
+    }
+
+    /// Draw the menu if the framebuffer being drawn to doesn't show it as
+    /// it is. Returns whether it drew anything, in which case the caller
+    /// waits for the last blit.
+    pub fn draw(&mut self, blitter: &mut BlitterGuard, font: &Font) -> bool {
+        let fb = framebuffer(blitter);
+        if fb.is_some_and(|fb| self.drawn[fb]) {
+            return false;
+        }
+
+        blitter.draw_square(self.x, self.y, self.width, self.height(), self.background);
+        let text_x = self.x + PADDING;
+        let mut y = self.y + PADDING;
+
+        if let Some(title) = self.title {
+            let centered = self.x + self.width.saturating_sub(text_width(title)) / 2;
+            blitter.wait_blit();
+            font.draw(blitter, centered, y, title, TextSize::Tall);
+            y += TextSize::Tall.height() + 2;
+        }
+
+        for (i, item) in self.items.iter().enumerate() {
+            let row_y = y + 1;
+            blitter.wait_blit();
+            if i == self.cursor as usize {
+                blitter.draw_square(self.x + 1, y, self.width.saturating_sub(2), ROW_HEIGHT, self.highlight);
+                blitter.wait_blit();
+                font.draw(blitter, text_x, row_y, ">", TextSize::Normal);
+                blitter.wait_blit();
+            }
+            font.draw(blitter, text_x + 2 * GLYPH_WIDTH, row_y, item.label(), TextSize::Normal);
+
+            if let Some(value) = item.value() {
+                let value_x = (self.x + self.width).saturating_sub(PADDING + text_width(value));
+                blitter.wait_blit();
+                font.draw(blitter, value_x, row_y, value, TextSize::Normal);
+            }
+            y += ROW_HEIGHT;
+        }
+
+        if let Some(fb) = fb {
+            self.drawn[fb] = true;
+        }
+        true
+    }
+}
+
+/// Where a line of text starting at `start` ends, in at most `columns`
+/// characters, and where the next one starts. Breaks at the last space that
+/// fits, or mid-word if there isn't one.
+fn wrap(text: &[u8], start: usize, columns: usize) -> (usize, usize) {
+    let mut last_space = None;
+    let mut i = start;
+    while i < text.len() && i - start < columns {
+        match text[i] {
+            b'\n' => return (i, i + 1),
+            b' ' => last_space = Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if i >= text.len() {
+        return (text.len(), text.len());
+    }
+    match (text[i], last_space) {
+        (b' ' | b'\n', _) => (i, i + 1),
+        (_, Some(space)) => (space, space + 1),
+        _ => (i, i),
+    }
+}
+
+/// Text in a box, a page of lines at a time, revealed a few characters a
+/// frame. Confirming shows the rest of the page, then turns to the next.
+pub struct TextBox<'a> {
+    text: &'a str,
+    x: u8,
+    y: u8,
+    width: u8,
+    lines: u8,
+    /// Characters revealed per frame, 0 for the whole page at once
+    speed: u8,
+    background: u8,
+    /// Start of the page shown
+    page: usize,
+    /// Characters of the page revealed
+    shown: usize,
+    /// Characters of the page each framebuffer shows, or `None` before its
+    /// box is drawn
+    drawn: [Option<usize>; 2],
+}
+
+impl<'a> TextBox<'a> {
+    /// `text` in a black box at `(x, y)`, `width` pixels wide and `lines`
+    /// lines tall, revealed two characters a frame.
+    pub const fn new(text: &'a str, x: u8, y: u8, width: u8, lines: u8) -> Self {
+        Self {
+            text,
+            x,
+            y,
+            width,
+            lines,
+            speed: 2,
+            background: BLACK,
+            page: 0,
+            shown: 0,
+            drawn: [None; 2],
+        }
+    }
+
+    /// Reveal `speed` characters a frame, or 0 for each page at once.
+    pub const fn with_speed(mut self, speed: u8) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Box color, given as for `draw_square`.
+    pub const fn with_background(mut self, background: u8) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub const fn height(&self) -> u8 {
+        2 * PADDING + self.lines * TextSize::Normal.height()
+    }
+
+    fn columns(&self) -> usize {
+        (self.width.saturating_sub(2 * PADDING) / GLYPH_WIDTH).max(1) as usize
+    }
+
+    /// Where the page starting at `start` ends, and where the next starts
+    fn page_end(&self, start: usize) -> (usize, usize) {
+        let (mut end, mut next) = (start, start);
+        for _ in 0..self.lines {
+            (end, next) = wrap(self.text.as_bytes(), next, self.columns());
+        }
+        (end, next)
+    }
+
+    /// Whether the whole page is showing
+    pub fn is_page_shown(&self) -> bool {
+        self.page + self.shown >= self.page_end(self.page).0
+    }
+
+    /// Whether there's a page after this one
+    pub fn has_more(&self) -> bool {
+        self.page_end(self.page).1 < self.text.len()
+    }
+
+    /// Draw it whole next time, to both framebuffers.
+    pub fn invalidate(&mut self) {
+        self.drawn = [None; 2];
+    }
+
+    /// Start over with new text.
+    pub fn set_text(&mut self, text: &'a str) {
+        self.text = text;
+        self.page = 0;
+        self.shown = 0;
+        self.invalidate();
+    }
+
+    /// Call once a frame. Reveals more of the page, and on
+    /// [`Nav::Confirm`] shows the rest of it or turns to the next. Returns
+    /// true when confirmed on the last page, all shown.
+    pub fn update(&mut self, nav: Option<Nav>) -> bool {
+        let end = self.page_end(self.page).0 - self.page;
+        if nav == Some(Nav::Confirm) {
+            if self.shown < end {
+                self.shown = end;
+            } else if self.has_more() {
+                self.page = self.page_end(self.page).1;
+                self.shown = 0;
+                self.invalidate();
+            } else {
+                return true;
+            }
+        } else if self.speed == 0 {
+            self.shown = end;
+        } else {
+            self.shown = (self.shown + self.speed as usize).min(end);
+        }
+        false
+    }
+
+    /// Draw the box and what's been revealed since it was last drawn to
+    /// this framebuffer. Returns whether it drew anything, in which case
+    /// the caller waits for the last blit.
+    pub fn draw(&mut self, blitter: &mut BlitterGuard, font: &Font) -> bool {
+        let fb = framebuffer(blitter);
+        let mut drew = false;
+        let from = match fb.and_then(|fb| self.drawn[fb]) {
+            Some(drawn) if drawn >= self.shown => return false,
+            Some(drawn) => drawn,
+            None => {
+                blitter.draw_square(self.x, self.y, self.width, self.height(), self.background);
+                drew = true;
+                0
+            }
+        };
+        let (from, to) = (self.page + from, self.page + self.shown);
+
+        let mut start = self.page;
+        let mut y = self.y + PADDING;
+        for _ in 0..self.lines {
+            let (end, next) = wrap(self.text.as_bytes(), start, self.columns());
+            let (first, last) = (start.max(from), end.min(to));
+            if first < last {
+                let x = self.x + PADDING + (first - start) as u8 * GLYPH_WIDTH;
+                if drew {
+                    blitter.wait_blit();
+                }
+                font.draw(blitter, x, y, self.text.get(first..last).unwrap_or("?"), TextSize::Normal);
+                drew = true;
+            }
+            start = next;
+            y += TextSize::Normal.height();
+        }
+
+        if self.shown >= self.page_end(self.page).0 - self.page && self.has_more() {
+            if drew {
+                blitter.wait_blit();
+            }
+            let x = (self.x + self.width).saturating_sub(PADDING + GLYPH_WIDTH);
+            font.draw(blitter, x, y - TextSize::Normal.height(), ">", TextSize::Normal);
+            drew = true;
+        }
+
+        if let Some(fb) = fb {
+            self.drawn[fb] = Some(self.shown);
+        }
+        drew
+    }
+}
@@ -37,20 +37,33 @@
 use bit_field::BitField;
 use volatile_register::{RW, WO};
 
+/// Shadow of the write-only ROM bank register
+static mut ROM_BANK: u8 = 0;
+/// Set while [`Via::change_rom_bank`] is shifting a bank number out
+static mut SWITCHING_BANK: bool = false;
+
+/// CPU cycles in a 60 Hz frame, at ~3.58 MHz.
+pub const FRAME_CYCLES: u16 = 59659;
+
+/// Timer 1 bit in the interrupt flags, set when it runs out.
+const IRQ_T1: u8 = 0b0100_0000;
+
 #[repr(C, packed)]
 pub struct Via {
     pub iorb: RW<u8>, // input/output register b
     pub iora: RW<u8>, // input/output register a
-    pub ddrb: WO<u8>, //
+    pub ddrb: RW<u8>, // port b directions, 1 for output
     pub ddra: WO<u8>,
-    pub t1cl: WO<u8>,
-    pub t1ch: WO<u8>,
+    pub t1cl: RW<u8>, // timer 1 counter; writes go to the latch
+    pub t1ch: RW<u8>, // writing loads the latch into the counter
+    pub t1ll: RW<u8>,
+    pub t1lh: RW<u8>,
     pub t2cl: WO<u8>,
     pub t2ch: WO<u8>,
-    pub sr: WO<u8>,
-    pub acr: WO<u8>,
-    pub pcr: WO<u8>,
-    pub ifr: WO<u8>,
+    pub sr: RW<u8>,
+    pub acr: RW<u8>,
+    pub pcr: RW<u8>,
+    pub ifr: RW<u8>,
     pub era: WO<u8>,
     pub iora_nh: WO<u8>,
 }
@@ -60,9 +73,25 @@ impl Via {
         unsafe { &mut *(0x2800 as *mut Via) }
     }
 
+    /// The bank last selected with [`change_rom_bank`](Self::change_rom_bank)
+    #[inline(always)]
+    pub fn rom_bank(&self) -> u8 {
+        unsafe { ROM_BANK }
+    }
+
+    /// Whether a bank switch is half done. The bank number goes out a bit
+    /// at a time, so an interrupt handler that lands in the middle of one
+    /// mustn't switch banks itself.
+    #[inline(always)]
+    pub fn is_switching_bank(&self) -> bool {
+        unsafe { core::ptr::read_volatile(&raw const SWITCHING_BANK) }
+    }
+
     #[inline(always)]
     pub fn change_rom_bank(&mut self, banknum: u8) {
         unsafe {
+            core::ptr::write_volatile(&raw mut SWITCHING_BANK, true);
+            ROM_BANK = banknum;
             self.ddra.write(0b00000111); // I have no idea what this does
             self.iora.write(0);
             self.iora.write((banknum.get_bit(7) as u8) << 1);
@@ -83,6 +112,44 @@ impl Via {
             self.iora.write(*self.iora.read().set_bit(0, true));
             self.iora.write(*self.iora.read().set_bit(2, true));
             self.iora.write(0);
+            core::ptr::write_volatile(&raw mut SWITCHING_BANK, false);
+        }
+    }
+
+    /// Run timer 1 down from $FFFF as a frame timer, for
+    /// [`frame_cycles`](Self::frame_cycles). Call right after waiting for
+    /// vblank. The profiler and scheduler both use it.
+    pub fn start_frame_timer(&mut self) {
+        unsafe {
+            // one-shot, so it keeps counting down past zero; the other bits
+            // belong to the expansion port
+            self.acr.modify(|acr| acr & 0x3F);
+            self.t1cl.write(0xFF);
+            self.t1ch.write(0xFF); // loads $FFFF and starts counting
+        }
+    }
+
+    /// Cycles since [`start_frame_timer`](Self::start_frame_timer), or
+    /// `u16::MAX` once the timer's run out.
+    pub fn frame_cycles(&self) -> u16 {
+        // reading the counter clears the flag, so look at it first
+        if self.ifr.read() & IRQ_T1 != 0 {
+            return u16::MAX;
+        }
+        self.timer1_elapsed()
+    }
+
+    /// Cycles timer 1 has counted down from $FFFF, without checking whether
+    /// it ran out
+    #[inline(always)]
+    pub fn timer1_elapsed(&self) -> u16 {
+        // the low byte can roll over between reads, so read high until it holds still
+        loop {
+            let high = self.t1ch.read();
+            let low = self.t1cl.read();
+            if self.t1ch.read() == high {
+                return !u16::from_le_bytes([low, high]);
+            }
         }
     }
 
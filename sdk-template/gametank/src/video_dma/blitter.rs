@@ -17,6 +17,22 @@
 //! blitter.wait_blit();
 //! ```
 //!
+//! ## Flipping, Transparency and Tiling
+//!
+//! [`draw_sprite_with`](BlitterGuard::draw_sprite_with) takes a
+//! [`DrawOptions`] for the blit, instead of setting `VideoFlags` by hand:
+//!
+//! ```ignore
+//! // Face left, letting the background show through color 0
+//! let facing_left = DrawOptions::DEFAULT.flip_x().transparent();
+//! blitter.draw_sprite_with(0, 0, x, y, 16, 16, facing_left);
+//! blitter.wait_blit();
+//!
+//! // Repeat a 16x16 tile across a 64x32 area
+//! blitter.draw_sprite_with(32, 0, 0, 96, 64, 32, DrawOptions::DEFAULT.tiled());
+//! blitter.wait_blit();
+//! ```
+//!
 //! ## Parallel Execution
 //!
 //! The blitter is a separate piece of hardware. While it's drawing, your CPU
@@ -58,7 +74,8 @@
 use crate::{
     boot::wait,
     blitter::{Bcr, SpriteQuadrant},
-    scr::VideoFlags,
+    gfx::MetaSprite,
+    scr::{BankFlags, VideoFlags},
     video_dma::{framebuffers::Framebuffers, spritemem::SpriteMem, VideoDma},
 };
 
@@ -90,6 +107,76 @@ fn write_video_flags(flags: VideoFlags) {
     }
 }
 
+/// Write bank flags to the hardware register at $2005.
+#[inline(always)]
+fn write_bank_flags(flags: BankFlags) {
+    unsafe {
+        core::ptr::write_volatile(0x2005 as *mut u8, flags.bits());
+    }
+}
+
+/// Bit 7 of the width and height registers flips the blit on that axis
+const FLIP: u8 = 0x80;
+
+/// How [`BlitterGuard::draw_sprite_with`] draws a sprite.
+///
+/// [`DEFAULT`](Self::DEFAULT) matches how [`Console::init`](crate::console::Console::init)
+/// sets up the blitter: unflipped, opaque, and reading the whole width and
+/// height from sprite RAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DrawOptions {
+    /// Mirror left to right
+    pub flip_x: bool,
+    /// Mirror top to bottom
+    pub flip_y: bool,
+    /// Draw color 0 too, rather than leaving what's under it
+    pub opaque: bool,
+    /// Read the whole rectangle from sprite RAM. Without it, the source
+    /// wraps every 16 pixels, tiling a 16x16 sprite across the rectangle.
+    pub gcarry: bool,
+}
+
+impl DrawOptions {
+    pub const DEFAULT: Self = Self { flip_x: false, flip_y: false, opaque: true, gcarry: true };
+
+    pub const fn flip_x(self) -> Self {
+        Self { flip_x: !self.flip_x, ..self }
+    }
+
+    pub const fn flip_y(self) -> Self {
+        Self { flip_y: !self.flip_y, ..self }
+    }
+
+    /// Skip color 0
+    pub const fn transparent(self) -> Self {
+        Self { opaque: false, ..self }
+    }
+
+    /// Repeat the 16x16 sprite at the source across the rectangle
+    pub const fn tiled(self) -> Self {
+        Self { gcarry: false, ..self }
+    }
+}
+
+impl Default for DrawOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The source coordinate and size register for one axis of a blit. A
+/// flipped blit reads backwards from the inverted coordinate, so it starts
+/// from the far edge of the sprite.
+#[inline(always)]
+fn flip_axis(src: u8, size: u8, flip: bool, gcarry: bool) -> (u8, u8) {
+    if !flip {
+        return (src, size);
+    }
+    // without gcarry the read only spans a 16 pixel tile
+    let span = if gcarry { size } else { size.min(16) };
+    (!src.wrapping_add(span.wrapping_sub(1)), size | FLIP)
+}
+
 /// Exclusive access to the blitter hardware.
 ///
 /// While you hold a `BlitterGuard`, you can perform drawing operations.
@@ -106,6 +193,8 @@ fn write_video_flags(flags: VideoFlags) {
 pub struct BlitterGuard<'a> {
     pub(crate) dma_slot: &'a mut Option<VideoDma>,
     pub(crate) video_flags: &'a mut VideoFlags,
+    /// Set when the guard came from [`Console::blitter`](crate::console::Console::blitter)
+    pub(crate) bank_flags: Option<&'a mut BankFlags>,
     #[allow(dead_code)]
     pub(crate) inner: Blitter,
 }
@@ -198,6 +287,51 @@ impl<'a> BlitterGuard<'a> {
         }
     }
 
+    /// Copy a rectangular region from sprite RAM to the framebuffer, flipped,
+    /// transparent or tiled as `options` say.
+    ///
+    /// Arguments are as for [`draw_sprite`](Self::draw_sprite), with the
+    /// width and height at most 127: bit 7 of each is the flip. The
+    /// transparency and tiling stay set for later `draw_sprite` calls.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = DrawOptions { flip_x: enemy.facing_left, ..DrawOptions::DEFAULT };
+    /// blitter.draw_sprite_with(16, 0, enemy.x, enemy.y, 16, 16, options);
+    /// blitter.wait_blit();
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    #[inline(always)]
+    pub fn draw_sprite_with(
+        &mut self,
+        sx: u8,
+        sy: u8,
+        fb_x: u8,
+        fb_y: u8,
+        width: u8,
+        height: u8,
+        options: DrawOptions,
+    ) {
+        self.video_flags.remove(VideoFlags::DMA_COLORFILL);
+        self.video_flags.set(VideoFlags::DMA_OPAQUE, options.opaque);
+        self.video_flags.set(VideoFlags::DMA_GCARRY, options.gcarry);
+        write_video_flags(*self.video_flags);
+
+        let (gx, width) = flip_axis(sx, width, options.flip_x, options.gcarry);
+        let (gy, height) = flip_axis(sy, height, options.flip_y, options.gcarry);
+        unsafe {
+            let bcr = Bcr::new();
+            bcr.vram_x.write(gx);
+            bcr.vram_y.write(gy);
+            bcr.fb_x.write(fb_x);
+            bcr.fb_y.write(fb_y);
+            bcr.width.write(width);
+            bcr.height.write(height);
+            bcr.start.write(1);
+        }
+    }
+
     /// Set the sprite RAM quadrant for subsequent operations.
     ///
     /// Sprite RAM is organized as 256×512 pixels. This selects which
@@ -215,6 +349,84 @@ impl<'a> BlitterGuard<'a> {
         }
     }
 
+    /// Select the sprite RAM page (0-7) to draw from, if this guard came
+    /// from [`Console::blitter`](crate::console::Console::blitter). Guards
+    /// from [`DmaManager::blitter`](super::DmaManager::blitter) can't see
+    /// the bank register and leave the page alone.
+    ///
+    /// Wait for any running blit first: it reads from the new page too.
+    #[inline(always)]
+    pub fn set_sprite_page(&mut self, page: u8) {
+        if let Some(bank_flags) = self.bank_flags.as_deref_mut() {
+            bank_flags.set_sprite_page(page);
+            write_bank_flags(*bank_flags);
+        }
+    }
+
+    /// Stop blits at the screen edges instead of wrapping around to the
+    /// other side: `clip_x` for left and right, `clip_y` for top and bottom.
+    /// Like [`set_sprite_page`](Self::set_sprite_page), only guards from
+    /// [`Console::blitter`](crate::console::Console::blitter) can do this.
+    #[inline(always)]
+    pub fn set_clip(&mut self, clip_x: bool, clip_y: bool) {
+        if let Some(bank_flags) = self.bank_flags.as_deref_mut() {
+            bank_flags.set(BankFlags::CLIP_X, clip_x);
+            bank_flags.set(BankFlags::CLIP_Y, clip_y);
+            write_bank_flags(*bank_flags);
+        }
+    }
+
+    /// The selected sprite RAM page, or `None` for a guard that can't
+    /// switch pages.
+    #[inline(always)]
+    pub fn sprite_page(&self) -> Option<u8> {
+        self.bank_flags.as_deref().map(BankFlags::sprite_page)
+    }
+
+    /// Draw every part of `sprite` with its origin at `(x, y)`.
+    ///
+    /// Waits between parts, so the caller only has to wait for the last
+    /// one, as with [`draw_sprite`](Self::draw_sprite). Parts that would
+    /// start off-screen are skipped. If a part switched the sprite page,
+    /// this waits for it and switches back before returning.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut blitter = console.blitter().unwrap();
+    /// blitter.draw_metasprite(&HERO, 40, 60);
+    /// blitter.wait_blit();
+    /// ```
+    pub fn draw_metasprite(&mut self, sprite: &MetaSprite, x: u8, y: u8) {
+        let start_page = self.sprite_page();
+        let mut page = start_page;
+        let mut drawn = false;
+
+        for part in sprite.parts() {
+            let (Some(fb_x), Some(fb_y)) = (x.checked_add_signed(part.dx), y.checked_add_signed(part.dy)) else {
+                continue;
+            };
+            if fb_x >= 128 || fb_y >= 128 {
+                continue;
+            }
+
+            if drawn {
+                self.wait_blit();
+            }
+            if page.is_some() && page != Some(part.page) {
+                self.set_sprite_page(part.page);
+                page = Some(part.page);
+            }
+            self.draw_sprite(part.sx, part.sy, fb_x, fb_y, part.width, part.height);
+            drawn = true;
+        }
+
+        if let Some(start_page) = start_page.filter(|&p| Some(p) != page) {
+            self.wait_blit();
+            self.set_sprite_page(start_page);
+        }
+    }
+
     /// Wait for the current blit operation to complete.
     ///
     /// **Must be called** after each draw operation before starting another,
@@ -72,7 +72,7 @@ pub mod framebuffers;
 pub mod spritemem;
 
 use crate::{
-    scr::VideoFlags,
+    scr::{BankFlags, VideoFlags},
     video_dma::{
         blitter::{Blitter, BlitterGuard},
         framebuffers::{Framebuffers, FramebuffersGuard},
@@ -127,10 +127,19 @@ impl DmaManager {
         Some(BlitterGuard {
             dma_slot: &mut self.video_dma,
             video_flags: vf,
+            bank_flags: None,
             inner: b,
         })
     }
 
+    /// [`blitter`](Self::blitter), with the bank register shadow so the
+    /// guard can switch sprite pages.
+    pub(crate) fn blitter_with_banks<'a>(&'a mut self, vf: &'a mut VideoFlags, bf: &'a mut BankFlags) -> Option<BlitterGuard<'a>> {
+        let mut guard = self.blitter(vf)?;
+        guard.bank_flags = Some(bf);
+        Some(guard)
+    }
+
     /// Get exclusive access to the framebuffers.
     ///
     /// Returns `None` if video hardware is currently in use.
@@ -0,0 +1,155 @@
+//! # Frame Watchdog
+//!
+//! Catches frames that take longer than a vblank. [`start`] counts vblanks
+//! in the NMI, and a [`Watchdog`] checked once a frame, right after
+//! `wait()`, sees how many went by since the last check. More than one
+//! means the last frame overran and the game skipped a vblank.
+//!
+//! ```ignore
+//! use rom::sdk::watchdog::{self, Watchdog};
+//!
+//! watchdog::start();
+//! let mut watchdog = Watchdog::new();
+//!
+//! loop {
+//!     unsafe { wait(); }
+//!     watchdog.check();
+//!     console.flip_framebuffers();
+//!
+//!     let mut blitter = console.blitter().unwrap();
+//!     draw_everything(&mut blitter);
+//!     watchdog.draw_border(&mut blitter);
+//! }
+//! ```
+//!
+//! ## Seeing Overruns
+//!
+//! [`draw_border`](Watchdog::draw_border) flashes a red border around the
+//! screen for [`FLASH_FRAMES`] after an overrun, so slow spots stand out
+//! while play testing. Each overrun is also logged to the emulator's
+//! [debug port](crate::debug), and counted in `GT_FRAME_OVERRUNS`, which
+//! `gtrom test` reports at the end of a run and test scripts can find with
+//! `sym`.
+
+// colors are written hue_saturation_luminance
+#![allow(clippy::unusual_byte_groupings)]
+
+use core::{fmt::Write, ptr};
+
+use crate::{debug::DebugPort, irq, video_dma::blitter::BlitterGuard};
+
+/// Frames the border flashes for after an overrun: half a second
+pub const FLASH_FRAMES: u8 = 30;
+
+/// Width of the border in pixels
+const BORDER: u8 = 2;
+
+/// Red, before inverting for the blitter
+const BORDER_COLOR: u8 = 0b010_11_100;
+
+/// Vblanks since [`start`], wrapping
+static mut VBLANKS: u8 = 0;
+
+/// Frames that overran since power-on, for `gtrom test` and scripts
+#[unsafe(no_mangle)]
+#[used]
+static mut GT_FRAME_OVERRUNS: u16 = 0;
+
+/// The vblank handler
+fn count_vblank() {
+    unsafe { ptr::write_volatile(&raw mut VBLANKS, vblanks().wrapping_add(1)) }
+}
+
+#[inline]
+fn vblanks() -> u8 {
+    unsafe { ptr::read_volatile(&raw const VBLANKS) }
+}
+
+/// Start counting vblanks for [`Watchdog`]. Returns `false` if the
+/// [`irq::on_vblank`] table is full.
+pub fn start() -> bool {
+    // don't count twice a vblank if it's called again
+    irq::remove_vblank(count_vblank);
+    irq::on_vblank(count_vblank)
+}
+
+/// Stop counting vblanks. Returns `false` if it wasn't on.
+pub fn stop() -> bool {
+    irq::remove_vblank(count_vblank)
+}
+
+/// Frames that overran since power-on
+pub fn overruns() -> u16 {
+    unsafe { ptr::read_volatile(&raw const GT_FRAME_OVERRUNS) }
+}
+
+/// Checks each frame for missed vblanks. See the [module docs](self).
+pub struct Watchdog {
+    /// [`vblanks`] at the last check
+    last: u8,
+    /// Vblanks the last frame missed
+    missed: u8,
+    /// Frames left to flash the border for
+    flash: u8,
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        Self { last: vblanks(), missed: 0, flash: 0 }
+    }
+
+    /// Call once a frame, right after `wait()`. Returns how many vblanks
+    /// the last frame missed, 0 if it fit in one.
+    pub fn check(&mut self) -> u8 {
+        let now = vblanks();
+        self.missed = now.wrapping_sub(self.last).saturating_sub(1);
+        self.last = now;
+
+        if self.missed > 0 {
+            self.flash = FLASH_FRAMES;
+            unsafe { ptr::write_volatile(&raw mut GT_FRAME_OVERRUNS, overruns().wrapping_add(1)) };
+            // no formatting, which could make this frame overrun too
+            let _ = DebugPort.write_str("watchdog: frame overran\n");
+        } else {
+            self.flash = self.flash.saturating_sub(1);
+        }
+        self.missed
+    }
+
+    /// Vblanks the last frame missed, as of the last [`check`](Self::check)
+    pub fn missed(&self) -> u8 {
+        self.missed
+    }
+
+    /// Whether a frame overran in the last [`FLASH_FRAMES`]
+    pub fn flashing(&self) -> bool {
+        self.flash > 0
+    }
+
+    /// Draw the border if a frame overran recently, blinking every 4
+    /// frames, and wait for the last blit. Draw it last so nothing covers it.
+    pub fn draw_border(&self, blitter: &mut BlitterGuard) {
+        if self.flash & 4 == 0 {
+            return;
+        }
+
+        // a blit is at most 127 pixels across, so each side is two halves
+        for half in [0, 64] {
+            for (x, y, w, h) in [
+                (half, 0, 64, BORDER),
+                (half, 128 - BORDER, 64, BORDER),
+                (0, half, BORDER, 64),
+                (128 - BORDER, half, BORDER, 64),
+            ] {
+                blitter.draw_square(x, y, w, h, !BORDER_COLOR);
+                blitter.wait_blit();
+            }
+        }
+    }
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,110 @@
+; Programming and erasing the save bank (see gametank::save)
+;
+; The flash can't be read while it programs or erases, and that includes
+; fetching instructions from the fixed bank. So these live in .data, which
+; boot copies into RAM, and only return once the flash is idle again. The
+; caller selects the save bank at $8000 and turns off the vblank NMI; IRQs
+; are off while these run.
+
+.section .data.zp,"aw",@progbits
+.Lsrc:      .word 0
+.Ldest:     .word 0
+.Lcount:    .word 0
+
+.section .data.flash,"awx",@progbits
+.global flash_program, flash_erase
+
+; void flash_program(const FlashWrite *args)
+;   __rc2/__rc3 = args:
+;     +0 destination in $8000-$BFFF, +2 source, +4 length
+flash_program:
+    PHP
+    SEI
+    LDY #0
+    LDA (__rc2),Y
+    STA .Ldest
+    INY
+    LDA (__rc2),Y
+    STA .Ldest+1
+    INY
+    LDA (__rc2),Y
+    STA .Lsrc
+    INY
+    LDA (__rc2),Y
+    STA .Lsrc+1
+    INY
+    LDA (__rc2),Y
+    STA .Lcount
+    INY
+    LDA (__rc2),Y
+    STA .Lcount+1
+
+.Lbyte:
+    LDA .Lcount
+    ORA .Lcount+1
+    BEQ .Ldone
+    LDA #0xAA
+    STA 0x8AAA
+    LDA #0x55
+    STA 0x8555
+    LDA #0xA0
+    STA 0x8AAA
+    LDY #0
+    LDA (.Lsrc),Y
+    STA (.Ldest),Y
+    JSR .Lwait
+
+    INC .Lsrc
+    BNE 1f
+    INC .Lsrc+1
+1:
+    INC .Ldest
+    BNE 2f
+    INC .Ldest+1
+2:
+    LDA .Lcount
+    BNE 3f
+    DEC .Lcount+1
+3:
+    DEC .Lcount
+    JMP .Lbyte
+
+.Ldone:
+    PLP
+    RTS
+
+; void flash_erase(u8 *block)
+;   __rc2/__rc3 = any address in the erase block, in $8000-$BFFF
+flash_erase:
+    PHP
+    SEI
+    LDA __rc2
+    STA .Ldest
+    LDA __rc3
+    STA .Ldest+1
+    LDA #0xAA
+    STA 0x8AAA
+    LDA #0x55
+    STA 0x8555
+    LDA #0x80
+    STA 0x8AAA
+    LDA #0xAA
+    STA 0x8AAA
+    LDA #0x55
+    STA 0x8555
+    LDA #0x30
+    LDY #0
+    STA (.Ldest),Y
+    JSR .Lwait
+    PLP
+    RTS
+
+; Bit 6 of every read toggles while the flash is busy, so it's done once
+; two reads in a row agree
+.Lwait:
+    LDY #0
+.Lpoll:
+    LDA (.Ldest),Y
+    CMP (.Ldest),Y
+    BNE .Lpoll
+    RTS
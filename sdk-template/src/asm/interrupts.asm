@@ -1,5 +1,5 @@
 .section .text
-.global wait, return_from_interrupt, enable_irq_handler, disable_irq_handler, __set_v
+.global wait, return_from_interrupt, enable_irq_handler, disable_irq_handler, __set_v, vblank_nmi, irq_handler
 
 wait:
     WAI
@@ -8,6 +8,85 @@ wait:
 return_from_interrupt:
     RTI
 
+; Handlers are Rust code that can interrupt other Rust code, so A, X, Y and
+; the imaginary registers __rc2-__rc31 are saved around them. (__rc0/__rc1
+; are the soft stack pointer, which the handlers push below.) A is already
+; on the stack.
+.macro save_registers
+    PHX
+    PHY
+    LDX #2
+1:
+    LDA __rc0,X
+    PHA
+    INX
+    CPX #32
+    BNE 1b
+.endm
+
+.macro restore_registers
+    LDX #31
+1:
+    PLA
+    STA __rc0,X
+    DEX
+    CPX #1
+    BNE 1b
+    PLY
+    PLX
+.endm
+
+; NMI handler: flag the vblank, then run the sdk::irq vblank handlers if
+; there are any. The first byte of each handler table is its count.
+vblank_nmi:
+    PHA
+    LDA RAM_BANK_FLAGS
+    BNE .Lother_bank
+    JSR .Lvblank
+    PLA
+    RTI
+
+; A RAM bank copy was in bank 1-3, whose zero page isn't ours. Go back to
+; bank 0 for the handlers, then return to the bank it was in.
+.Lother_bank:
+    PHX
+    TAX
+    AND #0x3F
+    STA 0x2005
+    PHX
+    JSR .Lvblank
+    PLX
+    STX 0x2005
+    PLX
+    PLA
+    RTI
+
+.Lvblank:
+    LDA #1
+    STA VBLANK
+    LDA VBLANK_HANDLERS
+    BEQ .Lvblank_done
+    save_registers
+    JSR run_vblank_handlers
+    restore_registers
+.Lvblank_done:
+    RTS
+
+; IRQ/BRK handler: run the sdk::irq IRQ handlers if there are any
+irq_handler:
+    PHA
+    LDA IRQ_HANDLERS
+    BNE .Lrun_irq
+    PLA
+    RTI
+
+.Lrun_irq:
+    save_registers
+    JSR run_irq_handlers
+    restore_registers
+    PLA
+    RTI
+
 enable_irq_handler:
     CLI
     RTS
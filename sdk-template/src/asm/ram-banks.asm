@@ -0,0 +1,152 @@
+; Copies between RAM bank 0 and banks 1-3 (see gametank::banking::RamBanks)
+;
+; Switching RAM banks swaps all of $0000-$1FFF, zero page and stacks
+; included, so Rust only ever runs in bank 0. These copy one byte per bank
+; switch, using only A, X, Y and zero page bytes that are written into the
+; other bank before they're needed there. Banks 1-3 keep their zero page
+; and stack page for this, and for the NMI handler.
+
+.section .data.zp,"aw",@progbits
+.global RAM_BANK_FLAGS
+
+; In each bank, the banking register value that selects it. Always 0 in
+; bank 0, so the NMI can tell which bank it interrupted.
+RAM_BANK_FLAGS:   .byte 0
+.Lhome_flags:     .byte 0
+.Lother_flags:    .byte 0
+.Lhome_ptr:       .word 0
+.Lother_ptr:      .word 0
+.Lcount:          .word 0
+
+.section .text
+.global ram_bank_copy, init_ram_banks
+
+; Mark banks 1-3 with their flags, before NMIs are turned on
+init_ram_banks:
+    LDA #0x40
+    STA 0x2005
+    STA RAM_BANK_FLAGS
+    LDA #0x80
+    STA 0x2005
+    STA RAM_BANK_FLAGS
+    LDA #0xC0
+    STA 0x2005
+    STA RAM_BANK_FLAGS
+    LDA #0
+    STA 0x2005
+    RTS
+
+; void ram_bank_copy(const RamCopy *args)
+;   __rc2/__rc3 = args:
+;     +0 home flags (bank 0), +1 other flags, +2 home pointer,
+;     +4 other pointer, +6 length, +8 nonzero to copy into the other bank
+ram_bank_copy:
+    PHP
+    SEI
+    LDY #0
+    LDA (__rc2),Y
+    STA .Lhome_flags
+    INY
+    LDA (__rc2),Y
+    STA .Lother_flags
+    INY
+    LDA (__rc2),Y
+    STA .Lhome_ptr
+    INY
+    LDA (__rc2),Y
+    STA .Lhome_ptr+1
+    INY
+    LDA (__rc2),Y
+    STA .Lother_ptr
+    INY
+    LDA (__rc2),Y
+    STA .Lother_ptr+1
+    INY
+    LDA (__rc2),Y
+    STA .Lcount
+    INY
+    LDA (__rc2),Y
+    STA .Lcount+1
+    INY
+    LDA (__rc2),Y
+    PHA                     ; direction, kept on bank 0's stack
+
+    ; the other bank needs its flags, its pointer and the way back. The
+    ; flags only differ in the bank bits, so clearing them gets back to 0.
+    LDX .Lother_ptr
+    LDY .Lother_ptr+1
+    LDA .Lother_flags
+    STA 0x2005
+    STA RAM_BANK_FLAGS
+    STX .Lother_ptr
+    STY .Lother_ptr+1
+    AND #0x3F
+    STA 0x2005
+    LDX .Lhome_flags
+    LDA .Lother_flags
+    STA 0x2005
+    STX .Lhome_flags
+    STX 0x2005
+
+    PLA
+    BEQ .Lstart_in
+
+.Lstart_out:
+    LDA .Lcount
+    ORA .Lcount+1
+    BEQ .Ldone
+    LDY #0
+.Lout:
+    LDA (.Lhome_ptr),Y
+    TAX
+    LDA .Lother_flags
+    STA 0x2005
+    TXA
+    STA (.Lother_ptr),Y
+    LDA .Lhome_flags
+    STA 0x2005
+    JSR .Lnext
+    BNE .Lout
+    BEQ .Ldone
+
+.Lstart_in:
+    LDA .Lcount
+    ORA .Lcount+1
+    BEQ .Ldone
+    LDY #0
+.Lin:
+    LDA .Lother_flags
+    STA 0x2005
+    LDA (.Lother_ptr),Y
+    TAX
+    LDA .Lhome_flags
+    STA 0x2005
+    TXA
+    STA (.Lhome_ptr),Y
+    JSR .Lnext
+    BNE .Lin
+
+.Ldone:
+    PLP
+    RTS
+
+; In bank 0: step Y and both pointers, and count down. Z is set when the
+; count reaches zero.
+.Lnext:
+    INY
+    BNE .Lcount_down
+    INC .Lhome_ptr+1
+    LDA .Lother_flags
+    STA 0x2005
+    INC .Lother_ptr+1
+    LDA .Lhome_flags
+    STA 0x2005
+.Lcount_down:
+    LDA .Lcount
+    BNE .Llow
+    DEC .Lcount+1
+.Llow:
+    DEC .Lcount
+    LDA .Lcount
+    ORA .Lcount+1
+    RTS
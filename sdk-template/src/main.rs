@@ -4,7 +4,7 @@
 #![allow(static_mut_refs)]
 
 use gametank::{
-    audio::FIRMWARE, boot::wait, console::Console, via::Via, video_dma::blitter::BlitterGuard,
+    audio::init_audio, boot::wait, console::Console, via::Via, video_dma::blitter::BlitterGuard,
 };
 
 use crate::ball::init_balls;
@@ -32,6 +32,7 @@ fn draw_background(blitter: &mut BlitterGuard) {
 
 #[unsafe(no_mangle)]
 fn main(console: &mut Console) {
+    init_audio(console);
     load_background_sprite(console);
 
     // load_background_sprite sets bank to 124, and our draw_background function is in bank 126
@@ -235,6 +235,9 @@ pub const RESET_VECTOR: u16 = 0xfffc;
 /// Address of the NMI interrupt vector.
 pub const NMI_VECTOR: u16 = 0xfffa;
 
+/// Length of the state packed by `W65C02S::save_state`.
+pub const STATE_LEN: usize = 9;
+
 /// The CPU is in one of the given states between `step`s.
 #[derive(Clone,Copy,Debug,PartialEq,Eq)]
 pub enum State {
@@ -252,6 +255,13 @@ pub enum State {
     Stopped,
 }
 
+/// An interrupt the CPU is about to take.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Interrupt {
+    Nmi,
+    Irq,
+}
+
 /// An instance of a W65C02S, encapsulating the entire runtime state of the
 /// processor itself. Not very useful without a `System` to go with it.
 #[derive(Copy,Clone,Debug,PartialEq,Eq)]
@@ -347,6 +357,53 @@ impl W65C02S {
     /// called during a `step`.
     #[inline(always)]
     pub fn get_state(&self) -> State { self.state }
+    /// The interrupt the next `step` will take instead of an instruction, if
+    /// any.
+    pub fn pending_interrupt(&self) -> Option<Interrupt> {
+        match self.state {
+            State::Running if self.nmi_pending => Some(Interrupt::Nmi),
+            State::Running if self.irq_pending => Some(Interrupt::Irq),
+            _ => None,
+        }
+    }
+    /// Pack the entire runtime state of the processor into bytes, e.g. for
+    /// an emulator's save states. `load_state` restores it.
+    pub fn save_state(&self) -> [u8; STATE_LEN] {
+        let state = match self.state {
+            State::HasBeenReset => 0,
+            State::Running => 1,
+            State::AwaitingInterrupt => 2,
+            State::Stopped => 3,
+        };
+        let lines = self.irq as u8
+            | (self.irq_pending as u8) << 1
+            | (self.nmi as u8) << 2
+            | (self.nmi_edge as u8) << 3
+            | (self.nmi_pending as u8) << 4;
+        let [pc_lo, pc_hi] = self.pc.to_le_bytes();
+        [state, pc_lo, pc_hi, self.a, self.x, self.y, self.s, self.p, lines]
+    }
+    /// Restore state packed by `save_state`. Returns `false`, leaving the
+    /// processor untouched, if the bytes didn't come from `save_state`.
+    pub fn load_state(&mut self, bytes: &[u8; STATE_LEN]) -> bool {
+        let state = match bytes[0] {
+            0 => State::HasBeenReset,
+            1 => State::Running,
+            2 => State::AwaitingInterrupt,
+            3 => State::Stopped,
+            _ => return false,
+        };
+        let lines = bytes[8];
+        *self = W65C02S {
+            state,
+            pc: u16::from_le_bytes([bytes[1], bytes[2]]),
+            a: bytes[3], x: bytes[4], y: bytes[5], s: bytes[6],
+            p: bytes[7] | P_1,
+            irq: lines & 1 != 0, irq_pending: lines & 2 != 0,
+            nmi: lines & 4 != 0, nmi_edge: lines & 8 != 0, nmi_pending: lines & 16 != 0,
+        };
+        true
+    }
     /// Push a value onto the stack using the given `System`.
     #[inline(always)]
     pub fn push<S: System>(&mut self, system: &mut S, value: u8) {
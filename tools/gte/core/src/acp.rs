@@ -0,0 +1,163 @@
+//! Inspecting the audio coprocessor
+//!
+//! The ACP runs whatever firmware the game uploads, so voice state is just
+//! bytes in its 4K of RAM. [`VoiceLayout`] knows where the SDK's firmwares
+//! keep their voices, and [`DacScope`] keeps the most recent samples written
+//! to the DAC for drawing a waveform.
+
+use alloc::vec::Vec;
+
+/// Where every SDK firmware keeps its voices, in ACP address space
+pub const VOICE_BASE: u16 = 0x0041;
+
+/// Samples kept by [`DacScope`], a little over 1/16th of a second at 14kHz
+pub const SCOPE_LEN: usize = 1024;
+
+/// Voice layout of one of the SDK's audio firmwares
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VoiceLayout {
+    /// `audio-wavetable-8ch`: phase, frequency, wavetable, volume (0-63)
+    Wavetable8,
+    /// `audio-wavetable-7ch-linear`: phase, frequency, wavetable, volume
+    /// table pointer and shift
+    Wavetable7Linear,
+    /// `audio-pcm`: 8.8 read position, end, step and flags
+    Pcm,
+}
+
+impl VoiceLayout {
+    pub const ALL: [VoiceLayout; 3] = [VoiceLayout::Wavetable8, VoiceLayout::Wavetable7Linear, VoiceLayout::Pcm];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VoiceLayout::Wavetable8 => "wavetable-8ch",
+            VoiceLayout::Wavetable7Linear => "wavetable-7ch-linear",
+            VoiceLayout::Pcm => "pcm",
+        }
+    }
+
+    pub fn voice_count(self) -> usize {
+        match self {
+            VoiceLayout::Wavetable8 => 8,
+            VoiceLayout::Wavetable7Linear => 7,
+            VoiceLayout::Pcm => 2,
+        }
+    }
+
+    pub fn voice_size(self) -> usize {
+        match self {
+            VoiceLayout::Wavetable8 => 7,
+            VoiceLayout::Wavetable7Linear => 9,
+            VoiceLayout::Pcm => 8,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&l| l == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Decode every voice from a copy of ACP RAM
+    pub fn voices(self, aram: &[u8; 0x1000]) -> Vec<AcpVoice> {
+        (0..self.voice_count()).map(|i| {
+            let start = VOICE_BASE as usize + i * self.voice_size();
+            AcpVoice::decode(self, &aram[start..start + self.voice_size()])
+        }).collect()
+    }
+}
+
+/// How loud a voice is, which each firmware stores differently
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VoiceLevel {
+    /// Volume from 0 to 63
+    Volume(u8),
+    /// Samples are looked up in `table`, then shifted right by `shift`
+    Shifted { table: u16, shift: u8 },
+    /// PCM flags, bit 7 playing and bit 6 streaming
+    Flags(u8),
+}
+
+/// One voice as the firmware currently sees it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AcpVoice {
+    /// Phase accumulator, or for PCM the read address
+    pub phase: u16,
+    /// Added to the phase every sample. Wavetables use 16.16 fixed point,
+    /// PCM 8.8.
+    pub step: u16,
+    /// Wavetable address, or for PCM where a one-shot stops
+    pub source: u16,
+    pub level: VoiceLevel,
+}
+
+impl AcpVoice {
+    fn decode(layout: VoiceLayout, bytes: &[u8]) -> Self {
+        let word = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        match layout {
+            VoiceLayout::Wavetable8 => Self {
+                phase: word(0),
+                step: word(2),
+                source: word(4),
+                level: VoiceLevel::Volume(bytes[6]),
+            },
+            VoiceLayout::Wavetable7Linear => Self {
+                phase: word(0),
+                step: word(2),
+                source: word(4),
+                level: VoiceLevel::Shifted { table: word(6), shift: bytes[8] },
+            },
+            VoiceLayout::Pcm => Self {
+                phase: word(1),
+                step: word(5),
+                source: word(3),
+                level: VoiceLevel::Flags(bytes[7]),
+            },
+        }
+    }
+
+    /// Whether the voice makes any sound
+    pub fn is_active(&self) -> bool {
+        match self.level {
+            VoiceLevel::Volume(v) => v > 0 && self.step > 0,
+            VoiceLevel::Shifted { .. } => self.step > 0,
+            VoiceLevel::Flags(f) => f & 0x80 != 0,
+        }
+    }
+
+    /// Pitch in Hz for wavetable voices, or playback rate in samples per
+    /// second for PCM
+    pub fn frequency_hz(&self, sample_rate: f64) -> f64 {
+        match self.level {
+            VoiceLevel::Flags(_) => self.step as f64 * sample_rate / 256.0,
+            _ => self.step as f64 * sample_rate / 65536.0,
+        }
+    }
+}
+
+/// The most recent DAC samples, for an oscilloscope
+#[derive(Clone, Debug)]
+pub struct DacScope {
+    samples: [u8; SCOPE_LEN],
+    next: usize,
+}
+
+impl Default for DacScope {
+    fn default() -> Self {
+        Self { samples: [0x80; SCOPE_LEN], next: 0 }
+    }
+}
+
+impl DacScope {
+    pub fn push(&mut self, sample: u8) {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % SCOPE_LEN;
+    }
+
+    /// Everything in the buffer, oldest first
+    pub fn samples(&self) -> Vec<u8> {
+        let mut samples = Vec::with_capacity(SCOPE_LEN);
+        samples.extend_from_slice(&self.samples[self.next..]);
+        samples.extend_from_slice(&self.samples[..self.next]);
+        samples
+    }
+}
@@ -0,0 +1,163 @@
+//! Emulator benchmarks
+//!
+//! [`Emulator::bench`](crate::emulator::Emulator::bench) runs a ROM as fast
+//! as it goes, with nothing drawn or played, and reports how fast the host
+//! emulated it: instructions per second, time per frame, and how that time
+//! split between the main CPU, the ACP (audio included), the blitter and
+//! everything else (the VIA, vblank, traces).
+//!
+//! Timing every step would cost more than some of the parts being timed, so
+//! only one step in [`SAMPLE_EVERY`] is split up; the split is a share of
+//! those, applied to the whole run, less what reading the clock costs. The
+//! emulator's clock has to be finer than a millisecond for the split to mean
+//! anything.
+
+use core::fmt;
+
+/// One step in this many has its parts timed
+pub const SAMPLE_EVERY: u32 = 16;
+
+/// Clock reads timed to find what one costs
+const CALIBRATION_READS: u32 = 1000;
+
+/// Where a step's time goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    Cpu,
+    /// The audio coprocessor, and resampling its output
+    Acp,
+    Blitter,
+    /// The VIA, vblank and anything recording the run
+    Other,
+}
+
+impl Part {
+    pub const ALL: [Part; 4] = [Part::Cpu, Part::Acp, Part::Blitter, Part::Other];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Part::Cpu => "CPU",
+            Part::Acp => "ACP",
+            Part::Blitter => "blitter",
+            Part::Other => "other",
+        }
+    }
+}
+
+/// Counts kept by the emulator while benchmarking
+#[derive(Debug, Clone, Default)]
+pub struct Bench {
+    steps: u32,
+    instructions: u64,
+    cycles: u64,
+    /// Sampled milliseconds spent in each [`Part`]
+    parts: [f64; 4],
+    /// Milliseconds a clock read takes, taken off each part
+    overhead: f64,
+}
+
+impl Bench {
+    /// Start benchmarking, with the cost of calling `now` measured
+    pub(crate) fn new(now: impl Fn() -> f64) -> Self {
+        let start = now();
+        for _ in 0..CALIBRATION_READS {
+            now();
+        }
+        let overhead = (now() - start) / (CALIBRATION_READS + 1) as f64;
+        Self { overhead, ..Self::default() }
+    }
+
+    /// Whether to time the step about to run
+    pub(crate) fn sample(&mut self) -> bool {
+        self.steps = self.steps.wrapping_add(1);
+        self.steps.is_multiple_of(SAMPLE_EVERY)
+    }
+
+    /// Count a step that took `cycles`, and ran an instruction unless the
+    /// CPU was waiting
+    pub(crate) fn count(&mut self, cycles: i32, instruction: bool) {
+        self.cycles += cycles as u64;
+        self.instructions += instruction as u64;
+    }
+
+    pub(crate) fn add(&mut self, part: Part, ms: f64) {
+        self.parts[part as usize] += (ms - self.overhead).max(0.0);
+    }
+
+    /// The report for a run of `frames` frames that took `ms` of host time,
+    /// the slowest frame `slowest_ms`, on a CPU clocked at `cpu_hz`
+    pub(crate) fn report(&self, frames: u32, ms: f64, slowest_ms: f64, cpu_hz: f64) -> BenchReport {
+        BenchReport {
+            frames,
+            ms,
+            slowest_frame_ms: slowest_ms,
+            instructions: self.instructions,
+            cycles: self.cycles,
+            emulated_ms: self.cycles as f64 * 1000.0 / cpu_hz,
+            parts: self.parts,
+        }
+    }
+}
+
+/// The results of [`Emulator::bench`](crate::emulator::Emulator::bench)
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    /// Frames run; fewer than asked for if the CPU stopped
+    pub frames: u32,
+    /// Host time for the whole run
+    pub ms: f64,
+    pub slowest_frame_ms: f64,
+    /// Main CPU instructions executed
+    pub instructions: u64,
+    /// Main CPU cycles, `WAI` included
+    pub cycles: u64,
+    /// How long the run would take on hardware
+    pub emulated_ms: f64,
+    /// Sampled host time in each [`Part`]
+    parts: [f64; 4],
+}
+
+impl BenchReport {
+    /// Millions of emulated instructions per second of host time
+    pub fn mips(&self) -> f64 {
+        self.instructions as f64 / self.ms.max(f64::MIN_POSITIVE) / 1000.0
+    }
+
+    pub fn ms_per_frame(&self) -> f64 {
+        self.ms / self.frames.max(1) as f64
+    }
+
+    /// How many times faster than hardware the run went
+    pub fn speed(&self) -> f64 {
+        self.emulated_ms / self.ms.max(f64::MIN_POSITIVE)
+    }
+
+    /// The fraction of host time spent in `part`
+    pub fn share(&self, part: Part) -> f64 {
+        let total: f64 = self.parts.iter().sum();
+        if total > 0.0 { self.parts[part as usize] / total } else { 0.0 }
+    }
+
+    /// Host time spent in `part`, estimated from the samples
+    pub fn part_ms(&self, part: Part) -> f64 {
+        self.share(part) * self.ms
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} frames in {:.1} ms ({:.2}x real time)", self.frames, self.ms, self.speed())?;
+        writeln!(f, "{:.3} ms per frame, slowest {:.3} ms", self.ms_per_frame(), self.slowest_frame_ms)?;
+        writeln!(f, "{:.2} MIPS ({} instructions, {} cycles)", self.mips(), self.instructions, self.cycles)?;
+        for part in Part::ALL {
+            writeln!(
+                f,
+                "  {:<8}{:>5.1}%  {:>8.3} ms/frame",
+                part.name(),
+                self.share(part) * 100.0,
+                self.part_ms(part) / self.frames.max(1) as f64,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,56 @@
+use alloc::string::String;
 use log::{debug, info, warn};
 use crate::gametank_bus::{CpuBus};
+use crate::savestate::{StateReader, StateWriter};
+
+/// A blit as the blitter latched it when it started, for debugging views
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlitInfo {
+    /// Sprite RAM page read from
+    pub vram_page: u8,
+    /// Framebuffer written to
+    pub framebuffer: u8,
+    pub src_x: u8,
+    pub src_y: u8,
+    pub dst_x: u8,
+    pub dst_y: u8,
+    pub width: u8,
+    pub height: u8,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub color_fill: bool,
+    pub gcarry: bool,
+    pub color: u8,
+}
+
+impl BlitInfo {
+    /// Top left corner, width and height of the sprite RAM read, or `None`
+    /// for a color fill. Without gcarry the source repeats every 16 pixels.
+    pub fn source(&self) -> Option<(u8, u8, u8, u8)> {
+        if self.color_fill {
+            return None
+        }
+
+        let (width, height) = if self.gcarry {
+            (self.width, self.height)
+        } else {
+            (self.width.min(16), self.height.min(16))
+        };
+
+        // flipped blits read backwards from the inverted coordinate
+        let x = if self.flip_x { !self.src_x } else { self.src_x };
+        let y = if self.flip_y { !self.src_y } else { self.src_y };
+        let x = if self.flip_x && self.gcarry { x.wrapping_sub(width.saturating_sub(1)) } else { x };
+        let y = if self.flip_y && self.gcarry { y.wrapping_sub(height.saturating_sub(1)) } else { y };
+
+        Some((x, y, width, height))
+    }
+
+    /// Top left corner, width and height of the framebuffer write
+    pub fn dest(&self) -> (u8, u8, u8, u8) {
+        (self.dst_x, self.dst_y, self.width, self.height)
+    }
+}
 
 #[derive(Debug)]
 pub struct Blitter {
@@ -24,6 +75,7 @@ pub struct Blitter {
     blitting: bool,
     cycles: i32,
     pub irq_trigger: bool,
+    last_blit: Option<BlitInfo>,
 }
 
 impl Blitter {
@@ -44,9 +96,41 @@ impl Blitter {
             blitting: false,
             cycles: 0,
             irq_trigger: false,
+            last_blit: None,
         }
     }
 
+    pub(crate) fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&[self.src_y, self.dst_y, self.height, self.src_x, self.dst_x, self.width, self.offset_x, self.offset_y, self.color]);
+        for flag in [self.flip_y, self.flip_x, self.color_fill, self.blitting, self.irq_trigger] {
+            w.bool(flag);
+        }
+        w.i32(self.cycles);
+    }
+
+    pub(crate) fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        for reg in [
+            &mut self.src_y, &mut self.dst_y, &mut self.height, &mut self.src_x, &mut self.dst_x,
+            &mut self.width, &mut self.offset_x, &mut self.offset_y, &mut self.color,
+        ] {
+            *reg = r.u8()?;
+        }
+        for flag in [&mut self.flip_y, &mut self.flip_x, &mut self.color_fill, &mut self.blitting, &mut self.irq_trigger] {
+            *flag = r.bool()?;
+        }
+        self.cycles = r.i32()?;
+        Ok(())
+    }
+
+    pub fn is_blitting(&self) -> bool {
+        self.blitting
+    }
+
+    /// The most recent blit since reset, which may still be running
+    pub fn last_blit(&self) -> Option<BlitInfo> {
+        self.last_blit
+    }
+
     pub fn clear_irq_trigger(&mut self) -> bool {
         let result = self.irq_trigger;
         self.irq_trigger = false;
@@ -78,6 +162,21 @@ impl Blitter {
             self.width = bus.blitter.width & 0b01111111;
             self.flip_x = bus.blitter.width & 0b10000000 != 0;
 
+            self.last_blit = Some(BlitInfo {
+                vram_page: bus.system_control.banking_register.vram_page(),
+                framebuffer: if bus.system_control.get_framebuffer_out() == 1 { 0 } else { 1 },
+                src_x: self.src_x,
+                src_y: self.src_y,
+                dst_x: self.dst_x,
+                dst_y: self.dst_y,
+                width: self.width,
+                height: self.height,
+                flip_x: self.flip_x,
+                flip_y: self.flip_y,
+                color_fill: self.color_fill,
+                gcarry: bus.system_control.dma_flags.dma_gcarry(),
+                color: self.color,
+            });
 
             debug!(target: "blitter", "starting blit from ({}, {}):({}, {}) page {} at ({}, {}); color mode {}, gcarry {}",
                 bus.blitter.gx, bus.blitter.gy,
@@ -170,7 +269,11 @@ impl Blitter {
                 quad += 128*128;
             }
 
-            bus.vram_banks[vram_page][blit_src_x + blit_src_y*128 + quad]
+            let index = blit_src_x + blit_src_y*128 + quad;
+            if let Some(heatmap) = &mut bus.heatmap {
+                heatmap.blitter_read(vram_page * 0x10000 + index);
+            }
+            bus.vram_banks[vram_page][index]
         };
 
         let out_x = self.dst_x.wrapping_add(self.offset_x) as usize;
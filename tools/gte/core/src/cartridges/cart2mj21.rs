@@ -1,12 +1,13 @@
-use alloc::{boxed::Box, string::ToString, vec::Vec};
+use alloc::{boxed::Box, string::{String, ToString}, vec::Vec};
 use log::warn;
 
 use crate::{
-    cartridges::Cartridge,
+    cartridges::{mapper::Mapper, Cartridge},
     gametank_bus::{DDRA, IORA},
+    savestate::{StateReader, StateWriter},
 };
 
-/// Block lengths for the 35 blocks in the 2MB flash cartridge
+/// Block lengths for the 35 blocks in each 2MB flash chip
 const BLOCK_LENGTHS: [usize; 35] = [
     // last 31 blocks: 64KB each (0x10000 bytes)
     0x10000, 0x10000, 0x10000, 0x10000, 0x10000, 0x10000, 0x10000,
@@ -59,16 +60,26 @@ const fn generate_block_mappings() -> [BlockMapping; 128] {
 
 const BLOCK_MAPPINGS: [BlockMapping; 128] = generate_block_mappings();
 
-const BANK_SIZE: usize = 0x4000;  // 16KB per bank
-const TOTAL_SIZE: usize = BANK_SIZE * 128;  // 2MB total (128 banks × 16KB)
+pub const BANK_SIZE: usize = 0x4000;  // 16KB per bank
+const CHIP_SIZE: usize = BANK_SIZE * 128;  // 2MB per flash chip (128 banks × 16KB)
 
-/// 2MB Flash Cartridge implementation with bank switching and flash memory emulation
+/// Physical bank made of the two 8KB erase blocks, the only ones small
+/// enough to rewrite often. On the 2MB cart games save there, selecting it
+/// as bank 63 because the bank pins are reversed.
+pub const SAVE_BANK: usize = 126;
+
+/// Flash cartridge implementation with bank switching and flash memory
+/// emulation: the 2MB cart, and the other revisions its [`Mapper`]s describe
 #[derive(Debug, Clone)]
 pub struct Cartridge2M {
-    data: Box<[u8; TOTAL_SIZE]>,
+    data: Box<[u8]>,
     pub bank_shifter: u8,
+    /// The physical bank mapped in at $8000
     pub bank_mask: u8,
     flash_state_machine: FlashStateMachine,
+    mapper: Mapper,
+    /// Bytes of [`save_bank`](Self::save_bank) frontends keep
+    save_size: usize,
 }
 
 // VIA Port A bit masks
@@ -308,20 +319,18 @@ impl FlashStateMachine {
         None
     }
 
-    /// Execute the current command
-    fn execute_command(&mut self, cartridge_data: &mut [u8; TOTAL_SIZE], bank_mask: u8) {
+    /// Execute the current command, with physical bank `bank` mapped in
+    fn execute_command(&mut self, cartridge_data: &mut [u8], bank: usize) {
         if let FlashState::CommandExecution(command) = &self.state {
             match command {
                 FlashCommand::ReadArray => {}
                 FlashCommand::Program(address, data) => {
                     warn!("Programming: address=0x{:04X}, data=0x{:02X}", address, data);
-                    let bank = (bank_mask & 0x7F) as usize;
                     let offset = (address & 0x3FFF) as usize;
                     let range = Cartridge2M::bank_range(bank);
                     cartridge_data[range.start + offset] &= data;
                 }
                 FlashCommand::UnlockBypassProgram(address, data) => {
-                    let bank = (bank_mask & 0x7F) as usize;
                     let offset = (address & 0x3FFF) as usize;
                     let range = Cartridge2M::bank_range(bank);
                     warn!(
@@ -335,45 +344,27 @@ impl FlashStateMachine {
                     cartridge_data.fill(0xFF);
                 }
                 FlashCommand::BlockErase(block_addr) => {
-                    let current_bank = (bank_mask & 0x7F) as usize;
-                    let target_block = BLOCK_MAPPINGS[current_bank].block_index as usize;
-                    let block_start: usize = BLOCK_LENGTHS[..target_block].iter().sum();
-                    let block_end = block_start + BLOCK_LENGTHS[target_block];
-
-                    let start_bank = block_start / BANK_SIZE;
-                    let end_bank = (block_end + BANK_SIZE - 1) / BANK_SIZE;
-                    let start_offset = block_start % BANK_SIZE;
-                    let end_offset = block_end % BANK_SIZE;
-
-                    let banks: Vec<_> = (start_bank..end_bank).collect();
-                    let block_size = block_end - block_start;
+                    // the block holding the address, which in the small
+                    // blocks needn't start at the bank's start; every chip
+                    // has the same layout
+                    let target = bank * BANK_SIZE + (*block_addr as usize & 0x3FFF);
+                    let mut block_start = target / CHIP_SIZE * CHIP_SIZE;
+                    let mut block_size = 0;
+                    for length in BLOCK_LENGTHS {
+                        if target < block_start + length {
+                            block_size = length;
+                            break;
+                        }
+                        block_start += length;
+                    }
 
                     warn!(
-                        "Erasing {}k block on bank(s) {}{}",
+                        "Erasing {}k block at ${:06X} (bank {})",
                         block_size / 1024,
-                        banks.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(", "),
-                        if block_size == 8 * 1024 {
-                            let half = if start_offset == 0 { "[1/2]" } else { "[2/2]" };
-                            half
-                        } else {
-                            ""
-                        }
+                        block_start,
+                        bank,
                     );
-
-                    for bank_index in banks {
-                        if bank_index >= 128 {
-                            continue;
-                        }
-
-                        let range = Cartridge2M::bank_range(bank_index);
-                        if bank_index == start_bank && start_offset != 0 {
-                            cartridge_data[range.start + start_offset..range.end].fill(0xFF);
-                        } else if bank_index == end_bank - 1 && end_offset != 0 {
-                            cartridge_data[range.start..range.start + end_offset].fill(0xFF);
-                        } else {
-                            cartridge_data[range].fill(0xFF);
-                        }
-                    }
+                    cartridge_data[block_start..block_start + block_size].fill(0xFF);
                 }
                 FlashCommand::UnlockBypassEnter => {
                     warn!("Entering unlock bypass mode");
@@ -393,6 +384,65 @@ impl FlashStateMachine {
 }
 
 impl Cartridge2M {
+    /// A cart wired as `mapper`, which has to be a flash one, holding
+    /// `slice` and keeping `save_size` bytes of saves (0 for the mapper's
+    /// default)
+    pub fn new(slice: &[u8], mapper: Mapper, save_size: usize) -> Self {
+        let mut cart = Self {
+            data: alloc::vec![0u8; mapper.banks() * BANK_SIZE].into_boxed_slice(),
+            bank_shifter: 0,
+            bank_mask: 0,
+            flash_state_machine: FlashStateMachine::new(),
+            mapper,
+            save_size: match save_size {
+                0 => mapper.default_save_size(),
+                n => n.min(BANK_SIZE),
+            },
+        };
+        cart.reset();
+
+        // Copy data bank by bank, through the same wiring the bank register goes through
+        let num_banks = slice.len().div_ceil(BANK_SIZE).min(mapper.banks());
+        for logical_bank in 0..num_banks {
+            let physical_bank = cart.physical_bank(logical_bank as u8);
+
+            let src_start = logical_bank * BANK_SIZE;
+            let src_end = (src_start + BANK_SIZE).min(slice.len());
+            let dst_range = Self::bank_range(physical_bank);
+
+            let copy_len = src_end - src_start;
+            cart.data[dst_range.start..dst_range.start + copy_len]
+                .copy_from_slice(&slice[src_start..src_end]);
+        }
+
+        cart
+    }
+
+    pub fn mapper(&self) -> Mapper {
+        self.mapper
+    }
+
+    /// The physical bank the game selects as `bank`
+    fn physical_bank(&self, bank: u8) -> usize {
+        let bank = bank as usize & (self.mapper.banks() - 1);
+        if self.mapper.reversed_pins() {
+            reverse_bank_bits(bank as u8) as usize
+        } else {
+            bank
+        }
+    }
+
+    /// The physical bank the 16K window at $C000 is fixed to
+    fn fixed_bank(&self) -> usize {
+        self.mapper.banks() - 1
+    }
+
+    /// The physical bank saves go in: [`SAVE_BANK`] on a 2MB cart, and the
+    /// same place in the last chip on bigger ones
+    pub fn save_bank(&self) -> usize {
+        self.mapper.banks() - 128 + SAVE_BANK
+    }
+
     /// Calculate the byte range for a given bank index
     fn bank_range(bank: usize) -> core::ops::Range<usize> {
         let start = bank * BANK_SIZE;
@@ -405,42 +455,67 @@ impl Cartridge2M {
         let range = Self::bank_range(bank);
         &self.data[range]
     }
+
+    /// The bank the game selected, undoing any reversed bank pins
+    pub fn bank(&self) -> u8 {
+        // the wiring is its own inverse
+        self.physical_bank(self.bank_mask) as u8
+    }
+
+    /// A byte of `bank` (as the game selects it), which needn't be the one
+    /// mapped in
+    pub fn peek_bank(&self, bank: u8, address: u16) -> u8 {
+        self.bank_slice(self.physical_bank(bank))[(address as usize) & 0x3FFF]
+    }
+
+    /// The save data in [`save_bank`](Self::save_bank), as it is now
+    pub fn save_data(&self) -> &[u8] {
+        &self.bank_slice(self.save_bank())[..self.save_size]
+    }
+
+    /// The save data in [`save_bank`](Self::save_bank), for frontends
+    /// restoring a save
+    pub fn save_data_mut(&mut self) -> &mut [u8] {
+        let start = Self::bank_range(self.save_bank()).start;
+        &mut self.data[start..start + self.save_size]
+    }
+
+    /// The whole flash and the bank latch. A flash command half written
+    /// when the state was saved is dropped.
+    pub(crate) fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.bank_shifter);
+        w.u8(self.bank_mask);
+        w.bytes(&self.data[..]);
+    }
+
+    pub(crate) fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.bank_shifter = r.u8()?;
+        self.bank_mask = r.u8()?;
+        r.bytes_into(&mut self.data[..])?;
+        self.flash_state_machine = FlashStateMachine::new();
+        Ok(())
+    }
+
+    /// Back to the bank registers at power-on; flash keeps its contents
+    pub(crate) fn reset(&mut self) {
+        self.bank_shifter = 0;
+        self.bank_mask = 0x7E;
+        self.flash_state_machine = FlashStateMachine::new();
+    }
 }
 
 impl Cartridge for Cartridge2M {
     fn from_slice(slice: &[u8]) -> Self {
-        let mut data = Box::new([0u8; TOTAL_SIZE]);
-        
-        // Copy data bank by bank, reversing the bank numbers to match hardware bug
-        let num_banks = (slice.len() + BANK_SIZE - 1) / BANK_SIZE;
-        for logical_bank in 0..num_banks {
-            let physical_bank = reverse_bank_bits(logical_bank as u8) as usize;
-            
-            let src_start = logical_bank * BANK_SIZE;
-            let src_end = (src_start + BANK_SIZE).min(slice.len());
-            let dst_range = Self::bank_range(physical_bank);
-            
-            let copy_len = src_end - src_start;
-            data[dst_range.start..dst_range.start + copy_len]
-                .copy_from_slice(&slice[src_start..src_end]);
-        }
-        
-        Self {
-            data,
-            bank_shifter: 0,
-            bank_mask: 0x7E,
-            flash_state_machine: FlashStateMachine::new(),
-        }
+        Self::new(slice, Mapper::Flash2M, 0)
     }
 
     fn read_byte(&self, address: u16) -> u8 {
         match address {
             0x4000..=0x7FFF => {
-                self.bank_slice(0x7F)[(address as usize) & 0x3FFF]
+                self.bank_slice(self.fixed_bank())[(address as usize) & 0x3FFF]
             }
             0x0000..=0x3FFF => {
-                let bank = (self.bank_mask & 0x7F) as usize;
-                self.bank_slice(bank)[(address as usize) & 0x3FFF]
+                self.bank_slice(self.bank_mask as usize)[(address as usize) & 0x3FFF]
             }
             _ => {
                 panic!("how the hell did you get here?");
@@ -452,7 +527,7 @@ impl Cartridge for Cartridge2M {
         let should_execute = self.flash_state_machine.add_input(address, data);
         if let Some(command) = should_execute {
             self.flash_state_machine
-                .execute_command(&mut self.data, self.bank_mask);
+                .execute_command(&mut self.data, self.bank_mask as usize);
         }
     }
 
@@ -471,9 +546,10 @@ impl Cartridge for Cartridge2M {
                 self.bank_shifter = (self.bank_shifter << 1) | pa_data_bit(pa_after);
             }
             PaEvent::LatchRisingEdge => {
-                // Latch the accumulated bank value on latch rising edge
-                // Reverse bits to correct hardware bug where bank pins were reversed
-                self.bank_mask = reverse_bank_bits(self.bank_shifter);
+                // Latch the accumulated bank value on latch rising edge,
+                // through the mapper's wiring (reversed on the 2MB cart,
+                // where the bank pins were reversed)
+                self.bank_mask = self.physical_bank(self.bank_shifter) as u8;
             }
             PaEvent::None => {}
         }
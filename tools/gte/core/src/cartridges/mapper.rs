@@ -0,0 +1,107 @@
+//! Cartridge mappers
+//!
+//! A mapper says how a ROM is wired: how many banks there are, how the bank
+//! register's bits reach the flash's address pins, and how much of the flash
+//! frontends keep as save data. A `.gtr` header names one by id (see
+//! [`rom_header`](crate::rom_header)); 0, and files without a header, pick
+//! one by the ROM's size, as the emulator always has.
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::cartridges::cart2mj21::BANK_SIZE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mapper {
+    /// Whatever fits the ROM's size
+    Auto,
+    /// 8K, 16K or 32K of ROM and no banking
+    Flat,
+    /// The 2MB flash cart: 128 banks, selected through a 7-bit shift
+    /// register whose pins are wired in reverse
+    Flash2M,
+    /// A 2MB flash cart with the bank pins wired straight
+    Flash2MStraight,
+    /// 4MB of flash as two 2MB chips: 256 banks, all 8 bits of the shift
+    /// register, wired straight
+    Flash4M,
+}
+
+impl Mapper {
+    pub const ALL: [Mapper; 5] = [Mapper::Auto, Mapper::Flat, Mapper::Flash2M, Mapper::Flash2MStraight, Mapper::Flash4M];
+
+    /// The mapper with header id `id`, if there is one
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.id() == id)
+    }
+
+    pub fn id(self) -> u8 {
+        match self {
+            Mapper::Auto => 0,
+            Mapper::Flat => 1,
+            Mapper::Flash2M => 2,
+            Mapper::Flash2MStraight => 3,
+            Mapper::Flash4M => 4,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mapper::Auto => "auto",
+            Mapper::Flat => "flat",
+            Mapper::Flash2M => "flash-2m",
+            Mapper::Flash2MStraight => "flash-2m-straight",
+            Mapper::Flash4M => "flash-4m",
+        }
+    }
+
+    /// The mapper for a ROM of `len` bytes, for [`Auto`](Self::Auto)
+    pub fn for_size(len: usize) -> Result<Self, String> {
+        match len {
+            0x2000 | 0x4000 | 0x8000 => Ok(Mapper::Flat),
+            0x200000 => Ok(Mapper::Flash2M),
+            0x400000 => Ok(Mapper::Flash4M),
+            _ => Err(format!("no cartridge holds a {} byte ROM", len)),
+        }
+    }
+
+    /// This mapper, or the one [`Auto`](Self::Auto) picks for `len` bytes.
+    /// Fails if a ROM that size doesn't fit it.
+    pub fn resolve(self, len: usize) -> Result<Self, String> {
+        let mapper = match self {
+            Mapper::Auto => Self::for_size(len)?,
+            m => m,
+        };
+        let fits = match mapper {
+            Mapper::Flat => Self::for_size(len) == Ok(Mapper::Flat),
+            m => len == m.banks() * BANK_SIZE,
+        };
+        if !fits {
+            return Err(format!("a {} byte ROM doesn't fit the {} mapper", len, mapper.name()));
+        }
+        Ok(mapper)
+    }
+
+    /// 16K banks, counting the fixed one; 0 for carts without banking
+    pub fn banks(self) -> usize {
+        match self {
+            Mapper::Auto | Mapper::Flat => 0,
+            Mapper::Flash2M | Mapper::Flash2MStraight => 128,
+            Mapper::Flash4M => 256,
+        }
+    }
+
+    /// Whether the bank register's 7 bits reach the address pins reversed
+    pub fn reversed_pins(self) -> bool {
+        self == Mapper::Flash2M
+    }
+
+    /// Bytes of save data, in the bank made of the small erase blocks,
+    /// when the header doesn't say
+    pub fn default_save_size(self) -> usize {
+        match self {
+            Mapper::Auto | Mapper::Flat => 0,
+            _ => BANK_SIZE,
+        }
+    }
+}
@@ -4,13 +4,17 @@ pub mod cart8k;
 pub mod cart16k;
 pub mod cart32k;
 pub mod cart2mj21;
+pub mod mapper;
 
 use alloc::boxed::Box;
+use alloc::string::String;
 use log::error;
 use crate::cartridges::cart2mj21::Cartridge2M;
 use crate::cartridges::cart8k::Cartridge8K;
 use crate::cartridges::cart16k::Cartridge16K;
 use crate::cartridges::cart32k::{Cartridge32K};
+use crate::cartridges::mapper::Mapper;
+use crate::savestate::{StateReader, StateWriter};
 
 pub trait Cartridge {
     fn from_slice(slice: &[u8]) -> Self;
@@ -33,22 +37,27 @@ pub enum CartridgeType {
 
 impl CartridgeType {
     pub fn from_slice(slice: &[u8]) -> Self {
-        match slice.len() {
-            0x2000 => {
-                CartridgeType::Cart8k(Cartridge8K::from_slice(slice))
-            }
-            0x4000 => {
-                CartridgeType::Cart16k(Cartridge16K::from_slice(slice))
-            }
-            0x8000 => {
-                CartridgeType::Cart32k(Cartridge32K::from_slice(slice))
-            }
-            0x200000 => {
-                CartridgeType::Cart2m(Box::new(Cartridge2M::from_slice(slice)))
-            }
-            _ => {
-                panic!("unimplemented");
-            }
+        match Self::with_mapper(slice, Mapper::Auto, 0) {
+            Ok(cart) => cart,
+            Err(_) => panic!("unimplemented"),
+        }
+    }
+
+    /// A cart wired as `mapper` holding `slice`, keeping `save_size` bytes
+    /// of saves (0 for the mapper's default). Fails if `slice` doesn't fit.
+    pub fn with_mapper(slice: &[u8], mapper: Mapper, save_size: usize) -> Result<Self, String> {
+        Ok(match (mapper.resolve(slice.len())?, slice.len()) {
+            (Mapper::Flat, 0x2000) => CartridgeType::Cart8k(Cartridge8K::from_slice(slice)),
+            (Mapper::Flat, 0x4000) => CartridgeType::Cart16k(Cartridge16K::from_slice(slice)),
+            (Mapper::Flat, _) => CartridgeType::Cart32k(Cartridge32K::from_slice(slice)),
+            (mapper, _) => CartridgeType::Cart2m(Box::new(Cartridge2M::new(slice, mapper, save_size))),
+        })
+    }
+
+    pub fn mapper(&self) -> Mapper {
+        match self {
+            CartridgeType::Cart2m(c) => c.mapper(),
+            _ => Mapper::Flat,
         }
     }
 
@@ -69,6 +78,73 @@ impl CartridgeType {
         }
     }
 
+    /// ROM bank mapped in at $8000, numbered as the game selects it and the
+    /// linker places it. Carts without banking report bank 0.
+    pub fn bank(&self) -> u8 {
+        match self {
+            CartridgeType::Cart2m(c) => c.bank(),
+            _ => 0,
+        }
+    }
+
+    /// A byte of any ROM bank, at `address` from the bank's start, as if
+    /// that bank were mapped in at $8000
+    pub fn peek_bank(&self, bank: u8, address: u16) -> u8 {
+        match self {
+            CartridgeType::Cart2m(c) => c.peek_bank(bank, address),
+            _ => self.read_byte(address),
+        }
+    }
+
+    /// The part of the cartridge games write saves to, if it has one
+    pub fn save_data(&self) -> Option<&[u8]> {
+        match self {
+            CartridgeType::Cart2m(c) => Some(c.save_data()),
+            _ => None,
+        }
+    }
+
+    pub fn save_data_mut(&mut self) -> Option<&mut [u8]> {
+        match self {
+            CartridgeType::Cart2m(c) => Some(c.save_data_mut()),
+            _ => None,
+        }
+    }
+
+    /// Which kind of cartridge, so a state is only loaded onto the same kind
+    pub(crate) fn kind(&self) -> u8 {
+        match self {
+            CartridgeType::Cart8k(_) => 0,
+            CartridgeType::Cart16k(_) => 1,
+            CartridgeType::Cart32k(_) => 2,
+            CartridgeType::Cart2m(c) => match c.mapper() {
+                Mapper::Flash2MStraight => 4,
+                Mapper::Flash4M => 5,
+                _ => 3,
+            },
+        }
+    }
+
+    /// Only flash carts have anything to save; ROM can't change
+    pub(crate) fn save_state(&self, w: &mut StateWriter) {
+        if let CartridgeType::Cart2m(c) = self {
+            c.save_state(w);
+        }
+    }
+
+    pub(crate) fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        match self {
+            CartridgeType::Cart2m(c) => c.load_state(r),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        if let CartridgeType::Cart2m(c) = self {
+            c.reset();
+        }
+    }
+
     pub fn update_via(&mut self, via: &mut [[u8; 16]; 2]) {
         match self {
             CartridgeType::Cart2m(c) => { c.update_via(via) }
@@ -0,0 +1,152 @@
+//! Cheats and memory freezes
+//!
+//! A [`Cheat`] writes a value back into RAM every frame at vblank, so lives
+//! or health stay put while testing. It can depend on what's there already,
+//! e.g. only topping health back up once it drops below 3.
+//!
+//! ## Codes
+//!
+//! `ADDRESS:VALUE` in hex, with two value digits for a byte or four for a
+//! little-endian word, then optionally `?` and a comparison with the
+//! current value:
+//!
+//! | Code | Effect |
+//! |---|---|
+//! | `0042:09` | $42 is always 9 |
+//! | `0300:03E8` | the word at $300 is always 1000 |
+//! | `0042:09?<03` | $42 goes back to 9 when it's below 3 |
+//! | `0042:00?!FF` | $42 is 0 unless it's $FF |
+//!
+//! The comparisons are `=`, `!`, `<` and `>`. Codes can be joined with `+`
+//! or spaces, as RetroArch does with multi-part cheats. Only RAM
+//! ($0000-$1FFF, in whichever bank is mapped) and audio RAM ($3000-$3FFF)
+//! can be written.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Width {
+    Byte,
+    /// Little-endian, like the 6502's own words
+    Word,
+}
+
+/// When a cheat writes, based on the value currently in memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Always,
+    Equal(u16),
+    NotEqual(u16),
+    Less(u16),
+    Greater(u16),
+}
+
+impl Condition {
+    pub fn holds(self, current: u16) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::Equal(v) => current == v,
+            Condition::NotEqual(v) => current != v,
+            Condition::Less(v) => current < v,
+            Condition::Greater(v) => current > v,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u16,
+    pub width: Width,
+    pub condition: Condition,
+}
+
+/// Whether `len` bytes from `address` are all RAM or audio RAM
+fn writable(address: u16, len: u16) -> bool {
+    let end = address as u32 + len as u32 - 1;
+    [(0x0000, 0x1FFF), (0x3000, 0x3FFF)].iter().any(|&(lo, hi)| address as u32 >= lo && end <= hi)
+}
+
+fn parse_hex(s: &str, what: &str, code: &str) -> Result<u16, String> {
+    let s = s.trim_start_matches('$');
+    if s.is_empty() || s.len() > 4 {
+        return Err(format!("{} in \"{}\" should be 1-4 hex digits", what, code));
+    }
+    u16::from_str_radix(s, 16).map_err(|_| format!("{} in \"{}\" isn't hex", what, code))
+}
+
+impl Cheat {
+    /// Hold the byte at `address` at `value`
+    pub fn freeze(address: u16, value: u8) -> Result<Self, String> {
+        if !writable(address, 1) {
+            return Err(format!("${:04X} isn't RAM", address));
+        }
+        Ok(Self { address, value: value as u16, width: Width::Byte, condition: Condition::Always })
+    }
+
+    /// All the cheats in a code; see the [module docs](self) for the format
+    pub fn parse(code: &str) -> Result<Vec<Self>, String> {
+        let cheats = code.split(|c: char| c == '+' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(Self::parse_one)
+            .collect::<Result<Vec<_>, _>>()?;
+        if cheats.is_empty() {
+            return Err("Empty cheat code".into());
+        }
+        Ok(cheats)
+    }
+
+    fn parse_one(code: &str) -> Result<Self, String> {
+        let (write, condition) = match code.split_once('?') {
+            Some((write, condition)) => (write, Some(condition)),
+            None => (code, None),
+        };
+        let (address, value) = write.split_once(':')
+            .ok_or_else(|| format!("\"{}\" should look like ADDRESS:VALUE", code))?;
+
+        let address = parse_hex(address, "The address", code)?;
+        let value_digits = value.trim_start_matches('$').len();
+        let width = if value_digits > 2 { Width::Word } else { Width::Byte };
+        let value = parse_hex(value, "The value", code)?;
+
+        let condition = match condition {
+            None => Condition::Always,
+            Some(c) => {
+                let mut chars = c.chars();
+                let op = chars.next().ok_or_else(|| format!("\"{}\" has nothing after ?", code))?;
+                let compare = parse_hex(chars.as_str(), "The comparison", code)?;
+                match op {
+                    '=' => Condition::Equal(compare),
+                    '!' => Condition::NotEqual(compare),
+                    '<' => Condition::Less(compare),
+                    '>' => Condition::Greater(compare),
+                    _ => return Err(format!("\"{}\": the comparison should be =, !, < or >", code)),
+                }
+            }
+        };
+
+        let len = match width { Width::Byte => 1, Width::Word => 2 };
+        if !writable(address, len) {
+            return Err(format!("\"{}\": only RAM ($0000-$1FFF) and audio RAM ($3000-$3FFF) can be changed", code));
+        }
+
+        Ok(Self { address, value, width, condition })
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = match self.width { Width::Byte => 2, Width::Word => 4 };
+        write!(f, "{:04X}:{:0w$X}", self.address, self.value, w = digits)?;
+        match self.condition {
+            Condition::Always => Ok(()),
+            Condition::Equal(v) => write!(f, "?={:0w$X}", v, w = digits),
+            Condition::NotEqual(v) => write!(f, "?!{:0w$X}", v, w = digits),
+            Condition::Less(v) => write!(f, "?<{:0w$X}", v, w = digits),
+            Condition::Greater(v) => write!(f, "?>{:0w$X}", v, w = digits),
+        }
+    }
+}
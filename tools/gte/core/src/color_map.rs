@@ -1,3 +1,6 @@
+use alloc::format;
+use alloc::string::String;
+
 pub static COLOR_MAP_WRONG: [(u8, u8, u8, u8); 256] = [
     (0x00, 0x00, 0x00, 0xFF), (0x1F, 0x1F, 0x1F, 0xFF), (0x3F, 0x3F, 0x3F, 0xFF), (0x5F, 0x5F, 0x5F, 0xFF), (0x7F, 0x7F, 0x7F, 0xFF), (0x9F, 0x9F, 0x9F, 0xFF), (0xBF, 0xBF, 0xBF, 0xFF), (0xDF, 0xDF, 0xDF, 0xFF),
     (0x00, 0x00, 0x00, 0xFF), (0x17, 0x27, 0x17, 0xFF), (0x2F, 0x4F, 0x2F, 0xFF), (0x47, 0x77, 0x47, 0xFF), (0x5F, 0x9F, 0x5F, 0xFF), (0x87, 0xB7, 0x87, 0xFF), (0xAF, 0xCF, 0xAF, 0xFF), (0xD7, 0xE7, 0xD7, 0xFF),
@@ -117,3 +120,73 @@ pub static COLOR_MAP_PERCEPTUALLY_AUTOMAPPED: [(u8, u8, u8, u8); 256] = [
     (0x1A, 0x1A, 0x19, 0xFF), (0x1A, 0x36, 0x2F, 0xFF), (0x1A, 0x54, 0x47, 0xFF), (0x2D, 0x88, 0x73, 0xFF), (0x5C, 0xB6, 0xA2, 0xFF), (0x72, 0xCD, 0xB8, 0xFF), (0x89, 0xC6, 0xB8, 0xFF), (0xA1, 0xBF, 0xB6, 0xFF),
     (0x1A, 0x1A, 0x19, 0xFF), (0x03, 0x3D, 0x31, 0xFF), (0x17, 0x72, 0x5D, 0xFF), (0x45, 0xA0, 0x8B, 0xFF), (0x72, 0xCD, 0xB8, 0xFF), (0x72, 0xCD, 0xB8, 0xFF), (0x72, 0xCD, 0xB8, 0xFF), (0x89, 0xC6, 0xB8, 0xFF),
 ];
+
+pub type ColorMap = [(u8, u8, u8, u8); 256];
+
+/// The built-in palettes, for matching a particular console or display.
+/// [`Emulator::set_palette`](crate::emulator::Emulator::set_palette) picks
+/// one, and [`parse_palette`] loads any other.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Palette {
+    /// [`COLOR_MAP`], measured from real hardware
+    #[default]
+    Measured,
+    /// [`COLOR_MAP_PERCEPTUALLY_AUTOMAPPED`], evened out by eye, closer to
+    /// how composite output looks on a TV
+    Perceptual,
+    /// [`COLOR_MAP_WRONG`], the emulator's colors before they were measured
+    Legacy,
+    /// The measured colors' luma, as a black and white TV shows them
+    Grayscale,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [Palette::Measured, Palette::Perceptual, Palette::Legacy, Palette::Grayscale];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Measured => "Measured",
+            Palette::Perceptual => "Perceptual",
+            Palette::Legacy => "Legacy",
+            Palette::Grayscale => "Grayscale",
+        }
+    }
+
+    /// The palette called `name`, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|palette| palette.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The one after this in [`ALL`](Self::ALL), wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&palette| palette == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn color_map(self) -> ColorMap {
+        match self {
+            Palette::Measured => COLOR_MAP,
+            Palette::Perceptual => COLOR_MAP_PERCEPTUALLY_AUTOMAPPED,
+            Palette::Legacy => COLOR_MAP_WRONG,
+            Palette::Grayscale => COLOR_MAP.map(|(r, g, b, a)| {
+                let y = ((r as u16 * 77 + g as u16 * 150 + b as u16 * 29) >> 8) as u8;
+                (y, y, y, a)
+            }),
+        }
+    }
+}
+
+/// A palette file: 256 colors in hardware index order, as RGB (768 bytes,
+/// the usual `.pal`) or RGBA (1024 bytes)
+pub fn parse_palette(bytes: &[u8]) -> Result<ColorMap, String> {
+    let channels = match bytes.len() {
+        768 => 3,
+        1024 => 4,
+        len => return Err(format!("palette is {} bytes, expected 768 (RGB) or 1024 (RGBA)", len)),
+    };
+    let mut map = [(0, 0, 0, 0xFF); 256];
+    for (color, chunk) in map.iter_mut().zip(bytes.chunks_exact(channels)) {
+        *color = (chunk[0], chunk[1], chunk[2], chunk.get(3).copied().unwrap_or(0xFF));
+    }
+    Ok(map)
+}
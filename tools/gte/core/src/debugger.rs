@@ -0,0 +1,202 @@
+use alloc::vec::Vec;
+use gte_w65c02s::{System, W65C02S};
+use crate::gametank_bus::CpuBus;
+use crate::heatmap::Access;
+use crate::symbols::SymbolMap;
+
+/// Snapshot of the CPU registers
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Registers {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub s: u8,
+    pub p: u8,
+}
+
+impl Registers {
+    pub fn of(cpu: &W65C02S) -> Self {
+        Self {
+            pc: cpu.get_pc(),
+            a: cpu.get_a(),
+            x: cpu.get_x(),
+            y: cpu.get_y(),
+            s: cpu.get_s(),
+            p: cpu.get_p(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches(self, write: bool) -> bool {
+        match self {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::ReadWrite => true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub address: u16,
+    pub kind: WatchKind,
+}
+
+/// Why the debugger paused the emulator
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StopReason {
+    Breakpoint(u16),
+    Watchpoint { address: u16, value: u8, write: bool },
+    Step,
+}
+
+/// Breakpoints and watchpoints for the main CPU.
+///
+/// Checks only run while at least one breakpoint or watchpoint is set, so an
+/// idle debugger costs nothing but a branch per instruction.
+#[derive(Default, Debug)]
+pub struct Debugger {
+    breakpoints: Vec<u16>,
+    watchpoints: Vec<Watchpoint>,
+    /// Set when the emulator pauses on a breakpoint, watchpoint, or step
+    pub stop_reason: Option<StopReason>,
+    /// Names for addresses, if the ROM's symbol file was loaded
+    pub symbols: SymbolMap,
+    /// Breakpoint to step over when resuming from it
+    resume_from: Option<u16>,
+    pending_hit: Option<StopReason>,
+}
+
+impl Debugger {
+    pub fn is_active(&self) -> bool {
+        !self.breakpoints.is_empty() || !self.watchpoints.is_empty()
+    }
+
+    pub fn breakpoints(&self) -> &[u16] {
+        &self.breakpoints
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        if !self.breakpoints.contains(&address) {
+            self.breakpoints.push(address);
+            self.breakpoints.sort_unstable();
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.retain(|&b| b != address);
+    }
+
+    /// Returns whether the breakpoint is now set
+    pub fn toggle_breakpoint(&mut self, address: u16) -> bool {
+        if self.breakpoints.contains(&address) {
+            self.remove_breakpoint(address);
+            false
+        } else {
+            self.add_breakpoint(address);
+            true
+        }
+    }
+
+    pub fn add_watchpoint(&mut self, address: u16, kind: WatchKind) {
+        self.remove_watchpoint(address);
+        self.watchpoints.push(Watchpoint { address, kind });
+        self.watchpoints.sort_unstable_by_key(|w| w.address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u16) {
+        self.watchpoints.retain(|w| w.address != address);
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    /// Don't stop on the breakpoint at `pc` until another instruction has run
+    pub(crate) fn resume(&mut self, pc: u16) {
+        self.stop_reason = None;
+        self.resume_from = Some(pc);
+    }
+
+    /// Whether the CPU should stop before executing the instruction at `pc`
+    pub(crate) fn should_break(&mut self, pc: u16) -> bool {
+        if self.resume_from.take() == Some(pc) {
+            return false;
+        }
+        if self.breakpoints.binary_search(&pc).is_ok() {
+            self.stop_reason = Some(StopReason::Breakpoint(pc));
+            return true;
+        }
+        false
+    }
+
+    /// The watchpoint hit during the last instruction, if any
+    pub(crate) fn take_hit(&mut self) -> Option<StopReason> {
+        self.pending_hit.take()
+    }
+
+    fn check_access(&mut self, address: u16, value: u8, write: bool) {
+        if self.pending_hit.is_none()
+            && self.watchpoints.iter().any(|w| w.address == address && w.kind.matches(write)) {
+            self.pending_hit = Some(StopReason::Watchpoint { address, value, write });
+        }
+    }
+}
+
+/// The CPU bus with watchpoint checks on every data access.
+/// Opcode and operand fetches, vector reads, and spurious reads don't count.
+pub(crate) struct WatchedBus<'a> {
+    pub bus: &'a mut CpuBus,
+    pub debugger: &'a mut Debugger,
+}
+
+impl System for WatchedBus<'_> {
+    fn read_opcode(&mut self, _: &mut W65C02S, addr: u16) -> u8 {
+        self.bus.cpu_read(addr, Access::Execute)
+    }
+
+    fn read_opcode_spurious(&mut self, _: &mut W65C02S, addr: u16) {
+        self.bus.read_byte(addr);
+    }
+
+    fn read_operand(&mut self, _: &mut W65C02S, addr: u16) -> u8 {
+        self.bus.read_byte(addr)
+    }
+
+    fn read_operand_spurious(&mut self, _: &mut W65C02S, addr: u16) {
+        self.bus.read_byte(addr);
+    }
+
+    fn read_spurious(&mut self, _: &mut W65C02S, addr: u16) {
+        self.bus.read_byte(addr);
+    }
+
+    fn read_vector(&mut self, _: &mut W65C02S, addr: u16) -> u8 {
+        self.bus.read_byte(addr)
+    }
+
+    fn read(&mut self, _: &mut W65C02S, addr: u16) -> u8 {
+        let value = self.bus.cpu_read(addr, Access::Read);
+        self.debugger.check_access(addr, value, false);
+        value
+    }
+
+    fn write(&mut self, _: &mut W65C02S, addr: u16, data: u8) {
+        self.debugger.check_access(addr, data, true);
+        self.bus.cpu_write(addr, data);
+    }
+}
@@ -0,0 +1,192 @@
+//! 65C02 disassembly
+//!
+//! [`decode`] reads one instruction, with the WDC 65C02's extra opcodes
+//! (`WAI`, `STP`, `RMB`/`SMB` and `BBR`/`BBS`). The opcodes it leaves
+//! undefined are `NOP`s. Those that skip an operand come out as `NOP` with
+//! it, so the listing keeps to the instructions the CPU runs; the one-byte
+//! ones come out as `.byte`, since in a ROM they're nearly always data.
+
+use alloc::format;
+use alloc::string::String;
+
+/// How an instruction finds its operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    ZeroPageIndirect,
+    ZeroPageXIndirect,
+    ZeroPageIndirectY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    AbsoluteIndirect,
+    AbsoluteXIndirect,
+    /// A branch, 8-bit signed offset from the next instruction
+    Relative,
+    /// `BBR`/`BBS`: a zero page address, then a branch offset
+    ZeroPageRelative,
+    /// Not an instruction
+    Data,
+}
+
+impl Mode {
+    /// Bytes of operand after the opcode
+    pub fn operand_len(self) -> usize {
+        use Mode::*;
+        match self {
+            Implied | Accumulator | Data => 0,
+            Immediate | ZeroPage | ZeroPageX | ZeroPageY | ZeroPageIndirect
+            | ZeroPageXIndirect | ZeroPageIndirectY | Relative => 1,
+            Absolute | AbsoluteX | AbsoluteY | AbsoluteIndirect | AbsoluteXIndirect
+            | ZeroPageRelative => 2,
+        }
+    }
+}
+
+use Mode::*;
+
+/// Mnemonic and addressing mode of every opcode
+const OPCODES: [(&str, Mode); 256] = [
+    /* 00 */ ("BRK", Implied), ("ORA", ZeroPageXIndirect), ("NOP", Immediate), (".byte", Data),
+    /* 04 */ ("TSB", ZeroPage), ("ORA", ZeroPage), ("ASL", ZeroPage), ("RMB0", ZeroPage),
+    /* 08 */ ("PHP", Implied), ("ORA", Immediate), ("ASL", Accumulator), (".byte", Data),
+    /* 0C */ ("TSB", Absolute), ("ORA", Absolute), ("ASL", Absolute), ("BBR0", ZeroPageRelative),
+    /* 10 */ ("BPL", Relative), ("ORA", ZeroPageIndirectY), ("ORA", ZeroPageIndirect), (".byte", Data),
+    /* 14 */ ("TRB", ZeroPage), ("ORA", ZeroPageX), ("ASL", ZeroPageX), ("RMB1", ZeroPage),
+    /* 18 */ ("CLC", Implied), ("ORA", AbsoluteY), ("INC", Accumulator), (".byte", Data),
+    /* 1C */ ("TRB", Absolute), ("ORA", AbsoluteX), ("ASL", AbsoluteX), ("BBR1", ZeroPageRelative),
+    /* 20 */ ("JSR", Absolute), ("AND", ZeroPageXIndirect), ("NOP", Immediate), (".byte", Data),
+    /* 24 */ ("BIT", ZeroPage), ("AND", ZeroPage), ("ROL", ZeroPage), ("RMB2", ZeroPage),
+    /* 28 */ ("PLP", Implied), ("AND", Immediate), ("ROL", Accumulator), (".byte", Data),
+    /* 2C */ ("BIT", Absolute), ("AND", Absolute), ("ROL", Absolute), ("BBR2", ZeroPageRelative),
+    /* 30 */ ("BMI", Relative), ("AND", ZeroPageIndirectY), ("AND", ZeroPageIndirect), (".byte", Data),
+    /* 34 */ ("BIT", ZeroPageX), ("AND", ZeroPageX), ("ROL", ZeroPageX), ("RMB3", ZeroPage),
+    /* 38 */ ("SEC", Implied), ("AND", AbsoluteY), ("DEC", Accumulator), (".byte", Data),
+    /* 3C */ ("BIT", AbsoluteX), ("AND", AbsoluteX), ("ROL", AbsoluteX), ("BBR3", ZeroPageRelative),
+    /* 40 */ ("RTI", Implied), ("EOR", ZeroPageXIndirect), ("NOP", Immediate), (".byte", Data),
+    /* 44 */ ("NOP", ZeroPage), ("EOR", ZeroPage), ("LSR", ZeroPage), ("RMB4", ZeroPage),
+    /* 48 */ ("PHA", Implied), ("EOR", Immediate), ("LSR", Accumulator), (".byte", Data),
+    /* 4C */ ("JMP", Absolute), ("EOR", Absolute), ("LSR", Absolute), ("BBR4", ZeroPageRelative),
+    /* 50 */ ("BVC", Relative), ("EOR", ZeroPageIndirectY), ("EOR", ZeroPageIndirect), (".byte", Data),
+    /* 54 */ ("NOP", ZeroPageX), ("EOR", ZeroPageX), ("LSR", ZeroPageX), ("RMB5", ZeroPage),
+    /* 58 */ ("CLI", Implied), ("EOR", AbsoluteY), ("PHY", Implied), (".byte", Data),
+    /* 5C */ ("NOP", Absolute), ("EOR", AbsoluteX), ("LSR", AbsoluteX), ("BBR5", ZeroPageRelative),
+    /* 60 */ ("RTS", Implied), ("ADC", ZeroPageXIndirect), ("NOP", Immediate), (".byte", Data),
+    /* 64 */ ("STZ", ZeroPage), ("ADC", ZeroPage), ("ROR", ZeroPage), ("RMB6", ZeroPage),
+    /* 68 */ ("PLA", Implied), ("ADC", Immediate), ("ROR", Accumulator), (".byte", Data),
+    /* 6C */ ("JMP", AbsoluteIndirect), ("ADC", Absolute), ("ROR", Absolute), ("BBR6", ZeroPageRelative),
+    /* 70 */ ("BVS", Relative), ("ADC", ZeroPageIndirectY), ("ADC", ZeroPageIndirect), (".byte", Data),
+    /* 74 */ ("STZ", ZeroPageX), ("ADC", ZeroPageX), ("ROR", ZeroPageX), ("RMB7", ZeroPage),
+    /* 78 */ ("SEI", Implied), ("ADC", AbsoluteY), ("PLY", Implied), (".byte", Data),
+    /* 7C */ ("JMP", AbsoluteXIndirect), ("ADC", AbsoluteX), ("ROR", AbsoluteX), ("BBR7", ZeroPageRelative),
+    /* 80 */ ("BRA", Relative), ("STA", ZeroPageXIndirect), ("NOP", Immediate), (".byte", Data),
+    /* 84 */ ("STY", ZeroPage), ("STA", ZeroPage), ("STX", ZeroPage), ("SMB0", ZeroPage),
+    /* 88 */ ("DEY", Implied), ("BIT", Immediate), ("TXA", Implied), (".byte", Data),
+    /* 8C */ ("STY", Absolute), ("STA", Absolute), ("STX", Absolute), ("BBS0", ZeroPageRelative),
+    /* 90 */ ("BCC", Relative), ("STA", ZeroPageIndirectY), ("STA", ZeroPageIndirect), (".byte", Data),
+    /* 94 */ ("STY", ZeroPageX), ("STA", ZeroPageX), ("STX", ZeroPageY), ("SMB1", ZeroPage),
+    /* 98 */ ("TYA", Implied), ("STA", AbsoluteY), ("TXS", Implied), (".byte", Data),
+    /* 9C */ ("STZ", Absolute), ("STA", AbsoluteX), ("STZ", AbsoluteX), ("BBS1", ZeroPageRelative),
+    /* A0 */ ("LDY", Immediate), ("LDA", ZeroPageXIndirect), ("LDX", Immediate), (".byte", Data),
+    /* A4 */ ("LDY", ZeroPage), ("LDA", ZeroPage), ("LDX", ZeroPage), ("SMB2", ZeroPage),
+    /* A8 */ ("TAY", Implied), ("LDA", Immediate), ("TAX", Implied), (".byte", Data),
+    /* AC */ ("LDY", Absolute), ("LDA", Absolute), ("LDX", Absolute), ("BBS2", ZeroPageRelative),
+    /* B0 */ ("BCS", Relative), ("LDA", ZeroPageIndirectY), ("LDA", ZeroPageIndirect), (".byte", Data),
+    /* B4 */ ("LDY", ZeroPageX), ("LDA", ZeroPageX), ("LDX", ZeroPageY), ("SMB3", ZeroPage),
+    /* B8 */ ("CLV", Implied), ("LDA", AbsoluteY), ("TSX", Implied), (".byte", Data),
+    /* BC */ ("LDY", AbsoluteX), ("LDA", AbsoluteX), ("LDX", AbsoluteY), ("BBS3", ZeroPageRelative),
+    /* C0 */ ("CPY", Immediate), ("CMP", ZeroPageXIndirect), ("NOP", Immediate), (".byte", Data),
+    /* C4 */ ("CPY", ZeroPage), ("CMP", ZeroPage), ("DEC", ZeroPage), ("SMB4", ZeroPage),
+    /* C8 */ ("INY", Implied), ("CMP", Immediate), ("DEX", Implied), ("WAI", Implied),
+    /* CC */ ("CPY", Absolute), ("CMP", Absolute), ("DEC", Absolute), ("BBS4", ZeroPageRelative),
+    /* D0 */ ("BNE", Relative), ("CMP", ZeroPageIndirectY), ("CMP", ZeroPageIndirect), (".byte", Data),
+    /* D4 */ ("NOP", ZeroPageX), ("CMP", ZeroPageX), ("DEC", ZeroPageX), ("SMB5", ZeroPage),
+    /* D8 */ ("CLD", Implied), ("CMP", AbsoluteY), ("PHX", Implied), ("STP", Implied),
+    /* DC */ ("NOP", Absolute), ("CMP", AbsoluteX), ("DEC", AbsoluteX), ("BBS5", ZeroPageRelative),
+    /* E0 */ ("CPX", Immediate), ("SBC", ZeroPageXIndirect), ("NOP", Immediate), (".byte", Data),
+    /* E4 */ ("CPX", ZeroPage), ("SBC", ZeroPage), ("INC", ZeroPage), ("SMB6", ZeroPage),
+    /* E8 */ ("INX", Implied), ("SBC", Immediate), ("NOP", Implied), (".byte", Data),
+    /* EC */ ("CPX", Absolute), ("SBC", Absolute), ("INC", Absolute), ("BBS6", ZeroPageRelative),
+    /* F0 */ ("BEQ", Relative), ("SBC", ZeroPageIndirectY), ("SBC", ZeroPageIndirect), (".byte", Data),
+    /* F4 */ ("NOP", ZeroPageX), ("SBC", ZeroPageX), ("INC", ZeroPageX), ("SMB7", ZeroPage),
+    /* F8 */ ("SED", Implied), ("SBC", AbsoluteY), ("PLX", Implied), (".byte", Data),
+    /* FC */ ("NOP", Absolute), ("SBC", AbsoluteX), ("INC", AbsoluteX), ("BBS7", ZeroPageRelative),
+];
+
+/// One decoded instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    pub mode: Mode,
+    /// The operand bytes, little-endian; for `BBR`/`BBS`, the zero page
+    /// address in the low byte and the offset in the high byte
+    pub operand: u16,
+}
+
+/// The instruction at the start of `bytes`, or `None` if they run out
+/// partway through it
+pub fn decode(bytes: &[u8]) -> Option<Instruction> {
+    let opcode = *bytes.first()?;
+    let (mnemonic, mode) = OPCODES[opcode as usize];
+    let operand = match mode.operand_len() {
+        0 => 0,
+        1 => *bytes.get(1)? as u16,
+        _ => u16::from_le_bytes([*bytes.get(1)?, *bytes.get(2)?]),
+    };
+    Some(Instruction { opcode, mnemonic, mode, operand })
+}
+
+impl Instruction {
+    /// Bytes the instruction takes, opcode included
+    pub fn size(&self) -> usize {
+        1 + self.mode.operand_len()
+    }
+
+    /// Where a jump, call or branch at `pc` goes, if it goes somewhere fixed
+    pub fn target(&self, pc: u16) -> Option<u16> {
+        let next = pc.wrapping_add(self.size() as u16);
+        match self.mode {
+            Relative => Some(next.wrapping_add_signed(self.operand as u8 as i8 as i16)),
+            ZeroPageRelative => Some(next.wrapping_add_signed((self.operand >> 8) as u8 as i8 as i16)),
+            Absolute if matches!(self.mnemonic, "JMP" | "JSR") => Some(self.operand),
+            _ => None,
+        }
+    }
+
+    /// Whether execution doesn't carry on to the next instruction
+    pub fn ends_flow(&self) -> bool {
+        matches!(self.mnemonic, "RTS" | "RTI" | "JMP" | "BRA" | "STP")
+    }
+
+    /// The instruction in assembler syntax, for one at `pc`
+    pub fn format(&self, pc: u16) -> String {
+        let op = self.operand;
+        let target = self.target(pc).unwrap_or(0);
+        let operand = match self.mode {
+            Implied => return String::from(self.mnemonic),
+            Data => return format!(".byte ${:02X}", self.opcode),
+            Accumulator => String::from("A"),
+            Immediate => format!("#${:02X}", op),
+            ZeroPage => format!("${:02X}", op),
+            ZeroPageX => format!("${:02X},X", op),
+            ZeroPageY => format!("${:02X},Y", op),
+            ZeroPageIndirect => format!("(${:02X})", op),
+            ZeroPageXIndirect => format!("(${:02X},X)", op),
+            ZeroPageIndirectY => format!("(${:02X}),Y", op),
+            Absolute => format!("${:04X}", op),
+            AbsoluteX => format!("${:04X},X", op),
+            AbsoluteY => format!("${:04X},Y", op),
+            AbsoluteIndirect => format!("(${:04X})", op),
+            AbsoluteXIndirect => format!("(${:04X},X)", op),
+            Relative => format!("${:04X}", target),
+            ZeroPageRelative => format!("${:02X},${:04X}", op & 0xFF, target),
+        };
+        format!("{} {}", self.mnemonic, operand)
+    }
+}
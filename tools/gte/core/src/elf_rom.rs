@@ -0,0 +1,234 @@
+//! Loading the ELF cargo builds as a ROM
+//!
+//! `gtrom build` lays an ELF's sections out into 128 banks and fills in the
+//! bank table before writing the `.gtr`. [`load`] does the same in memory,
+//! so the emulator can run the ELF as it comes out of the linker and keep
+//! its function names for the debugger. Source lines are only in the `.sym`
+//! `gtrom build` writes.
+//!
+//! Sections go where the SDK's linker script puts them:
+//! - `.text.bankN` and `.rodata.bankN` in bank N, for banks 0-126
+//! - `.text`, `.rodata`, `.bank_table`, `.rom_info` and `.vector_table` in
+//!   the fixed bank, 127
+//! - the starting values of `.data` and `.zp` in the fixed bank, at
+//!   `__data_load` and `__zp_load`, for crt0 to copy into RAM
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use elf::{abi::STT_FUNC, endian::AnyEndian, ElfBytes};
+use log::warn;
+use rustc_demangle::demangle;
+
+use crate::symbols::{Function, SymbolMap};
+
+pub const BANK_SIZE: usize = 0x4000;
+pub const BANK_COUNT: usize = 128;
+pub const FIXED_BANK: u8 = 127;
+
+/// Sections copied into RAM at boot, and the symbols marking where their
+/// starting values are in ROM
+pub const LOADED_SECTIONS: [(&str, &str); 2] = [(".data", "__data_load"), (".zp", "__zp_load")];
+
+/// Whether `bytes` is an ELF rather than a ROM image
+pub fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x7FELF")
+}
+
+#[derive(Debug, Clone)]
+pub struct ElfSection {
+    pub name: String,
+    pub bytes: Vec<u8>,
+    pub size: usize,
+    pub mem_loc: usize,
+    /// Where it's linked in ROM; the same as `mem_loc` unless it's copied to RAM
+    pub rom_loc: usize,
+    pub bank_loc: usize,
+    pub bank: u8,
+}
+
+impl ElfSection {
+    pub fn from_static(section_name: String, elf: &ElfBytes<'_, AnyEndian>, bank: u8) -> Option<Self> {
+        let header = elf.section_header_by_name(&section_name).ok().flatten()?;
+
+        let load_addr = header.sh_addr as usize;
+        let size = header.sh_size as usize;
+        let offset_in_bank = load_addr & 0x3FFF;
+
+        let (d, _ch) = elf.section_data(&header).ok()?;
+
+        Some(Self {
+            name: section_name,
+            bytes: Vec::from(d),
+            size,
+            mem_loc: load_addr,
+            rom_loc: load_addr,
+            bank,
+            bank_loc: offset_in_bank,
+        })
+    }
+
+    pub fn from_loaded(
+        section_name: String,
+        elf: &ElfBytes<'_, AnyEndian>,
+        load_symbol: &str,
+    ) -> Option<Self> {
+        let header = elf.section_header_by_name(&section_name).ok().flatten()?;
+        let (bytes, _) = elf.section_data(&header).ok()?;
+
+        let (symtab, strtab) = elf.symbol_table().ok().flatten()?;
+        let load_sym = symtab.iter().find_map(|sym| {
+            let name = strtab.get(sym.st_name as usize).ok()?;
+            if name == load_symbol { Some(sym) } else { None }
+        })?;
+
+        let load_rom_addr = load_sym.st_value as usize; // where the section is in ROM
+        let mem_target_addr = header.sh_addr as usize; // where the section ends up in RAM
+
+        Some(Self {
+            name: section_name,
+            bytes: bytes.to_vec(),
+            size: bytes.len(),
+            mem_loc: mem_target_addr,
+            rom_loc: load_rom_addr,
+            bank: FIXED_BANK,
+            bank_loc: load_rom_addr & 0x3FFF,
+        })
+    }
+}
+
+/// ROM bank a linked address lives in: BANKn is linked at $8000 + n * $10000,
+/// and the fixed bank is 127
+pub fn bank_of_addr(addr: usize) -> Option<u8> {
+    match addr {
+        0xC000..=0xFFFF => Some(FIXED_BANK),
+        _ if (0x8000..0xC000).contains(&(addr & 0xFFFF)) && addr >> 16 <= 126 => Some((addr >> 16) as u8),
+        _ => None,
+    }
+}
+
+/// The sections each bank is made of, by bank
+pub fn static_sections() -> [Vec<String>; BANK_COUNT] {
+    core::array::from_fn(|i| match i {
+        0..=126 => vec![format!(".text.bank{}", i), format!(".rodata.bank{}", i)],
+        _ => vec![
+            ".text".to_string(),
+            ".rodata".to_string(),
+            ".bank_table".to_string(),
+            ".rom_info".to_string(),
+            ".vector_table".to_string(),
+        ],
+    })
+}
+
+/// Every section of `elf` that goes into the ROM
+pub fn rom_sections(elf: &ElfBytes<'_, AnyEndian>) -> Vec<ElfSection> {
+    static_sections()
+        .into_iter()
+        .enumerate()
+        .flat_map(|(bank, names)| {
+            names
+                .into_iter()
+                .filter_map(move |name| ElfSection::from_static(name, elf, bank as u8))
+        })
+        .chain(LOADED_SECTIONS.iter().filter_map(|(section, load_symbol)| {
+            ElfSection::from_loaded(section.to_string(), elf, load_symbol)
+        }))
+        .collect()
+}
+
+/// The flat 2 MB image of `sections`, bank 0 first. The gaps are 0xFF, like
+/// erased flash, so unused banks needn't be written to a cartridge.
+pub fn rom_image(sections: &[ElfSection]) -> Vec<u8> {
+    let mut rom = vec![0xFFu8; BANK_SIZE * BANK_COUNT];
+    for s in sections {
+        let start = s.bank as usize * BANK_SIZE + s.bank_loc;
+        rom[start..start + s.size].copy_from_slice(&s.bytes);
+    }
+    rom
+}
+
+/// A `__bank_of_<item>` bank table entry and the bank `<item>` was linked into
+pub struct BankTableEntry {
+    pub item: String,
+    pub entry_addr: usize,
+    pub bank: Option<u8>,
+}
+
+pub fn read_bank_table(elf: &ElfBytes<'_, AnyEndian>) -> Vec<BankTableEntry> {
+    let Some((symtab, strtab)) = elf.symbol_table().ok().flatten() else {
+        return vec![];
+    };
+
+    let symbols: Vec<(&str, usize)> = symtab.iter()
+        .filter_map(|sym| Some((strtab.get(sym.st_name as usize).ok()?, sym.st_value as usize)))
+        .collect();
+
+    symbols.iter()
+        .filter_map(|(name, addr)| {
+            let item = name.strip_prefix("__bank_of_")?;
+            let bank = symbols.iter()
+                .find(|(n, _)| n == &item)
+                .and_then(|(_, item_addr)| bank_of_addr(*item_addr));
+            Some(BankTableEntry { item: item.to_string(), entry_addr: *addr, bank })
+        })
+        .collect()
+}
+
+/// Every sized function in the symbol table, demangled, by address
+pub fn functions(elf: &ElfBytes<'_, AnyEndian>) -> Vec<Function> {
+    let Some((symtab, strtab)) = elf.symbol_table().ok().flatten() else {
+        return vec![];
+    };
+
+    let mut functions: Vec<Function> = symtab.iter()
+        .filter(|sym| sym.st_symtype() == STT_FUNC && sym.st_size > 0)
+        .filter_map(|sym| {
+            let name = strtab.get(sym.st_name as usize).ok()?;
+            Some(Function {
+                address: sym.st_value as u32,
+                size: sym.st_size as u32,
+                name: format!("{:#}", demangle(name)),
+            })
+        })
+        .collect();
+
+    functions.sort_by_key(|f| f.address);
+    functions.dedup_by_key(|f| f.address);
+    functions
+}
+
+/// The ROM image `gtrom build` would make from an ELF, minus the `.gtr`
+/// header, and its function names. Unlike `gtrom build` it doesn't check
+/// the layout, past leaving out sections that run off the end of their bank.
+pub fn load(bytes: &[u8]) -> Result<(Vec<u8>, SymbolMap), String> {
+    let elf = ElfBytes::<AnyEndian>::minimal_parse(bytes)
+        .map_err(|e| format!("Failed to parse ELF: {}", e))?;
+
+    let sections: Vec<ElfSection> = rom_sections(&elf).into_iter()
+        .filter(|s| {
+            let fits = s.bank_loc + s.size <= BANK_SIZE;
+            if !fits {
+                warn!(" - {} doesn't fit in bank {}, leaving it out", s.name, s.bank);
+            }
+            fits
+        })
+        .collect();
+    if !sections.iter().any(|s| s.name == ".vector_table") {
+        return Err("ELF has no .vector_table section, so it can't boot".to_string());
+    }
+
+    let mut rom = rom_image(&sections);
+    for entry in read_bank_table(&elf) {
+        match entry.bank {
+            Some(bank) if bank_of_addr(entry.entry_addr) == Some(FIXED_BANK) => {
+                rom[FIXED_BANK as usize * BANK_SIZE + (entry.entry_addr & 0x3FFF)] = bank;
+            }
+            _ => warn!(" - bank table entry for {} left unfilled", entry.item),
+        }
+    }
+
+    Ok((rom, SymbolMap::from_functions(functions(&elf))))
+}
@@ -1,27 +1,103 @@
 use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
 use gte_w65c02s::{System, W65C02S};
 use log::{debug, error, info, warn};
-use gte_w65c02s::State::AwaitingInterrupt;
+use gte_w65c02s::State::{AwaitingInterrupt, Running, Stopped};
+use core::cell::Ref;
 use core::fmt::{Debug, Formatter};
 use bytemuck::bytes_of;
 use heapless::{FnvIndexMap};
 use rtrb::PushError;
 use gte_acp::audio_output::GameTankAudio;
-use crate::blitter::Blitter;
-use crate::cartridges::CartridgeType;
+use crate::blitter::{BlitInfo, Blitter};
+use crate::cartridges::{mapper::Mapper, CartridgeType};
+use crate::debugger::{Debugger, Registers, StopReason, WatchedBus};
+use crate::symbols::linked_address;
+use crate::elf_rom;
+use crate::rom_header::{crc32, RomHeader};
 use crate::emulator::PlayState::{Paused, Playing, WasmInit};
-use crate::gametank_bus::{CpuBus};
-use gte_acp::AcpBus;
-use crate::inputs::{ControllerButton, InputCommand, KeyState};
+use crate::gametank_bus::{BlitterRegisters, CpuBus, FrameBuffer};
+use crate::savestate::{self, RewindBuffer, StateReader, StateWriter};
+use gte_acp::{AcpBus, ARAM};
+use crate::acp::{AcpVoice, DacScope, VoiceLayout};
+use crate::inputs::{ControllerButton, GamePad, InputCommand, KeyState};
+use crate::movie::Movie;
+use crate::cheats::{Cheat, Width};
+use crate::trace::Trace;
+use crate::heatmap::Heatmap;
+use crate::bench::{Bench, BenchReport, Part};
+use crate::color_map::{parse_palette, ColorMap, Palette};
 use crate::inputs::ControllerButton::{Down, Left, Right, Start, Up, A, B, C};
-use crate::inputs::InputCommand::{Controller1, Controller2, HardReset, PlayPause, SoftReset};
+use crate::inputs::InputCommand::{Controller1, Controller2, FastForward, FrameAdvance, HardReset, PlayPause, Rewind, SoftReset};
 use crate::inputs::KeyState::JustReleased;
 
 pub const WIDTH: u32 = 128;
 pub const HEIGHT: u32 = 128;
 
+/// ACP cycles per CPU cycle on real hardware
+pub const ACP_CLOCK_RATIO: i32 = 4;
+
+/// Opcode of JSR, for finding return addresses on the stack
+const JSR: u8 = 0x20;
+
+/// Opcode of RTI, for timing interrupt handlers in a trace
+const RTI: u8 = 0x40;
+
+/// CPU cycles the ACP may fall behind in [`AcpAccuracy::Fast`]
+pub const FAST_ACP_BATCH: i32 = 256;
+
+/// Frames of history for [`Emulator::enable_rewind`]: ten seconds
+pub const REWIND_FRAMES: usize = 600;
+
+/// Frames stepped back per update while [`InputCommand::Rewind`] is held,
+/// so rewinding runs at double speed
+const REWIND_SPEED: usize = 2;
+
+/// Speed while [`InputCommand::FastForward`] is held
+pub const FAST_FORWARD_SPEED: f32 = 4.0;
+
+/// Speeds frontends step through with [`next_speed`]
+pub const SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// The next of [`SPEEDS`] above `speed`, or below it unless `faster`,
+/// stopping at either end
+pub fn next_speed(speed: f32, faster: bool) -> f32 {
+    if faster {
+        SPEEDS.iter().copied().find(|&s| s > speed).unwrap_or(SPEEDS[SPEEDS.len() - 1])
+    } else {
+        SPEEDS.iter().copied().rev().find(|&s| s < speed).unwrap_or(SPEEDS[0])
+    }
+}
+
+/// One image of a capture, with how many frames it stayed on screen
+pub struct CapturedFrame {
+    pub pixels: Box<[u8; (WIDTH * HEIGHT) as usize]>,
+    pub frames: u32,
+}
+
+/// A movie being recorded or played back
+enum MovieRun {
+    Recording(Movie),
+    /// The movie, and the next frame to play
+    Playing(Movie, usize),
+}
+
+/// How the ACP is interleaved with the main CPU
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AcpAccuracy {
+    /// Run the ACP after every CPU instruction at [`ACP_CLOCK_RATIO`],
+    /// carrying leftover cycles over so neither side drifts. Firmware sees
+    /// the same cycle budget per sample as on hardware.
+    Accurate,
+    /// Let the ACP fall up to [`FAST_ACP_BATCH`] CPU cycles behind and catch
+    /// up in one go, at `acp_clock_ratio`. Cheaper on slow hosts, but writes
+    /// to audio RAM land late and slow ratios starve the firmware.
+    Fast,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum PlayState {
     WasmInit,
@@ -50,7 +126,42 @@ pub struct Emulator<Clock: TimeDaemon> {
     pub audio_out: Option<GameTankAudio>,
     pub target_sample_rate: f64,
     pub play_state: PlayState,
+    /// Emulated time per real time; see [`set_speed`](Self::set_speed)
+    speed: f32,
+    /// Whether [`InputCommand::FastForward`] is held
+    fast_forward: bool,
     pub wait_counter: u64,
+    /// ACP cycles run per CPU cycle in [`AcpAccuracy::Fast`]. Lower than
+    /// [`ACP_CLOCK_RATIO`] is cheaper to emulate, but firmware gets less time
+    /// per sample.
+    pub acp_clock_ratio: i32,
+    pub acp_accuracy: AcpAccuracy,
+    /// ACP cycles owed to (or, when negative, run ahead of) the CPU
+    acp_cycles: i32,
+    /// Recent samples the ACP sent to the DAC
+    pub acp_scope: DacScope,
+
+    pub debugger: Debugger,
+    /// A save state for each recent frame, when rewind is enabled
+    pub rewind: Option<RewindBuffer>,
+    /// What was on screen each frame, while capturing
+    capture: Option<Vec<CapturedFrame>>,
+    /// Frames, blits, interrupts and bank switches, while tracing
+    trace: Option<Box<Trace>>,
+    /// Step counts and sampled timings, while benchmarking
+    bench: Option<Box<Bench>>,
+    /// Controller input for each frame, while recording or playing a movie
+    movie: Option<MovieRun>,
+    /// CRC-32 of the loaded ROM, without its header
+    rom_crc: u32,
+    /// The loaded ROM's header, or the one built into it
+    rom_info: Option<RomHeader>,
+    /// Written at every vblank, before the NMI; see [`cheats`](crate::cheats)
+    pub cheats: Vec<Cheat>,
+    /// Colors for each hardware color index, for frontends to draw with
+    color_map: Box<ColorMap>,
+    /// Which built-in palette `color_map` is, or `None` for one loaded from a file
+    palette: Option<Palette>,
 
     pub input_state: FnvIndexMap<InputCommand, KeyState, 32>, // capacity of 32 entries
 
@@ -58,9 +169,59 @@ pub struct Emulator<Clock: TimeDaemon> {
 }
 
 impl <Clock: TimeDaemon> Emulator<Clock> {
+    /// Load a ROM image, with or without a `.gtr` header, or an ELF straight
+    /// from cargo, which also brings its function names to the debugger.
+    /// The previous ROM's symbols are dropped either way.
     pub fn load_rom(&mut self, bytes: &[u8]) {
+        if elf_rom::is_elf(bytes) {
+            warn!("loading rom from an ELF, size: {}", bytes.len());
+            match elf_rom::load(bytes) {
+                Ok((rom, symbols)) => {
+                    warn!(" - {} function symbol(s)", symbols.functions().len());
+                    self.load_rom(&rom);
+                    self.debugger.symbols = symbols;
+                }
+                Err(e) => error!(" - {}", e),
+            }
+            return;
+        }
+
         warn!("loading new rom from memory, size: {}", bytes.len());
-        self.cpu_bus.cartridge = CartridgeType::from_slice(bytes);
+        let (header, bytes) = RomHeader::split(bytes);
+        let header = match header {
+            Some(Ok(header)) => {
+                warn!(" - .gtr header: \"{}\" by \"{}\", {} bank(s)", header.title, header.author, header.bank_count);
+                if let Err(e) = header.validate(bytes) {
+                    error!(" - .gtr header mismatch: {}", e);
+                }
+                Some(header)
+            }
+            Some(Err(e)) => { error!(" - invalid .gtr header: {}", e); None }
+            None => None,
+        };
+        let (mapper, save_size) = match &header {
+            Some(header) => {
+                let mapper = Mapper::from_id(header.mapper).unwrap_or_else(|| {
+                    error!(" - unknown mapper {}, going by the ROM's size", header.mapper);
+                    Mapper::Auto
+                });
+                (mapper, header.save_size as usize * 1024)
+            }
+            None => (Mapper::Auto, 0),
+        };
+        let cartridge = match CartridgeType::with_mapper(bytes, mapper, save_size) {
+            Ok(cartridge) => cartridge,
+            Err(e) => {
+                error!(" - {}", e);
+                return;
+            }
+        };
+        warn!(" - {} mapper", cartridge.mapper().name());
+        self.rom_info = header.or_else(|| RomHeader::embedded(bytes));
+        self.cpu_bus.cartridge = cartridge;
+        self.rom_crc = crc32(bytes);
+        self.debugger.symbols = Default::default();
+        self.movie = None;
         warn!(" - cartridge loaded from memory");
         self.cpu.reset();
         warn!(" - cpu reset");
@@ -68,6 +229,9 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
         warn!(" - acp reset");
         self.blitter.clear_irq_trigger();
         warn!(" - blitter irq cleared");
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
     }
 }
 
@@ -126,7 +290,24 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
             last_render_time,
             audio_out: None,
             target_sample_rate,
+            speed: 1.0,
+            fast_forward: false,
             wait_counter: 0,
+            acp_clock_ratio: ACP_CLOCK_RATIO,
+            acp_accuracy: AcpAccuracy::Accurate,
+            acp_cycles: 0,
+            acp_scope: DacScope::default(),
+            debugger: Debugger::default(),
+            rewind: None,
+            capture: None,
+            trace: None,
+            bench: None,
+            movie: None,
+            rom_crc: 0,
+            rom_info: None,
+            cheats: vec![],
+            color_map: Box::new(Palette::default().color_map()),
+            palette: Some(Palette::default()),
             input_state: Default::default(),
             clock,
         }
@@ -140,6 +321,11 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
         }
 
         let now_ms = self.clock.get_now_ms();
+        if self.is_rewinding() {
+            // rewinding replaces running; don't make up the time afterwards
+            self.last_emu_tick = now_ms;
+            return
+        }
         let mut elapsed_ms = now_ms - self.last_emu_tick;
 
         if elapsed_ms > 33.0 {
@@ -147,12 +333,17 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
             elapsed_ms = 16.667;
         }
 
-        let elapsed_ns = elapsed_ms * 1000000.0;
+        let elapsed_ns = elapsed_ms * self.run_speed() as f64 * 1000000.0;
         let mut remaining_cycles: i32 = (elapsed_ns / self.cpu_ns_per_cycle) as i32;
 
-        let mut acp_cycle_accumulator = 0;
-
         while remaining_cycles > 0 {
+            if self.debugger.is_active()
+                && self.cpu.get_state() == Running
+                && self.debugger.should_break(self.cpu.get_pc()) {
+                self.play_state = Paused;
+                break;
+            }
+
             if self.cpu.get_state() == AwaitingInterrupt {
                 self.wait_counter += 1;
                 // get cpu's current asm code
@@ -161,44 +352,455 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
                 self.wait_counter = 0;
             }
 
-            let cpu_cycles = self.cpu.step(&mut self.cpu_bus);
+            remaining_cycles -= self.step_cpu();
 
-            remaining_cycles -= cpu_cycles;
+            if let Some(hit) = self.debugger.take_hit() {
+                self.debugger.stop_reason = Some(hit);
+                self.play_state = Paused;
+                break;
+            }
+        }
+
+        self.last_emu_tick = now_ms;
+
+        if !is_web && (now_ms - self.last_render_time) >= 16.67 {
+            debug!("time since last render: {}", now_ms - self.last_render_time);
+            self.last_render_time = now_ms;
+        }
+    }
+
+    /// Run one CPU step along with everything clocked alongside it:
+    /// the ACP, the blitter, and vblank. Returns the CPU cycles taken.
+    fn step_cpu(&mut self) -> i32 {
+        if let Some(trace) = &mut self.trace {
+            let returning = self.cpu_bus.peek_byte(self.cpu.get_pc()) == RTI;
+            trace.before_step(self.cpu.pending_interrupt(), returning);
+        }
 
-            acp_cycle_accumulator += cpu_cycles * 4;
+        // while benchmarking, time this step's parts now and then
+        let sampled = self.bench.as_mut().is_some_and(|bench| bench.sample());
+        let mut lap = sampled.then(|| self.clock.get_now_ms());
+        let running = self.cpu.get_state() == Running;
 
-            // pass aram to acp
-            if self.cpu_bus.system_control.acp_enabled() {
-                self.run_acp(&mut acp_cycle_accumulator);
+        let cpu_cycles = if self.debugger.is_active() {
+            self.cpu.step(&mut WatchedBus { bus: &mut self.cpu_bus, debugger: &mut self.debugger })
+        } else {
+            self.cpu.step(&mut self.cpu_bus)
+        };
+        if let Some(bench) = &mut self.bench {
+            bench.count(cpu_cycles, running);
+        }
+        self.bench_lap(&mut lap, Part::Cpu);
+
+        // pass aram to acp
+        if self.cpu_bus.system_control.acp_enabled() {
+            let (ratio, batch) = match self.acp_accuracy {
+                AcpAccuracy::Accurate => (ACP_CLOCK_RATIO, 0),
+                AcpAccuracy::Fast => (self.acp_clock_ratio, FAST_ACP_BATCH * self.acp_clock_ratio),
+            };
+
+            self.acp_cycles += cpu_cycles * ratio;
+            if self.acp_cycles > batch {
+                self.run_acp(ratio);
             }
+        } else {
+            // a stopped ACP doesn't build up a backlog
+            self.acp_cycles = 0;
+        }
+        self.bench_lap(&mut lap, Part::Acp);
+
+        // blit
+        for _ in 0..cpu_cycles {
+            self.blitter.cycle(&mut self.cpu_bus);
+        }
+        // TODO: instant blit option
+        self.bench_lap(&mut lap, Part::Blitter);
+
+        let blit_irq = self.blitter.irq_trigger;
+        if blit_irq {
+            debug!("blit irq");
+        }
+        self.cpu.set_irq(blit_irq);
+
+        self.cpu_bus.system_control.tick_via(cpu_cycles);
+
+        if self.trace.is_some() {
+            let waiting = self.cpu.get_state() == AwaitingInterrupt;
+            let blit = if self.blitter.is_blitting() { self.blitter.last_blit() } else { None };
+            let banks = self.banks();
+            if let Some(trace) = &mut self.trace {
+                trace.after_step(cpu_cycles, waiting, blit, banks);
+            }
+        }
+
+        self.clock_cycles_to_vblank -= cpu_cycles;
+        if self.clock_cycles_to_vblank <= 0 {
+            self.vblank();
+        }
+        self.bench_lap(&mut lap, Part::Other);
+
+        cpu_cycles
+    }
+
+    /// Add the time since `lap` to `part` of the benchmark, and start the
+    /// next part's lap. Does nothing for steps that aren't timed.
+    fn bench_lap(&mut self, lap: &mut Option<f64>, part: Part) {
+        if let (Some(start), Some(bench)) = (lap.as_mut(), &mut self.bench) {
+            let now = self.clock.get_now_ms();
+            bench.add(part, now - *start);
+            *start = now;
+        }
+    }
 
-            // blit
-            for _ in 0..cpu_cycles {
-                self.blitter.cycle(&mut self.cpu_bus);
+    /// Run until the next vblank, ignoring the clock, for driving the
+    /// emulator headless. Stops early if the CPU executes `STP`.
+    pub fn run_frame(&mut self) {
+        self.process_inputs();
+        self.run_to_vblank();
+    }
+
+    fn run_to_vblank(&mut self) {
+        while self.cpu.get_state() != Stopped {
+            let before = self.clock_cycles_to_vblank;
+            self.step_cpu();
+            // vblank tops the count back up
+            if self.clock_cycles_to_vblank > before {
+                break;
             }
-            // TODO: instant blit option
+        }
+    }
+
+    /// Run `frames` frames headless as fast as they go, and report how fast
+    /// the host emulated them. Stops early if the CPU executes `STP`. See
+    /// [`bench`](crate::bench).
+    pub fn bench(&mut self, frames: u32) -> BenchReport {
+        self.bench = Some(Box::new(Bench::new(|| self.clock.get_now_ms())));
+        let start = self.clock.get_now_ms();
+        let mut slowest: f64 = 0.0;
+        let mut run = 0;
 
-            let blit_irq = self.blitter.irq_trigger;
-            if blit_irq {
-                debug!("blit irq");
+        while run < frames && self.cpu.get_state() != Stopped {
+            let frame_start = self.clock.get_now_ms();
+            self.run_frame();
+            // nothing plays the audio, so throw it away as a frontend would
+            // play it
+            if let Some(audio) = &mut self.audio_out {
+                while audio.output_buffer.pop().is_ok() {}
             }
-            self.cpu.set_irq(blit_irq);
+            slowest = slowest.max(self.clock.get_now_ms() - frame_start);
+            run += 1;
+        }
+
+        let ms = self.clock.get_now_ms() - start;
+        let bench = self.bench.take().unwrap_or_default();
+        bench.report(run, ms, slowest, self.cpu_frequency_hz)
+    }
+
+    /// Lines the ROM printed to the debug port since the last call, oldest
+    /// first. See [`DebugPort`](crate::gametank_bus::DebugPort).
+    pub fn take_debug_messages(&mut self) -> Vec<String> {
+        self.cpu_bus.system_control.debug_port.take().collect()
+    }
+
+    /// What the loaded ROM says it is: its .gtr header, or else the header
+    /// `gametank_rom!` built into it
+    pub fn rom_info(&self) -> Option<&RomHeader> {
+        self.rom_info.as_ref()
+    }
+
+    /// Whether the CPU has executed `STP` and won't run again until reset
+    pub fn is_stopped(&self) -> bool {
+        self.cpu.get_state() == Stopped
+    }
 
-            self.clock_cycles_to_vblank -= cpu_cycles;
-            if self.clock_cycles_to_vblank <= 0 {
-                self.vblank();
+    /// Sprite RAM page `page` (0-7) as a 256x256 image, row by row
+    pub fn sprite_page(&self, page: usize) -> Box<[u8; 256 * 256]> {
+        let bank = &self.cpu_bus.vram_banks[page];
+        let mut image = Box::new([0; 256 * 256]);
+        // sprite RAM is stored as four 128x128 quadrants
+        for (quadrant, pixels) in bank.chunks_exact(128 * 128).enumerate() {
+            let (qx, qy) = ((quadrant % 2) * 128, (quadrant / 2) * 128);
+            for (row, line) in pixels.chunks_exact(128).enumerate() {
+                let start = (qy + row) * 256 + qx;
+                image[start..start + 128].copy_from_slice(line);
             }
         }
+        image
+    }
 
-        self.last_emu_tick = now_ms;
+    /// Framebuffer `index` (0 or 1), whether or not it's on screen
+    pub fn framebuffer(&self, index: usize) -> Ref<'_, FrameBuffer> {
+        self.cpu_bus.framebuffers[index].borrow()
+    }
 
-        if !is_web && (now_ms - self.last_render_time) >= 16.67 {
-            debug!("time since last render: {}", now_ms - self.last_render_time);
-            self.last_render_time = now_ms;
+    /// Which framebuffer is on screen
+    pub fn displayed_framebuffer(&self) -> usize {
+        self.cpu_bus.system_control.get_framebuffer_out()
+    }
+
+    /// The framebuffer on screen, as palette indices (see
+    /// [`color_map`](Self::color_map))
+    pub fn screenshot(&self) -> [u8; (WIDTH * HEIGHT) as usize] {
+        **self.cpu_bus.read_full_framebuffer()
+    }
+
+    /// The colors to draw each hardware color index with
+    pub fn color_map(&self) -> &ColorMap {
+        &self.color_map
+    }
+
+    /// The built-in palette in use, or `None` after [`load_palette`](Self::load_palette)
+    pub fn palette(&self) -> Option<Palette> {
+        self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        *self.color_map = palette.color_map();
+        self.palette = Some(palette);
+    }
+
+    /// Use the colors in a palette file (see [`parse_palette`])
+    pub fn load_palette(&mut self, bytes: &[u8]) -> Result<(), String> {
+        *self.color_map = parse_palette(bytes)?;
+        self.palette = None;
+        Ok(())
+    }
+
+    /// Start keeping what's on screen at every vblank, until
+    /// [`stop_capture`](Self::stop_capture)
+    pub fn start_capture(&mut self) {
+        self.capture = Some(Vec::new());
+    }
+
+    /// The frames since [`start_capture`](Self::start_capture). A frame
+    /// that didn't change is counted rather than kept again.
+    pub fn stop_capture(&mut self) -> Vec<CapturedFrame> {
+        self.capture.take().unwrap_or_default()
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// Start recording a [`Trace`] of each frame, until
+    /// [`stop_trace`](Self::stop_trace)
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Box::new(Trace::new(self.banks())));
+    }
+
+    /// The trace since [`start_trace`](Self::start_trace), if tracing
+    pub fn stop_trace(&mut self) -> Option<Trace> {
+        self.trace.take().map(|trace| *trace)
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Start counting memory accesses into a [`Heatmap`], until
+    /// [`stop_heatmap`](Self::stop_heatmap)
+    pub fn start_heatmap(&mut self) {
+        self.cpu_bus.heatmap = Some(Box::new(Heatmap::new()));
+    }
+
+    /// The counts since [`start_heatmap`](Self::start_heatmap), if recording
+    pub fn stop_heatmap(&mut self) -> Option<Heatmap> {
+        self.cpu_bus.heatmap.take().map(|heatmap| *heatmap)
+    }
+
+    pub fn heatmap(&self) -> Option<&Heatmap> {
+        self.cpu_bus.heatmap.as_deref()
+    }
+
+    /// Start the counts over, if recording
+    pub fn clear_heatmap(&mut self) {
+        if let Some(heatmap) = &mut self.cpu_bus.heatmap {
+            heatmap.clear();
+        }
+    }
+
+    /// ROM bank, RAM bank and sprite RAM page, for the trace
+    fn banks(&self) -> [u8; 3] {
+        let system_control = &self.cpu_bus.system_control;
+        [self.cpu_bus.cartridge.bank(), system_control.get_ram_bank() as u8, system_control.banking_register.vram_page()]
+    }
+
+    fn capture_frame(&mut self) {
+        let Some(capture) = &mut self.capture else { return };
+        let pixels = self.cpu_bus.read_full_framebuffer();
+        match capture.last_mut() {
+            Some(last) if *last.pixels == **pixels => last.frames += 1,
+            _ => capture.push(CapturedFrame { pixels: Box::new(**pixels), frames: 1 }),
+        }
+    }
+
+    /// The blitter registers as the CPU last wrote them
+    pub fn blitter_registers(&self) -> &BlitterRegisters {
+        &self.cpu_bus.blitter
+    }
+
+    /// The most recent blit since reset, which may still be running
+    pub fn last_blit(&self) -> Option<BlitInfo> {
+        self.blitter.last_blit()
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers::of(&self.cpu)
+    }
+
+    pub fn acp_registers(&self) -> Registers {
+        Registers::of(&self.acp)
+    }
+
+    /// The ACP's 4K of RAM
+    pub fn acp_ram(&self) -> &[u8; 0x1000] {
+        unsafe { &*ARAM }
+    }
+
+    /// The ACP's voices, read as `layout`
+    pub fn acp_voices(&self, layout: VoiceLayout) -> Vec<AcpVoice> {
+        layout.voices(self.acp_ram())
+    }
+
+    /// Samples per second the ACP is interrupted at, 0 while audio is off
+    pub fn acp_sample_rate(&self) -> f64 {
+        match self.cpu_bus.system_control.sample_rate() {
+            0 => 0.0,
+            rate => self.cpu_frequency_hz / rate as f64,
+        }
+    }
+
+    /// Read memory as the CPU currently sees it, without side effects
+    pub fn peek(&self, address: u16) -> u8 {
+        self.cpu_bus.peek_byte(address)
+    }
+
+    /// Read memory at a linked address, so banked code can be read without
+    /// switching banks. Plain CPU addresses read as [`peek`](Self::peek) does.
+    pub fn peek_linked(&self, address: u32) -> u8 {
+        let bank = (address >> 16) as u8;
+        match address as u16 {
+            pc @ 0x8000..=0xBFFF if address > 0xFFFF && bank != self.current_bank() => {
+                self.cpu_bus.cartridge.peek_bank(bank, pc - 0x8000)
+            }
+            pc => self.peek(pc),
+        }
+    }
+
+    /// Write memory as the CPU would, for debuggers. Writes to I/O registers
+    /// take effect as usual.
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.cpu_bus.write_byte(address, value);
+    }
+
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.cpu.set_pc(registers.pc);
+        self.cpu.set_a(registers.a);
+        self.cpu.set_x(registers.x);
+        self.cpu.set_y(registers.y);
+        self.cpu.set_s(registers.s);
+        self.cpu.set_p(registers.p);
+    }
+
+    /// ROM bank mapped in at $8000
+    pub fn current_bank(&self) -> u8 {
+        self.cpu_bus.cartridge.bank()
+    }
+
+    /// The PC as a linked address, for looking it up in the symbol map
+    pub fn linked_pc(&self) -> u32 {
+        linked_address(self.cpu.get_pc(), self.current_bank())
+    }
+
+    /// Best guess at the call stack, innermost first: the PC, then the JSR
+    /// behind each return address on the hardware stack. Bytes pushed with
+    /// PHA can look like return addresses, so once symbols are loaded only
+    /// calls from inside a known function count. Banked callers are assumed
+    /// to be in the current bank.
+    pub fn backtrace(&self) -> Vec<u32> {
+        let bank = self.current_bank();
+        let symbols = &self.debugger.symbols;
+        let mut frames = vec![self.linked_pc()];
+
+        let mut sp = self.cpu.get_s() as u16 + 1;
+        while sp < 0xFF {
+            let lo = self.peek(0x0100 + sp) as u16;
+            let hi = self.peek(0x0100 + sp + 1) as u16;
+            // JSR pushes the address of its own last byte
+            let call = (hi << 8 | lo).wrapping_sub(2);
+            let linked = linked_address(call, bank);
+
+            if call >= 0x8000 && self.peek(call) == JSR
+                && (symbols.is_empty() || symbols.function_at(linked).is_some()) {
+                frames.push(linked);
+                sp += 2;
+            } else {
+                sp += 1;
+            }
         }
+
+        frames
+    }
+
+    pub fn pause(&mut self) {
+        self.play_state = Paused;
     }
 
-    fn run_acp(&mut self, acp_cycle_accumulator: &mut i32) {
+    /// Run at `speed` times real time, e.g. 2.0 for double speed. Audio is
+    /// only played at normal speed, since faster or slower would overrun or
+    /// starve the output.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(SPEEDS[0], SPEEDS[SPEEDS.len() - 1]);
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Whether [`InputCommand::FastForward`] is held
+    pub fn is_fast_forwarding(&self) -> bool {
+        self.fast_forward
+    }
+
+    /// The speed being run at, with fast-forward
+    fn run_speed(&self) -> f32 {
+        if self.fast_forward { FAST_FORWARD_SPEED } else { self.speed }
+    }
+
+    /// Pause and run until the next vblank, for stepping through a frame at
+    /// a time
+    pub fn frame_advance(&mut self) {
+        self.play_state = Paused;
+        self.debugger.stop_reason = None;
+        self.run_to_vblank();
+        self.last_emu_tick = self.clock.get_now_ms();
+    }
+
+    /// Continue running, stepping over a breakpoint at the current PC
+    pub fn resume(&mut self) {
+        self.debugger.resume(self.cpu.get_pc());
+        self.last_emu_tick = self.clock.get_now_ms();
+        self.play_state = Playing;
+    }
+
+    /// Pause and execute a single instruction. While the CPU is waiting
+    /// for an interrupt this runs until it wakes (at most one frame).
+    pub fn step_instruction(&mut self) {
+        self.play_state = Paused;
+        self.debugger.stop_reason = None;
+
+        let mut budget = 59659;
+        while self.cpu.get_state() != Running && budget > 0 {
+            budget -= self.step_cpu();
+        }
+        self.step_cpu();
+
+        self.debugger.stop_reason = Some(self.debugger.take_hit().unwrap_or(StopReason::Step));
+    }
+
+    /// Run the ACP until it has caught up with the CPU, at `ratio` ACP cycles
+    /// per CPU cycle
+    fn run_acp(&mut self, ratio: i32) {
         if self.cpu_bus.system_control.clear_acp_reset() {
             self.acp.reset();
         }
@@ -207,9 +809,9 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
             self.acp.set_nmi(true);
         }
 
-        while *acp_cycle_accumulator > 0 {
+        while self.acp_cycles > 0 {
             let acp_cycles = self.acp.step(&mut self.acp_bus);
-            *acp_cycle_accumulator -= acp_cycles;
+            self.acp_cycles -= acp_cycles;
             self.acp_bus.irq_counter -= acp_cycles;
 
             // clear stuff ig
@@ -217,7 +819,7 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
             self.acp.set_nmi(false);
 
             if self.acp_bus.irq_counter <= 0 {
-                self.acp_bus.irq_counter = self.cpu_bus.system_control.sample_rate() as i32 * 4;
+                self.acp_bus.irq_counter = self.cpu_bus.system_control.sample_rate() as i32 * ratio;
                 self.acp.set_irq(true);
 
                 let sample_rate = self.cpu_frequency_hz / self.cpu_bus.system_control.sample_rate() as f64;
@@ -227,7 +829,10 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
                     self.audio_out = Some(GameTankAudio::new(sample_rate, self.target_sample_rate));
                 }
 
-                if let Some(audio) = &mut self.audio_out {
+                self.acp_scope.push(self.acp_bus.sample);
+
+                let audible = self.run_speed() == 1.0;
+                if let Some(audio) = self.audio_out.as_mut().filter(|_| audible) {
                     let next_sample_u8 = self.acp_bus.sample;
                     if let Err(e) = audio.producer.push(next_sample_u8) {
                         error!("not enough slots in audio producer: {e}");
@@ -244,11 +849,134 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
 
     fn vblank(&mut self) {
         self.clock_cycles_to_vblank += 59659;
+        self.capture_frame();
+        if let Some(trace) = &mut self.trace {
+            trace.vblank();
+        }
+        if let Some(heatmap) = &mut self.cpu_bus.heatmap {
+            heatmap.vblank();
+        }
+        self.latch_movie_input();
+        self.apply_cheats();
 
         if self.cpu_bus.vblank_nmi_enabled() {
             self.cpu.set_nmi(true);
             debug!("vblanked");
         }
+
+        // after the NMI is raised, so rewinding to here takes it again
+        if self.rewind.is_some() {
+            let state = self.save_state();
+            if let Some(rewind) = &mut self.rewind {
+                rewind.push(state);
+            }
+        }
+    }
+
+    /// Write each cheat whose condition holds, in order
+    fn apply_cheats(&mut self) {
+        for cheat in &self.cheats {
+            let current = match cheat.width {
+                Width::Byte => self.cpu_bus.peek_byte(cheat.address) as u16,
+                Width::Word => u16::from_le_bytes([self.cpu_bus.peek_byte(cheat.address), self.cpu_bus.peek_byte(cheat.address + 1)]),
+            };
+            if !cheat.condition.holds(current) {
+                continue;
+            }
+            let [lo, hi] = cheat.value.to_le_bytes();
+            self.cpu_bus.write_byte(cheat.address, lo);
+            if cheat.width == Width::Word {
+                self.cpu_bus.write_byte(cheat.address + 1, hi);
+            }
+        }
+    }
+
+    /// The whole machine, for loading later with [`load_state`](Self::load_state).
+    /// See [`savestate`] for what's included.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        w.u8(self.cpu_bus.cartridge.kind());
+        savestate::save_cpu(&mut w, &self.cpu);
+        savestate::save_cpu(&mut w, &self.acp);
+        w.i32(self.clock_cycles_to_vblank);
+        w.i32(self.acp_cycles);
+        savestate::save_acp_bus(&mut w, &self.acp_bus);
+        savestate::save_cpu_bus(&mut w, &self.cpu_bus);
+        self.blitter.save_state(&mut w);
+        w.finish()
+    }
+
+    /// Length of [`save_state`](Self::save_state)'s output with the
+    /// current cartridge
+    pub fn state_size(&self) -> usize {
+        self.save_state().len()
+    }
+
+    /// Restore a state from [`save_state`](Self::save_state). States from
+    /// another version or another kind of cartridge are refused without
+    /// changing anything.
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let mut r = StateReader::new(bytes)?;
+        if r.u8()? != self.cpu_bus.cartridge.kind() {
+            return Err("save state is for a different kind of cartridge".to_string());
+        }
+        let expected = self.state_size();
+        if bytes.len() != expected {
+            return Err(format!("save state is {} bytes, expected {}", bytes.len(), expected));
+        }
+        self.load_state_unchecked(bytes)
+    }
+
+    fn load_state_unchecked(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let mut r = StateReader::new(bytes)?;
+        r.u8()?; // cartridge kind, checked by load_state
+        savestate::load_cpu(&mut r, &mut self.cpu)?;
+        savestate::load_cpu(&mut r, &mut self.acp)?;
+        self.clock_cycles_to_vblank = r.i32()?;
+        self.acp_cycles = r.i32()?;
+        savestate::load_acp_bus(&mut r, &mut self.acp_bus)?;
+        savestate::load_cpu_bus(&mut r, &mut self.cpu_bus)?;
+        self.blitter.load_state(&mut r)?;
+        r.finish()?;
+
+        self.debugger.stop_reason = None;
+        self.wait_counter = 0;
+        Ok(())
+    }
+
+    /// Keep `frames` frames of history to [`rewind`](Self::rewind) through,
+    /// or turn rewind off with 0. Costs a save state every frame.
+    pub fn enable_rewind(&mut self, frames: usize) {
+        self.rewind = (frames > 0).then(|| RewindBuffer::new(frames));
+    }
+
+    /// Go back up to `frames` frames. Returns how many it went back, which
+    /// is fewer once the history runs out.
+    pub fn rewind(&mut self, frames: usize) -> usize {
+        let Some(rewind) = &mut self.rewind else { return 0 };
+        let rewound = rewind.rewind(frames);
+        if rewound > 0 {
+            // states in the buffer came from save_state, so they fit
+            let rewind = self.rewind.take().unwrap();
+            if let Err(e) = self.load_state_unchecked(rewind.current().unwrap_or_default()) {
+                error!("couldn't rewind: {}", e);
+            }
+            self.rewind = Some(rewind);
+        }
+        // the frames rewound over are redone, with whatever input comes next
+        match &mut self.movie {
+            Some(MovieRun::Recording(movie)) => {
+                movie.frames.truncate(movie.frames.len().saturating_sub(rewound));
+            }
+            Some(MovieRun::Playing(_, next)) => *next = next.saturating_sub(rewound),
+            None => {}
+        }
+        rewound
+    }
+
+    /// Whether the rewind input is held
+    pub fn is_rewinding(&self) -> bool {
+        self.rewind.is_some() && self.input_state.get(&Rewind).is_some_and(KeyState::is_pressed)
     }
 
     pub fn set_input_state(&mut self, input_command: InputCommand, state: KeyState) {
@@ -264,45 +992,186 @@ impl <Clock: TimeDaemon> Emulator<Clock> {
 
         for key in &keys {
             match key {
+                // a movie sets the controllers at vblank instead
+                Controller1(_) | Controller2(_) if self.movie.is_some() => {}
                 Controller1(button) => { self.set_gamepad_input(0, &key, &button); }
                 Controller2(button) => { self.set_gamepad_input(1, &key, &button); }
                 PlayPause => {
                     if self.input_state[key] == JustReleased {
                         match self.play_state {
-                            Paused => { self.play_state = Playing; }
+                            Paused => { self.resume(); }
                             Playing => { self.play_state = Paused; }
                             WasmInit => { self.play_state = Playing; }
                         }
                     }
                 }
                 SoftReset => {
+                    self.end_movie_on_reset();
                     self.cpu.reset();
                 }
+                Rewind => {
+                    if self.input_state[key].is_pressed() {
+                        self.rewind(REWIND_SPEED);
+                    }
+                }
+                FastForward => self.fast_forward = self.input_state[key].is_pressed(),
+                FrameAdvance => {
+                    if self.input_state[key] == JustReleased {
+                        self.frame_advance();
+                    }
+                }
                 HardReset => {
-                    // hard reset reinitializes memory/cpus
-                    let cart = self.cpu_bus.cartridge.clone();
-                    self.cpu_bus = CpuBus::default();
-                    self.cpu_bus.cartridge = cart;
-                    self.cpu = W65C02S::new();
-                    self.cpu.step(&mut self.cpu_bus); // take one initial step, to get through the reset vector
-                    self.acp = W65C02S::new();
-                    self.blitter = Blitter::default();
+                    self.end_movie_on_reset();
+                    self.hard_reset();
                 }
             }
             self.input_state.insert(*key, self.input_state[key].update()).expect("shit's full dog ://");
         }
     }
     fn set_gamepad_input(&mut self, gamepad: usize, key: &InputCommand, button: &ControllerButton) {
-        let gamepad = &mut self.cpu_bus.system_control.gamepads[gamepad];
-        match button {
-            Up =>     { gamepad.up    = self.input_state[&key].is_pressed(); }
-            Down =>   { gamepad.down  = self.input_state[&key].is_pressed(); }
-            Left =>   { gamepad.left  = self.input_state[&key].is_pressed(); }
-            Right =>  { gamepad.right = self.input_state[&key].is_pressed(); }
-            B =>      { gamepad.b     = self.input_state[&key].is_pressed(); }
-            A =>      { gamepad.a     = self.input_state[&key].is_pressed(); }
-            Start =>  { gamepad.start = self.input_state[&key].is_pressed(); }
-            C =>      { gamepad.c     = self.input_state[&key].is_pressed(); }
+        let pressed = self.input_state[&key].is_pressed();
+        set_button(&mut self.cpu_bus.system_control.gamepads[gamepad], button, pressed);
+    }
+
+    /// Reinitialize memory and both CPUs, keeping the cartridge
+    fn hard_reset(&mut self) {
+        let mut cart = self.cpu_bus.cartridge.clone();
+        cart.reset();
+        self.cpu_bus = CpuBus::default();
+        self.cpu_bus.cartridge = cart;
+        self.cpu = W65C02S::new();
+        self.cpu.step(&mut self.cpu_bus); // take one initial step, to get through the reset vector
+        self.acp = W65C02S::new();
+        self.acp_bus = AcpBus::default();
+        unsafe { ARAM.fill(0) };
+        self.acp_cycles = 0;
+        self.clock_cycles_to_vblank = 59659;
+        self.blitter = Blitter::default();
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+    }
+
+    /// Start recording a movie, from a hard reset if `from_power_on`, or
+    /// else from the current state. Replaces any movie already running.
+    pub fn record_movie(&mut self, from_power_on: bool) {
+        if from_power_on {
+            self.hard_reset();
         }
+        self.clear_gamepads();
+        let start_state = self.save_state();
+        let start_hash = crc32(&start_state);
+        self.movie = Some(MovieRun::Recording(Movie {
+            rom_crc: self.rom_crc,
+            start_hash,
+            start_state: (!from_power_on).then_some(start_state),
+            frames: Vec::new(),
+        }));
+    }
+
+    /// Play `movie` back from its start. Fails without changing anything if
+    /// it was recorded on another ROM or its save state doesn't load.
+    /// Starting somewhere other than where it was recorded only warns, as
+    /// the run will usually still be close.
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), String> {
+        if movie.rom_crc != self.rom_crc {
+            return Err(format!("movie is for ROM {:08X}, but {:08X} is loaded", movie.rom_crc, self.rom_crc));
+        }
+        match &movie.start_state {
+            Some(state) => self.load_state(state)?,
+            None => self.hard_reset(),
+        }
+        self.clear_gamepads();
+        if crc32(&self.save_state()) != movie.start_hash {
+            warn!("movie starts from a different state than it was recorded from; it may not replay the same");
+        }
+        self.movie = Some(MovieRun::Playing(movie, 0));
+        Ok(())
+    }
+
+    /// Stop recording or playing, returning the movie
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        match self.movie.take()? {
+            MovieRun::Recording(movie) | MovieRun::Playing(movie, _) => Some(movie),
+        }
+    }
+
+    pub fn is_recording_movie(&self) -> bool {
+        matches!(self.movie, Some(MovieRun::Recording(_)))
+    }
+
+    /// Whether a movie is playing. Playback stops by itself after the last
+    /// frame, handing the controllers back.
+    pub fn is_playing_movie(&self) -> bool {
+        matches!(self.movie, Some(MovieRun::Playing(..)))
+    }
+
+    /// Frames recorded, or played so far, of the running movie
+    pub fn movie_frame(&self) -> Option<usize> {
+        match &self.movie {
+            Some(MovieRun::Recording(movie)) => Some(movie.len()),
+            Some(MovieRun::Playing(_, next)) => Some(*next),
+            None => None,
+        }
+    }
+
+    fn clear_gamepads(&mut self) {
+        for pad in &mut self.cpu_bus.system_control.gamepads {
+            pad.set_buttons(0);
+        }
+    }
+
+    /// A reset isn't something a movie can replay, so it ends one
+    fn end_movie_on_reset(&mut self) {
+        if self.movie.take().is_some() {
+            warn!("reset while a movie was running; the movie stopped");
+        }
+    }
+
+    /// At vblank, set the controllers from the movie, or record what's held
+    fn latch_movie_input(&mut self) {
+        let pads = match &mut self.movie {
+            None => return,
+            Some(MovieRun::Recording(movie)) => {
+                let mut pads = [GamePad::default(), GamePad::default()];
+                for (command, state) in self.input_state.iter() {
+                    match command {
+                        Controller1(button) => set_button(&mut pads[0], button, state.is_pressed()),
+                        Controller2(button) => set_button(&mut pads[1], button, state.is_pressed()),
+                        _ => {}
+                    }
+                }
+                let frame = [pads[0].buttons(), pads[1].buttons()];
+                movie.frames.push(frame);
+                frame
+            }
+            Some(MovieRun::Playing(movie, next)) => match movie.frames.get(*next) {
+                Some(&frame) => {
+                    *next += 1;
+                    frame
+                }
+                None => {
+                    warn!("movie finished after {} frames", movie.len());
+                    self.movie = None;
+                    [0, 0]
+                }
+            },
+        };
+        for (pad, buttons) in self.cpu_bus.system_control.gamepads.iter_mut().zip(pads) {
+            pad.set_buttons(buttons);
+        }
+    }
+}
+
+fn set_button(gamepad: &mut GamePad, button: &ControllerButton, pressed: bool) {
+    match button {
+        Up =>     { gamepad.up    = pressed; }
+        Down =>   { gamepad.down  = pressed; }
+        Left =>   { gamepad.left  = pressed; }
+        Right =>  { gamepad.right = pressed; }
+        B =>      { gamepad.b     = pressed; }
+        A =>      { gamepad.a     = pressed; }
+        Start =>  { gamepad.start = pressed; }
+        C =>      { gamepad.c     = pressed; }
     }
 }
\ No newline at end of file
@@ -11,6 +11,9 @@ use crate::gametank_bus::reg_blitter::{BlitStart, BlitterRegisters};
 use crate::gametank_bus::reg_etc::{new_framebuffer, BankingRegister, BlitterFlags, FrameBuffer, GraphicsMemoryMap, SharedFrameBuffer};
 use crate::gametank_bus::reg_system_control::*;
 use crate::inputs::GamePad;
+use crate::heatmap::{Access, Heatmap};
+use crate::gametank_bus::link_port::LinkPort;
+use crate::gametank_bus::debug_port::DebugPort;
 
 const CURRENT_GAME: &[u8] = &[0; 0x2000];
 
@@ -43,6 +46,9 @@ pub struct CpuBus {
 
     // pub aram: Option<ARAM>,
     pub cartridge: CartridgeType,
+
+    /// Access counts, while recording a heatmap
+    pub(crate) heatmap: Option<Box<Heatmap>>,
 }
 
 impl Default for CpuBus {
@@ -53,9 +59,11 @@ impl Default for CpuBus {
                 nmi_acp: 0,
                 banking_register: BankingRegister(0),
                 via_regs: [0; 16],
+                link: LinkPort::default(),
                 audio_enable_sample_rate: 0,
                 dma_flags: BlitterFlags(0b0111_1111),
-                gamepads: [GamePad::default(), GamePad::default()]
+                gamepads: [GamePad::default(), GamePad::default()],
+                debug_port: DebugPort::default(),
             },
             blitter: BlitterRegisters {
                 vx: 0,
@@ -76,6 +84,7 @@ impl Default for CpuBus {
             cartridge: CartridgeType::from_slice(CURRENT_GAME),
             // aram: Some(Box::new([0; 0x1000])),
             vram_quad_written: [false; 32],
+            heatmap: None,
         };
 
         bus
@@ -141,7 +150,7 @@ impl CpuBus {
                 let before_reg = self.system_control.via_regs.clone();
 
                 let register = (address & 0xF) as usize;
-                self.system_control.via_regs[register] = data;
+                self.system_control.write_via(register, data);
 
                 self.cartridge.update_via(&mut [before_reg, self.system_control.via_regs]);
             }
@@ -195,7 +204,7 @@ impl CpuBus {
             // versatile interface adapter (GPIO, timers)
             0x2800..=0x280F => {
                 let register = (address & 0xF) as usize;
-                return self.system_control.via_regs[register]
+                return self.system_control.read_via(register)
             }
 
             // audio RAM
@@ -260,17 +269,88 @@ impl CpuBus {
         }
     }
 
+    /// Read a byte the way the CPU would see it, without side effects
+    pub fn peek_byte(&self, address: u16) -> u8 {
+        match address {
+            0x2800..=0x280F => self.system_control.via_regs[(address & 0xF) as usize],
+            0x8000..=0xFFFF => self.cartridge.read_byte(address - 0x8000),
+            _ => match self.peek_byte_decorated(address) {
+                ZeroPage(b) | CpuStack(b) | SystemRam(b) | AudioRam(b) | Vram(b)
+                | ByteDecorator::Framebuffer(b) | ByteDecorator::Aram(b) | Unreadable(b) => b,
+            },
+        }
+    }
+
     pub fn vblank_nmi_enabled(&self) -> bool {
         self.system_control.dma_flags.dma_nmi()
     }
 }
 
+impl CpuBus {
+    /// Sprite RAM byte `address` reaches, as `page * 0x10000 + offset`, if
+    /// sprite RAM is mapped in
+    fn sprite_ram_index(&self, address: u16) -> Option<usize> {
+        if !(0x4000..=0x7FFF).contains(&address)
+            || !matches!(self.system_control.get_graphics_memory_map(), GraphicsMemoryMap::VRAM) {
+            return None;
+        }
+        let page = self.system_control.banking_register.vram_page() as usize;
+        Some(page * 0x10000 + self.blitter.vram_quadrant() * 128 * 128 + (address as usize - 0x4000))
+    }
+
+    /// A read or opcode fetch by the CPU, counted in the heatmap
+    pub(crate) fn cpu_read(&mut self, address: u16, access: Access) -> u8 {
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.cpu_access(access, address);
+        }
+        self.read_byte(address)
+    }
+
+    /// A write by the CPU, counted in the heatmap
+    pub(crate) fn cpu_write(&mut self, address: u16, data: u8) {
+        if self.heatmap.is_some() {
+            let sprite = self.sprite_ram_index(address);
+            if let Some(heatmap) = &mut self.heatmap {
+                heatmap.cpu_access(Access::Write, address);
+                if let Some(index) = sprite {
+                    heatmap.sprite_write(index);
+                }
+            }
+        }
+        self.write_byte(address, data);
+    }
+}
+
 impl System for CpuBus {
-    fn read(&mut self, _: &mut W65C02S, addr: u16) -> u8 {
+    fn read_opcode(&mut self, _: &mut W65C02S, addr: u16) -> u8 {
+        self.cpu_read(addr, Access::Execute)
+    }
+
+    fn read_opcode_spurious(&mut self, _: &mut W65C02S, addr: u16) {
+        self.read_byte(addr);
+    }
+
+    fn read_operand(&mut self, _: &mut W65C02S, addr: u16) -> u8 {
         self.read_byte(addr)
     }
 
+    fn read_operand_spurious(&mut self, _: &mut W65C02S, addr: u16) {
+        self.read_byte(addr);
+    }
+
+    fn read_spurious(&mut self, _: &mut W65C02S, addr: u16) {
+        self.read_byte(addr);
+    }
+
+    fn read_vector(&mut self, _: &mut W65C02S, addr: u16) -> u8 {
+        self.read_byte(addr)
+    }
+
+    fn read(&mut self, _: &mut W65C02S, addr: u16) -> u8 {
+        self.cpu_read(addr, Access::Read)
+    }
+
     fn write(&mut self, _: &mut W65C02S, addr: u16, data: u8) {
-        self.write_byte(addr, data);
+        self.cpu_write(addr, data);
     }
 }
@@ -0,0 +1,55 @@
+//! A debug port for printing from the ROM
+//!
+//! Bytes written to [`DEBUG_PORT`] are gathered into lines of text for the
+//! frontend to show: a newline ends a message, as does a line reaching
+//! [`MAX_LINE`] bytes. The console leaves the address unused, so on hardware
+//! the writes go nowhere.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// An unused system control register
+pub const DEBUG_PORT: u16 = 0x2002;
+
+/// Longest message before it's cut into another
+pub const MAX_LINE: usize = 256;
+
+/// Messages held for the frontend before dropping the oldest, so a frontend
+/// that never takes them doesn't grow them forever
+const MAX_MESSAGES: usize = 64;
+
+#[derive(Debug, Default)]
+pub struct DebugPort {
+    /// The message being written
+    line: Vec<u8>,
+    messages: VecDeque<String>,
+}
+
+impl DebugPort {
+    pub fn write(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.end_line(),
+            b'\r' => {}
+            _ => {
+                self.line.push(byte);
+                if self.line.len() >= MAX_LINE {
+                    self.end_line();
+                }
+            }
+        }
+    }
+
+    fn end_line(&mut self) {
+        if self.messages.len() >= MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(String::from_utf8_lossy(&self.line).into_owned());
+        self.line.clear();
+    }
+
+    /// Messages written since the last call, oldest first
+    pub fn take(&mut self) -> impl Iterator<Item = String> + '_ {
+        self.messages.drain(..)
+    }
+}
@@ -0,0 +1,101 @@
+//! The expansion port, as a link cable to another console
+//!
+//! The cable joins port B to port B, with each side's CB2 going to the
+//! other's CB1. A port B write that pulses CB2 sends the byte to the other
+//! side, where it's latched on CB1 and flagged in IFR. The frontend moves
+//! [`LinkPort::outgoing`] to the other console however it likes (a socket,
+//! usually) and hands what comes back to [`LinkPort::receive`].
+
+use alloc::collections::VecDeque;
+use super::reg_system_control::{VIA_ACR, VIA_DDRB, VIA_IFR, VIA_IORB, VIA_PCR};
+
+/// CB1's bit in IFR and IER
+pub const VIA_IRQ_CB1: u8 = 0b0001_0000;
+/// ACR bit that latches port B's inputs on CB1 edges
+pub const VIA_ACR_PB_LATCH: u8 = 0b0000_0010;
+/// PCR bits for CB2's mode
+pub const VIA_PCR_CB2_MASK: u8 = 0b1110_0000;
+/// PCR: CB2 handshakes on port B writes
+pub const VIA_PCR_CB2_HANDSHAKE: u8 = 0b1000_0000;
+/// PCR: CB2 pulses on port B writes
+pub const VIA_PCR_CB2_PULSE: u8 = 0b1010_0000;
+
+/// Bytes either side holds before dropping the oldest, so a console that
+/// stops reading doesn't grow them forever
+const QUEUE_LIMIT: usize = 256;
+
+#[derive(Debug)]
+pub struct LinkPort {
+    /// Whether there's a console on the other end. Without one, port B
+    /// behaves as if nothing's plugged in.
+    pub connected: bool,
+    /// Bytes this console sent, waiting to go to the other one
+    pub outgoing: VecDeque<u8>,
+    incoming: VecDeque<u8>,
+    /// What the other side is driving on port B
+    input: u8,
+    /// Port B's inputs as of the last CB1 edge
+    latched: u8,
+}
+
+impl Default for LinkPort {
+    fn default() -> Self {
+        Self {
+            connected: false,
+            outgoing: VecDeque::new(),
+            incoming: VecDeque::new(),
+            // pulled up
+            input: 0xFF,
+            latched: 0xFF,
+        }
+    }
+}
+
+impl LinkPort {
+    /// A byte from the other console, delivered when the last one's been read
+    pub fn receive(&mut self, byte: u8) {
+        if self.incoming.len() >= QUEUE_LIMIT {
+            self.incoming.pop_front();
+        }
+        self.incoming.push_back(byte);
+    }
+
+    /// Forget anything in flight, e.g. when the other side goes away
+    pub fn disconnect(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Port B was written: send it if CB2 pulses on writes
+    pub(crate) fn write_port(&mut self, via_regs: &mut [u8; 16], data: u8) {
+        let cb2 = via_regs[VIA_PCR] & VIA_PCR_CB2_MASK;
+        if self.connected && (cb2 == VIA_PCR_CB2_PULSE || cb2 == VIA_PCR_CB2_HANDSHAKE) {
+            let ddrb = via_regs[VIA_DDRB];
+            if self.outgoing.len() >= QUEUE_LIMIT {
+                self.outgoing.pop_front();
+            }
+            // undriven pins float high
+            self.outgoing.push_back((data & ddrb) | !ddrb);
+        }
+        via_regs[VIA_IFR] &= !VIA_IRQ_CB1;
+    }
+
+    /// Port B was read: the outputs, and the inputs from the other side
+    pub(crate) fn read_port(&mut self, via_regs: &mut [u8; 16]) -> u8 {
+        let ddrb = via_regs[VIA_DDRB];
+        let input = if via_regs[VIA_ACR] & VIA_ACR_PB_LATCH != 0 { self.latched } else { self.input };
+        via_regs[VIA_IFR] &= !VIA_IRQ_CB1;
+        (via_regs[VIA_IORB] & ddrb) | (input & !ddrb)
+    }
+
+    /// Strobe CB1 with the next byte, once the game's taken the last one
+    pub(crate) fn deliver(&mut self, via_regs: &mut [u8; 16]) {
+        if via_regs[VIA_IFR] & VIA_IRQ_CB1 != 0 {
+            return;
+        }
+        if let Some(byte) = self.incoming.pop_front() {
+            self.input = byte;
+            self.latched = byte;
+            via_regs[VIA_IFR] |= VIA_IRQ_CB1;
+        }
+    }
+}
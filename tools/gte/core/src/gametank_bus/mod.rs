@@ -3,6 +3,12 @@ mod cpu_bus;
 mod reg_system_control;
 mod reg_blitter;
 mod via_bus;
+mod link_port;
+mod debug_port;
 
 pub use cpu_bus::*;
+pub use reg_blitter::{BlitStart, BlitterRegisters};
+pub use reg_etc::FrameBuffer;
 pub use via_bus::*;
+pub use link_port::LinkPort;
+pub use debug_port::{DebugPort, DEBUG_PORT};
@@ -1,6 +1,8 @@
 use log::{debug, warn};
 use crate::inputs::GamePad;
 use crate::gametank_bus::reg_etc::{BankingRegister, BlitterFlags, GraphicsMemoryMap};
+use crate::gametank_bus::link_port::LinkPort;
+use crate::gametank_bus::debug_port::{DebugPort, DEBUG_PORT};
 
 pub const VIA_IORB: usize    = 0x0;
 pub const VIA_IORA: usize    = 0x1;
@@ -19,6 +21,11 @@ pub const VIA_IFR: usize    = 0xD;
 pub const VIA_IER: usize    = 0xE;
 pub const VIA_ORA_NH: usize = 0xF;
 
+/// Timer 1's bit in IFR and IER
+pub const VIA_IRQ_T1: u8 = 0b0100_0000;
+/// ACR bit that makes timer 1 reload from its latch when it runs out
+pub const VIA_ACR_T1_FREE_RUN: u8 = 0b0100_0000;
+
 pub const VIA_SPI_BIT_CLK : u8 = 0b00000001;
 pub const VIA_SPI_BIT_MOSI: u8 = 0b00000010;
 pub const VIA_SPI_BIT_CS  : u8 = 0b00000100;
@@ -33,11 +40,16 @@ pub struct SystemControl {
     pub banking_register: BankingRegister,
 
     pub via_regs: [u8; 16],
+    /// What's on the expansion port, via port B
+    pub link: LinkPort,
 
     pub audio_enable_sample_rate: u8,
     pub dma_flags: BlitterFlags,
 
-    pub gamepads: [GamePad; 2]
+    pub gamepads: [GamePad; 2],
+
+    /// What the ROM prints, see [`DebugPort`]
+    pub debug_port: DebugPort,
 }
 
 impl SystemControl {
@@ -87,6 +99,70 @@ impl SystemControl {
         self.dma_flags.dma_page_out() as usize
     }
 
+    /// Write a VIA register. Timer 1 is kept in `via_regs` itself: the
+    /// counter in T1CL/T1CH and the latch in T1LL/T1LH, as the 6522 reads
+    /// them back.
+    pub fn write_via(&mut self, register: usize, data: u8) {
+        match register {
+            // writing the counter's low byte only sets the latch
+            VIA_T1CL | VIA_T1LL => self.via_regs[VIA_T1LL] = data,
+            // writing the high byte loads the counter and starts it
+            VIA_T1CH => {
+                self.via_regs[VIA_T1LH] = data;
+                self.via_regs[VIA_T1CL] = self.via_regs[VIA_T1LL];
+                self.via_regs[VIA_T1CH] = data;
+                self.via_regs[VIA_IFR] &= !VIA_IRQ_T1;
+            }
+            VIA_T1LH => {
+                self.via_regs[VIA_T1LH] = data;
+                self.via_regs[VIA_IFR] &= !VIA_IRQ_T1;
+            }
+            VIA_IORB => {
+                self.via_regs[VIA_IORB] = data;
+                self.link.write_port(&mut self.via_regs, data);
+            }
+            _ => self.via_regs[register] = data,
+        }
+    }
+
+    /// Read a VIA register. Reading T1CL acknowledges timer 1's interrupt,
+    /// and port B reads the link when one's connected.
+    pub fn read_via(&mut self, register: usize) -> u8 {
+        if register == VIA_T1CL {
+            self.via_regs[VIA_IFR] &= !VIA_IRQ_T1;
+        }
+        if self.link.connected {
+            self.link.deliver(&mut self.via_regs);
+            if register == VIA_IORB {
+                return self.link.read_port(&mut self.via_regs);
+            }
+        }
+        self.via_regs[register]
+    }
+
+    /// Count VIA timer 1 down by `cycles` CPU cycles. Running out sets its
+    /// interrupt flag, and reloads the latch in free-run mode; in one-shot
+    /// mode it keeps counting down from $FFFF. The interrupt itself isn't
+    /// raised.
+    pub fn tick_via(&mut self, cycles: i32) {
+        let counter = u16::from_le_bytes([self.via_regs[VIA_T1CL], self.via_regs[VIA_T1CH]]) as i32;
+        let mut next = counter - cycles;
+
+        if next < 0 {
+            self.via_regs[VIA_IFR] |= VIA_IRQ_T1;
+            if self.via_regs[VIA_ACR] & VIA_ACR_T1_FREE_RUN != 0 {
+                let latch = u16::from_le_bytes([self.via_regs[VIA_T1LL], self.via_regs[VIA_T1LH]]) as i32;
+                next = latch + 1 + next % (latch + 2);
+            } else {
+                next &= 0xFFFF;
+            }
+        }
+
+        let [low, high] = (next as u16).to_le_bytes();
+        self.via_regs[VIA_T1CL] = low;
+        self.via_regs[VIA_T1CH] = high;
+    }
+
     #[inline(always)]
     pub fn write_byte(&mut self, address: u16, data: u8) {
         match address {
@@ -98,6 +174,7 @@ impl SystemControl {
             }
             0x2006 => { self.audio_enable_sample_rate = data }
             0x2007 => { self.dma_flags.0 = data }
+            DEBUG_PORT => { self.debug_port.write(data) }
             _ => {
                 warn!("Attempted to write read-only memory at: ${:02X}", address);
             }
@@ -0,0 +1,462 @@
+//! GDB remote serial protocol
+//!
+//! [`GdbStub`] lets gdb or lldb debug a running ROM with the symbols from
+//! its ELF (`target remote :1234` / `gdb-remote 1234`). It only speaks the
+//! protocol; the frontend moves bytes between it and a socket.
+//!
+//! Addresses are linked addresses, as in the ELF and the `.sym` file: banked
+//! code is at `$8000 + bank * $10000`, so the PC reads as a 32-bit value and
+//! breakpoints in a bank only stop there while that bank is mapped in.
+//!
+//! Registers follow llvm-mos: A, X and Y, then the imaginary registers
+//! `rc0`-`rc63` in zero page, which hold arguments, locals and the soft stack
+//! pointer (`rc0`/`rc1`).
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::debugger::{Registers, StopReason, WatchKind};
+use crate::emulator::{Emulator, PlayState, TimeDaemon};
+
+/// Imaginary registers the SDK's linker script puts in zero page
+const RC_COUNT: usize = 64;
+
+/// PC, A, X, Y, S and P, then the imaginary registers
+const REGISTER_COUNT: usize = 6 + RC_COUNT;
+
+/// llvm-mos numbers `rcN` as DWARF register `RC_DWARF_BASE + N`
+const RC_DWARF_BASE: usize = 0x10;
+
+/// Sent by the debugger to interrupt a running target
+const INTERRUPT: u8 = 0x03;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Parse {
+    /// Between packets
+    Idle,
+    Body,
+    Checksum,
+}
+
+/// A debugger's connection to the emulator. Feed it bytes with
+/// [`receive`](Self::receive), call [`poll`](Self::poll) after running the
+/// emulator, and send what [`take_output`](Self::take_output) returns.
+#[derive(Debug)]
+pub struct GdbStub {
+    parse: Parse,
+    packet: Vec<u8>,
+    checksum: Vec<u8>,
+    output: Vec<u8>,
+    /// The debugger asked to stop acknowledging packets
+    no_ack: bool,
+    /// The debugger is waiting for the emulator to stop
+    running: bool,
+    /// Breakpoints as the debugger set them, with banks
+    breakpoints: Vec<u32>,
+}
+
+impl Default for GdbStub {
+    fn default() -> Self {
+        Self {
+            parse: Parse::Idle,
+            packet: Vec::new(),
+            checksum: Vec::new(),
+            output: Vec::new(),
+            no_ack: false,
+            running: false,
+            breakpoints: Vec::new(),
+        }
+    }
+}
+
+fn hex_u32(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+fn hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn push_hex(out: &mut String, bytes: &[u8]) {
+    for b in bytes {
+        let _ = write!(out, "{:02x}", b);
+    }
+}
+
+/// `addr,length`
+fn address_range(text: &str) -> Option<(u32, u32)> {
+    let (address, length) = text.split_once(',')?;
+    Some((hex_u32(address)?, hex_u32(length)?))
+}
+
+/// Register description for `qXfer:features:read:target.xml`
+fn target_xml() -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\"?>",
+        "<!DOCTYPE target SYSTEM \"gdb-target.dtd\">",
+        "<target version=\"1.0\"><architecture>mos</architecture>",
+        "<feature name=\"org.gametank.mos\">",
+        "<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\" regnum=\"0\" generic=\"pc\"/>",
+        "<reg name=\"a\" bitsize=\"8\" type=\"uint8\" dwarf_regnum=\"0\"/>",
+        "<reg name=\"x\" bitsize=\"8\" type=\"uint8\" dwarf_regnum=\"2\"/>",
+        "<reg name=\"y\" bitsize=\"8\" type=\"uint8\" dwarf_regnum=\"4\"/>",
+        "<reg name=\"s\" bitsize=\"8\" type=\"uint8\"/>",
+        "<reg name=\"p\" bitsize=\"8\" type=\"uint8\" generic=\"flags\"/>",
+    ));
+    for rc in 0..RC_COUNT {
+        let _ = write!(xml, "<reg name=\"rc{}\" bitsize=\"8\" type=\"uint8\" dwarf_regnum=\"{}\"/>", rc, RC_DWARF_BASE + rc);
+    }
+    xml.push_str("</feature></target>");
+    xml
+}
+
+impl GdbStub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the emulator is running on the debugger's behalf
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Bytes to send to the debugger
+    pub fn take_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.output)
+    }
+
+    /// Handle bytes from the debugger
+    pub fn receive<C: TimeDaemon>(&mut self, bytes: &[u8], emulator: &mut Emulator<C>) {
+        for &byte in bytes {
+            match self.parse {
+                Parse::Idle => match byte {
+                    b'$' => {
+                        self.packet.clear();
+                        self.parse = Parse::Body;
+                    }
+                    INTERRUPT if self.running => emulator.pause(),
+                    // acks, and anything else between packets
+                    _ => {}
+                },
+                Parse::Body => match byte {
+                    b'#' => {
+                        self.checksum.clear();
+                        self.parse = Parse::Checksum;
+                    }
+                    _ => self.packet.push(byte),
+                },
+                Parse::Checksum => {
+                    self.checksum.push(byte);
+                    if self.checksum.len() == 2 {
+                        self.parse = Parse::Idle;
+                        self.finish_packet(emulator);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send a stop reply once a `continue` ends, on a breakpoint, watchpoint
+    /// or interrupt. Breakpoints that only matched in another bank are
+    /// stepped over.
+    pub fn poll<C: TimeDaemon>(&mut self, emulator: &mut Emulator<C>) {
+        if !self.running || emulator.play_state == PlayState::Playing {
+            return;
+        }
+
+        if let Some(StopReason::Breakpoint(pc)) = emulator.debugger.stop_reason {
+            let linked = emulator.linked_pc();
+            let ours = self.breakpoints.iter().any(|&b| b as u16 == pc);
+            if ours && !self.breakpoints.contains(&linked) {
+                emulator.resume();
+                return;
+            }
+        }
+
+        self.running = false;
+        self.stop_reply(emulator);
+    }
+
+    /// The debugger went away: let the game run on without its breakpoints
+    pub fn detach<C: TimeDaemon>(&mut self, emulator: &mut Emulator<C>) {
+        for address in core::mem::take(&mut self.breakpoints) {
+            emulator.debugger.remove_breakpoint(address as u16);
+        }
+        self.running = false;
+        self.no_ack = false;
+        self.parse = Parse::Idle;
+        emulator.resume();
+    }
+
+    fn finish_packet<C: TimeDaemon>(&mut self, emulator: &mut Emulator<C>) {
+        let sum = self.packet.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        let expected = core::str::from_utf8(&self.checksum).ok()
+            .and_then(|c| u8::from_str_radix(c, 16).ok());
+        if !self.no_ack {
+            if expected != Some(sum) {
+                self.output.push(b'-');
+                return;
+            }
+            self.output.push(b'+');
+        }
+
+        let packet = core::mem::take(&mut self.packet);
+        if let Some(reply) = self.handle(&packet, emulator) {
+            self.send(&reply);
+        }
+        if packet == b"QStartNoAckMode" {
+            self.no_ack = true;
+        }
+    }
+
+    fn send(&mut self, data: &str) {
+        let sum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        self.output.push(b'$');
+        self.output.extend_from_slice(data.as_bytes());
+        self.output.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+    }
+
+    /// The reply to `packet`, or `None` when it comes later
+    fn handle<C: TimeDaemon>(&mut self, packet: &[u8], emulator: &mut Emulator<C>) -> Option<String> {
+        // dispatch on the raw byte: decoding first could turn a stray one
+        // into a multi-byte replacement character
+        let Some((&command, args)) = packet.split_first() else { return Some(String::new()) };
+        let args = &*String::from_utf8_lossy(args);
+        let reply = match command {
+            b'?' => {
+                // the debugger expects to attach to a stopped target
+                emulator.pause();
+                self.running = false;
+                self.stop_reply(emulator);
+                return None;
+            }
+            b'g' => self.read_registers(emulator),
+            b'G' => self.write_registers(args, emulator).unwrap_or_else(|| "E01".into()),
+            b'p' => hex_u32(args).and_then(|n| self.read_register(n as usize, emulator)).unwrap_or_else(|| "E01".into()),
+            b'P' => self.write_register(args, emulator).unwrap_or_else(|| "E01".into()),
+            b'm' => self.read_memory(args, emulator).unwrap_or_else(|| "E01".into()),
+            b'M' => self.write_memory(args, emulator).unwrap_or_else(|| "E01".into()),
+            b'c' => {
+                if let Some(address) = hex_u32(args) {
+                    emulator.cpu.set_pc(address as u16);
+                }
+                self.running = true;
+                emulator.resume();
+                return None;
+            }
+            b's' => {
+                if let Some(address) = hex_u32(args) {
+                    emulator.cpu.set_pc(address as u16);
+                }
+                emulator.step_instruction();
+                self.stop_reply(emulator);
+                return None;
+            }
+            b'Z' | b'z' => self.set_point(command == b'Z', args, emulator).unwrap_or_else(|| "E01".into()),
+            b'D' => {
+                self.send("OK");
+                self.detach(emulator);
+                return None;
+            }
+            b'k' => {
+                self.detach(emulator);
+                return None;
+            }
+            b'H' => "OK".into(),
+            b'T' => "OK".into(),
+            _ => self.query(&String::from_utf8_lossy(packet)),
+        };
+        Some(reply)
+    }
+
+    /// `q` and `Q` packets. Unknown ones get an empty reply, which means
+    /// unsupported.
+    fn query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+;swbreak+;hwbreak+".into();
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = address_range(range) else { return "E01".into() };
+            let xml = target_xml();
+            let start = (offset as usize).min(xml.len());
+            let end = (start + length as usize).min(xml.len());
+            let more = if end < xml.len() { 'm' } else { 'l' };
+            return format!("{}{}", more, &xml[start..end]);
+        }
+        match packet {
+            "QStartNoAckMode" => "OK".into(),
+            "qAttached" => "1".into(),
+            "qC" => "QC1".into(),
+            "qfThreadInfo" => "m1".into(),
+            "qsThreadInfo" => "l".into(),
+            _ => String::new(),
+        }
+    }
+
+    fn stop_reply<C: TimeDaemon>(&mut self, emulator: &Emulator<C>) {
+        let reply = match emulator.debugger.stop_reason {
+            Some(StopReason::Breakpoint(_)) => format!("T{:02x}thread:1;swbreak:;", SIGTRAP),
+            Some(StopReason::Watchpoint { address, write, .. }) => {
+                let kind = emulator.debugger.watchpoints().iter()
+                    .find(|w| w.address == address)
+                    .map_or(WatchKind::Write, |w| w.kind);
+                let name = match (kind, write) {
+                    (WatchKind::ReadWrite, _) => "awatch",
+                    (_, true) => "watch",
+                    (_, false) => "rwatch",
+                };
+                format!("T{:02x}thread:1;{}:{:x};", SIGTRAP, name, address)
+            }
+            Some(StopReason::Step) => format!("T{:02x}thread:1;", SIGTRAP),
+            None => format!("T{:02x}thread:1;", SIGINT),
+        };
+        self.send(&reply);
+    }
+
+    fn register_bytes<C: TimeDaemon>(&self, n: usize, emulator: &Emulator<C>) -> Option<Vec<u8>> {
+        let r = emulator.registers();
+        Some(match n {
+            0 => emulator.linked_pc().to_le_bytes().to_vec(),
+            1 => vec![r.a],
+            2 => vec![r.x],
+            3 => vec![r.y],
+            4 => vec![r.s],
+            5 => vec![r.p],
+            n if n < REGISTER_COUNT => vec![emulator.peek((n - 6) as u16)],
+            _ => return None,
+        })
+    }
+
+    fn read_registers<C: TimeDaemon>(&self, emulator: &Emulator<C>) -> String {
+        let mut out = String::new();
+        for n in 0..REGISTER_COUNT {
+            if let Some(bytes) = self.register_bytes(n, emulator) {
+                push_hex(&mut out, &bytes);
+            }
+        }
+        out
+    }
+
+    fn read_register<C: TimeDaemon>(&self, n: usize, emulator: &Emulator<C>) -> Option<String> {
+        let mut out = String::new();
+        push_hex(&mut out, &self.register_bytes(n, emulator)?);
+        Some(out)
+    }
+
+    /// Set register `n` from little-endian bytes. A banked PC can only be
+    /// set within the bank that's mapped in.
+    fn set_register<C: TimeDaemon>(&self, n: usize, bytes: &[u8], emulator: &mut Emulator<C>) -> Option<()> {
+        let mut r: Registers = emulator.registers();
+        let byte = *bytes.first()?;
+        match n {
+            0 => r.pc = u16::from_le_bytes([byte, *bytes.get(1)?]),
+            1 => r.a = byte,
+            2 => r.x = byte,
+            3 => r.y = byte,
+            4 => r.s = byte,
+            5 => r.p = byte,
+            n if n < REGISTER_COUNT => {
+                emulator.poke((n - 6) as u16, byte);
+                return Some(());
+            }
+            _ => return None,
+        }
+        emulator.set_registers(r);
+        Some(())
+    }
+
+    fn write_registers<C: TimeDaemon>(&self, args: &str, emulator: &mut Emulator<C>) -> Option<String> {
+        let bytes = hex_bytes(args)?;
+        let mut offset = 0;
+        for n in 0..REGISTER_COUNT {
+            let size = if n == 0 { 4 } else { 1 };
+            let Some(value) = bytes.get(offset..offset + size) else { break };
+            self.set_register(n, value, emulator)?;
+            offset += size;
+        }
+        Some("OK".into())
+    }
+
+    fn write_register<C: TimeDaemon>(&self, args: &str, emulator: &mut Emulator<C>) -> Option<String> {
+        let (n, value) = args.split_once('=')?;
+        self.set_register(hex_u32(n)? as usize, &hex_bytes(value)?, emulator)?;
+        Some("OK".into())
+    }
+
+    fn read_memory<C: TimeDaemon>(&self, args: &str, emulator: &Emulator<C>) -> Option<String> {
+        let (address, length) = address_range(args)?;
+        let bytes: Vec<u8> = (0..length.min(0x800))
+            .map(|i| emulator.peek_linked(address.wrapping_add(i)))
+            .collect();
+        let mut out = String::new();
+        push_hex(&mut out, &bytes);
+        Some(out)
+    }
+
+    /// RAM and registers only: writes to ROM would go to the flash chip as
+    /// commands
+    fn write_memory<C: TimeDaemon>(&self, args: &str, emulator: &mut Emulator<C>) -> Option<String> {
+        let (range, data) = args.split_once(':')?;
+        let (address, _) = address_range(range)?;
+        let bytes = hex_bytes(data)?;
+        if address as usize + bytes.len() > 0x8000 {
+            return None;
+        }
+        for (i, byte) in bytes.into_iter().enumerate() {
+            emulator.poke(address as u16 + i as u16, byte);
+        }
+        Some("OK".into())
+    }
+
+    /// `Z`/`z` packets: `type,addr,kind`. Types 0 and 1 are breakpoints,
+    /// 2-4 write, read and access watchpoints over `kind` bytes.
+    fn set_point<C: TimeDaemon>(&mut self, insert: bool, args: &str, emulator: &mut Emulator<C>) -> Option<String> {
+        let mut fields = args.splitn(3, ',');
+        let kind = fields.next()?;
+        let address = hex_u32(fields.next()?)?;
+        let length = fields.next().and_then(|l| hex_u32(l.split(';').next()?)).unwrap_or(1);
+
+        let watch = match kind {
+            "0" | "1" => {
+                if insert {
+                    if !self.breakpoints.contains(&address) {
+                        self.breakpoints.push(address);
+                    }
+                    emulator.debugger.add_breakpoint(address as u16);
+                } else {
+                    self.breakpoints.retain(|&b| b != address);
+                    // another bank may have one at the same CPU address
+                    if !self.breakpoints.iter().any(|&b| b as u16 == address as u16) {
+                        emulator.debugger.remove_breakpoint(address as u16);
+                    }
+                }
+                return Some("OK".into());
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::ReadWrite,
+            _ => return Some(String::new()),
+        };
+
+        for i in 0..length.min(0x100) {
+            let address = address.wrapping_add(i) as u16;
+            if insert {
+                emulator.debugger.add_watchpoint(address, watch);
+            } else {
+                emulator.debugger.remove_watchpoint(address);
+            }
+        }
+        Some("OK".into())
+    }
+}
@@ -0,0 +1,155 @@
+//! Memory access heatmaps
+//!
+//! While recording, the emulator counts every CPU data read and write by
+//! address, and every instruction by the address of its opcode. Sprite RAM
+//! gets its own counts, by byte across all eight pages: writes by the CPU,
+//! and reads by the blitter as it copies sprites. Operand fetches, dummy
+//! reads and the emulator's own pokes (cheats, scripts) aren't counted.
+//!
+//! RAM is counted by CPU address, whichever bank was selected.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Bytes of sprite RAM, eight 256x256 pages
+const SPRITE_RAM: usize = 8 * 256 * 256;
+
+/// What the CPU did with an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// Fetched an opcode, i.e. ran the instruction there
+    Execute,
+}
+
+impl Access {
+    pub const ALL: [Access; 3] = [Access::Read, Access::Write, Access::Execute];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Access::Read => "reads",
+            Access::Write => "writes",
+            Access::Execute => "executes",
+        }
+    }
+}
+
+/// Access counts since [`Emulator::start_heatmap`](crate::emulator::Emulator::start_heatmap)
+/// or the last clear. Counts stop at `u32::MAX`.
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    /// By [`Access`], then CPU address
+    cpu: [Vec<u32>; 3],
+    /// By page, then byte as stored (quadrant by quadrant)
+    sprite_writes: Vec<u32>,
+    blitter_reads: Vec<u32>,
+    frames: u32,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self {
+            cpu: [vec![0; 0x10000], vec![0; 0x10000], vec![0; 0x10000]],
+            sprite_writes: vec![0; SPRITE_RAM],
+            blitter_reads: vec![0; SPRITE_RAM],
+            frames: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    #[inline]
+    pub(crate) fn cpu_access(&mut self, access: Access, address: u16) {
+        let count = &mut self.cpu[access as usize][address as usize];
+        *count = count.saturating_add(1);
+    }
+
+    /// A CPU write to sprite RAM byte `index` (`page * 0x10000 + offset`)
+    #[inline]
+    pub(crate) fn sprite_write(&mut self, index: usize) {
+        let count = &mut self.sprite_writes[index % SPRITE_RAM];
+        *count = count.saturating_add(1);
+    }
+
+    /// A blitter read of sprite RAM byte `index`
+    #[inline]
+    pub(crate) fn blitter_read(&mut self, index: usize) {
+        let count = &mut self.blitter_reads[index % SPRITE_RAM];
+        *count = count.saturating_add(1);
+    }
+
+    pub(crate) fn vblank(&mut self) {
+        self.frames = self.frames.saturating_add(1);
+    }
+
+    /// Frames counted over
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Counts for every CPU address
+    pub fn cpu(&self, access: Access) -> &[u32] {
+        &self.cpu[access as usize]
+    }
+
+    pub fn count(&self, access: Access, address: u16) -> u32 {
+        self.cpu[access as usize][address as usize]
+    }
+
+    /// Counts for each 256-byte page of the address space
+    pub fn pages(&self, access: Access) -> [u32; 256] {
+        let mut pages = [0u32; 256];
+        for (page, counts) in pages.iter_mut().zip(self.cpu(access).chunks_exact(256)) {
+            *page = counts.iter().fold(0u32, |sum, &c| sum.saturating_add(c));
+        }
+        pages
+    }
+
+    /// The `count` busiest addresses, busiest first
+    pub fn hottest(&self, access: Access, count: usize) -> Vec<(u16, u32)> {
+        let mut hot: Vec<(u16, u32)> = self.cpu(access).iter()
+            .enumerate()
+            .filter(|(_, &c)| c > 0)
+            .map(|(address, &c)| (address as u16, c))
+            .collect();
+        hot.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot.truncate(count);
+        hot
+    }
+
+    /// CPU writes to sprite RAM page `page`, laid out like
+    /// [`Emulator::sprite_page`](crate::emulator::Emulator::sprite_page)
+    pub fn sprite_writes(&self, page: usize) -> Box<[u32; 256 * 256]> {
+        Self::page_image(&self.sprite_writes, page)
+    }
+
+    /// Blitter reads of sprite RAM page `page`, laid out like
+    /// [`Emulator::sprite_page`](crate::emulator::Emulator::sprite_page)
+    pub fn blitter_reads(&self, page: usize) -> Box<[u32; 256 * 256]> {
+        Self::page_image(&self.blitter_reads, page)
+    }
+
+    fn page_image(counts: &[u32], page: usize) -> Box<[u32; 256 * 256]> {
+        let bank = &counts[(page % 8) * 0x10000..][..0x10000];
+        let mut image = Box::new([0; 256 * 256]);
+        // stored as four 128x128 quadrants
+        for (quadrant, pixels) in bank.chunks_exact(128 * 128).enumerate() {
+            let (qx, qy) = ((quadrant % 2) * 128, (quadrant / 2) * 128);
+            for (row, line) in pixels.chunks_exact(128).enumerate() {
+                let start = (qy + row) * 256 + qx;
+                image[start..start + 128].copy_from_slice(line);
+            }
+        }
+        image
+    }
+}
@@ -14,6 +14,27 @@ pub struct GamePad {
     pub port_select: bool,
 }
 
+impl GamePad {
+    /// The buttons as a bitmask, up, down, left, right, B, A, C and start
+    /// from bit 0
+    pub fn buttons(&self) -> u8 {
+        [self.up, self.down, self.left, self.right, self.b, self.a, self.c, self.start]
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &pressed)| bits | (pressed as u8) << i)
+    }
+
+    /// Set every button from a [`buttons`](Self::buttons) bitmask
+    pub fn set_buttons(&mut self, bits: u8) {
+        for (i, pressed) in [
+            &mut self.up, &mut self.down, &mut self.left, &mut self.right,
+            &mut self.b, &mut self.a, &mut self.c, &mut self.start,
+        ].into_iter().enumerate() {
+            *pressed = bits & (1 << i) != 0;
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 #[derive(Eq, Hash, PartialEq)]
 pub enum ControllerButton {
@@ -35,6 +56,12 @@ pub enum InputCommand {
     PlayPause,
     SoftReset,
     HardReset,
+    /// Step back through recent frames while held, if rewind is enabled
+    Rewind,
+    /// Run at [`FAST_FORWARD_SPEED`](crate::emulator::FAST_FORWARD_SPEED) while held
+    FastForward,
+    /// Pause, then run a single frame on release
+    FrameAdvance,
 }
 
 #[derive(Copy, Clone, Debug)]
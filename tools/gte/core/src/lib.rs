@@ -10,4 +10,19 @@ pub mod blitter;
 pub mod gametank_bus;
 pub mod cartridges;
 pub mod emulator;
+pub mod debugger;
+pub mod acp;
+pub mod gdb;
+pub mod symbols;
+pub mod elf_rom;
 pub mod inputs;
+pub mod rom_header;
+pub mod savestate;
+pub mod movie;
+pub mod cheats;
+pub mod trace;
+pub mod heatmap;
+pub mod bench;
+pub mod disasm;
+#[cfg(feature = "scripting")]
+pub mod script;
@@ -0,0 +1,114 @@
+//! Input movies (`.gtm`)
+//!
+//! A movie is the controller state for every frame, plus where the run
+//! started, so it replays exactly: for sharing repro cases, or driving
+//! regression runs in `gtrom test`. Controllers are only read at vblank
+//! while a movie is recording or playing, which keeps input on frame
+//! boundaries however the host schedules the emulator.
+//!
+//! ## Format
+//!
+//! All integers are little-endian.
+//!
+//! | Field | Size |
+//! |---|---|
+//! | `GTMV` | 4 |
+//! | version | 1 |
+//! | CRC-32 of the ROM, without its `.gtr` header | 4 |
+//! | CRC-32 of the save state the first frame started from | 4 |
+//! | length of the starting save state, 0 for power-on | 4 |
+//! | starting save state | as given |
+//! | frame count | 4 |
+//! | controller 1 and 2 buttons, per frame | 2 each |
+//!
+//! Buttons are [`GamePad::buttons`](crate::inputs::GamePad::buttons) bitmasks.
+//! Movies from power-on also depend on the cartridge's save flash, so the
+//! start hash is there to tell when a replay won't match.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+pub const MAGIC: &[u8; 4] = b"GTMV";
+pub const VERSION: u8 = 1;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Movie {
+    /// The ROM it was recorded on, from [`crc32`](crate::rom_header::crc32)
+    pub rom_crc: u32,
+    /// CRC-32 of the machine's save state as the first frame began
+    pub start_hash: u32,
+    /// Save state to start from, or `None` for a hard reset
+    pub start_state: Option<Vec<u8>>,
+    /// Controller 1 and 2 buttons for each frame
+    pub frames: Vec<[u8; 2]>,
+}
+
+impl Movie {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let state = self.start_state.as_deref().unwrap_or_default();
+        let mut bytes = Vec::with_capacity(21 + state.len() + self.frames.len() * 2);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.rom_crc.to_le_bytes());
+        bytes.extend_from_slice(&self.start_hash.to_le_bytes());
+        bytes.extend_from_slice(&(state.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(state);
+        bytes.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for frame in &self.frames {
+            bytes.extend_from_slice(frame);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut r = Reader(bytes);
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err("not a GameTank movie".to_string());
+        }
+        let version = r.take(1)?[0];
+        if version != VERSION {
+            return Err(format!("movie is version {}, expected {}", version, VERSION));
+        }
+        let rom_crc = r.u32()?;
+        let start_hash = r.u32()?;
+        let state_len = r.u32()? as usize;
+        let start_state = match state_len {
+            0 => None,
+            len => Some(r.take(len)?.to_vec()),
+        };
+        let frame_count = r.u32()? as usize;
+        let frames = r.take(frame_count.saturating_mul(2))?
+            .chunks_exact(2)
+            .map(|pads| [pads[0], pads[1]])
+            .collect();
+
+        Ok(Self { rom_crc, start_hash, start_state, frames })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < len {
+            return Err("movie is cut short".to_string());
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
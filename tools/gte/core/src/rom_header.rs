@@ -0,0 +1,214 @@
+//! `.gtr` container header
+//!
+//! A `.gtr` file is a flat cartridge image, optionally prefixed with a
+//! fixed-size header describing the ROM. Files without the header are still
+//! accepted everywhere, so old ROMs keep working.
+//!
+//! Header layout (little-endian, `HEADER_SIZE` bytes):
+//!
+//! | offset | size | field                                   |
+//! |--------|------|-----------------------------------------|
+//! | 0      | 4    | magic, `GTR\x1A`                         |
+//! | 4      | 1    | format version                          |
+//! | 5      | 1    | bank count (16K banks, 0 for < 16K ROMs) |
+//! | 6      | 2    | entry point (reset vector)              |
+//! | 8      | 4    | CRC32 of the ROM payload                |
+//! | 12     | 4    | ROM payload length in bytes             |
+//! | 16     | 32   | title, UTF-8, NUL padded                |
+//! | 48     | 32   | author, UTF-8, NUL padded               |
+//! | 80     | 16   | ROM version, UTF-8, NUL padded          |
+//! | 96     | 2    | boot splash address, in ROM only        |
+//! | 98     | 1    | mapper id, 0 to go by the ROM's size    |
+//! | 99     | 1    | save size in KB, 0 for the mapper's own |
+//! | 100    | 28   | reserved, zero                          |
+//!
+//! Mapper ids are listed in [`Mapper`](crate::cartridges::mapper::Mapper).
+//!
+//! The SDK's `gametank_rom!` puts the same layout in the ROM itself, at
+//! [`INFO_ADDRESS`] in the fixed bank, with zero for the fields only known
+//! once the ROM is built. [`RomHeader::embedded`] reads it back, so a
+//! headerless image can still say what it is.
+
+use alloc::string::{String, ToString};
+use core::fmt::{Display, Formatter};
+
+pub const MAGIC: [u8; 4] = *b"GTR\x1A";
+pub const VERSION: u8 = 1;
+pub const HEADER_SIZE: usize = 128;
+
+/// Where `gametank_rom!` links its header, just below the vector table
+pub const INFO_ADDRESS: u16 = 0xFF7A;
+
+const TITLE_OFFSET: usize = 16;
+const AUTHOR_OFFSET: usize = 48;
+const TEXT_LEN: usize = 32;
+const ROM_VERSION_OFFSET: usize = 80;
+const ROM_VERSION_LEN: usize = 16;
+const MAPPER_OFFSET: usize = 98;
+const SAVE_SIZE_OFFSET: usize = 99;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u8),
+    LengthMismatch { expected: u32, actual: u32 },
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl Display for HeaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            HeaderError::Truncated => write!(f, "header is truncated"),
+            HeaderError::BadMagic => write!(f, "not a .gtr header"),
+            HeaderError::UnsupportedVersion(v) => write!(f, "unsupported header version {}", v),
+            HeaderError::LengthMismatch { expected, actual } => {
+                write!(f, "payload is {} bytes, header says {}", actual, expected)
+            }
+            HeaderError::ChecksumMismatch { expected, actual } => {
+                write!(f, "CRC32 is {:08X}, header says {:08X}", actual, expected)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomHeader {
+    pub version: u8,
+    pub bank_count: u8,
+    pub entry_point: u16,
+    pub crc32: u32,
+    pub rom_size: u32,
+    pub title: String,
+    pub author: String,
+    /// The game's own version, like `1.2`
+    pub rom_version: String,
+    /// Which [`Mapper`](crate::cartridges::mapper::Mapper) the cartridge is
+    /// wired as, 0 to go by the ROM's size
+    pub mapper: u8,
+    /// KB of the cartridge kept as save data, 0 for the mapper's default
+    pub save_size: u8,
+}
+
+impl RomHeader {
+    /// Describe a ROM payload. The entry point is read from the reset vector.
+    pub fn new(title: &str, author: &str, rom: &[u8]) -> Self {
+        let entry_point = match rom.len() {
+            n if n >= 4 => u16::from_le_bytes([rom[n - 4], rom[n - 3]]),
+            _ => 0,
+        };
+
+        Self {
+            version: VERSION,
+            bank_count: (rom.len() / 0x4000).min(u8::MAX as usize) as u8,
+            entry_point,
+            crc32: crc32(rom),
+            rom_size: rom.len() as u32,
+            title: title.to_string(),
+            author: author.to_string(),
+            rom_version: String::new(),
+            mapper: 0,
+            save_size: 0,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut out = [0u8; HEADER_SIZE];
+        out[0..4].copy_from_slice(&MAGIC);
+        out[4] = self.version;
+        out[5] = self.bank_count;
+        out[6..8].copy_from_slice(&self.entry_point.to_le_bytes());
+        out[8..12].copy_from_slice(&self.crc32.to_le_bytes());
+        out[12..16].copy_from_slice(&self.rom_size.to_le_bytes());
+        write_text(&mut out[TITLE_OFFSET..TITLE_OFFSET + TEXT_LEN], &self.title);
+        write_text(&mut out[AUTHOR_OFFSET..AUTHOR_OFFSET + TEXT_LEN], &self.author);
+        write_text(&mut out[ROM_VERSION_OFFSET..ROM_VERSION_OFFSET + ROM_VERSION_LEN], &self.rom_version);
+        out[MAPPER_OFFSET] = self.mapper;
+        out[SAVE_SIZE_OFFSET] = self.save_size;
+        out
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, HeaderError> {
+        if bytes.len() < 4 || bytes[0..4] != MAGIC {
+            return Err(HeaderError::BadMagic);
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(HeaderError::Truncated);
+        }
+        if bytes[4] != VERSION {
+            return Err(HeaderError::UnsupportedVersion(bytes[4]));
+        }
+
+        Ok(Self {
+            version: bytes[4],
+            bank_count: bytes[5],
+            entry_point: u16::from_le_bytes([bytes[6], bytes[7]]),
+            crc32: u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            rom_size: u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+            title: read_text(&bytes[TITLE_OFFSET..TITLE_OFFSET + TEXT_LEN]),
+            author: read_text(&bytes[AUTHOR_OFFSET..AUTHOR_OFFSET + TEXT_LEN]),
+            rom_version: read_text(&bytes[ROM_VERSION_OFFSET..ROM_VERSION_OFFSET + ROM_VERSION_LEN]),
+            mapper: bytes[MAPPER_OFFSET],
+            save_size: bytes[SAVE_SIZE_OFFSET],
+        })
+    }
+
+    /// The header `gametank_rom!` put in a ROM payload, if it has one. Only
+    /// the title, author and ROM version are filled in.
+    pub fn embedded(rom: &[u8]) -> Option<Self> {
+        // the last byte of the ROM is always $FFFF
+        let start = rom.len().checked_sub(0x10000 - INFO_ADDRESS as usize)?;
+        Self::parse(rom.get(start..start + HEADER_SIZE)?).ok()
+    }
+
+    /// Check the payload against the header's length and checksum
+    pub fn validate(&self, rom: &[u8]) -> Result<(), HeaderError> {
+        if rom.len() as u32 != self.rom_size {
+            return Err(HeaderError::LengthMismatch { expected: self.rom_size, actual: rom.len() as u32 });
+        }
+
+        let actual = crc32(rom);
+        if actual != self.crc32 {
+            return Err(HeaderError::ChecksumMismatch { expected: self.crc32, actual });
+        }
+
+        Ok(())
+    }
+
+    /// Split a file into its header (if any) and the ROM payload
+    pub fn split(bytes: &[u8]) -> (Option<Result<Self, HeaderError>>, &[u8]) {
+        if bytes.len() < 4 || bytes[0..4] != MAGIC {
+            return (None, bytes);
+        }
+
+        let payload = bytes.get(HEADER_SIZE..).unwrap_or(&[]);
+        (Some(Self::parse(bytes)), payload)
+    }
+}
+
+fn write_text(dst: &mut [u8], text: &str) {
+    // truncate on a char boundary so the stored text stays valid UTF-8
+    let mut end = text.len().min(dst.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    dst[..end].copy_from_slice(&text.as_bytes()[..end]);
+}
+
+fn read_text(src: &[u8]) -> String {
+    let end = src.iter().position(|&b| b == 0).unwrap_or(src.len());
+    String::from_utf8_lossy(&src[..end]).to_string()
+}
+
+/// CRC-32 (IEEE), same as zlib/crc32fast
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
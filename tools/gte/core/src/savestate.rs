@@ -0,0 +1,371 @@
+//! Save states and rewind
+//!
+//! A save state is everything the emulated machine needs to carry on from
+//! where it was: both CPUs, RAM, VRAM, the framebuffers, audio RAM, the
+//! blitter mid-blit, and whatever flash the cartridge can write. Host-side
+//! things (timing, audio output, the debugger, held keys) aren't included.
+//! See [`Emulator::save_state`](crate::emulator::Emulator::save_state).
+//!
+//! [`RewindBuffer`] keeps the last few seconds of states. Consecutive frames
+//! are nearly identical, so only the newest is kept whole, and each older one
+//! as the bytes that changed since, run-length encoded.
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use gte_acp::{AcpBus, ARAM};
+use gte_w65c02s::{W65C02S, STATE_LEN};
+use crate::gametank_bus::CpuBus;
+use crate::inputs::GamePad;
+
+/// Marks the start of every save state
+pub const MAGIC: &[u8; 4] = b"GTST";
+/// Bumped whenever the layout changes, so old states are refused rather
+/// than loaded wrong
+pub const VERSION: u8 = 1;
+
+/// Appends fields to a save state
+pub(crate) struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        Self { bytes }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.bytes.push(value as u8);
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads fields back in the order [`StateWriter`] wrote them
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, String> {
+        let mut reader = Self { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("not a GameTank save state".to_string());
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(format!("save state is version {}, expected {}", version, VERSION));
+        }
+        Ok(reader)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err("save state is truncated".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn i32(&mut self) -> Result<i32, String> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn bytes_into(&mut self, out: &mut [u8]) -> Result<(), String> {
+        out.copy_from_slice(self.take(out.len())?);
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        if !self.bytes.is_empty() {
+            return Err(format!("{} unexpected bytes at the end of the save state", self.bytes.len()));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn save_cpu(w: &mut StateWriter, cpu: &W65C02S) {
+    w.bytes(&cpu.save_state());
+}
+
+pub(crate) fn load_cpu(r: &mut StateReader, cpu: &mut W65C02S) -> Result<(), String> {
+    let mut bytes = [0; STATE_LEN];
+    r.bytes_into(&mut bytes)?;
+    if !cpu.load_state(&bytes) {
+        return Err("save state has an invalid CPU state".to_string());
+    }
+    Ok(())
+}
+
+/// The ACP's bus, along with audio RAM
+pub(crate) fn save_acp_bus(w: &mut StateWriter, bus: &AcpBus) {
+    w.i32(bus.irq_counter);
+    w.u8(bus.sample);
+    unsafe { w.bytes(&ARAM[..]) };
+}
+
+pub(crate) fn load_acp_bus(r: &mut StateReader, bus: &mut AcpBus) -> Result<(), String> {
+    bus.irq_counter = r.i32()?;
+    bus.sample = r.u8()?;
+    unsafe { r.bytes_into(&mut ARAM[..]) }
+}
+
+fn save_gamepad(w: &mut StateWriter, pad: &GamePad) {
+    for b in [pad.up, pad.down, pad.left, pad.right, pad.b, pad.a, pad.c, pad.start, pad.port_select] {
+        w.bool(b);
+    }
+}
+
+fn load_gamepad(r: &mut StateReader, pad: &mut GamePad) -> Result<(), String> {
+    for b in [
+        &mut pad.up, &mut pad.down, &mut pad.left, &mut pad.right,
+        &mut pad.b, &mut pad.a, &mut pad.c, &mut pad.start, &mut pad.port_select,
+    ] {
+        *b = r.bool()?;
+    }
+    Ok(())
+}
+
+/// Registers and memory on the main bus, including the cartridge
+pub(crate) fn save_cpu_bus(w: &mut StateWriter, bus: &CpuBus) {
+    let sc = &bus.system_control;
+    w.u8(sc.reset_acp);
+    w.u8(sc.nmi_acp);
+    w.u8(sc.banking_register.0);
+    w.bytes(&sc.via_regs);
+    w.u8(sc.audio_enable_sample_rate);
+    w.u8(sc.dma_flags.0);
+    for pad in &sc.gamepads {
+        save_gamepad(w, pad);
+    }
+
+    let regs = &bus.blitter;
+    w.bytes(&[regs.vx, regs.vy, regs.gx, regs.gy, regs.width, regs.height, regs.color]);
+    w.u8(regs.start.write);
+    w.bool(regs.start.addressed);
+
+    for bank in bus.ram_banks.iter() {
+        w.bytes(bank);
+    }
+    for fb in &bus.framebuffers {
+        w.bytes(&fb.borrow()[..]);
+    }
+    for bank in bus.vram_banks.iter() {
+        w.bytes(bank);
+    }
+    for &written in &bus.vram_quad_written {
+        w.bool(written);
+    }
+
+    bus.cartridge.save_state(w);
+}
+
+pub(crate) fn load_cpu_bus(r: &mut StateReader, bus: &mut CpuBus) -> Result<(), String> {
+    let sc = &mut bus.system_control;
+    sc.reset_acp = r.u8()?;
+    sc.nmi_acp = r.u8()?;
+    sc.banking_register.0 = r.u8()?;
+    r.bytes_into(&mut sc.via_regs)?;
+    sc.audio_enable_sample_rate = r.u8()?;
+    sc.dma_flags.0 = r.u8()?;
+    for pad in &mut sc.gamepads {
+        load_gamepad(r, pad)?;
+    }
+
+    let regs = &mut bus.blitter;
+    for reg in [
+        &mut regs.vx, &mut regs.vy, &mut regs.gx, &mut regs.gy,
+        &mut regs.width, &mut regs.height, &mut regs.color,
+    ] {
+        *reg = r.u8()?;
+    }
+    regs.start.write = r.u8()?;
+    regs.start.addressed = r.bool()?;
+
+    for bank in bus.ram_banks.iter_mut() {
+        r.bytes_into(bank)?;
+    }
+    for fb in &bus.framebuffers {
+        r.bytes_into(&mut fb.borrow_mut()[..])?;
+    }
+    for bank in bus.vram_banks.iter_mut() {
+        r.bytes_into(bank)?;
+    }
+    for written in &mut bus.vram_quad_written {
+        *written = r.bool()?;
+    }
+
+    bus.cartridge.load_state(r)
+}
+
+/// Frames of save states, newest first, for stepping back through
+#[derive(Debug, Clone)]
+pub struct RewindBuffer {
+    /// The newest state, whole
+    current: Vec<u8>,
+    /// Each one turns a state into the one before it, oldest first
+    deltas: VecDeque<Vec<u8>>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    /// A buffer holding up to `frames` frames of history
+    pub fn new(frames: usize) -> Self {
+        Self {
+            current: Vec::new(),
+            deltas: VecDeque::new(),
+            capacity: frames,
+        }
+    }
+
+    /// How many frames back it's possible to go
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Bytes of history held, roughly
+    pub fn memory_used(&self) -> usize {
+        self.current.len() + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn clear(&mut self) {
+        self.current.clear();
+        self.deltas.clear();
+    }
+
+    /// The newest state, if any were pushed
+    pub fn current(&self) -> Option<&[u8]> {
+        (!self.current.is_empty()).then_some(&self.current[..])
+    }
+
+    /// Record the state for a new frame, dropping the oldest past capacity
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.current.len() == state.len() {
+            self.deltas.push_back(encode_delta(&state, &self.current));
+            while self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        } else {
+            // a different cartridge; the old history doesn't apply
+            self.deltas.clear();
+        }
+        self.current = state;
+    }
+
+    /// Go back up to `frames` frames, making that the newest state. Returns
+    /// how many frames it went back.
+    pub fn rewind(&mut self, frames: usize) -> usize {
+        let mut rewound = 0;
+        while rewound < frames {
+            let Some(delta) = self.deltas.pop_back() else { break };
+            apply_delta(&mut self.current, &delta);
+            rewound += 1;
+        }
+        rewound
+    }
+}
+
+/// Encode the XOR of two equal-length states as runs of
+/// `[unchanged length][changed length][changed bytes XORed]`, with LEB128
+/// lengths. XOR works both ways, so applying it to `to` gives back `from`.
+fn encode_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < from.len() {
+        let start = i;
+        while i < from.len() && from[i] == to[i] {
+            i += 1;
+        }
+        if i == from.len() {
+            break;
+        }
+        let skipped = i - start;
+
+        // a short unchanged stretch costs less as literals than a new run
+        let changed_start = i;
+        let mut same = 0;
+        while i < from.len() && same < 4 {
+            same = if from[i] == to[i] { same + 1 } else { 0 };
+            i += 1;
+        }
+        let changed_end = i - same;
+        i = changed_end;
+
+        write_len(&mut out, skipped);
+        write_len(&mut out, changed_end - changed_start);
+        out.extend(from[changed_start..changed_end].iter().zip(&to[changed_start..changed_end]).map(|(a, b)| a ^ b));
+    }
+    out
+}
+
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut d = 0;
+    while d < delta.len() {
+        pos += read_len(delta, &mut d);
+        let len = read_len(delta, &mut d);
+        for (byte, xor) in state[pos..pos + len].iter_mut().zip(&delta[d..d + len]) {
+            *byte ^= xor;
+        }
+        pos += len;
+        d += len;
+    }
+}
+
+fn write_len(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 0x80 {
+        out.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+}
+
+fn read_len(bytes: &[u8], pos: &mut usize) -> usize {
+    let mut len = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*pos];
+        *pos += 1;
+        len |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return len;
+        }
+        shift += 7;
+    }
+}
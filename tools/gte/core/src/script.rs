@@ -0,0 +1,265 @@
+//! Scripted input playback and checks, with the `scripting` feature
+//!
+//! A [Rhai](https://rhai.rs) script drives the controllers and checks RAM
+//! one frame at a time, for repeatable bug reports and for `gtrom test`.
+//! The script's `on_frame(frame)` runs before each frame, counting from 0:
+//!
+//! ```rhai
+//! fn on_frame(frame) {
+//!     if frame == 60 { tap(1, "start"); }
+//!     if frame >= 90 && frame < 150 { press(1, "right"); } else { release(1, "right"); }
+//!
+//!     if frame == 200 {
+//!         assert_eq(peek(sym("PLAYER_X")), 96, "player walked right");
+//!         pass();
+//!     }
+//! }
+//! ```
+//!
+//! | Function | |
+//! |---|---|
+//! | `press(pad, button)`, `release(pad, button)` | hold or let go of a button on pad 1 or 2 |
+//! | `tap(pad, button)` | press for one frame |
+//! | `peek(address)`, `peek16(address)` | read RAM as it was at the end of the last frame |
+//! | `poke(address, value)` | write RAM before the frame runs |
+//! | `sym(name)` | address of a symbol in RAM, when the runner has the ELF |
+//! | `pass()`, `fail(message)` | end the run |
+//! | `assert(condition, message)`, `assert_eq(actual, expected, message)` | fail if not |
+//!
+//! Buttons are `up`, `down`, `left`, `right`, `a`, `b`, `c` and `start`.
+//! Only RAM (`$0000-$1FFF`, in whichever bank is selected) can be peeked
+//! and poked.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+
+use crate::emulator::{Emulator, TimeDaemon};
+use crate::inputs::{ControllerButton, InputCommand, KeyState};
+
+const RAM_SIZE: usize = 0x2000;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// How a script ended the run
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScriptOutcome {
+    Passed,
+    Failed(String),
+}
+
+/// Something the script asked for, applied once `on_frame` returns
+enum Action {
+    Button(InputCommand, bool),
+    Poke(u16, u8),
+}
+
+/// What the script's functions see and change
+struct ScriptState {
+    ram: Vec<u8>,
+    symbols: BTreeMap<String, u16>,
+    actions: Vec<Action>,
+    /// Buttons from `tap`, let go of before the next frame
+    tapped: Vec<InputCommand>,
+    outcome: Option<ScriptOutcome>,
+    log: Vec<String>,
+}
+
+impl ScriptState {
+    fn ram_address(address: i64) -> ScriptResult<usize> {
+        match usize::try_from(address) {
+            Ok(a) if a < RAM_SIZE => Ok(a),
+            _ => Err(format!("${:04X} is outside RAM", address).into()),
+        }
+    }
+
+    fn finish(&mut self, outcome: ScriptOutcome) {
+        // the first pass or fail sticks
+        if self.outcome.is_none() {
+            self.outcome = Some(outcome);
+        }
+    }
+}
+
+fn button_command(pad: i64, button: &str) -> ScriptResult<InputCommand> {
+    let button = match button {
+        "up" => ControllerButton::Up,
+        "down" => ControllerButton::Down,
+        "left" => ControllerButton::Left,
+        "right" => ControllerButton::Right,
+        "a" => ControllerButton::A,
+        "b" => ControllerButton::B,
+        "c" => ControllerButton::C,
+        "start" => ControllerButton::Start,
+        _ => return Err(format!("unknown button \"{}\"", button).into()),
+    };
+    match pad {
+        1 => Ok(InputCommand::Controller1(button)),
+        2 => Ok(InputCommand::Controller2(button)),
+        _ => Err(format!("there's no pad {}; use 1 or 2", pad).into()),
+    }
+}
+
+/// A compiled script and what it has done so far
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Rc<RefCell<ScriptState>>,
+    frame: i64,
+}
+
+impl Script {
+    /// Compile `source` and run its top level once. It must define `on_frame`.
+    pub fn compile(source: &str) -> Result<Self, String> {
+        let state = Rc::new(RefCell::new(ScriptState {
+            ram: alloc::vec![0; RAM_SIZE],
+            symbols: BTreeMap::new(),
+            actions: Vec::new(),
+            tapped: Vec::new(),
+            outcome: None,
+            log: Vec::new(),
+        }));
+
+        let mut engine = Engine::new();
+        register_functions(&mut engine, &state);
+
+        let ast = engine.compile(source).map_err(|e| format!("Failed to compile script: {}", e))?;
+        if !ast.iter_functions().any(|f| f.name == "on_frame" && f.params.len() == 1) {
+            return Err("Script has no on_frame(frame) function".to_string());
+        }
+
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast).map_err(|e| format!("Script failed: {}", e))?;
+
+        Ok(Self { engine, ast, scope, state, frame: 0 })
+    }
+
+    /// Make `sym(name)` know these RAM addresses, e.g. from the ROM's ELF
+    pub fn set_symbols(&mut self, symbols: impl IntoIterator<Item = (String, u16)>) {
+        self.state.borrow_mut().symbols = symbols.into_iter().collect();
+    }
+
+    /// Frames the script has seen
+    pub fn frame(&self) -> i64 {
+        self.frame
+    }
+
+    /// Call `on_frame` for the frame `emulator` is about to run, then apply
+    /// its inputs and pokes
+    pub fn before_frame<Clock: TimeDaemon>(&mut self, emulator: &mut Emulator<Clock>) -> Result<(), String> {
+        {
+            let mut state = self.state.borrow_mut();
+            for (address, byte) in state.ram.iter_mut().enumerate() {
+                *byte = emulator.peek(address as u16);
+            }
+            let tapped = core::mem::take(&mut state.tapped);
+            state.actions.extend(tapped.into_iter().map(|command| Action::Button(command, false)));
+        }
+
+        let frame = self.frame;
+        self.frame += 1;
+        let result = self.engine.call_fn::<Dynamic>(&mut self.scope, &self.ast, "on_frame", (frame,));
+
+        // apply what it did before the error too
+        for action in core::mem::take(&mut self.state.borrow_mut().actions) {
+            match action {
+                Action::Button(command, pressed) => {
+                    let previous = emulator.input_state.get(&command).copied().unwrap_or(KeyState::Released);
+                    emulator.set_input_state(command, previous.update_state(pressed));
+                }
+                Action::Poke(address, value) => emulator.cpu_bus.write_byte(address, value),
+            }
+        }
+
+        result.map(|_| ()).map_err(|e| format!("Script failed on frame {}: {}", frame, e))
+    }
+
+    /// Whether the script has called `pass` or `fail`, or an assert failed
+    pub fn outcome(&self) -> Option<ScriptOutcome> {
+        self.state.borrow().outcome.clone()
+    }
+
+    /// Lines the script printed since the last call
+    pub fn take_log(&mut self) -> Vec<String> {
+        core::mem::take(&mut self.state.borrow_mut().log)
+    }
+}
+
+fn register_functions(engine: &mut Engine, state: &Rc<RefCell<ScriptState>>) {
+    let s = state.clone();
+    engine.on_print(move |text| s.borrow_mut().log.push(text.to_string()));
+    let s = state.clone();
+    engine.on_debug(move |text, _, _| s.borrow_mut().log.push(text.to_string()));
+
+    let s = state.clone();
+    engine.register_fn("press", move |pad: i64, button: &str| -> ScriptResult<()> {
+        s.borrow_mut().actions.push(Action::Button(button_command(pad, button)?, true));
+        Ok(())
+    });
+    let s = state.clone();
+    engine.register_fn("release", move |pad: i64, button: &str| -> ScriptResult<()> {
+        s.borrow_mut().actions.push(Action::Button(button_command(pad, button)?, false));
+        Ok(())
+    });
+    let s = state.clone();
+    engine.register_fn("tap", move |pad: i64, button: &str| -> ScriptResult<()> {
+        let command = button_command(pad, button)?;
+        let mut state = s.borrow_mut();
+        state.actions.push(Action::Button(command, true));
+        state.tapped.push(command);
+        Ok(())
+    });
+
+    let s = state.clone();
+    engine.register_fn("peek", move |address: i64| -> ScriptResult<i64> {
+        Ok(s.borrow().ram[ScriptState::ram_address(address)?] as i64)
+    });
+    let s = state.clone();
+    engine.register_fn("peek16", move |address: i64| -> ScriptResult<i64> {
+        let state = s.borrow();
+        let low = state.ram[ScriptState::ram_address(address)?];
+        let high = state.ram[ScriptState::ram_address(address + 1)?];
+        Ok(u16::from_le_bytes([low, high]) as i64)
+    });
+    let s = state.clone();
+    engine.register_fn("poke", move |address: i64, value: i64| -> ScriptResult<()> {
+        let address = ScriptState::ram_address(address)?;
+        let value = u8::try_from(value).map_err(|_| format!("{} doesn't fit in a byte", value))?;
+        let mut state = s.borrow_mut();
+        state.ram[address] = value;
+        state.actions.push(Action::Poke(address as u16, value));
+        Ok(())
+    });
+    let s = state.clone();
+    engine.register_fn("sym", move |name: &str| -> ScriptResult<i64> {
+        s.borrow().symbols.get(name)
+            .map(|&address| address as i64)
+            .ok_or_else(|| format!("no symbol \"{}\" in RAM", name).into())
+    });
+
+    let s = state.clone();
+    engine.register_fn("pass", move || s.borrow_mut().finish(ScriptOutcome::Passed));
+    let s = state.clone();
+    engine.register_fn("fail", move |message: &str| {
+        s.borrow_mut().finish(ScriptOutcome::Failed(message.to_string()));
+    });
+    let s = state.clone();
+    engine.register_fn("assert", move |condition: bool, message: &str| {
+        if !condition {
+            s.borrow_mut().finish(ScriptOutcome::Failed(format!("assertion failed: {}", message)));
+        }
+    });
+    let s = state.clone();
+    engine.register_fn("assert_eq", move |actual: Dynamic, expected: Dynamic, message: &str| {
+        if actual.to_string() != expected.to_string() {
+            s.borrow_mut().finish(ScriptOutcome::Failed(format!("{}: got {}, expected {}", message, actual, expected)));
+        }
+    });
+}
@@ -0,0 +1,146 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::format;
+
+/// A function from the ROM's symbol table
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    /// Linked address: banked code is at `$8000 + bank * $10000`
+    pub address: u32,
+    pub size: u32,
+    /// Demangled path, e.g. `game::player::update`
+    pub name: String,
+}
+
+impl Function {
+    /// The address to break on. Banked functions break in whichever bank is
+    /// mapped in when the CPU gets there.
+    pub fn cpu_address(&self) -> u16 {
+        self.address as u16
+    }
+}
+
+/// A source location
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SourceLine<'a> {
+    pub file: &'a str,
+    pub line: u32,
+}
+
+/// Function names and source lines from the `.sym` file `gtrom build` writes
+/// next to the ROM, or just the names from an ELF (see
+/// [`elf_rom`](crate::elf_rom)).
+#[derive(Default, Debug)]
+pub struct SymbolMap {
+    /// Sorted by address
+    functions: Vec<Function>,
+    files: Vec<String>,
+    /// `(address, file, line)` sorted by address, file 0 where there's no source
+    lines: Vec<(u32, u32, u32)>,
+}
+
+/// The linked address of `pc`, given the bank mapped in at $8000
+pub fn linked_address(pc: u16, bank: u8) -> u32 {
+    match pc {
+        0x8000..=0xBFFF => (bank as u32) << 16 | pc as u32,
+        _ => pc as u32,
+    }
+}
+
+fn hex(field: Option<&str>) -> Option<u32> {
+    u32::from_str_radix(field?, 16).ok()
+}
+
+impl SymbolMap {
+    /// Names without source lines, as read from an ELF
+    pub fn from_functions(mut functions: Vec<Function>) -> Self {
+        functions.sort_by_key(|f| f.address);
+        Self { functions, ..Self::default() }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut map = Self::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let bad_line = || format!("Bad symbol file line {}: {}", i + 1, line);
+            let (kind, rest) = line.split_once(' ').ok_or_else(bad_line)?;
+            match kind {
+                "file" => {
+                    // paths and names can have spaces in them, so they come last
+                    let (_index, path) = rest.split_once(' ').ok_or_else(bad_line)?;
+                    map.files.push(path.to_string());
+                }
+                "func" => {
+                    let mut fields = rest.splitn(3, ' ');
+                    let address = hex(fields.next()).ok_or_else(bad_line)?;
+                    let size = hex(fields.next()).ok_or_else(bad_line)?;
+                    let name = fields.next().ok_or_else(bad_line)?;
+                    map.functions.push(Function { address, size, name: name.to_string() });
+                }
+                "line" => {
+                    let mut fields = rest.split(' ');
+                    let address = hex(fields.next()).ok_or_else(bad_line)?;
+                    let file = fields.next().and_then(|f| f.parse().ok()).ok_or_else(bad_line)?;
+                    let number = fields.next().and_then(|f| f.parse().ok()).ok_or_else(bad_line)?;
+                    map.lines.push((address, file, number));
+                }
+                // newer gtrom may write records we don't know yet
+                _ => {}
+            }
+        }
+
+        map.functions.sort_by_key(|f| f.address);
+        map.lines.sort_by_key(|&(address, file, _)| (address, file != 0));
+        Ok(map)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    /// The function containing `address` and how far into it it is
+    pub fn function_at(&self, address: u32) -> Option<(&Function, u32)> {
+        let index = self.functions.partition_point(|f| f.address <= address).checked_sub(1)?;
+        let function = &self.functions[index];
+        let offset = address - function.address;
+        (offset < function.size).then_some((function, offset))
+    }
+
+    /// The source line `address` was compiled from
+    pub fn line_at(&self, address: u32) -> Option<SourceLine<'_>> {
+        let index = self.lines.partition_point(|&(a, _, _)| a <= address).checked_sub(1)?;
+        let (_, file, line) = self.lines[index];
+        let file = self.files.get(file.checked_sub(1)? as usize)?;
+        Some(SourceLine { file, line })
+    }
+
+    /// Look a function up by name: its full path, or any tail of it
+    /// (`update` or `player::update` for `game::player::update`). Prefers an
+    /// exact match, then the shortest path.
+    pub fn find(&self, name: &str) -> Option<&Function> {
+        let suffix = format!("::{}", name);
+        self.functions.iter().find(|f| f.name == name).or_else(|| {
+            self.functions.iter()
+                .filter(|f| f.name.ends_with(&suffix))
+                .min_by_key(|f| f.name.len())
+        })
+    }
+
+    /// `name+offset` for an address, e.g. `game::update+0x1A`
+    pub fn describe(&self, address: u32) -> Option<String> {
+        let (function, offset) = self.function_at(address)?;
+        Some(match offset {
+            0 => function.name.clone(),
+            _ => format!("{}+{:#X}", function.name, offset),
+        })
+    }
+}
@@ -0,0 +1,221 @@
+//! Frame traces in Chrome's trace event format
+//!
+//! While tracing, the emulator notes each frame with how much of it the CPU
+//! spent running rather than waiting in `WAI`, every blit from start to
+//! finish, the time spent in NMI and IRQ handlers, and each switch of ROM
+//! bank, RAM bank or sprite RAM page. [`Trace::to_chrome_json`] writes it
+//! all out for `chrome://tracing` or [Perfetto](https://ui.perfetto.dev),
+//! with the CPU and blitter on separate tracks.
+//!
+//! Times are CPU cycles since tracing started, shown as microseconds of
+//! real time at the GameTank's clock rate.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use gte_w65c02s::Interrupt;
+use crate::blitter::BlitInfo;
+
+/// CPU cycles per microsecond
+const CYCLES_PER_US: f64 = 3.579545;
+
+/// Events kept before tracing stops recording, about ten minutes of a busy
+/// game
+const MAX_EVENTS: usize = 2_000_000;
+
+/// Track ids in the trace
+const TRACK_FRAMES: u8 = 1;
+const TRACK_CPU: u8 = 2;
+const TRACK_BLITTER: u8 = 3;
+
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    /// A frame, vblank to vblank, and the cycles the CPU wasn't waiting
+    Frame { number: u32, start: u64, end: u64, busy: u64 },
+    /// The CPU sitting in `WAI`
+    Wait { start: u64, end: u64 },
+    /// An NMI or IRQ handler, from taking the interrupt to its `RTI`
+    Handler { interrupt: Interrupt, start: u64, end: u64 },
+    Blit { start: u64, end: u64, info: BlitInfo },
+    /// The bank named `kind` switched to `bank`
+    Bank { at: u64, kind: &'static str, bank: u8 },
+}
+
+/// What was recorded since [`Emulator::start_trace`](crate::emulator::Emulator::start_trace)
+#[derive(Debug, Clone)]
+pub struct Trace {
+    cycle: u64,
+    events: Vec<Event>,
+    frame: u32,
+    frame_start: u64,
+    /// Cycles this frame the CPU was waiting
+    frame_waiting: u64,
+    waiting_since: Option<u64>,
+    blit: Option<(u64, BlitInfo)>,
+    /// Handlers being run, innermost last
+    handlers: Vec<(Interrupt, u64)>,
+    /// ROM bank, RAM bank and sprite RAM page
+    banks: [u8; 3],
+    /// Whether events were lost to [`MAX_EVENTS`]
+    truncated: bool,
+}
+
+impl Trace {
+    pub(crate) fn new(banks: [u8; 3]) -> Self {
+        Self {
+            cycle: 0,
+            events: Vec::new(),
+            frame: 0,
+            frame_start: 0,
+            frame_waiting: 0,
+            waiting_since: None,
+            blit: None,
+            handlers: Vec::new(),
+            banks,
+            truncated: false,
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        if self.events.len() < MAX_EVENTS {
+            self.events.push(event);
+        } else {
+            self.truncated = true;
+        }
+    }
+
+    /// Before a CPU step: note an interrupt about to be taken, or an `RTI`
+    /// about to return from one
+    pub(crate) fn before_step(&mut self, interrupt: Option<Interrupt>, returning: bool) {
+        if let Some(interrupt) = interrupt {
+            self.handlers.push((interrupt, self.cycle));
+        } else if returning {
+            if let Some((interrupt, start)) = self.handlers.pop() {
+                // ends once the RTI has run
+                self.push(Event::Handler { interrupt, start, end: self.cycle + 6 });
+            }
+        }
+    }
+
+    /// After a CPU step of `cycles`, with whether the CPU is now waiting,
+    /// the blit underway if any, and the banks
+    pub(crate) fn after_step(&mut self, cycles: i32, waiting: bool, blit: Option<BlitInfo>, banks: [u8; 3]) {
+        let start = self.cycle;
+        self.cycle += cycles as u64;
+
+        match (self.waiting_since, waiting) {
+            (None, true) => self.waiting_since = Some(start),
+            (Some(since), false) => {
+                self.waiting_since = None;
+                self.frame_waiting += start - since;
+                self.push(Event::Wait { start: since, end: start });
+            }
+            _ => {}
+        }
+
+        match (self.blit, blit) {
+            (None, Some(info)) => self.blit = Some((start, info)),
+            (Some((since, info)), None) => {
+                self.blit = None;
+                self.push(Event::Blit { start: since, end: self.cycle, info });
+            }
+            _ => {}
+        }
+
+        const KINDS: [&str; 3] = ["ROM bank", "RAM bank", "sprite page"];
+        for (i, kind) in KINDS.into_iter().enumerate() {
+            if banks[i] != self.banks[i] {
+                self.banks[i] = banks[i];
+                self.push(Event::Bank { at: start, kind, bank: banks[i] });
+            }
+        }
+    }
+
+    /// At vblank: close the frame
+    pub(crate) fn vblank(&mut self) {
+        let mut waiting = self.frame_waiting;
+        if let Some(since) = self.waiting_since {
+            // split a wait across the frame boundary
+            waiting += self.cycle - since;
+            self.push(Event::Wait { start: since, end: self.cycle });
+            self.waiting_since = Some(self.cycle);
+        }
+
+        let (start, end) = (self.frame_start, self.cycle);
+        self.push(Event::Frame { number: self.frame, start, end, busy: (end - start).saturating_sub(waiting) });
+        self.frame += 1;
+        self.frame_start = self.cycle;
+        self.frame_waiting = 0;
+    }
+
+    /// Frames recorded
+    pub fn frames(&self) -> u32 {
+        self.frame
+    }
+
+    /// Whether the trace got too long and stopped recording
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// The trace as JSON for `chrome://tracing` or Perfetto
+    pub fn to_chrome_json(&self) -> String {
+        let us = |cycles: u64| cycles as f64 / CYCLES_PER_US;
+        let mut out = String::from("{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n");
+
+        for (track, name) in [(TRACK_FRAMES, "Frames"), (TRACK_CPU, "CPU"), (TRACK_BLITTER, "Blitter")] {
+            let _ = writeln!(out, "{{\"ph\":\"M\",\"pid\":1,\"tid\":{},\"name\":\"thread_name\",\"args\":{{\"name\":\"{}\"}}}},", track, name);
+        }
+
+        let span = |out: &mut String, track: u8, name: &str, start: u64, end: u64, args: String| {
+            let _ = writeln!(
+                out,
+                "{{\"ph\":\"X\",\"pid\":1,\"tid\":{},\"name\":\"{}\",\"ts\":{:.3},\"dur\":{:.3},\"args\":{{{}}}}},",
+                track, name, us(start), us(end - start), args
+            );
+        };
+
+        for event in &self.events {
+            match *event {
+                Event::Frame { number, start, end, busy } => {
+                    let percent = busy as f64 * 100.0 / (end - start).max(1) as f64;
+                    span(&mut out, TRACK_FRAMES, &format!("frame {}", number), start, end,
+                        format!("\"frame\":{},\"cpu_cycles\":{},\"cpu_percent\":{:.1}", number, busy, percent));
+                    let _ = writeln!(out, "{{\"ph\":\"C\",\"pid\":1,\"name\":\"CPU load %\",\"ts\":{:.3},\"args\":{{\"busy\":{:.1}}}}},", us(start), percent);
+                }
+                Event::Wait { start, end } => span(&mut out, TRACK_CPU, "WAI", start, end, String::new()),
+                Event::Handler { interrupt, start, end } => {
+                    let name = match interrupt {
+                        Interrupt::Nmi => "NMI",
+                        Interrupt::Irq => "IRQ",
+                    };
+                    span(&mut out, TRACK_CPU, name, start, end, String::new());
+                }
+                Event::Blit { start, end, info } => {
+                    let name = if info.color_fill { "fill" } else { "sprite" };
+                    span(&mut out, TRACK_BLITTER, name, start, end, format!(
+                        "\"dst\":\"{},{}\",\"size\":\"{}x{}\",\"src\":\"{},{}\",\"page\":{},\"framebuffer\":{},\"pixels\":{}",
+                        info.dst_x, info.dst_y, info.width, info.height, info.src_x, info.src_y,
+                        info.vram_page, info.framebuffer, info.width as u32 * info.height as u32,
+                    ));
+                }
+                Event::Bank { at, kind, bank } => {
+                    let _ = writeln!(
+                        out,
+                        "{{\"ph\":\"i\",\"s\":\"t\",\"pid\":1,\"tid\":{},\"name\":\"{} {}\",\"ts\":{:.3},\"args\":{{\"bank\":{}}}}},",
+                        TRACK_CPU, kind, bank, us(at), bank
+                    );
+                }
+            }
+        }
+
+        // JSON doesn't allow the last comma
+        if out.ends_with(",\n") {
+            out.truncate(out.len() - 2);
+            out.push('\n');
+        }
+        out.push_str("]}\n");
+        out
+    }
+}
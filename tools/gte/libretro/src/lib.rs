@@ -1,19 +1,33 @@
 #![allow(unused)]
 
-use std::collections::HashMap;
+mod options;
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CStr, CString};
+use std::path::PathBuf;
 
 #[macro_use]
 use libretro_rs::prelude::*;
 use libretro_rs::retro::env::SetEnvironment;
+use libretro_rs::ffi::{retro_log_callback, retro_log_level};
 
 use std::ffi::c_uint;
 use std::time::Instant;
-use gte_core::color_map::COLOR_MAP;
+use gte_core::cheats::Cheat;
+use gte_core::color_map::ColorMap;
 use gte_core::emulator::{Emulator, PlayState, TimeDaemon};
 use gte_core::inputs::{ControllerButton, InputCommand, KeyState};
 use gte_core::inputs::InputCommand::{Controller1, Controller2};
 use gte_core::inputs::KeyState::{JustPressed, JustReleased};
-use libretro_rs::prelude::env::{GetAvInfo, Init, Reset, Run, UnloadGame};
+use gte_core::movie::Movie;
+use libretro_rs::prelude::env::{CheatReset, CheatSet, GetAvInfo, GetMemoryData, GetMemorySize, GetSerializeSize, Init, Reset, Run, Serialize, UnloadGame, Unserialize};
+use crate::options::CoreOptions;
+
+/// A `.gtm` movie to play from power-on once the game loads
+const MOVIE_VAR: &str = "GAMETANK_MOVIE";
+/// Where to save a movie recorded from power-on, written when the game
+/// unloads or resets
+const RECORD_VAR: &str = "GAMETANK_RECORD";
 
 struct CoreEmulator {
     emu: Emulator<InstantClock>,
@@ -21,6 +35,14 @@ struct CoreEmulator {
     input_bindings: HashMap<(c_uint, JoypadButton), InputCommand>,
     pixel_format: Option<ActiveFormat<ORGB1555>>,
     framebuffer: FrameBufferThing,
+    /// `None` until the first frame, so options are read once the frontend is ready
+    options: Option<CoreOptions>,
+    /// Where the movie being recorded goes
+    record_to: Option<PathBuf>,
+    /// The frontend's enabled cheats, by its index for them
+    cheats: BTreeMap<c_uint, Vec<Cheat>>,
+    /// The frontend's logger, when it offers one
+    log: Option<retro_log_callback>,
 }
 
 struct FrameBufferThing {
@@ -67,15 +89,69 @@ impl Default for CoreEmulator {
             rendering_mode: None,
             pixel_format: None,
             framebuffer: FrameBufferThing { video_frame: vec![] },
+            options: None,
+            record_to: None,
+            cheats: BTreeMap::new(),
+            log: None,
         }
     }
 }
 
-pub fn buffer_to_color_image(framebuffer: &[u8; 128*128]) -> Vec<u8> {
+impl CoreEmulator {
+    /// Play or record a movie, as the environment asks. Frontends have no
+    /// way to pass one in, so problems are only printed.
+    fn start_movie(&mut self) {
+        if let Some(path) = std::env::var_os(MOVIE_VAR) {
+            let played = std::fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| Movie::from_bytes(&bytes))
+                .and_then(|movie| self.emu.play_movie(movie));
+            if let Err(e) = played {
+                eprintln!("Failed to play {}: {}", PathBuf::from(path).display(), e);
+            }
+        } else if let Some(path) = std::env::var_os(RECORD_VAR) {
+            self.emu.record_movie(true);
+            self.record_to = Some(path.into());
+        }
+    }
+
+    /// Write out the movie being recorded, if there is one
+    fn save_movie(&mut self) {
+        let Some(path) = self.record_to.take() else { return };
+        let Some(movie) = self.emu.stop_movie() else { return };
+        if let Err(e) = std::fs::write(&path, movie.to_bytes()) {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+
+    /// Pass a debug port message to the frontend's log, or to stderr if it
+    /// has none
+    fn log_debug_message(&self, message: &str) {
+        let log = self.log.and_then(|callback| callback.log);
+        let text = CString::new(format!("[GameTank] {}\n", message));
+        match (log, text) {
+            // through "%s", so the game's text is never taken as a format
+            (Some(log), Ok(text)) => unsafe { log(retro_log_level::RETRO_LOG_INFO, c"%s".as_ptr(), text.as_ptr()) },
+            _ => eprintln!("[GameTank] {}", message),
+        }
+    }
+
+    /// Hand the enabled cheats to the emulator, in the frontend's order
+    fn update_cheats(&mut self) {
+        self.emu.cheats = self.cheats.values().flatten().copied().collect();
+    }
+}
+
+pub fn buffer_to_color_image(framebuffer: &[u8; 128*128], color_map: &ColorMap, options: &CoreOptions) -> Vec<u8> {
     let mut pixels = Vec::with_capacity(128 * 128 * 2);
 
-    for &index in framebuffer.iter() {
-        let (r, g, b, _) = COLOR_MAP[index as usize];
+    for (i, &index) in framebuffer.iter().enumerate() {
+        let (r, g, b) = if options.is_masked(i % 128, i / 128) {
+            (0, 0, 0)
+        } else {
+            let (r, g, b, _) = color_map[index as usize];
+            (r, g, b)
+        };
 
         // Convert 8-bit channels → 5 bits each, ignore alpha.
         let r5 = (r >> 3) as u16;
@@ -99,10 +175,14 @@ impl<'a> Core<'a> for CoreEmulator {
         SystemInfo::new(
             c_utf8!("GameTank Rust!"),
             c_utf8!("0.1.2"),
-            ext!["zip", "gtr", "gtrs", "bin", "rom", "hex"],
+            ext!["zip", "gtr", "gtrs", "bin", "rom", "hex", "elf"],
         )
     }
 
+    fn set_environment(env: &mut impl SetEnvironment) {
+        options::declare(env);
+    }
+
     fn init(env: &mut impl Init) -> Self::Init {        
         env.set_support_no_game(true);
         Self::default()
@@ -122,18 +202,32 @@ impl<'a> Core<'a> for CoreEmulator {
         core.emu.load_rom(game_slice);
         // core.game_data = Some(game_data);
         core.emu.play_state = PlayState::Playing;
+        core.start_movie();
         core.rendering_mode = Some(rendering_mode);
         core.pixel_format = Some(pixel_format);
+        core.log = env.get_log_interface().ok();
 
         Ok(core)
     }
 
     fn get_system_av_info(&self, env: &mut impl GetAvInfo) -> SystemAVInfo {
         // default timing is 60FPS, 44.1KHz
-        SystemAVInfo::default_timings(GameGeometry::fixed(128, 128))
+        SystemAVInfo::default_timings(self.options.unwrap_or_default().geometry())
     }
 
     fn run(&mut self, env: &mut impl Run, callbacks: &mut impl Callbacks) -> InputsPolled {
+        if self.options.is_none() || env.get_variable_update().unwrap_or(false) {
+            let options = CoreOptions::read(env);
+            let previous = self.options.unwrap_or_default();
+            if options.integer_scaling != previous.integer_scaling {
+                let _ = env.set_geometry(&options.geometry());
+            }
+            self.emu.acp_accuracy = options.acp_accuracy;
+            self.emu.acp_clock_ratio = options.acp_clock_ratio;
+            self.emu.set_palette(options.palette);
+            self.options = Some(options);
+        }
+
         let inputs_polled = callbacks.poll_inputs();
         // update emulator inputs
         for ((port, button), command) in &self.input_bindings {
@@ -144,7 +238,20 @@ impl<'a> Core<'a> for CoreEmulator {
             }
         }
         
-        self.emu.process_cycles(false);
+        // uncapped, the frontend calls run faster than real time, so go by
+        // frames instead of the clock, which would barely move and leave
+        // audio lagging behind
+        if env.get_fastforwarding().unwrap_or(false) {
+            if self.emu.play_state == PlayState::Playing {
+                self.emu.run_frame();
+            }
+            self.emu.last_emu_tick = self.emu.clock.get_now_ms();
+        } else {
+            self.emu.process_cycles(false);
+        }
+        for message in self.emu.take_debug_messages() {
+            self.log_debug_message(&message);
+        }
         if let Some(ref mut audio_out) = &mut self.emu.audio_out {
             let mut audio_samples = Vec::with_capacity(4096);
             while !audio_out.output_buffer.is_empty() {
@@ -163,7 +270,8 @@ impl<'a> Core<'a> for CoreEmulator {
 
 
         let framebuffer = self.emu.cpu_bus.read_full_framebuffer();
-        self.framebuffer.video_frame = buffer_to_color_image(&framebuffer);
+        let options = self.options.unwrap_or_default();
+        self.framebuffer.video_frame = buffer_to_color_image(&framebuffer, self.emu.color_map(), &options);
 
         let rendering_mode = self.rendering_mode.take().unwrap();
         let pixel_format = self.pixel_format.take().unwrap();
@@ -176,13 +284,76 @@ impl<'a> Core<'a> for CoreEmulator {
     }
 
     fn reset(&mut self, env: &mut impl Reset) {
+        // the reset ends the movie, so keep what was recorded
+        self.save_movie();
         self.emu.input_state.insert(InputCommand::HardReset, JustReleased);
     }
 
-    fn unload_game(self, env: &mut impl UnloadGame) -> Self::Init {
-        // self will be dropped here, so we can just return the default state
+    fn unload_game(mut self, env: &mut impl UnloadGame) -> Self::Init {
+        self.save_movie();
+        // the frontend has already read the save RAM, so the emulator and
+        // cartridge can go; the next game starts from a fresh core
         Self::default()
     }
+
+    /// Save RAM is the cartridge's save flash, so the frontend writes it to
+    /// a .srm when content closes and restores it after loading. System RAM
+    /// is all four 8K banks in a row, for the frontend's cheat search and
+    /// RAM cheats.
+    fn get_memory_data(&mut self, env: &mut impl GetMemoryData, id: MemoryType) -> Result<Option<&mut [u8]>, CoreError> {
+        match id {
+            MemoryType::SaveRam => Ok(self.emu.cpu_bus.cartridge.save_data_mut()),
+            MemoryType::SystemRam => Ok(Some(self.emu.cpu_bus.ram_banks.as_flattened_mut())),
+            _ => Ok(None),
+        }
+    }
+
+    fn get_memory_size(&self, env: &mut impl GetMemorySize, id: MemoryType) -> Result<usize, CoreError> {
+        match id {
+            MemoryType::SaveRam => Ok(self.emu.cpu_bus.cartridge.save_data().map_or(0, |data| data.len())),
+            MemoryType::SystemRam => Ok(self.emu.cpu_bus.ram_banks.as_flattened().len()),
+            _ => Ok(0),
+        }
+    }
+
+    fn cheat_reset(&mut self, env: &mut impl CheatReset) {
+        self.cheats.clear();
+        self.update_cheats();
+    }
+
+    /// Codes are gte-core's (see `gte_core::cheats`), like `0042:09`. A
+    /// bad one is printed and skipped, since frontends can't show why.
+    fn cheat_set(&mut self, env: &mut impl CheatSet, index: c_uint, enabled: bool, code: &CStr) {
+        self.cheats.remove(&index);
+        if enabled {
+            match Cheat::parse(&code.to_string_lossy()) {
+                Ok(cheats) => { self.cheats.insert(index, cheats); }
+                Err(e) => eprintln!("Failed to add cheat {}: {}", index, e),
+            }
+        }
+        self.update_cheats();
+    }
+
+    /// Save states are gte-core's, which is also what frontend rewind uses
+    fn get_serialize_size(&self, env: &mut impl GetSerializeSize) -> usize {
+        self.emu.state_size()
+    }
+
+    fn serialize(&self, env: &mut impl Serialize, data: &mut [u8]) -> Result<(), CoreError> {
+        let state = self.emu.save_state();
+        let Some(out) = data.get_mut(..state.len()) else {
+            return Err(CoreError::new());
+        };
+        out.copy_from_slice(&state);
+        Ok(())
+    }
+
+    fn unserialize(&mut self, env: &mut impl Unserialize, data: &[u8]) -> Result<(), CoreError> {
+        // frontends may pad the buffer out to the size they were given
+        let size = self.emu.state_size();
+        let state = data.get(..size).unwrap_or(data);
+        self.emu.load_state(state).map_err(|_| CoreError::new())
+    }
 }
 
 unsafe impl FrameBuffer for FrameBufferThing {
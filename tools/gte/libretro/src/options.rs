@@ -0,0 +1,131 @@
+//! Core options, shown in the frontend's "Core Options" menu.
+//!
+//! Values use the libretro `"Description; default|other|..."` convention.
+
+use std::ffi::CStr;
+
+use gte_core::color_map::Palette;
+use gte_core::emulator::{AcpAccuracy, ACP_CLOCK_RATIO};
+use libretro_rs::ffi::retro_variable;
+use libretro_rs::prelude::GameGeometry;
+use libretro_rs::prelude::env::{Run, SetEnvironment};
+
+const PALETTE: &CStr = c"gametank_palette";
+const OVERSCAN: &CStr = c"gametank_overscan";
+const INTEGER_SCALING: &CStr = c"gametank_integer_scaling";
+const ACP_ACCURACY: &CStr = c"gametank_acp_accuracy";
+const ACP_CLOCK: &CStr = c"gametank_acp_clock";
+
+/// Rows hidden at the top and bottom when overscan is masked. Matches the
+/// SDK's `draw_letterbox`, which also blanks the rightmost column.
+const OVERSCAN_ROWS: usize = 10;
+
+/// Aspect ratio of the picture on a TV, stretched wider than it's tall
+const TV_ASPECT_RATIO: f32 = 4.0 / 3.0;
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct CoreOptions {
+    pub palette: Palette,
+    /// Hide what a TV's overscan would, as games are designed to expect
+    pub mask_overscan: bool,
+    /// Report square pixels, so the frontend's integer scaling keeps them
+    /// even, rather than stretching to a TV's 4:3
+    pub integer_scaling: bool,
+    pub acp_accuracy: AcpAccuracy,
+    /// Only used when `acp_accuracy` is [`AcpAccuracy::Fast`]
+    pub acp_clock_ratio: i32,
+}
+
+impl Default for CoreOptions {
+    fn default() -> Self {
+        Self {
+            palette: Palette::Measured,
+            mask_overscan: false,
+            integer_scaling: true,
+            acp_accuracy: AcpAccuracy::Accurate,
+            acp_clock_ratio: ACP_CLOCK_RATIO,
+        }
+    }
+}
+
+/// Tell the frontend which options exist
+pub fn declare(env: &mut impl SetEnvironment) {
+    let variables = [
+        retro_variable {
+            key: PALETTE.as_ptr(),
+            value: c"Palette; Measured|Perceptual|Legacy|Grayscale".as_ptr(),
+        },
+        retro_variable {
+            key: OVERSCAN.as_ptr(),
+            value: c"Mask overscan; disabled|enabled".as_ptr(),
+        },
+        retro_variable {
+            key: INTEGER_SCALING.as_ptr(),
+            value: c"Square pixels for integer scaling (off stretches to 4:3); enabled|disabled".as_ptr(),
+        },
+        retro_variable {
+            key: ACP_ACCURACY.as_ptr(),
+            value: c"Audio coprocessor timing; accurate|fast".as_ptr(),
+        },
+        retro_variable {
+            key: ACP_CLOCK.as_ptr(),
+            value: c"Audio coprocessor clock (fast timing); 4x CPU|2x CPU|1x CPU (fastest)".as_ptr(),
+        },
+        retro_variable {
+            key: std::ptr::null(),
+            value: std::ptr::null(),
+        },
+    ];
+
+    let _ = env.set_variables(&variables);
+}
+
+impl CoreOptions {
+    /// Read the current values, falling back to defaults for anything the
+    /// frontend doesn't report
+    pub fn read(env: &impl Run) -> Self {
+        let get = |key: &CStr| env.get_variable(key).ok().map(|v| v.as_str().to_string());
+        let default = Self::default();
+
+        let palette = match get(PALETTE).as_deref() {
+            Some(v) => Palette::from_name(v).unwrap_or_default(),
+            None => default.palette,
+        };
+
+        let mask_overscan = match get(OVERSCAN).as_deref() {
+            Some(v) => v == "enabled",
+            None => default.mask_overscan,
+        };
+
+        let integer_scaling = match get(INTEGER_SCALING).as_deref() {
+            Some(v) => v == "enabled",
+            None => default.integer_scaling,
+        };
+
+        let acp_accuracy = match get(ACP_ACCURACY).as_deref() {
+            Some("fast") => AcpAccuracy::Fast,
+            Some(_) => AcpAccuracy::Accurate,
+            None => default.acp_accuracy,
+        };
+
+        let acp_clock_ratio = match get(ACP_CLOCK).as_deref() {
+            Some(v) if v.starts_with("2x") => 2,
+            Some(v) if v.starts_with("1x") => 1,
+            Some(_) => ACP_CLOCK_RATIO,
+            None => default.acp_clock_ratio,
+        };
+
+        Self { palette, mask_overscan, integer_scaling, acp_accuracy, acp_clock_ratio }
+    }
+
+    /// The picture's geometry: 128x128, with square pixels or stretched
+    pub fn geometry(&self) -> GameGeometry {
+        let aspect_ratio = if self.integer_scaling { 1.0 } else { TV_ASPECT_RATIO };
+        GameGeometry { aspect_ratio, ..GameGeometry::fixed(128, 128) }
+    }
+
+    /// Whether a framebuffer pixel is hidden by the overscan mask
+    pub fn is_masked(&self, x: usize, y: usize) -> bool {
+        self.mask_overscan && (x == 127 || !(OVERSCAN_ROWS..128 - OVERSCAN_ROWS).contains(&y))
+    }
+}
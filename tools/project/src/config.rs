@@ -0,0 +1,492 @@
+//! Project configuration
+//!
+//! Settings are read from `gtrom.toml` in the project root. Every field is
+//! optional, and a project without the file gets the defaults below.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+pub const CONFIG_FILE: &str = "gtrom.toml";
+
+/// Written by `gtrom configure --init`
+pub const DEFAULT_CONFIG: &str = r#"# gtrom project configuration. Every setting is optional.
+
+# Where llvm-mos comes from: "rustup-mos" (a local `cargo +mos`), "llvm-mos"
+# (llvm-mos and a mos-capable cargo on PATH), "podman" or "docker". "auto"
+# tries them in that order on the next build and saves the first that works.
+toolchain = "auto"
+
+[container]
+image = "docker.io/dwbrite/rust-mos:gte"
+name = "gametank"
+
+[build]
+# "release", "debug", or a [profile.<name>] below; `gtrom build --profile`
+# (or --release/--debug) overrides this
+profile = "release"
+# ROM path relative to the project root (defaults to <crate name>.gtr)
+# output = "my-game.gtr"
+# cargo's target directory, shared between projects so the SDK and core
+# aren't compiled again for each one. Relative to the project root, or ~/;
+# mounted into the build container. Unset builds in each ROM's target/.
+# target-dir = "~/.cache/gametank/target"
+
+[assets]
+# Directories (relative to the project root) that `gtrom build` converts
+# into target/assets and `gtrom build --watch` watches
+dirs = ["assets"]
+
+[asm]
+# Searched by .include and .incbin in src/asm (relative to the ROM crate),
+# besides the including file's directory
+include = []
+# Symbols defined for every .asm, .s and .S file, like --defsym
+# defines = { PAL = 0, MAX_SPRITES = 16 }
+
+# A workspace with more than one ROM (say the game, test carts and demos)
+# lists each crate. `gtrom build --rom <name>` builds one, `--all` builds
+# every one, and otherwise gtrom builds the ROM the current directory is in,
+# or the first. Each ROM goes to <name>.gtr unless it sets `output`.
+# [[rom]]
+# name = "game"
+# path = "rom"
+#
+# [[rom]]
+# name = "test-cart"
+# path = "carts/test"
+# output = "build/test-cart.gtr"
+
+# Named build profiles. "size" (opt-level "z") and "speed" (opt-level 2) are
+# built in, and settings here override theirs one by one; any other name
+# makes a new profile. Each is passed to cargo as a profile of the same name,
+# so its build goes in target/mos-unknown-none/<name>.
+# [profile.speed]
+# inherits = "release"     # what unset settings come from: "release" or "debug"
+# opt-level = 2            # 0-3, "s" or "z"
+# lto = "fat"              # true, false, "fat", "thin" or "off"
+# codegen-units = 1
+# panic = "abort"
+# inline-threshold = 300   # LLVM's inlining threshold; lower makes smaller code
+
+[hooks]
+# Commands run during `gtrom build`, from the ROM crate's directory, each a
+# string or a list run in order. pre-build runs before assets are converted,
+# post-link once cargo has linked the ELF, and post-rom once the .gtr is
+# written. They get GTROM_ROM, GTROM_ELF, GTROM_TARGET_DIR, GTROM_ROM_DIR,
+# GTROM_PROJECT_DIR, GTROM_ROM_NAME and GTROM_PROFILE, and a failing command
+# fails the build.
+# pre-build = "python3 tools/gen_tables.py"
+# post-link = []
+# post-rom = ["tools/stamp-checksum.sh \"$GTROM_ROM\""]
+# Run them in the build container rather than on the host (podman and docker)
+# in-container = false
+
+[size]
+# Highest percentage of each memory region `gtrom build --size-report` allows
+# (zero page is checked on every build)
+zp = 90
+ram = 90
+fixed = 100
+bank = 100
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Toolchain {
+    /// Pick one on the next build, and record it with [`record_toolchain`]
+    #[default]
+    Auto,
+    /// llvm-mos installed on the host as the `mos` rustup toolchain
+    RustupMos,
+    /// llvm-mos tools on PATH, and a `cargo` that targets mos without `+mos`
+    LlvmMos,
+    Podman,
+    Docker,
+}
+
+impl Toolchain {
+    pub fn name(self) -> &'static str {
+        match self {
+            Toolchain::Auto => "auto",
+            Toolchain::RustupMos => "rustup-mos",
+            Toolchain::LlvmMos => "llvm-mos",
+            Toolchain::Podman => "podman",
+            Toolchain::Docker => "docker",
+        }
+    }
+
+    /// Whether builds run inside a podman or docker container
+    pub fn is_container(self) -> bool {
+        matches!(self, Toolchain::Podman | Toolchain::Docker)
+    }
+
+    /// Arguments to put before cargo's subcommand
+    pub fn cargo_prefix(self) -> &'static [&'static str] {
+        match self {
+            Toolchain::LlvmMos => &[],
+            _ => &["+mos"],
+        }
+    }
+}
+
+/// A build profile: "release", "debug", or one named in `[profile.<name>]`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub struct Profile(String);
+
+impl Profile {
+    pub fn release() -> Self {
+        Self("release".to_string())
+    }
+
+    pub fn debug() -> Self {
+        Self("debug".to_string())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.0 == "debug"
+    }
+
+    /// Name of the cargo profile directory under target/
+    pub fn dir_name(&self) -> &str {
+        &self.0
+    }
+
+    /// What cargo calls it; the debug profile is `dev`
+    pub fn cargo_name(&self) -> &str {
+        if self.is_debug() { "dev" } else { &self.0 }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::release()
+    }
+}
+
+impl From<String> for Profile {
+    fn from(name: String) -> Self {
+        Self(name)
+    }
+}
+
+/// `[profile.<name>]`: code generation settings, passed to cargo as
+/// `--config profile.<name>.*`. Anything unset comes from `inherits`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ProfileConfig {
+    /// "release" or "debug", for profiles other than those two
+    pub inherits: Option<String>,
+    /// 0-3, "s" or "z"
+    pub opt_level: Option<toml::Value>,
+    /// true, false, "fat", "thin" or "off"
+    pub lto: Option<toml::Value>,
+    pub codegen_units: Option<u32>,
+    /// "abort" or "unwind"
+    pub panic: Option<String>,
+    /// LLVM's inlining threshold, passed as `-C llvm-args`
+    pub inline_threshold: Option<u32>,
+}
+
+impl ProfileConfig {
+    /// Built-in settings for `name`, if it has any
+    fn built_in(name: &str) -> Option<Self> {
+        let opt_level = match name {
+            "size" => toml::Value::String("z".to_string()),
+            "speed" => toml::Value::Integer(2),
+            _ => return None,
+        };
+        Some(Self {
+            inherits: Some("release".to_string()),
+            opt_level: Some(opt_level),
+            lto: Some(toml::Value::String("fat".to_string())),
+            codegen_units: Some(1),
+            panic: Some("abort".to_string()),
+            inline_threshold: None,
+        })
+    }
+
+    /// These settings, with `base` filling in the ones that aren't set
+    fn or(self, base: Self) -> Self {
+        Self {
+            inherits: self.inherits.or(base.inherits),
+            opt_level: self.opt_level.or(base.opt_level),
+            lto: self.lto.or(base.lto),
+            codegen_units: self.codegen_units.or(base.codegen_units),
+            panic: self.panic.or(base.panic),
+            inline_threshold: self.inline_threshold.or(base.inline_threshold),
+        }
+    }
+
+    /// `--config` values for cargo, as `profile.<name>.<key>=<value>`
+    pub fn cargo_configs(&self, profile: &Profile) -> Vec<String> {
+        let key = |setting: &str| format!("profile.{}.{}", profile.cargo_name(), setting);
+        let mut configs = vec![];
+        if let Some(inherits) = &self.inherits {
+            configs.push(format!("{}={:?}", key("inherits"), inherits));
+        }
+        if let Some(opt_level) = &self.opt_level {
+            configs.push(format!("{}={}", key("opt-level"), opt_level));
+        }
+        if let Some(lto) = &self.lto {
+            configs.push(format!("{}={}", key("lto"), lto));
+        }
+        if let Some(units) = self.codegen_units {
+            configs.push(format!("{}={}", key("codegen-units"), units));
+        }
+        if let Some(panic) = &self.panic {
+            configs.push(format!("{}={:?}", key("panic"), panic));
+        }
+        configs
+    }
+
+    /// Extra rustflags for the ROM target
+    pub fn rustflags(&self) -> Vec<String> {
+        self.inline_threshold.iter()
+            .map(|threshold| format!("-Cllvm-args=-inline-threshold={}", threshold))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContainerConfig {
+    pub image: String,
+    pub name: String,
+}
+
+impl Default for ContainerConfig {
+    fn default() -> Self {
+        Self {
+            image: "docker.io/dwbrite/rust-mos:gte".to_string(),
+            name: "gametank".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BuildConfig {
+    pub profile: Profile,
+    pub output: Option<String>,
+    /// cargo's target directory, shared between projects
+    pub target_dir: Option<String>,
+}
+
+/// `[asm]`: extra llvm-mc flags for `src/asm`, for code ported from other
+/// 6502 assemblers
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AsmConfig {
+    /// Directories searched by `.include` and `.incbin`, relative to the
+    /// ROM crate, passed as `-I`
+    pub include: Vec<String>,
+    /// Symbols passed as `--defsym NAME=VALUE`
+    pub defines: BTreeMap<String, i64>,
+}
+
+impl AsmConfig {
+    /// The flags for llvm-mc
+    pub fn args(&self) -> Vec<String> {
+        let includes = self.include.iter().map(|dir| format!("-I{}", dir));
+        let defines = self.defines.iter().map(|(name, value)| format!("--defsym={}={}", name, value));
+        includes.chain(defines).collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssetConfig {
+    pub dirs: Vec<String>,
+}
+
+impl Default for AssetConfig {
+    fn default() -> Self {
+        Self { dirs: vec!["assets".to_string()] }
+    }
+}
+
+/// One hook's commands: a single string or a list in gtrom.toml
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "OneOrMany")]
+pub struct HookCommands(Vec<String>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<OneOrMany> for HookCommands {
+    fn from(commands: OneOrMany) -> Self {
+        match commands {
+            OneOrMany::One(command) => Self(vec![command]),
+            OneOrMany::Many(commands) => Self(commands),
+        }
+    }
+}
+
+impl HookCommands {
+    pub fn commands(&self) -> &[String] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// `[hooks]`: shell commands run at points in the build
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HooksConfig {
+    /// Before assets are converted
+    pub pre_build: HookCommands,
+    /// After cargo links the ELF, before it becomes a .gtr
+    pub post_link: HookCommands,
+    /// After the .gtr is written and checked
+    pub post_rom: HookCommands,
+    /// Run in the build container when building through one
+    pub in_container: bool,
+}
+
+/// Usage limits checked by `gtrom build --size-report`, in percent
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SizeConfig {
+    /// Checked on every build, not just with `--size-report`
+    pub zp: u8,
+    /// Leave some RAM for the soft stack
+    pub ram: u8,
+    pub fixed: u8,
+    pub bank: u8,
+}
+
+impl Default for SizeConfig {
+    fn default() -> Self {
+        Self { zp: 90, ram: 90, fixed: 100, bank: 100 }
+    }
+}
+
+/// One ROM crate in a multi-ROM workspace
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RomConfig {
+    pub name: String,
+    /// The crate's directory, relative to the workspace root
+    pub path: String,
+    /// ROM path relative to the workspace root (defaults to <name>.gtr)
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub toolchain: Toolchain,
+    pub container: ContainerConfig,
+    pub build: BuildConfig,
+    pub assets: AssetConfig,
+    pub asm: AsmConfig,
+    pub size: SizeConfig,
+    pub hooks: HooksConfig,
+    /// `[profile.<name>]` tables
+    #[serde(rename = "profile")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// `[[rom]]` tables; empty for a project with a single ROM
+    #[serde(rename = "rom")]
+    pub roms: Vec<RomConfig>,
+}
+
+impl Config {
+    /// Load `gtrom.toml` from the project root, or the defaults if there isn't one
+    pub fn load(project_dir: &Path) -> Result<Self, String> {
+        let path = project_dir.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    /// Whether gtrom.toml lists the project's ROMs
+    pub fn is_workspace(&self) -> bool {
+        !self.roms.is_empty()
+    }
+
+    /// Settings for building with `profile`, from gtrom.toml and the
+    /// built-in profiles
+    pub fn profile_config(&self, profile: &Profile) -> Result<ProfileConfig, String> {
+        let name = profile.name();
+        let built_in = ProfileConfig::built_in(name);
+        let settings = match (self.profiles.get(name).cloned(), built_in) {
+            (Some(settings), Some(built_in)) => settings.or(built_in),
+            (Some(settings), None) => settings,
+            (None, Some(built_in)) => built_in,
+            (None, None) if name == "release" || profile.is_debug() => ProfileConfig::default(),
+            (None, None) => return Err(format!("There's no profile named {}; add [profile.{}] to {}", name, name, CONFIG_FILE)),
+        };
+
+        match (name, settings.inherits.as_deref()) {
+            ("release" | "debug", None) => Ok(settings),
+            ("release" | "debug", Some(_)) => Err(format!("[profile.{}] in {} can't set inherits", name, CONFIG_FILE)),
+            (_, None) => Ok(ProfileConfig { inherits: Some("release".to_string()), ..settings }),
+            (_, Some("release")) => Ok(settings),
+            (_, Some("debug")) => Ok(ProfileConfig { inherits: Some("dev".to_string()), ..settings }),
+            (_, Some(other)) => Err(format!("[profile.{}] in {} inherits {}, but it has to be \"release\" or \"debug\"", name, CONFIG_FILE, other)),
+        }
+    }
+
+    /// Where the built ROM goes
+    pub fn output_path(&self, project_dir: &Path, crate_name: &str) -> PathBuf {
+        match &self.build.output {
+            Some(output) => project_dir.join(output),
+            None => project_dir.join(format!("{}.gtr", crate_name)),
+        }
+    }
+
+    /// The shared cargo target directory from `[build] target-dir`, if set:
+    /// `~/` is the home directory, and relative paths start at the project root
+    pub fn target_dir(&self, project_dir: &Path) -> Option<PathBuf> {
+        let dir = self.build.target_dir.as_deref()?;
+        let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+        match (dir.strip_prefix("~/").or(dir.strip_prefix("~\\")), home) {
+            (Some(rest), Some(home)) => Some(PathBuf::from(home).join(rest)),
+            _ => Some(project_dir.join(dir)),
+        }
+    }
+}
+
+/// Set `toolchain` in gtrom.toml, creating the file if there isn't one.
+/// Only that line changes; comments and other settings are kept.
+pub fn record_toolchain(project_dir: &Path, toolchain: Toolchain) -> Result<(), String> {
+    let path = project_dir.join(CONFIG_FILE);
+    let content = if path.exists() {
+        std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+    } else {
+        DEFAULT_CONFIG.to_string()
+    };
+
+    let setting = format!("toolchain = \"{}\"", toolchain.name());
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    // top-level keys have to come before the first table
+    let top_level = lines.iter().position(|l| l.trim_start().starts_with('[')).unwrap_or(lines.len());
+
+    match lines[..top_level].iter().position(|l| l.trim_start().starts_with("toolchain")) {
+        Some(i) => lines[i] = setting,
+        None => lines.insert(top_level, format!("{}\n", setting)),
+    }
+
+    std::fs::write(&path, lines.join("\n") + "\n")
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
@@ -0,0 +1,165 @@
+//! GameTank projects
+//!
+//! A project is a directory holding a ROM crate, either in a `rom/`
+//! subdirectory or at the top, or a workspace whose `gtrom.toml` lists
+//! several. `gtrom` finds the one it's run in, and gtgo opens one to scope
+//! its screens to; both go through this crate so they agree on what and
+//! where a project is.
+//!
+//! - [`config`]: the project's `gtrom.toml`
+//! - [`manifest`]: the ROM's `assets.toml`
+
+pub mod config;
+pub mod manifest;
+
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, CONFIG_FILE};
+
+/// Check if a directory is a GameTank ROM project
+/// A GameTank project has Cargo.toml and either:
+/// - src/asm/ directory (unique to GameTank projects)
+/// - asset-macros/ directory
+/// - sdk/ subdirectory (when sdk is a separate crate)
+/// - gametank-asset-macros or sdk dependency in Cargo.toml
+pub fn is_gametank_project(dir: &Path) -> bool {
+    if !dir.join("Cargo.toml").exists() {
+        return false;
+    }
+
+    // Check for unique GameTank markers
+    if dir.join("src/asm").exists() {
+        return true;
+    }
+
+    if dir.join("asset-macros").exists() {
+        return true;
+    }
+
+    // Check for sdk/ subdirectory (when sdk is a separate crate)
+    if dir.join("sdk").exists() && dir.join("sdk/Cargo.toml").exists() {
+        return true;
+    }
+
+    // Check Cargo.toml for gametank dependencies
+    if let Ok(cargo_content) = std::fs::read_to_string(dir.join("Cargo.toml")) {
+        if cargo_content.contains("gametank-asset-macros")
+            || cargo_content.contains("gametank-sdk")
+            || (cargo_content.contains("sdk") && cargo_content.contains("path = \"sdk\"")) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// The ROM crate of a project rooted at `dir`: its `rom/` subdirectory, or
+/// `dir` itself
+pub fn rom_dir_in(dir: &Path) -> Option<PathBuf> {
+    let rom_dir = dir.join("rom");
+    if is_gametank_project(&rom_dir) {
+        return Some(rom_dir);
+    }
+    is_gametank_project(dir).then(|| dir.to_path_buf())
+}
+
+/// The project `start` is in, walking up from it: the project root and its
+/// ROM directory
+pub fn find_rom_dir_from(start: &Path) -> Result<(PathBuf, PathBuf), String> {
+    start.ancestors()
+        .find_map(|dir| rom_dir_in(dir).map(|rom_dir| (dir.to_path_buf(), rom_dir)))
+        .ok_or_else(|| "Could not find ROM project (no rom/ dir or GameTank project found)".to_string())
+}
+
+/// Get crate name from Cargo.toml in the given directory
+pub fn get_crate_name(dir: &Path) -> Result<String, String> {
+    let cargo_toml_path = dir.join("Cargo.toml");
+    let cargo_content = std::fs::read_to_string(&cargo_toml_path)
+        .map_err(|e| format!("Failed to read Cargo.toml: {}", e))?;
+    parse_crate_name(&cargo_content)
+}
+
+/// Parse crate name from Cargo.toml content
+pub fn parse_crate_name(content: &str) -> Result<String, String> {
+    content.lines()
+        .find(|l| l.trim().starts_with("name"))
+        .and_then(|l| l.split('=').nth(1))
+        .map(|s| s.trim().trim_matches('"').to_string())
+        .ok_or_else(|| "Could not find crate name in Cargo.toml".to_string())
+}
+
+/// Get the first author from Cargo.toml in the given directory, without the email
+pub fn get_crate_author(dir: &Path) -> Option<String> {
+    let cargo_content = std::fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    cargo_content.lines()
+        .find(|l| l.trim().starts_with("authors"))
+        .and_then(|l| l.split('"').nth(1))
+        .map(|s| s.split('<').next().unwrap_or(s).trim().to_string())
+}
+
+/// A ROM crate to build, and where its `.gtr` goes
+#[derive(Debug, Clone)]
+pub struct RomTarget {
+    /// The `[[rom]]` name in a workspace, or the crate name
+    pub name: String,
+    pub dir: PathBuf,
+    pub output: PathBuf,
+}
+
+/// The nearest directory at or above `start` whose gtrom.toml has `[[rom]]`
+/// tables
+pub fn find_workspace_from(start: &Path) -> Result<Option<(PathBuf, Config)>, String> {
+    for dir in start.ancestors() {
+        if dir.join(CONFIG_FILE).exists() {
+            let config = Config::load(dir)?;
+            if config.is_workspace() {
+                return Ok(Some((dir.to_path_buf(), config)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Find the project root, its settings, and the ROMs to build.
+///
+/// In a workspace, `name` picks a ROM by its `[[rom]]` name and `all` picks
+/// every one; with neither, it's the ROM `start` is in, or the first one
+/// listed. Without a workspace there's just the one ROM from
+/// [`find_rom_dir_from`].
+pub fn find_roms_from(start: &Path, name: Option<&str>, all: bool) -> Result<(PathBuf, Config, Vec<RomTarget>), String> {
+    let Some((working_dir, config)) = find_workspace_from(start)? else {
+        let (working_dir, rom_dir) = find_rom_dir_from(start)?;
+        let config = Config::load(&working_dir)?;
+        let crate_name = get_crate_name(&rom_dir)?;
+        if let Some(name) = name.filter(|n| *n != crate_name) {
+            return Err(format!("There's no ROM named {}; list a workspace's ROMs as [[rom]] in {}", name, CONFIG_FILE));
+        }
+        let output = config.output_path(&working_dir, &crate_name);
+        return Ok((working_dir, config, vec![RomTarget { name: crate_name, dir: rom_dir, output }]));
+    };
+
+    let mut roms: Vec<RomTarget> = config.roms.iter()
+        .map(|rom| RomTarget {
+            name: rom.name.clone(),
+            dir: working_dir.join(&rom.path),
+            output: working_dir.join(rom.output.clone().unwrap_or_else(|| format!("{}.gtr", rom.name))),
+        })
+        .collect();
+
+    if let Some(rom) = roms.iter().find(|rom| !is_gametank_project(&rom.dir)) {
+        return Err(format!("ROM {} in {}: {} isn't a GameTank ROM crate", rom.name, CONFIG_FILE, rom.dir.display()));
+    }
+
+    if !all {
+        let index = match name {
+            Some(name) => roms.iter().position(|rom| rom.name == name).ok_or_else(|| {
+                let names: Vec<&str> = roms.iter().map(|rom| rom.name.as_str()).collect();
+                format!("There's no ROM named {} (the workspace has {})", name, names.join(", "))
+            })?,
+            None => roms.iter().position(|rom| start.starts_with(&rom.dir)).unwrap_or(0),
+        };
+        roms = vec![roms.swap_remove(index)];
+    }
+
+    Ok((working_dir, config, roms))
+}
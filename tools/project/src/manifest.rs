@@ -0,0 +1,124 @@
+//! The asset manifest
+//!
+//! `assets.toml` in the ROM dir lists assets by name, with their conversion
+//! settings:
+//!
+//! ```toml
+//! [[asset]]
+//! name = "player_sprite"
+//! path = "assets/sprites/player.png"
+//! dither = "floyd-steinberg"
+//!
+//! [[asset]]
+//! name = "jump"
+//! path = "assets/sfx/jump.wav"
+//! ```
+//!
+//! `gtrom build` converts and packs them into ROM banks; see its `bundle`
+//! module.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+pub const MANIFEST_FILE: &str = "assets.toml";
+
+/// Flash bank `gametank::save` keeps saves in
+pub const SAVE_BANK: u8 = 63;
+
+/// Dithering applied while quantizing to the GameTank palette
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Dither {
+    /// Nearest color only
+    None,
+    /// Floyd-Steinberg error diffusion
+    FloydSteinberg,
+    /// 4x4 Bayer ordered dithering
+    Ordered,
+}
+
+/// How to convert an asset
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    /// A PNG, as a `SpriteAsset`
+    Sprite,
+    /// A WAV, as a `SampleAsset`
+    Sample,
+    /// A WAV, as a `WavetableAsset`
+    Wavetable,
+    /// Any file, as-is, as a `BankedSlice<u8>`
+    Data,
+}
+
+impl Kind {
+    /// What an asset is when `kind` isn't given
+    pub fn of(path: &Path) -> Self {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        match ext.as_str() {
+            "png" => Self::Sprite,
+            "wav" => Self::Sample,
+            _ => Self::Data,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AssetEntry {
+    /// Becomes the constant's name in SCREAMING_SNAKE_CASE
+    pub name: String,
+    /// Relative to the ROM dir
+    pub path: String,
+    /// Guessed from the extension if left out
+    pub kind: Option<Kind>,
+    /// Sprites only
+    pub dither: Option<Dither>,
+    /// Samples only, in Hz
+    pub rate: Option<u32>,
+    /// Store it compressed, for `gametank::compress`. Sprites and data only.
+    #[serde(default)]
+    pub compress: bool,
+}
+
+impl AssetEntry {
+    /// What the asset is, from `kind` or its extension
+    pub fn kind(&self) -> Kind {
+        self.kind.unwrap_or_else(|| Kind::of(Path::new(&self.path)))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Banks the assets may go in. The game's own `.rodata.bankN` sections
+    /// should stay out of this range.
+    #[serde(default)]
+    pub first_bank: u8,
+    #[serde(default = "default_last_bank")]
+    pub last_bank: u8,
+    #[serde(default, rename = "asset")]
+    pub assets: Vec<AssetEntry>,
+}
+
+/// Stay below the save bank unless told otherwise
+fn default_last_bank() -> u8 {
+    SAVE_BANK - 1
+}
+
+impl Manifest {
+    /// Load the ROM's `assets.toml`. `None` if there isn't one.
+    pub fn load(rom_dir: &Path) -> Result<Option<Self>, String> {
+        let path = rom_dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+}
@@ -1,6 +1,7 @@
 use std::cell::{Cell, OnceCell};
 use std::fs::File;
 use std::io::Read;
+use std::path::Path;
 use std::sync::Arc;
 use egui::{epaint, vec2, Align, Button, Color32, Frame, Id, LayerId, Layout, Pos2, Rect, ResizeDirection, ScrollArea, TextureOptions, Ui, UiBuilder, Vec2, ViewportCommand};
 use egui_wgpu::ScreenDescriptor;
@@ -10,17 +11,57 @@ use winit::application::ApplicationHandler;
 use winit::dpi::LogicalSize;
 use winit::event::{ElementState, KeyEvent, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
-use winit::window::{Window, WindowId};
-use crate::app_ui::gametankboy::GameTankBoyUI;
+use winit::window::{Fullscreen, Window, WindowId};
+use crate::app_ui::gametankboy::{GameTankBoyUI, DEFAULT_SCALE};
 use crate::app_ui::ram_inspector::MemoryInspector;
 use crate::app_ui::vram_viewer::{VRAMViewer, VRAMViewerLayout};
 use crate::app_uninit::App;
 use gte_core::color_map::{COLOR_MAP, COLOR_MAP_PERCEPTUALLY_AUTOMAPPED, COLOR_MAP_WRONG};
 use crate::egui_renderer::EguiRenderer;
-use gte_core::emulator::{Emulator, HEIGHT, WIDTH};
+use gte_core::emulator::{next_speed, Emulator, HEIGHT, REWIND_FRAMES, WIDTH};
+use gte_core::elf_rom::is_elf;
+use gte_core::rom_header::RomHeader;
 use crate::graphics::GraphicsContext;
 use crate::audio::GameTankAudio; // <--- added
+#[cfg(not(target_arch = "wasm32"))]
+use crate::reload::RomReloader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::gdb_server::GdbServer;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::capture::Capturer;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::app_ui::netplay::NetplayPanel;
+
+/// What can be dropped on the window to load it, besides an ELF from cargo
+/// which usually has no extension
+const ROM_EXTENSIONS: &[&str] = &["gtr", "bin", "rom", "elf"];
+
+/// The first argument that isn't a flag or the path after `--trace`
+fn rom_path_from_args() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--trace" {
+            args.next();
+        } else if !arg.starts_with("--") {
+            return Some(arg);
+        }
+    }
+    None
+}
 
+/// Name the window after the ROM that's running
+/// Title the window after the ROM's header, or its file name without one
+fn set_title(window: &Window, rom: Option<&Path>, info: Option<&RomHeader>) {
+    let name = match info.filter(|info| !info.title.is_empty()) {
+        Some(info) if info.rom_version.is_empty() => Some(info.title.clone()),
+        Some(info) => Some(format!("{} {}", info.title, info.rom_version)),
+        None => rom.and_then(Path::file_name).map(|name| name.to_string_lossy().to_string()),
+    };
+    match name {
+        Some(name) => window.set_title(&format!("{} - GameTank: The Emulator!", name)),
+        None => window.set_title("GameTank: The Emulator!"),
+    }
+}
 
 pub struct AppInitialized {
     pub emulator: Emulator<InstantClock>,
@@ -37,8 +78,28 @@ pub struct AppInitialized {
     show_left_pane: bool,
     show_right_pane: bool,
     show_bottom_pane: bool,
+    /// The panel bar and panels, toggled with F1 to leave just the console
+    show_debug_ui: bool,
+    /// Whether there's a ROM to run, or just a hint to drop one
+    rom_loaded: bool,
 
     audio: Option<GameTankAudio>,
+
+    /// Set by `--watch`, to load the ROM again whenever it's rebuilt
+    #[cfg(not(target_arch = "wasm32"))]
+    reloader: Option<RomReloader>,
+
+    /// Set by `--gdb`, for attaching gdb or lldb
+    #[cfg(not(target_arch = "wasm32"))]
+    gdb: Option<GdbServer>,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    capturer: Capturer,
+
+    /// Netplay through the link port, from the right panel or `--link-host`
+    /// and `--link-join`
+    #[cfg(not(target_arch = "wasm32"))]
+    netplay: NetplayPanel,
 }
 
 impl From<&mut App> for AppInitialized {
@@ -64,16 +125,41 @@ impl From<&mut App> for AppInitialized {
         input_bindings.insert(keyboard::Key::Character(SmolStr::new("z")), Controller1(ControllerButton::A));
         input_bindings.insert(keyboard::Key::Character(SmolStr::new("x")), Controller1(ControllerButton::B));
         input_bindings.insert(keyboard::Key::Character(SmolStr::new("c")), Controller1(ControllerButton::C));
+        input_bindings.insert(keyboard::Key::Named(Backspace), InputCommand::Rewind);
+        input_bindings.insert(keyboard::Key::Named(Tab), InputCommand::FastForward);
+        input_bindings.insert(keyboard::Key::Character(SmolStr::new("p")), InputCommand::PlayPause);
+        input_bindings.insert(keyboard::Key::Character(SmolStr::new(".")), InputCommand::FrameAdvance);
+
+        emulator.enable_rewind(REWIND_FRAMES);
 
-        if let Some(filename) = std::env::args().nth(1) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let gdb = GdbServer::port_from_args().and_then(|port| {
+            GdbServer::bind(port).map_err(|e| error!("{}", e)).ok()
+        });
+        #[cfg(not(target_arch = "wasm32"))]
+        let wait_for_debugger = gdb.is_some();
+        #[cfg(target_arch = "wasm32")]
+        let wait_for_debugger = false;
+
+        let rom_path = rom_path_from_args();
+        let mut rom_loaded = false;
+        if let Some(filename) = &rom_path {
             if let Ok(data) = std::fs::read(filename) {
                 emulator.load_rom(&data);
-                emulator.play_state = Playing;
+                rom_loaded = true;
+                // with --gdb, hold at reset so breakpoints can be set first
+                emulator.play_state = if wait_for_debugger { Paused } else { Playing };
+                set_title(&window, Some(Path::new(filename)), emulator.rom_info());
             } else {
                 error!("couldn't open provided file");
             }
         }
 
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut capturer = Capturer::new(rom_path.as_deref());
+        #[cfg(not(target_arch = "wasm32"))]
+        capturer.start_trace(&mut emulator);
+
         // Create audio bridge if emulator already has audio_out (don't take or clone the ring endpoints)
         let audio_bridge = if emulator.audio_out.is_some() {
             Some(GameTankAudio::new())
@@ -93,7 +179,19 @@ impl From<&mut App> for AppInitialized {
             show_left_pane: false,
             show_right_pane: false,
             show_bottom_pane: false,
+            show_debug_ui: true,
+            rom_loaded,
             audio: audio_bridge,
+            #[cfg(not(target_arch = "wasm32"))]
+            reloader: rom_path
+                .filter(|_| std::env::args().any(|arg| arg == "--watch"))
+                .map(|path| RomReloader::new(path.into())),
+            #[cfg(not(target_arch = "wasm32"))]
+            gdb,
+            #[cfg(not(target_arch = "wasm32"))]
+            capturer,
+            #[cfg(not(target_arch = "wasm32"))]
+            netplay: NetplayPanel::from_args(),
         }
     }
 }
@@ -127,7 +225,7 @@ impl AppInitialized {
         };
 
         #[cfg(not(target_arch = "wasm32"))]
-        {
+        if self.show_debug_ui {
             egui::TopBottomPanel::bottom("bottom_pane_2").resizable(false).show_separator_line(true).show_animated(self.egui_renderer.context(), self.show_bottom_pane, |ui| {
                 ui.vertical(|ui| {
                     ui.vertical_centered(|ui| {
@@ -143,6 +241,9 @@ impl AppInitialized {
                     ui.toggle_value(&mut self.show_left_pane, "show left panel");
                     ui.toggle_value(&mut self.show_bottom_pane, "show bottom panel");
                     ui.toggle_value(&mut self.show_right_pane, "show right panel");
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        ui.weak("P: pause  .: frame  Tab: fast-forward  -/=: speed  F1: hide UI  F11: fullscreen");
+                    });
                 });
             });
 
@@ -177,7 +278,7 @@ impl AppInitialized {
                                 ui.set_min_width(24.0);
                                 // ui.set_width(ui.available_width());
                                 ui.set_height(ui.available_height());
-                                ui.label("here's some gui shit");
+                                self.netplay.draw(ui, &mut self.emulator);
                             })
                         });
 
@@ -195,6 +296,9 @@ impl AppInitialized {
                 ui.set_height(ui.available_height());
                 self.console_gui.draw(ui, &mut self.emulator);
             });
+            if !self.rom_loaded {
+                ui.put(ui.max_rect(), egui::Label::new(egui::RichText::new("Drop a .gtr here").color(Color32::from_gray(160))));
+            }
         });
 
         self.egui_renderer.end_frame_and_draw(
@@ -226,6 +330,50 @@ impl AppInitialized {
     }
 
 
+    /// Load a ROM dropped on the window, and start it. `--watch` follows
+    /// the new file from then on.
+    fn load_dropped(&mut self, path: &Path) {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("Failed to read {}: {}", path.display(), e);
+                return;
+            }
+        };
+        if !ROM_EXTENSIONS.contains(&extension.as_str()) && !is_elf(&bytes) {
+            error!("{} isn't a ROM (.gtr, .bin, .rom or an ELF)", path.display());
+            return;
+        }
+
+        self.emulator.load_rom(&bytes);
+        self.emulator.play_state = Playing;
+        self.rom_loaded = true;
+        set_title(&self.window, Some(path), self.emulator.rom_info());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.capturer.set_rom(path.to_str());
+            if self.reloader.is_some() {
+                self.reloader = Some(RomReloader::new(path.to_path_buf()));
+            }
+        }
+        warn!("loaded {}", path.display());
+    }
+
+    /// Run a step slower or faster, for slow motion or skipping ahead
+    fn step_speed(&mut self, faster: bool) {
+        self.emulator.set_speed(next_speed(self.emulator.speed(), faster));
+        warn!("speed {}x", self.emulator.speed());
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let fullscreen = self.window.fullscreen().is_none();
+        self.window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+        // let the screen fill a monitor, rather than stopping at the windowed size
+        self.console_gui.desired_scale = if fullscreen { None } else { Some(DEFAULT_SCALE) };
+    }
+
     fn handle_resized(&mut self, width: u32, height: u32) {
         self.gc.surface_config.width = width;
         self.gc.surface_config.height = height;
@@ -241,7 +389,7 @@ use gte_core::inputs::InputCommand::Controller1;
 use wasm_bindgen::prelude::*;
 use winit::event::ElementState::Pressed;
 use winit::keyboard;
-use winit::keyboard::NamedKey::{ArrowDown, ArrowLeft, ArrowRight, ArrowUp, Enter};
+use winit::keyboard::NamedKey::{ArrowDown, ArrowLeft, ArrowRight, ArrowUp, Backspace, Enter, Tab};
 use winit::keyboard::SmolStr;
 use crate::app_delegation::InstantClock;
 
@@ -276,6 +424,9 @@ pub fn emulator_stop() {
 impl AppInitialized {
     pub fn process_cycles(&mut self) {
         self.emulator.process_cycles(false);
+        for message in self.emulator.take_debug_messages() {
+            warn!("rom: {}", message);
+        }
 
         // If emulator created audio after initialization, create the bridge.
         if self.audio.is_none() && self.emulator.audio_out.is_some() {
@@ -312,6 +463,8 @@ impl ApplicationHandler for AppInitialized {
         match event {
             WindowEvent::CloseRequested => {
                 println!("The close button was pressed; stopping");
+                #[cfg(not(target_arch = "wasm32"))]
+                self.capturer.finish_trace(&mut self.emulator);
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
@@ -322,6 +475,18 @@ impl ApplicationHandler for AppInitialized {
                 self.handle_resized(new_size.width, new_size.height);
             }
             WindowEvent::KeyboardInput { event, .. } => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if event.state == Pressed && !event.repeat {
+                    match event.logical_key {
+                        keyboard::Key::Named(keyboard::NamedKey::F12) => self.capturer.screenshot(&self.emulator),
+                        keyboard::Key::Named(keyboard::NamedKey::F10) => self.capturer.toggle(&mut self.emulator, "png"),
+                        keyboard::Key::Named(keyboard::NamedKey::F9) => self.capturer.toggle(&mut self.emulator, "gif"),
+                        keyboard::Key::Named(keyboard::NamedKey::F11) => self.toggle_fullscreen(),
+                        keyboard::Key::Named(keyboard::NamedKey::F1) => self.show_debug_ui = !self.show_debug_ui,
+                        keyboard::Key::Character(ref c) if c == "-" || c == "=" => self.step_speed(c == "="),
+                        _ => {}
+                    }
+                }
                 let KeyEvent {  logical_key,   state,  .. } = event;
                 if let Some(cmd) = self.input_bindings.get(&logical_key).copied() {
                     if let Some(ks) = self.emulator.input_state.get(&cmd) {
@@ -333,22 +498,7 @@ impl ApplicationHandler for AppInitialized {
             },
             WindowEvent::MouseInput { .. } => { self.emulator.wasm_init(); }
             WindowEvent::Touch(_) => { self.emulator.wasm_init(); }
-            WindowEvent::DroppedFile(path) => {
-                warn!("reading file from path...");
-                // check if filename ends in .gtr and load file into slice
-                let filename = path.file_name().unwrap().to_str().unwrap();
-                if !filename.ends_with(".gtr") {
-                    error!("not a valid gtr");
-                    return
-                }
-
-                let mut file = File::open(&path).unwrap();
-                let mut bytes = Vec::new();
-                file.read_to_end(&mut bytes).unwrap();
-
-                self.emulator.load_rom(bytes.as_slice());
-                warn!("successfully loaded {}", filename);
-            }
+            WindowEvent::DroppedFile(path) => self.load_dropped(&path),
             _ => (),
         }
     }
@@ -359,14 +509,30 @@ impl ApplicationHandler for AppInitialized {
             warn!("got rom data!");
             if !data.is_empty() {
                 self.emulator.load_rom(data);
+                self.rom_loaded = true;
             }
             self.emulator.play_state = Playing;
         }
 
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(data) = self.reloader.as_mut().and_then(RomReloader::poll) {
+            warn!("ROM changed on disk, reloading");
+            self.emulator.load_rom(&data);
+            self.emulator.play_state = Playing;
+        }
+
         if EMULATOR_STOP.with(|flag| flag.get()) {
             self.emulator.play_state = Paused;
         }
 
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(gdb) = &mut self.gdb {
+            gdb.poll(&mut self.emulator);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.netplay.poll(&mut self.emulator);
+
         if SHOULD_SHUTDOWN.with(|flag| flag.get()) {
             event_loop.exit();
         }
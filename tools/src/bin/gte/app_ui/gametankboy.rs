@@ -12,14 +12,20 @@ use crate::graphics::GraphicsContext;
 
 const MIN_GAME_SIZE: f32 = 128.0;
 
-fn calculate_game_size(width: f32, height: f32, min_size: f32) -> f32 {
-    let min_dimension = width.min(height);
-    (min_dimension / min_size).floor() * min_size
+/// Largest scale in a window, leaving room around the screen
+pub const DEFAULT_SCALE: u8 = 6;
+
+/// The biggest whole number of physical pixels per GameTank pixel that fits,
+/// so pixels stay square and sharp on scaled displays too
+fn calculate_game_scale(width: f32, height: f32, pixels_per_point: f32) -> f32 {
+    let min_dimension = width.min(height) * pixels_per_point;
+    (min_dimension / MIN_GAME_SIZE).floor().max(1.0)
 }
 
 
 pub struct GameTankBoyUI {
-    desired_scale: Option<u8>,
+    /// The most points per GameTank pixel, if it's capped
+    pub desired_scale: Option<u8>,
     screen: Box<TextureHandle>,
     textures: HashMap<String, TextureHandle>,
 
@@ -90,7 +96,7 @@ impl GameTankBoyUI {
         textures.insert("power_pressed".into(), power2);
 
         Self {
-            desired_scale: Some(6),
+            desired_scale: Some(DEFAULT_SCALE),
             screen: Box::new(game_texture),
             textures
         }
@@ -110,15 +116,14 @@ impl GameTankBoyUI {
 
         let available_width = ui.available_width();
         let available_height = ui.available_height();
-        let mut game_size = calculate_game_size(available_width, available_height, MIN_GAME_SIZE);
-        let orig_scale = game_size / MIN_GAME_SIZE;
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let mut scale = calculate_game_scale(available_width, available_height, pixels_per_point);
 
         // scale override, assuming there's enough space
-        if let Some(scale) = self.desired_scale {
-            if scale as f32 <= orig_scale {
-                game_size = MIN_GAME_SIZE * scale as f32
-            }
+        if let Some(desired) = self.desired_scale {
+            scale = scale.min((desired as f32 * pixels_per_point).floor());
         }
+        let game_size = MIN_GAME_SIZE * scale / pixels_per_point;
 
         let sized_texture = egui::load::SizedTexture::new(self.screen.id(), vec2(game_size, game_size));
 
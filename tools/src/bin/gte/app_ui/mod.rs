@@ -1,3 +1,5 @@
 pub mod gametankboy;
 pub mod vram_viewer;
 pub mod ram_inspector;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod netplay;
@@ -0,0 +1,96 @@
+//! Netplay (link): joining two emulators by their link ports, to develop
+//! two-console games without the cable
+
+use egui::{Color32, RichText, TextEdit, Ui};
+use gametank_sdk::link::{NetLink, DEFAULT_PORT};
+use gte_core::emulator::Emulator;
+use tracing::warn;
+use crate::app_delegation::InstantClock;
+
+pub struct NetplayPanel {
+    link: Option<NetLink>,
+    /// Where Join connects, as typed
+    address: String,
+    status: String,
+}
+
+impl NetplayPanel {
+    /// Starting with the link from `--link-host` or `--link-join`, if given
+    pub fn from_args() -> Self {
+        let mut panel = Self { link: None, address: format!("127.0.0.1:{}", DEFAULT_PORT), status: String::new() };
+        if let Some(result) = NetLink::from_args() {
+            panel.open(result);
+        }
+        panel
+    }
+
+    fn open(&mut self, result: Result<NetLink, String>) {
+        match result {
+            Ok(link) => {
+                self.status = link.status();
+                warn!("netplay: {}", self.status);
+                self.link = Some(link);
+            }
+            Err(e) => {
+                warn!("netplay: {}", e);
+                self.status = e;
+            }
+        }
+    }
+
+    fn close(&mut self, emulator: &mut Emulator<InstantClock>) {
+        self.link = None;
+        emulator.cpu_bus.system_control.link.disconnect();
+        self.status = "closed".to_string();
+    }
+
+    /// Trade bytes with the other emulator. Call once a frame.
+    pub fn poll(&mut self, emulator: &mut Emulator<InstantClock>) {
+        let Some(link) = &mut self.link else { return };
+        let was_connected = link.is_connected();
+
+        match link.pump(&mut emulator.cpu_bus.system_control.link) {
+            Ok(()) if link.is_connected() && !was_connected => {
+                self.status = link.status();
+                warn!("netplay: {}", self.status);
+            }
+            Ok(()) => {}
+            Err(e) => {
+                warn!("netplay: {}", e);
+                self.status = e;
+                self.link = None;
+            }
+        }
+    }
+
+    pub fn draw(&mut self, ui: &mut Ui, emulator: &mut Emulator<InstantClock>) {
+        ui.heading("Netplay (link)");
+
+        if self.link.is_some() {
+            if ui.button("Disconnect").clicked() {
+                self.close(emulator);
+            }
+        } else {
+            ui.horizontal(|ui| {
+                if ui.button("Host").clicked() {
+                    self.open(NetLink::host(DEFAULT_PORT));
+                }
+                ui.weak(format!("on port {}", DEFAULT_PORT));
+            });
+            ui.horizontal(|ui| {
+                ui.add(TextEdit::singleline(&mut self.address).desired_width(140.0));
+                if ui.button("Join").clicked() {
+                    let result = NetLink::join(self.address.trim());
+                    self.open(result);
+                }
+            });
+        }
+
+        let color = match &self.link {
+            Some(link) if link.is_connected() => Color32::LIGHT_GREEN,
+            Some(_) => Color32::YELLOW,
+            None => Color32::GRAY,
+        };
+        ui.label(RichText::new(&self.status).color(color));
+    }
+}
@@ -0,0 +1,64 @@
+//! `gte bench rom.gtr [--frames N]`: runs the ROM headless as fast as it
+//! goes and prints how fast it was emulated, for comparing builds of gte.
+//! See `gte_core::bench`.
+
+use std::time::Instant;
+
+use gte_core::emulator::{Emulator, TimeDaemon};
+
+/// Frames run without `--frames`
+const DEFAULT_FRAMES: u32 = 10_000;
+
+/// The bench's clock, finer than gte's millisecond one
+struct BenchClock {
+    start: Instant,
+}
+
+impl TimeDaemon for BenchClock {
+    fn get_now_ms(&self) -> f64 {
+        self.start.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+/// Whether gte was started as `gte bench ...`
+pub fn requested() -> bool {
+    std::env::args().nth(1).is_some_and(|arg| arg == "bench")
+}
+
+/// The ROM path and `--frames N` or `--frames=N` after `bench`
+fn parse_args() -> Result<(String, u32), String> {
+    let mut rom = None;
+    let mut frames = DEFAULT_FRAMES;
+    let mut args = std::env::args().skip(2);
+    let parse = |value: Option<&str>| value
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| "--frames takes a number of frames".to_string());
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--frames").map(|rest| rest.strip_prefix('=')) {
+            Some(None) if arg == "--frames" => frames = parse(args.next().as_deref())?,
+            Some(Some(value)) => frames = parse(Some(value))?,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => rom = Some(arg),
+        }
+    }
+    let rom = rom.ok_or_else(|| "Usage: gte bench <rom> [--frames N]".to_string())?;
+    Ok((rom, frames))
+}
+
+/// Run the benchmark and print its report
+pub fn run() -> Result<(), String> {
+    let (rom_path, frames) = parse_args()?;
+    let rom = std::fs::read(&rom_path)
+        .map_err(|e| format!("Failed to read {}: {}", rom_path, e))?;
+
+    let mut emulator = Emulator::init(BenchClock { start: Instant::now() }, 44100.0);
+    emulator.load_rom(&rom);
+
+    println!("Benchmarking {} for {} frames...", rom_path, frames);
+    let report = emulator.bench(frames);
+    if report.frames < frames {
+        println!("The CPU stopped after {} frames", report.frames);
+    }
+    print!("{}", report);
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//! Screenshots (F12) and captures (F10 for APNG, F9 for GIF), written to the
+//! current directory and named after the ROM, and the Chrome trace from
+//! `--trace out.json`, written on exit
+
+use std::path::{Path, PathBuf};
+
+use gametank_sdk::capture::{numbered_path, save_capture, save_png};
+use gte_core::emulator::{Emulator, TimeDaemon};
+use tracing::{error, warn};
+
+pub struct Capturer {
+    /// File names start with this
+    stem: String,
+    /// Extension of the capture being recorded
+    recording: Option<&'static str>,
+    /// Where to write the trace, while tracing
+    trace: Option<PathBuf>,
+}
+
+fn stem(rom_path: Option<&str>) -> String {
+    rom_path
+        .and_then(|path| Path::new(path).file_stem())
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "gte".to_string())
+}
+
+/// The path from `--trace PATH` or `--trace=PATH`, if given
+pub fn trace_path_from_args() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--trace").map(|rest| rest.strip_prefix('=')) {
+            Some(None) if arg == "--trace" => return args.next().map(PathBuf::from),
+            Some(Some(path)) => return Some(PathBuf::from(path)),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Capturer {
+    pub fn new(rom_path: Option<&str>) -> Self {
+        Self { stem: stem(rom_path), recording: None, trace: None }
+    }
+
+    /// Name files after a newly loaded ROM, carrying on with any capture or
+    /// trace
+    pub fn set_rom(&mut self, rom_path: Option<&str>) {
+        self.stem = stem(rom_path);
+    }
+
+    /// Trace the emulator until [`finish_trace`](Self::finish_trace), if
+    /// `--trace` was given
+    pub fn start_trace<C: TimeDaemon>(&mut self, emulator: &mut Emulator<C>) {
+        self.trace = trace_path_from_args();
+        if let Some(path) = &self.trace {
+            emulator.start_trace();
+            warn!("tracing to {} on exit", path.display());
+        }
+    }
+
+    /// Write the trace, if tracing
+    pub fn finish_trace<C: TimeDaemon>(&mut self, emulator: &mut Emulator<C>) {
+        let (Some(path), Some(trace)) = (self.trace.take(), emulator.stop_trace()) else { return };
+        match std::fs::write(&path, trace.to_chrome_json()) {
+            Ok(()) => warn!("saved trace {} ({} frames)", path.display(), trace.frames()),
+            Err(e) => error!("Failed to write {}: {}", path.display(), e),
+        }
+    }
+
+    pub fn screenshot<C: TimeDaemon>(&self, emulator: &Emulator<C>) {
+        let path = numbered_path(Path::new("."), &self.stem, "png");
+        match save_png(&emulator.screenshot(), &path) {
+            Ok(()) => warn!("saved screenshot {}", path.display()),
+            Err(e) => error!("{}", e),
+        }
+    }
+
+    /// Start capturing as `ext` ("png" or "gif"), or stop and save the
+    /// capture that's running
+    pub fn toggle<C: TimeDaemon>(&mut self, emulator: &mut Emulator<C>, ext: &'static str) {
+        let Some(ext) = self.recording.take() else {
+            emulator.start_capture();
+            self.recording = Some(ext);
+            warn!("capturing, press again to stop");
+            return;
+        };
+
+        let frames = emulator.stop_capture();
+        let path = numbered_path(Path::new("."), &self.stem, ext);
+        // encoding a long capture takes a moment; keep the emulator going
+        std::thread::spawn(move || match save_capture(&frames, &path) {
+            Ok(()) => warn!("saved capture {} ({} images)", path.display(), frames.len()),
+            Err(e) => error!("{}", e),
+        });
+    }
+}
@@ -0,0 +1,89 @@
+//! Serving the GDB remote protocol over TCP, for `gte <rom> --gdb[=PORT]`
+//! (which `gtrom run --debug` uses)
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use gte_core::emulator::{Emulator, TimeDaemon};
+use gte_core::gdb::GdbStub;
+use tracing::{error, warn};
+
+pub const DEFAULT_PORT: u16 = 1234;
+
+/// One debugger at a time, polled from the event loop so the emulator never
+/// leaves the main thread
+pub struct GdbServer {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    stub: GdbStub,
+}
+
+impl GdbServer {
+    pub fn bind(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        listener.set_nonblocking(true)
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        warn!("waiting for a debugger on localhost:{}", port);
+        Ok(Self { listener, stream: None, stub: GdbStub::new() })
+    }
+
+    /// The port from `--gdb` or `--gdb=PORT`, if given
+    pub fn port_from_args() -> Option<u16> {
+        std::env::args().find_map(|arg| match arg.strip_prefix("--gdb")? {
+            "" => Some(DEFAULT_PORT),
+            port => port.strip_prefix('=')?.parse().ok(),
+        })
+    }
+
+    /// Accept a debugger, pass along what it sent, and send back replies
+    pub fn poll<C: TimeDaemon>(&mut self, emulator: &mut Emulator<C>) {
+        if self.stream.is_none() {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    warn!("debugger attached from {}", address);
+                    let _ = stream.set_nonblocking(true);
+                    let _ = stream.set_nodelay(true);
+                    emulator.pause();
+                    self.stream = Some(stream);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    error!("couldn't accept debugger: {}", e);
+                    return;
+                }
+            }
+        }
+        let Some(stream) = &mut self.stream else { return };
+
+        let mut buf = [0u8; 4096];
+        let mut closed = false;
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(n) => self.stub.receive(&buf[..n], emulator),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    error!("debugger connection failed: {}", e);
+                    closed = true;
+                    break;
+                }
+            }
+        }
+
+        self.stub.poll(emulator);
+        let output = self.stub.take_output();
+        if !output.is_empty() && stream.write_all(&output).is_err() {
+            closed = true;
+        }
+
+        if closed {
+            warn!("debugger detached");
+            self.stub.detach(emulator);
+            self.stream = None;
+        }
+    }
+}
@@ -9,6 +9,14 @@ mod app_ui;
 pub mod app_initialized;
 mod app_delegation;
 mod audio;
+#[cfg(not(target_arch = "wasm32"))]
+mod reload;
+#[cfg(not(target_arch = "wasm32"))]
+mod gdb_server;
+#[cfg(not(target_arch = "wasm32"))]
+mod capture;
+#[cfg(not(target_arch = "wasm32"))]
+mod bench;
 
 use app_delegation::DelegatedApp::Uninitialized;
 use std::cmp::PartialEq;
@@ -100,6 +108,15 @@ pub fn main() {
         setup_logging();
         info!("stdout logger started");
 
+        // headless, without a window
+        if bench::requested() {
+            if let Err(e) = bench::run() {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+
         let event_loop = EventLoop::<()>::with_user_event().build().unwrap();
         event_loop.set_control_flow(ControlFlow::Poll);
 
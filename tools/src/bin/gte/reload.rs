@@ -0,0 +1,45 @@
+//! Reloading the ROM when it changes on disk, for `gte <rom> --watch` (which
+//! `gtrom run --hold` uses)
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often to look at the file
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct RomReloader {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// The file changed at the last check, so it may still be being written
+    changed: bool,
+    last_check: Instant,
+}
+
+impl RomReloader {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        Self { path, modified, changed: false, last_check: Instant::now() }
+    }
+
+    /// The new ROM, once the file has changed and then stayed the same for a
+    /// check, so a half-written ROM isn't loaded
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = std::fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified != self.modified {
+            self.modified = modified;
+            self.changed = true;
+            return None;
+        }
+        if !self.changed || modified.is_none() {
+            return None;
+        }
+
+        self.changed = false;
+        std::fs::read(&self.path).ok()
+    }
+}
@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+
+use crossbeam_channel::Sender;
+use gte_core::{acp::{VoiceLayout, VoiceLevel, VOICE_BASE}, emulator::{Emulator, PlayState}};
+use ratatui::{crossterm::event::Event, layout::{Constraint, Layout, Rect}, style::{Color, Stylize}, symbols::{border, Marker}, text::{Line, Span}, widgets::{Axis, Block, Chart, Dataset, GraphType, Paragraph}, Frame};
+
+use crate::{helpers::{read_rom, InstantClock, SCHEME}, keymap::{Help, Keymap}, main_menu::MainMenu, ui::file_picker::{FilePicker, PickerMode}, Component, GlobalEvent};
+
+/// Bytes of ACP RAM per hex dump row
+const ROW_BYTES: usize = 16;
+
+/// Samples drawn by the oscilloscope, the newest end of the scope buffer
+const SCOPE_SAMPLES: usize = 512;
+
+#[derive(Clone, Copy)]
+enum AcpKey {
+    Quit,
+    Pause,
+    NextFrame,
+    Layout,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    Top,
+    Voices,
+    Open,
+}
+
+fn keymap() -> Keymap<AcpKey> {
+    Keymap::new("audio_debugger", &[
+        ("layout", AcpKey::Layout, &["l"], "layout"),
+        ("scroll_up", AcpKey::ScrollUp, &["up"], "scroll up"),
+        ("scroll_down", AcpKey::ScrollDown, &["down"], "scroll down"),
+        ("page_up", AcpKey::PageUp, &["pgup"], "page up"),
+        ("page_down", AcpKey::PageDown, &["pgdn"], "page down"),
+        ("top", AcpKey::Top, &["home"], "top of RAM"),
+        ("voices", AcpKey::Voices, &["v"], "voices"),
+        ("pause", AcpKey::Pause, &["p", "space"], "pause"),
+        ("next_frame", AcpKey::NextFrame, &["n"], "next frame"),
+        ("open", AcpKey::Open, &["o"], "open"),
+        ("quit", AcpKey::Quit, &["esc", "q"], "quit"),
+    ])
+}
+
+/// Watch the audio coprocessor while a ROM runs: its registers, the voices
+/// of the SDK firmware it's running, its RAM and what it sends to the DAC
+pub struct AcpViewer {
+    tx: Sender<GlobalEvent>,
+    emulator: Emulator<InstantClock>,
+    rom_path: Option<PathBuf>,
+    picker: Option<FilePicker>,
+    layout: VoiceLayout,
+    /// First hex dump row on screen
+    scroll: usize,
+    keys: Keymap<AcpKey>,
+    status: String,
+}
+
+impl AcpViewer {
+    pub fn init(tx: Sender<GlobalEvent>) -> Self {
+        let dir = std::env::current_dir().unwrap_or_default();
+
+        Self {
+            tx,
+            emulator: Emulator::init(InstantClock::default(), 44_100.0),
+            rom_path: None,
+            picker: Some(FilePicker::init(PickerMode::Open, "gtr", &dir)),
+            layout: VoiceLayout::Wavetable8,
+            scroll: 0,
+            keys: keymap(),
+            status: "Pick a ROM to inspect".to_string(),
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = read_rom(path)?;
+        self.emulator.load_rom(&bytes);
+        self.emulator.resume();
+        self.rom_path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    fn quit(&self) {
+        let menu = MainMenu::init(self.tx.clone());
+        let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(menu)));
+    }
+
+    fn is_running(&self) -> bool {
+        self.emulator.play_state == PlayState::Playing
+    }
+
+    fn update_picker(&mut self, events: Vec<Event>) {
+        let Some(picker) = &mut self.picker else { return };
+        picker.update(events);
+        if picker.is_active() {
+            return;
+        }
+
+        let picked = picker.take_picked();
+        self.picker = None;
+
+        match picked {
+            Some(path) => {
+                self.status = match self.load(&path) {
+                    Ok(()) => format!("Loaded {}", path.display()),
+                    Err(e) => e,
+                };
+            }
+            None if self.rom_path.is_none() => self.quit(),
+            None => {}
+        }
+    }
+
+    fn handle_key(&mut self, key: AcpKey) {
+        let rows = 0x1000 / ROW_BYTES;
+        match key {
+            AcpKey::Quit => self.quit(),
+            AcpKey::Pause => {
+                if self.is_running() {
+                    self.emulator.pause();
+                } else {
+                    self.emulator.resume();
+                }
+            }
+            AcpKey::NextFrame if !self.is_running() => self.emulator.run_frame(),
+            AcpKey::Layout => {
+                self.layout = self.layout.next();
+                self.status = format!("Reading voices as {}", self.layout.name());
+            }
+            AcpKey::ScrollUp => self.scroll = self.scroll.saturating_sub(1),
+            AcpKey::ScrollDown => self.scroll = (self.scroll + 1).min(rows - 1),
+            AcpKey::PageUp => self.scroll = self.scroll.saturating_sub(16),
+            AcpKey::PageDown => self.scroll = (self.scroll + 16).min(rows - 1),
+            AcpKey::Top => self.scroll = 0,
+            AcpKey::Voices => self.scroll = VOICE_BASE as usize / ROW_BYTES,
+            AcpKey::Open => {
+                let dir = self.rom_path.as_ref()
+                    .and_then(|p| p.parent().map(Path::to_path_buf))
+                    .unwrap_or_default();
+                self.picker = Some(FilePicker::init(PickerMode::Open, "gtr", &dir));
+            }
+            AcpKey::NextFrame => {}
+        }
+    }
+
+    fn register_lines(&self) -> Vec<Line<'static>> {
+        let r = self.emulator.acp_registers();
+        let label = |name: &str| Span::from(format!("{} ", name)).fg(SCHEME.gray[2]);
+        let rate = self.emulator.acp_sample_rate();
+
+        vec![
+            Line::from(vec![
+                label("PC"), Span::from(format!("${:04X}  ", r.pc)),
+                label("A"), Span::from(format!("${:02X} ", r.a)),
+                label("X"), Span::from(format!("${:02X} ", r.x)),
+                label("Y"), Span::from(format!("${:02X} ", r.y)),
+                label("S"), Span::from(format!("${:02X} ", r.s)),
+                label("P"), Span::from(format!("${:02X}", r.p)),
+            ]),
+            Line::from(vec![
+                label("DAC"), Span::from(format!("${:02X}  ", self.emulator.acp_bus.sample)),
+                label("rate"), Span::from(if rate > 0.0 { format!("{:.0}Hz", rate) } else { "off".to_string() }),
+            ]),
+        ]
+    }
+
+    fn voice_lines(&self) -> Vec<Line<'static>> {
+        let rate = self.emulator.acp_sample_rate();
+        let header = match self.layout {
+            VoiceLayout::Pcm => " #  addr  step     rate   end   flags",
+            _ => " #  phase step    pitch  table level",
+        };
+
+        let mut lines = vec![Line::from(header).fg(SCHEME.gray[2])];
+        for (i, voice) in self.emulator.acp_voices(self.layout).iter().enumerate() {
+            let level = match voice.level {
+                VoiceLevel::Volume(v) => format!("{:>2}/63", v),
+                VoiceLevel::Shifted { table, shift } => format!("${:04X}>>{}", table, shift),
+                VoiceLevel::Flags(f) => match (f & 0x80 != 0, f & 0x40 != 0) {
+                    (true, true) => "stream".to_string(),
+                    (true, false) => "play".to_string(),
+                    _ => "-".to_string(),
+                },
+            };
+            let hz = voice.frequency_hz(rate);
+            let line = Line::from(format!(
+                "{:>2}  {:04X}  {:04X} {:>8.1} ${:04X} {}",
+                i, voice.phase, voice.step, hz, voice.source, level,
+            ));
+            lines.push(if voice.is_active() { line } else { line.fg(SCHEME.gray[2]) });
+        }
+        lines
+    }
+
+    fn memory_lines(&self, rows: usize) -> Vec<Line<'static>> {
+        let ram = self.emulator.acp_ram();
+        let voices = VOICE_BASE as usize..VOICE_BASE as usize + self.layout.voice_count() * self.layout.voice_size();
+
+        (self.scroll..(self.scroll + rows).min(ram.len() / ROW_BYTES)).map(|row| {
+            let start = row * ROW_BYTES;
+            let mut spans = vec![Span::from(format!("{:04X}", start)).fg(SCHEME.gray[2])];
+            for (addr, byte) in ram[start..start + ROW_BYTES].iter().enumerate().map(|(i, b)| (start + i, b)) {
+                let span = Span::from(format!(" {:02X}", byte));
+                spans.push(if voices.contains(&addr) { span.fg(SCHEME.orange[1]) } else { span });
+            }
+            let text: String = ram[start..start + ROW_BYTES].iter()
+                .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                .collect();
+            spans.push(Span::from(format!("  {}", text)).fg(SCHEME.gray[2]));
+            Line::from(spans)
+        }).collect()
+    }
+}
+
+impl Component for AcpViewer {
+    fn update(&mut self, events: Vec<Event>) {
+        if self.picker.is_some() {
+            self.update_picker(events);
+        } else {
+            let keys: Vec<AcpKey> = self.keys.actions(&events).collect();
+            for key in keys {
+                self.handle_key(key);
+            }
+        }
+
+        if self.is_running() {
+            self.emulator.process_cycles(false);
+        }
+    }
+
+    fn help(&self) -> Vec<Help> {
+        vec![self.keys.help()]
+    }
+
+    fn captures_text(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let panel = |title: String| Block::bordered()
+            .title(title)
+            .title_style(style.bold().fg(SCHEME.orange[1]))
+            .border_set(border::ROUNDED)
+            .style(style);
+
+        let voice_rows = self.layout.voice_count() as u16 + 3;
+        let [main, footer] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [upper, memory_area] = Layout::vertical([Constraint::Length(voice_rows + 4), Constraint::Fill(1)]).areas(main);
+        let [side, scope_area] = Layout::horizontal([Constraint::Length(46), Constraint::Fill(1)]).areas(upper);
+        let [regs_area, voices_area] = Layout::vertical([Constraint::Length(4), Constraint::Fill(1)]).areas(side);
+
+        frame.render_widget(Paragraph::new(self.register_lines()).block(panel(" ACP ".to_string())), regs_area);
+        frame.render_widget(
+            Paragraph::new(self.voice_lines()).block(panel(format!(" Voices ({}) ", self.layout.name()))),
+            voices_area,
+        );
+
+        let samples = self.emulator.acp_scope.samples();
+        let points: Vec<(f64, f64)> = samples[samples.len() - SCOPE_SAMPLES..].iter()
+            .enumerate()
+            .map(|(i, &s)| (i as f64, s as f64))
+            .collect();
+        let scope = Chart::new(vec![
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(style.fg(SCHEME.green[1]))
+                .data(&points),
+        ])
+            .block(panel(" DAC ".to_string()))
+            .style(style)
+            .x_axis(Axis::default().bounds([0.0, SCOPE_SAMPLES as f64 - 1.0]))
+            .y_axis(Axis::default().bounds([0.0, 255.0]));
+        frame.render_widget(scope, scope_area);
+
+        let memory_block = panel(" Audio RAM ".to_string());
+        let rows = memory_block.inner(memory_area).height as usize;
+        frame.render_widget(Paragraph::new(self.memory_lines(rows)).block(memory_block), memory_area);
+
+        let state = if self.is_running() { "RUN " } else { "STOP" };
+        let footer_line = Line::from(vec![
+            Span::from(format!(" {} ", state)).bold().fg(SCHEME.orange[1]),
+            Span::from(self.status.clone()),
+            Span::from(format!("  {} ?:keys", self.keys.hints(&["layout", "voices", "pause", "next_frame", "open", "quit"]))).fg(SCHEME.gray[2]),
+        ]);
+        frame.render_widget(footer_line, footer);
+
+        if let Some(picker) = &mut self.picker {
+            picker.render(frame, frame.area());
+        }
+    }
+}
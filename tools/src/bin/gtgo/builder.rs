@@ -0,0 +1,427 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use ratatui::{crossterm::event::Event, layout::{Constraint, Layout, Rect}, style::{Color, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, List, ListItem, ListState, Paragraph}, Frame};
+
+use crate::{emulator::EmulatorScreen, flasher::Flasher, helpers::SCHEME, keymap::{Help, Keymap}, main_menu::MainMenu, Component, GlobalEvent};
+
+/// What `gtrom build` prints last when it worked
+const BUILD_COMPLETE: &str = "Build complete: ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Level {
+    Error,
+    Warning,
+}
+
+/// An error or warning from the build, and where it points
+struct Diagnostic {
+    level: Level,
+    message: String,
+    /// Index into the output of the line it starts on
+    output_line: usize,
+    location: Option<(PathBuf, usize, usize)>,
+}
+
+/// Read a diagnostic heading like `error[E0308]: mismatched types`.
+/// Cargo's summaries (`warning: `rom` generated 3 warnings`) aren't one.
+fn parse_heading(line: &str) -> Option<(Level, String)> {
+    let (head, message) = line.split_once(": ")?;
+    let level = match head.split('[').next()? {
+        "error" | "Error" => Level::Error,
+        "warning" => Level::Warning,
+        // the linker's own errors
+        h if h.ends_with("lld: error") => Level::Error,
+        _ => return None,
+    };
+    if message.starts_with("could not compile") || (message.starts_with('`') && message.contains(" generated ")) {
+        return None;
+    }
+    Some((level, message.to_string()))
+}
+
+/// Read the `  --> src/main.rs:10:5` under a diagnostic heading
+fn parse_location(line: &str) -> Option<(PathBuf, usize, usize)> {
+    let location = line.trim_start().strip_prefix("--> ")?;
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    Some((PathBuf::from(parts.next()?), line, column))
+}
+
+/// Where to find `gtrom`: next to `gtgo` if it's there, or on the PATH
+fn gtrom_command() -> Command {
+    let sibling = std::env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(format!("gtrom{}", std::env::consts::EXE_SUFFIX)))
+        .filter(|path| path.exists());
+    Command::new(sibling.unwrap_or_else(|| PathBuf::from("gtrom")))
+}
+
+/// Send each line `reader` produces until it closes
+fn forward_lines(reader: impl Read + Send + 'static, tx: Sender<String>) {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Output,
+    Problems,
+}
+
+#[derive(Clone, Copy)]
+enum BuildKey {
+    Quit,
+    Focus,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    Show,
+    Edit,
+    Rebuild,
+    Run,
+    Flash,
+}
+
+fn keymap() -> Keymap<BuildKey> {
+    Keymap::new("build", &[
+        ("focus", BuildKey::Focus, &["tab"], "focus"),
+        ("up", BuildKey::Up, &["up"], "up"),
+        ("down", BuildKey::Down, &["down"], "down"),
+        ("page_up", BuildKey::PageUp, &["pgup"], "page up"),
+        ("page_down", BuildKey::PageDown, &["pgdn"], "page down"),
+        ("top", BuildKey::Top, &["home"], "top"),
+        ("bottom", BuildKey::Bottom, &["end"], "follow output"),
+        ("show", BuildKey::Show, &["enter"], "show"),
+        ("edit", BuildKey::Edit, &["e"], "edit"),
+        ("rebuild", BuildKey::Rebuild, &["b"], "rebuild"),
+        ("run", BuildKey::Run, &["g"], "run"),
+        ("flash", BuildKey::Flash, &["f"], "flash"),
+        ("quit", BuildKey::Quit, &["esc", "q"], "quit"),
+    ])
+}
+
+/// Run `gtrom build` for the project in the current directory, show its
+/// output as it comes, and list the errors and warnings to jump between
+pub struct BuildScreen {
+    tx: Sender<GlobalEvent>,
+    /// The running build, and the lines from its stdout and stderr
+    child: Option<(Child, Receiver<String>)>,
+    output: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+    problem_state: ListState,
+    /// First output line shown, or `None` to follow the end
+    scroll: Option<usize>,
+    /// Output lines that fit in the pane, as of the last render
+    page: usize,
+    focus: Focus,
+    /// The finished ROM, once a build has worked
+    rom_path: Option<PathBuf>,
+    succeeded: Option<bool>,
+    keys: Keymap<BuildKey>,
+    status: String,
+}
+
+impl BuildScreen {
+    pub fn init(tx: Sender<GlobalEvent>) -> Self {
+        let mut screen = Self {
+            tx,
+            child: None,
+            output: vec![],
+            diagnostics: vec![],
+            problem_state: ListState::default(),
+            scroll: None,
+            page: 1,
+            focus: Focus::Output,
+            rom_path: None,
+            succeeded: None,
+            keys: keymap(),
+            status: String::new(),
+        };
+        screen.start();
+        screen
+    }
+
+    fn is_building(&self) -> bool {
+        self.child.is_some()
+    }
+
+    fn quit(&mut self) {
+        if let Some((child, _)) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let menu = MainMenu::init(self.tx.clone());
+        let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(menu)));
+    }
+
+    fn start(&mut self) {
+        self.output.clear();
+        self.diagnostics.clear();
+        self.problem_state.select(None);
+        self.scroll = None;
+        self.rom_path = None;
+        self.succeeded = None;
+
+        let spawned = gtrom_command()
+            .arg("build")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                self.succeeded = Some(false);
+                self.status = format!("Failed to run gtrom: {}", e);
+                return;
+            }
+        };
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        if let Some(stdout) = child.stdout.take() {
+            forward_lines(stdout, tx.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward_lines(stderr, tx);
+        }
+
+        self.child = Some((child, rx));
+        self.status = "Building...".to_string();
+    }
+
+    fn push_line(&mut self, line: String) {
+        if let Some((level, message)) = parse_heading(&line) {
+            self.diagnostics.push(Diagnostic { level, message, output_line: self.output.len(), location: None });
+        } else if let Some(location) = parse_location(&line) {
+            if let Some(diagnostic) = self.diagnostics.last_mut().filter(|d| d.location.is_none()) {
+                diagnostic.location = Some(location);
+            }
+        } else if let Some(path) = line.strip_prefix(BUILD_COMPLETE) {
+            self.rom_path = Some(PathBuf::from(path));
+        }
+        self.output.push(line);
+    }
+
+    /// Take the build's output since the last frame, and notice when it ends
+    fn update_build(&mut self) {
+        let Some((_, rx)) = &self.child else { return };
+
+        let mut lines = vec![];
+        let closed = loop {
+            match rx.try_recv() {
+                Ok(line) => lines.push(line),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        for line in lines {
+            self.push_line(line);
+        }
+        if !closed {
+            return;
+        }
+
+        let Some((mut child, _)) = self.child.take() else { return };
+        let success = child.wait().map(|status| status.success()).unwrap_or(false);
+        self.succeeded = Some(success);
+
+        let count = |level| self.diagnostics.iter().filter(|d| d.level == level).count();
+        let (errors, warnings) = (count(Level::Error), count(Level::Warning));
+        self.status = match success {
+            true => format!("Build complete, {} warning(s)", warnings),
+            false => format!("Build failed, {} error(s), {} warning(s)", errors, warnings),
+        };
+        if !self.diagnostics.is_empty() {
+            let first = self.diagnostics.iter().position(|d| d.level == Level::Error).unwrap_or(0);
+            self.problem_state.select(Some(first));
+        }
+        if !success {
+            self.focus = Focus::Problems;
+        }
+    }
+
+    fn selected(&self) -> Option<&Diagnostic> {
+        self.problem_state.selected().and_then(|i| self.diagnostics.get(i))
+    }
+
+    /// Scroll the output so the selected problem is at the top
+    fn show_selected(&mut self) {
+        if let Some(line) = self.selected().map(|d| d.output_line) {
+            self.scroll = Some(line);
+            self.focus = Focus::Output;
+        }
+    }
+
+    /// Paths in diagnostics are relative to the crate that was compiled,
+    /// which is usually `rom/`
+    fn resolve(path: &Path) -> PathBuf {
+        let dir = std::env::current_dir().unwrap_or_default();
+        [dir.join("rom").join(path), dir.join(path)]
+            .into_iter()
+            .find(|p| p.exists())
+            .unwrap_or_else(|| dir.join(path))
+    }
+
+    /// Open the selected problem in `$EDITOR`, at its line
+    fn edit_selected(&mut self) {
+        let Some((path, line, _)) = self.selected().and_then(|d| d.location.clone()) else {
+            self.status = "That problem has no source location".to_string();
+            return;
+        };
+        let Ok(editor) = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")) else {
+            self.status = "Set $EDITOR to open problems in an editor".to_string();
+            return;
+        };
+
+        let path = Self::resolve(&path);
+        let _ = self.tx.send(GlobalEvent::Suspend(Box::new(move || {
+            let _ = Command::new(&editor).arg(format!("+{}", line)).arg(&path).status();
+        })));
+    }
+
+    fn scroll_by(&mut self, lines: isize) {
+        let last = self.output.len().saturating_sub(self.page);
+        let top = self.scroll.unwrap_or(last).saturating_add_signed(lines);
+        // scrolling back to the end follows the output again
+        self.scroll = (top < last).then_some(top);
+    }
+
+    fn handle_key(&mut self, key: BuildKey) {
+        let page = self.page as isize;
+        match (key, self.focus) {
+            (BuildKey::Quit, _) => self.quit(),
+            (BuildKey::Focus, Focus::Output) if !self.diagnostics.is_empty() => self.focus = Focus::Problems,
+            (BuildKey::Focus, _) => self.focus = Focus::Output,
+            (BuildKey::Up, Focus::Output) => self.scroll_by(-1),
+            (BuildKey::Down, Focus::Output) => self.scroll_by(1),
+            (BuildKey::PageUp, _) => self.scroll_by(-page),
+            (BuildKey::PageDown, _) => self.scroll_by(page),
+            (BuildKey::Top, _) => self.scroll = Some(0),
+            (BuildKey::Bottom, _) => self.scroll = None,
+            (BuildKey::Up, Focus::Problems) => self.problem_state.select_previous(),
+            (BuildKey::Down, Focus::Problems) => self.problem_state.select_next(),
+            (BuildKey::Show, Focus::Problems) => self.show_selected(),
+            (BuildKey::Edit, _) => self.edit_selected(),
+            (BuildKey::Rebuild, _) if !self.is_building() => self.start(),
+            (BuildKey::Run, _) if !self.is_building() => {
+                if let Some(path) = &self.rom_path {
+                    let emulator = EmulatorScreen::with_rom(self.tx.clone(), path);
+                    let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(emulator)));
+                }
+            }
+            (BuildKey::Flash, _) if !self.is_building() => {
+                if let Some(path) = &self.rom_path {
+                    let flasher = Flasher::with_rom(self.tx.clone(), path);
+                    let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(flasher)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn output_line(&self, index: usize) -> Line<'static> {
+        let text = self.output[index].clone();
+        match parse_heading(&text) {
+            Some((Level::Error, _)) => Line::from(text).bold().fg(SCHEME.red[2]),
+            Some((Level::Warning, _)) => Line::from(text).bold().fg(SCHEME.yellow[2]),
+            None if parse_location(&text).is_some() => Line::from(text).fg(SCHEME.blue[2]),
+            None => Line::from(text),
+        }
+    }
+
+    fn problem_item(diagnostic: &Diagnostic) -> ListItem<'static> {
+        let (label, color) = match diagnostic.level {
+            Level::Error => ("error ", SCHEME.red[2]),
+            Level::Warning => ("warn  ", SCHEME.yellow[2]),
+        };
+        let location = match &diagnostic.location {
+            Some((path, line, column)) => format!("{}:{}:{} ", path.display(), line, column),
+            None => String::new(),
+        };
+        ListItem::new(Line::from(vec![
+            Span::from(label).bold().fg(color),
+            Span::from(location).fg(SCHEME.gray[2]),
+            Span::from(diagnostic.message.clone()),
+        ]))
+    }
+}
+
+impl Component for BuildScreen {
+    fn update(&mut self, events: Vec<Event>) {
+        self.update_build();
+
+        let keys: Vec<BuildKey> = self.keys.actions(&events).collect();
+        for key in keys {
+            self.handle_key(key);
+        }
+    }
+
+    fn help(&self) -> Vec<Help> {
+        vec![self.keys.help()]
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let panel = |title: String, focused: bool| Block::bordered()
+            .title(title)
+            .title_style(style.bold().fg(if focused { SCHEME.orange[1] } else { SCHEME.gray[2] }))
+            .border_set(border::ROUNDED)
+            .style(style);
+
+        let problem_rows = (self.diagnostics.len() as u16).clamp(1, 8) + 2;
+        let [output_area, problem_area, footer] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(problem_rows),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+
+        let output_block = panel(" Build Output ".to_string(), self.focus == Focus::Output);
+        self.page = output_block.inner(output_area).height.max(1) as usize;
+        let last = self.output.len().saturating_sub(self.page);
+        let top = self.scroll.unwrap_or(last).min(last);
+        let lines: Vec<Line> = (top..self.output.len().min(top + self.page))
+            .map(|i| self.output_line(i))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(output_block), output_area);
+
+        let problems: Vec<ListItem> = self.diagnostics.iter().map(Self::problem_item).collect();
+        let title = format!(" Problems ({}) ", self.diagnostics.len());
+        let problem_list = List::new(problems)
+            .block(panel(title, self.focus == Focus::Problems))
+            .highlight_style(style.bold().fg(SCHEME.orange[1]))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(problem_list, problem_area, &mut self.problem_state);
+
+        let state = match self.succeeded {
+            _ if self.is_building() => "BUILDING",
+            Some(true) => "OK",
+            _ => "FAILED",
+        };
+        let keys = if self.is_building() {
+            format!("  {} {}:cancel", self.keys.hints(&["focus", "page_up", "page_down"]), self.keys.keys("quit"))
+        } else if self.rom_path.is_some() {
+            format!("  {} ?:keys", self.keys.hints(&["focus", "show", "edit", "rebuild", "run", "flash", "quit"]))
+        } else {
+            format!("  {} ?:keys", self.keys.hints(&["focus", "show", "edit", "rebuild", "quit"]))
+        };
+        let footer_line = Line::from(vec![
+            Span::from(format!(" {} ", state)).bold().fg(SCHEME.orange[1]),
+            Span::from(self.status.clone()),
+            Span::from(keys).fg(SCHEME.gray[2]),
+        ]);
+        frame.render_widget(footer_line, footer);
+    }
+}
@@ -0,0 +1,499 @@
+use std::path::{Path, PathBuf};
+
+use crossbeam_channel::Sender;
+use gte_core::{cheats::Cheat, debugger::{StopReason, WatchKind}, emulator::{Emulator, PlayState}, symbols::linked_address};
+use ratatui::{crossterm::event::{Event, KeyCode, KeyEvent}, layout::{Constraint, Layout, Rect}, style::{Color, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, Paragraph}, Frame};
+
+use crate::{helpers::{read_rom, read_symbols, InstantClock, SCHEME}, keymap::{Help, Keymap}, main_menu::MainMenu, ui::{file_picker::{FilePicker, PickerMode}, framebuffer::FramebufferView}, Component, GlobalEvent};
+
+/// Rows of 8 bytes shown in the memory panel
+const MEMORY_ROWS: u16 = 8;
+/// Longest symbol name the breakpoint prompt takes
+const MAX_SYMBOL_LEN: usize = 48;
+/// Longest cheat code the freeze prompt takes
+const MAX_CODE_LEN: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PromptKind {
+    Breakpoint,
+    Watch(WatchKind),
+    Memory,
+    Freeze,
+}
+
+impl PromptKind {
+    fn label(self) -> &'static str {
+        match self {
+            PromptKind::Breakpoint => "Toggle breakpoint at (address or function)",
+            PromptKind::Watch(WatchKind::Write) => "Watch writes to",
+            PromptKind::Watch(_) => "Watch reads of",
+            PromptKind::Memory => "Show memory at",
+            PromptKind::Freeze => "Freeze (address, address:value or cheat code)",
+        }
+    }
+}
+
+struct Prompt {
+    kind: PromptKind,
+    text: String,
+}
+
+impl Prompt {
+    /// What typing `c` adds, if anything: hex digits, for breakpoints
+    /// anything that can be in a function name, and for freezes a cheat code
+    fn accept(&self, c: char) -> Option<char> {
+        match self.kind {
+            PromptKind::Breakpoint => (!c.is_whitespace() && self.text.len() < MAX_SYMBOL_LEN).then_some(c),
+            PromptKind::Freeze => {
+                let code = c.is_ascii_hexdigit() || ":?=!<>+".contains(c);
+                (code && self.text.len() < MAX_CODE_LEN).then(|| c.to_ascii_uppercase())
+            }
+            _ => (c.is_ascii_hexdigit() && self.text.len() < 4).then(|| c.to_ascii_uppercase()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum DebuggerKey {
+    Quit,
+    RunPause,
+    Step,
+    Breakpoint,
+    WatchWrite,
+    WatchRead,
+    Memory,
+    Freeze,
+    Clear,
+    Open,
+    MemoryUp,
+    MemoryDown,
+}
+
+fn keymap() -> Keymap<DebuggerKey> {
+    Keymap::new("debugger", &[
+        ("run_pause", DebuggerKey::RunPause, &["c", "f5"], "run/pause"),
+        ("step", DebuggerKey::Step, &["s", "f10"], "step"),
+        ("breakpoint", DebuggerKey::Breakpoint, &["b", "f9"], "break"),
+        ("watch_write", DebuggerKey::WatchWrite, &["w"], "watch writes"),
+        ("watch_read", DebuggerKey::WatchRead, &["r"], "watch reads"),
+        ("memory", DebuggerKey::Memory, &["m"], "mem"),
+        ("freeze", DebuggerKey::Freeze, &["f"], "freeze"),
+        ("memory_up", DebuggerKey::MemoryUp, &["pgup"], "memory page up"),
+        ("memory_down", DebuggerKey::MemoryDown, &["pgdn"], "memory page down"),
+        ("clear", DebuggerKey::Clear, &["x"], "clear"),
+        ("open", DebuggerKey::Open, &["o"], "open"),
+        ("quit", DebuggerKey::Quit, &["q", "esc"], "quit"),
+    ])
+}
+
+/// Step through a ROM while watching the screen, registers and memory
+pub struct Debugger {
+    tx: Sender<GlobalEvent>,
+    emulator: Emulator<InstantClock>,
+    rom_path: Option<PathBuf>,
+    picker: Option<FilePicker>,
+    prompt: Option<Prompt>,
+    memory_addr: u16,
+    keys: Keymap<DebuggerKey>,
+    status: String,
+    last_stop: Option<StopReason>,
+}
+
+impl Debugger {
+    pub fn init(tx: Sender<GlobalEvent>) -> Self {
+        let mut emulator = Emulator::init(InstantClock::default(), 44_100.0);
+        emulator.pause();
+
+        let dir = std::env::current_dir().unwrap_or_default();
+
+        Self {
+            tx,
+            emulator,
+            rom_path: None,
+            picker: Some(FilePicker::init(PickerMode::Open, "gtr", &dir)),
+            prompt: None,
+            memory_addr: 0,
+            keys: keymap(),
+            status: "Pick a ROM to debug".to_string(),
+            last_stop: None,
+        }
+    }
+
+    /// Load a ROM and its symbols. Returns how many functions have names.
+    fn load(&mut self, path: &Path) -> Result<usize, String> {
+        let bytes = read_rom(path)?;
+        self.emulator.load_rom(&bytes);
+        self.emulator.pause();
+        self.emulator.debugger.stop_reason = None;
+        self.emulator.cheats.clear();
+        // an ELF brings its own function names, but a .sym has source lines too
+        if let Some(symbols) = read_symbols(path).transpose()? {
+            self.emulator.debugger.symbols = symbols;
+        }
+        self.rom_path = Some(path.to_path_buf());
+        Ok(self.emulator.debugger.symbols.functions().len())
+    }
+
+    fn quit(&self) {
+        let menu = MainMenu::init(self.tx.clone());
+        let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(menu)));
+    }
+
+    fn is_running(&self) -> bool {
+        self.emulator.play_state == PlayState::Playing
+    }
+
+    fn update_picker(&mut self, events: Vec<Event>) {
+        let Some(picker) = &mut self.picker else { return };
+        picker.update(events);
+        if picker.is_active() {
+            return;
+        }
+
+        let picked = picker.take_picked();
+        self.picker = None;
+
+        match picked {
+            Some(path) => {
+                self.status = match self.load(&path) {
+                    Ok(0) => format!("Loaded {} (no symbols), paused at reset", path.display()),
+                    Ok(n) => format!("Loaded {} with {} symbols, paused at reset", path.display(), n),
+                    Err(e) => e,
+                };
+            }
+            // nothing loaded yet, so there's nothing to debug
+            None if self.rom_path.is_none() => self.quit(),
+            None => {}
+        }
+    }
+
+    /// Parse a hex address, or for breakpoints, a function name
+    fn resolve(&self, prompt: &Prompt) -> Result<u16, String> {
+        if let Ok(address) = u16::from_str_radix(prompt.text.trim_start_matches('$'), 16) {
+            return Ok(address);
+        }
+        if prompt.kind != PromptKind::Breakpoint {
+            return Err(format!("\"{}\" isn't a hex address", prompt.text));
+        }
+
+        let symbols = &self.emulator.debugger.symbols;
+        if symbols.is_empty() {
+            return Err(format!("\"{}\" isn't a hex address, and there's no symbol file", prompt.text));
+        }
+        symbols.find(&prompt.text)
+            .map(|f| f.cpu_address())
+            .ok_or_else(|| format!("No function named \"{}\"", prompt.text))
+    }
+
+    /// Freeze or unfreeze an address at its current value, or add a cheat
+    /// code, replacing freezes at the same addresses
+    fn freeze(&mut self, text: &str) -> Result<String, String> {
+        let cheats = &mut self.emulator.cheats;
+        if !text.contains(':') {
+            let address = u16::from_str_radix(text, 16).map_err(|_| format!("\"{}\" isn't a hex address", text))?;
+            if cheats.iter().any(|c| c.address == address) {
+                cheats.retain(|c| c.address != address);
+                return Ok(format!("Unfroze ${:04X}", address));
+            }
+            let cheat = Cheat::freeze(address, self.emulator.peek(address))?;
+            self.emulator.cheats.push(cheat);
+            return Ok(format!("Froze ${:04X} at ${:02X}", address, cheat.value));
+        }
+
+        let new = Cheat::parse(text)?;
+        cheats.retain(|c| new.iter().all(|n| n.address != c.address));
+        cheats.extend(&new);
+        let codes: Vec<String> = new.iter().map(Cheat::to_string).collect();
+        Ok(format!("Froze {}", codes.join(" ")))
+    }
+
+    fn submit_prompt(&mut self, prompt: Prompt) {
+        if prompt.kind == PromptKind::Freeze {
+            self.status = self.freeze(&prompt.text).unwrap_or_else(|e| e);
+            return;
+        }
+
+        let address = match self.resolve(&prompt) {
+            Ok(address) => address,
+            Err(e) => {
+                self.status = e;
+                return;
+            }
+        };
+
+        let label = self.label(address);
+        let debugger = &mut self.emulator.debugger;
+        self.status = match prompt.kind {
+            PromptKind::Breakpoint => {
+                if debugger.toggle_breakpoint(address) {
+                    format!("Breakpoint set at {}", label)
+                } else {
+                    format!("Breakpoint at {} cleared", label)
+                }
+            }
+            PromptKind::Watch(kind) => {
+                if debugger.watchpoints().iter().any(|w| w.address == address && w.kind == kind) {
+                    debugger.remove_watchpoint(address);
+                    format!("Watchpoint at ${:04X} cleared", address)
+                } else {
+                    debugger.add_watchpoint(address, kind);
+                    format!("Watching ${:04X}", address)
+                }
+            }
+            PromptKind::Memory => {
+                self.memory_addr = address & !7;
+                format!("Showing memory at ${:04X}", self.memory_addr)
+            }
+            PromptKind::Freeze => unreachable!("freezes aren't addresses"),
+        };
+    }
+
+    fn update_prompt(&mut self, code: KeyCode) {
+        let Some(prompt) = &mut self.prompt else { return };
+        match code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                if let Some(prompt) = self.prompt.take() {
+                    self.submit_prompt(prompt);
+                }
+            }
+            KeyCode::Backspace => { prompt.text.pop(); }
+            KeyCode::Char(c) => {
+                let accepted = prompt.accept(c);
+                prompt.text.extend(accepted);
+            }
+            _ => {}
+        }
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        self.prompt = Some(Prompt { kind, text: String::new() });
+    }
+
+    fn handle_key(&mut self, key: DebuggerKey) {
+        match key {
+            DebuggerKey::Quit => self.quit(),
+            DebuggerKey::RunPause => {
+                if self.is_running() {
+                    self.emulator.pause();
+                    self.status = "Paused".to_string();
+                } else {
+                    self.emulator.resume();
+                    self.status = "Running".to_string();
+                }
+            }
+            DebuggerKey::Step => {
+                self.emulator.step_instruction();
+                self.status = match self.emulator.debugger.stop_reason {
+                    Some(StopReason::Step) | None => format!("Stepped to {}", self.label(self.emulator.registers().pc)),
+                    Some(reason) => self.describe(reason),
+                };
+                self.last_stop = self.emulator.debugger.stop_reason;
+            }
+            DebuggerKey::Breakpoint => self.open_prompt(PromptKind::Breakpoint),
+            DebuggerKey::WatchWrite => self.open_prompt(PromptKind::Watch(WatchKind::Write)),
+            DebuggerKey::WatchRead => self.open_prompt(PromptKind::Watch(WatchKind::Read)),
+            DebuggerKey::Memory => self.open_prompt(PromptKind::Memory),
+            DebuggerKey::Freeze => self.open_prompt(PromptKind::Freeze),
+            DebuggerKey::Clear => {
+                self.emulator.debugger.clear();
+                self.emulator.cheats.clear();
+                self.status = "Cleared all breakpoints, watchpoints and freezes".to_string();
+            }
+            DebuggerKey::Open => {
+                let dir = self.rom_path.as_ref()
+                    .and_then(|p| p.parent().map(Path::to_path_buf))
+                    .unwrap_or_default();
+                self.picker = Some(FilePicker::init(PickerMode::Open, "gtr", &dir));
+            }
+            DebuggerKey::MemoryUp => self.memory_addr = self.memory_addr.wrapping_sub(8 * MEMORY_ROWS),
+            DebuggerKey::MemoryDown => self.memory_addr = self.memory_addr.wrapping_add(8 * MEMORY_ROWS),
+        }
+    }
+
+    /// `$C123 (game::update+0x4)`, or just the address without a symbol
+    fn label(&self, address: u16) -> String {
+        let linked = linked_address(address, self.emulator.current_bank());
+        match self.emulator.debugger.symbols.describe(linked) {
+            Some(name) => format!("${:04X} ({})", address, name),
+            None => format!("${:04X}", address),
+        }
+    }
+
+    fn describe(&self, reason: StopReason) -> String {
+        match reason {
+            StopReason::Breakpoint(pc) => format!("Hit breakpoint at {}", self.label(pc)),
+            StopReason::Watchpoint { address, value, write: true } => format!("Wrote ${:02X} to ${:04X}", value, address),
+            StopReason::Watchpoint { address, value, write: false } => format!("Read ${:02X} from ${:04X}", value, address),
+            StopReason::Step => "Stepped".to_string(),
+        }
+    }
+
+    fn cpu_lines(&self) -> Vec<Line<'static>> {
+        let r = self.emulator.registers();
+        let flags: String = "NV-BDIZC".chars().enumerate()
+            .map(|(i, c)| if r.p & (0x80 >> i) != 0 { c } else { '·' })
+            .collect();
+        let next: Vec<String> = (0..4)
+            .map(|i| format!("{:02X}", self.emulator.peek(r.pc.wrapping_add(i))))
+            .collect();
+
+        let symbols = &self.emulator.debugger.symbols;
+        let linked = self.emulator.linked_pc();
+        let function = symbols.describe(linked).unwrap_or_default();
+        let source = symbols.line_at(linked)
+            .map(|s| format!("{}:{}", file_name(s.file), s.line))
+            .unwrap_or_default();
+
+        vec![
+            Line::from(format!("PC ${:04X}   S ${:02X}", r.pc, r.s)),
+            Line::from(format!("A  ${:02X}  X ${:02X}  Y ${:02X}", r.a, r.x, r.y)),
+            Line::from(format!("P  {}", flags)),
+            Line::from(format!("@PC {}", next.join(" "))).fg(SCHEME.gray[2]),
+            Line::from(function).fg(SCHEME.orange[1]),
+            Line::from(source).fg(SCHEME.gray[2]),
+        ]
+    }
+
+    fn stack_lines(&self) -> Vec<Line<'static>> {
+        let symbols = &self.emulator.debugger.symbols;
+        self.emulator.backtrace().into_iter().enumerate().map(|(i, address)| {
+            let name = symbols.describe(address).unwrap_or_else(|| "?".to_string());
+            let line = Line::from(format!("{:04X} {}", address as u16, name));
+            if i == 0 { line.fg(SCHEME.orange[1]) } else { line }
+        }).collect()
+    }
+
+    fn memory_lines(&self) -> Vec<Line<'static>> {
+        let watched: Vec<u16> = self.emulator.debugger.watchpoints().iter().map(|w| w.address).collect();
+
+        (0..MEMORY_ROWS).map(|row| {
+            let base = self.memory_addr.wrapping_add(row * 8);
+            let mut spans = vec![Span::from(format!("{:04X} ", base)).fg(SCHEME.gray[2])];
+            for i in 0..8 {
+                let addr = base.wrapping_add(i);
+                let byte = Span::from(format!(" {:02X}", self.emulator.peek(addr)));
+                spans.push(if watched.contains(&addr) { byte.fg(SCHEME.orange[1]).bold() } else { byte });
+            }
+            Line::from(spans)
+        }).collect()
+    }
+
+    fn point_lines(&self) -> Vec<Line<'static>> {
+        let debugger = &self.emulator.debugger;
+        let mut lines: Vec<Line> = debugger.breakpoints().iter()
+            .map(|&b| Line::from(format!("● {}", self.label(b))).fg(SCHEME.red[1]))
+            .collect();
+        lines.extend(debugger.watchpoints().iter().map(|w| {
+            let kind = match w.kind {
+                WatchKind::Read => "read",
+                WatchKind::Write => "write",
+                WatchKind::ReadWrite => "read/write",
+            };
+            Line::from(format!("◆ ${:04X} {}", w.address, kind)).fg(SCHEME.blue[1])
+        }));
+        lines.extend(self.emulator.cheats.iter().map(|c| Line::from(format!("❄ {}", c)).fg(SCHEME.purple[1])));
+
+        if lines.is_empty() {
+            lines.push(Line::from("none").fg(SCHEME.gray[2]));
+        }
+        lines
+    }
+}
+
+/// Last part of a source path, to fit the side panel
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+impl Component for Debugger {
+    fn update(&mut self, events: Vec<Event>) {
+        if self.picker.is_some() {
+            self.update_picker(events);
+        } else {
+            for e in events {
+                if self.prompt.is_some() {
+                    let Event::Key(KeyEvent { code, .. }) = e else { continue };
+                    self.update_prompt(code);
+                } else if let Some(key) = self.keys.action(&e) {
+                    self.handle_key(key);
+                }
+            }
+        }
+
+        if self.is_running() {
+            self.emulator.process_cycles(false);
+        }
+
+        let stop = self.emulator.debugger.stop_reason;
+        if stop != self.last_stop {
+            if let Some(reason) = stop {
+                self.status = self.describe(reason);
+            }
+            self.last_stop = stop;
+        }
+    }
+
+    fn help(&self) -> Vec<Help> {
+        vec![self.keys.help()]
+    }
+
+    fn captures_text(&self) -> bool {
+        self.picker.is_some() || self.prompt.is_some()
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let panel = |title: &'static str| Block::bordered()
+            .title(title)
+            .title_style(style.bold().fg(SCHEME.orange[1]))
+            .border_set(border::ROUNDED)
+            .style(style);
+
+        let [main, footer] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [screen_area, side] = Layout::horizontal([Constraint::Fill(1), Constraint::Length(34)]).areas(main);
+        let [cpu_area, mem_area, points_area, stack_area] = Layout::vertical([
+            Constraint::Length(8),
+            Constraint::Length(MEMORY_ROWS + 2),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ]).areas(side);
+
+        let title = match &self.rom_path {
+            Some(path) => format!(" {} ", path.file_name().unwrap_or_default().to_string_lossy()),
+            None => " Debugger ".to_string(),
+        };
+        let screen_block = panel(" Screen ").title(Line::from(title).right_aligned());
+        let inner = screen_block.inner(screen_area);
+        frame.render_widget(screen_block, screen_area);
+
+        let (w, h) = FramebufferView::size_in(inner);
+        let fb_area = Rect::new(inner.x + inner.width.saturating_sub(w) / 2, inner.y, w.min(inner.width), h.min(inner.height));
+        frame.render_widget(FramebufferView::new(&self.emulator.cpu_bus.read_full_framebuffer()).palette(self.emulator.color_map()), fb_area);
+
+        frame.render_widget(Paragraph::new(self.cpu_lines()).block(panel(" CPU ")), cpu_area);
+        frame.render_widget(Paragraph::new(self.memory_lines()).block(panel(" Memory ")), mem_area);
+        frame.render_widget(Paragraph::new(self.point_lines()).block(panel(" Breakpoints ")), points_area);
+        frame.render_widget(Paragraph::new(self.stack_lines()).block(panel(" Stack ")), stack_area);
+
+        let footer_line = match &self.prompt {
+            Some(prompt) => {
+                let sigil = if matches!(prompt.kind, PromptKind::Breakpoint | PromptKind::Freeze) { "" } else { "$" };
+                Line::from(format!(" {} {}{}_", prompt.kind.label(), sigil, prompt.text)).fg(SCHEME.white[0])
+            }
+            None => {
+                let state = if self.is_running() { "RUN " } else { "STOP" };
+                Line::from(vec![
+                    Span::from(format!(" {} ", state)).bold().fg(SCHEME.orange[1]),
+                    Span::from(self.status.clone()),
+                    Span::from(format!("  {} ?:keys", self.keys.hints(&["run_pause", "step", "breakpoint", "watch_write", "watch_read", "memory", "freeze", "clear", "open", "quit"]))).fg(SCHEME.gray[2]),
+                ])
+            }
+        };
+        frame.render_widget(footer_line, footer);
+
+        if let Some(picker) = &mut self.picker {
+            picker.render(frame, frame.area());
+        }
+    }
+}
@@ -0,0 +1,647 @@
+use std::{collections::{HashMap, VecDeque}, io::stdout, path::{Path, PathBuf}, time::{Duration, Instant}};
+
+use crossbeam_channel::Sender;
+use gametank_sdk::capture::{numbered_path, save_capture, save_png};
+use gametank_sdk::link::{NetLink, DEFAULT_PORT};
+use gte_core::{color_map::Palette, emulator::{next_speed, AcpAccuracy, Emulator, PlayState, FAST_FORWARD_SPEED, REWIND_FRAMES}, inputs::{ControllerButton, InputCommand, KeyState}, movie::Movie, script::{Script, ScriptOutcome}};
+use klingt::CpalDevice;
+use ratatui::{crossterm::{event::{Event, KeyEvent, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags}, execute, terminal::supports_keyboard_enhancement}, layout::{Constraint, Layout, Rect}, style::{Color, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, Paragraph}, Frame};
+
+use crate::{audio::GameTankAudio, helpers::{read_rom, InstantClock, SCHEME}, keymap::{Help, Keymap}, main_menu::MainMenu, ui::{file_picker::{FilePicker, PickerMode}, framebuffer::FramebufferView}, Component, GlobalEvent};
+
+/// Without key release events, a button counts as held for this long after
+/// its last press (terminal key repeat keeps it alive)
+const HOLD_TIME: Duration = Duration::from_millis(150);
+
+/// Frames stepped back per Backspace. Terminal key repeat is about 30 a
+/// second, so holding it rewinds at roughly double speed.
+const REWIND_STEP: usize = 4;
+
+/// A palette to start with: a built-in one's name, or a `.pal` file
+const PALETTE_VAR: &str = "GAMETANK_PALETTE";
+
+/// Where joining a link connects, unless `GAMETANK_LINK` says otherwise
+const LINK_ADDRESS: &str = "127.0.0.1";
+
+/// Lines of the ROM's debug port output kept for the log pane
+const LOG_LINES: usize = 200;
+
+/// Width of the log pane, when the ROM has printed anything
+const LOG_WIDTH: u16 = 40;
+
+const BUTTONS: [ControllerButton; 8] = [
+    ControllerButton::Up,
+    ControllerButton::Down,
+    ControllerButton::Left,
+    ControllerButton::Right,
+    ControllerButton::A,
+    ControllerButton::B,
+    ControllerButton::C,
+    ControllerButton::Start,
+];
+
+#[derive(Clone, Copy)]
+enum EmuKey {
+    Pad(ControllerButton),
+    Quit,
+    Pause,
+    FrameAdvance,
+    FastForward,
+    Slower,
+    Faster,
+    Reset,
+    Rewind,
+    AcpTiming,
+    Open,
+    Script,
+    Movie,
+    PlayMovie,
+    Screenshot,
+    CapturePng,
+    CaptureGif,
+    LinkHost,
+    LinkJoin,
+    Palette,
+}
+
+/// The pad is the same layout as gte: arrows, Z/X/C for A/B/C, Enter for Start
+fn keymap() -> Keymap<EmuKey> {
+    Keymap::new("emulator", &[
+        ("pad_up", EmuKey::Pad(ControllerButton::Up), &["up"], "pad up"),
+        ("pad_down", EmuKey::Pad(ControllerButton::Down), &["down"], "pad down"),
+        ("pad_left", EmuKey::Pad(ControllerButton::Left), &["left"], "pad left"),
+        ("pad_right", EmuKey::Pad(ControllerButton::Right), &["right"], "pad right"),
+        ("pad_a", EmuKey::Pad(ControllerButton::A), &["z"], "pad A"),
+        ("pad_b", EmuKey::Pad(ControllerButton::B), &["x"], "pad B"),
+        ("pad_c", EmuKey::Pad(ControllerButton::C), &["c"], "pad C"),
+        ("pad_start", EmuKey::Pad(ControllerButton::Start), &["enter"], "pad Start"),
+        ("pause", EmuKey::Pause, &["p"], "pause"),
+        ("frame_advance", EmuKey::FrameAdvance, &["."], "step a frame"),
+        ("fast_forward", EmuKey::FastForward, &["tab"], "fast-forward"),
+        ("slower", EmuKey::Slower, &["-"], "slower"),
+        ("faster", EmuKey::Faster, &["="], "faster"),
+        ("reset", EmuKey::Reset, &["r"], "reset"),
+        ("rewind", EmuKey::Rewind, &["backspace"], "rewind"),
+        ("acp_timing", EmuKey::AcpTiming, &["a"], "acp timing"),
+        ("palette", EmuKey::Palette, &["v"], "next palette"),
+        ("open", EmuKey::Open, &["o"], "open"),
+        ("script", EmuKey::Script, &["s"], "script"),
+        ("movie", EmuKey::Movie, &["f5"], "record movie"),
+        ("play_movie", EmuKey::PlayMovie, &["f6"], "play movie"),
+        ("screenshot", EmuKey::Screenshot, &["f12"], "screenshot"),
+        ("capture_png", EmuKey::CapturePng, &["f10"], "capture apng"),
+        ("capture_gif", EmuKey::CaptureGif, &["f9"], "capture gif"),
+        ("link_host", EmuKey::LinkHost, &["h"], "host netplay (link)"),
+        ("link_join", EmuKey::LinkJoin, &["j"], "join netplay (link)"),
+        ("quit", EmuKey::Quit, &["esc", "q"], "quit"),
+    ])
+}
+
+/// What the open file picker is choosing
+#[derive(Clone, Copy, Default)]
+enum Picking {
+    #[default]
+    Rom,
+    Script,
+    Movie,
+}
+
+/// Play a ROM in the terminal
+pub struct EmulatorScreen {
+    tx: Sender<GlobalEvent>,
+    emulator: Emulator<InstantClock>,
+    audio: Option<GameTankAudio>,
+    rom_path: Option<PathBuf>,
+    picker: Option<FilePicker>,
+    picking: Picking,
+    /// Drives controller 1 one frame per update instead of the keyboard
+    script: Option<Script>,
+    /// Whether the terminal reports key releases
+    has_releases: bool,
+    /// When each held button was last pressed
+    held: HashMap<ControllerButton, Instant>,
+    keys: Keymap<EmuKey>,
+    /// Extension of the capture being recorded
+    recording: Option<&'static str>,
+    /// Netplay, through the link port
+    link: Option<NetLink>,
+    /// What the ROM printed to the debug port, oldest first
+    log: VecDeque<String>,
+    status: String,
+}
+
+impl EmulatorScreen {
+    pub fn init(tx: Sender<GlobalEvent>) -> Self {
+        let target_sample_rate = CpalDevice::default_output()
+            .map(|d| d.sample_rate() as f64)
+            .unwrap_or(48_000.0);
+        let mut emulator = Emulator::init(InstantClock::default(), target_sample_rate);
+        emulator.enable_rewind(REWIND_FRAMES);
+
+        let has_releases = matches!(supports_keyboard_enhancement(), Ok(true))
+            && execute!(stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)).is_ok();
+
+        let dir = std::env::current_dir().unwrap_or_default();
+        let status = load_palette(&mut emulator).err().unwrap_or_default();
+
+        Self {
+            tx,
+            emulator,
+            audio: None,
+            rom_path: None,
+            picker: Some(FilePicker::init(PickerMode::Open, "gtr", &dir)),
+            picking: Picking::Rom,
+            script: None,
+            has_releases,
+            held: HashMap::new(),
+            keys: keymap(),
+            recording: None,
+            link: None,
+            log: VecDeque::new(),
+            status,
+        }
+    }
+
+    /// Start playing `path` straight away, without the file picker
+    pub fn with_rom(tx: Sender<GlobalEvent>, path: &Path) -> Self {
+        let mut screen = Self::init(tx);
+        screen.picker = None;
+        if let Err(e) = screen.load(path) {
+            screen.status = e;
+        }
+        screen
+    }
+
+    fn load(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = read_rom(path)?;
+        // loading a ROM ends the movie, so keep what was recorded
+        self.save_movie();
+        self.emulator.load_rom(&bytes);
+        self.emulator.resume();
+        self.rom_path = Some(path.to_path_buf());
+        self.log.clear();
+        Ok(())
+    }
+
+    /// Restart the ROM under `path`'s script, so its frame 0 is the first
+    /// frame after boot
+    fn load_script(&mut self, path: &Path) -> Result<(), String> {
+        let Some(rom_path) = self.rom_path.clone() else {
+            return Err("Open a ROM before a script".to_string());
+        };
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let script = Script::compile(&source)?;
+
+        self.load(&rom_path)?;
+        self.held.clear();
+        self.script = Some(script);
+        Ok(())
+    }
+
+    /// Run one frame under the script, stopping it when it passes, fails or
+    /// errors
+    fn update_script(&mut self) {
+        let Some(script) = &mut self.script else { return };
+
+        let result = script.before_frame(&mut self.emulator);
+        if let Some(line) = script.take_log().pop() {
+            self.status = line;
+        }
+        let ended = match (result, script.outcome()) {
+            (Err(e), _) => Some(e),
+            (Ok(()), Some(ScriptOutcome::Passed)) => Some(format!("Script passed on frame {}", script.frame() - 1)),
+            (Ok(()), Some(ScriptOutcome::Failed(reason))) => Some(format!("Script failed on frame {}: {}", script.frame() - 1, reason)),
+            (Ok(()), None) => None,
+        };
+
+        match ended {
+            Some(status) => {
+                self.status = status;
+                self.script = None;
+                self.emulator.pause();
+            }
+            None => self.emulator.run_frame(),
+        }
+    }
+
+    /// Play the movie at `path` from its start, instead of the keyboard
+    fn load_movie(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let movie = Movie::from_bytes(&bytes)
+            .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+
+        self.script = None;
+        self.emulator.play_movie(movie)?;
+        self.emulator.resume();
+        Ok(())
+    }
+
+    /// Start recording a movie from power-on, or stop and save it
+    fn toggle_movie(&mut self) {
+        if !self.emulator.is_recording_movie() {
+            self.script = None;
+            self.emulator.record_movie(true);
+            self.emulator.resume();
+            self.status = "Recording movie ".to_string();
+            return;
+        }
+        self.save_movie();
+    }
+
+    /// Save the movie being recorded, if there is one
+    fn save_movie(&mut self) {
+        if !self.emulator.is_recording_movie() {
+            return;
+        }
+        let Some(movie) = self.emulator.stop_movie() else { return };
+        let path = self.capture_path("gtm");
+        self.status = match std::fs::write(&path, movie.to_bytes()) {
+            Ok(()) => format!("Saved {} ({} frames)", path.display(), movie.len()),
+            Err(e) => format!("Failed to write {}: {}", path.display(), e),
+        };
+    }
+
+    /// File names for screenshots and captures, after the ROM
+    fn capture_path(&self, ext: &str) -> PathBuf {
+        let stem = self.rom_path.as_ref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "gtgo".to_string());
+        numbered_path(Path::new("."), &stem, ext)
+    }
+
+    fn screenshot(&mut self) {
+        let path = self.capture_path("png");
+        self.status = match save_png(&self.emulator.screenshot(), &path) {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(e) => e,
+        };
+    }
+
+    /// Start capturing as `ext` ("png" or "gif"), or stop and save
+    fn toggle_capture(&mut self, ext: &'static str) {
+        let Some(ext) = self.recording.take() else {
+            self.emulator.start_capture();
+            self.recording = Some(ext);
+            self.status = "Capturing ".to_string();
+            return;
+        };
+
+        let frames = self.emulator.stop_capture();
+        let path = self.capture_path(ext);
+        self.status = match save_capture(&frames, &path) {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(e) => e,
+        };
+    }
+
+    /// Host or join a link with another emulator, or close the one that's open
+    fn toggle_link(&mut self, host: bool) {
+        if self.link.take().is_some() {
+            self.emulator.cpu_bus.system_control.link.disconnect();
+            self.status = "Link closed".to_string();
+            return;
+        }
+
+        let result = if host {
+            NetLink::host(DEFAULT_PORT)
+        } else {
+            NetLink::join(&std::env::var("GAMETANK_LINK").unwrap_or_else(|_| LINK_ADDRESS.to_string()))
+        };
+        match result {
+            Ok(link) => {
+                self.status = link.status();
+                self.link = Some(link);
+            }
+            Err(e) => self.status = e,
+        }
+    }
+
+    /// Trade bytes with the other emulator, if there's a link
+    fn update_link(&mut self) {
+        let Some(link) = &mut self.link else { return };
+        let was_connected = link.is_connected();
+
+        match link.pump(&mut self.emulator.cpu_bus.system_control.link) {
+            Ok(()) if link.is_connected() && !was_connected => self.status = link.status(),
+            Ok(()) => {}
+            Err(e) => {
+                self.status = format!("Link closed: {}", e);
+                self.link = None;
+            }
+        }
+    }
+
+    fn quit(&self) {
+        let menu = MainMenu::init(self.tx.clone());
+        let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(menu)));
+    }
+
+    fn update_picker(&mut self, events: Vec<Event>) {
+        let Some(picker) = &mut self.picker else { return };
+
+        // with release events turned on, every key would otherwise count twice
+        let presses = events.into_iter()
+            .filter(|e| !matches!(e, Event::Key(KeyEvent { kind: KeyEventKind::Release, .. })))
+            .collect();
+        picker.update(presses);
+        if picker.is_active() {
+            return;
+        }
+
+        let picked = picker.take_picked();
+        self.picker = None;
+        let picking = std::mem::take(&mut self.picking);
+
+        match (picked, picking) {
+            (Some(path), Picking::Script) => {
+                self.status = match self.load_script(&path) {
+                    Ok(()) => format!("Running {}", path.display()),
+                    Err(e) => e,
+                };
+            }
+            (Some(path), Picking::Movie) => {
+                self.status = match self.load_movie(&path) {
+                    Ok(()) => format!("Playing {}", path.display()),
+                    Err(e) => e,
+                };
+            }
+            (Some(path), Picking::Rom) => {
+                self.script = None;
+                self.status = match self.load(&path) {
+                    Ok(()) => String::new(),
+                    Err(e) => e,
+                };
+            }
+            (None, _) if self.rom_path.is_none() => self.quit(),
+            (None, _) => {}
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let Some(action) = self.keys.lookup(&key) else { return };
+
+        if let EmuKey::Pad(button) = action {
+            match key.kind {
+                KeyEventKind::Release => { self.held.remove(&button); }
+                _ => { self.held.insert(button, Instant::now()); }
+            }
+            return;
+        }
+
+        // held where the terminal reports releases, toggled where it doesn't
+        if let EmuKey::FastForward = action {
+            let on = match key.kind {
+                KeyEventKind::Release => false,
+                _ if self.has_releases => true,
+                _ if key.kind == KeyEventKind::Repeat => return,
+                _ => !self.emulator.is_fast_forwarding(),
+            };
+            self.emulator.set_input_state(InputCommand::FastForward, KeyState::new(on));
+            return;
+        }
+
+        if key.kind == KeyEventKind::Release {
+            return;
+        }
+
+        match action {
+            EmuKey::Pad(_) => {}
+            EmuKey::Quit => self.quit(),
+            EmuKey::Pause => {
+                if self.emulator.play_state == PlayState::Playing {
+                    self.emulator.pause();
+                } else {
+                    self.emulator.resume();
+                }
+            }
+            EmuKey::FrameAdvance => self.emulator.frame_advance(),
+            EmuKey::Slower | EmuKey::Faster => {
+                let speed = next_speed(self.emulator.speed(), matches!(action, EmuKey::Faster));
+                self.emulator.set_speed(speed);
+                self.status = format!("Speed {}x ", speed);
+            }
+            EmuKey::Reset => {
+                // a reset can't be replayed, so it ends a movie
+                self.save_movie();
+                self.emulator.stop_movie();
+                self.emulator.cpu.reset();
+            }
+            EmuKey::Screenshot => self.screenshot(),
+            EmuKey::LinkHost => self.toggle_link(true),
+            EmuKey::LinkJoin => self.toggle_link(false),
+            EmuKey::CapturePng => self.toggle_capture("png"),
+            EmuKey::CaptureGif => self.toggle_capture("gif"),
+            EmuKey::Movie if self.rom_path.is_some() => self.toggle_movie(),
+            EmuKey::PlayMovie if self.emulator.is_playing_movie() => {
+                self.emulator.stop_movie();
+                self.status = "Movie stopped".to_string();
+            }
+            EmuKey::PlayMovie if self.rom_path.is_some() => {
+                let dir = std::env::current_dir().unwrap_or_default();
+                self.picker = Some(FilePicker::init(PickerMode::Open, "gtm", &dir));
+                self.picking = Picking::Movie;
+            }
+            EmuKey::Rewind => {
+                // key repeat keeps it going while held
+                let rewound = self.emulator.rewind(REWIND_STEP);
+                self.status = if rewound == 0 { "nothing left to rewind ".to_string() } else { String::new() };
+            }
+            EmuKey::AcpTiming => {
+                self.emulator.acp_accuracy = match self.emulator.acp_accuracy {
+                    AcpAccuracy::Accurate => AcpAccuracy::Fast,
+                    AcpAccuracy::Fast => AcpAccuracy::Accurate,
+                };
+            }
+            EmuKey::Palette => {
+                let palette = self.emulator.palette().map_or(Palette::default(), Palette::next);
+                self.emulator.set_palette(palette);
+                self.status = format!("Palette: {} ", palette.name());
+            }
+            EmuKey::Open => {
+                let dir = self.rom_path.as_ref()
+                    .and_then(|p| p.parent().map(Path::to_path_buf))
+                    .unwrap_or_default();
+                self.picker = Some(FilePicker::init(PickerMode::Open, "gtr", &dir));
+            }
+            EmuKey::Script if self.script.is_some() => {
+                self.script = None;
+                self.status = "Script stopped".to_string();
+            }
+            EmuKey::Script => {
+                let dir = std::env::current_dir().unwrap_or_default();
+                self.picker = Some(FilePicker::init(PickerMode::Open, "rhai", &dir));
+                self.picking = Picking::Script;
+            }
+            _ => {}
+        }
+    }
+
+    /// Feed the held buttons to controller 1
+    fn update_gamepad(&mut self) {
+        if !self.has_releases {
+            self.held.retain(|_, pressed| pressed.elapsed() < HOLD_TIME);
+        }
+
+        for button in BUTTONS {
+            let command = InputCommand::Controller1(button);
+            let previous = self.emulator.input_state.get(&command).copied().unwrap_or(KeyState::Released);
+            let state = previous.update_state(self.held.contains_key(&button));
+            self.emulator.set_input_state(command, state);
+        }
+    }
+
+    /// Keep what the ROM printed for the log pane
+    fn update_log(&mut self) {
+        self.log.extend(self.emulator.take_debug_messages());
+        let excess = self.log.len().saturating_sub(LOG_LINES);
+        self.log.drain(..excess);
+    }
+
+    /// Forward the emulator's audio to the output device, the same way gte does
+    fn update_audio(&mut self) {
+        if self.audio.is_none() && self.emulator.audio_out.is_some() && CpalDevice::default_output().is_ok() {
+            self.audio = Some(GameTankAudio::new());
+        }
+
+        if let (Some(audio_out), Some(audio)) = (&mut self.emulator.audio_out, &mut self.audio) {
+            while let Ok(buf) = audio_out.output_buffer.pop() {
+                audio.push_buffer(buf);
+            }
+            audio.process_audio();
+        }
+    }
+}
+
+/// Use the palette `GAMETANK_PALETTE` names, if it's set
+fn load_palette(emulator: &mut Emulator<InstantClock>) -> Result<(), String> {
+    let Ok(name) = std::env::var(PALETTE_VAR) else { return Ok(()) };
+    if let Some(palette) = Palette::from_name(&name) {
+        emulator.set_palette(palette);
+        return Ok(());
+    }
+    let bytes = std::fs::read(&name).map_err(|e| format!("Failed to read palette {}: {}", name, e))?;
+    emulator.load_palette(&bytes).map_err(|e| format!("Failed to load palette {}: {}", name, e))
+}
+
+impl Drop for EmulatorScreen {
+    fn drop(&mut self) {
+        if self.has_releases {
+            let _ = execute!(stdout(), PopKeyboardEnhancementFlags);
+        }
+    }
+}
+
+impl Component for EmulatorScreen {
+    fn update(&mut self, events: Vec<Event>) {
+        if self.picker.is_some() {
+            self.update_picker(events);
+            return;
+        }
+
+        for e in events {
+            if let Event::Key(key) = e {
+                self.handle_key(key);
+            }
+        }
+
+        if self.script.is_some() {
+            if self.emulator.play_state == PlayState::Playing {
+                self.update_script();
+            }
+        } else {
+            self.update_gamepad();
+            self.emulator.process_cycles(false);
+        }
+        self.update_link();
+        self.update_audio();
+        self.update_log();
+    }
+
+    fn help(&self) -> Vec<Help> {
+        vec![self.keys.help()]
+    }
+
+    fn captures_text(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let [main, footer] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+
+        let info = self.emulator.rom_info().filter(|info| !info.title.is_empty());
+        let title = match (info, &self.rom_path) {
+            (Some(info), _) if info.rom_version.is_empty() => format!(" {} ", info.title),
+            (Some(info), _) => format!(" {} {} ", info.title, info.rom_version),
+            (None, Some(path)) => format!(" {} ", path.file_name().unwrap_or_default().to_string_lossy()),
+            (None, None) => " Emulator ".to_string(),
+        };
+        let block = Block::bordered()
+            .title(title)
+            .title_style(style.bold().fg(SCHEME.orange[1]))
+            .border_set(border::ROUNDED)
+            .style(style);
+        let mut inner = block.inner(main);
+        frame.render_widget(block, main);
+
+        if !self.log.is_empty() {
+            let [screen, log_area] = Layout::horizontal([Constraint::Fill(1), Constraint::Length(LOG_WIDTH)]).areas(inner);
+            let log_block = Block::bordered()
+                .title(" ROM log ")
+                .title_style(style.fg(SCHEME.gray[2]))
+                .border_set(border::ROUNDED)
+                .style(style);
+            let rows = log_block.inner(log_area).height as usize;
+            let lines: Vec<Line> = self.log.iter().skip(self.log.len().saturating_sub(rows)).map(|l| Line::from(l.as_str())).collect();
+            frame.render_widget(Paragraph::new(lines).block(log_block), log_area);
+            inner = screen;
+        }
+
+        let (w, h) = FramebufferView::size_in(inner);
+        let fb_area = Rect::new(
+            inner.x + inner.width.saturating_sub(w) / 2,
+            inner.y + inner.height.saturating_sub(h) / 2,
+            w.min(inner.width),
+            h.min(inner.height),
+        );
+        frame.render_widget(FramebufferView::new(&self.emulator.cpu_bus.read_full_framebuffer()).palette(self.emulator.color_map()), fb_area);
+
+        let state = match (self.emulator.play_state == PlayState::Playing, &self.script, self.emulator.movie_frame()) {
+            (true, Some(script), _) => format!("SCRIPT {}", script.frame()),
+            (true, None, Some(frame)) if self.emulator.is_recording_movie() => format!("REC {}", frame),
+            (true, None, Some(frame)) => format!("MOVIE {}", frame),
+            (true, None, None) => "PLAY ".to_string(),
+            (false, ..) => "PAUSE".to_string(),
+        };
+        let speed = match (self.emulator.is_fast_forwarding(), self.emulator.speed()) {
+            (true, _) => format!(">>{}x ", FAST_FORWARD_SPEED),
+            (false, speed) if speed != 1.0 => format!("{}x ", speed),
+            _ => String::new(),
+        };
+        let timing = match self.emulator.acp_accuracy {
+            AcpAccuracy::Accurate => "ACP accurate ",
+            AcpAccuracy::Fast => "ACP fast ",
+        };
+        let link = match &self.link {
+            Some(link) if link.is_connected() => "LINK ",
+            Some(_) => "LINK? ",
+            None => "",
+        };
+        let footer_line = Line::from(vec![
+            Span::from(format!(" {} ", state)).bold().fg(SCHEME.orange[1]),
+            Span::from(speed).bold().fg(SCHEME.yellow[1]),
+            Span::from(timing).fg(SCHEME.gray[2]),
+            Span::from(link).fg(SCHEME.purple[1]),
+            Span::from(self.status.clone()),
+            Span::from(format!("  {} ?:keys", self.keys.hints(&["pause", "frame_advance", "fast_forward", "reset", "rewind", "open", "script", "movie", "play_movie", "screenshot", "link_host", "quit"]))).fg(SCHEME.gray[2]),
+        ]);
+        frame.render_widget(footer_line, footer);
+
+        if let Some(picker) = &mut self.picker {
+            picker.render(frame, frame.area());
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crossbeam_channel::{Receiver, Sender};
+use gametank_sdk::flash::{self, Progress};
+use gte_core::rom_header::RomHeader;
+use ratatui::{crossterm::event::{Event, KeyEvent, KeyEventKind}, layout::{Constraint, Layout, Rect}, style::{Color, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, Gauge, List, ListItem, ListState, Paragraph}, Frame};
+
+use crate::{helpers::SCHEME, keymap::{Help, Keymap}, main_menu::MainMenu, ui::file_picker::{FilePicker, PickerMode}, Component, GlobalEvent};
+
+/// What the flashing thread reports back
+enum FlashEvent {
+    Progress(Progress),
+    Finished(Result<(), String>),
+}
+
+#[derive(Clone, Copy)]
+enum FlasherKey {
+    Quit,
+    Up,
+    Down,
+    Rescan,
+    Verify,
+    Open,
+    Flash,
+}
+
+fn keymap() -> Keymap<FlasherKey> {
+    Keymap::new("flasher", &[
+        ("up", FlasherKey::Up, &["up"], "previous port"),
+        ("down", FlasherKey::Down, &["down"], "next port"),
+        ("rescan", FlasherKey::Rescan, &["r"], "rescan"),
+        ("verify", FlasherKey::Verify, &["v"], "verify"),
+        ("open", FlasherKey::Open, &["o"], "open"),
+        ("flash", FlasherKey::Flash, &["enter", "f"], "flash"),
+        ("quit", FlasherKey::Quit, &["esc", "q"], "quit"),
+    ])
+}
+
+/// Write a ROM to a cartridge: pick a `.gtr` and the programmer's port,
+/// then watch it erase, write and verify
+pub struct Flasher {
+    tx: Sender<GlobalEvent>,
+    picker: Option<FilePicker>,
+    rom_path: Option<PathBuf>,
+    header: Option<RomHeader>,
+    rom: Vec<u8>,
+    ports: Vec<String>,
+    port_state: ListState,
+    verify: bool,
+    /// Events from the flashing thread, while it runs
+    job: Option<Receiver<FlashEvent>>,
+    progress: Option<Progress>,
+    keys: Keymap<FlasherKey>,
+    status: String,
+}
+
+impl Flasher {
+    pub fn init(tx: Sender<GlobalEvent>) -> Self {
+        let dir = std::env::current_dir().unwrap_or_default();
+
+        let mut flasher = Self {
+            tx,
+            picker: Some(FilePicker::init(PickerMode::Open, "gtr", &dir)),
+            rom_path: None,
+            header: None,
+            rom: vec![],
+            ports: vec![],
+            port_state: ListState::default(),
+            verify: true,
+            job: None,
+            progress: None,
+            keys: keymap(),
+            status: String::new(),
+        };
+        flasher.scan_ports();
+        flasher
+    }
+
+    /// Open with `path` already loaded, without the file picker
+    pub fn with_rom(tx: Sender<GlobalEvent>, path: &Path) -> Self {
+        let mut flasher = Self::init(tx);
+        flasher.picker = None;
+        if let Err(e) = flasher.load(path) {
+            flasher.status = e;
+        }
+        flasher
+    }
+
+    fn quit(&self) {
+        let menu = MainMenu::init(self.tx.clone());
+        let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(menu)));
+    }
+
+    fn is_flashing(&self) -> bool {
+        self.job.is_some()
+    }
+
+    fn load(&mut self, path: &Path) -> Result<(), String> {
+        let (header, rom) = flash::read_gtr(path)?;
+        self.header = header;
+        self.rom = rom;
+        self.rom_path = Some(path.to_path_buf());
+        self.progress = None;
+        Ok(())
+    }
+
+    /// List the USB serial ports again, keeping the selection if it's still there
+    fn scan_ports(&mut self) {
+        let selected = self.selected_port().map(str::to_string);
+        match flash::usb_ports() {
+            Ok(ports) => {
+                self.ports = ports;
+                self.status = match self.ports.len() {
+                    0 => "No USB serial ports found. Is the programmer plugged in?".to_string(),
+                    n => format!("Found {} USB serial port(s)", n),
+                };
+            }
+            Err(e) => {
+                self.ports.clear();
+                self.status = e;
+            }
+        }
+
+        let index = selected.and_then(|s| self.ports.iter().position(|p| *p == s));
+        self.port_state.select(index.or((!self.ports.is_empty()).then_some(0)));
+    }
+
+    fn selected_port(&self) -> Option<&str> {
+        self.port_state.selected().and_then(|i| self.ports.get(i)).map(String::as_str)
+    }
+
+    fn start(&mut self) {
+        let Some(port) = self.selected_port().map(str::to_string) else {
+            self.status = "Pick a port first (r to rescan)".to_string();
+            return;
+        };
+        if self.rom.is_empty() {
+            self.status = "Pick a ROM first (o to open)".to_string();
+            return;
+        }
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let rom = self.rom.clone();
+        let verify = self.verify;
+        thread::spawn(move || {
+            let result = flash::flash(&rom, &port, verify, |p| {
+                let _ = tx.send(FlashEvent::Progress(p));
+            });
+            let _ = tx.send(FlashEvent::Finished(result));
+        });
+
+        self.job = Some(rx);
+        self.progress = None;
+        self.status = "Connecting to the programmer...".to_string();
+    }
+
+    /// Take what the flashing thread has sent since the last frame
+    fn update_job(&mut self) {
+        let Some(job) = &self.job else { return };
+        let events: Vec<FlashEvent> = job.try_iter().collect();
+
+        for event in events {
+            match event {
+                FlashEvent::Progress(p) => {
+                    self.progress = Some(p);
+                    self.status = match p {
+                        Progress::Erasing => "Erasing cartridge...".to_string(),
+                        Progress::Writing { .. } => "Writing".to_string(),
+                        Progress::Verifying { .. } => "Verifying".to_string(),
+                    };
+                }
+                FlashEvent::Finished(result) => {
+                    self.job = None;
+                    self.status = match result {
+                        Ok(()) if self.verify => "Flash complete, all banks verified".to_string(),
+                        Ok(()) => "Flash complete (not verified)".to_string(),
+                        Err(e) => e,
+                    };
+                }
+            }
+        }
+    }
+
+    fn update_picker(&mut self, events: Vec<Event>) {
+        let Some(picker) = &mut self.picker else { return };
+
+        let presses = events.into_iter()
+            .filter(|e| !matches!(e, Event::Key(KeyEvent { kind: KeyEventKind::Release, .. })))
+            .collect();
+        picker.update(presses);
+        if picker.is_active() {
+            return;
+        }
+
+        let picked = picker.take_picked();
+        self.picker = None;
+
+        match picked {
+            Some(path) => {
+                self.status = match self.load(&path) {
+                    Ok(()) => format!("Loaded {}", path.display()),
+                    Err(e) => e,
+                };
+            }
+            None if self.rom_path.is_none() => self.quit(),
+            None => {}
+        }
+    }
+
+    fn handle_key(&mut self, key: FlasherKey) {
+        // a half-written cartridge is worse than waiting
+        if self.is_flashing() {
+            return;
+        }
+
+        match key {
+            FlasherKey::Quit => self.quit(),
+            FlasherKey::Up => self.port_state.select_previous(),
+            FlasherKey::Down => self.port_state.select_next(),
+            FlasherKey::Rescan => self.scan_ports(),
+            FlasherKey::Verify => self.verify = !self.verify,
+            FlasherKey::Open => {
+                let dir = self.rom_path.as_ref()
+                    .and_then(|p| p.parent().map(Path::to_path_buf))
+                    .unwrap_or_default();
+                self.picker = Some(FilePicker::init(PickerMode::Open, "gtr", &dir));
+            }
+            FlasherKey::Flash => self.start(),
+        }
+    }
+
+    fn rom_lines(&self) -> Vec<Line<'static>> {
+        let Some(path) = &self.rom_path else {
+            return vec![Line::from("No ROM loaded").fg(SCHEME.gray[2])];
+        };
+
+        let mut lines = vec![Line::from(path.display().to_string())];
+        if let Some(header) = &self.header {
+            let version = if header.rom_version.is_empty() { String::new() } else { format!(" {}", header.rom_version) };
+            let by = if header.author.is_empty() { String::new() } else { format!(" by {}", header.author) };
+            lines.push(Line::from(format!("\"{}\"{}{}", header.title, version, by)).bold());
+        }
+        lines.push(Line::from(format!("{} KB, {} bank(s)", self.rom.len() / 1024, self.rom.len().div_ceil(0x4000))).fg(SCHEME.gray[2]));
+        lines
+    }
+
+    fn gauge(&self) -> Gauge<'static> {
+        let (ratio, label) = match self.progress {
+            None => (0.0, String::new()),
+            Some(Progress::Erasing) => (0.0, "erasing".to_string()),
+            Some(Progress::Writing { done, total, bank }) => {
+                (done as f64 / total.max(1) as f64, format!("write {}/{} (bank {:02X})", done, total, bank))
+            }
+            Some(Progress::Verifying { done, total, bank }) => {
+                (done as f64 / total.max(1) as f64, format!("verify {}/{} (bank {:02X})", done, total, bank))
+            }
+        };
+        Gauge::default()
+            .ratio(ratio.clamp(0.0, 1.0))
+            .label(label)
+            .gauge_style(SCHEME.style(Color::Rgb(36, 36, 36)).fg(SCHEME.orange[1]))
+    }
+}
+
+impl Component for Flasher {
+    fn update(&mut self, events: Vec<Event>) {
+        self.update_job();
+
+        if self.picker.is_some() {
+            self.update_picker(events);
+            return;
+        }
+
+        let keys: Vec<FlasherKey> = self.keys.actions(&events).collect();
+        for key in keys {
+            self.handle_key(key);
+        }
+    }
+
+    fn help(&self) -> Vec<Help> {
+        vec![self.keys.help()]
+    }
+
+    fn captures_text(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let panel = |title: &'static str| Block::bordered()
+            .title(title)
+            .title_style(style.bold().fg(SCHEME.orange[1]))
+            .border_set(border::ROUNDED)
+            .style(style);
+
+        let [main, footer] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [rom_area, port_area, progress_area] = Layout::vertical([
+            Constraint::Length(5),
+            Constraint::Fill(1),
+            Constraint::Length(3),
+        ]).areas(main);
+
+        frame.render_widget(Paragraph::new(self.rom_lines()).block(panel(" ROM ")), rom_area);
+
+        let verify = if self.verify { "[x] verify " } else { "[ ] verify " };
+        let ports: Vec<ListItem> = self.ports.iter().map(|p| ListItem::new(p.as_str())).collect();
+        let port_list = List::new(ports)
+            .block(panel(" Programmer Port ").title(Line::from(verify).right_aligned()))
+            .highlight_style(style.bold().fg(SCHEME.orange[1]))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(port_list, port_area, &mut self.port_state);
+
+        frame.render_widget(self.gauge().block(panel(" Progress ")), progress_area);
+
+        let state = if self.is_flashing() { "FLASHING" } else { "READY" };
+        let footer_line = Line::from(vec![
+            Span::from(format!(" {} ", state)).bold().fg(SCHEME.orange[1]),
+            Span::from(self.status.clone()),
+            Span::from(format!("  {} ?:keys", self.keys.hints(&["rescan", "verify", "open", "flash", "quit"]))).fg(SCHEME.gray[2]),
+        ]);
+        frame.render_widget(footer_line, footer);
+
+        if let Some(picker) = &mut self.picker {
+            picker.render(frame, frame.area());
+        }
+    }
+}
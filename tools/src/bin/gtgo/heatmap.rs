@@ -0,0 +1,434 @@
+use std::path::{Path, PathBuf};
+
+use crossbeam_channel::Sender;
+use gte_core::{emulator::{Emulator, PlayState}, heatmap::{Access, Heatmap}};
+use ratatui::{buffer::Buffer, crossterm::event::Event, layout::{Constraint, Layout, Rect}, style::{Color, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, Paragraph, Widget}, Frame};
+
+use crate::{helpers::{read_rom, InstantClock, SCHEME}, keymap::{Help, Keymap}, main_menu::MainMenu, ui::file_picker::{FilePicker, PickerMode}, Component, GlobalEvent};
+
+/// Tint of an address nothing touched
+const COLD: Color = Color::Rgb(48, 48, 48);
+
+/// Tints from least to most active
+const GRADIENT: [(u8, u8, u8); 4] = [(30, 50, 140), (200, 40, 40), (240, 200, 40), (255, 255, 230)];
+
+/// Addresses listed under Hottest
+const HOTTEST: usize = 12;
+
+/// Counted regions of the CPU address space, for the legend
+const REGIONS: [(&str, u16); 4] = [("RAM", 0x0000), ("I/O", 0x2000), ("VRAM", 0x4000), ("ROM", 0x8000)];
+
+/// `count` as a color between [`COLD`] and the top of [`GRADIENT`], on a log
+/// scale up to `max`
+fn heat_color(count: u32, max: u32) -> Color {
+    if count == 0 || max == 0 {
+        return COLD;
+    }
+    let t = ((count as f64).ln_1p() / (max as f64).ln_1p()).clamp(0.0, 1.0);
+    let scaled = t * (GRADIENT.len() - 1) as f64;
+    let i = (scaled as usize).min(GRADIENT.len() - 2);
+    let f = scaled - i as f64;
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * f) as u8;
+    let ((r0, g0, b0), (r1, g1, b1)) = (GRADIENT[i], GRADIENT[i + 1]);
+    Color::Rgb(mix(r0, r1), mix(g0, g1), mix(b0, b1))
+}
+
+/// Which counts are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Cpu(Access),
+    SpriteWrites,
+    BlitterReads,
+}
+
+const SOURCES: [Source; 5] = [
+    Source::Cpu(Access::Read),
+    Source::Cpu(Access::Write),
+    Source::Cpu(Access::Execute),
+    Source::SpriteWrites,
+    Source::BlitterReads,
+];
+
+impl Source {
+    fn index(self) -> usize {
+        SOURCES.iter().position(|&s| s == self).unwrap_or(0)
+    }
+
+    fn next(self) -> Self {
+        SOURCES[(self.index() + 1) % SOURCES.len()]
+    }
+
+    fn previous(self) -> Self {
+        SOURCES[(self.index() + SOURCES.len() - 1) % SOURCES.len()]
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Source::Cpu(access) => access.name(),
+            Source::SpriteWrites => "sprite RAM writes",
+            Source::BlitterReads => "blitter reads",
+        }
+    }
+}
+
+/// Counts for a 256x256 sprite page drawn with half-block characters, each
+/// cell the sum of the pixels it covers. Shrinks by whole steps to fit.
+struct PageHeat<'a> {
+    counts: &'a [u32; 256 * 256],
+}
+
+impl PageHeat<'_> {
+    fn step(area: Rect) -> u16 {
+        256u16.div_ceil(area.width.max(1)).max(256u16.div_ceil(area.height.max(1) * 2)).max(1)
+    }
+
+    fn block(&self, x: u16, y: u16, step: u16) -> u32 {
+        let mut sum = 0u32;
+        for row in y..(y + step).min(256) {
+            let start = row as usize * 256;
+            for &c in &self.counts[start + x as usize..start + (x + step).min(256) as usize] {
+                sum = sum.saturating_add(c);
+            }
+        }
+        sum
+    }
+}
+
+impl Widget for PageHeat<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let step = Self::step(area);
+        let (width, height) = (256u16.div_ceil(step), 256u16.div_ceil(step * 2));
+
+        let mut blocks = vec![0u32; width as usize * height as usize * 2];
+        for by in 0..height * 2 {
+            for bx in 0..width {
+                if by * step < 256 {
+                    blocks[(by * width + bx) as usize] = self.block(bx * step, by * step, step);
+                }
+            }
+        }
+        let max = blocks.iter().copied().max().unwrap_or(0);
+
+        for cy in 0..height.min(area.height) {
+            for cx in 0..width.min(area.width) {
+                let top = blocks[(cy * 2 * width + cx) as usize];
+                let bottom = blocks[((cy * 2 + 1) * width + cx) as usize];
+                if let Some(cell) = buf.cell_mut((area.x + cx, area.y + cy)) {
+                    cell.set_char('▀')
+                        .set_fg(heat_color(top, max))
+                        .set_bg(heat_color(bottom, max));
+                }
+            }
+        }
+    }
+}
+
+/// A 16x16 grid of counts, two cells each, with the selected one marked and
+/// `label` naming each row
+fn grid_lines(counts: &[u32], selected: Option<usize>, label: impl Fn(usize) -> String) -> Vec<Line<'static>> {
+    let max = counts.iter().copied().max().unwrap_or(0);
+    counts.chunks(16).enumerate().map(|(row, cells)| {
+        let mut spans = vec![Span::from(label(row)).fg(SCHEME.gray[2])];
+        for (col, &count) in cells.iter().enumerate() {
+            let color = heat_color(count, max);
+            if selected == Some(row * 16 + col) {
+                spans.push(Span::from("[]").bold().fg(Color::White).bg(color));
+            } else {
+                spans.push(Span::from("  ").bg(color));
+            }
+        }
+        Line::from(spans)
+    }).collect()
+}
+
+#[derive(Clone, Copy)]
+enum HeatmapKey {
+    Quit,
+    Pause,
+    NextFrame,
+    NextSource,
+    PreviousSource,
+    Up,
+    Down,
+    Left,
+    Right,
+    Page(u8),
+    Clear,
+    Open,
+}
+
+fn keymap() -> Keymap<HeatmapKey> {
+    Keymap::new("heatmap", &[
+        ("next_source", HeatmapKey::NextSource, &["tab"], "next counts"),
+        ("previous_source", HeatmapKey::PreviousSource, &["backtab"], "previous counts"),
+        ("up", HeatmapKey::Up, &["up"], "page up a row"),
+        ("down", HeatmapKey::Down, &["down"], "page down a row"),
+        ("left", HeatmapKey::Left, &["left"], "previous page"),
+        ("right", HeatmapKey::Right, &["right"], "next page"),
+        ("page_0", HeatmapKey::Page(0), &["0"], "sprite page 0"),
+        ("page_1", HeatmapKey::Page(1), &["1"], "sprite page 1"),
+        ("page_2", HeatmapKey::Page(2), &["2"], "sprite page 2"),
+        ("page_3", HeatmapKey::Page(3), &["3"], "sprite page 3"),
+        ("page_4", HeatmapKey::Page(4), &["4"], "sprite page 4"),
+        ("page_5", HeatmapKey::Page(5), &["5"], "sprite page 5"),
+        ("page_6", HeatmapKey::Page(6), &["6"], "sprite page 6"),
+        ("page_7", HeatmapKey::Page(7), &["7"], "sprite page 7"),
+        ("clear", HeatmapKey::Clear, &["c"], "start counting over"),
+        ("pause", HeatmapKey::Pause, &["p", "space"], "pause"),
+        ("next_frame", HeatmapKey::NextFrame, &["n"], "next frame"),
+        ("open", HeatmapKey::Open, &["o"], "open"),
+        ("quit", HeatmapKey::Quit, &["esc", "q"], "quit"),
+    ])
+}
+
+/// Run a ROM and see which memory it uses most: the CPU address space by
+/// 256-byte page, one page by byte, or sprite RAM, tinted by how often each
+/// was read, written or run
+pub struct HeatmapViewer {
+    tx: Sender<GlobalEvent>,
+    emulator: Emulator<InstantClock>,
+    rom_path: Option<PathBuf>,
+    picker: Option<FilePicker>,
+    source: Source,
+    /// Selected page of the CPU address space
+    page: u8,
+    sprite_page: u8,
+    keys: Keymap<HeatmapKey>,
+    status: String,
+}
+
+impl HeatmapViewer {
+    pub fn init(tx: Sender<GlobalEvent>) -> Self {
+        let dir = std::env::current_dir().unwrap_or_default();
+
+        Self {
+            tx,
+            emulator: Emulator::init(InstantClock::default(), 44_100.0),
+            rom_path: None,
+            picker: Some(FilePicker::init(PickerMode::Open, "gtr", &dir)),
+            source: Source::Cpu(Access::Read),
+            page: 0,
+            sprite_page: 0,
+            keys: keymap(),
+            status: "Pick a ROM to profile".to_string(),
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = read_rom(path)?;
+        self.emulator.load_rom(&bytes);
+        self.emulator.start_heatmap();
+        self.emulator.resume();
+        self.rom_path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    fn quit(&self) {
+        let menu = MainMenu::init(self.tx.clone());
+        let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(menu)));
+    }
+
+    fn is_running(&self) -> bool {
+        self.emulator.play_state == PlayState::Playing
+    }
+
+    fn update_picker(&mut self, events: Vec<Event>) {
+        let Some(picker) = &mut self.picker else { return };
+        picker.update(events);
+        if picker.is_active() {
+            return;
+        }
+
+        let picked = picker.take_picked();
+        self.picker = None;
+
+        match picked {
+            Some(path) => {
+                self.status = match self.load(&path) {
+                    Ok(()) => format!("Loaded {}", path.display()),
+                    Err(e) => e,
+                };
+            }
+            None if self.rom_path.is_none() => self.quit(),
+            None => {}
+        }
+    }
+
+    fn handle_key(&mut self, key: HeatmapKey) {
+        match key {
+            HeatmapKey::Quit => self.quit(),
+            HeatmapKey::Pause => {
+                if self.is_running() {
+                    self.emulator.pause();
+                } else {
+                    self.emulator.resume();
+                }
+            }
+            HeatmapKey::NextFrame if !self.is_running() => self.emulator.run_frame(),
+            HeatmapKey::NextFrame => {}
+            HeatmapKey::NextSource => self.source = self.source.next(),
+            HeatmapKey::PreviousSource => self.source = self.source.previous(),
+            HeatmapKey::Up => self.page = self.page.wrapping_sub(16),
+            HeatmapKey::Down => self.page = self.page.wrapping_add(16),
+            HeatmapKey::Left => self.page = self.page.wrapping_sub(1),
+            HeatmapKey::Right => self.page = self.page.wrapping_add(1),
+            HeatmapKey::Page(page) => {
+                self.sprite_page = page;
+                if let Source::Cpu(_) = self.source {
+                    self.source = Source::SpriteWrites;
+                }
+            }
+            HeatmapKey::Clear => {
+                self.emulator.clear_heatmap();
+                self.status = "Counting from now".to_string();
+            }
+            HeatmapKey::Open => {
+                let dir = self.rom_path.as_ref()
+                    .and_then(|p| p.parent().map(Path::to_path_buf))
+                    .unwrap_or_default();
+                self.picker = Some(FilePicker::init(PickerMode::Open, "gtr", &dir));
+            }
+        }
+    }
+
+    fn title(&self) -> String {
+        match self.source {
+            Source::Cpu(access) => format!(" Address Space: {} ", access.name()),
+            source => format!(" Sprite Page {}: {} ", self.sprite_page, source.name()),
+        }
+    }
+
+    /// `count` and its rate per frame
+    fn count_text(count: u64, frames: u32) -> String {
+        if frames == 0 {
+            return count.to_string();
+        }
+        format!("{} ({:.1}/frame)", count, count as f64 / frames as f64)
+    }
+
+    fn legend_line() -> Line<'static> {
+        let mut spans = vec![];
+        for (name, start) in REGIONS {
+            spans.push(Span::from(format!("{} ", name)).fg(SCHEME.gray[2]));
+            spans.push(Span::from(format!("${:02X}  ", start >> 8)));
+        }
+        spans.push(Span::from("cold ").fg(SCHEME.gray[2]));
+        for i in [0, 1, 10, 100, 1000] {
+            spans.push(Span::from(" ").bg(heat_color(i, 1000)));
+        }
+        spans.push(Span::from(" hot").fg(SCHEME.gray[2]));
+        Line::from(spans)
+    }
+
+    fn render_cpu(&self, frame: &mut Frame, heatmap: &Heatmap, access: Access, view: Rect, side: Rect, panel: &impl Fn(String) -> Block<'static>) {
+        let pages = heatmap.pages(access);
+        let mut lines = grid_lines(&pages, Some(self.page as usize), |row| format!("${:X}000 ", row));
+        lines.push(Line::default());
+        lines.push(Self::legend_line());
+        frame.render_widget(Paragraph::new(lines).block(panel(self.title())), view);
+
+        let [page_area, hot_area] = Layout::vertical([Constraint::Length(20), Constraint::Fill(1)]).areas(side);
+
+        let start = self.page as usize * 256;
+        let bytes = &heatmap.cpu(access)[start..start + 256];
+        let mut lines = grid_lines(bytes, None, |row| format!("{:X}0 ", row));
+        lines.push(Line::default());
+        let total: u64 = bytes.iter().map(|&c| c as u64).sum();
+        lines.push(Line::from(Self::count_text(total, heatmap.frames())));
+        frame.render_widget(Paragraph::new(lines).block(panel(format!(" Page ${:02X} ", self.page))), page_area);
+
+        let hottest = heatmap.hottest(access, HOTTEST);
+        let lines: Vec<Line> = if hottest.is_empty() {
+            vec![Line::from("Nothing yet").fg(SCHEME.gray[2])]
+        } else {
+            hottest.into_iter().map(|(address, count)| Line::from(vec![
+                Span::from(format!("${:04X}  ", address)).fg(SCHEME.gray[2]),
+                Span::from(Self::count_text(count as u64, heatmap.frames())),
+            ])).collect()
+        };
+        frame.render_widget(Paragraph::new(lines).block(panel(" Hottest ".to_string())), hot_area);
+    }
+
+    fn render_sprite(&self, frame: &mut Frame, heatmap: &Heatmap, view: Rect, side: Rect, panel: &impl Fn(String) -> Block<'static>) {
+        let counts = match self.source {
+            Source::BlitterReads => heatmap.blitter_reads(self.sprite_page as usize),
+            _ => heatmap.sprite_writes(self.sprite_page as usize),
+        };
+
+        let view_block = panel(self.title());
+        let inner = view_block.inner(view);
+        frame.render_widget(view_block, view);
+        frame.render_widget(PageHeat { counts: &counts }, inner);
+
+        let frames = heatmap.frames();
+        let mut lines = vec![];
+        for page in 0..8 {
+            let total = |counts: Box<[u32; 256 * 256]>| counts.iter().map(|&c| c as u64).sum::<u64>();
+            let (writes, reads) = (total(heatmap.sprite_writes(page)), total(heatmap.blitter_reads(page)));
+            let style = if page == self.sprite_page as usize { SCHEME.orange[1] } else { SCHEME.gray[2] };
+            lines.push(Line::from(format!("page {}", page)).fg(style).bold());
+            lines.push(Line::from(format!(" w {}", Self::count_text(writes, frames))));
+            lines.push(Line::from(format!(" r {}", Self::count_text(reads, frames))));
+        }
+        frame.render_widget(Paragraph::new(lines).block(panel(" Sprite RAM ".to_string())), side);
+    }
+}
+
+impl Component for HeatmapViewer {
+    fn update(&mut self, events: Vec<Event>) {
+        if self.picker.is_some() {
+            self.update_picker(events);
+        } else {
+            let keys: Vec<HeatmapKey> = self.keys.actions(&events).collect();
+            for key in keys {
+                self.handle_key(key);
+            }
+        }
+
+        if self.is_running() {
+            self.emulator.process_cycles(false);
+        }
+    }
+
+    fn help(&self) -> Vec<Help> {
+        vec![self.keys.help()]
+    }
+
+    fn captures_text(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let panel = |title: String| Block::bordered()
+            .title(title)
+            .title_style(style.bold().fg(SCHEME.orange[1]))
+            .border_set(border::ROUNDED)
+            .style(style);
+
+        let [main, footer] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [view_area, side] = Layout::horizontal([Constraint::Fill(1), Constraint::Length(40)]).areas(main);
+
+        match self.emulator.heatmap() {
+            Some(heatmap) => match self.source {
+                Source::Cpu(access) => self.render_cpu(frame, heatmap, access, view_area, side, &panel),
+                _ => self.render_sprite(frame, heatmap, view_area, side, &panel),
+            },
+            None => frame.render_widget(Paragraph::new("No ROM loaded").block(panel(self.title())), main),
+        }
+
+        let state = if self.is_running() { "RUN " } else { "STOP" };
+        let frames = self.emulator.heatmap().map_or(0, Heatmap::frames);
+        let footer_line = Line::from(vec![
+            Span::from(format!(" {} ", state)).bold().fg(SCHEME.orange[1]),
+            Span::from(format!("{} frames  ", frames)),
+            Span::from(self.status.clone()),
+            Span::from(format!("  {} ?:keys", self.keys.hints(&["next_source", "clear", "pause", "next_frame", "open", "quit"]))).fg(SCHEME.gray[2]),
+        ]);
+        frame.render_widget(footer_line, footer);
+
+        if let Some(picker) = &mut self.picker {
+            picker.render(frame, frame.area());
+        }
+    }
+}
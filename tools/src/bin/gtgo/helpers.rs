@@ -1,4 +1,6 @@
-use std::time::Duration;
+use std::{path::{Path, PathBuf}, time::{Duration, Instant}};
+
+use gte_core::{elf_rom::is_elf, emulator::TimeDaemon, rom_header::RomHeader, symbols::SymbolMap};
 
 use ratatui::{crossterm::event::{self, Event}, layout::{Constraint, Direction, Layout, Rect}};
 
@@ -35,4 +37,55 @@ pub fn poll_events() -> Vec<Event> {
     events
 }
 
-pub const SCHEME: rat_theme::Scheme = rat_theme::scheme::MONEKAI;
\ No newline at end of file
+/// Where gtgo keeps its settings: `~/.config/gtgo`, or under `$XDG_CONFIG_HOME`
+pub fn config_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("gtgo"))
+}
+
+pub const SCHEME: rat_theme::Scheme = rat_theme::scheme::MONEKAI;
+
+/// Wall clock for running the emulator
+pub struct InstantClock {
+    pub instant: Instant,
+}
+
+impl Default for InstantClock {
+    fn default() -> Self {
+        Self { instant: Instant::now() }
+    }
+}
+
+impl TimeDaemon for InstantClock {
+    fn get_now_ms(&self) -> f64 {
+        self.instant.elapsed().as_millis() as f64
+    }
+}
+
+/// Read a .gtr for the emulator, rejecting images it can't load
+pub fn read_rom(path: &Path) -> Result<Vec<u8>, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    // the emulator lays an ELF out into a 2 MB ROM itself
+    if is_elf(&bytes) {
+        return Ok(bytes);
+    }
+
+    // the emulator panics on cartridge sizes it doesn't know
+    let (_, rom) = RomHeader::split(&bytes);
+    if !matches!(rom.len(), 0x2000 | 0x4000 | 0x8000 | 0x200000) {
+        return Err(format!("{} is {} bytes, not a supported cartridge size", path.display(), rom.len()));
+    }
+
+    Ok(bytes)
+}
+
+/// Read the symbol file `gtrom build` writes next to a ROM. `None` if there
+/// isn't one.
+pub fn read_symbols(rom_path: &Path) -> Option<Result<SymbolMap, String>> {
+    let path = rom_path.with_extension("sym");
+    let text = std::fs::read_to_string(&path).ok()?;
+    Some(SymbolMap::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)))
+}
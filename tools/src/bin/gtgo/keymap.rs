@@ -0,0 +1,286 @@
+//! Key bindings
+//!
+//! Every screen looks its keys up in a [`Keymap`] of named actions, so they
+//! can be changed in `~/.config/gtgo/keys.toml`, one table per screen:
+//!
+//! ```toml
+//! [emulator]
+//! pause = ["p", "f8"]
+//! screenshot = "ctrl+s"
+//!
+//! [pattern_editor]
+//! play_pause = "f5"
+//! ```
+//!
+//! Keys are written like `x`, `shift+up`, `ctrl+alt+pgdn` or `f12`. Press
+//! `?` on any screen to see its table name and what's bound there.
+
+use std::{collections::HashMap, fmt, path::PathBuf, sync::OnceLock};
+
+use ratatui::crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use serde::Deserialize;
+
+use crate::helpers::config_dir;
+
+pub const KEYS_FILE: &str = "keys.toml";
+
+/// A key and the modifiers held with it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pgup", KeyCode::PageUp),
+    ("pgdn", KeyCode::PageDown),
+    ("space", KeyCode::Char(' ')),
+];
+
+impl KeyChord {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = s.split('+').collect();
+        // "+" and "ctrl++" name the plus key itself
+        if s.ends_with('+') {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let key = parts.pop().filter(|k| !k.is_empty()).ok_or_else(|| format!("\"{}\" has no key", s))?;
+
+        for part in parts {
+            modifiers |= match part.to_ascii_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("\"{}\" isn't ctrl, alt or shift in \"{}\"", part, s)),
+            };
+        }
+
+        let lower = key.to_ascii_lowercase();
+        let mut chars = key.chars();
+        let code = if let Some(&(_, code)) = NAMED_KEYS.iter().find(|(name, _)| *name == lower) {
+            code
+        } else if let (Some(c), None) = (chars.next(), chars.next()) {
+            KeyCode::Char(c)
+        } else if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse().ok()).filter(|n| (1..=24).contains(n)) {
+            KeyCode::F(n)
+        } else {
+            return Err(format!("unknown key \"{}\"", key));
+        };
+
+        Ok(Self::from_event(&KeyEvent::new(code, modifiers)))
+    }
+
+    /// The chord for a key event. Shift goes into the character itself, since
+    /// terminals disagree on whether `?` or `A` come with it.
+    fn from_event(key: &KeyEvent) -> Self {
+        match key.code {
+            KeyCode::Char(c) => {
+                let shifted = key.modifiers.contains(KeyModifiers::SHIFT) && c.is_ascii_lowercase();
+                Self {
+                    code: KeyCode::Char(if shifted { c.to_ascii_uppercase() } else { c }),
+                    modifiers: key.modifiers - KeyModifiers::SHIFT,
+                }
+            }
+            code => Self { code, modifiers: key.modifiers },
+        }
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [(KeyModifiers::CONTROL, "ctrl+"), (KeyModifiers::ALT, "alt+"), (KeyModifiers::SHIFT, "shift+")] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(c) if c.is_ascii_uppercase() => write!(f, "shift+{}", c.to_ascii_lowercase()),
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            code => match NAMED_KEYS.iter().find(|(_, named)| *named == code) {
+                Some((name, _)) => f.write_str(name),
+                None => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// keys.toml: screen, then action name, then keys
+#[derive(Default)]
+struct KeysFile {
+    screens: HashMap<String, HashMap<String, KeyList>>,
+    error: Option<String>,
+}
+
+fn keys_path() -> Option<PathBuf> {
+    Some(config_dir()?.join(KEYS_FILE))
+}
+
+/// Read once, the first time a screen asks
+fn keys_file() -> &'static KeysFile {
+    static KEYS: OnceLock<KeysFile> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        let Some(path) = keys_path().filter(|p| p.exists()) else {
+            return KeysFile::default();
+        };
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+            .and_then(|s| toml::from_str(&s).map_err(|e| format!("Failed to parse {}: {}", path.display(), e)));
+        match parsed {
+            Ok(screens) => KeysFile { screens, error: None },
+            Err(e) => KeysFile { screens: HashMap::new(), error: Some(e) },
+        }
+    })
+}
+
+struct Binding<A> {
+    name: &'static str,
+    action: A,
+    keys: Vec<KeyChord>,
+    help: &'static str,
+}
+
+/// A screen's actions and the keys for them
+pub struct Keymap<A> {
+    section: &'static str,
+    bindings: Vec<Binding<A>>,
+    /// Problems with this screen's table in keys.toml
+    errors: Vec<String>,
+}
+
+impl<A: Copy> Keymap<A> {
+    /// `defaults` are `(name, action, keys, help)`, with `name` being what
+    /// keys.toml calls it in the `section` table
+    pub fn new(section: &'static str, defaults: &[(&'static str, A, &[&str], &'static str)]) -> Self {
+        let mut bindings: Vec<Binding<A>> = defaults.iter()
+            .map(|&(name, action, keys, help)| Binding {
+                name,
+                action,
+                keys: keys.iter().map(|k| KeyChord::parse(k).expect("default key binding")).collect(),
+                help,
+            })
+            .collect();
+
+        let mut errors = vec![];
+        if let Some(overrides) = keys_file().screens.get(section) {
+            for (name, keys) in overrides {
+                let Some(binding) = bindings.iter_mut().find(|b| b.name == name) else {
+                    errors.push(format!("[{}] has no action called {}", section, name));
+                    continue;
+                };
+                let keys = match keys {
+                    KeyList::One(key) => std::slice::from_ref(key),
+                    KeyList::Many(keys) => keys.as_slice(),
+                };
+                match keys.iter().map(|k| KeyChord::parse(k)).collect() {
+                    Ok(keys) => binding.keys = keys,
+                    Err(e) => errors.push(format!("[{}] {}: {}", section, name, e)),
+                }
+            }
+        }
+        errors.sort();
+
+        Self { section, bindings, errors }
+    }
+
+    /// The action bound to `key`, whether it's pressed or released
+    pub fn lookup(&self, key: &KeyEvent) -> Option<A> {
+        let chord = KeyChord::from_event(key);
+        self.bindings.iter().find(|b| b.keys.contains(&chord)).map(|b| b.action)
+    }
+
+    /// The action for a key press or repeat
+    pub fn action(&self, event: &Event) -> Option<A> {
+        match event {
+            Event::Key(key) if key.kind != KeyEventKind::Release => self.lookup(key),
+            _ => None,
+        }
+    }
+
+    /// The actions pressed among `events`, in order
+    pub fn actions<'a>(&'a self, events: &'a [Event]) -> impl Iterator<Item = A> + 'a {
+        events.iter().filter_map(|e| self.action(e))
+    }
+
+    /// The keys bound to `name`, like `p/f8`
+    pub fn keys(&self, name: &str) -> String {
+        self.bindings.iter()
+            .find(|b| b.name == name)
+            .map(|b| b.keys.iter().map(KeyChord::to_string).collect::<Vec<_>>().join("/"))
+            .unwrap_or_default()
+    }
+
+    /// A footer hint like `p:pause r:reset` for the named actions, using
+    /// each one's first key
+    pub fn hints(&self, names: &[&str]) -> String {
+        names.iter()
+            .filter_map(|name| self.bindings.iter().find(|b| b.name == *name))
+            .filter_map(|b| Some(format!("{}:{}", b.keys.first()?, b.help)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn help(&self) -> Help {
+        Help {
+            section: self.section,
+            rows: self.bindings.iter()
+                .map(|b| HelpRow {
+                    keys: b.keys.iter().map(KeyChord::to_string).collect::<Vec<_>>().join(" "),
+                    name: b.name.to_string(),
+                    help: b.help.to_string(),
+                })
+                .collect(),
+            errors: self.errors.clone(),
+        }
+    }
+}
+
+/// What the `?` overlay shows for one keymap
+pub struct Help {
+    pub section: &'static str,
+    pub rows: Vec<HelpRow>,
+    pub errors: Vec<String>,
+}
+
+pub struct HelpRow {
+    pub keys: String,
+    /// The action's name in keys.toml, empty for keys that can't be changed
+    pub name: String,
+    pub help: String,
+}
+
+impl Help {
+    /// A row for keys that aren't in the keymap, like a menu's hotkeys
+    pub fn fixed(mut self, keys: impl Into<String>, help: impl Into<String>) -> Self {
+        self.rows.push(HelpRow { keys: keys.into(), name: String::new(), help: help.into() });
+        self
+    }
+}
+
+/// Where keys.toml is read from, and whether reading it failed
+pub fn keys_file_status() -> (Option<PathBuf>, Option<&'static str>) {
+    (keys_path(), keys_file().error.as_deref())
+}
@@ -1,42 +1,101 @@
 pub mod main_menu;
+pub mod acp;
+pub mod builder;
 pub mod helpers;
+pub mod keymap;
 pub mod ui;
 pub mod tracker;
+pub mod debugger;
+pub mod emulator;
+pub mod flasher;
+pub mod heatmap;
+pub mod project;
+pub mod vram;
 
-use std::{thread::sleep, time::Duration};
+// shared with gte
+#[path = "../gte/audio.rs"]
+mod audio;
+
+use std::{path::Path, thread::sleep, time::Duration};
 
 use ratatui::{crossterm::event::Event, layout::Rect, DefaultTerminal, Frame};
 use anyhow::{bail, Ok, Result};
 
-use crate::{helpers::poll_events, main_menu::MainMenu};
+use crate::{helpers::poll_events, keymap::{Help, Keymap}, main_menu::MainMenu, project::Project, ui::help::HelpOverlay};
 
 pub trait Component {
     fn update(&mut self, events: Vec<Event>);
     fn render(&mut self, frame: &mut Frame, area: Rect);
+
+    /// Key bindings for the `?` overlay, most specific first
+    fn help(&self) -> Vec<Help> {
+        vec![]
+    }
+
+    /// Whether keys are being typed into something, so `?` is just a character
+    fn captures_text(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone, Copy)]
+enum GlobalKey {
+    Help,
 }
 
 pub enum GlobalEvent {
     ChangeInterface(Box<dyn Component>),
+    /// Hand the terminal to something else, e.g. an editor, until it returns
+    Suspend(Box<dyn FnOnce()>),
     Quit,
 }
 
 pub struct GtGo {
     terminal: DefaultTerminal,
     state: Box<dyn Component>,
-    rx: crossbeam_channel::Receiver<GlobalEvent>
+    rx: crossbeam_channel::Receiver<GlobalEvent>,
+    keys: Keymap<GlobalKey>,
+    help: Option<HelpOverlay>,
 }
 
 impl GtGo {
     fn run(&mut self) -> Result<()> {
         let _ = self.terminal.draw(|f| {
-            let events = poll_events();
+            let mut events = poll_events();
+
+            if let Some(help) = &mut self.help {
+                // the screen keeps running, but keys go to the overlay
+                let toggled = self.keys.actions(&events).next().is_some();
+                help.update(std::mem::take(&mut events));
+                if toggled || !help.is_active() {
+                    self.help = None;
+                }
+            } else if !self.state.captures_text() && self.keys.actions(&events).next().is_some() {
+                events.retain(|e| self.keys.action(e).is_none());
+                let mut sections = self.state.help();
+                sections.push(self.keys.help());
+                self.help = Some(HelpOverlay::init(sections));
+            }
+
             self.state.update(events);
             self.state.render(f, f.area()); // unhandled error
+            if let Some(help) = &mut self.help {
+                help.render(f, f.area());
+            }
         });
 
         for event in self.rx.try_iter() {
             match event {
-                GlobalEvent::ChangeInterface(component) => self.state = component,
+                GlobalEvent::ChangeInterface(component) => {
+                    self.state = component;
+                    self.help = None;
+                }
+                GlobalEvent::Suspend(run) => {
+                    ratatui::restore();
+                    run();
+                    self.terminal = ratatui::init();
+                    self.terminal.clear()?;
+                }
                 GlobalEvent::Quit => bail!("Exit"),
             }
         }
@@ -46,6 +105,16 @@ impl GtGo {
 }
 
 fn main() -> Result<()> {
+    // `gtgo [DIR]` opens the project DIR is in; otherwise the one we're in
+    match std::env::args().nth(1) {
+        Some(dir) => if let Err(e) = Project::open(Path::new(&dir)) {
+            bail!(e);
+        },
+        None => if let Some(project) = Project::current() {
+            project::remember(&project.root);
+        },
+    }
+
     let terminal = ratatui::init();
     let result = run(terminal);
     ratatui::restore();
@@ -59,6 +128,10 @@ fn run(terminal: DefaultTerminal) -> Result<()> {
         terminal, 
         state: Box::new(MainMenu::init(tx)),
         rx,
+        keys: Keymap::new("global", &[
+            ("help", GlobalKey::Help, &["?"], "show keys"),
+        ]),
+        help: None,
     };
 
     // Drain any pending terminal input (for example a newline from launching via a
@@ -1,48 +1,95 @@
 use crossbeam_channel::Sender;
-use ratatui::{crossterm::event::Event, layout::Rect, style::{Color, Stylize}, symbols::border, widgets::{Block, Widget}, Frame};
+use ratatui::{crossterm::event::Event, layout::Rect, style::{Color, Stylize}, symbols::border, text::Line, widgets::{Block, Widget}, Frame};
 
-use crate::{helpers::SCHEME, tracker::Tracker, ui::quickmenu::{qi, QuickMenu}, Component, GlobalEvent};
+use crate::{acp::AcpViewer, builder::BuildScreen, debugger::Debugger, emulator::EmulatorScreen, flasher::Flasher, heatmap::HeatmapViewer, helpers::SCHEME, keymap::Help, project::{Project, ProjectBrowser}, tracker::Tracker, ui::quickmenu::{qi, QuickMenu}, vram::VramViewer, Component, GlobalEvent};
 
 #[allow(dead_code)]
 pub struct MainMenu {
-    has_podman: bool,
     quit: bool,
     qm: QuickMenu,
-    tx: Sender<GlobalEvent>
+    tx: Sender<GlobalEvent>,
+    project: Option<Project>,
 }
 
 impl MainMenu {
     pub fn init(tx_main: Sender<GlobalEvent>) -> Self {
-        // TODO: if has podman
-        let has_podman = false;
-
         let txx = tx_main.clone();
+        let tx_debug = tx_main.clone();
+        let tx_emu = tx_main.clone();
+        let tx_flash = tx_main.clone();
+        let tx_vram = tx_main.clone();
+        let tx_heat = tx_main.clone();
+        let tx_build = tx_main.clone();
+        let tx_acp = tx_main.clone();
+        let tx_project = tx_main.clone();
+
+        let project = Project::current();
+        let rom = project.as_ref().and_then(Project::rom);
 
         let qm = QuickMenu::init(" Program Select ".to_string(), vec![
-            qi("_Emulator", true, || { todo!() }),
+            qi("_Open Project", true, move || {
+                let browser = ProjectBrowser::init(tx_project.clone());
+                let _ = tx_project.send(GlobalEvent::ChangeInterface(Box::new(browser)));
+            }),
+            qi("_Emulator", true, move || {
+                let emulator = EmulatorScreen::init(tx_emu.clone());
+                let _ = tx_emu.send(GlobalEvent::ChangeInterface(Box::new(emulator)));
+            }),
             qi("_Tracker", true, move || {
                 let tracker = Tracker::init(txx.clone());
                 let _ = txx.send(GlobalEvent::ChangeInterface(Box::new(tracker))); 
             }),
-            qi("_Build", has_podman, || { println!("ur mom") }),
-            qi("ROM _Flasher", true, || { todo!() }),
+            qi("_Debugger", true, move || {
+                let debugger = Debugger::init(tx_debug.clone());
+                let _ = tx_debug.send(GlobalEvent::ChangeInterface(Box::new(debugger)));
+            }),
+            qi("_VRAM Viewer", true, move || {
+                let viewer = VramViewer::init(tx_vram.clone());
+                let _ = tx_vram.send(GlobalEvent::ChangeInterface(Box::new(viewer)));
+            }),
+            qi("_Heatmap", true, move || {
+                let viewer = HeatmapViewer::init(tx_heat.clone());
+                let _ = tx_heat.send(GlobalEvent::ChangeInterface(Box::new(viewer)));
+            }),
+            qi("_Audio Debugger", true, move || {
+                let viewer = AcpViewer::init(tx_acp.clone());
+                let _ = tx_acp.send(GlobalEvent::ChangeInterface(Box::new(viewer)));
+            }),
+            qi("_Build", project.is_some(), move || {
+                let build = BuildScreen::init(tx_build.clone());
+                let _ = tx_build.send(GlobalEvent::ChangeInterface(Box::new(build)));
+            }),
+            qi("ROM _Flasher", true, move || {
+                let flasher = match &rom {
+                    Some(rom) => Flasher::with_rom(tx_flash.clone(), rom),
+                    None => Flasher::init(tx_flash.clone()),
+                };
+                let _ = tx_flash.send(GlobalEvent::ChangeInterface(Box::new(flasher)));
+            }),
         ]);
 
         Self {
-            has_podman,
             quit: false,
             qm,
             tx: tx_main,
+            project,
         }
     }
-}
 
+    fn project_line(&self) -> Line<'static> {
+        match &self.project {
+            Some(project) => Line::from(format!(" {} ({}) ", project.name, project.root.display())).fg(SCHEME.gray[2]),
+            None => Line::from(" no project: o to open one ").fg(SCHEME.gray[2]),
+        }
+    }
+}
 
 impl Component for MainMenu {
     fn render(&mut self, frame: &mut Frame, area: Rect) {
         let block = Block::bordered()
             .border_set(border::ROUNDED)
             .title("─ GameTank GO! ")
+            .title_bottom(self.project_line())
             .title_style(SCHEME.style(Color::Rgb(36, 36, 36)).italic().bold());
         block.render(frame.area(), frame.buffer_mut());
         self.qm.render(frame, area);
@@ -55,4 +102,8 @@ impl Component for MainMenu {
             let _ = self.tx.send(GlobalEvent::Quit);
         }
     }
+
+    fn help(&self) -> Vec<Help> {
+        self.qm.help()
+    }
 }
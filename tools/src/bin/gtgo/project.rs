@@ -0,0 +1,343 @@
+//! The open project
+//!
+//! gtgo works on one GameTank project at a time: the one its current
+//! directory is in, so Build, the Tracker and the Flasher all start there.
+//! Opening another changes directory to it. Projects opened before are kept
+//! in `~/.config/gtgo/recent-projects`, newest first.
+
+use std::path::{Path, PathBuf};
+
+use crossbeam_channel::Sender;
+use gametank_project::{config::Config, find_rom_dir_from, get_crate_name, rom_dir_in};
+use ratatui::{crossterm::event::Event, layout::{Constraint, Layout, Rect}, style::{Color, Modifier, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, List, ListItem, ListState}, Frame};
+
+use crate::{helpers::{config_dir, SCHEME}, keymap::{Help, Keymap}, main_menu::MainMenu, Component, GlobalEvent};
+
+const RECENT_FILE: &str = "recent-projects";
+
+/// Projects kept in the recent list
+const MAX_RECENT: usize = 10;
+
+/// A GameTank project: where it is, and its ROM crate
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub rom_dir: PathBuf,
+    pub name: String,
+}
+
+impl Project {
+    /// The project gtgo's current directory is in, if any
+    pub fn current() -> Option<Self> {
+        let dir = std::env::current_dir().ok()?;
+        Self::find(&dir).ok()
+    }
+
+    fn find(dir: &Path) -> Result<Self, String> {
+        let (root, rom_dir) = find_rom_dir_from(dir)?;
+        let name = get_crate_name(&rom_dir)?;
+        Ok(Self { root, rom_dir, name })
+    }
+
+    /// Open the project `dir` is in: change to its directory and put it at
+    /// the top of the recent list
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let dir = dir.canonicalize()
+            .map_err(|e| format!("Failed to open {}: {}", dir.display(), e))?;
+        let project = Self::find(&dir)?;
+        std::env::set_current_dir(&project.root)
+            .map_err(|e| format!("Failed to change to {}: {}", project.root.display(), e))?;
+        remember(&project.root);
+        Ok(project)
+    }
+
+    /// The ROM `gtrom build` last wrote, if there is one
+    pub fn rom(&self) -> Option<PathBuf> {
+        let config = Config::load(&self.root).ok()?;
+        let path = config.output_path(&self.root, &self.name);
+        path.exists().then_some(path)
+    }
+}
+
+fn recent_path() -> Option<PathBuf> {
+    Some(config_dir()?.join(RECENT_FILE))
+}
+
+/// Recently opened project roots, newest first, leaving out any that have
+/// gone
+pub fn load_recent() -> Vec<PathBuf> {
+    let Some(text) = recent_path().and_then(|p| std::fs::read_to_string(p).ok()) else {
+        return vec![];
+    };
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(PathBuf::from)
+        .filter(|p| p.is_dir())
+        .take(MAX_RECENT)
+        .collect()
+}
+
+fn save_recent(recent: &[PathBuf]) -> Result<(), String> {
+    let path = recent_path().ok_or_else(|| "No config directory (HOME isn't set)".to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let text: String = recent.iter().map(|p| format!("{}\n", p.display())).collect();
+    std::fs::write(&path, text)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Move `root` to the top of the recent list. Failing to save it isn't worth
+/// stopping for.
+pub fn remember(root: &Path) {
+    let mut recent = load_recent();
+    recent.retain(|p| p != root);
+    recent.insert(0, root.to_path_buf());
+    recent.truncate(MAX_RECENT);
+    let _ = save_recent(&recent);
+}
+
+/// Take `root` off the recent list
+pub fn forget(root: &Path) -> Result<(), String> {
+    let mut recent = load_recent();
+    recent.retain(|p| p != root);
+    save_recent(&recent)
+}
+
+#[derive(Clone, Copy)]
+enum BrowserKey {
+    Quit,
+    Up,
+    Down,
+    Focus,
+    Enter,
+    Parent,
+    OpenHere,
+    Forget,
+}
+
+fn keymap() -> Keymap<BrowserKey> {
+    Keymap::new("projects", &[
+        ("up", BrowserKey::Up, &["up"], "previous"),
+        ("down", BrowserKey::Down, &["down"], "next"),
+        ("focus", BrowserKey::Focus, &["tab"], "recent / browse"),
+        ("enter", BrowserKey::Enter, &["enter"], "open project or directory"),
+        ("parent", BrowserKey::Parent, &["backspace"], "parent directory"),
+        ("open_here", BrowserKey::OpenHere, &["o"], "open this directory"),
+        ("forget", BrowserKey::Forget, &["d", "delete"], "forget recent"),
+        ("quit", BrowserKey::Quit, &["esc", "q"], "back to the menu"),
+    ])
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    Recent,
+    Browse,
+}
+
+struct Entry {
+    name: String,
+    is_project: bool,
+}
+
+/// Open a project: from the recent list, or by browsing for its directory
+pub struct ProjectBrowser {
+    tx: Sender<GlobalEvent>,
+    recent: Vec<PathBuf>,
+    recent_state: ListState,
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    browse_state: ListState,
+    focus: Focus,
+    keys: Keymap<BrowserKey>,
+    status: String,
+}
+
+impl ProjectBrowser {
+    pub fn init(tx: Sender<GlobalEvent>) -> Self {
+        let dir = std::env::current_dir().unwrap_or_default();
+        let recent = load_recent();
+        let focus = if recent.is_empty() { Focus::Browse } else { Focus::Recent };
+
+        let mut browser = Self {
+            tx,
+            recent_state: ListState::default().with_selected((!recent.is_empty()).then_some(0)),
+            recent,
+            dir,
+            entries: vec![],
+            browse_state: ListState::default(),
+            focus,
+            keys: keymap(),
+            status: String::new(),
+        };
+        browser.refresh();
+        browser
+    }
+
+    fn quit(&self) {
+        let menu = MainMenu::init(self.tx.clone());
+        let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(menu)));
+    }
+
+    /// List the browsed directory's subdirectories, marking projects
+    fn refresh(&mut self) {
+        let mut entries: Vec<Entry> = std::fs::read_dir(&self.dir).into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| !name.starts_with('.'))
+            .map(|name| Entry { is_project: rom_dir_in(&self.dir.join(&name)).is_some(), name })
+            .collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if self.dir.parent().is_some() {
+            entries.insert(0, Entry { name: "..".to_string(), is_project: false });
+        }
+        self.entries = entries;
+        self.browse_state.select((!self.entries.is_empty()).then_some(0));
+    }
+
+    fn enter_dir(&mut self, name: &str) {
+        if name == ".." {
+            if let Some(parent) = self.dir.parent() {
+                self.dir = parent.to_path_buf();
+            }
+        } else {
+            self.dir.push(name);
+        }
+        self.refresh();
+    }
+
+    fn open(&mut self, dir: &Path) {
+        match Project::open(dir) {
+            Ok(_) => self.quit(),
+            Err(e) => self.status = e,
+        }
+    }
+
+    fn handle_key(&mut self, key: BrowserKey) {
+        let state = match self.focus {
+            Focus::Recent => &mut self.recent_state,
+            Focus::Browse => &mut self.browse_state,
+        };
+
+        match key {
+            BrowserKey::Quit => self.quit(),
+            BrowserKey::Up => state.select_previous(),
+            BrowserKey::Down => state.select_next(),
+            BrowserKey::Focus => {
+                self.focus = match self.focus {
+                    Focus::Recent => Focus::Browse,
+                    Focus::Browse => Focus::Recent,
+                };
+            }
+            BrowserKey::Enter => match self.focus {
+                Focus::Recent => {
+                    if let Some(root) = self.recent_state.selected().and_then(|i| self.recent.get(i)).cloned() {
+                        self.open(&root);
+                    }
+                }
+                Focus::Browse => {
+                    let Some(entry) = self.browse_state.selected().and_then(|i| self.entries.get(i)) else { return };
+                    let (name, is_project) = (entry.name.clone(), entry.is_project);
+                    if is_project {
+                        let dir = self.dir.join(name);
+                        self.open(&dir);
+                    } else {
+                        self.enter_dir(&name);
+                    }
+                }
+            },
+            BrowserKey::Parent => self.enter_dir(".."),
+            BrowserKey::OpenHere => {
+                let dir = self.dir.clone();
+                self.open(&dir);
+            }
+            BrowserKey::Forget => {
+                if self.focus != Focus::Recent {
+                    return;
+                }
+                let Some(i) = self.recent_state.selected().filter(|&i| i < self.recent.len()) else { return };
+                let root = self.recent.remove(i);
+                self.status = match forget(&root) {
+                    Ok(()) => format!("Forgot {}", root.display()),
+                    Err(e) => e,
+                };
+                if i >= self.recent.len() {
+                    self.recent_state.select(self.recent.len().checked_sub(1));
+                }
+            }
+        }
+    }
+}
+
+impl Component for ProjectBrowser {
+    fn update(&mut self, events: Vec<Event>) {
+        let keys: Vec<BrowserKey> = self.keys.actions(&events).collect();
+        for key in keys {
+            self.handle_key(key);
+        }
+    }
+
+    fn help(&self) -> Vec<Help> {
+        vec![self.keys.help()]
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let panel = |title: &'static str, focused: bool| Block::bordered()
+            .title(title)
+            .title_style(style.bold().fg(if focused { SCHEME.orange[1] } else { SCHEME.gray[2] }))
+            .border_set(border::ROUNDED)
+            .style(style);
+        let highlight = |focused: bool| if focused { style.add_modifier(Modifier::BOLD).fg(SCHEME.orange[1]) } else { style };
+
+        let [main, footer] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [recent_area, browse_area] = Layout::horizontal([Constraint::Percentage(40), Constraint::Fill(1)]).areas(main);
+
+        let focused = self.focus == Focus::Recent;
+        let recent: Vec<ListItem> = if self.recent.is_empty() {
+            vec![ListItem::new(Line::from("Nothing opened yet").fg(SCHEME.gray[2]))]
+        } else {
+            self.recent.iter().map(|root| {
+                let name = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                ListItem::new(vec![
+                    Line::from(name).bold(),
+                    Line::from(root.display().to_string()).fg(SCHEME.gray[2]),
+                ])
+            }).collect()
+        };
+        let recent_list = List::new(recent)
+            .block(panel(" Recent Projects ", focused))
+            .highlight_style(highlight(focused))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(recent_list, recent_area, &mut self.recent_state);
+
+        let focused = self.focus == Focus::Browse;
+        let entries: Vec<ListItem> = self.entries.iter().map(|e| {
+            if e.is_project {
+                ListItem::new(Line::from(vec![
+                    Span::from(format!("{}/", e.name)).fg(SCHEME.green[1]),
+                    Span::from("  project").fg(SCHEME.gray[2]),
+                ]))
+            } else {
+                ListItem::new(Line::from(format!("{}/", e.name)).fg(SCHEME.blue[1]))
+            }
+        }).collect();
+        let browse_list = List::new(entries)
+            .block(panel(" Browse ", focused).title_bottom(Line::from(self.dir.display().to_string()).fg(SCHEME.gray[2])))
+            .highlight_style(highlight(focused))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(browse_list, browse_area, &mut self.browse_state);
+
+        let footer_line = Line::from(vec![
+            Span::from(" OPEN PROJECT ").bold().fg(SCHEME.orange[1]),
+            Span::from(self.status.clone()),
+            Span::from(format!("  {} ?:keys", self.keys.hints(&["focus", "enter", "open_here", "forget", "quit"]))).fg(SCHEME.gray[2]),
+        ]);
+        frame.render_widget(footer_line, footer);
+    }
+}
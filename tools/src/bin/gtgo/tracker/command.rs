@@ -0,0 +1,108 @@
+//! Text form of tracker commands
+//!
+//! Effects and sequencer commands are typed into a prompt, one command per
+//! `;`. Numbers are decimal, or hex with a `$` or `0x` prefix.
+//!
+//! | channel effect        | sequencer        |
+//! |-----------------------|------------------|
+//! | `trem depth speed`    | `tempo bpm`      |
+//! | `vib depth speed`     | `load slot addr` |
+//! | `wave addr`           | `pattern n`      |
+//! | `phase value`         | `beat row`       |
+//! | `vslide rows delta`   | `advance`        |
+//! | `vstop`               | `stop`           |
+//! | `pslide rows delta`   |                  |
+//! | `pstop`               |                  |
+
+use crate::tracker::{ChannelCmd, SequencerCmd};
+
+/// A decimal or hex number, maybe negative
+pub fn parse_int(s: &str) -> Result<i64, String> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix('$').or_else(|| digits.strip_prefix("0x")) {
+        i64::from_str_radix(hex, 16)
+    } else {
+        digits.parse()
+    }.map_err(|_| format!("'{}' isn't a number", s))?;
+
+    Ok(if negative { -value } else { value })
+}
+
+/// The `index`th argument, checked against the type's range
+fn arg<T: TryFrom<i64>>(args: &[&str], index: usize) -> Result<T, String> {
+    let s = args.get(index).ok_or("missing argument")?;
+    let value = parse_int(s)?;
+    T::try_from(value).map_err(|_| format!("{} is out of range", s))
+}
+
+fn split(text: &str) -> Vec<Vec<&str>> {
+    text.split(';')
+        .map(|cmd| cmd.split_whitespace().collect::<Vec<_>>())
+        .filter(|words| !words.is_empty())
+        .collect()
+}
+
+/// Parse effects for a channel. Notes and volumes have their own columns.
+pub fn parse_channel_cmds(text: &str) -> Result<Vec<ChannelCmd>, String> {
+    split(text).into_iter().map(|words| {
+        let args = &words[1..];
+        let cmd = match words[0] {
+            "trem" => ChannelCmd::Tremolo(arg(args, 0)?, arg(args, 1)?),
+            "vib" => ChannelCmd::Vibrato(arg(args, 0)?, arg(args, 1)?),
+            "wave" => ChannelCmd::Wavetable(arg(args, 0)?),
+            "phase" => ChannelCmd::Phase(arg(args, 0)?),
+            "vslide" => ChannelCmd::SlideVol(arg(args, 0)?, arg(args, 1)?),
+            "vstop" => ChannelCmd::StopVSlide,
+            "pslide" => ChannelCmd::SlidePitch(arg(args, 0)?, arg(args, 1)?),
+            "pstop" => ChannelCmd::StopPSlide,
+            other => return Err(format!("unknown effect '{}'", other)),
+        };
+        Ok(cmd)
+    }).collect()
+}
+
+pub fn parse_sequencer_cmds(text: &str) -> Result<Vec<SequencerCmd>, String> {
+    split(text).into_iter().map(|words| {
+        let args = &words[1..];
+        let cmd = match words[0] {
+            "tempo" => SequencerCmd::Tempo(arg(args, 0)?),
+            "load" => SequencerCmd::Load(arg(args, 0)?, arg(args, 1)?),
+            "pattern" => SequencerCmd::Pattern(arg(args, 0)?),
+            "beat" => SequencerCmd::Beat(arg(args, 0)?),
+            "advance" => SequencerCmd::Advance,
+            "stop" => SequencerCmd::Stop,
+            other => return Err(format!("unknown command '{}'", other)),
+        };
+        Ok(cmd)
+    }).collect()
+}
+
+/// Text for an effect, or `None` for notes and volumes
+pub fn format_channel_cmd(cmd: &ChannelCmd) -> Option<String> {
+    Some(match *cmd {
+        ChannelCmd::Tremolo(depth, speed) => format!("trem {} {}", depth, speed),
+        ChannelCmd::Vibrato(depth, speed) => format!("vib {} {}", depth, speed),
+        ChannelCmd::Wavetable(addr) => format!("wave ${:04X}", addr),
+        ChannelCmd::Phase(phase) => format!("phase ${:04X}", phase),
+        ChannelCmd::SlideVol(rows, delta) => format!("vslide {} {}", rows, delta),
+        ChannelCmd::StopVSlide => "vstop".to_string(),
+        ChannelCmd::SlidePitch(rows, delta) => format!("pslide {} {}", rows, delta),
+        ChannelCmd::StopPSlide => "pstop".to_string(),
+        ChannelCmd::Note(_) | ChannelCmd::Volume(_) => return None,
+    })
+}
+
+pub fn format_sequencer_cmd(cmd: &SequencerCmd) -> String {
+    match *cmd {
+        SequencerCmd::Tempo(bpm) => format!("tempo {}", bpm),
+        SequencerCmd::Load(slot, addr) => format!("load {} ${:04X}", slot, addr),
+        SequencerCmd::Pattern(p) => format!("pattern {}", p),
+        SequencerCmd::Beat(b) => format!("beat {}", b),
+        SequencerCmd::Advance => "advance".to_string(),
+        SequencerCmd::Stop => "stop".to_string(),
+    }
+}
@@ -0,0 +1,378 @@
+//! Effect editor
+//!
+//! A popup for one fx cell, listing its effects in the order they run. Each
+//! effect's parameters are stepped or typed, and kept within what the
+//! sequencer makes use of: a tremolo deeper than the volume range, say,
+//! does nothing more.
+
+use ratatui::{crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind}, layout::Rect, style::{Color, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, BorderType, Clear, Padding, Paragraph}, Frame};
+
+use crate::{helpers::SCHEME, keymap::{Help, Keymap}, tracker::{command::parse_int, preview::MAX_VOLUME, ChannelCmd}};
+
+#[derive(Clone, Copy)]
+pub enum FxEvent {
+    Done,
+    Up,
+    Down,
+    Left,
+    Right,
+    MoveUp,
+    MoveDown,
+    Add,
+    Remove,
+    Increment,
+    Decrement,
+    IncrementMore,
+    DecrementMore,
+    Edit,
+    Text,
+}
+
+/// A number an effect takes, and the values that make sense for it
+struct Param {
+    name: &'static str,
+    min: i32,
+    max: i32,
+    /// Shown in hex, for addresses
+    hex: bool,
+}
+
+const fn param(name: &'static str, min: i32, max: i32) -> Param {
+    Param { name, min, max, hex: false }
+}
+
+/// Tremolo swings the volume by up to its depth either way
+static TREMOLO: [Param; 2] = [param("depth", 0, MAX_VOLUME as i32), param("speed", 0, 255)];
+static VIBRATO: [Param; 2] = [param("depth", 0, 255), param("speed", 0, 255)];
+static WAVETABLE: [Param; 1] = [Param { name: "addr", min: 0, max: 0xFFFF, hex: true }];
+static PHASE: [Param; 1] = [Param { name: "phase", min: 0, max: 0xFFFF, hex: true }];
+static SLIDE_VOL: [Param; 2] = [param("rows", 1, 255), param("delta", -(MAX_VOLUME as i32), MAX_VOLUME as i32)];
+static SLIDE_PITCH: [Param; 2] = [param("rows", 1, 255), param("delta", i16::MIN as i32, i16::MAX as i32)];
+
+/// Effects in the order `+`/`-` cycles through them, with their defaults
+fn kinds() -> [ChannelCmd; 8] {
+    [
+        ChannelCmd::Tremolo(8, 8),
+        ChannelCmd::Vibrato(8, 8),
+        ChannelCmd::Wavetable(0),
+        ChannelCmd::Phase(0),
+        ChannelCmd::SlideVol(4, -1),
+        ChannelCmd::StopVSlide,
+        ChannelCmd::SlidePitch(4, 16),
+        ChannelCmd::StopPSlide,
+    ]
+}
+
+fn kind_index(cmd: &ChannelCmd) -> usize {
+    kinds().iter()
+        .position(|k| std::mem::discriminant(k) == std::mem::discriminant(cmd))
+        .unwrap_or(0)
+}
+
+fn kind_name(cmd: &ChannelCmd) -> &'static str {
+    match cmd {
+        ChannelCmd::Tremolo(..) => "trem",
+        ChannelCmd::Vibrato(..) => "vib",
+        ChannelCmd::Wavetable(_) => "wave",
+        ChannelCmd::Phase(_) => "phase",
+        ChannelCmd::SlideVol(..) => "vslide",
+        ChannelCmd::StopVSlide => "vstop",
+        ChannelCmd::SlidePitch(..) => "pslide",
+        ChannelCmd::StopPSlide => "pstop",
+        ChannelCmd::Note(_) => "note",
+        ChannelCmd::Volume(_) => "vol",
+    }
+}
+
+fn params(cmd: &ChannelCmd) -> &'static [Param] {
+    match cmd {
+        ChannelCmd::Tremolo(..) => &TREMOLO,
+        ChannelCmd::Vibrato(..) => &VIBRATO,
+        ChannelCmd::Wavetable(_) => &WAVETABLE,
+        ChannelCmd::Phase(_) => &PHASE,
+        ChannelCmd::SlideVol(..) => &SLIDE_VOL,
+        ChannelCmd::SlidePitch(..) => &SLIDE_PITCH,
+        _ => &[],
+    }
+}
+
+fn get(cmd: &ChannelCmd, i: usize) -> i32 {
+    match (*cmd, i) {
+        (ChannelCmd::Tremolo(a, _) | ChannelCmd::Vibrato(a, _), 0) => a as i32,
+        (ChannelCmd::Tremolo(_, b) | ChannelCmd::Vibrato(_, b), _) => b as i32,
+        (ChannelCmd::Wavetable(v) | ChannelCmd::Phase(v), _) => v as i32,
+        (ChannelCmd::SlideVol(rows, _) | ChannelCmd::SlidePitch(rows, _), 0) => rows as i32,
+        (ChannelCmd::SlideVol(_, delta) | ChannelCmd::SlidePitch(_, delta), _) => delta as i32,
+        _ => 0,
+    }
+}
+
+/// Set parameter `i`, which has to be in its range already
+fn set(cmd: &mut ChannelCmd, i: usize, value: i32) {
+    match (cmd, i) {
+        (ChannelCmd::Tremolo(a, _) | ChannelCmd::Vibrato(a, _), 0) => *a = value as u8,
+        (ChannelCmd::Tremolo(_, b) | ChannelCmd::Vibrato(_, b), _) => *b = value as u8,
+        (ChannelCmd::Wavetable(v) | ChannelCmd::Phase(v), _) => *v = value as u16,
+        (ChannelCmd::SlideVol(rows, _) | ChannelCmd::SlidePitch(rows, _), 0) => *rows = value as u8,
+        (ChannelCmd::SlideVol(_, delta) | ChannelCmd::SlidePitch(_, delta), _) => *delta = value as i16,
+        _ => {}
+    }
+}
+
+fn format_value(p: &Param, value: i32) -> String {
+    if p.hex { format!("${:04X}", value) } else { value.to_string() }
+}
+
+pub struct FxEditor {
+    /// The cell's effects, without its note and volume
+    pub cmds: Vec<ChannelCmd>,
+    sel: usize,
+    /// 0 is the effect's kind, then its parameters
+    field: usize,
+    /// A value being typed into the selected field
+    typing: Option<String>,
+    done: bool,
+    /// Asked for the text prompt instead
+    text: bool,
+    title: String,
+    keys: Keymap<FxEvent>,
+}
+
+impl FxEditor {
+    pub fn init(title: String, cmds: Vec<ChannelCmd>) -> Self {
+        let keys = Keymap::new("fx_editor", &[
+            ("done", FxEvent::Done, &["esc"], "back to the pattern"),
+            ("up", FxEvent::Up, &["up"], "previous effect"),
+            ("down", FxEvent::Down, &["down"], "next effect"),
+            ("left", FxEvent::Left, &["left"], "previous field"),
+            ("right", FxEvent::Right, &["right"], "next field"),
+            ("move_up", FxEvent::MoveUp, &["shift+up"], "run earlier"),
+            ("move_down", FxEvent::MoveDown, &["shift+down"], "run later"),
+            ("add", FxEvent::Add, &["insert", "a"], "add effect"),
+            ("remove", FxEvent::Remove, &["delete", "x"], "remove effect"),
+            ("increment", FxEvent::Increment, &["=", "+"], "nudge up"),
+            ("decrement", FxEvent::Decrement, &["-"], "nudge down"),
+            ("increment_more", FxEvent::IncrementMore, &["]"], "16 up"),
+            ("decrement_more", FxEvent::DecrementMore, &["["], "16 down"),
+            ("edit", FxEvent::Edit, &["enter"], "type a value"),
+            ("text", FxEvent::Text, &["t"], "type the effects as text"),
+        ]);
+
+        Self { cmds, sel: 0, field: 0, typing: None, done: false, text: false, title, keys }
+    }
+
+    pub fn help(&self) -> Help {
+        self.keys.help().fixed("0-9 $", "while typing: decimal, or hex after $")
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Whether to swap the editor for the text prompt, which takes the
+    /// `trem 8 4; vib 2 6` form
+    pub fn wants_text(&self) -> bool {
+        self.text
+    }
+
+    /// Whether a value is being typed, so keys are characters
+    pub fn is_typing(&self) -> bool {
+        self.typing.is_some()
+    }
+
+    /// Handle keys. Returns a status message, if there's news.
+    pub fn update(&mut self, events: &[Event]) -> Option<String> {
+        let mut status = None;
+        for e in events {
+            let Event::Key(key) = e else { continue };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            let result = if self.typing.is_some() {
+                self.type_key(key)
+            } else {
+                match self.keys.lookup(key) {
+                    Some(event) => self.apply(event),
+                    None => None,
+                }
+            };
+            if result.is_some() {
+                status = result;
+            }
+        }
+        status
+    }
+
+    fn type_key(&mut self, key: &KeyEvent) -> Option<String> {
+        let text = self.typing.as_mut()?;
+        match key.code {
+            KeyCode::Char(c) => text.push(c),
+            KeyCode::Backspace => { text.pop(); }
+            KeyCode::Esc => self.typing = None,
+            KeyCode::Enter => {
+                let text = self.typing.take()?;
+                return match self.set_typed(&text) {
+                    Ok(()) => None,
+                    Err(e) => {
+                        // keep the text so it can be fixed
+                        self.typing = Some(text);
+                        Some(e)
+                    }
+                };
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Check a typed value against the field's range, and set it
+    fn set_typed(&mut self, text: &str) -> Result<(), String> {
+        let field = self.field;
+        let (cmd, p) = self.selected_param().ok_or("nothing to set")?;
+        let value = parse_int(text.trim())?;
+        if value < p.min as i64 || value > p.max as i64 {
+            return Err(format!("{} is {} to {}", p.name, format_value(p, p.min), format_value(p, p.max)));
+        }
+        set(cmd, field - 1, value as i32);
+        Ok(())
+    }
+
+    /// The selected effect and parameter, unless the kind is selected
+    fn selected_param(&mut self) -> Option<(&mut ChannelCmd, &'static Param)> {
+        let cmd = self.cmds.get_mut(self.sel)?;
+        let p = params(cmd).get(self.field.checked_sub(1)?)?;
+        Some((cmd, p))
+    }
+
+    fn apply(&mut self, event: FxEvent) -> Option<String> {
+        let fields = self.cmds.get(self.sel).map_or(1, |cmd| params(cmd).len() + 1);
+        match event {
+            FxEvent::Done => self.done = true,
+            FxEvent::Text => self.text = true,
+            FxEvent::Up => self.select(self.sel.saturating_sub(1)),
+            FxEvent::Down => self.select(self.sel + 1),
+            FxEvent::Left => self.field = self.field.saturating_sub(1),
+            FxEvent::Right => self.field = (self.field + 1).min(fields - 1),
+            FxEvent::MoveUp if self.sel > 0 && self.sel < self.cmds.len() => {
+                self.cmds.swap(self.sel, self.sel - 1);
+                self.sel -= 1;
+            }
+            FxEvent::MoveDown if self.sel + 1 < self.cmds.len() => {
+                self.cmds.swap(self.sel, self.sel + 1);
+                self.sel += 1;
+            }
+            FxEvent::MoveUp | FxEvent::MoveDown => {}
+            FxEvent::Add => {
+                let at = if self.cmds.is_empty() { 0 } else { self.sel + 1 };
+                self.cmds.insert(at, kinds()[0]);
+                self.sel = at;
+                self.field = 0;
+            }
+            FxEvent::Remove if self.sel < self.cmds.len() => {
+                let cmd = self.cmds.remove(self.sel);
+                self.select(self.sel);
+                return Some(format!("Removed {}", kind_name(&cmd)));
+            }
+            FxEvent::Remove => {}
+            FxEvent::Increment => return self.step(1),
+            FxEvent::Decrement => return self.step(-1),
+            FxEvent::IncrementMore => return self.step(16),
+            FxEvent::DecrementMore => return self.step(-16),
+            FxEvent::Edit if self.field == 0 => return Some("+/- changes the effect".to_string()),
+            FxEvent::Edit => {
+                let field = self.field;
+                let (cmd, p) = self.selected_param()?;
+                let text = format_value(p, get(cmd, field - 1));
+                self.typing = Some(text);
+            }
+        }
+        None
+    }
+
+    fn select(&mut self, sel: usize) {
+        self.sel = sel.min(self.cmds.len().saturating_sub(1));
+        let fields = self.cmds.get(self.sel).map_or(1, |cmd| params(cmd).len() + 1);
+        self.field = self.field.min(fields - 1);
+    }
+
+    /// Change the selected field by `delta`: the kind cycles, and values stop
+    /// at the ends of their range
+    fn step(&mut self, delta: i32) -> Option<String> {
+        let field = self.field;
+        let cmd = self.cmds.get_mut(self.sel)?;
+
+        if field == 0 {
+            let count = kinds().len() as i32;
+            let next = (kind_index(cmd) as i32 + delta.signum()).rem_euclid(count);
+            *cmd = kinds()[next as usize];
+            return None;
+        }
+
+        let p = params(cmd).get(field - 1)?;
+        let value = (get(cmd, field - 1) + delta).clamp(p.min, p.max);
+        set(cmd, field - 1, value);
+        None
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let width = 44.min(area.width);
+        let height = (self.cmds.len().max(1) as u16 + 5).min(area.height);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        let block = Block::bordered()
+            .title(format!(" {} ", self.title))
+            .title_style(style.bold().not_italic().fg(SCHEME.orange[1]))
+            .style(style.fg(SCHEME.orange[1]))
+            .padding(Padding::horizontal(1))
+            .border_set(border::ROUNDED)
+            .border_type(BorderType::Thick);
+        let inner = block.inner(popup);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(block, popup);
+
+        let mut lines: Vec<Line> = self.cmds.iter().enumerate().map(|(i, cmd)| {
+            let selected = i == self.sel;
+            let field_style = |field: usize| {
+                let span_style = style.fg(SCHEME.white[1]);
+                if selected && field == self.field { span_style.reversed() } else { span_style }
+            };
+
+            let mut spans = vec![
+                Span::from(if selected { "▸ " } else { "  " }).fg(SCHEME.orange[1]),
+                Span::from(format!("{:<6}", kind_name(cmd))).style(field_style(0).fg(SCHEME.yellow[1])),
+            ];
+            for (f, p) in params(cmd).iter().enumerate() {
+                spans.push(Span::from(format!("  {} ", p.name)).fg(SCHEME.gray[2]));
+                spans.push(Span::from(format_value(p, get(cmd, f))).style(field_style(f + 1)));
+            }
+            Line::from(spans)
+        }).collect();
+
+        if lines.is_empty() {
+            lines.push(Line::from("no effects").fg(SCHEME.gray[1]));
+        }
+
+        // what the selected field takes
+        lines.push(Line::default());
+        let hint = match (&self.typing, self.cmds.get(self.sel)) {
+            (Some(text), _) => Line::from(vec![
+                Span::from("= ").fg(SCHEME.orange[1]),
+                Span::from(format!("{}_", text)).fg(SCHEME.white[1]),
+            ]),
+            (None, Some(cmd)) => match self.field.checked_sub(1).and_then(|f| params(cmd).get(f)) {
+                Some(p) => Line::from(format!("{}: {} to {}", p.name, format_value(p, p.min), format_value(p, p.max))).fg(SCHEME.gray[2]),
+                None => Line::from(format!("+/- picks the effect, {} of {}", kind_index(cmd) + 1, kinds().len())).fg(SCHEME.gray[2]),
+            },
+            (None, None) => Line::from(format!("{}:add", self.keys.keys("add"))).fg(SCHEME.gray[2]),
+        };
+        lines.push(hint);
+
+        frame.render_widget(Paragraph::new(lines), inner);
+    }
+}
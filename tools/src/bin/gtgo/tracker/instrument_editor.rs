@@ -0,0 +1,290 @@
+//! Instrument editor
+//!
+//! A popup for the song's wavetables: the slots `gtrom build` loads before
+//! the song plays, one for each `.wav` in the `<name>.wavetables` directory
+//! next to the project. A WAV is imported into a slot by converting it the
+//! way `gtrom convert` does, one pitch-detected cycle or the whole file as
+//! one, and can be heard before it's saved. Saved tables are 256-sample
+//! WAVs, which the ROM build uses byte for byte.
+
+use std::path::{Path, PathBuf};
+
+use gametank_sdk::wav::{load_wavetable, write_wavetable, Audio, Cycle};
+use ratatui::{crossterm::event::{Event, KeyEventKind}, layout::{Constraint, Layout, Rect}, style::{Color, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, BorderType, Clear, Padding, Paragraph, Sparkline}, Frame};
+
+use crate::{helpers::SCHEME, keymap::{Help, Keymap}, tracker::project};
+
+pub type Table = [u8; 256];
+
+#[derive(Clone, Copy)]
+pub enum InstrumentEvent {
+    Done,
+    Up,
+    Down,
+    Import,
+    ToggleCycle,
+    Preview,
+    Save,
+}
+
+/// Rows the waveform takes up
+const WAVE_HEIGHT: u16 = 6;
+
+fn to_table(bytes: Vec<u8>) -> Result<Table, String> {
+    bytes.try_into().map_err(|_| "not a 256 byte wavetable".to_string())
+}
+
+/// The wavetables of the song at `gtt`, by slot, for the preview. Ones that
+/// don't load are silent.
+pub fn song_wavetables(gtt: &Path) -> Vec<Table> {
+    project::wavetable_files(gtt).unwrap_or_default().iter()
+        .map(|path| load_wavetable(path).and_then(|(table, _)| to_table(table)).unwrap_or([0x80; 256]))
+        .collect()
+}
+
+struct Slot {
+    path: PathBuf,
+    table: Result<Table, String>,
+}
+
+/// A WAV being imported
+struct Import {
+    source: PathBuf,
+    cycle: Cycle,
+    /// The table and its pitch
+    result: Result<(Table, f32), String>,
+}
+
+impl Import {
+    fn convert(source: PathBuf, cycle: Cycle) -> Self {
+        let result = Audio::load(&source)
+            .and_then(|audio| audio.wavetable(cycle))
+            .and_then(|(table, pitch)| Ok((to_table(table)?, pitch)));
+        Self { source, cycle, result }
+    }
+}
+
+pub struct InstrumentEditor {
+    gtt: PathBuf,
+    slots: Vec<Slot>,
+    /// Selected slot; one past the last is a new slot
+    sel: usize,
+    import: Option<Import>,
+    done: bool,
+    /// Asked for a file picker to import from
+    pick: bool,
+    /// A table to hear, for the pattern editor to play
+    preview: Option<Table>,
+    keys: Keymap<InstrumentEvent>,
+}
+
+impl InstrumentEditor {
+    /// The editor for the wavetables of the song saved at `gtt`
+    pub fn init(gtt: &Path) -> Self {
+        let keys = Keymap::new("instrument_editor", &[
+            ("done", InstrumentEvent::Done, &["esc"], "back to the pattern"),
+            ("up", InstrumentEvent::Up, &["up"], "previous slot"),
+            ("down", InstrumentEvent::Down, &["down"], "next slot"),
+            ("import", InstrumentEvent::Import, &["i"], "import a WAV"),
+            ("cycle", InstrumentEvent::ToggleCycle, &["c"], "detect a cycle or use the whole file"),
+            ("preview", InstrumentEvent::Preview, &["space"], "hear it"),
+            ("save", InstrumentEvent::Save, &["enter"], "save the import to the slot"),
+        ]);
+
+        let mut editor = Self {
+            gtt: gtt.to_path_buf(),
+            slots: vec![],
+            sel: 0,
+            import: None,
+            done: false,
+            pick: false,
+            preview: None,
+            keys,
+        };
+        editor.reload();
+        editor
+    }
+
+    fn reload(&mut self) {
+        self.slots = project::wavetable_files(&self.gtt).unwrap_or_default().into_iter()
+            .map(|path| {
+                let table = load_wavetable(&path).and_then(|(table, _)| to_table(table));
+                Slot { path, table }
+            })
+            .collect();
+        self.sel = self.sel.min(self.slots.len());
+    }
+
+    pub fn help(&self) -> Help {
+        self.keys.help()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Whether a WAV to import was asked for
+    pub fn take_pick(&mut self) -> bool {
+        std::mem::take(&mut self.pick)
+    }
+
+    /// The table to play, once asked for
+    pub fn take_preview(&mut self) -> Option<Table> {
+        self.preview.take()
+    }
+
+    /// Convert `source`, to hear and save
+    pub fn import(&mut self, source: &Path) -> String {
+        let cycle = self.import.as_ref().map_or(Cycle::Detect, |i| i.cycle);
+        let import = Import::convert(source.to_path_buf(), cycle);
+        let status = match &import.result {
+            Ok((table, _)) => {
+                self.preview = Some(*table);
+                format!("Imported {}", source.display())
+            }
+            Err(e) => e.clone(),
+        };
+        self.import = Some(import);
+        status
+    }
+
+    /// The table shown and played: the import, else the selected slot's
+    fn table(&self) -> Option<&Table> {
+        match &self.import {
+            Some(import) => import.result.as_ref().ok().map(|(table, _)| table),
+            None => self.slots.get(self.sel)?.table.as_ref().ok(),
+        }
+    }
+
+    /// Handle keys. Returns a status message, if there's news.
+    pub fn update(&mut self, events: &[Event]) -> Option<String> {
+        let mut status = None;
+        for e in events {
+            let Event::Key(key) = e else { continue };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            if let Some(result) = self.keys.lookup(key).and_then(|event| self.apply(event)) {
+                status = Some(result);
+            }
+        }
+        status
+    }
+
+    fn apply(&mut self, event: InstrumentEvent) -> Option<String> {
+        match event {
+            InstrumentEvent::Done => self.done = true,
+            InstrumentEvent::Up => self.sel = self.sel.saturating_sub(1),
+            InstrumentEvent::Down => self.sel = (self.sel + 1).min(self.slots.len()),
+            InstrumentEvent::Import => self.pick = true,
+            InstrumentEvent::ToggleCycle => {
+                let import = self.import.take()?;
+                let cycle = match import.cycle {
+                    Cycle::Detect => Cycle::Whole,
+                    Cycle::Whole => Cycle::Detect,
+                };
+                self.import = Some(Import::convert(import.source, cycle));
+                self.preview = self.table().copied();
+            }
+            InstrumentEvent::Preview => match self.table() {
+                Some(table) => self.preview = Some(*table),
+                None => return Some("Nothing to hear".to_string()),
+            },
+            InstrumentEvent::Save => return Some(self.save().unwrap_or_else(|e| e)),
+        }
+        None
+    }
+
+    /// Write the import into the selected slot, or a new one
+    fn save(&mut self) -> Result<String, String> {
+        let import = self.import.as_ref().ok_or("Import a WAV first")?;
+        let (table, pitch) = import.result.as_ref().map_err(Clone::clone)?;
+
+        let path = match self.slots.get(self.sel) {
+            Some(slot) => slot.path.clone(),
+            None => {
+                // slots go in name order, so numbered names keep theirs
+                let stem = import.source.file_stem().unwrap_or_default().to_string_lossy();
+                project::wavetables_dir(&self.gtt).join(format!("{:02}-{}.wav", self.slots.len(), stem))
+            }
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        write_wavetable(&path, table, *pitch)?;
+
+        self.import = None;
+        self.reload();
+        self.sel = self.slots.iter().position(|slot| slot.path == path).unwrap_or(self.sel);
+        Ok(format!("Saved slot {} to {}", self.sel, path.display()))
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let width = 64.min(area.width);
+        let height = (self.slots.len() as u16 + WAVE_HEIGHT + 7).min(area.height);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        let dir = project::wavetables_dir(&self.gtt);
+        let block = Block::bordered()
+            .title(format!(" Wavetables: {} ", dir.file_name().unwrap_or_default().to_string_lossy()))
+            .title_style(style.bold().not_italic().fg(SCHEME.orange[1]))
+            .style(style.fg(SCHEME.orange[1]))
+            .padding(Padding::horizontal(1))
+            .border_set(border::ROUNDED)
+            .border_type(BorderType::Thick);
+        let inner = block.inner(popup);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(block, popup);
+
+        let [list_area, wave_area, info_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(WAVE_HEIGHT),
+            Constraint::Length(2),
+        ]).areas(inner);
+
+        let mut lines: Vec<Line> = self.slots.iter().enumerate().map(|(i, slot)| {
+            let name = slot.path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let mut spans = vec![
+                Span::from(if i == self.sel { "▸ " } else { "  " }).fg(SCHEME.orange[1]),
+                Span::from(format!("{:02} ", i)).fg(SCHEME.yellow[1]),
+                Span::from(name).fg(SCHEME.white[1]),
+            ];
+            if let Err(e) = &slot.table {
+                spans.push(Span::from(format!("  {}", e)).fg(SCHEME.red[1]));
+            }
+            Line::from(spans)
+        }).collect();
+        lines.push(Line::from(vec![
+            Span::from(if self.sel == self.slots.len() { "▸ " } else { "  " }).fg(SCHEME.orange[1]),
+            Span::from("new slot").fg(SCHEME.gray[2]),
+        ]));
+        frame.render_widget(Paragraph::new(lines), list_area);
+
+        if let Some(table) = self.table() {
+            let columns = wave_area.width.max(1) as usize;
+            let bars: Vec<u64> = (0..columns).map(|x| table[x * 256 / columns] as u64 + 1).collect();
+            frame.render_widget(Sparkline::default().data(bars).max(256).style(style.fg(SCHEME.yellow[1])), wave_area);
+        }
+
+        let info = match &self.import {
+            Some(Import { source, cycle, result }) => {
+                let name = source.file_name().unwrap_or_default().to_string_lossy();
+                match (result, cycle) {
+                    (Ok((_, pitch)), Cycle::Detect) => Line::from(format!("{}: cycle at {:.1} Hz", name, pitch)).fg(SCHEME.white[1]),
+                    (Ok(_), Cycle::Whole) => Line::from(format!("{}: the whole file", name)).fg(SCHEME.white[1]),
+                    (Err(e), _) => Line::from(format!("{}: {}", name, e)).fg(SCHEME.red[1]),
+                }
+            }
+            None => Line::from("no import").fg(SCHEME.gray[1]),
+        };
+        let hint = Line::from(self.keys.hints(&["import", "cycle", "preview", "save"])).fg(SCHEME.gray[2]);
+        frame.render_widget(Paragraph::new(vec![info, hint]), info_area);
+    }
+}
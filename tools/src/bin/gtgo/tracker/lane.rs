@@ -1,6 +1,6 @@
 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LaneKind {
     Beat,
     Seq,
@@ -50,10 +50,21 @@ impl Lane {
         }
     }
 
+    /// A collapsed channel: just its notes, under a narrow title
+    pub fn collapsed(ch: u8) -> Self {
+        Self {
+            title: format!(" {ch} "),
+            padding: (0, 0),
+            width: 3,
+            kind: LaneKind::Note,
+            ch: Some(ch as usize)
+        }
+    }
+
     pub fn vol(ch: u8) -> Self {
         Self {
             title: " v ".to_string(),
-            padding: (1, 1),
+            padding: (0, 1),
             width: 3,
             kind: LaneKind::Vol,
             ch: Some(ch as usize)
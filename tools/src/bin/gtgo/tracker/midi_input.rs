@@ -0,0 +1,79 @@
+//! MIDI keyboard input
+//!
+//! Connects to a hardware controller with midir. Messages arrive on midir's
+//! thread and are passed along a channel, so the editor picks them up with
+//! the rest of its input each frame.
+
+use crossbeam_channel::{Receiver, Sender};
+use midir::{Ignore, MidiInput, MidiInputConnection};
+
+const CLIENT_NAME: &str = "gtgo";
+
+#[derive(Clone, Copy, Debug)]
+pub enum MidiEvent {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+}
+
+impl MidiEvent {
+    /// Note on and off from any MIDI channel; everything else is dropped
+    fn parse(message: &[u8]) -> Option<Self> {
+        let &[status, note, velocity, ..] = message else { return None };
+        match status & 0xF0 {
+            // a note on with no velocity is how many keyboards send note off
+            0x90 if velocity > 0 => Some(Self::NoteOn { note, velocity }),
+            0x80 | 0x90 => Some(Self::NoteOff { note }),
+            _ => None,
+        }
+    }
+}
+
+pub struct MidiKeyboard {
+    connection: Option<MidiInputConnection<()>>,
+    tx: Sender<MidiEvent>,
+    rx: Receiver<MidiEvent>,
+    /// Port to connect to next
+    next_port: usize,
+}
+
+impl MidiKeyboard {
+    pub fn new() -> Self {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        Self { connection: None, tx, rx, next_port: 0 }
+    }
+
+    /// Connect to the next input port, wrapping around, so repeated calls
+    /// cycle through them. Returns the port's name.
+    pub fn connect_next(&mut self) -> Result<String, String> {
+        // only one connection at a time
+        self.connection = None;
+
+        let mut input = MidiInput::new(CLIENT_NAME)
+            .map_err(|e| format!("Failed to open MIDI: {}", e))?;
+        input.ignore(Ignore::All);
+
+        let ports = input.ports();
+        if ports.is_empty() {
+            return Err("No MIDI input ports".to_string());
+        }
+        let port = &ports[self.next_port % ports.len()];
+        self.next_port = (self.next_port + 1) % ports.len();
+
+        let name = input.port_name(port)
+            .map_err(|e| format!("Failed to read MIDI port name: {}", e))?;
+        let tx = self.tx.clone();
+        let connection = input.connect(port, "gtgo-tracker", move |_, message, _| {
+            if let Some(event) = MidiEvent::parse(message) {
+                let _ = tx.send(event);
+            }
+        }, ()).map_err(|e| format!("Failed to connect to {}: {}", name, e))?;
+
+        self.connection = Some(connection);
+        Ok(name)
+    }
+
+    /// Events received since the last call
+    pub fn events(&self) -> impl Iterator<Item = MidiEvent> + '_ {
+        self.rx.try_iter()
+    }
+}
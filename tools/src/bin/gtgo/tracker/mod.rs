@@ -1,21 +1,25 @@
 pub mod pattern_editor;
+mod order_list;
+mod fx_editor;
+mod instrument_editor;
 mod midi;
+mod midi_input;
 pub mod lane;
+mod preview;
+mod command;
 
 use crossbeam_channel::{Receiver, Sender};
-use ratatui::{crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers}, layout::{Alignment, Constraint, Direction, Layout, Rect}, style::Stylize, widgets::{Block, Borders}};
+use ratatui::{crossterm::event::Event, layout::{Alignment, Constraint, Direction, Layout, Rect}, style::Stylize, widgets::{Block, Borders}};
 
-use crate::{helpers::SCHEME, main_menu::MainMenu, tracker::pattern_editor::PatternEditor, Component, GlobalEvent};
+use crate::{helpers::SCHEME, keymap::{Help, Keymap}, main_menu::MainMenu, tracker::pattern_editor::PatternEditor, Component, GlobalEvent};
 
-pub struct Handler {
-    pub event: Event,
-    pub action: Box<dyn Fn()>
-}
+// the song model is shared with gtrom, which compiles projects into ROMs
+pub use gametank_sdk::tracker::{empty_pattern, export, project, Beat, ChannelCmd, ChannelFlags, OrderEntry, Pattern, SequencerCmd, TrackerData, CHANNELS, DEFAULT_TEMPO};
 
 // tracker subcomponent
 pub trait TSub: Component {
-    fn active_handlers(&self) -> &Vec<Handler>;
-    fn global_handlers(&self) -> &Vec<Handler>;
+    /// Act on key presses while focused
+    fn handle_keys(&mut self, events: &[Event]);
 }
 
 // pub enum Modes {
@@ -30,12 +34,6 @@ pub enum TrackerCmd {
     FocusComponent(Option<usize>),
 }
 
-type Pattern = [[Beat; 64]; 9];
-
-fn empty_pattern() -> Pattern {
-    std::array::from_fn(|_| std::array::from_fn(|_| Beat::default()))
-}
-
 pub enum VoiceOpKind {
     Tremolo,
     Vibrato,
@@ -60,23 +58,6 @@ pub struct VoiceBeat {
     // idk: IndexMap<>,
 }
 
-#[derive(Debug, Default, Clone)]
-pub struct Beat {
-    cmd_list: Vec<ChannelCmd>,
-    sqc_list: Vec<SequencerCmd>
-}
-
-
-#[derive(Debug, Clone)]
-pub enum SequencerCmd {
-    Tempo(u8), // 0 - 256 in bpm. 60hz * 60s = 3600 / tempo = tick counter.
-    Load(u8, u16), // load a wavetable from a pointer?
-    Pattern(u8), // change to pattern #
-    Beat(u8), // set next beat to beat #
-    Advance, // continues to the next pattern in the sequence
-    Stop, // stops the sequencer
-}
-
 pub enum ChannelFx {
     Tremolo(u8, u8),
     Vibrato(u8, u8),
@@ -84,48 +65,14 @@ pub enum ChannelFx {
 }
 
 
-#[derive(Debug, Clone)]
-pub enum ChannelCmd {
-    Tremolo(u8, u8), // volume
-    Vibrato(u8, u8), // pitch
-    Wavetable(u16), // set wavetable
-    Phase(u16), // set phase
-    Note(u8), // set note (freq)
-    Volume(u8), // volume index (0..=16)
-    SlideVol(u8, i16), // how many beats, delta
-    StopVSlide,
-    SlidePitch(u8, i16), // how many beats, delta
-    StopPSlide,
-}
-
-
-
-#[allow(dead_code)]
-pub struct TrackerData {
-    beat: u8,
-    pattern: u8,
-    sequence: u8,
-
-    sequences: [usize; 256], // a sequence is an array of pattern indices
-    patterns: Vec<Pattern>,
-}
-
 pub struct Tracker {
     tx_main: Sender<GlobalEvent>,
-    #[allow(dead_code)]
     tr_tx: Sender<TrackerCmd>,
     tr_rx: Receiver<TrackerCmd>,
 
     selected_subcomponent: Option<usize>,
     subcomponents: Vec<Box<dyn TSub>>,
-    handlers: Vec<Handler>,
-}
-
-pub fn tx_handler(tx: &Sender<TrackerCmd>, code: KeyCode, cmd: TrackerCmd) -> Handler {
-    let txx = tx.clone();
-    Handler { event: Event::Key(KeyEvent::new(code, KeyModifiers::NONE)), action: Box::new(move || {
-        let _ = txx.send(cmd);
-    })}
+    keys: Keymap<TrackerCmd>,
 }
 
 impl Tracker {
@@ -136,9 +83,10 @@ impl Tracker {
             Box::new(PatternEditor::init(tr_tx.clone())),
         ];
 
-        let handlers = vec![
-            tx_handler(&tr_tx, KeyCode::Char('q'), TrackerCmd::Quit),
-        ];
+        let keys = Keymap::new("tracker", &[
+            ("quit", TrackerCmd::Quit, &["q"], "back to the menu"),
+            ("edit_pattern", TrackerCmd::FocusComponent(Some(0)), &["enter"], "edit the pattern"),
+        ]);
 
         Tracker {
             tx_main,
@@ -146,30 +94,18 @@ impl Tracker {
             tr_rx,
             selected_subcomponent: Some(0),
             subcomponents,
-            handlers,
+            keys,
         }
     }
 }
 
 impl Component for Tracker {
     fn update(&mut self, events: Vec<ratatui::crossterm::event::Event>) {
-        for e in &events {
-            let handlers = match self.selected_subcomponent {
-                Some(selected) => self.subcomponents[selected].active_handlers(),
-                None => &self.handlers,
-            };
-
-            for h in handlers {
-                if h.event == *e {
-                    (h.action)()
-                }
-            }
-
-            for h in self.subcomponents.iter().flat_map(|c| c.global_handlers()) {
-                if h.event == *e {
-                    (h.action)()
-                }
-            }
+        match self.selected_subcomponent {
+            Some(selected) => self.subcomponents[selected].handle_keys(&events),
+            None => for cmd in self.keys.actions(&events) {
+                let _ = self.tr_tx.send(cmd);
+            },
         }
 
         for component in &mut self.subcomponents {
@@ -189,6 +125,17 @@ impl Component for Tracker {
         }
     }
 
+    fn help(&self) -> Vec<Help> {
+        match self.selected_subcomponent {
+            Some(selected) => self.subcomponents[selected].help(),
+            None => vec![self.keys.help()],
+        }
+    }
+
+    fn captures_text(&self) -> bool {
+        self.subcomponents.iter().any(|c| c.captures_text())
+    }
+
     fn render(&mut self, frame: &mut ratatui::Frame, _area: Rect) {
         let layout = Layout::default()
             .direction(Direction::Vertical)
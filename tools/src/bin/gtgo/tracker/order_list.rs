@@ -0,0 +1,213 @@
+//! Song arrangement
+//!
+//! The order list says which pattern plays when: each entry is a pattern
+//! and how many of its rows to play. After the loop end, playback goes back
+//! to the loop start, and entries past the loop end are left out of exports.
+
+use ratatui::{layout::Rect, style::Stylize, text::{Line, Span}, widgets::{Block, Borders, Paragraph}, Frame};
+
+use crate::{helpers::SCHEME, keymap::{Help, Keymap}, tracker::{empty_pattern, OrderEntry, TrackerData}};
+
+/// Columns the panel takes up beside the pattern
+pub const WIDTH: u16 = 16;
+
+#[derive(Clone, Copy)]
+pub enum OrderEvent {
+    Up,
+    Down,
+    Back,
+    Edit,
+    Play,
+    Insert,
+    Delete,
+    Duplicate,
+    Clone,
+    PatternUp,
+    PatternDown,
+    RowsUp,
+    RowsDown,
+    RowsUpMore,
+    RowsDownMore,
+    LoopStart,
+    LoopEnd,
+}
+
+pub struct OrderList {
+    /// Selected entry
+    pub sel: usize,
+    pub keys: Keymap<OrderEvent>,
+}
+
+impl OrderList {
+    pub fn init() -> Self {
+        let keys = Keymap::new("order_list", &[
+            ("back", OrderEvent::Back, &["esc", "tab"], "back to the pattern"),
+            ("up", OrderEvent::Up, &["up"], "up"),
+            ("down", OrderEvent::Down, &["down"], "down"),
+            ("edit", OrderEvent::Edit, &["enter"], "edit the entry's pattern"),
+            ("play", OrderEvent::Play, &["space"], "play the song from here"),
+            ("insert", OrderEvent::Insert, &["insert"], "insert entry"),
+            ("delete", OrderEvent::Delete, &["delete", "backspace"], "delete entry"),
+            ("duplicate", OrderEvent::Duplicate, &["d"], "duplicate entry"),
+            ("clone", OrderEvent::Clone, &["c"], "duplicate into a new pattern"),
+            ("pattern_up", OrderEvent::PatternUp, &["right"], "next pattern"),
+            ("pattern_down", OrderEvent::PatternDown, &["left"], "previous pattern"),
+            ("rows_up", OrderEvent::RowsUp, &["=", "+"], "longer"),
+            ("rows_down", OrderEvent::RowsDown, &["-"], "shorter"),
+            ("rows_up_more", OrderEvent::RowsUpMore, &["]"], "8 rows longer"),
+            ("rows_down_more", OrderEvent::RowsDownMore, &["["], "8 rows shorter"),
+            ("loop_start", OrderEvent::LoopStart, &["s"], "loop from here"),
+            ("loop_end", OrderEvent::LoopEnd, &["e"], "loop after here"),
+        ]);
+
+        Self { sel: 0, keys }
+    }
+
+    pub fn help(&self) -> Help {
+        self.keys.help()
+    }
+
+    fn selected<'a>(&self, data: &'a mut TrackerData) -> &'a mut OrderEntry {
+        let last = data.order.len() - 1;
+        &mut data.order[self.sel.min(last)]
+    }
+
+    /// Edit the order list. Returns a status message, if there's news.
+    pub fn apply(&mut self, data: &mut TrackerData, event: OrderEvent) -> Option<String> {
+        self.sel = self.sel.min(data.order.len() - 1);
+
+        let result = match event {
+            OrderEvent::Up => {
+                self.sel = self.sel.saturating_sub(1);
+                Ok(None)
+            }
+            OrderEvent::Down => {
+                self.sel = (self.sel + 1).min(data.order.len() - 1);
+                Ok(None)
+            }
+            OrderEvent::Insert => {
+                let entry = OrderEntry::new(data.pattern as usize);
+                data.insert_order(self.sel, entry).map(|_| None)
+            }
+            OrderEvent::Delete => data.remove_order(self.sel).map(|entry| {
+                self.sel = self.sel.min(data.order.len() - 1);
+                Some(format!("Removed pattern {:02X} from the song", entry.pattern))
+            }),
+            OrderEvent::Duplicate => {
+                let entry = *self.selected(data);
+                data.insert_order(self.sel + 1, entry).map(|_| {
+                    self.sel += 1;
+                    None
+                })
+            }
+            OrderEvent::Clone => self.clone_entry(data),
+            OrderEvent::PatternUp => {
+                // stepping past the last pattern makes a new one, like pgdn
+                let next = self.selected(data).pattern + 1;
+                if next >= data.patterns.len() && data.patterns.len() < u8::MAX as usize {
+                    data.patterns.push(empty_pattern());
+                }
+                self.selected(data).pattern = next.min(data.patterns.len() - 1);
+                Ok(None)
+            }
+            OrderEvent::PatternDown => {
+                let last = data.patterns.len() - 1;
+                let entry = self.selected(data);
+                entry.pattern = entry.pattern.saturating_sub(1).min(last);
+                Ok(None)
+            }
+            OrderEvent::RowsUp => Ok(self.resize(data, 1)),
+            OrderEvent::RowsDown => Ok(self.resize(data, -1)),
+            OrderEvent::RowsUpMore => Ok(self.resize(data, 8)),
+            OrderEvent::RowsDownMore => Ok(self.resize(data, -8)),
+            OrderEvent::LoopStart => {
+                data.loop_start = self.sel;
+                data.loop_end = data.loop_end.max(self.sel);
+                Ok(Some(format!("Looping from {:02X}", self.sel)))
+            }
+            OrderEvent::LoopEnd => {
+                data.loop_end = self.sel;
+                data.loop_start = data.loop_start.min(self.sel);
+                Ok(Some(format!("Looping after {:02X}", self.sel)))
+            }
+            // the pattern editor handles these
+            OrderEvent::Back | OrderEvent::Edit | OrderEvent::Play => Ok(None),
+        };
+
+        // the pattern shown follows the selection, to one that exists
+        let last = data.patterns.len() - 1;
+        data.pattern = u8::try_from(self.selected(data).pattern.min(last)).unwrap_or(u8::MAX);
+
+        result.unwrap_or_else(Some)
+    }
+
+    /// Copy the selected entry's pattern into a new one, and put that after it
+    fn clone_entry(&mut self, data: &mut TrackerData) -> Result<Option<String>, String> {
+        if data.patterns.len() >= u8::MAX as usize {
+            return Err("no room for another pattern".to_string());
+        }
+
+        let entry = *self.selected(data);
+        let copy = data.patterns.get(entry.pattern).cloned().unwrap_or_else(empty_pattern);
+        let pattern = data.patterns.len();
+
+        data.insert_order(self.sel + 1, OrderEntry { pattern, ..entry })?;
+        data.patterns.push(copy);
+        self.sel += 1;
+        Ok(Some(format!("Copied pattern {:02X} to {:02X}", entry.pattern, pattern)))
+    }
+
+    fn resize(&mut self, data: &mut TrackerData, delta: i16) -> Option<String> {
+        let entry = self.selected(data);
+        entry.rows = (entry.rows as i16 + delta).clamp(1, 64) as u8;
+        Some(format!("{} rows", entry.rows))
+    }
+
+    /// Draw the list, marking the entry `playing` and the loop
+    pub fn render(&self, frame: &mut Frame, area: Rect, data: &TrackerData, playing: Option<usize>, focused: bool) {
+        let block = Block::new()
+            .borders(Borders::RIGHT)
+            .title(" ORDER")
+            .fg(if focused { SCHEME.orange[1] } else { SCHEME.gray[1] })
+            .bg(SCHEME.true_dark_color(SCHEME.black[0]));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        // keep the selection in view
+        let height = inner.height as usize;
+        let first = self.sel.saturating_sub(height.saturating_sub(1) / 2)
+            .min(data.order.len().saturating_sub(height));
+
+        let lines: Vec<Line> = data.order.iter().enumerate().skip(first).take(height).map(|(i, entry)| {
+            let marker = match (i == data.loop_start, i == data.loop_end) {
+                (true, true) => "[",
+                (true, false) => "┌",
+                (false, true) => "└",
+                _ if i > data.loop_start && i < data.loop_end => "│",
+                _ => " ",
+            };
+            let missing = entry.pattern >= data.patterns.len();
+
+            let mut spans = vec![
+                Span::from(if playing == Some(i) { "▶" } else { " " }).fg(SCHEME.orange[1]),
+                Span::from(format!("{:02X}", i)).fg(SCHEME.deepblue[2]).italic(),
+                Span::from(format!(" {} ", marker)).fg(SCHEME.purple[1]),
+                Span::from(format!("{:02X}", entry.pattern)).fg(if missing { SCHEME.red[1] } else { SCHEME.orange[1] }),
+                Span::from(format!(" {:>2}", entry.rows)).fg(SCHEME.gray[2]),
+            ];
+            if i > data.loop_end {
+                // never played
+                spans = spans.into_iter().map(|s| s.fg(SCHEME.gray[0])).collect();
+            }
+
+            let mut line = Line::from(spans);
+            if i == self.sel {
+                let bg = if focused { SCHEME.blue[3] } else { SCHEME.blue[0] };
+                line = line.bg(SCHEME.true_dark_color(bg));
+            }
+            line
+        }).collect();
+
+        frame.render_widget(Paragraph::new(lines), inner);
+    }
+}
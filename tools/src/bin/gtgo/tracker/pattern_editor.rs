@@ -1,8 +1,10 @@
+use std::{ops::RangeInclusive, path::{Path, PathBuf}};
+
 use crossbeam_channel::{Receiver, Sender};
 use rat_widget::table::{selection::RowSelection, textdata::{Cell, Row}, Table, TableData, TableState};
-use ratatui::{crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers}, layout::{Constraint, Direction, Layout, Rect}, style::{Modifier, Style, Stylize}, text::{Line, Span}, widgets::Widget};
+use ratatui::{crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers}, layout::{Constraint, Direction, Layout, Rect}, style::{Modifier, Style, Stylize}, text::{Line, Span}, widgets::Widget};
 
-use crate::{helpers::SCHEME, tracker::{empty_pattern, lane::{Lane, LaneKind}, midi::MidiNote, Beat, ChannelCmd, Handler, Pattern, TSub, TrackerCmd, TrackerData}, Component};
+use crate::{helpers::SCHEME, keymap::{Help, Keymap}, tracker::{command, empty_pattern, export, fx_editor::FxEditor, instrument_editor::{song_wavetables, InstrumentEditor}, lane::{Lane, LaneKind}, midi::MidiNote, midi_input::{MidiEvent, MidiKeyboard}, order_list::{self, OrderEvent, OrderList}, preview::{Preview, MAX_VOLUME}, project, Beat, ChannelCmd, ChannelFlags, OrderEntry, Pattern, TSub, TrackerCmd, TrackerData, CHANNELS, DEFAULT_TEMPO}, ui::file_picker::{FilePicker, PickerMode}, Component};
 
 #[derive(Clone, Copy)]
 pub enum PatternEvent {
@@ -13,7 +15,112 @@ pub enum PatternEvent {
     Quit,
     Enter,
     SmallIncrement,
-    SmallDecrement
+    SmallDecrement,
+    Export,
+    Save,
+    Open,
+    PlayPause,
+    Stop,
+    ToggleLoop,
+    OctaveUp,
+    OctaveDown,
+    Delete,
+    InsertRow,
+    DeleteRow,
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    Copy,
+    Cut,
+    Paste,
+    TransposeUp,
+    TransposeDown,
+    PrevPattern,
+    NextPattern,
+    ToggleRecord,
+    QuantizeUp,
+    QuantizeDown,
+    NextMidiPort,
+    FocusOrder,
+    Order(OrderEvent),
+    ToggleMute,
+    ToggleSolo,
+    ToggleCollapse,
+    CollapseUnused,
+    Instruments,
+}
+
+/// Extension of exported songs, written next to the project
+const EXPORT_EXTENSION: &str = "gts";
+
+const ROWS: u8 = 64;
+
+/// Row the cursor stays on while the pattern scrolls past
+const CURSOR_ROW: i8 = 8;
+
+const DEFAULT_OCTAVE: u8 = 4;
+const MAX_OCTAVE: u8 = 8;
+
+/// Rows between recorded MIDI notes, at most
+const MAX_QUANTIZE: u8 = 16;
+
+/// Piano-style note keys: the bottom row plays the current octave, the top
+/// row the one above. Returns semitones above the octave's C.
+fn piano_key(c: char) -> Option<u8> {
+    const LOWER: &str = "zsxdcvgbhnjm";
+    const UPPER: &str = "q2w3er5t6y7ui";
+    LOWER.find(c).map(|i| i as u8)
+        .or_else(|| UPPER.find(c).map(|i| 12 + i as u8))
+}
+
+/// Columns for the channels as flagged: a note, volume and fx column each,
+/// or just a narrow note column when collapsed
+fn channel_lanes(channels: &[ChannelFlags]) -> Vec<Lane> {
+    let mut lanes = vec![Lane::beat(), Lane::seq()];
+    for (ch, flags) in channels.iter().enumerate() {
+        let ch = ch as u8;
+        if flags.collapsed {
+            lanes.push(Lane::collapsed(ch));
+        } else {
+            lanes.extend([Lane::note(ch), Lane::vol(ch), Lane::fx(ch)]);
+        }
+    }
+    lanes
+}
+
+/// Whether a channel command shows in the given column
+fn in_column(kind: LaneKind, cmd: &ChannelCmd) -> bool {
+    match kind {
+        LaneKind::Note => matches!(cmd, ChannelCmd::Note(_)),
+        LaneKind::Vol => matches!(cmd, ChannelCmd::Volume(_)),
+        LaneKind::Fx => !matches!(cmd, ChannelCmd::Note(_) | ChannelCmd::Volume(_)),
+        LaneKind::Beat | LaneKind::Seq => false,
+    }
+}
+
+/// The part of a beat that one column shows
+fn column_part(beat: &Beat, kind: LaneKind) -> Beat {
+    match kind {
+        LaneKind::Seq => Beat { cmd_list: vec![], sqc_list: beat.sqc_list.clone() },
+        _ => Beat {
+            cmd_list: beat.cmd_list.iter().filter(|c| in_column(kind, c)).cloned().collect(),
+            sqc_list: vec![],
+        },
+    }
+}
+
+fn clear_column(beat: &mut Beat, kind: LaneKind) {
+    match kind {
+        LaneKind::Seq => beat.sqc_list.clear(),
+        _ => beat.cmd_list.retain(|c| !in_column(kind, c)),
+    }
+}
+
+/// Text entry for the effect and sequencer columns
+struct Prompt {
+    kind: LaneKind,
+    text: String,
 }
 
 pub struct PatternEditor {
@@ -23,75 +130,297 @@ pub struct PatternEditor {
     pub scroll: i8,
     lanes: Vec<Lane>,
     tracker_data: TrackerData,
-    active_handlers: Vec<Handler>,
-    global_handlers: Vec<Handler>,
+    keys: Keymap<PatternEvent>,
     cx_rx: Receiver<PatternEvent>,
-    #[allow(dead_code)]
     cx_tx: Sender<PatternEvent>,
     par_tx: Sender<TrackerCmd>,
+    status: String,
+    project_path: Option<PathBuf>,
+    picker: Option<FilePicker>,
+    preview: Preview,
+    octave: u8,
+    prompt: Option<Prompt>,
+    /// Effects of the selected fx cell, being edited
+    fx: Option<FxEditor>,
+    /// The song's wavetables, being edited
+    instruments: Option<InstrumentEditor>,
+    /// Corner of the block selection opposite the cursor
+    anchor: Option<(u8, u8)>,
+    /// One entry per copied column, holding only that column's commands
+    clipboard: Vec<(LaneKind, Vec<Beat>)>,
+    midi: MidiKeyboard,
+    /// Whether MIDI notes are written into the pattern, not just heard
+    record: bool,
+    /// MIDI notes land on multiples of this many rows while playing, and
+    /// step the cursor this far while stopped
+    quantize: u8,
+    /// The MIDI key sounding, and the channel it's on
+    held: Option<(u8, usize)>,
+    order: OrderList,
+    /// Whether keys go to the order list instead of the pattern
+    order_focused: bool,
 }
 
 
-pub fn tx_handler(tx: &Sender<PatternEvent>, code: KeyCode, cmd: PatternEvent) -> Handler {
-    let txx = tx.clone();
-    let cmd = cmd.clone();
-    Handler { event: Event::Key(KeyEvent::new(code, KeyModifiers::NONE)), action: Box::new(move || {
-        let _ = txx.send(cmd);
-    })}
-}
-
 impl PatternEditor {
     pub fn init(parent_tx: Sender<TrackerCmd>) -> Self {
         let (cx_tx, cx_rx) = crossbeam_channel::unbounded();
 
-        let handlers = vec![
-            tx_handler(&cx_tx, KeyCode::Esc, PatternEvent::Quit),
-            tx_handler(&cx_tx, KeyCode::Char('q'), PatternEvent::Quit),
-            tx_handler(&cx_tx, KeyCode::Up, PatternEvent::Up),
-            tx_handler(&cx_tx, KeyCode::Down, PatternEvent::Down),
-            tx_handler(&cx_tx, KeyCode::Left, PatternEvent::Left),
-            tx_handler(&cx_tx, KeyCode::Right, PatternEvent::Right),
-            tx_handler(&cx_tx, KeyCode::Char('j'), PatternEvent::SmallIncrement),
-            tx_handler(&cx_tx, KeyCode::Char('k'), PatternEvent::SmallDecrement),
-        ];
+        let keys = Keymap::new("pattern_editor", &[
+            ("back", PatternEvent::Quit, &["esc"], "drop selection, then leave the pattern"),
+            ("up", PatternEvent::Up, &["up"], "up"),
+            ("down", PatternEvent::Down, &["down"], "down"),
+            ("left", PatternEvent::Left, &["left"], "left"),
+            ("right", PatternEvent::Right, &["right"], "right"),
+            ("select_up", PatternEvent::SelectUp, &["shift+up"], "select up"),
+            ("select_down", PatternEvent::SelectDown, &["shift+down"], "select down"),
+            ("select_left", PatternEvent::SelectLeft, &["shift+left"], "select left"),
+            ("select_right", PatternEvent::SelectRight, &["shift+right"], "select right"),
+            ("edit_commands", PatternEvent::Enter, &["enter"], "edit cmds"),
+            ("increment", PatternEvent::SmallIncrement, &["=", "+"], "nudge up"),
+            ("decrement", PatternEvent::SmallDecrement, &["-"], "nudge down"),
+            ("octave_up", PatternEvent::OctaveUp, &["]"], "octave up"),
+            ("octave_down", PatternEvent::OctaveDown, &["["], "octave down"),
+            ("delete", PatternEvent::Delete, &["delete"], "clear"),
+            ("insert_row", PatternEvent::InsertRow, &["insert"], "insert row"),
+            ("delete_row", PatternEvent::DeleteRow, &["backspace"], "delete row"),
+            ("copy", PatternEvent::Copy, &["ctrl+c"], "copy"),
+            ("cut", PatternEvent::Cut, &["ctrl+x"], "cut"),
+            ("paste", PatternEvent::Paste, &["ctrl+v"], "paste"),
+            ("transpose_up", PatternEvent::TransposeUp, &["alt+up"], "transpose up"),
+            ("transpose_down", PatternEvent::TransposeDown, &["alt+down"], "transpose down"),
+            ("prev_pattern", PatternEvent::PrevPattern, &["pgup"], "previous pattern"),
+            ("next_pattern", PatternEvent::NextPattern, &["pgdn"], "next pattern"),
+            ("play_pause", PatternEvent::PlayPause, &["space"], "play/pause"),
+            ("stop", PatternEvent::Stop, &["."], "stop"),
+            ("loop", PatternEvent::ToggleLoop, &["l"], "loop"),
+            ("record", PatternEvent::ToggleRecord, &["ctrl+r"], "record MIDI"),
+            ("quantize_up", PatternEvent::QuantizeUp, &[">"], "quantize up"),
+            ("quantize_down", PatternEvent::QuantizeDown, &["<"], "quantize down"),
+            ("midi_port", PatternEvent::NextMidiPort, &["ctrl+k"], "MIDI port"),
+            ("order_list", PatternEvent::FocusOrder, &["tab"], "arrange the song"),
+            ("mute", PatternEvent::ToggleMute, &["alt+m"], "mute channel"),
+            ("solo", PatternEvent::ToggleSolo, &["alt+s"], "solo channel"),
+            ("collapse", PatternEvent::ToggleCollapse, &["alt+c"], "collapse channel"),
+            ("collapse_unused", PatternEvent::CollapseUnused, &["alt+u"], "collapse unused channels"),
+            ("instruments", PatternEvent::Instruments, &["alt+w"], "wavetables"),
+            ("save", PatternEvent::Save, &["ctrl+s"], "save"),
+            ("open", PatternEvent::Open, &["ctrl+o"], "open"),
+            ("export", PatternEvent::Export, &["ctrl+e"], "export"),
+        ]);
+
+        // a keyboard plugged in before starting just works
+        let mut midi = MidiKeyboard::new();
+        let status = match midi.connect_next() {
+            Ok(name) => format!("MIDI: {}", name),
+            Err(_) => String::new(),
+        };
 
         Self {
-            scroll: -8,
-            lanes: vec![
-                Lane::beat(),
-                Lane::seq(),
-                Lane::note(0), Lane::vol(0), Lane::fx(0),
-                Lane::note(1), Lane::vol(1), Lane::fx(1),
-                Lane::note(2), Lane::vol(2), Lane::fx(2),
-                Lane::note(3), Lane::vol(3), Lane::fx(3),
-                Lane::note(4), Lane::vol(4), Lane::fx(4),
-                Lane::note(5), Lane::vol(5), Lane::fx(5),
-                Lane::note(6), Lane::vol(6), Lane::fx(6),
-                Lane::note(7), Lane::vol(7), Lane::fx(7),
-            ],
+            scroll: 2 - CURSOR_ROW,
+            lanes: channel_lanes(&[ChannelFlags::default(); CHANNELS]),
             tracker_data: TrackerData {
                 beat: 0,
                 pattern: 0,
                 sequence: 0,
-                sequences: [0; 256],
+                tempo: DEFAULT_TEMPO,
+                order: vec![OrderEntry::new(0)],
+                loop_start: 0,
+                loop_end: 0,
                 patterns: vec![empty_pattern()],
+                channels: [ChannelFlags::default(); CHANNELS],
             },
             sel_x: 2,
             sel_y: 2,
-            active_handlers: handlers,
+            keys,
             cx_rx,
             cx_tx,
             par_tx: parent_tx,
-            global_handlers: vec![], // mostly for mouse events ig
+            status,
+            project_path: None,
+            picker: None,
+            preview: Preview::new(),
+            octave: DEFAULT_OCTAVE,
+            prompt: None,
+            fx: None,
+            instruments: None,
+            anchor: None,
+            clipboard: vec![],
+            midi,
+            record: false,
+            quantize: 1,
+            held: None,
+            order: OrderList::init(),
+            order_focused: false,
+        }
+    }
+
+    fn save(&mut self, path: &Path) {
+        self.status = match project::save(&self.tracker_data, path) {
+            Ok(()) => {
+                self.project_path = Some(path.to_path_buf());
+                self.preview.set_wavetables(song_wavetables(path));
+                format!("Saved {}", path.display())
+            }
+            Err(e) => e,
+        };
+    }
+
+    fn open(&mut self, path: &Path) {
+        self.status = match project::load(path) {
+            Ok(data) => {
+                self.tracker_data = data;
+                self.order.sel = 0;
+                self.relayout();
+                self.project_path = Some(path.to_path_buf());
+                self.preview.set_wavetables(song_wavetables(path));
+                format!("Opened {}", path.display())
+            }
+            Err(e) => e,
+        };
+    }
+
+    fn open_picker(&mut self, mode: PickerMode) {
+        self.picker = Some(FilePicker::init(mode, project::EXTENSION, &self.project_dir()));
+    }
+
+    /// Where the project is, or the current directory before it's saved
+    fn project_dir(&self) -> PathBuf {
+        self.project_path.as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    /// Feed input to the open file picker, acting on its result once it closes
+    fn update_picker(&mut self, events: Vec<Event>) {
+        let Some(picker) = &mut self.picker else { return };
+        picker.update(events);
+
+        if picker.is_active() {
+            return;
+        }
+
+        let mode = picker.mode();
+        let picked = picker.take_picked();
+        self.picker = None;
+
+        // the instrument editor asked for a WAV
+        if let Some(editor) = &mut self.instruments {
+            if let Some(path) = picked {
+                self.status = editor.import(&path);
+            }
+            return;
+        }
+
+        match (mode, picked) {
+            (PickerMode::Save, Some(path)) => self.save(&path),
+            (PickerMode::Open, Some(path)) => self.open(&path),
+            (_, None) => {}
+        }
+    }
+
+    fn export(&mut self) {
+        let Some(path) = self.project_path.as_ref().map(|p| p.with_extension(EXPORT_EXTENSION)) else {
+            self.status = "Save the project first: the song is exported next to it".to_string();
+            return;
+        };
+        let result = export::export_song(&self.tracker_data).and_then(|bytes| {
+            std::fs::write(&path, &bytes)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            Ok(bytes.len())
+        });
+
+        self.status = match result {
+            Ok(len) => format!("Exported {} ({} bytes)", path.display(), len),
+            Err(e) => format!("Export failed: {}", e),
+        };
+    }
+
+    fn play_pause(&mut self) {
+        if self.preview.is_playing() {
+            self.preview.pause();
+        } else if !self.preview.play(&self.tracker_data, self.tracker_data.pattern as usize, self.sel_y) {
+            self.status = "No audio device".to_string();
+        }
+    }
+
+    fn order_event(&mut self, event: OrderEvent) {
+        match event {
+            OrderEvent::Back | OrderEvent::Edit => self.order_focused = false,
+            OrderEvent::Play if self.preview.is_playing() => self.preview.pause(),
+            OrderEvent::Play => if !self.preview.play_order(&self.tracker_data, self.order.sel) {
+                self.status = "No audio device".to_string();
+            },
+            _ => if let Some(status) = self.order.apply(&mut self.tracker_data, event) {
+                self.status = status;
+            },
         }
     }
 
+    /// Rebuild the columns after channels collapse or expand, keeping the
+    /// cursor on the same channel
+    fn relayout(&mut self) {
+        let lane = &self.lanes[self.sel_x as usize];
+        let (ch, kind) = (lane.ch, lane.kind);
+
+        self.lanes = channel_lanes(&self.tracker_data.channels);
+        self.anchor = None;
+        let sel = self.lanes.iter().position(|l| l.ch == ch && l.kind == kind)
+            .or_else(|| self.lanes.iter().position(|l| l.ch == ch))
+            .unwrap_or(0);
+        self.sel_x = sel as u8;
+    }
+
+    /// Flip a flag of the cursor's channel
+    fn toggle_channel(&mut self, event: PatternEvent) {
+        let Some(ch) = self.lanes[self.sel_x as usize].ch else {
+            self.status = "Not on a channel".to_string();
+            return;
+        };
+
+        let flags = &mut self.tracker_data.channels[ch];
+        let (name, on) = match event {
+            PatternEvent::ToggleMute => { flags.muted = !flags.muted; ("muted", flags.muted) }
+            PatternEvent::ToggleSolo => { flags.solo = !flags.solo; ("soloed", flags.solo) }
+            _ => { flags.collapsed = !flags.collapsed; ("collapsed", flags.collapsed) }
+        };
+        self.status = format!("Channel {} {}{}", ch, if on { "" } else { "un" }, name);
+
+        if matches!(event, PatternEvent::ToggleCollapse) {
+            self.relayout();
+        }
+    }
+
+    /// Collapse the channels no pattern uses, and expand the rest
+    fn collapse_unused(&mut self) {
+        let mut collapsed = 0;
+        for ch in 0..CHANNELS {
+            let unused = self.tracker_data.is_unused(ch);
+            self.tracker_data.channels[ch].collapsed = unused;
+            collapsed += unused as usize;
+        }
+        self.status = format!("Collapsed {} unused channel(s)", collapsed);
+        self.relayout();
+    }
+
+    /// Hear the selected row after changing it
+    fn audition(&mut self) {
+        self.preview.audition(&self.tracker_data, self.tracker_data.pattern as usize, self.sel_y);
+    }
+
+    /// The pattern shown, or the last one if that's past the end
+    fn current_index(&self) -> usize {
+        (self.tracker_data.pattern as usize).min(self.tracker_data.patterns.len() - 1)
+    }
+
     pub fn current_pattern(&self) -> &Pattern {
-        &self.tracker_data.patterns[self.tracker_data.pattern as usize]
+        &self.tracker_data.patterns[self.current_index()]
     }
 
     pub fn current_pattern_mut(&mut self) -> &mut Pattern {
-        &mut self.tracker_data.patterns[self.tracker_data.pattern as usize]
+        let index = self.current_index();
+        &mut self.tracker_data.patterns[index]
     }
 
     fn get_channel_beat(ch: Option<usize>, beat: u8, pattern: &Pattern) -> &Beat {
@@ -101,17 +430,455 @@ impl PatternEditor {
         }
     }
 
-    pub fn get_selected_beat(&mut self) -> Option<&mut Beat> {
-        // TODO: this is gonna confuse the SHIT out of people
-        let beat_idx = self.sel_y as usize;
+    /// Index into the pattern of the selected column's lane, or `None` on
+    /// the beat column (which stands for every lane)
+    fn selected_lane(&self) -> Option<usize> {
         let lane = &self.lanes[self.sel_x as usize];
-        let ch_idx = match lane.kind {
+        match lane.kind {
             LaneKind::Beat => None,
             LaneKind::Seq => Some(0),
-            _ => lane.ch,
-        }?;
+            _ => lane.ch.map(|ch| ch + 1),
+        }
+    }
+
+    pub fn get_selected_beat(&mut self) -> Option<&mut Beat> {
+        let beat_idx = self.sel_y as usize;
+        let lane = self.selected_lane()?;
+        Some(&mut self.current_pattern_mut()[lane][beat_idx])
+    }
+
+    fn move_cursor(&mut self, dx: i16, dy: i16) {
+        self.sel_x = (self.sel_x as i16 + dx).clamp(0, self.lanes.len() as i16 - 1) as u8;
+        self.sel_y = (self.sel_y as i16 + dy).clamp(0, ROWS as i16 - 1) as u8;
+        self.scroll = self.sel_y as i8 - CURSOR_ROW;
+    }
+
+    fn enter_note(&mut self, semitone: u8) {
+        let note = ((self.octave + 1) * 12 + semitone).min(127);
+        let Some(beat) = self.get_selected_beat() else { return };
+
+        beat.cmd_list.retain(|c| !matches!(c, ChannelCmd::Note(_)));
+        beat.cmd_list.insert(0, ChannelCmd::Note(note));
+
+        self.audition();
+        self.move_cursor(0, 1);
+    }
+
+    /// Hear MIDI notes on the cursor's channel, and write them into the
+    /// pattern when recording. The keyboard plays at its own pitch in the
+    /// default octave, and `[`/`]` shift it.
+    fn update_midi(&mut self) {
+        let events: Vec<MidiEvent> = self.midi.events().collect();
+        let ch = self.lanes[self.sel_x as usize].ch.unwrap_or(0);
+        let shift = (self.octave as i16 - DEFAULT_OCTAVE as i16) * 12;
+        // typing into the picker or prompt shouldn't be disturbed
+        let can_record = self.record && self.picker.is_none() && self.prompt.is_none() && self.fx.is_none() && self.instruments.is_none();
+
+        for event in events {
+            match event {
+                MidiEvent::NoteOn { note: key, velocity } => {
+                    let note = (key as i16 + shift).clamp(0, 127) as u8;
+                    let volume = ((velocity as u16 * MAX_VOLUME as u16 / 127) as u8).max(1);
+                    if !self.preview.note_on(ch, note, volume) {
+                        self.status = "No audio device".to_string();
+                    }
+                    self.held = Some((key, ch));
 
-        Some(&mut self.current_pattern_mut()[ch_idx][beat_idx])
+                    if can_record {
+                        self.record_note(ch + 1, note);
+                    }
+                }
+                MidiEvent::NoteOff { note: key } => {
+                    // only the latest key, so playing legato doesn't cut notes off
+                    if let Some((_, ch)) = self.held.filter(|&(held, _)| held == key) {
+                        self.preview.release(ch);
+                        self.held = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write a MIDI note into `lane`: at the quantized playhead while playing,
+    /// otherwise at the cursor, stepping it down
+    fn record_note(&mut self, lane: usize, note: u8) {
+        let q = self.quantize as u16;
+        let (pattern, row) = match self.preview.playhead() {
+            Some((pattern, row)) => (pattern, ((row as u16 + q / 2) / q * q % ROWS as u16) as u8),
+            None => (self.tracker_data.pattern as usize, self.sel_y),
+        };
+        let Some(pattern) = self.tracker_data.patterns.get_mut(pattern) else { return };
+
+        let beat = &mut pattern[lane][row as usize];
+        beat.cmd_list.retain(|c| !matches!(c, ChannelCmd::Note(_)));
+        beat.cmd_list.insert(0, ChannelCmd::Note(note));
+
+        if !self.preview.is_playing() {
+            self.move_cursor(0, self.quantize as i16);
+        }
+    }
+
+    fn connect_midi(&mut self) {
+        self.status = match self.midi.connect_next() {
+            Ok(name) => format!("MIDI: {}", name),
+            Err(e) => e,
+        };
+    }
+
+    /// Shift a hex digit into the volume, e.g. `3` then `F` for 0x3F
+    fn enter_volume_digit(&mut self, digit: u8) {
+        let Some(beat) = self.get_selected_beat() else { return };
+
+        let existing = beat.cmd_list.iter_mut().find_map(|c| match c {
+            ChannelCmd::Volume(v) => Some(v),
+            _ => None,
+        });
+        match existing {
+            Some(v) => *v = ((*v << 4) | digit).min(MAX_VOLUME),
+            None => beat.cmd_list.push(ChannelCmd::Volume(digit)),
+        }
+    }
+
+    /// Step the note or volume under the cursor
+    fn nudge(&mut self, delta: i16) {
+        let kind = self.lanes[self.sel_x as usize].kind;
+        let Some(beat) = self.get_selected_beat() else { return };
+
+        match kind {
+            LaneKind::Note => {
+                let found = beat.cmd_list.iter_mut().rev().find_map(|c| match c {
+                    ChannelCmd::Note(n) => {
+                        *n = (*n as i16 + delta).clamp(0, 127) as u8; Some(())
+                    }
+                    _ => None,
+                });
+                if found.is_none() {
+                    beat.cmd_list.push(ChannelCmd::Note(MidiNote::C4 as u8));
+                }
+                self.audition();
+            }
+            LaneKind::Vol => {
+                let found = beat.cmd_list.iter_mut().rev().find_map(|c| match c {
+                    ChannelCmd::Volume(v) => {
+                        *v = (*v as i16 + delta).clamp(0, MAX_VOLUME as i16) as u8; Some(())
+                    }
+                    _ => None,
+                });
+                if found.is_none() {
+                    beat.cmd_list.push(ChannelCmd::Volume(MAX_VOLUME));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Move the cursor, dropping the block selection
+    fn move_to(&mut self, dx: i16, dy: i16) {
+        self.anchor = None;
+        self.move_cursor(dx, dy);
+    }
+
+    /// Move the cursor, growing the block selection
+    fn select(&mut self, dx: i16, dy: i16) {
+        if self.anchor.is_none() {
+            self.anchor = Some((self.sel_x, self.sel_y));
+        }
+        self.move_cursor(dx, dy);
+    }
+
+    /// Columns and rows covered by the selection, or just the cursor without
+    /// one. The beat column stands for every column.
+    fn block(&self) -> (RangeInclusive<usize>, RangeInclusive<usize>) {
+        let (ax, ay) = self.anchor.unwrap_or((self.sel_x, self.sel_y));
+        let (x0, x1) = (ax.min(self.sel_x) as usize, ax.max(self.sel_x) as usize);
+        let (y0, y1) = (ay.min(self.sel_y) as usize, ay.max(self.sel_y) as usize);
+
+        let cols = if x0 == 0 { 1..=self.lanes.len() - 1 } else { x0..=x1 };
+        (cols, y0..=y1)
+    }
+
+    fn in_block(&self, column: usize, row: i8) -> bool {
+        if self.anchor.is_none() || row < 0 {
+            return false;
+        }
+        let (cols, rows) = self.block();
+        cols.contains(&column) && rows.contains(&(row as usize))
+    }
+
+    /// Index into the pattern of a column's lane
+    fn pattern_lane(&self, column: usize) -> usize {
+        match self.lanes[column].ch {
+            Some(ch) => ch + 1,
+            None => 0,
+        }
+    }
+
+    fn copy(&mut self) {
+        let (cols, rows) = self.block();
+        let pattern = self.current_pattern();
+
+        let clipboard: Vec<_> = cols.map(|col| {
+            let kind = self.lanes[col].kind;
+            let lane = &pattern[self.pattern_lane(col)];
+            (kind, rows.clone().map(|row| column_part(&lane[row], kind)).collect())
+        }).collect();
+
+        self.status = format!("Copied {} column(s) x {} row(s)", clipboard.len(), rows.count());
+        self.clipboard = clipboard;
+    }
+
+    /// Clear whatever the selected columns show
+    fn delete_block(&mut self) {
+        let (cols, rows) = self.block();
+
+        for col in cols {
+            let kind = self.lanes[col].kind;
+            let lane = self.pattern_lane(col);
+            for row in rows.clone() {
+                clear_column(&mut self.current_pattern_mut()[lane][row], kind);
+            }
+        }
+    }
+
+    /// Paste at the cursor, into columns of the same kind. The pasted block
+    /// ends up selected, ready to transpose.
+    fn paste(&mut self) {
+        if self.clipboard.is_empty() {
+            self.status = "Clipboard is empty".to_string();
+            return;
+        }
+
+        let start_col = (self.sel_x as usize).max(1);
+        let start_row = self.sel_y as usize;
+        let clipboard = std::mem::take(&mut self.clipboard);
+        let mut skipped = 0;
+
+        for (i, (kind, cells)) in clipboard.iter().enumerate() {
+            let col = start_col + i;
+            if col >= self.lanes.len() {
+                break;
+            }
+            if self.lanes[col].kind != *kind {
+                skipped += 1;
+                continue;
+            }
+
+            let lane = self.pattern_lane(col);
+            for (row, part) in (start_row..ROWS as usize).zip(cells) {
+                let beat = &mut self.current_pattern_mut()[lane][row];
+                clear_column(beat, *kind);
+                beat.cmd_list.extend(part.cmd_list.iter().cloned());
+                beat.sqc_list.extend(part.sqc_list.iter().cloned());
+            }
+        }
+
+        let (width, height) = (clipboard.len(), clipboard[0].1.len());
+        self.clipboard = clipboard;
+        self.sel_x = start_col as u8;
+        self.anchor = Some((
+            (start_col + width - 1).min(self.lanes.len() - 1) as u8,
+            (start_row + height - 1).min(ROWS as usize - 1) as u8,
+        ));
+        self.status = match skipped {
+            0 => "Pasted".to_string(),
+            n => format!("Pasted, skipped {} column(s) of a different kind", n),
+        };
+    }
+
+    /// Shift every note in the block by `delta` semitones
+    fn transpose(&mut self, delta: i16) {
+        let (cols, rows) = self.block();
+
+        let lanes: Vec<usize> = cols
+            .filter(|&c| self.lanes[c].kind == LaneKind::Note)
+            .map(|c| self.pattern_lane(c))
+            .collect();
+
+        for lane in lanes {
+            for row in rows.clone() {
+                for cmd in &mut self.current_pattern_mut()[lane][row].cmd_list {
+                    if let ChannelCmd::Note(n) = cmd {
+                        *n = (*n as i16 + delta).clamp(0, 127) as u8;
+                    }
+                }
+            }
+        }
+
+        self.audition();
+    }
+
+    fn change_pattern(&mut self, delta: i16) {
+        let data = &mut self.tracker_data;
+        let next = (data.pattern as i16 + delta).clamp(0, u8::MAX as i16) as u8;
+
+        if next as usize == data.patterns.len() {
+            data.patterns.push(empty_pattern());
+        }
+        data.pattern = next;
+        self.status = format!("Pattern {:02X}", next);
+    }
+
+    /// Insert an empty row at the cursor, pushing the rest of the lane down.
+    /// On the beat column this applies to every lane.
+    fn shift_rows(&mut self, insert: bool) {
+        let row = self.sel_y as usize;
+        let selected = self.selected_lane();
+
+        for (i, lane) in self.current_pattern_mut().iter_mut().enumerate() {
+            if selected.is_some_and(|s| s != i) {
+                continue;
+            }
+            if insert {
+                lane[row..].rotate_right(1);
+                lane[row] = Beat::default();
+            } else {
+                lane[row..].rotate_left(1);
+                lane[ROWS as usize - 1] = Beat::default();
+            }
+        }
+    }
+
+    /// Edit the selected cell's commands: effects in the effect editor,
+    /// sequencer commands in the prompt
+    fn open_prompt(&mut self) {
+        let lane = self.lanes[self.sel_x as usize].clone();
+        let row = self.sel_y;
+        let Some(beat) = self.get_selected_beat() else { return };
+
+        match lane.kind {
+            LaneKind::Fx => {
+                let cmds = beat.cmd_list.iter().filter(|c| in_column(LaneKind::Fx, c)).copied().collect();
+                let title = format!("FX {} row {:02X}", lane.ch.unwrap_or(0) + 1, row);
+                self.fx = Some(FxEditor::init(title, cmds));
+            }
+            LaneKind::Seq => {
+                let text = beat.sqc_list.iter().map(command::format_sequencer_cmd).collect::<Vec<_>>().join("; ");
+                self.prompt = Some(Prompt { kind: lane.kind, text });
+            }
+            _ => {}
+        }
+    }
+
+    /// Feed keys to the effect editor, writing its effects back into the cell
+    fn update_fx(&mut self, events: &[Event]) {
+        let Some(fx) = &mut self.fx else { return };
+        if let Some(status) = fx.update(events) {
+            self.status = status;
+        }
+        let cmds = fx.cmds.clone();
+        let as_text = fx.wants_text();
+        if fx.is_done() || as_text {
+            self.fx = None;
+        }
+        if as_text {
+            let text = cmds.iter().filter_map(command::format_channel_cmd).collect::<Vec<_>>().join("; ");
+            self.prompt = Some(Prompt { kind: LaneKind::Fx, text });
+        }
+
+        if let Some(beat) = self.get_selected_beat() {
+            beat.cmd_list.retain(|c| !in_column(LaneKind::Fx, c));
+            beat.cmd_list.extend(cmds);
+        }
+    }
+
+    fn open_instruments(&mut self) {
+        match &self.project_path {
+            Some(path) => self.instruments = Some(InstrumentEditor::init(path)),
+            None => self.status = "Save the project first: its wavetables go next to it".to_string(),
+        }
+    }
+
+    /// Feed keys to the instrument editor, opening the file picker and
+    /// playing tables for it
+    fn update_instruments(&mut self, events: &[Event]) {
+        let Some(editor) = &mut self.instruments else { return };
+        if let Some(status) = editor.update(events) {
+            self.status = status;
+        }
+        let pick = editor.take_pick();
+        let table = editor.take_preview();
+        if editor.is_done() {
+            self.instruments = None;
+            if let Some(path) = &self.project_path {
+                self.preview.set_wavetables(song_wavetables(path));
+            }
+        }
+
+        if pick {
+            self.picker = Some(FilePicker::init(PickerMode::Open, "wav", &self.project_dir()));
+        }
+        if let Some(table) = table {
+            let note = (self.octave + 1) * 12;
+            if !self.preview.audition_wavetable(&table, note) {
+                self.status = "No audio device".to_string();
+            }
+        }
+    }
+
+    /// Replace the selected cell's commands with the prompt's
+    fn apply_prompt(&mut self, prompt: Prompt) {
+        let result = match prompt.kind {
+            LaneKind::Fx => command::parse_channel_cmds(&prompt.text).map(|cmds| {
+                if let Some(beat) = self.get_selected_beat() {
+                    beat.cmd_list.retain(|c| !in_column(LaneKind::Fx, c));
+                    beat.cmd_list.extend(cmds);
+                }
+            }),
+            LaneKind::Seq => command::parse_sequencer_cmds(&prompt.text).map(|cmds| {
+                if let Some(beat) = self.get_selected_beat() {
+                    beat.sqc_list = cmds;
+                }
+            }),
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            self.status = e;
+            // keep the text so it can be fixed
+            self.prompt = Some(prompt);
+        }
+    }
+
+    fn update_prompt(&mut self, events: Vec<Event>) {
+        for e in events {
+            let Event::Key(KeyEvent { code, kind: KeyEventKind::Press, .. }) = e else { continue };
+            let Some(prompt) = &mut self.prompt else { return };
+
+            match code {
+                KeyCode::Char(c) => prompt.text.push(c),
+                KeyCode::Backspace => { prompt.text.pop(); }
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Enter => {
+                    if let Some(prompt) = self.prompt.take() {
+                        self.status.clear();
+                        self.apply_prompt(prompt);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Note, volume, and hex digit keys, which depend on the selected column
+    fn update_entry(&mut self, events: &[Event]) {
+        let kind = self.lanes[self.sel_x as usize].kind;
+
+        for e in events {
+            let Event::Key(KeyEvent { code: KeyCode::Char(c), modifiers, kind: KeyEventKind::Press, .. }) = e else { continue };
+            if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                continue;
+            }
+
+            match kind {
+                LaneKind::Note => if let Some(semitone) = piano_key(*c) {
+                    self.enter_note(semitone);
+                },
+                LaneKind::Vol => if let Some(digit) = c.to_digit(16) {
+                    self.enter_volume_digit(digit as u8);
+                },
+                _ => {}
+            }
+        }
     }
 
     pub fn get_cell(&self, row: usize, column: usize) -> CellDisplay {
@@ -181,15 +948,24 @@ impl <'a> TableData<'a> for &mut PatternEditor {
             SCHEME.magenta[3],
         ];
 
+        let data = &self.tracker_data;
+        // channels the preview isn't playing are greyed out
+        let color = |ch: usize| if data.audible(ch) { c[ch] } else { SCHEME.gray[0] };
+
         let mut cells = vec![];
 
         for lane in &self.lanes {
             let cell = Cell::new(match lane.kind {
                 LaneKind::Beat => Span::from(lane.title.clone()),
                 LaneKind::Seq => Span::from(lane.title.clone()),
-                LaneKind::Note => Span::from(lane.title.clone()).fg(c[lane.ch.unwrap()]).italic(),
-                LaneKind::Vol => Span::from(lane.title.clone()).fg(c[lane.ch.unwrap()]),
-                LaneKind::Fx => Span::from(lane.title.clone()).fg(c[lane.ch.unwrap()]),
+                LaneKind::Note => {
+                    let ch = lane.ch.unwrap();
+                    let flags = data.channels[ch];
+                    let marker = if flags.solo { "S" } else if flags.muted { "M" } else { " " };
+                    Span::from(format!("{}{}", marker, &lane.title[1..])).fg(color(ch)).italic()
+                }
+                LaneKind::Vol => Span::from(lane.title.clone()).fg(color(lane.ch.unwrap())),
+                LaneKind::Fx => Span::from(lane.title.clone()).fg(color(lane.ch.unwrap())),
             });
             cells.push(cell);
         }
@@ -212,15 +988,19 @@ impl <'a> TableData<'a> for &mut PatternEditor {
         let is_active = (0..64).contains(&offset);
         let row_selected = row == (self.sel_y as i8 - self.scroll) as usize;
         let col_selected = column == self.sel_x as usize;
+        let row_playing = self.preview.playhead()
+            .is_some_and(|(pattern, beat)| pattern == self.tracker_data.pattern as usize && offset == beat as i8);
 
         let cell = self.get_cell(row, column);
         
-        let style = if row_selected {
-            if col_selected {
-                CellStyle::SelectedCell
-            } else {
-                CellStyle::SelectedRow
-            }
+        let style = if row_selected && col_selected {
+            CellStyle::SelectedCell
+        } else if self.in_block(column, offset) {
+            CellStyle::Block
+        } else if row_selected {
+            CellStyle::SelectedRow
+        } else if row_playing {
+            CellStyle::Bar
         } else if row_even {
             CellStyle::EvenRow
         } else {
@@ -241,13 +1021,14 @@ pub enum CellStyle {
     SelectedRow,
     SelectedCell,
     Bar,
+    Block,
 }
 
 pub enum CellDisplay {
     BeatNum(u8), // cell number & is_active
     SeqCmds(usize), // 0 is ---, n is [n]
     Note(MidiNote),
-    Vol(Option<u8>), // 0..=16 (no change is -)
+    Vol(Option<u8>), // 0..=63 (no change is --)
     Fx(u8), // fx count, 0 is ---, n is [n]
 }
 
@@ -261,8 +1042,8 @@ impl CellDisplay {
             },
             CellDisplay::Note(midi_note) => midi_note.to_string(),
             CellDisplay::Vol(maybe_set) => match maybe_set {
-                Some(v) => format!("{:1x}", v),
-                None => "-".to_string(),
+                Some(v) => format!("{:02X}", v),
+                None => "--".to_string(),
             },
             CellDisplay::Fx(n) => match n {
                 0 => "---".to_string(),
@@ -306,7 +1087,8 @@ impl CellDisplay {
                 style = style.fg(SCHEME.deepblue[1]);
                 (SCHEME.true_dark_color(SCHEME.blue[3]), Modifier::SLOW_BLINK | Modifier::REVERSED)
             },
-            CellStyle::Bar => todo!(),
+            CellStyle::Bar => (SCHEME.true_dark_color(SCHEME.orange[3]), Modifier::empty()),
+            CellStyle::Block => (SCHEME.true_dark_color(SCHEME.purple[3]), Modifier::empty()),
         };
 
         let style = style.bg(row_bg).add_modifier(add_modifiers);
@@ -340,51 +1122,130 @@ impl CellDisplay {
 }
 
 impl Component for PatternEditor {
-    fn update(&mut self, _events: Vec<Event>) {
-        let (lane_kind, ch) = {
-            let lane = &self.lanes[self.sel_x as usize];
-            let kind = lane.kind;
-            let ch = lane.ch;
-            (kind, ch)
-        };
-        let sel_beat = self.sel_y as usize;
+    fn update(&mut self, events: Vec<Event>) {
+        self.preview.update(&self.tracker_data);
+        self.update_midi();
+
+        if self.picker.is_some() {
+            // keys belong to the picker while it's open
+            self.cx_rx.try_iter().for_each(drop);
+            self.update_picker(events);
+            return;
+        }
+
+        if self.prompt.is_some() {
+            // and to the prompt while that's open
+            self.cx_rx.try_iter().for_each(drop);
+            self.update_prompt(events);
+            return;
+        }
+
+        if self.fx.is_some() {
+            self.cx_rx.try_iter().for_each(drop);
+            self.update_fx(&events);
+            return;
+        }
+
+        if self.instruments.is_some() {
+            self.cx_rx.try_iter().for_each(drop);
+            self.update_instruments(&events);
+            return;
+        }
 
         while let Ok(event) = self.cx_rx.try_recv() {
             match event {
-                PatternEvent::Up => self.sel_y -= 1,
-                PatternEvent::Down => self.sel_y += 1,
-                PatternEvent::Left => self.sel_x -= 1,
-                PatternEvent::Right => self.sel_x += 1,
-                PatternEvent::Enter => todo!(),
+                PatternEvent::Up => self.move_to(0, -1),
+                PatternEvent::Down => self.move_to(0, 1),
+                PatternEvent::Left => self.move_to(-1, 0),
+                PatternEvent::Right => self.move_to(1, 0),
+                PatternEvent::SelectUp => self.select(0, -1),
+                PatternEvent::SelectDown => self.select(0, 1),
+                PatternEvent::SelectLeft => self.select(-1, 0),
+                PatternEvent::SelectRight => self.select(1, 0),
+                PatternEvent::Copy => self.copy(),
+                PatternEvent::Cut => {
+                    self.copy();
+                    self.delete_block();
+                }
+                PatternEvent::Paste => self.paste(),
+                PatternEvent::TransposeUp => self.transpose(1),
+                PatternEvent::TransposeDown => self.transpose(-1),
+                PatternEvent::PrevPattern => self.change_pattern(-1),
+                PatternEvent::NextPattern => self.change_pattern(1),
+                PatternEvent::Enter => self.open_prompt(),
+                // Esc drops the selection first
+                PatternEvent::Quit if self.anchor.is_some() => self.anchor = None,
                 PatternEvent::Quit => { let _ = self.par_tx.send(TrackerCmd::FocusComponent(None)); },
-                PatternEvent::SmallIncrement => {
-                    if let Some(channel) = ch {
-                        let beat = &mut self.current_pattern_mut()[channel+1][sel_beat];
-                        match lane_kind {
-                            LaneKind::Note => {
-                                let found = beat.cmd_list.iter_mut().rev().find_map(|c| match c {
-                                    ChannelCmd::Note(n) => {
-                                        *n = n.saturating_add(1).min(127); Some(())
-                                    }
-                                    _ => None,
-                                });
-                                if found.is_none() {
-                                    beat.cmd_list.push(ChannelCmd::Note(MidiNote::C4 as u8));
-                                }
-                            }
-                            LaneKind::Vol => todo!(),
-                            _ => { println!("wrong col!") }
-                        }
-                    }
+                PatternEvent::SmallIncrement => self.nudge(1),
+                PatternEvent::SmallDecrement => self.nudge(-1),
+                PatternEvent::OctaveUp => self.octave = (self.octave + 1).min(MAX_OCTAVE),
+                PatternEvent::OctaveDown => self.octave = self.octave.saturating_sub(1),
+                PatternEvent::Delete => self.delete_block(),
+                PatternEvent::InsertRow => self.shift_rows(true),
+                PatternEvent::DeleteRow => self.shift_rows(false),
+                PatternEvent::Export => self.export(),
+                PatternEvent::Save => match self.project_path.clone() {
+                    Some(path) => self.save(&path),
+                    None => self.open_picker(PickerMode::Save),
+                },
+                PatternEvent::Open => self.open_picker(PickerMode::Open),
+                PatternEvent::PlayPause => self.play_pause(),
+                PatternEvent::Stop => self.preview.pause(),
+                PatternEvent::ToggleRecord => {
+                    self.record = !self.record;
+                    self.status = if self.record { "Recording MIDI" } else { "Not recording" }.to_string();
+                }
+                PatternEvent::QuantizeUp => self.quantize = (self.quantize * 2).min(MAX_QUANTIZE),
+                PatternEvent::QuantizeDown => self.quantize = (self.quantize / 2).max(1),
+                PatternEvent::NextMidiPort => self.connect_midi(),
+                PatternEvent::FocusOrder => {
+                    self.anchor = None;
+                    self.order_focused = true;
+                }
+                PatternEvent::Order(event) => self.order_event(event),
+                PatternEvent::ToggleMute | PatternEvent::ToggleSolo | PatternEvent::ToggleCollapse => self.toggle_channel(event),
+                PatternEvent::CollapseUnused => self.collapse_unused(),
+                PatternEvent::Instruments => self.open_instruments(),
+                PatternEvent::ToggleLoop => {
+                    self.preview.loop_pattern = !self.preview.loop_pattern;
+                    self.status = if self.preview.loop_pattern { "Looping pattern" } else { "Playing song" }.to_string();
                 }
-                PatternEvent::SmallDecrement => todo!(),
             }
         }
+
+        if self.prompt.is_none() && self.picker.is_none() && !self.order_focused {
+            self.update_entry(&events);
+        }
+    }
+
+    fn help(&self) -> Vec<Help> {
+        if let Some(fx) = &self.fx {
+            return vec![fx.help()];
+        }
+        if let Some(editor) = &self.instruments {
+            return vec![editor.help()];
+        }
+        if self.order_focused {
+            return vec![self.order.help()];
+        }
+        vec![self.keys.help()
+            .fixed("zsxdc.. q2w3e..", "notes, in a note column")
+            .fixed("0-9 a-f", "volume, in a volume column")]
+    }
+
+    fn captures_text(&self) -> bool {
+        self.picker.is_some() || self.prompt.is_some() || self.fx.as_ref().is_some_and(FxEditor::is_typing)
     }
 
     fn render(&mut self, frame: &mut ratatui::Frame, area: Rect) {
         let table_width = self.lanes.iter().map(|l| l.width).sum();
+        let [area, status_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ]).areas(area);
+
         let lower_layouts = Layout::default().constraints([
+            Constraint::Length(order_list::WIDTH),
             Constraint::Fill(1),
             // TODO: use widths and sum them from
             Constraint::Length(table_width),
@@ -392,23 +1253,68 @@ impl Component for PatternEditor {
         ]).direction(Direction::Horizontal).split(area);
 
         let widths = self.widths();
+        let hints = if self.order_focused {
+            self.order.keys.hints(&["back", "play", "insert", "duplicate", "clone", "rows_up", "rows_down", "loop_start", "loop_end"])
+        } else {
+            self.keys.hints(&["play_pause", "stop", "loop", "octave_up", "octave_down", "increment", "edit_commands", "record", "order_list", "save"])
+        };
+        let transport = match (self.preview.is_playing(), self.preview.loop_pattern) {
+            (true, true) => " ▶ loop ",
+            (true, false) => " ▶ ",
+            (false, _) => " ■ ",
+        };
+        let status = match &self.prompt {
+            Some(prompt) => Line::from(vec![
+                Span::from(if matches!(prompt.kind, LaneKind::Seq) { " seq> " } else { " fx> " }).fg(SCHEME.orange[1]),
+                Span::from(format!("{}_", prompt.text)),
+                Span::from(format!("  {}", self.status)).fg(SCHEME.gray[2]),
+            ]),
+            None => Line::from(vec![
+                Span::from(transport).fg(SCHEME.orange[1]),
+                Span::from(if self.record { "● rec " } else { "" }).fg(SCHEME.red[1]),
+                Span::from(format!("pat {:02X} oct {} q {} ", self.tracker_data.pattern, self.octave, self.quantize)).fg(SCHEME.orange[1]),
+                Span::from(self.status.clone()).fg(SCHEME.gray[2]),
+                Span::from(format!("  {} ?:keys", hints)).fg(SCHEME.gray[0]),
+            ]),
+        };
 
         let table = Table::default()
-            .data(self)
+            .data(&mut *self)
             .style(SCHEME.true_dark_black(0).fg(SCHEME.white[0]))
             .widths(widths);
 
         let mut ts = TableState::<RowSelection>::default();        
-        frame.render_stateful_widget(table, lower_layouts[1], &mut ts);
+        frame.render_stateful_widget(table, lower_layouts[2], &mut ts);
+
+        let playing = self.preview.order_position();
+        self.order.render(frame, lower_layouts[0], &self.tracker_data, playing, self.order_focused);
+
+        frame.render_widget(status, status_area);
+
+        if let Some(fx) = &self.fx {
+            fx.render(frame, lower_layouts[2]);
+        }
+
+        if let Some(editor) = &self.instruments {
+            editor.render(frame, area);
+        }
+
+        if let Some(picker) = &mut self.picker {
+            picker.render(frame, area);
+        }
     }
 }
 
 impl TSub for PatternEditor {
-    fn active_handlers(&self) -> &Vec<Handler> {
-        &self.active_handlers
-    }
-    
-    fn global_handlers(&self) -> &Vec<Handler> {
-        &self.global_handlers
+    fn handle_keys(&mut self, events: &[Event]) {
+        if self.order_focused {
+            for event in self.order.keys.actions(events) {
+                let _ = self.cx_tx.send(PatternEvent::Order(event));
+            }
+        } else {
+            for event in self.keys.actions(events) {
+                let _ = self.cx_tx.send(event);
+            }
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,551 @@
+//! Live preview
+//!
+//! Plays patterns through the host's speakers while editing. The voices are
+//! a port of the wavetable-8ch firmware's mixer, and rows and effects follow
+//! the same rules as `gametank::audio::sequencer`, so what you hear is what
+//! the console plays.
+
+use std::time::Instant;
+
+use dasp_graph::Buffer;
+use klingt::CpalDevice;
+
+use crate::{audio::GameTankAudio, tracker::{ChannelCmd, SequencerCmd, TrackerData, CHANNELS}};
+
+/// Firmware sample rate, as used by the SDK's pitch table
+const FS: u32 = 13_983;
+const FRAME_RATE: u32 = 60;
+const ROWS: u8 = 64;
+pub const MAX_VOLUME: u8 = 63;
+
+/// Output blocks to stay ahead of the audio device
+const LEAD_BLOCKS: u64 = 4;
+/// Past this many missed blocks, skip ahead instead of catching up
+const MAX_BEHIND: u64 = 16;
+
+/// How long an auditioned row rings, in frames
+const AUDITION_FRAMES: u8 = 15;
+/// How long a wavetable being tried out rings, in frames
+const WAVETABLE_AUDITION_FRAMES: u8 = 45;
+
+/// ACP address of wavetable slot 0; slot n is n pages on, as in the SDK's
+/// `audio::WAVETABLE`
+const WAVETABLE_BASE: u16 = 0x0300;
+
+/// `center + amplitude * sin`, the shape of both firmware tables
+fn sine_table(center: f64, amplitude: f64) -> [u8; 256] {
+    std::array::from_fn(|i| (center + amplitude * (i as f64 * std::f64::consts::TAU / 256.0).sin()).round() as u8)
+}
+
+/// Phase increments per MIDI note, computed exactly like the SDK's pitch table
+fn midi_increments() -> [u16; 128] {
+    let mut freq_q16: u32 = 535_400; // MIDI 0 in 16.16
+    std::array::from_fn(|_| {
+        let inc = ((freq_q16 as u64 + FS as u64 / 2) / FS as u64) as u16;
+        freq_q16 = ((freq_q16 as u64 * 69_433) >> 16) as u32; // 2^(1/12)
+        inc
+    })
+}
+
+/// The table a voice plays
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Wave {
+    /// The firmware's built-in sine
+    #[default]
+    Sine,
+    /// One of the song's wavetables
+    Slot(usize),
+    /// A table being tried out in the instrument editor
+    Audition,
+}
+
+impl Wave {
+    /// What a voice pointed at ACP address `addr` plays
+    fn at(addr: u16) -> Self {
+        match addr.checked_sub(WAVETABLE_BASE) {
+            Some(offset) if offset & 0xFF == 0 => Wave::Slot((offset >> 8) as usize),
+            _ => Wave::Sine,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Voice {
+    phase: u16,
+    frequency: u16,
+    volume: u8,
+    wave: Wave,
+}
+
+/// The firmware's mixer. Voices play the built-in sine table until a
+/// wavetable command points them at one of the song's slots.
+struct Synth {
+    voices: [Voice; CHANNELS],
+    sine: [u8; 256],
+    /// The song's wavetables, by slot, as the sequencer loads them
+    tables: Vec<[u8; 256]>,
+    audition: [u8; 256],
+    vol: [u8; 256],
+}
+
+impl Synth {
+    fn new() -> Self {
+        Self {
+            voices: [Voice::default(); CHANNELS],
+            sine: sine_table(128.0, 64.0),
+            tables: Vec::new(),
+            audition: [0x80; 256],
+            vol: sine_table(64.0, 16.0),
+        }
+    }
+
+    /// One DAC sample
+    fn sample(&mut self) -> u8 {
+        let mut out: u8 = 0x80;
+        for v in &mut self.voices {
+            v.phase = v.phase.wrapping_add(v.frequency);
+            let wave = match v.wave {
+                Wave::Sine => &self.sine,
+                // slots past the song's hold whatever's in ACP RAM; the sine will do
+                Wave::Slot(slot) => self.tables.get(slot).unwrap_or(&self.sine),
+                Wave::Audition => &self.audition,
+            };
+            let s = wave[(v.phase >> 8) as usize] >> 1;
+            let low = self.vol[s.wrapping_sub(v.volume) as usize];
+            let high = self.vol[s.wrapping_add(v.volume) as usize];
+            out = out.wrapping_add(low.wrapping_sub(high));
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Channel {
+    frequency: u16,
+    volume: u8,
+    vol_slide: i16,
+    vol_slide_rows: u8,
+    pitch_slide: i16,
+    pitch_slide_rows: u8,
+    tremolo_depth: u8,
+    tremolo_speed: u8,
+    tremolo_phase: u8,
+    vibrato_depth: u8,
+    vibrato_speed: u8,
+    vibrato_phase: u8,
+}
+
+impl Channel {
+    /// Triangle LFO in -64..=63, scaled by depth / 64
+    fn lfo(phase: u8, depth: u8) -> i16 {
+        let tri = if phase < 128 { phase } else { 255 - phase } as i16;
+        ((tri - 64) * depth as i16) >> 6
+    }
+}
+
+pub struct Preview {
+    audio: Option<GameTankAudio>,
+    device_rate: f64,
+    started: Instant,
+    blocks_sent: u64,
+
+    synth: Synth,
+    increments: [u16; 128],
+    /// Synth samples between the two being interpolated
+    synth_pos: f64,
+    prev_sample: u8,
+    next_sample: u8,
+    /// Synth samples until the next 60Hz frame
+    frame_countdown: u32,
+
+    channels: [Channel; CHANNELS],
+    playing: bool,
+    pub loop_pattern: bool,
+    pattern: usize,
+    order_pos: usize,
+    /// Rows the current order entry plays for
+    order_rows: u8,
+    next_row: u8,
+    current_row: u8,
+    frames_per_row: u8,
+    frame: u8,
+    audition_frames: u8,
+}
+
+impl Preview {
+    pub fn new() -> Self {
+        Self {
+            audio: None,
+            device_rate: 48_000.0,
+            started: Instant::now(),
+            blocks_sent: 0,
+            synth: Synth::new(),
+            increments: midi_increments(),
+            synth_pos: 0.0,
+            prev_sample: 0x80,
+            next_sample: 0x80,
+            frame_countdown: FS / FRAME_RATE,
+            channels: [Channel::default(); CHANNELS],
+            playing: false,
+            loop_pattern: false,
+            pattern: 0,
+            order_pos: 0,
+            order_rows: ROWS,
+            next_row: 0,
+            current_row: 0,
+            frames_per_row: 1,
+            frame: 0,
+            audition_frames: 0,
+        }
+    }
+
+    /// Open the output device if it isn't already. Returns whether there is one.
+    fn ensure_audio(&mut self) -> bool {
+        if self.audio.is_none() {
+            let Ok(device) = CpalDevice::default_output() else { return false };
+            self.device_rate = device.sample_rate() as f64;
+            self.audio = Some(GameTankAudio::new());
+            self.started = Instant::now();
+            self.blocks_sent = 0;
+        }
+        true
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// The pattern and row being played
+    pub fn playhead(&self) -> Option<(usize, u8)> {
+        self.playing.then_some((self.pattern, self.current_row))
+    }
+
+    /// The order entry being played
+    pub fn order_position(&self) -> Option<usize> {
+        (self.playing && !self.loop_pattern).then_some(self.order_pos)
+    }
+
+    /// Start playing `pattern` from `row`, carrying on from its first place
+    /// in the order list. Returns false without an audio device.
+    pub fn play(&mut self, data: &TrackerData, pattern: usize, row: u8) -> bool {
+        let pos = data.song().iter().position(|e| e.pattern == pattern);
+        self.start(data, pos.unwrap_or(0), pattern, row)
+    }
+
+    /// Start playing the song from order entry `pos`. Returns false without
+    /// an audio device.
+    pub fn play_order(&mut self, data: &TrackerData, pos: usize) -> bool {
+        let Some(entry) = data.order.get(pos) else { return false };
+        self.start(data, pos, entry.pattern, 0)
+    }
+
+    fn start(&mut self, data: &TrackerData, pos: usize, pattern: usize, row: u8) -> bool {
+        if !self.ensure_audio() {
+            return false;
+        }
+
+        self.channels = [Channel::default(); CHANNELS];
+        for voice in &mut self.synth.voices {
+            voice.wave = Wave::Sine;
+        }
+        self.pattern = pattern;
+        self.order_pos = pos;
+        self.order_rows = data.order.get(pos).map_or(ROWS, |e| e.rows);
+        self.next_row = row % ROWS;
+        self.current_row = self.next_row;
+        self.frame = 0;
+        self.audition_frames = 0;
+        self.set_tempo(data.tempo);
+        self.playing = true;
+        true
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+        self.silence();
+    }
+
+    /// Play a single row's notes, for hearing edits as they're made
+    pub fn audition(&mut self, data: &TrackerData, pattern: usize, row: u8) {
+        if self.playing || !self.ensure_audio() {
+            return;
+        }
+
+        let Some(pattern) = data.patterns.get(pattern) else { return };
+        for (ch, lane) in pattern.iter().skip(1).enumerate().take(CHANNELS) {
+            let beat = &lane[row as usize % ROWS as usize];
+            if beat.cmd_list.is_empty() {
+                continue;
+            }
+            for cmd in &beat.cmd_list {
+                self.channel_cmd(ch, cmd);
+            }
+            // a note with no volume set yet would be silent
+            if self.channels[ch].volume == 0 {
+                self.channels[ch].volume = MAX_VOLUME;
+            }
+        }
+        self.audition_frames = AUDITION_FRAMES;
+    }
+
+    /// Start a note on `ch` from a live keyboard. It holds until
+    /// [`release`](Self::release), or while playing, until the pattern's next
+    /// note on that channel. Returns false without an audio device.
+    pub fn note_on(&mut self, ch: usize, note: u8, volume: u8) -> bool {
+        if !self.ensure_audio() {
+            return false;
+        }
+
+        self.channel_cmd(ch, &ChannelCmd::Note(note));
+        self.channels[ch].volume = volume.min(MAX_VOLUME);
+        // held, rather than timed like an audition
+        self.audition_frames = 0;
+        true
+    }
+
+    /// The song's wavetables, for the slots wavetable commands point at
+    pub fn set_wavetables(&mut self, tables: Vec<[u8; 256]>) {
+        self.synth.tables = tables;
+    }
+
+    /// Play `note` with `table` on the first channel for a moment, stopping
+    /// the song if it's playing. Returns false without an audio device.
+    pub fn audition_wavetable(&mut self, table: &[u8; 256], note: u8) -> bool {
+        if !self.ensure_audio() {
+            return false;
+        }
+
+        self.pause();
+        self.synth.audition = *table;
+        self.synth.voices[0].wave = Wave::Audition;
+        self.channel_cmd(0, &ChannelCmd::Note(note));
+        self.channels[0].volume = MAX_VOLUME;
+        self.audition_frames = WAVETABLE_AUDITION_FRAMES;
+        true
+    }
+
+    /// End a note from [`note_on`](Self::note_on). While playing, the channel
+    /// is left to the pattern.
+    pub fn release(&mut self, ch: usize) {
+        if !self.playing {
+            self.channels[ch].volume = 0;
+        }
+    }
+
+    fn silence(&mut self) {
+        for ch in &mut self.channels {
+            ch.volume = 0;
+            ch.vol_slide_rows = 0;
+            ch.tremolo_depth = 0;
+        }
+        for voice in &mut self.synth.voices {
+            if voice.wave == Wave::Audition {
+                voice.wave = Wave::Sine;
+            }
+        }
+    }
+
+    fn set_tempo(&mut self, bpm: u8) {
+        // one row per beat, 60 frames per second
+        self.frames_per_row = (3600 / bpm.max(15) as u16) as u8;
+    }
+
+    fn jump_to_order(&mut self, data: &TrackerData, pos: usize) {
+        let entry = data.order[pos];
+        self.order_pos = pos;
+        self.order_rows = entry.rows;
+        self.jump_to_pattern(data, entry.pattern);
+    }
+
+    fn jump_to_pattern(&mut self, data: &TrackerData, pattern: usize) {
+        self.pattern = if pattern < data.patterns.len() { pattern } else { 0 };
+        self.next_row = 0;
+    }
+
+    /// The end of an order entry, or an `Advance`
+    fn advance(&mut self, data: &TrackerData) {
+        if self.loop_pattern {
+            self.next_row = 0;
+        } else {
+            self.jump_to_order(data, data.next_order(self.order_pos));
+        }
+    }
+
+    fn channel_cmd(&mut self, ch: usize, cmd: &ChannelCmd) {
+        let state = &mut self.channels[ch];
+        match *cmd {
+            ChannelCmd::Note(n) => {
+                state.frequency = self.increments[(n & 0x7F) as usize];
+                state.pitch_slide_rows = 0;
+            }
+            ChannelCmd::Volume(v) => state.volume = v.min(MAX_VOLUME),
+            ChannelCmd::Wavetable(addr) => self.synth.voices[ch].wave = Wave::at(addr),
+            ChannelCmd::Phase(phase) => self.synth.voices[ch].phase = phase,
+            ChannelCmd::Tremolo(depth, speed) => {
+                state.tremolo_depth = depth;
+                state.tremolo_speed = speed;
+            }
+            ChannelCmd::Vibrato(depth, speed) => {
+                state.vibrato_depth = depth;
+                state.vibrato_speed = speed;
+            }
+            ChannelCmd::SlideVol(rows, delta) => {
+                state.vol_slide_rows = rows;
+                state.vol_slide = delta;
+            }
+            ChannelCmd::StopVSlide => state.vol_slide_rows = 0,
+            ChannelCmd::SlidePitch(rows, delta) => {
+                state.pitch_slide_rows = rows;
+                state.pitch_slide = delta;
+            }
+            ChannelCmd::StopPSlide => state.pitch_slide_rows = 0,
+        }
+    }
+
+    fn step_row(&mut self, data: &TrackerData) {
+        for ch in self.channels.iter_mut() {
+            if ch.vol_slide_rows > 0 {
+                ch.vol_slide_rows -= 1;
+                let vol = ch.volume as i16 + ch.vol_slide;
+                ch.volume = vol.clamp(0, MAX_VOLUME as i16) as u8;
+            }
+            if ch.pitch_slide_rows > 0 {
+                ch.pitch_slide_rows -= 1;
+                ch.frequency = ch.frequency.wrapping_add_signed(ch.pitch_slide);
+            }
+        }
+
+        // a short order entry cuts its pattern off, but a looped pattern
+        // plays in full
+        let rows = if self.loop_pattern { ROWS } else { self.order_rows };
+        if self.next_row >= rows {
+            self.advance(data);
+        }
+
+        let Some(pattern) = data.patterns.get(self.pattern) else {
+            self.pause();
+            return;
+        };
+
+        let row = self.next_row;
+        self.current_row = row;
+        self.next_row = row + 1;
+
+        // channel lanes first, like the exporter
+        for (ch, lane) in pattern.iter().skip(1).enumerate().take(CHANNELS) {
+            for cmd in &lane[row as usize].cmd_list {
+                self.channel_cmd(ch, cmd);
+            }
+        }
+
+        for cmd in &pattern[0][row as usize].sqc_list {
+            match *cmd {
+                SequencerCmd::Tempo(bpm) => self.set_tempo(bpm),
+                SequencerCmd::Load(..) => {}
+                SequencerCmd::Pattern(p) if !self.loop_pattern => self.jump_to_pattern(data, p as usize),
+                SequencerCmd::Pattern(_) => self.next_row = 0,
+                SequencerCmd::Beat(b) => self.next_row = b % ROWS,
+                SequencerCmd::Advance => self.advance(data),
+                SequencerCmd::Stop => {
+                    self.pause();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Advance by one 60Hz frame
+    fn tick(&mut self, data: &TrackerData) {
+        if self.playing {
+            if self.frame == 0 {
+                self.step_row(data);
+            }
+            self.frame += 1;
+            if self.frame >= self.frames_per_row {
+                self.frame = 0;
+            }
+        } else if self.audition_frames > 0 {
+            self.audition_frames -= 1;
+            if self.audition_frames == 0 {
+                self.silence();
+            }
+        }
+
+        for (i, (voice, ch)) in self.synth.voices.iter_mut().zip(self.channels.iter_mut()).enumerate() {
+            let mut frequency = ch.frequency;
+            let mut volume = ch.volume;
+
+            if ch.vibrato_depth > 0 {
+                ch.vibrato_phase = ch.vibrato_phase.wrapping_add(ch.vibrato_speed);
+                frequency = frequency.wrapping_add_signed(Channel::lfo(ch.vibrato_phase, ch.vibrato_depth));
+            }
+
+            if ch.tremolo_depth > 0 {
+                ch.tremolo_phase = ch.tremolo_phase.wrapping_add(ch.tremolo_speed);
+                let vol = volume as i16 + Channel::lfo(ch.tremolo_phase, ch.tremolo_depth);
+                volume = vol.clamp(0, MAX_VOLUME as i16) as u8;
+            }
+
+            voice.frequency = frequency;
+            // muted channels keep running, so unmuting picks up mid-note
+            voice.volume = if data.audible(i) { volume } else { 0 };
+        }
+    }
+
+    /// Next synth sample, ticking the sequencer at 60Hz
+    fn next_synth_sample(&mut self, data: &TrackerData) -> u8 {
+        self.frame_countdown -= 1;
+        if self.frame_countdown == 0 {
+            self.frame_countdown = FS / FRAME_RATE;
+            self.tick(data);
+        }
+        self.synth.sample()
+    }
+
+    /// One block at the device rate, linearly interpolated like the emulator
+    fn render_block(&mut self, data: &TrackerData) -> Buffer {
+        let step = FS as f64 / self.device_rate;
+        let mut buf = Buffer::SILENT;
+
+        for out in buf.iter_mut() {
+            self.synth_pos += step;
+            while self.synth_pos >= 1.0 {
+                self.synth_pos -= 1.0;
+                self.prev_sample = self.next_sample;
+                self.next_sample = self.next_synth_sample(data);
+            }
+
+            let a = self.prev_sample as f64;
+            let b = self.next_sample as f64;
+            let s = a + (b - a) * self.synth_pos;
+            *out = (s / 255.0 * 2.0 - 1.0) as f32;
+        }
+
+        buf
+    }
+
+    /// Keep the audio device fed. Call every frame.
+    pub fn update(&mut self, data: &TrackerData) {
+        if self.audio.is_none() {
+            return;
+        }
+
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let target = (elapsed * self.device_rate / 64.0) as u64 + LEAD_BLOCKS;
+        if target > self.blocks_sent + MAX_BEHIND {
+            self.blocks_sent = target - LEAD_BLOCKS;
+        }
+
+        while self.blocks_sent < target {
+            let buf = self.render_block(data);
+            if let Some(audio) = &mut self.audio {
+                audio.push_buffer(buf);
+            }
+            self.blocks_sent += 1;
+        }
+
+        if let Some(audio) = &mut self.audio {
+            audio.process_audio();
+        }
+    }
+}
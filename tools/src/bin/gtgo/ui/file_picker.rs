@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+
+use ratatui::{crossterm::event::{Event, KeyCode, KeyEvent}, layout::{Constraint, Layout, Rect}, style::{Color, Modifier, Stylize}, symbols::border, text::Line, widgets::{Block, BorderType, Clear, List, ListState, Padding}, Frame};
+
+use crate::{helpers::{centered_rect, SCHEME}, Component};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PickerMode {
+    Open,
+    Save,
+}
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+}
+
+/// Modal directory browser. Lists subdirectories and files with the given
+/// extension; in save mode a file name can also be typed in.
+pub struct FilePicker {
+    mode: PickerMode,
+    extension: &'static str,
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    selection: usize,
+    filename: String,
+    picked: Option<PathBuf>,
+    is_active: bool,
+}
+
+impl FilePicker {
+    pub fn init(mode: PickerMode, extension: &'static str, dir: &Path) -> Self {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+
+        let mut picker = Self {
+            mode,
+            extension,
+            dir,
+            entries: vec![],
+            selection: 0,
+            filename: String::new(),
+            picked: None,
+            is_active: true,
+        };
+        picker.refresh();
+        picker
+    }
+
+    pub fn mode(&self) -> PickerMode {
+        self.mode
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.is_active
+    }
+
+    /// The chosen path, once the picker has closed. `None` if cancelled.
+    pub fn take_picked(&mut self) -> Option<PathBuf> {
+        self.picked.take()
+    }
+
+    fn refresh(&mut self) {
+        let mut dirs = vec![];
+        let mut files = vec![];
+
+        if let Ok(read_dir) = std::fs::read_dir(&self.dir) {
+            for entry in read_dir.flatten() {
+                let path = entry.path();
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.starts_with('.') {
+                    continue;
+                }
+
+                if path.is_dir() {
+                    dirs.push(Entry { name, is_dir: true });
+                } else if path.extension().is_some_and(|e| e == self.extension) {
+                    files.push(Entry { name, is_dir: false });
+                }
+            }
+        }
+
+        dirs.sort_by(|a, b| a.name.cmp(&b.name));
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.entries = vec![];
+        if self.dir.parent().is_some() {
+            self.entries.push(Entry { name: "..".to_string(), is_dir: true });
+        }
+        self.entries.extend(dirs);
+        self.entries.extend(files);
+        self.selection = 0;
+    }
+
+    fn enter_dir(&mut self, name: &str) {
+        if name == ".." {
+            if let Some(parent) = self.dir.parent() {
+                self.dir = parent.to_path_buf();
+            }
+        } else {
+            self.dir.push(name);
+        }
+        self.refresh();
+    }
+
+    fn finish(&mut self, path: Option<PathBuf>) {
+        self.picked = path;
+        self.is_active = false;
+    }
+
+    fn confirm(&mut self) {
+        // a typed name wins over the highlighted entry
+        if self.mode == PickerMode::Save && !self.filename.is_empty() {
+            let mut path = self.dir.join(&self.filename);
+            if path.extension().is_none_or(|e| e != self.extension) {
+                path.set_extension(self.extension);
+            }
+            self.finish(Some(path));
+            return;
+        }
+
+        let Some(entry) = self.entries.get(self.selection) else { return };
+        if entry.is_dir {
+            let name = entry.name.clone();
+            self.enter_dir(&name);
+        } else {
+            let path = self.dir.join(&entry.name);
+            self.finish(Some(path));
+        }
+    }
+
+    fn move_sel(&mut self, dir: i32) {
+        let len = self.entries.len() as i32;
+        if len > 0 {
+            self.selection = (self.selection as i32 + dir).rem_euclid(len) as usize;
+        }
+    }
+}
+
+impl Component for FilePicker {
+    fn update(&mut self, events: Vec<Event>) {
+        for e in events {
+            let Event::Key(KeyEvent { code, .. }) = e else { continue };
+            match code {
+                KeyCode::Esc => self.finish(None),
+                KeyCode::Up => self.move_sel(-1),
+                KeyCode::Down => self.move_sel(1),
+                KeyCode::Enter => self.confirm(),
+                KeyCode::Backspace if self.mode == PickerMode::Save && !self.filename.is_empty() => {
+                    self.filename.pop();
+                }
+                KeyCode::Backspace => self.enter_dir(".."),
+                KeyCode::Char(c) if self.mode == PickerMode::Save => self.filename.push(c),
+                _ => {}
+            }
+
+            if !self.is_active {
+                break;
+            }
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let title = match self.mode {
+            PickerMode::Open => " Open ",
+            PickerMode::Save => " Save As ",
+        };
+
+        let area = centered_rect(60, 60, frame.area());
+        let block = Block::bordered()
+            .title(title)
+            .title_bottom(Line::from(self.dir.display().to_string()).fg(SCHEME.gray[2]))
+            .title_style(style.bold().not_italic().fg(SCHEME.orange[1]))
+            .style(style.fg(SCHEME.orange[1]))
+            .padding(Padding::new(1, 1, 1, 1))
+            .border_set(border::ROUNDED)
+            .border_type(BorderType::Thick);
+
+        let inner = block.inner(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+
+        let [list_area, name_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(if self.mode == PickerMode::Save { 1 } else { 0 }),
+        ]).areas(inner);
+
+        let items: Vec<Line> = self.entries.iter().map(|e| {
+            if e.is_dir {
+                Line::from(format!("{}/", e.name)).fg(SCHEME.blue[1])
+            } else {
+                Line::from(e.name.clone())
+            }
+        }).collect();
+
+        let list = List::new(items)
+            .highlight_symbol("» ")
+            .highlight_style(style.add_modifier(Modifier::BOLD))
+            .style(style.not_bold());
+
+        let mut state = ListState::default().with_selected(Some(self.selection));
+        frame.render_stateful_widget(list, list_area, &mut state);
+
+        if self.mode == PickerMode::Save {
+            let name = Line::from(format!("Name: {}_", self.filename)).fg(SCHEME.white[0]);
+            frame.render_widget(name, name_area);
+        }
+    }
+}
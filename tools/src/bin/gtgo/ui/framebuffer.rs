@@ -0,0 +1,105 @@
+use gte_core::color_map::{ColorMap, COLOR_MAP};
+use ratatui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+
+const SIZE: u16 = 128;
+const PAGE_SIZE: u16 = 256;
+
+/// Color of the outline drawn by [`FramebufferView::highlight`]
+const HIGHLIGHT: Color = Color::Rgb(255, 0, 255);
+
+/// Draws a GameTank framebuffer (or a 256x256 sprite RAM page) with
+/// half-block characters, two pixels per cell. Shrinks by whole steps until
+/// it fits the area.
+pub struct FramebufferView<'a> {
+    pixels: &'a [u8],
+    size: u16,
+    colors: &'a ColorMap,
+    /// Outlined rectangle: x, y, width, height in pixels
+    highlight: Option<(u16, u16, u16, u16)>,
+}
+
+impl<'a> FramebufferView<'a> {
+    pub fn new(pixels: &'a [u8; 128 * 128]) -> Self {
+        Self { pixels, size: SIZE, colors: &COLOR_MAP, highlight: None }
+    }
+
+    /// A sprite RAM page, row by row
+    pub fn sprite_page(pixels: &'a [u8; 256 * 256]) -> Self {
+        Self { pixels, size: PAGE_SIZE, colors: &COLOR_MAP, highlight: None }
+    }
+
+    /// Draw with `colors` rather than the measured palette
+    pub fn palette(mut self, colors: &'a ColorMap) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Outline a rectangle, clipped to the image
+    pub fn highlight(mut self, x: u8, y: u8, width: u8, height: u8) -> Self {
+        if width > 0 && height > 0 {
+            self.highlight = Some((x as u16, y as u16, width as u16, height as u16));
+        }
+        self
+    }
+
+    /// Pixels per cell column at the largest scale that fits `size` pixels in `area`
+    fn step(size: u16, area: Rect) -> u16 {
+        let fit_w = size.div_ceil(area.width.max(1));
+        let fit_h = size.div_ceil(area.height.max(1) * 2);
+        fit_w.max(fit_h).max(1)
+    }
+
+    fn cells(size: u16, area: Rect) -> (u16, u16) {
+        let step = Self::step(size, area);
+        (size.div_ceil(step), size.div_ceil(step * 2))
+    }
+
+    /// Cells needed to show the whole screen in `area`
+    pub fn size_in(area: Rect) -> (u16, u16) {
+        Self::cells(SIZE, area)
+    }
+
+    /// Cells needed to show a whole sprite page in `area`
+    pub fn page_size_in(area: Rect) -> (u16, u16) {
+        Self::cells(PAGE_SIZE, area)
+    }
+
+    /// Whether the `step` by `step` block at (x, y) touches the outline
+    fn on_outline(&self, x: u16, y: u16, step: u16) -> bool {
+        let Some((hx, hy, w, h)) = self.highlight else { return false };
+        let (right, bottom) = ((hx + w - 1).min(self.size - 1), (hy + h - 1).min(self.size - 1));
+        let covers = |start: u16, edge: u16| (start..start + step).contains(&edge);
+
+        let inside = x + step > hx && x <= right && y + step > hy && y <= bottom;
+        inside && (covers(x, hx) || covers(x, right) || covers(y, hy) || covers(y, bottom))
+    }
+
+    fn color(&self, x: u16, y: u16, step: u16) -> Color {
+        if self.on_outline(x, y, step) {
+            return HIGHLIGHT;
+        }
+        let (r, g, b, _) = self.colors[self.pixels[y as usize * self.size as usize + x as usize] as usize];
+        Color::Rgb(r, g, b)
+    }
+}
+
+impl Widget for FramebufferView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let step = Self::step(self.size, area);
+        let (width, height) = Self::cells(self.size, area);
+
+        for cy in 0..height.min(area.height) {
+            for cx in 0..width.min(area.width) {
+                let x = cx * step;
+                let top = cy * step * 2;
+                let bottom = (top + step).min(self.size - 1);
+
+                if let Some(cell) = buf.cell_mut((area.x + cx, area.y + cy)) {
+                    cell.set_char('▀')
+                        .set_fg(self.color(x, top, step))
+                        .set_bg(self.color(x, bottom, step));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use ratatui::{crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind}, layout::Rect, style::{Color, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, BorderType, Clear, Padding, Paragraph}, Frame};
+
+use crate::{helpers::{centered_rect, SCHEME}, keymap::{keys_file_status, Help}, Component};
+
+/// The `?` popup listing the current screen's key bindings
+pub struct HelpOverlay {
+    sections: Vec<Help>,
+    scroll: u16,
+    is_active: bool,
+}
+
+impl HelpOverlay {
+    pub fn init(sections: Vec<Help>) -> Self {
+        Self { sections, scroll: 0, is_active: true }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.is_active
+    }
+
+    fn lines(&self) -> Vec<Line<'static>> {
+        let mut lines = vec![];
+        let (path, file_error) = keys_file_status();
+
+        if let Some(e) = file_error {
+            lines.push(Line::from(e.to_string()).fg(SCHEME.red[1]));
+            lines.push(Line::default());
+        }
+
+        if self.sections.is_empty() {
+            lines.push(Line::from("Nothing to bind here").fg(SCHEME.gray[2]));
+        }
+
+        let width = self.sections.iter().flat_map(|s| &s.rows).map(|r| r.keys.len()).max().unwrap_or(0);
+        for section in &self.sections {
+            lines.push(Line::from(format!("[{}]", section.section)).bold().fg(SCHEME.orange[1]));
+            for row in &section.rows {
+                lines.push(Line::from(vec![
+                    Span::from(format!("  {:width$}  ", row.keys, width = width)).fg(SCHEME.white[0]),
+                    Span::from(row.help.clone()),
+                    Span::from(if row.name.is_empty() { String::new() } else { format!("  {}", row.name) }).fg(SCHEME.gray[2]),
+                ]));
+            }
+            for e in &section.errors {
+                lines.push(Line::from(format!("  {}", e)).fg(SCHEME.red[1]));
+            }
+            lines.push(Line::default());
+        }
+
+        if let Some(path) = path {
+            lines.push(Line::from(format!("Rebind by action name in {}", path.display())).fg(SCHEME.gray[2]));
+        }
+        lines
+    }
+}
+
+impl Component for HelpOverlay {
+    fn update(&mut self, events: Vec<Event>) {
+        for e in events {
+            let Event::Key(KeyEvent { code, kind, .. }) = e else { continue };
+            if kind == KeyEventKind::Release {
+                continue;
+            }
+            match code {
+                KeyCode::Esc | KeyCode::Char('q') => self.is_active = false,
+                KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+                KeyCode::Down => self.scroll = self.scroll.saturating_add(1),
+                KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+                KeyCode::PageDown => self.scroll = self.scroll.saturating_add(10),
+                _ => {}
+            }
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let area = centered_rect(70, 80, frame.area());
+        let block = Block::bordered()
+            .title(" Keys ")
+            .title_bottom(Line::from(" up/down:scroll esc:close ").fg(SCHEME.gray[2]))
+            .title_style(style.bold().not_italic().fg(SCHEME.orange[1]))
+            .style(style.fg(SCHEME.orange[1]))
+            .padding(Padding::new(1, 1, 1, 1))
+            .border_set(border::ROUNDED)
+            .border_type(BorderType::Thick);
+
+        let lines = self.lines();
+        let inner = block.inner(area);
+        let max_scroll = (lines.len() as u16).saturating_sub(inner.height);
+        self.scroll = self.scroll.min(max_scroll);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).style(style.fg(SCHEME.white[0])).scroll((self.scroll, 0)).block(block), area);
+    }
+}
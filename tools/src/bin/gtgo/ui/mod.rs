@@ -1 +1,4 @@
 pub mod quickmenu;
+pub mod file_picker;
+pub mod framebuffer;
+pub mod help;
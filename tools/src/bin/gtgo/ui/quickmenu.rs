@@ -1,8 +1,8 @@
 use std::{collections::HashMap, rc::Rc};
 
-use ratatui::{crossterm::event::{Event, KeyCode, KeyEvent}, layout::Rect, style::{Color, Modifier, Style, Stylize}, symbols::border, text::{Line, Span, Text}, widgets::{Block, BorderType, List, ListState, Padding}, Frame};
+use ratatui::{crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind}, layout::Rect, style::{Color, Modifier, Style, Stylize}, symbols::border, text::{Line, Span, Text}, widgets::{Block, BorderType, List, ListState, Padding}, Frame};
 
-use crate::{helpers::SCHEME, Component};
+use crate::{helpers::SCHEME, keymap::{Help, Keymap}, Component};
 
 pub struct QmItem {
     label: &'static str,
@@ -58,7 +58,9 @@ pub struct QuickMenu {
     title: String,
     selection: usize,
     list_items: Vec<QuickMenuItem>,
-    bound_keys: HashMap<KeyCode, Input>,
+    keys: Keymap<Input>,
+    /// Each item's underlined letter
+    hotkeys: HashMap<KeyCode, usize>,
     is_active: bool,
     width: u16,
     height: u16,
@@ -66,12 +68,13 @@ pub struct QuickMenu {
 
 impl QuickMenu {
     pub fn init(title: String, items: Vec<QmItem>) -> Self {
-        let mut bound_keys = HashMap::new();
-        bound_keys.insert(KeyCode::Esc, Input::Quit);
-        bound_keys.insert(KeyCode::Char('q'), Input::Quit);
-        bound_keys.insert(KeyCode::Up, Input::Up);
-        bound_keys.insert(KeyCode::Down, Input::Down);
-        bound_keys.insert(KeyCode::Enter, Input::Enter);
+        let keys = Keymap::new("menu", &[
+            ("quit", Input::Quit, &["esc", "q"], "quit"),
+            ("up", Input::Up, &["up"], "up"),
+            ("down", Input::Down, &["down"], "down"),
+            ("select", Input::Enter, &["enter"], "open"),
+        ]);
+        let mut hotkeys = HashMap::new();
 
         let mut list_items = vec![];
 
@@ -86,14 +89,15 @@ impl QuickMenu {
 
             list_items.push(QuickMenuItem { label: without, enabled: en, hotkey_idx: idx, active });
             if let Some(keychar) = hotkey {
-                bound_keys.insert(KeyCode::Char(keychar.to_ascii_lowercase()), Input::Selection(list_items.len() - 1));
+                hotkeys.insert(KeyCode::Char(keychar.to_ascii_lowercase()), list_items.len() - 1);
             }
         }
 
         Self {
             selection: 0,
             list_items,
-            bound_keys,
+            keys,
+            hotkeys,
             is_active: true,
             width: 24,
             height: height as u16,
@@ -158,35 +162,53 @@ impl Component for QuickMenu {
 
     fn update(&mut self, events: Vec<Event>) {
         for e in events {
-            match e {
-                Event::Key(KeyEvent { code, .. }) if self.bound_keys.contains_key(&code) => {
-                    match self.bound_keys.get(&code).unwrap() {
-                        Input::Selection(n) => {
-                            if !self.list_items.get(*n).unwrap().enabled {
-                                continue; // handle other events
-                            }
-
-                            if self.selection == *n {
-                                self.select();
-                            } else {
-                                self.selection = *n
-                            }
-                        },
-                        Input::Quit => self.set_active(false),
-                        Input::Enter => {
-                            self.select();
-                        },
-                        Input::Up => {
-                            self.move_sel(-1);
-                        },
-                        Input::Down => {
-                            self.move_sel(1);
-                        },
-                    }
+            let input = match &e {
+                Event::Key(KeyEvent { code, kind, .. }) if *kind != KeyEventKind::Release && self.hotkeys.contains_key(code) => {
+                    Input::Selection(self.hotkeys[code])
                 }
-                _ => {}
+                _ => match self.keys.action(&e) {
+                    Some(input) => input,
+                    None => continue,
+                },
+            };
+
+            match input {
+                Input::Selection(n) => {
+                    if !self.list_items.get(n).unwrap().enabled {
+                        continue; // handle other events
+                    }
+
+                    if self.selection == n {
+                        self.select();
+                    } else {
+                        self.selection = n
+                    }
+                },
+                Input::Quit => self.set_active(false),
+                Input::Enter => {
+                    self.select();
+                },
+                Input::Up => {
+                    self.move_sel(-1);
+                },
+                Input::Down => {
+                    self.move_sel(1);
+                },
+            }
+        }
+    }
+
+    fn help(&self) -> Vec<Help> {
+        let mut hotkeys: Vec<_> = self.hotkeys.iter().collect();
+        hotkeys.sort_by_key(|(_, &n)| n);
+
+        let mut help = self.keys.help();
+        for (code, &n) in hotkeys {
+            if let KeyCode::Char(c) = code {
+                help = help.fixed(c.to_string(), self.list_items[n].label.clone());
             }
         }
+        vec![help]
     }
 }
 
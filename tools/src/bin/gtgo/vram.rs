@@ -0,0 +1,298 @@
+use std::path::{Path, PathBuf};
+
+use crossbeam_channel::Sender;
+use gte_core::emulator::{Emulator, PlayState};
+use ratatui::{crossterm::event::Event, layout::{Constraint, Layout, Rect}, style::{Color, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, Paragraph}, Frame};
+
+use crate::{helpers::{read_rom, InstantClock, SCHEME}, keymap::{Help, Keymap}, main_menu::MainMenu, ui::{file_picker::{FilePicker, PickerMode}, framebuffer::FramebufferView}, Component, GlobalEvent};
+
+/// Sprite RAM pages, then the two framebuffers
+const VIEW_COUNT: u8 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Page(u8),
+    Framebuffer(u8),
+}
+
+impl View {
+    fn from_index(index: u8) -> Self {
+        match index % VIEW_COUNT {
+            i @ 0..=7 => View::Page(i),
+            i => View::Framebuffer(i - 8),
+        }
+    }
+
+    fn index(self) -> u8 {
+        match self {
+            View::Page(p) => p,
+            View::Framebuffer(f) => 8 + f,
+        }
+    }
+
+    fn next(self) -> Self {
+        Self::from_index(self.index() + 1)
+    }
+
+    fn previous(self) -> Self {
+        Self::from_index(self.index() + VIEW_COUNT - 1)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum VramKey {
+    Quit,
+    Pause,
+    NextFrame,
+    NextView,
+    PreviousView,
+    Page(u8),
+    Framebuffer,
+    Open,
+}
+
+fn keymap() -> Keymap<VramKey> {
+    Keymap::new("vram_viewer", &[
+        ("next_view", VramKey::NextView, &["right", "tab"], "next view"),
+        ("previous_view", VramKey::PreviousView, &["left", "backtab"], "previous view"),
+        ("page_0", VramKey::Page(0), &["0"], "page 0"),
+        ("page_1", VramKey::Page(1), &["1"], "page 1"),
+        ("page_2", VramKey::Page(2), &["2"], "page 2"),
+        ("page_3", VramKey::Page(3), &["3"], "page 3"),
+        ("page_4", VramKey::Page(4), &["4"], "page 4"),
+        ("page_5", VramKey::Page(5), &["5"], "page 5"),
+        ("page_6", VramKey::Page(6), &["6"], "page 6"),
+        ("page_7", VramKey::Page(7), &["7"], "page 7"),
+        ("framebuffer", VramKey::Framebuffer, &["f"], "framebuffer"),
+        ("pause", VramKey::Pause, &["p", "space"], "pause"),
+        ("next_frame", VramKey::NextFrame, &["n"], "next frame"),
+        ("open", VramKey::Open, &["o"], "open"),
+        ("quit", VramKey::Quit, &["esc", "q"], "quit"),
+    ])
+}
+
+/// Look at sprite RAM and both framebuffers while a ROM runs, with the last
+/// blit outlined
+pub struct VramViewer {
+    tx: Sender<GlobalEvent>,
+    emulator: Emulator<InstantClock>,
+    rom_path: Option<PathBuf>,
+    picker: Option<FilePicker>,
+    view: View,
+    keys: Keymap<VramKey>,
+    status: String,
+}
+
+impl VramViewer {
+    pub fn init(tx: Sender<GlobalEvent>) -> Self {
+        let dir = std::env::current_dir().unwrap_or_default();
+
+        Self {
+            tx,
+            emulator: Emulator::init(InstantClock::default(), 44_100.0),
+            rom_path: None,
+            picker: Some(FilePicker::init(PickerMode::Open, "gtr", &dir)),
+            view: View::Page(0),
+            keys: keymap(),
+            status: "Pick a ROM to inspect".to_string(),
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = read_rom(path)?;
+        self.emulator.load_rom(&bytes);
+        self.emulator.resume();
+        self.rom_path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    fn quit(&self) {
+        let menu = MainMenu::init(self.tx.clone());
+        let _ = self.tx.send(GlobalEvent::ChangeInterface(Box::new(menu)));
+    }
+
+    fn is_running(&self) -> bool {
+        self.emulator.play_state == PlayState::Playing
+    }
+
+    fn update_picker(&mut self, events: Vec<Event>) {
+        let Some(picker) = &mut self.picker else { return };
+        picker.update(events);
+        if picker.is_active() {
+            return;
+        }
+
+        let picked = picker.take_picked();
+        self.picker = None;
+
+        match picked {
+            Some(path) => {
+                self.status = match self.load(&path) {
+                    Ok(()) => format!("Loaded {}", path.display()),
+                    Err(e) => e,
+                };
+            }
+            None if self.rom_path.is_none() => self.quit(),
+            None => {}
+        }
+    }
+
+    fn handle_key(&mut self, key: VramKey) {
+        match key {
+            VramKey::Quit => self.quit(),
+            VramKey::Pause => {
+                if self.is_running() {
+                    self.emulator.pause();
+                } else {
+                    self.emulator.resume();
+                }
+            }
+            VramKey::NextFrame if !self.is_running() => self.emulator.run_frame(),
+            VramKey::NextView => self.view = self.view.next(),
+            VramKey::PreviousView => self.view = self.view.previous(),
+            VramKey::Page(page) => self.view = View::Page(page),
+            VramKey::Framebuffer => {
+                self.view = match self.view {
+                    View::Framebuffer(f) => View::Framebuffer(1 - f),
+                    View::Page(_) => View::Framebuffer(self.emulator.displayed_framebuffer() as u8),
+                };
+            }
+            VramKey::Open => {
+                let dir = self.rom_path.as_ref()
+                    .and_then(|p| p.parent().map(Path::to_path_buf))
+                    .unwrap_or_default();
+                self.picker = Some(FilePicker::init(PickerMode::Open, "gtr", &dir));
+            }
+            VramKey::NextFrame => {}
+        }
+    }
+
+    fn title(&self) -> String {
+        match self.view {
+            View::Page(p) => format!(" Sprite Page {} ", p),
+            View::Framebuffer(f) if f as usize == self.emulator.displayed_framebuffer() => format!(" Framebuffer {} (shown) ", f),
+            View::Framebuffer(f) => format!(" Framebuffer {} (drawing) ", f),
+        }
+    }
+
+    fn register_lines(&self) -> Vec<Line<'static>> {
+        let regs = self.emulator.blitter_registers();
+        let row = |name: &str, value: u8| Line::from(vec![
+            Span::from(format!("{:<7}", name)).fg(SCHEME.gray[2]),
+            Span::from(format!("${:02X} {:>3}", value, value)),
+        ]);
+
+        vec![
+            row("VX", regs.vx),
+            row("VY", regs.vy),
+            row("GX", regs.gx),
+            row("GY", regs.gy),
+            row("WIDTH", regs.width),
+            row("HEIGHT", regs.height),
+            row("COLOR", regs.color),
+        ]
+    }
+
+    fn blit_lines(&self) -> Vec<Line<'static>> {
+        let Some(blit) = self.emulator.last_blit() else {
+            return vec![Line::from("No blits yet").fg(SCHEME.gray[2])];
+        };
+
+        let mut lines = vec![];
+        match blit.source() {
+            Some((x, y, w, h)) => lines.push(Line::from(format!("src  page {} {},{} {}x{}", blit.vram_page, x, y, w, h))),
+            None => lines.push(Line::from(format!("fill color ${:02X}", blit.color))),
+        }
+        let (x, y, w, h) = blit.dest();
+        lines.push(Line::from(format!("dst  fb {} {},{} {}x{}", blit.framebuffer, x, y, w, h)));
+
+        let flags: Vec<&str> = [
+            (blit.flip_x, "flip-x"),
+            (blit.flip_y, "flip-y"),
+            (!blit.gcarry, "no-gcarry"),
+        ].iter().filter(|(on, _)| *on).map(|(_, name)| *name).collect();
+        if !flags.is_empty() {
+            lines.push(Line::from(flags.join(" ")).fg(SCHEME.gray[2]));
+        }
+        lines
+    }
+}
+
+impl Component for VramViewer {
+    fn update(&mut self, events: Vec<Event>) {
+        if self.picker.is_some() {
+            self.update_picker(events);
+        } else {
+            let keys: Vec<VramKey> = self.keys.actions(&events).collect();
+            for key in keys {
+                self.handle_key(key);
+            }
+        }
+
+        if self.is_running() {
+            self.emulator.process_cycles(false);
+        }
+    }
+
+    fn help(&self) -> Vec<Help> {
+        vec![self.keys.help()]
+    }
+
+    fn captures_text(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    fn render(&mut self, frame: &mut Frame, _area: Rect) {
+        let style = SCHEME.style(Color::Rgb(36, 36, 36));
+        let panel = |title: String| Block::bordered()
+            .title(title)
+            .title_style(style.bold().fg(SCHEME.orange[1]))
+            .border_set(border::ROUNDED)
+            .style(style);
+
+        let [main, footer] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [view_area, side] = Layout::horizontal([Constraint::Fill(1), Constraint::Length(30)]).areas(main);
+        let [regs_area, blit_area] = Layout::vertical([Constraint::Length(9), Constraint::Fill(1)]).areas(side);
+
+        let view_block = panel(self.title());
+        let inner = view_block.inner(view_area);
+        frame.render_widget(view_block, view_area);
+
+        let blit = self.emulator.last_blit();
+        match self.view {
+            View::Page(page) => {
+                let pixels = self.emulator.sprite_page(page as usize);
+                let mut view = FramebufferView::sprite_page(&pixels);
+                if let Some((x, y, w, h)) = blit.filter(|b| b.vram_page == page).and_then(|b| b.source()) {
+                    view = view.highlight(x, y, w, h);
+                }
+                let (w, h) = FramebufferView::page_size_in(inner);
+                frame.render_widget(view, Rect::new(inner.x, inner.y, w.min(inner.width), h.min(inner.height)));
+            }
+            View::Framebuffer(index) => {
+                let pixels = self.emulator.framebuffer(index as usize);
+                let mut view = FramebufferView::new(&pixels);
+                if let Some((x, y, w, h)) = blit.filter(|b| b.framebuffer == index).map(|b| b.dest()) {
+                    view = view.highlight(x, y, w, h);
+                }
+                let (w, h) = FramebufferView::size_in(inner);
+                frame.render_widget(view, Rect::new(inner.x, inner.y, w.min(inner.width), h.min(inner.height)));
+            }
+        }
+
+        frame.render_widget(Paragraph::new(self.register_lines()).block(panel(" Blitter ".to_string())), regs_area);
+        frame.render_widget(Paragraph::new(self.blit_lines()).block(panel(" Last Blit ".to_string())), blit_area);
+
+        let state = if self.is_running() { "RUN " } else { "STOP" };
+        let footer_line = Line::from(vec![
+            Span::from(format!(" {} ", state)).bold().fg(SCHEME.orange[1]),
+            Span::from(self.status.clone()),
+            Span::from(format!("  {} ?:keys", self.keys.hints(&["next_view", "previous_view", "framebuffer", "pause", "next_frame", "open", "quit"]))).fg(SCHEME.gray[2]),
+        ]);
+        frame.render_widget(footer_line, footer);
+
+        if let Some(picker) = &mut self.picker {
+            picker.render(frame, frame.area());
+        }
+    }
+}
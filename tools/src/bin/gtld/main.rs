@@ -1,5 +1,6 @@
 use dialoguer::Select;
 use dialoguer::console::style;
+use gte_core::rom_header::RomHeader;
 use serialport::{SerialPort, SerialPortInfo, available_ports};
 use std::fs;
 use std::io::{Read, Write};
@@ -112,7 +113,16 @@ fn get_port() -> anyhow::Result<Box<dyn SerialPort>> {
 fn load_rom(port: &mut Box<dyn SerialPort>, file: Option<String>) -> anyhow::Result<String> {
     // probably return a checksum?
     let path = file.ok_or_else(|| anyhow::anyhow!("No file provided"))?;
-    let rom_buffer = fs::read(&path)?;
+    let file_buffer = fs::read(&path)?;
+
+    // the cartridge only wants the raw image, not the .gtr header
+    let (header, rom) = RomHeader::split(&file_buffer);
+    if let Some(header) = header {
+        let header = header.map_err(|e| anyhow::anyhow!("Invalid .gtr header: {}", e))?;
+        header.validate(rom).map_err(|e| anyhow::anyhow!("ROM does not match its header: {}", e))?;
+        println!("Loading \"{}\" by {}", header.title, header.author);
+    }
+    let rom_buffer = rom.to_vec();
 
     read_output(port);
 
@@ -0,0 +1,534 @@
+//! Aseprite (.ase/.aseprite) conversion
+//!
+//! Reads [Aseprite](https://www.aseprite.org/) files directly, so animation
+//! tags and frame durations survive without exporting PNG strips:
+//!
+//! - every frame, with the visible layers flattened, is laid out in sprite
+//!   RAM (`<name>.bin`), as many to a quadrant as fit without crossing an
+//!   edge
+//! - `<NAME>_FRAMES` says where each frame ended up, for `gfx::anim::Frames`
+//! - `<NAME>_ANIM` loops every frame in order, and each tag gets an
+//!   `<NAME>_ANIM_<TAG>` with its direction, ping-pong and repeat count.
+//!   Durations are rounded to vblanks.
+//!
+//! Layers are stacked with normal blending whatever their blend mode.
+//! Tilemap layers aren't supported.
+
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+
+use crate::sprite::{const_name, Dither, SpriteSheet, QUADRANT_SIZE};
+
+const FILE_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_OLD_PALETTE_6BIT: u16 = 0x0011;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_TAGS: u16 = 0x2018;
+const CHUNK_PALETTE: u16 = 0x2019;
+
+const LAYER_VISIBLE: u16 = 1;
+const LAYER_BACKGROUND: u16 = 8;
+const LAYER_GROUP: u16 = 1;
+const LAYER_TILEMAP: u16 = 2;
+
+/// Header flag: layer opacity is set
+const OPACITY_VALID: u32 = 1;
+
+/// Sprite RAM quadrants across all 8 pages
+const MAX_QUADRANTS: usize = 32;
+
+/// Little-endian reader over a chunk of the file
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| "file is truncated".to_string())?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), String> {
+        self.take(len).map(|_| ())
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.bytes[self.pos..];
+        self.pos = self.bytes.len();
+        rest
+    }
+}
+
+struct Layer {
+    /// Visible, and so is every group it's in
+    visible: bool,
+    background: bool,
+    group: bool,
+    opacity: u8,
+}
+
+#[derive(Clone)]
+struct Cel {
+    x: i32,
+    y: i32,
+    opacity: u8,
+    width: u32,
+    height: u32,
+    /// Pixels in the file's color depth
+    pixels: Vec<u8>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+struct Tag {
+    name: String,
+    from: usize,
+    to: usize,
+    direction: Direction,
+    /// Times to play, 0 for forever
+    repeat: u16,
+}
+
+struct Sprite {
+    width: u32,
+    height: u32,
+    /// Bits per pixel: 32 (RGBA), 16 (grayscale) or 8 (indexed)
+    depth: u16,
+    transparent_index: u8,
+    palette: Vec<[u8; 4]>,
+    layers: Vec<Layer>,
+    /// Milliseconds each frame is shown for
+    durations: Vec<u16>,
+    /// For each frame, the cel on each layer
+    cels: Vec<Vec<Option<Cel>>>,
+    tags: Vec<Tag>,
+}
+
+impl Sprite {
+    fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut header = Reader::new(bytes);
+        header.skip(4)?;
+        if header.u16()? != FILE_MAGIC {
+            return Err("not an Aseprite file".to_string());
+        }
+        let frames = header.u16()? as usize;
+        let width = header.u16()? as u32;
+        let height = header.u16()? as u32;
+        let depth = header.u16()?;
+        let flags = header.u32()?;
+        header.skip(10)?;
+        let transparent_index = header.u8()?;
+
+        if !matches!(depth, 8 | 16 | 32) {
+            return Err(format!("unknown color depth {}", depth));
+        }
+
+        let mut sprite = Sprite {
+            width,
+            height,
+            depth,
+            transparent_index,
+            palette: vec![],
+            layers: vec![],
+            durations: vec![],
+            cels: vec![],
+            tags: vec![],
+        };
+        // groups containing the layer being read, by child level
+        let mut groups_visible: Vec<bool> = vec![];
+
+        let mut pos = 128;
+        for frame in 0..frames {
+            let mut r = Reader::new(&bytes[pos.min(bytes.len())..]);
+            let frame_len = r.u32()? as usize;
+            if r.u16()? != FRAME_MAGIC {
+                return Err(format!("frame {} is corrupt", frame));
+            }
+            let old_chunks = r.u16()? as usize;
+            sprite.durations.push(r.u16()?);
+            r.skip(2)?;
+            let chunks = match r.u32()? as usize {
+                0 => old_chunks,
+                n => n,
+            };
+            sprite.cels.push(vec![]);
+
+            for _ in 0..chunks {
+                let chunk_len = r.u32()? as usize;
+                let kind = r.u16()?;
+                let mut chunk = Reader::new(r.take(chunk_len.saturating_sub(6))?);
+                match kind {
+                    CHUNK_LAYER => {
+                        let layer_flags = chunk.u16()?;
+                        let layer_type = chunk.u16()?;
+                        let level = chunk.u16()? as usize;
+                        chunk.skip(6)?;
+                        let opacity = chunk.u8()?;
+                        chunk.skip(3)?;
+                        let name = chunk.string()?;
+
+                        groups_visible.truncate(level);
+                        let visible = layer_flags & LAYER_VISIBLE != 0 && groups_visible.iter().all(|&v| v);
+                        if layer_type == LAYER_TILEMAP && visible {
+                            return Err(format!("layer \"{}\" is a tilemap, which isn't supported", name));
+                        }
+                        if layer_type == LAYER_GROUP {
+                            groups_visible.push(layer_flags & LAYER_VISIBLE != 0);
+                        }
+                        sprite.layers.push(Layer {
+                            visible,
+                            background: layer_flags & LAYER_BACKGROUND != 0,
+                            group: layer_type == LAYER_GROUP,
+                            opacity: if flags & OPACITY_VALID != 0 { opacity } else { 255 },
+                        });
+                    }
+                    CHUNK_CEL => {
+                        let (layer, cel) = sprite.parse_cel(&mut chunk, frame)?;
+                        let cels = sprite.cels.last_mut().unwrap();
+                        if cels.len() <= layer {
+                            cels.resize(layer + 1, None);
+                        }
+                        cels[layer] = cel;
+                    }
+                    CHUNK_PALETTE => {
+                        let size = chunk.u32()? as usize;
+                        let first = chunk.u32()? as usize;
+                        let last = chunk.u32()? as usize;
+                        chunk.skip(8)?;
+                        sprite.palette.resize(size.max(sprite.palette.len()), [0; 4]);
+                        for i in first..=last {
+                            let entry_flags = chunk.u16()?;
+                            let rgba = [chunk.u8()?, chunk.u8()?, chunk.u8()?, chunk.u8()?];
+                            if entry_flags & 1 != 0 {
+                                chunk.string()?;
+                            }
+                            if let Some(color) = sprite.palette.get_mut(i) {
+                                *color = rgba;
+                            }
+                        }
+                    }
+                    // only used when there's no new palette, which comes first
+                    CHUNK_OLD_PALETTE | CHUNK_OLD_PALETTE_6BIT if sprite.palette.is_empty() => {
+                        let scale = |v: u8| if kind == CHUNK_OLD_PALETTE_6BIT { (v as u32 * 255 / 63) as u8 } else { v };
+                        let mut palette = vec![[0, 0, 0, 255]; 256];
+                        let mut index = 0;
+                        for _ in 0..chunk.u16()? {
+                            index += chunk.u8()? as usize;
+                            let count = match chunk.u8()? {
+                                0 => 256,
+                                n => n as usize,
+                            };
+                            for _ in 0..count {
+                                let rgb = [scale(chunk.u8()?), scale(chunk.u8()?), scale(chunk.u8()?), 255];
+                                if let Some(color) = palette.get_mut(index) {
+                                    *color = rgb;
+                                }
+                                index += 1;
+                            }
+                        }
+                        sprite.palette = palette;
+                    }
+                    CHUNK_TAGS => {
+                        let count = chunk.u16()?;
+                        chunk.skip(8)?;
+                        for _ in 0..count {
+                            let from = chunk.u16()? as usize;
+                            let to = chunk.u16()? as usize;
+                            let direction = match chunk.u8()? {
+                                1 => Direction::Reverse,
+                                2 => Direction::PingPong,
+                                3 => Direction::PingPongReverse,
+                                _ => Direction::Forward,
+                            };
+                            let repeat = chunk.u16()?;
+                            chunk.skip(10)?;
+                            let name = chunk.string()?;
+                            if from > to || to >= frames {
+                                return Err(format!("tag \"{}\" covers frames {} to {}, but there are {}", name, from, to, frames));
+                            }
+                            sprite.tags.push(Tag { name, from, to, direction, repeat });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            pos += frame_len;
+        }
+
+        Ok(sprite)
+    }
+
+    /// A cel chunk: which layer it's on, and the cel unless it's empty
+    fn parse_cel(&self, chunk: &mut Reader, frame: usize) -> Result<(usize, Option<Cel>), String> {
+        let layer = chunk.u16()? as usize;
+        let x = chunk.i16()? as i32;
+        let y = chunk.i16()? as i32;
+        let opacity = chunk.u8()?;
+        let cel_type = chunk.u16()?;
+        chunk.skip(7)?;
+
+        let bytes_per_pixel = self.depth as usize / 8;
+        let cel = match cel_type {
+            0 | 2 => {
+                let width = chunk.u16()? as u32;
+                let height = chunk.u16()? as u32;
+                let len = (width * height) as usize * bytes_per_pixel;
+                let pixels = if cel_type == 0 {
+                    chunk.take(len)?.to_vec()
+                } else {
+                    let mut pixels = Vec::with_capacity(len);
+                    ZlibDecoder::new(chunk.rest()).read_to_end(&mut pixels)
+                        .map_err(|e| format!("Failed to decompress a cel in frame {}: {}", frame, e))?;
+                    pixels
+                };
+                if pixels.len() < len {
+                    return Err(format!("a cel in frame {} is truncated", frame));
+                }
+                Some(Cel { x, y, opacity, width, height, pixels })
+            }
+            1 => {
+                let linked = chunk.u16()? as usize;
+                let cel = self.cels.get(linked).and_then(|cels| cels.get(layer)).cloned().flatten();
+                cel.map(|cel| Cel { x, y, opacity, ..cel })
+            }
+            3 => return Err(format!("frame {} has a tilemap cel, which isn't supported", frame)),
+            other => return Err(format!("frame {} has a cel of unknown type {}", frame, other)),
+        };
+        Ok((layer, cel))
+    }
+
+    /// A pixel of a cel on `layer` as RGBA
+    fn color(&self, layer: &Layer, pixel: &[u8]) -> [u8; 4] {
+        match self.depth {
+            32 => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            16 => [pixel[0], pixel[0], pixel[0], pixel[1]],
+            _ if pixel[0] == self.transparent_index && !layer.background => [0; 4],
+            _ => self.palette.get(pixel[0] as usize).copied().unwrap_or([0, 0, 0, 255]),
+        }
+    }
+
+    /// Every visible layer of `frame`, stacked bottom to top
+    fn flatten(&self, frame: usize) -> image::RgbaImage {
+        let mut image = image::RgbaImage::new(self.width, self.height);
+        let bytes_per_pixel = self.depth as usize / 8;
+
+        for (index, cel) in self.cels[frame].iter().enumerate() {
+            let (Some(cel), Some(layer)) = (cel, self.layers.get(index)) else { continue };
+            if !layer.visible || layer.group {
+                continue;
+            }
+            let opacity = cel.opacity as u32 * layer.opacity as u32 / 255;
+
+            for cy in 0..cel.height {
+                for cx in 0..cel.width {
+                    let (x, y) = (cel.x + cx as i32, cel.y + cy as i32);
+                    if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+                        continue;
+                    }
+                    let offset = (cy * cel.width + cx) as usize * bytes_per_pixel;
+                    let [r, g, b, a] = self.color(layer, &cel.pixels[offset..offset + bytes_per_pixel]);
+                    let alpha = a as u32 * opacity / 255;
+                    if alpha == 0 {
+                        continue;
+                    }
+
+                    let below = image.get_pixel_mut(x as u32, y as u32);
+                    let blend = |top: u8, bottom: u8| ((top as u32 * alpha + bottom as u32 * (255 - alpha)) / 255) as u8;
+                    below.0 = [
+                        blend(r, below[0]),
+                        blend(g, below[1]),
+                        blend(b, below[2]),
+                        (alpha + below[3] as u32 * (255 - alpha) / 255) as u8,
+                    ];
+                }
+            }
+        }
+        image
+    }
+}
+
+/// Milliseconds to vblanks, at least one
+fn vblanks(ms: u16) -> u8 {
+    ((ms as u32 * 60 + 500) / 1000).clamp(1, 255) as u8
+}
+
+/// Frame indices a tag starts from, in order, and whether it ping-pongs
+fn tag_steps(tag: &Tag) -> (Vec<usize>, bool) {
+    let forward: Vec<usize> = (tag.from..=tag.to).collect();
+    let backward: Vec<usize> = forward.iter().rev().copied().collect();
+    match tag.direction {
+        Direction::Forward => (forward, false),
+        Direction::Reverse => (backward, false),
+        Direction::PingPong => (forward, true),
+        Direction::PingPongReverse => (backward, true),
+    }
+}
+
+/// `gametank::gfx::anim::Animation` expression for `steps`. Aseprite's
+/// repeat count becomes plays through, there and back for ping-pong.
+fn animation(sprite: &Sprite, steps: &[usize], ping_pong: bool, repeat: u16) -> String {
+    let frames: Vec<String> = steps.iter()
+        .map(|&i| format!("    gametank::gfx::anim::AnimFrame {{ frame: {}, duration: {} }},\n", i, vblanks(sprite.durations[i])))
+        .collect();
+    let mut animation = format!("gametank::gfx::anim::Animation::new(&[\n{}])", frames.concat());
+    if ping_pong {
+        animation.push_str(".mode(gametank::gfx::anim::PlayMode::PingPong)");
+    } else if repeat == 1 {
+        animation.push_str(".mode(gametank::gfx::anim::PlayMode::Once)");
+    }
+    if repeat > 1 || (ping_pong && repeat > 0) {
+        animation.push_str(&format!(".repeat({})", repeat.min(255)));
+    }
+    animation
+}
+
+/// Convert an Aseprite file into `<output>.bin` and `<output>.rs`
+pub fn convert_aseprite(input: &str, output: Option<&str>) -> Result<(), String> {
+    let input_path = Path::new(input);
+    let sprite = Sprite::load(input_path)?;
+
+    let (width, height) = (sprite.width, sprite.height);
+    if width == 0 || height == 0 || width > QUADRANT_SIZE || height > QUADRANT_SIZE {
+        return Err(format!("{}: frames are {}x{}, but must fit in a 128x128 quadrant", input, width, height));
+    }
+    let frame_count = sprite.durations.len();
+    if frame_count > 256 {
+        return Err(format!("{}: {} frames, but frame indices are bytes", input, frame_count));
+    }
+
+    // whole frames per quadrant, so none straddles a quadrant edge
+    let columns = QUADRANT_SIZE / width;
+    let per_quadrant = (columns * (QUADRANT_SIZE / height)) as usize;
+    let quadrants = frame_count.div_ceil(per_quadrant).max(1);
+    if quadrants > MAX_QUADRANTS {
+        return Err(format!("{}: {} frames need {} quadrants, but sprite RAM has {}", input, frame_count, quadrants, MAX_QUADRANTS));
+    }
+
+    // one quadrant wide, so quadrant n of the sheet is quadrant n % 4 of page n / 4
+    let mut sheet = image::RgbaImage::new(QUADRANT_SIZE, quadrants as u32 * QUADRANT_SIZE);
+    let mut frames = String::new();
+    for i in 0..frame_count {
+        let quadrant = i / per_quadrant;
+        let slot = (i % per_quadrant) as u32;
+        let (x, y) = ((slot % columns) * width, (slot / columns) * height);
+
+        let image = sprite.flatten(i);
+        for (px, py, pixel) in image.enumerate_pixels() {
+            sheet.put_pixel(x + px, quadrant as u32 * QUADRANT_SIZE + y + py, *pixel);
+        }
+
+        let (gx, gy) = ((quadrant % 2) as u32 * QUADRANT_SIZE, (quadrant / 2 % 2) as u32 * QUADRANT_SIZE);
+        frames.push_str(&format!(
+            "    gametank::gfx::anim::Frame {{ page: {}, sx: {}, sy: {} }},\n",
+            quadrant / 4, gx + x, gy + y,
+        ));
+    }
+    let sheet = SpriteSheet::from_image(&sheet, Dither::None);
+
+    let rs_path = match output {
+        Some(out) => Path::new(out).with_extension("rs"),
+        None => input_path.with_extension("rs"),
+    };
+    let bin_path = rs_path.with_extension("bin");
+    let stem = rs_path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "sprite".to_string());
+    let name = const_name(&stem);
+    let bin_name = format!("{}.bin", stem);
+
+    println!(
+        "Converting Aseprite file: {} ({}x{}, {} frame(s), {} tag(s)) -> {}",
+        input, width, height, frame_count, sprite.tags.len(), rs_path.display()
+    );
+
+    std::fs::write(&bin_path, &sheet.data)
+        .map_err(|e| format!("Failed to write {}: {}", bin_path.display(), e))?;
+
+    let all: Vec<usize> = (0..frame_count).collect();
+    let mut module = format!(
+        "// Generated by `gtrom convert` from {input}. Do not edit.\n\
+         \n\
+         /// Frame width in pixels\n\
+         pub const {name}_WIDTH: u8 = {width};\n\
+         /// Frame height in pixels\n\
+         pub const {name}_HEIGHT: u8 = {height};\n\
+         \n\
+         /// Sprite RAM data, one 0x4000 byte quadrant after another. Load it\n\
+         /// at the top-left of a page with `SpriteSheet`.\n\
+         pub static {name}: &[u8; {len}] = include_bytes!(\"{bin_name}\");\n\
+         \n\
+         /// Where each frame is, from the page {name} is loaded into\n\
+         pub static {name}_FRAMES: gametank::gfx::anim::Frames = gametank::gfx::anim::Frames::new({width}, {height}, &[\n\
+         {frames}]);\n\
+         \n\
+         /// Every frame in order\n\
+         pub static {name}_ANIM: gametank::gfx::anim::Animation = {anim};\n",
+        len = sheet.data.len(),
+        anim = animation(&sprite, &all, false, 0),
+    );
+
+    for tag in &sprite.tags {
+        let (steps, ping_pong) = tag_steps(tag);
+        module.push_str(&format!(
+            "\n/// Tag \"{}\", frames {} to {}\n\
+             pub static {}_ANIM_{}: gametank::gfx::anim::Animation = {};\n",
+            tag.name, tag.from, tag.to, name, const_name(&tag.name), animation(&sprite, &steps, ping_pong, tag.repeat),
+        ));
+    }
+
+    std::fs::write(&rs_path, module)
+        .map_err(|e| format!("Failed to write {}: {}", rs_path.display(), e))?;
+    println!("Wrote sprite data ({} quadrant(s)): {}", sheet.quadrant_count(), bin_path.display());
+    println!("Wrote Rust module: {}", rs_path.display());
+
+    Ok(())
+}
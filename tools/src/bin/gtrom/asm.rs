@@ -1,86 +1,253 @@
 //! Assembly compilation
 //!
-//! Handles assembling .asm files into libasm.a using llvm-mc and llvm-ar.
+//! Handles assembling the .asm, .s and .S files in `src/asm` into libasm.a
+//! using llvm-mc and llvm-ar, with the include directories and defines from
+//! `[asm]` in gtrom.toml. llvm-mc doesn't run the C preprocessor, so .S
+//! files are assembled as they are. Sources are assembled in name order
+//! with relative paths, and the archive is made without timestamps, so
+//! libasm.a is the same on every machine.
+//!
+//! A hash of each source, along with the files it pulls in with `.include`
+//! and `.incbin`, is kept in `target/asm/asm-cache`. Only sources whose hash
+//! changed are assembled again, and libasm.a is only remade when one was or
+//! a source was added or removed.
 
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use crate::container::podman_exec;
+use gametank_project::config::{AsmConfig, Config};
+
+use crate::assets::{fnv1a, load_cache, save_cache, FNV_OFFSET};
+use crate::container::container_exec;
+
+/// Source hashes from the last assembly, under the ROM dir
+const CACHE_FILE: &str = "target/asm/asm-cache";
+
+/// Extensions of the files in `src/asm` that get assembled
+const EXTENSIONS: [&str; 3] = ["asm", "s", "S"];
+
+/// The assembly files in `asm_dir`, by file name. read_dir order varies
+/// between machines, and archive member order ends up in the ROM.
+fn asm_sources(asm_dir: &Path) -> Result<Vec<String>, String> {
+    if !asm_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut files: Vec<String> = std::fs::read_dir(asm_dir)
+        .map_err(|e| format!("Failed to read {}: {}", asm_dir.display(), e))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| EXTENSIONS.iter().any(|&e| ext == e)))
+        .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    files.sort();
+
+    // objects are named after the stem, so foo.asm and foo.s would collide
+    let mut stems: Vec<&str> = files.iter().map(|file| stem(file)).collect();
+    stems.sort();
+    if let Some(pair) = stems.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(format!("src/asm has more than one source named {}", pair[0]));
+    }
+    Ok(files)
+}
+
+/// A source's name without its extension, which its object is named after
+fn stem(file: &str) -> &str {
+    file.rsplit_once('.').map_or(file, |(stem, _)| stem)
+}
+
+/// `llvm-mc` arguments to assemble `file` from `src/asm`
+fn mc_args(file: &str, asm: &AsmConfig) -> Vec<String> {
+    let mut args = vec!["--filetype=obj".to_string(), "-triple=mos".to_string(), "-mcpu=mosw65c02".to_string()];
+    args.extend(asm.args());
+    args.extend([
+        format!("src/asm/{}", file),
+        "-o".to_string(),
+        format!("target/asm/{}.o", stem(file)),
+    ]);
+    args
+}
+
+/// The file an `.include` or `.incbin` line names, and whether it's an
+/// include (so it may include more)
+fn directive(line: &str) -> Option<(&str, bool)> {
+    let line = line.trim_start();
+    let (rest, include) = if let Some(rest) = line.strip_prefix(".include") {
+        (rest, true)
+    } else if let Some(rest) = line.strip_prefix(".incbin") {
+        (rest, false)
+    } else {
+        return None;
+    };
+    let rest = rest.trim_start().strip_prefix('"')?;
+    Some((&rest[..rest.find('"')?], include))
+}
+
+/// Find an included file the way llvm-mc does: next to the file including
+/// it, in the directory it runs in, then in the `-I` directories
+fn resolve(name: &str, from: &Path, workdir: &Path, asm: &AsmConfig) -> Option<PathBuf> {
+    let beside = from.parent().map(|dir| dir.join(name));
+    let searched = asm.include.iter().map(|dir| workdir.join(dir).join(name));
+    beside.into_iter()
+        .chain([workdir.join(name)])
+        .chain(searched)
+        .find(|path| path.is_file())
+}
+
+/// Hash `path` and everything it includes into `hash`, each file once
+fn hash_source(hash: &mut u64, path: &Path, workdir: &Path, asm: &AsmConfig, seen: &mut BTreeSet<PathBuf>) -> Result<(), String> {
+    if !seen.insert(path.to_path_buf()) {
+        return Ok(());
+    }
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    *hash = fnv1a(*hash, &bytes);
+
+    for line in String::from_utf8_lossy(&bytes).lines() {
+        let Some((name, include)) = directive(line) else { continue };
+        *hash = fnv1a(*hash, name.as_bytes());
+        // a missing file is llvm-mc's to report
+        let Some(included) = resolve(name, path, workdir, asm) else { continue };
+        if include {
+            hash_source(hash, &included, workdir, asm, seen)?;
+        } else if seen.insert(included.clone()) {
+            let bytes = std::fs::read(&included)
+                .map_err(|e| format!("Failed to read {}: {}", included.display(), e))?;
+            *hash = fnv1a(*hash, &bytes);
+        }
+    }
+    Ok(())
+}
+
+/// What needs assembling this build
+struct AsmPlan {
+    /// Every source, by file name
+    files: Vec<String>,
+    /// The sources that changed since they were last assembled
+    stale: Vec<String>,
+    /// Whether libasm.a needs making again
+    archive: bool,
+    /// Each source's hash, for the cache
+    hashes: BTreeMap<PathBuf, u64>,
+    /// Sources assembled last time that are gone now
+    removed: Vec<PathBuf>,
+}
+
+/// Hash the sources and compare them with the last build's
+fn plan(workdir: &Path, asm: &AsmConfig) -> Result<AsmPlan, String> {
+    let target_dir = workdir.join("target/asm");
+    let old_cache = load_cache(&workdir.join(CACHE_FILE));
+    let files = asm_sources(&workdir.join("src/asm"))?;
+    let settings = format!("{} {}", env!("CARGO_PKG_VERSION"), asm.args().join(" "));
+
+    let mut hashes = BTreeMap::new();
+    let mut stale = Vec::new();
+    for file in &files {
+        let mut hash = fnv1a(FNV_OFFSET, settings.as_bytes());
+        hash_source(&mut hash, &workdir.join("src/asm").join(file), workdir, asm, &mut BTreeSet::new())?;
+
+        let key = PathBuf::from(file);
+        let object = target_dir.join(format!("{}.o", stem(file)));
+        if old_cache.get(&key) != Some(&hash) || !object.exists() {
+            stale.push(file.clone());
+        }
+        hashes.insert(key, hash);
+    }
+
+    let removed: Vec<PathBuf> = old_cache.into_keys().filter(|file| !hashes.contains_key(file)).collect();
+    let archive = !stale.is_empty() || !removed.is_empty() || !target_dir.join("libasm.a").exists();
+    Ok(AsmPlan { files, stale, archive, hashes, removed })
+}
+
+impl AsmPlan {
+    /// Drop the objects of removed sources and remember what was assembled
+    fn finish(&self, workdir: &Path) -> Result<(), String> {
+        for file in &self.removed {
+            let file = file.to_string_lossy();
+            // foo.asm renamed to foo.s keeps its object
+            if self.files.iter().all(|current| stem(current) != stem(&file)) {
+                let _ = std::fs::remove_file(workdir.join("target/asm").join(format!("{}.o", stem(&file))));
+            }
+        }
+        save_cache(&workdir.join(CACHE_FILE), &self.hashes)?;
+
+        let up_to_date = self.files.len() - self.stale.len();
+        println!("  {} assembled, {} up to date", self.stale.len(), up_to_date);
+        Ok(())
+    }
+}
+
+/// `llvm-ar` arguments to archive the objects of `files` into a fresh
+/// libasm.a. `D` zeroes timestamps, owners and modes, so the archive only
+/// depends on its contents.
+fn ar_args(files: &[String]) -> Vec<String> {
+    let mut args = vec!["rcsD".to_string(), "target/asm/libasm.a".to_string()];
+    args.extend(files.iter().map(|file| format!("target/asm/{}.o", stem(file))));
+    args
+}
+
+/// Remove a libasm.a from an earlier build, so objects from deleted .asm
+/// files don't linger in it
+fn remove_old_archive(target_dir: &Path) -> Result<(), String> {
+    let archive = target_dir.join("libasm.a");
+    if archive.exists() {
+        std::fs::remove_file(&archive)
+            .map_err(|e| format!("Failed to remove {}: {}", archive.display(), e))?;
+    }
+    Ok(())
+}
 
 /// Build assembly files into libasm.a (runs directly)
-pub fn build_asm(workdir: &str) -> Result<(), String> {
+///
+/// Paths are relative to `workdir`, so they don't end up in the objects.
+pub fn build_asm(workdir: &str, asm: &AsmConfig) -> Result<(), String> {
     println!("Assembling .asm files...");
     
-    let asm_dir = Path::new(workdir).join("src/asm");
     let target_dir = Path::new(workdir).join("target/asm");
     
     std::fs::create_dir_all(&target_dir)
         .map_err(|e| format!("Failed to create target/asm: {}", e))?;
 
-    // Find and assemble all .asm files
-    if asm_dir.exists() {
-        for entry in std::fs::read_dir(&asm_dir).map_err(|e| e.to_string())? {
-            let entry = entry.map_err(|e| e.to_string())?;
-            let path = entry.path();
-            if path.extension().map_or(false, |ext| ext == "asm") {
-                let filename = path.file_stem().unwrap().to_string_lossy();
-                println!("  Assembling {}...", filename);
-                
-                let status = Command::new("llvm-mc")
-                    .args([
-                        "--filetype=obj",
-                        "-triple=mos",
-                        "-mcpu=mosw65c02",
-                        path.to_str().unwrap(),
-                        "-o",
-                        &format!("{}/target/asm/{}.o", workdir, filename),
-                    ])
-                    .status()
-                    .map_err(|e| format!("Failed to assemble {}: {}", filename, e))?;
-
-                if !status.success() {
-                    return Err(format!("Failed to assemble {}", filename));
-                }
-            }
+    let plan = plan(Path::new(workdir), asm)?;
+    for file in &plan.stale {
+        println!("  Assembling {}...", file);
+
+        let status = Command::new("llvm-mc")
+            .current_dir(workdir)
+            .args(mc_args(file, asm))
+            .status()
+            .map_err(|e| format!("Failed to assemble {}: {}", file, e))?;
+
+        if !status.success() {
+            return Err(format!("Failed to assemble {}", file));
         }
     }
 
-    // Archive into libasm.a
-    println!("  Creating libasm.a...");
-    let o_files: Vec<_> = std::fs::read_dir(&target_dir)
-        .map_err(|e| e.to_string())?
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().map_or(false, |ext| ext == "o"))
-        .map(|e| e.path().to_string_lossy().to_string())
-        .collect();
-
-    if !o_files.is_empty() {
-        let mut args = vec!["rcs".to_string(), format!("{}/target/asm/libasm.a", workdir)];
-        args.extend(o_files.clone());
-        
+    // Archive into libasm.a, keeping the objects for next time
+    if plan.archive {
+        remove_old_archive(&target_dir)?;
+    }
+    if plan.archive && !plan.files.is_empty() {
+        println!("  Creating libasm.a...");
         let status = Command::new("llvm-ar")
-            .args(&args)
+            .current_dir(workdir)
+            .args(ar_args(&plan.files))
             .status()
             .map_err(|e| format!("Failed to archive: {}", e))?;
 
         if !status.success() {
             return Err("Failed to create libasm.a".to_string());
         }
-
-        // Clean up .o files
-        for o_file in o_files {
-            let _ = std::fs::remove_file(o_file);
-        }
     }
 
-    Ok(())
+    plan.finish(Path::new(workdir))
 }
 
 /// Build assembly files via container
-pub fn build_asm_in_container(workdir: &Path, working_dir: &Path) -> Result<(), String> {
+pub fn build_asm_in_container(workdir: &Path, working_dir: &Path, config: &Config) -> Result<(), String> {
     println!("Assembling .asm files...");
     
-    let asm_dir = workdir.join("src/asm");
     let target_dir = workdir.join("target/asm");
     
     std::fs::create_dir_all(&target_dir)
@@ -89,56 +256,28 @@ pub fn build_asm_in_container(workdir: &Path, working_dir: &Path) -> Result<(),
     let rel_workdir = workdir.strip_prefix(working_dir).unwrap_or(workdir);
     let workspace_dir = format!("/workspace/{}", rel_workdir.to_string_lossy());
 
-    // Find and assemble all .asm files
-    if asm_dir.exists() {
-        for entry in std::fs::read_dir(&asm_dir).map_err(|e| e.to_string())? {
-            let entry = entry.map_err(|e| e.to_string())?;
-            let path = entry.path();
-            if path.extension().map_or(false, |ext| ext == "asm") {
-                let filename = path.file_stem().unwrap().to_string_lossy();
-                println!("  Assembling {}...", filename);
-                
-                podman_exec("/workspace", &[
-                    "llvm-mc",
-                    "--filetype=obj",
-                    "-triple=mos",
-                    "-mcpu=mosw65c02",
-                    &format!("{}/src/asm/{}.asm", workspace_dir, filename),
-                    "-o",
-                    &format!("{}/target/asm/{}.o", workspace_dir, filename),
-                ])?;
-            }
-        }
-    }
+    let plan = plan(workdir, &config.asm)?;
+    for file in &plan.stale {
+        println!("  Assembling {}...", file);
 
-    // Archive into libasm.a
-    let o_files: Vec<_> = std::fs::read_dir(&target_dir)
-        .map_err(|e| e.to_string())?
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().map_or(false, |ext| ext == "o"))
-        .map(|e| format!("{}/target/asm/{}", workspace_dir, e.path().file_name().unwrap().to_string_lossy()))
-        .collect();
+        let mut args = vec!["llvm-mc".to_string()];
+        args.extend(mc_args(file, &config.asm));
+        let args_ref: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        container_exec(config, &workspace_dir, &args_ref)?;
+    }
 
-    if !o_files.is_empty() {
+    // Archive into libasm.a, keeping the objects for next time
+    if plan.archive {
+        remove_old_archive(&target_dir)?;
+    }
+    if plan.archive && !plan.files.is_empty() {
         println!("  Creating libasm.a...");
-        let mut args = vec![
-            "llvm-ar".to_string(),
-            "rcs".to_string(),
-            format!("{}/target/asm/libasm.a", workspace_dir),
-        ];
-        args.extend(o_files);
-        
-        let args_ref: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-        podman_exec("/workspace", &args_ref)?;
+        let mut args = vec!["llvm-ar".to_string()];
+        args.extend(ar_args(&plan.files));
 
-        // Clean up .o files
-        for entry in std::fs::read_dir(&target_dir).map_err(|e| e.to_string())? {
-            let entry = entry.map_err(|e| e.to_string())?;
-            if entry.path().extension().map_or(false, |ext| ext == "o") {
-                let _ = std::fs::remove_file(entry.path());
-            }
-        }
+        let args_ref: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        container_exec(config, &workspace_dir, &args_ref)?;
     }
 
-    Ok(())
+    plan.finish(workdir)
 }
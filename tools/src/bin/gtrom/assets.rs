@@ -0,0 +1,340 @@
+//! Asset conversion for `gtrom build`
+//!
+//! Before compiling, every PNG, Aseprite file and WAV in the asset
+//! directories from gtrom.toml is converted in parallel into `target/assets`,
+//! mirroring the directory layout:
+//!
+//! - `*.png` becomes sprite RAM data, like `gtrom convert --format rs`
+//! - `*.ase` and `*.aseprite` become sprite RAM data with a frame table
+//!   and animations, like `gtrom convert`
+//! - `*.wav` becomes a sample, or a wavetable if it's in a `wavetables`
+//!   directory
+//! - `*.gtt` tracker projects become songs, with the wavetables in their
+//!   `<name>.wavetables` directory, like `gtrom convert`
+//!
+//! Each one gets a `.bin` and a `.rs` module that `include_bytes!`s it, and
+//! `target/assets/assets.rs` pulls all the modules in:
+//!
+//! ```ignore
+//! include!("../target/assets/assets.rs");
+//! // assets/player.png
+//! let sheet = player::PLAYER;
+//! ```
+//!
+//! A hash of each source file is kept in `target/assets-cache`, so only
+//! assets that changed since the last build are converted again. In a
+//! workspace with several ROMs, converted assets are also kept by hash in the
+//! workspace's `target/shared-assets`, so an asset the ROMs share is only
+//! converted once.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use gametank_sdk::tracker::project::{wavetable_files, WAVETABLES_SUFFIX};
+use gametank_sdk::wav::{Cycle, ACP_SAMPLE_RATE};
+use rayon::prelude::*;
+
+use crate::aseprite::convert_aseprite;
+use crate::song::convert_gtt;
+use crate::sprite::{convert_png, Dither, SpriteFormat};
+use crate::wav::{convert_wav, WavMode};
+
+/// Where converted assets go, under the ROM dir
+pub const OUTPUT_DIR: &str = "target/assets";
+/// Source hashes from the last conversion, under the ROM dir
+pub const CACHE_FILE: &str = "target/assets-cache";
+/// Converted assets shared between a workspace's ROMs, under the workspace root
+pub const SHARED_DIR: &str = "target/shared-assets";
+
+/// How to convert an asset
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Sprite,
+    Aseprite,
+    Sample,
+    Wavetable,
+    Song,
+}
+
+impl Kind {
+    fn of(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "png" => Some(Self::Sprite),
+            "ase" | "aseprite" => Some(Self::Aseprite),
+            "wav" if path.components().any(|c| c.as_os_str() == "wavetables") => Some(Self::Wavetable),
+            "wav" => Some(Self::Sample),
+            "gtt" => Some(Self::Song),
+            _ => None,
+        }
+    }
+
+    fn convert(self, input: &Path, output: &Path) -> Result<(), String> {
+        let input = input.to_string_lossy();
+        let output = output.to_string_lossy();
+        match self {
+            Self::Sprite => convert_png(&input, Some(&output), SpriteFormat::Rs, Dither::None, false),
+            Self::Aseprite => convert_aseprite(&input, Some(&output)),
+            Self::Sample => convert_wav(&input, Some(&output), WavMode::Sample, ACP_SAMPLE_RATE, Cycle::Detect),
+            Self::Wavetable => convert_wav(&input, Some(&output), WavMode::Wavetable, ACP_SAMPLE_RATE, Cycle::Detect),
+            Self::Song => convert_gtt(&input, Some(&output)),
+        }
+    }
+}
+
+/// One file to convert
+struct Asset {
+    source: PathBuf,
+    /// Path relative to its asset directory, without the extension
+    name: PathBuf,
+    kind: Kind,
+}
+
+impl Asset {
+    /// The `.bin` this asset converts to. The `.rs` goes next to it.
+    fn output(&self, output_dir: &Path) -> PathBuf {
+        output_dir.join(&self.name).with_extension("bin")
+    }
+
+    /// Where the shared cache keeps this asset's `.bin` once converted. The
+    /// file name is part of it because the `.rs` refers to the `.bin` by name.
+    fn shared(&self, shared_dir: &Path, hash: u64) -> PathBuf {
+        let file_name = self.name.file_name().unwrap_or_default();
+        shared_dir.join(format!("{:016x}", hash)).join(file_name).with_extension("bin")
+    }
+
+    /// Convert into `output`, or copy from `shared_dir` if another ROM already
+    /// converted the same file. Returns whether it was copied.
+    fn convert_shared(&self, output: &Path, shared_dir: Option<&Path>, hash: u64) -> Result<bool, String> {
+        let Some(shared_dir) = shared_dir else {
+            self.kind.convert(&self.source, output)?;
+            return Ok(false);
+        };
+
+        let stored = self.shared(shared_dir, hash);
+        let copy = |from: &Path, to: &Path| -> Result<(), String> {
+            for ext in ["bin", "rs"] {
+                let (from, to) = (from.with_extension(ext), to.with_extension(ext));
+                std::fs::copy(&from, &to)
+                    .map_err(|e| format!("Failed to copy {} to {}: {}", from.display(), to.display(), e))?;
+            }
+            Ok(())
+        };
+
+        if stored.exists() && stored.with_extension("rs").exists() {
+            copy(&stored, output)?;
+            return Ok(true);
+        }
+
+        self.kind.convert(&self.source, output)?;
+        // the ROM has its asset either way, so a failed store isn't an error
+        if let Some(parent) = stored.parent() {
+            if std::fs::create_dir_all(parent).is_ok() {
+                let _ = copy(output, &stored);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Cache key: the gtrom version, how it's converted, and the file
+    /// contents, along with a song's wavetables
+    fn hash(&self) -> Result<u64, String> {
+        let bytes = std::fs::read(&self.source)
+            .map_err(|e| format!("Failed to read {}: {}", self.source.display(), e))?;
+        let settings = format!("{} {:?}", env!("CARGO_PKG_VERSION"), self.kind);
+        let mut hash = fnv1a(fnv1a(FNV_OFFSET, settings.as_bytes()), &bytes);
+
+        if self.kind == Kind::Song {
+            for path in wavetable_files(&self.source)? {
+                let bytes = std::fs::read(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                hash = fnv1a(fnv1a(hash, name.as_bytes()), &bytes);
+            }
+        }
+        Ok(hash)
+    }
+}
+
+pub const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a, which unlike std's hasher is the same on every Rust version
+pub fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash = (hash ^ b as u64).wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Every convertible file under `dir`
+fn collect(dir: &Path, sub: &Path, assets: &mut Vec<Asset>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir.join(sub))
+        .map_err(|e| format!("Failed to read {}: {}", dir.join(sub).display(), e))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let rel = sub.join(entry.file_name());
+        let path = entry.path();
+
+        // a song's wavetables go in the song, see `song`
+        if path.is_dir() && entry.file_name().to_string_lossy().ends_with(WAVETABLES_SUFFIX) {
+            continue;
+        } else if path.is_dir() {
+            collect(dir, &rel, assets)?;
+        } else if let Some(kind) = Kind::of(&path) {
+            assets.push(Asset { source: path, name: rel.with_extension(""), kind });
+        }
+    }
+    Ok(())
+}
+
+/// The asset directories from gtrom.toml that exist, relative to the ROM
+/// dir or the project root
+fn asset_dirs(working_dir: &Path, rom_dir: &Path, dirs: &[String]) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for base in [rom_dir, working_dir] {
+        for dir in dirs {
+            let path = base.join(dir);
+            if path.is_dir() && !found.contains(&path) {
+                found.push(path);
+            }
+        }
+    }
+    found
+}
+
+pub fn load_cache(path: &Path) -> BTreeMap<PathBuf, u64> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return BTreeMap::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let (hash, name) = line.split_once(' ')?;
+            Some((PathBuf::from(name), u64::from_str_radix(hash, 16).ok()?))
+        })
+        .collect()
+}
+
+pub fn save_cache(path: &Path, cache: &BTreeMap<PathBuf, u64>) -> Result<(), String> {
+    let text: String = cache.iter()
+        .map(|(name, hash)| format!("{:016x} {}\n", hash, name.display()))
+        .collect();
+    std::fs::write(path, text)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// A Rust identifier for an asset's module: `sprites/player` -> `sprites_player`
+fn module_name(name: &Path) -> String {
+    let mut module: String = name.to_string_lossy().chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if module.is_empty() || module.starts_with(|c: char| c.is_ascii_digit()) {
+        module.insert(0, '_');
+    }
+    module
+}
+
+/// `target/assets/assets.rs`, with a module for every asset
+fn write_index(output_dir: &Path, assets: &[Asset]) -> Result<(), String> {
+    let mut index = String::from("// Generated by `gtrom build`. Do not edit.\n");
+    for asset in assets {
+        let rs = asset.name.with_extension("rs");
+        index.push_str(&format!(
+            "\npub mod {} {{\n    include!(\"{}\");\n}}\n",
+            module_name(&asset.name),
+            rs.to_string_lossy().replace('\\', "/"),
+        ));
+    }
+
+    let path = output_dir.join("assets.rs");
+    // leave it alone if nothing changed, so cargo doesn't rebuild for nothing
+    if std::fs::read_to_string(&path).is_ok_and(|old| old == index) {
+        return Ok(());
+    }
+    std::fs::write(&path, index)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Convert the project's assets into `target/assets`, skipping ones that
+/// haven't changed. Does nothing if there are no asset directories.
+/// `shared_dir` is the workspace's [`SHARED_DIR`], if there's more than one ROM.
+pub fn convert_assets(working_dir: &Path, rom_dir: &Path, dirs: &[String], shared_dir: Option<&Path>) -> Result<(), String> {
+    let dirs = asset_dirs(working_dir, rom_dir, dirs);
+    if dirs.is_empty() {
+        return Ok(());
+    }
+
+    let mut assets = Vec::new();
+    for dir in &dirs {
+        collect(dir, Path::new(""), &mut assets)?;
+    }
+    assets.sort_by(|a, b| a.name.cmp(&b.name));
+    if let Some(pair) = assets.windows(2).find(|pair| pair[0].name == pair[1].name) {
+        return Err(format!(
+            "{} and {} would both convert to {}",
+            pair[0].source.display(), pair[1].source.display(), pair[0].name.display()
+        ));
+    }
+
+    let output_dir = rom_dir.join(OUTPUT_DIR);
+    let cache_path = rom_dir.join(CACHE_FILE);
+    let old_cache = load_cache(&cache_path);
+
+    // hash and convert on every core; large sprite sheets take a while
+    // (name, hash, converted, copied from the shared cache)
+    let results: Vec<Result<(PathBuf, u64, bool, bool), String>> = assets.par_iter()
+        .map(|asset| {
+            let hash = asset.hash()?;
+            let output = asset.output(&output_dir);
+            let fresh = old_cache.get(&asset.name) == Some(&hash) && output.exists();
+            let mut copied = false;
+            if !fresh {
+                if let Some(parent) = output.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+                }
+                copied = asset.convert_shared(&output, shared_dir, hash)?;
+            }
+            Ok((asset.name.clone(), hash, !fresh && !copied, copied))
+        })
+        .collect();
+
+    let mut cache = BTreeMap::new();
+    let mut converted = 0;
+    let mut shared = 0;
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok((name, hash, was_converted, was_copied)) => {
+                converted += was_converted as usize;
+                shared += was_copied as usize;
+                cache.insert(name, hash);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    // forget assets that were deleted, along with what they converted to
+    for name in old_cache.keys().filter(|name| !cache.contains_key(*name)) {
+        let output = output_dir.join(name);
+        let _ = std::fs::remove_file(output.with_extension("bin"));
+        let _ = std::fs::remove_file(output.with_extension("rs"));
+    }
+
+    // keep what did convert, so a fix only reconverts the broken asset
+    std::fs::create_dir_all(&output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", output_dir.display(), e))?;
+    save_cache(&cache_path, &cache)?;
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    write_index(&output_dir, &assets)?;
+    let up_to_date = assets.len() - converted - shared;
+    match shared {
+        0 => println!("Assets: {} converted, {} up to date", converted, up_to_date),
+        _ => println!("Assets: {} converted, {} shared, {} up to date", converted, shared, up_to_date),
+    }
+    Ok(())
+}
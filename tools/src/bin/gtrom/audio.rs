@@ -5,7 +5,10 @@
 use std::path::Path;
 use std::process::Command;
 
-use crate::container::{ensure_container, is_in_container, podman_exec};
+use gametank_project::config::{Config, Toolchain};
+
+use crate::container::{ensure_container, container_exec, uses_container};
+use crate::toolchain::resolve as resolve_toolchain;
 
 /// Get firmware name from directory name
 fn get_firmware_name(path: &Path) -> Result<String, String> {
@@ -93,14 +96,15 @@ fn build_audio_asm(path: &Path, name: &str, output_dir: &Path) -> Result<(), Str
 }
 
 /// Build audio firmware (Rust project) - runs directly
-fn build_audio_rust(path: &Path, name: &str, output_dir: &Path) -> Result<(), String> {
+fn build_audio_rust(path: &Path, name: &str, output_dir: &Path, toolchain: Toolchain) -> Result<(), String> {
     println!("Building Rust audio firmware: {}", name);
     
     // Build with cargo
     let status = Command::new("cargo")
         .current_dir(path)
+        .args(toolchain.cargo_prefix())
         .args([
-            "+mos", "build",
+            "build",
             "-Z", "build-std=core",
             "--target", "mos-unknown-none",
             "--release",
@@ -140,7 +144,7 @@ fn build_audio_rust(path: &Path, name: &str, output_dir: &Path) -> Result<(), St
 }
 
 /// Build audio firmware (ASM project) - runs inside container
-fn build_audio_asm_in_container(path: &Path, name: &str, output_dir: &Path, working_dir: &Path) -> Result<(), String> {
+fn build_audio_asm_in_container(path: &Path, name: &str, output_dir: &Path, working_dir: &Path, config: &Config) -> Result<(), String> {
     println!("Building ASM audio firmware: {}", name);
     
     let build_dir = path.join("build");
@@ -164,7 +168,7 @@ fn build_audio_asm_in_container(path: &Path, name: &str, output_dir: &Path, work
             let filename = file_path.file_stem().unwrap().to_string_lossy();
             println!("  Assembling {}...", filename);
             
-            podman_exec("/workspace", &[
+            container_exec(config, "/workspace", &[
                 "llvm-mc",
                 "--filetype=obj",
                 "-triple=mos",
@@ -197,14 +201,14 @@ fn build_audio_asm_in_container(path: &Path, name: &str, output_dir: &Path, work
     link_args.push(elf_path.clone());
     
     let link_args_ref: Vec<&str> = link_args.iter().map(|s| s.as_str()).collect();
-    podman_exec("/workspace", &link_args_ref)?;
+    container_exec(config, "/workspace", &link_args_ref)?;
     
     // Extract binary
     std::fs::create_dir_all(output_dir)
         .map_err(|e| format!("Failed to create output dir: {}", e))?;
     
     let bin_path = format!("{}/{}.bin", workspace_output, name);
-    podman_exec("/workspace", &[
+    container_exec(config, "/workspace", &[
         "llvm-objcopy",
         "-O", "binary",
         &elf_path,
@@ -238,23 +242,26 @@ pub fn do_audio_build(path_str: &str) -> Result<(), String> {
         // Fallback - just put it next to the source
         path.join("bin")
     };
+
+    let mut config = Config::load(&working_dir)?;
+    config.toolchain = resolve_toolchain(&config, &working_dir)?;
     
-    if is_in_container() {
-        // Direct build inside container
+    if !uses_container(&config) {
+        // Direct build (inside container, or with a local toolchain)
         if path.join("Cargo.toml").exists() {
-            build_audio_rust(path, &name, &output_dir)
+            build_audio_rust(path, &name, &output_dir, config.toolchain)
         } else {
             build_audio_asm(path, &name, &output_dir)
         }
     } else {
-        // Orchestrate from outside container - run llvm commands via podman exec
-        let (workspace_root, _runtime) = ensure_container()?;
+        // Orchestrate from outside container - run llvm commands in the container
+        let (workspace_root, _runtime) = ensure_container(&config, &working_dir)?;
         
         if path.join("Cargo.toml").exists() {
             // TODO: Rust audio build via container
             Err("Rust audio firmware build from outside container not yet implemented".to_string())
         } else {
-            build_audio_asm_in_container(path, &name, &output_dir, &workspace_root)
+            build_audio_asm_in_container(path, &name, &output_dir, &workspace_root, &config)
         }
     }
 }
@@ -0,0 +1,277 @@
+//! Asset bundling from `assets.toml`
+//!
+//! Where the asset directories convert every file they find, `assets.toml`
+//! in the ROM dir lists assets by name, with their conversion settings (see
+//! [`gametank_project::manifest`]).
+//!
+//! `gtrom build` converts them, packs them into ROM banks itself, and writes
+//! `src/assets_gen.rs` with a typed constant for each from
+//! `gametank::assets`, so game code says `PLAYER_SPRITE.load(console, 0)`
+//! instead of juggling banks and offsets. The converted data goes in
+//! `target/bundle`.
+//!
+//! Sprites and data with `compress = true` are stored compressed, which
+//! suits big title screens and maps; see [`crate::compress`].
+
+use std::path::Path;
+
+use gametank_project::manifest::{AssetEntry, Kind, Manifest, MANIFEST_FILE, SAVE_BANK};
+use gametank_sdk::wav::{load_sample, load_wavetable, ACP_SAMPLE_RATE};
+
+use crate::compress::compress;
+use crate::sprite::{const_name, Dither, SpriteSheet, QUADRANT_BYTES, QUADRANT_SIZE};
+
+/// The generated module, under the ROM dir
+pub const GENERATED_FILE: &str = "src/assets_gen.rs";
+/// Where the packed data goes, under the ROM dir
+pub const OUTPUT_DIR: &str = "target/bundle";
+
+/// Bytes in a ROM bank
+const BANK_SIZE: usize = 0x4000;
+/// The last switchable bank; 127 is the fixed bank
+const LAST_BANK: u8 = 126;
+
+/// A converted asset, as what goes in the generated module
+enum Converted {
+    Sprite { width: u32, height: u32, quadrants: Vec<Vec<u8>> },
+    Sample { rate: u32, data: Vec<u8> },
+    Wavetable { source_hz: u16, data: Vec<u8> },
+    Data(Vec<u8>),
+}
+
+impl Converted {
+    fn load(entry: &AssetEntry, rom_dir: &Path) -> Result<Self, String> {
+        let path = rom_dir.join(&entry.path);
+        let kind = entry.kind();
+        if entry.dither.is_some() && kind != Kind::Sprite {
+            return Err(format!("{}: dither is only for sprites", entry.name));
+        }
+        if entry.rate.is_some() && kind != Kind::Sample {
+            return Err(format!("{}: rate is only for samples", entry.name));
+        }
+        if entry.compress && !matches!(kind, Kind::Sprite | Kind::Data) {
+            return Err(format!("{}: compress is only for sprites and data", entry.name));
+        }
+
+        let mut asset = match kind {
+            Kind::Sprite => {
+                let sheet = SpriteSheet::from_png(&path, entry.dither.unwrap_or(Dither::None))?;
+                // leave out the rows under the image, so small sheets share banks
+                let quadrants = sheet.data.chunks(QUADRANT_BYTES)
+                    .enumerate()
+                    .map(|(i, quadrant)| {
+                        let top = (i as u32 / sheet.quadrants_x) * QUADRANT_SIZE;
+                        let rows = (sheet.height - top).min(QUADRANT_SIZE);
+                        quadrant[..(rows * QUADRANT_SIZE) as usize].to_vec()
+                    })
+                    .collect();
+                Self::Sprite { width: sheet.width, height: sheet.height, quadrants }
+            }
+            Kind::Sample => {
+                let rate = entry.rate.unwrap_or(ACP_SAMPLE_RATE);
+                Self::Sample { rate, data: load_sample(&path, rate)? }
+            }
+            Kind::Wavetable => {
+                let (data, pitch) = load_wavetable(&path)?;
+                Self::Wavetable { source_hz: pitch.round() as u16, data }
+            }
+            Kind::Data => Self::Data(
+                std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+            ),
+        };
+
+        if entry.compress {
+            match &mut asset {
+                Self::Sprite { quadrants, .. } => {
+                    for quadrant in quadrants {
+                        *quadrant = compress(quadrant)?;
+                    }
+                }
+                Self::Data(data) => *data = compress(data).map_err(|e| format!("{}: {}", entry.name, e))?,
+                _ => {}
+            }
+        }
+        Ok(asset)
+    }
+
+    /// The pieces that each go in one bank
+    fn chunks(&self) -> Vec<&[u8]> {
+        match self {
+            Self::Sprite { quadrants, .. } => quadrants.iter().map(Vec::as_slice).collect(),
+            Self::Sample { data, .. } | Self::Wavetable { data, .. } | Self::Data(data) => vec![data],
+        }
+    }
+}
+
+/// One piece of an asset, and where it was packed
+struct Chunk {
+    /// `<name>.bin`, or `<name>.<quadrant>.bin` for sprites
+    file: String,
+    /// The static it's linked as
+    symbol: String,
+    len: usize,
+    bank: u8,
+}
+
+/// First-fit decreasing: the biggest chunks go first, each into the first
+/// bank it fits in. Returns a bank per chunk.
+fn pack(sizes: &[(String, usize)], first_bank: u8, last_bank: u8) -> Result<Vec<u8>, String> {
+    let banks: Vec<u8> = (first_bank..=last_bank.min(LAST_BANK)).filter(|&b| b != SAVE_BANK).collect();
+    let mut free = vec![BANK_SIZE; banks.len()];
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+    let mut placed = vec![0; sizes.len()];
+    for i in order {
+        let (name, len) = &sizes[i];
+        if *len > BANK_SIZE {
+            return Err(format!("{} is {} bytes, more than a ROM bank ({})", name, len, BANK_SIZE));
+        }
+        let slot = free.iter().position(|&f| f >= *len).ok_or_else(|| {
+            format!("{} doesn't fit in banks {}-{}", name, first_bank, last_bank)
+        })?;
+        free[slot] -= len;
+        placed[i] = banks[slot];
+    }
+    Ok(placed)
+}
+
+/// Write `contents` unless the file already has them, so cargo doesn't
+/// rebuild for nothing
+fn write_if_changed(path: &Path, contents: &[u8]) -> Result<(), String> {
+    if std::fs::read(path).is_ok_and(|old| old == contents) {
+        return Ok(());
+    }
+    std::fs::write(path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn slice(chunk: &Chunk) -> String {
+    format!("unsafe {{ BankedSlice::new({}, &{}) }}", chunk.bank, chunk.symbol)
+}
+
+/// `src/assets_gen.rs`
+fn generate(entries: &[AssetEntry], converted: &[Converted], chunks: &[Vec<Chunk>]) -> String {
+    let mut module = format!(
+        "// Generated by `gtrom build` from {}. Do not edit.\n\
+         #![allow(dead_code, unused_imports)]\n\
+         \n\
+         use gametank::assets::{{SampleAsset, SpriteAsset, WavetableAsset}};\n\
+         use gametank::banking::BankedSlice;\n",
+        MANIFEST_FILE,
+    );
+
+    for ((entry, asset), chunks) in entries.iter().zip(converted).zip(chunks) {
+        let name = const_name(&entry.name);
+        module.push('\n');
+        for chunk in chunks {
+            module.push_str(&format!(
+                "#[unsafe(link_section = \".rodata.bank{}\")]\n\
+                 static {}: [u8; {}] = *include_bytes!(\"../{}/{}\");\n",
+                chunk.bank, chunk.symbol, chunk.len, OUTPUT_DIR, chunk.file,
+            ));
+        }
+
+        module.push_str(&format!("/// {}\n", entry.path));
+        if entry.compress && matches!(asset, Converted::Data(_)) {
+            module.push_str("///\n/// Compressed: unpack it with `gametank::compress`.\n");
+        }
+        let value = match asset {
+            Converted::Sprite { width, height, .. } => format!(
+                "pub const {name}: SpriteAsset = SpriteAsset {{\n    \
+                     width: {width},\n    \
+                     height: {height},\n    \
+                     compressed: {compressed},\n    \
+                     quadrants: &[\n{}    ],\n\
+                 }};\n",
+                chunks.iter().map(|c| format!("        {},\n", slice(c))).collect::<String>(),
+                compressed = entry.compress,
+            ),
+            Converted::Sample { rate, .. } => format!(
+                "pub const {name}: SampleAsset = SampleAsset {{ rate: {rate}, data: {} }};\n",
+                slice(&chunks[0]),
+            ),
+            Converted::Wavetable { source_hz, .. } => format!(
+                "pub const {name}: WavetableAsset = WavetableAsset {{ source_hz: {source_hz}, data: {} }};\n",
+                slice(&chunks[0]),
+            ),
+            Converted::Data(_) => format!("pub const {name}: BankedSlice<u8> = {};\n", slice(&chunks[0])),
+        };
+        module.push_str(&value);
+    }
+    module
+}
+
+/// Convert and pack the assets in the ROM's `assets.toml`, and generate
+/// `src/assets_gen.rs`. Does nothing if there's no manifest.
+pub fn bundle_assets(rom_dir: &Path) -> Result<(), String> {
+    let Some(manifest) = Manifest::load(rom_dir)? else {
+        return Ok(());
+    };
+
+    let mut names: Vec<String> = manifest.assets.iter().map(|a| const_name(&a.name)).collect();
+    names.sort();
+    if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(format!("{}: more than one asset is named {}", MANIFEST_FILE, pair[0]));
+    }
+
+    let converted = manifest.assets.iter()
+        .map(|entry| Converted::load(entry, rom_dir).map_err(|e| format!("{}: {}", MANIFEST_FILE, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // pack every chunk at once, so the biggest are placed first
+    let mut sizes = Vec::new();
+    for (entry, asset) in manifest.assets.iter().zip(&converted) {
+        for (i, data) in asset.chunks().iter().enumerate() {
+            let label = match asset {
+                Converted::Sprite { .. } => format!("{} (quadrant {})", entry.name, i),
+                _ => entry.name.clone(),
+            };
+            sizes.push((label, data.len()));
+        }
+    }
+    let mut banks = pack(&sizes, manifest.first_bank, manifest.last_bank)?.into_iter();
+
+    let output_dir = rom_dir.join(OUTPUT_DIR);
+    std::fs::create_dir_all(&output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", output_dir.display(), e))?;
+
+    let mut chunks = Vec::new();
+    let mut files = Vec::new();
+    for (entry, asset) in manifest.assets.iter().zip(&converted) {
+        let name = const_name(&entry.name);
+        let is_sprite = matches!(asset, Converted::Sprite { .. });
+        let mut asset_chunks = Vec::new();
+        for (i, data) in asset.chunks().into_iter().enumerate() {
+            let (file, symbol) = if is_sprite {
+                (format!("{}.{}.bin", name.to_lowercase(), i), format!("__{}_{}", name, i))
+            } else {
+                (format!("{}.bin", name.to_lowercase()), format!("__{}", name))
+            };
+            write_if_changed(&output_dir.join(&file), data)?;
+            files.push(file.clone());
+            asset_chunks.push(Chunk { file, symbol, len: data.len(), bank: banks.next().unwrap_or_default() });
+        }
+        chunks.push(asset_chunks);
+    }
+
+    // drop data from assets that were removed or renamed
+    if let Ok(entries) = std::fs::read_dir(&output_dir) {
+        for entry in entries.flatten() {
+            if !files.iter().any(|f| entry.file_name() == f.as_str()) {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    let module = generate(&manifest.assets, &converted, &chunks);
+    write_if_changed(&rom_dir.join(GENERATED_FILE), module.as_bytes())?;
+
+    let used: std::collections::BTreeSet<u8> = chunks.iter().flatten().map(|c| c.bank).collect();
+    println!(
+        "Bundled {} asset(s) from {} into {} bank(s) -> {}",
+        manifest.assets.len(), MANIFEST_FILE, used.len(), GENERATED_FILE
+    );
+    Ok(())
+}
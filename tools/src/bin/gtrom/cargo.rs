@@ -5,106 +5,96 @@
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use crate::container::podman_exec;
-
-/// Get crate name from Cargo.toml in the given directory
-pub fn get_crate_name(dir: &Path) -> Result<String, String> {
-    let cargo_toml_path = dir.join("Cargo.toml");
-    let cargo_content = std::fs::read_to_string(&cargo_toml_path)
-        .map_err(|e| format!("Failed to read Cargo.toml: {}", e))?;
-    parse_crate_name(&cargo_content)
-}
+use gametank_project::config::{Config, Profile, ProfileConfig, Toolchain};
+use gametank_project::{find_rom_dir_from, find_roms_from, find_workspace_from, RomTarget};
+
+use crate::container::{container_exec, TARGET_MOUNT};
 
-/// Parse crate name from Cargo.toml content
-pub fn parse_crate_name(content: &str) -> Result<String, String> {
-    content.lines()
-        .find(|l| l.trim().starts_with("name"))
-        .and_then(|l| l.split('=').nth(1))
-        .map(|s| s.trim().trim_matches('"').to_string())
-        .ok_or_else(|| "Could not find crate name in Cargo.toml".to_string())
+fn current_dir() -> Result<PathBuf, String> {
+    std::env::current_dir().map_err(|e| format!("Failed to get current directory: {}", e))
 }
 
 /// Find the ROM directory (either rom/ subdirectory or current dir with Cargo.toml)
 /// Walks up the directory tree to find the project root
 pub fn find_rom_dir() -> Result<(PathBuf, PathBuf), String> {
-    let mut current_dir = std::env::current_dir()
-        .map_err(|e| format!("Failed to get current directory: {}", e))?;
-    
-    // Walk up the directory tree to find a rom/ dir or Cargo.toml
-    loop {
-        // Check if this directory has a rom/ subdirectory with a GameTank project
-        if current_dir.join("rom").exists() {
-            let rom_dir = current_dir.join("rom");
-            if is_gametank_project(&rom_dir) {
-                return Ok((current_dir, rom_dir));
-            }
-        }
-        
-        // Check if this directory itself is a GameTank ROM project
-        if is_gametank_project(&current_dir) {
-            return Ok((current_dir.clone(), current_dir));
-        }
-        
-        // Move up to parent directory
-        if let Some(parent) = current_dir.parent() {
-            current_dir = parent.to_path_buf();
-        } else {
-            // Reached filesystem root without finding project
-            return Err("Could not find ROM project (no rom/ dir or GameTank project found)".to_string());
-        }
-    }
+    find_rom_dir_from(&current_dir()?)
 }
 
-/// Check if a directory is a GameTank ROM project
-/// A GameTank project has Cargo.toml and either:
-/// - src/asm/ directory (unique to GameTank projects)
-/// - asset-macros/ directory 
-/// - sdk/ subdirectory (when sdk is a separate crate)
-/// - gametank-asset-macros or sdk dependency in Cargo.toml
-fn is_gametank_project(dir: &Path) -> bool {
-    if !dir.join("Cargo.toml").exists() {
-        return false;
-    }
-    
-    // Check for unique GameTank markers
-    if dir.join("src/asm").exists() {
-        return true;
+/// The nearest directory at or above the current one whose gtrom.toml has
+/// `[[rom]]` tables
+pub fn find_workspace() -> Result<Option<(PathBuf, Config)>, String> {
+    find_workspace_from(&current_dir()?)
+}
+
+/// Find the project root, its settings, and the ROMs to build, from the
+/// current directory (see [`find_roms_from`])
+pub fn find_roms(name: Option<&str>, all: bool) -> Result<(PathBuf, Config, Vec<RomTarget>), String> {
+    find_roms_from(&current_dir()?, name, all)
+}
+
+/// `--config` that adds `--remap-path-prefix` for each `(from, to)` to the
+/// ROM target's rustflags, so build paths don't end up in the ROM (through
+/// panic locations, say), along with the profile's `extra` flags. Arrays
+/// from `--config` are appended to the project's own rustflags rather than
+/// replacing them. Backslashes in Windows paths are escaped for TOML.
+fn rustflags_config(prefixes: &[(&str, &str)], extra: &[String]) -> String {
+    let flags: Vec<String> = prefixes.iter()
+        .map(|(from, to)| format!("\"--remap-path-prefix={}={}\"", from.replace('\\', "\\\\"), to))
+        .chain(extra.iter().map(|flag| format!("{:?}", flag)))
+        .collect();
+    format!("target.mos-unknown-none.rustflags=[{}]", flags.join(", "))
+}
+
+/// Cargo arguments selecting `profile` and applying its settings
+fn profile_args(profile: &Profile, settings: &ProfileConfig) -> Vec<String> {
+    let mut args = match profile.name() {
+        "debug" => vec![],
+        "release" => vec!["--release".to_string()],
+        name => vec!["--profile".to_string(), name.to_string()],
+    };
+    for config in settings.cargo_configs(profile) {
+        args.push("--config".to_string());
+        args.push(config);
     }
+    args
+}
+
+/// Run cargo build for the ROM (runs directly), into `target_dir` if it's
+/// shared rather than the ROM's own target/
+pub fn cargo_build(workdir: &str, toolchain: Toolchain, profile: &Profile, settings: &ProfileConfig, target_dir: Option<&Path>) -> Result<(), String> {
+    println!("Building ROM with cargo...");
     
-    if dir.join("asset-macros").exists() {
-        return true;
+    // the project, and the toolchain and registry under the home directory
+    let home = std::env::var("CARGO_HOME").ok()
+        .or_else(|| std::env::var("HOME").ok().map(|home| format!("{}/.cargo", home)));
+    let rustup = std::env::var("RUSTUP_HOME").ok()
+        .or_else(|| std::env::var("HOME").ok().map(|home| format!("{}/.rustup", home)));
+    let mut prefixes = vec![(workdir, ".")];
+    if let Some(home) = &home {
+        prefixes.push((home, "/cargo"));
     }
-    
-    // Check for sdk/ subdirectory (when sdk is a separate crate)
-    if dir.join("sdk").exists() && dir.join("sdk/Cargo.toml").exists() {
-        return true;
+    if let Some(rustup) = &rustup {
+        prefixes.push((rustup, "/rustup"));
     }
-    
-    // Check Cargo.toml for gametank dependencies
-    if let Ok(cargo_content) = std::fs::read_to_string(dir.join("Cargo.toml")) {
-        if cargo_content.contains("gametank-asset-macros") 
-            || cargo_content.contains("gametank-sdk")
-            || (cargo_content.contains("sdk") && cargo_content.contains("path = \"sdk\"")) {
-            return true;
-        }
+    // build scripts' output lives here, and it's different on each machine
+    let target_str = target_dir.map(|dir| dir.to_string_lossy().to_string());
+    if let Some(target) = &target_str {
+        prefixes.push((target, "/target"));
     }
-    
-    false
-}
+    let rustflags = rustflags_config(&prefixes, &settings.rustflags());
+    let profile_args = profile_args(profile, settings);
 
-/// Run cargo build for the ROM (runs directly)
-pub fn cargo_build(workdir: &str, release: bool) -> Result<(), String> {
-    println!("Building ROM with cargo...");
-    
-    let mut args = vec![
-        "+mos", "build",
-        "-Z", "build-std=core",
+    let mut args = toolchain.cargo_prefix().to_vec();
+    args.extend([
+        "build",
+        "-Z", "build-std=core,alloc",
         "--target", "mos-unknown-none",
-    ];
-    
-    if release {
-        args.push("--release");
+        "--config", &rustflags,
+    ]);
+    if let Some(target) = &target_str {
+        args.extend(["--target-dir", target]);
     }
+    args.extend(profile_args.iter().map(String::as_str));
 
     let status = Command::new("cargo")
         .current_dir(workdir)
@@ -120,21 +110,31 @@ pub fn cargo_build(workdir: &str, release: bool) -> Result<(), String> {
 }
 
 /// Run cargo build via container
-pub fn cargo_build_in_container(workdir: &Path, working_dir: &Path, release: bool) -> Result<(), String> {
+pub fn cargo_build_in_container(workdir: &Path, working_dir: &Path, config: &Config, profile: &Profile, settings: &ProfileConfig) -> Result<(), String> {
     println!("Building ROM with cargo...");
     
     let rel_workdir = workdir.strip_prefix(working_dir).unwrap_or(workdir);
     let workspace_dir = format!("/workspace/{}", rel_workdir.to_string_lossy());
 
+    // the toolchain lives at the same place in every container
+    let shared_target = config.build.target_dir.is_some();
+    let mut prefixes = vec![(workspace_dir.as_str(), ".")];
+    if shared_target {
+        prefixes.push((TARGET_MOUNT, "/target"));
+    }
+    let rustflags = rustflags_config(&prefixes, &settings.rustflags());
+    let profile_args = profile_args(profile, settings);
+
     let mut args = vec![
         "cargo", "+mos", "build",
-        "-Z", "build-std=core",
+        "-Z", "build-std=core,alloc",
         "--target", "mos-unknown-none",
+        "--config", &rustflags,
     ];
-    
-    if release {
-        args.push("--release");
+    if shared_target {
+        args.extend(["--target-dir", TARGET_MOUNT]);
     }
+    args.extend(profile_args.iter().map(String::as_str));
 
-    podman_exec(&workspace_dir, &args)
+    container_exec(config, &workspace_dir, &args)
 }
@@ -0,0 +1,113 @@
+//! Removing build outputs
+//!
+//! `gtrom clean` deletes what builds leave behind: assembled objects,
+//! converted assets and their caches, and the ROMs themselves, optionally
+//! along with the build container.
+
+use std::path::{Path, PathBuf};
+
+use gametank_project::config::{Config, Toolchain};
+use gametank_project::RomTarget;
+
+use crate::assets::{CACHE_FILE, OUTPUT_DIR as ASSETS_DIR, SHARED_DIR};
+use crate::bundle::OUTPUT_DIR as BUNDLE_DIR;
+use crate::container::select_runtime;
+
+/// What `gtrom clean` removes. With none of `assets`, `asm` and `roms`
+/// picked, it's all three.
+pub struct Clean {
+    pub assets: bool,
+    pub asm: bool,
+    pub roms: bool,
+    /// Each ROM's whole target directory, cargo's build included, and the ROMs
+    pub all: bool,
+    pub container: bool,
+}
+
+/// Delete a file or directory. Returns whether there was anything there.
+fn remove(path: &Path) -> Result<bool, String> {
+    let removed = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else if path.exists() {
+        std::fs::remove_file(path)
+    } else {
+        return Ok(false);
+    };
+    removed.map(|_| true).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+}
+
+/// The paths to remove for `clean`, in order
+fn clean_paths(working_dir: &Path, config: &Config, roms: &[RomTarget], clean: &Clean) -> Vec<PathBuf> {
+    let picked = clean.assets || clean.asm || clean.roms;
+    let assets = clean.assets || !picked;
+    let asm = clean.asm || !picked;
+    let rom_outputs = clean.roms || clean.all || !picked;
+
+    let mut paths = vec![];
+    for rom in roms {
+        if clean.all {
+            paths.push(rom.dir.join("target"));
+        } else {
+            if assets {
+                paths.extend([ASSETS_DIR, CACHE_FILE, BUNDLE_DIR].map(|dir| rom.dir.join(dir)));
+            }
+            if asm {
+                paths.push(rom.dir.join("target/asm"));
+            }
+        }
+        if rom_outputs {
+            paths.push(rom.output.clone());
+            paths.push(rom.output.with_extension("sym"));
+        }
+    }
+    if (assets || clean.all) && config.is_workspace() {
+        paths.push(working_dir.join(SHARED_DIR));
+    }
+    paths
+}
+
+/// Stop and remove the build container, with whichever runtime has it
+fn remove_container(config: &Config) -> Result<(), String> {
+    let name = config.container.name.as_str();
+    let toolchains = match config.toolchain {
+        toolchain if toolchain.is_container() => vec![toolchain],
+        _ => vec![Toolchain::Podman, Toolchain::Docker],
+    };
+    let runtimes: Vec<_> = toolchains.iter().filter_map(|&t| select_runtime(t)).collect();
+    if runtimes.is_empty() {
+        let names: Vec<&str> = toolchains.iter().map(|t| t.name()).collect();
+        return Err(format!("Can't remove the {} container: {} isn't installed", name, names.join(" or ")));
+    }
+
+    let mut found = false;
+    for runtime in runtimes {
+        if runtime.exists(name)? {
+            runtime.remove(name);
+            println!("Removed container {} ({})", name, runtime.command());
+            found = true;
+        }
+    }
+    if !found {
+        println!("No container named {}", name);
+    }
+    Ok(())
+}
+
+/// Remove the outputs `clean` picks for each ROM
+pub fn do_clean(working_dir: &Path, config: &Config, roms: &[RomTarget], clean: Clean) -> Result<(), String> {
+    let mut removed = 0;
+    for path in clean_paths(working_dir, config, roms, &clean) {
+        if remove(&path)? {
+            println!("Removed {}", path.strip_prefix(working_dir).unwrap_or(&path).display());
+            removed += 1;
+        }
+    }
+    if removed == 0 {
+        println!("Nothing to clean");
+    }
+
+    if clean.container {
+        remove_container(config)?;
+    }
+    Ok(())
+}
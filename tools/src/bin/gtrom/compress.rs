@@ -0,0 +1,89 @@
+//! Compression for `gametank::compress`
+//!
+//! A byte-aligned LZ with a 256-byte window, so the 6502 side stays small
+//! and quick. The format is described in the SDK module. Data is parsed
+//! optimally, working back from the end for the fewest bytes overall.
+
+/// Set in a token that copies from earlier output
+const MATCH_FLAG: u8 = 0x80;
+/// Match lengths a token can hold
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 0x7F + MIN_MATCH;
+/// Literal run lengths a token can hold
+const MAX_LITERALS: usize = 0x80;
+/// How far back a match can reach
+const WINDOW: usize = 256;
+
+/// Most data one header can describe
+pub const MAX_LEN: usize = u16::MAX as usize;
+
+/// How to encode the data from a position on
+#[derive(Clone, Copy)]
+enum Step {
+    Literals(usize),
+    Match { len: usize, distance: usize },
+}
+
+/// Longest match for `data[i..]` within the window, as (length, distance)
+fn longest_match(data: &[u8], i: usize) -> (usize, usize) {
+    let limit = (data.len() - i).min(MAX_MATCH);
+    let mut best = (0, 0);
+    for distance in 1..=i.min(WINDOW) {
+        let len = (0..limit).take_while(|&k| data[i + k] == data[i + k - distance]).count();
+        if len > best.0 {
+            best = (len, distance);
+            if len == limit {
+                break;
+            }
+        }
+    }
+    best
+}
+
+/// Compress `data`, which has to be at most [`MAX_LEN`] bytes
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() > MAX_LEN {
+        return Err(format!("{} bytes is too much to compress in one piece (at most {})", data.len(), MAX_LEN));
+    }
+
+    // cost[i]: fewest bytes to encode data[i..], and the step that gets it
+    let n = data.len();
+    let mut cost = vec![0usize; n + 1];
+    let mut steps = vec![Step::Literals(0); n];
+    for i in (0..n).rev() {
+        let mut best = (usize::MAX, Step::Literals(0));
+        for run in 1..=(n - i).min(MAX_LITERALS) {
+            let c = 1 + run + cost[i + run];
+            if c < best.0 {
+                best = (c, Step::Literals(run));
+            }
+        }
+        let (longest, distance) = longest_match(data, i);
+        for len in MIN_MATCH..=longest {
+            let c = 2 + cost[i + len];
+            if c < best.0 {
+                best = (c, Step::Match { len, distance });
+            }
+        }
+        cost[i] = best.0;
+        steps[i] = best.1;
+    }
+
+    let mut out = (n as u16).to_le_bytes().to_vec();
+    let mut i = 0;
+    while i < n {
+        match steps[i] {
+            Step::Literals(run) => {
+                out.push((run - 1) as u8);
+                out.extend_from_slice(&data[i..i + run]);
+                i += run;
+            }
+            Step::Match { len, distance } => {
+                out.push(MATCH_FLAG | (len - MIN_MATCH) as u8);
+                out.push((distance - 1) as u8);
+                i += len;
+            }
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,82 @@
+//! `gtrom configure`
+//!
+//! The settings themselves are in [`gametank_project::config`].
+
+use std::path::Path;
+
+use gametank_project::config::{record_toolchain, Config, Profile, Toolchain, CONFIG_FILE, DEFAULT_CONFIG};
+
+use crate::toolchain;
+
+/// Print the effective configuration, or write a starter gtrom.toml
+pub fn do_configure(project_dir: &Path, init: bool) -> Result<(), String> {
+    let path = project_dir.join(CONFIG_FILE);
+
+    if init {
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()));
+        }
+        std::fs::write(&path, DEFAULT_CONFIG)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        println!("Created {}", path.display());
+
+        match toolchain::detect() {
+            Some(found) => {
+                record_toolchain(project_dir, found)?;
+                println!("Using the {} toolchain", found.name());
+            }
+            None => println!("No toolchain found yet; the next build will look again"),
+        }
+        return Ok(());
+    }
+
+    let config = Config::load(project_dir)?;
+    if path.exists() {
+        println!("Configuration from {}", path.display());
+    } else {
+        println!("No {} found, using defaults (create one with `gtrom configure --init`)", CONFIG_FILE);
+    }
+
+    let toolchain_status = match config.toolchain {
+        Toolchain::Auto => match toolchain::detect() {
+            Some(found) => format!("(would use {})", found.name()),
+            None => "(nothing found)".to_string(),
+        },
+        t if toolchain::is_available(t) => String::new(),
+        _ => "(not found)".to_string(),
+    };
+    println!("  toolchain:       {} {}", config.toolchain.name(), toolchain_status);
+    println!("  container image: {}", config.container.image);
+    println!("  container name:  {}", config.container.name);
+    println!("  build profile:   {}", config.build.profile.name());
+    println!("  output:          {}", config.build.output.as_deref().unwrap_or("<crate name>.gtr"));
+    println!("  asset dirs:      {}", config.assets.dirs.join(", "));
+    if !config.asm.args().is_empty() {
+        println!("  asm flags:       {}", config.asm.args().join(" "));
+    }
+    for rom in &config.roms {
+        let output = rom.output.clone().unwrap_or_else(|| format!("{}.gtr", rom.name));
+        println!("  rom:             {} ({} -> {})", rom.name, rom.path, output);
+    }
+    for name in config.profiles.keys() {
+        let profile = Profile::from(name.clone());
+        let settings = match config.profile_config(&profile) {
+            Ok(settings) => [settings.cargo_configs(&profile), settings.rustflags()].concat().join(" "),
+            Err(e) => e,
+        };
+        println!("  profile:         {} ({})", name, settings);
+    }
+    for (stage, commands) in [("pre-build", &config.hooks.pre_build), ("post-link", &config.hooks.post_link), ("post-rom", &config.hooks.post_rom)] {
+        for command in commands.commands() {
+            println!("  {:<17}{}", format!("{} hook:", stage), command);
+        }
+    }
+    if !config.hooks.pre_build.is_empty() || !config.hooks.post_link.is_empty() || !config.hooks.post_rom.is_empty() {
+        println!("  hooks run:       {}", if config.hooks.in_container { "in the container" } else { "on the host" });
+    }
+    println!(
+        "  size limits:     zp {}%, RAM {}%, fixed {}%, banks {}%",
+        config.size.zp, config.size.ram, config.size.fixed, config.size.bank
+    );
+    Ok(())
+}
@@ -1,38 +1,163 @@
 //! Container orchestration for builds
 //! 
 //! Manages the podman/docker container lifecycle for llvm-mos toolchain access.
+//! Which runtime to use comes from gtrom.toml, see [`crate::toolchain`].
 
 use std::path::Path;
 use std::process::{Command, Stdio};
 
-/// Container runtime to use
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum ContainerRuntime {
-    Podman,
-    Docker,
+use gametank_project::config::{Config, Toolchain};
+
+use crate::toolchain::is_available;
+
+/// Where `[build] target-dir` is mounted in the container
+pub const TARGET_MOUNT: &str = "/cargo-target";
+
+/// A container engine that can run the build image. Podman and Docker take
+/// almost the same commands; the differences live in the implementations.
+pub trait ContainerRuntime {
+    /// The executable to run
+    fn command(&self) -> &'static str;
+
+    /// The `-v` argument mounting `host` at `path`
+    fn volume(&self, host: &Path, path: &str) -> String;
+
+    /// Start `image` detached as `name` with `volumes` mounted, replacing
+    /// any old container with that name
+    fn run(&self, name: &str, image: &str, volumes: &[String]) -> Result<bool, String>;
+
+    /// Whether a container called `name` is running
+    fn is_running(&self, name: &str) -> Result<bool, String> {
+        let output = Command::new(self.command())
+            .args(["ps", "--filter", &format!("name={}", name), "--filter", "status=running", "--format", "{{.Names}}"])
+            .output()
+            .map_err(|e| format!("Failed to check container status: {}", e))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines().any(|l| l.trim() == name))
+    }
+
+    /// Whether a container called `name` exists, running or not
+    fn exists(&self, name: &str) -> Result<bool, String> {
+        let output = Command::new(self.command())
+            .args(["ps", "-a", "--filter", &format!("name={}", name), "--format", "{{.Names}}"])
+            .output()
+            .map_err(|e| format!("Failed to check container status: {}", e))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines().any(|l| l.trim() == name))
+    }
+
+    /// Stop and delete `name`, if it exists
+    fn remove(&self, name: &str) {
+        // the runtime complains if there's nothing to remove
+        let _ = Command::new(self.command())
+            .args(["rm", "-f", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+
+    /// Whether `image` has been pulled or built
+    fn has_image(&self, image: &str) -> bool {
+        Command::new(self.command())
+            .args(["image", "inspect", image])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    /// Pull `image`, showing the runtime's progress
+    fn pull(&self, image: &str) -> Result<bool, String> {
+        let status = Command::new(self.command())
+            .args(["pull", image])
+            .status()
+            .map_err(|e| format!("Failed to pull {}: {}", image, e))?;
+        Ok(status.success())
+    }
+
+    /// Build `containerfile` (in the empty directory `dir`) and tag it `image`
+    fn build(&self, image: &str, containerfile: &Path, dir: &Path) -> Result<bool, String> {
+        let status = Command::new(self.command())
+            .args(["build", "-t", image, "-f"])
+            .arg(containerfile)
+            .arg(dir)
+            .status()
+            .map_err(|e| format!("Failed to build {}: {}", image, e))?;
+        Ok(status.success())
+    }
+
+    /// Run `args` inside `name`, from `workdir`
+    fn exec(&self, name: &str, workdir: &str, args: &[&str]) -> Result<bool, String> {
+        let status = Command::new(self.command())
+            .args(["exec", "-t", "-w", workdir, name])
+            .args(args)
+            .status()
+            .map_err(|e| format!("Failed to exec in container: {}", e))?;
+        Ok(status.success())
+    }
 }
 
-impl ContainerRuntime {
-    /// Detect which container runtime is available
-    pub fn detect() -> Option<Self> {
-        // Prefer podman over docker
-        if Command::new("podman").arg("--version").output().is_ok() {
-            return Some(Self::Podman);
-        }
-        if Command::new("docker").arg("--version").output().is_ok() {
-            return Some(Self::Docker);
-        }
-        None
+pub struct Podman;
+
+impl ContainerRuntime for Podman {
+    fn command(&self) -> &'static str {
+        "podman"
     }
-    
-    fn as_str(&self) -> &'static str {
-        match self {
-            Self::Podman => "podman",
-            Self::Docker => "docker",
-        }
+
+    fn volume(&self, host: &Path, path: &str) -> String {
+        // :z relabels the mount so SELinux lets the container use it
+        format!("{}:{}:z", host.display(), path)
+    }
+
+    fn run(&self, name: &str, image: &str, volumes: &[String]) -> Result<bool, String> {
+        let status = Command::new(self.command())
+            .args(["run", "-d", "--replace", "--name", name])
+            .args(volumes.iter().flat_map(|volume| ["-v", volume]))
+            .args([image, "sleep", "infinity"])
+            .status()
+            .map_err(|e| format!("Failed to start container: {}", e))?;
+        Ok(status.success())
     }
 }
 
+pub struct Docker;
+
+impl ContainerRuntime for Docker {
+    fn command(&self) -> &'static str {
+        "docker"
+    }
+
+    fn volume(&self, host: &Path, path: &str) -> String {
+        // Docker has no SELinux labels, and Docker Desktop rejects :z on
+        // Windows paths
+        format!("{}:{}", host.display(), path)
+    }
+
+    fn run(&self, name: &str, image: &str, volumes: &[String]) -> Result<bool, String> {
+        // no --replace, so clear out the old container first
+        self.remove(name);
+        let status = Command::new(self.command())
+            .args(["run", "-d", "--name", name])
+            .args(volumes.iter().flat_map(|volume| ["-v", volume]))
+            .args([image, "sleep", "infinity"])
+            .status()
+            .map_err(|e| format!("Failed to start container: {}", e))?;
+        Ok(status.success())
+    }
+}
+
+/// The runtime for a resolved toolchain, if it's a container one and installed
+pub fn select_runtime(toolchain: Toolchain) -> Option<&'static dyn ContainerRuntime> {
+    let runtime: &'static dyn ContainerRuntime = match toolchain {
+        Toolchain::Podman => &Podman,
+        Toolchain::Docker => &Docker,
+        _ => return None,
+    };
+    is_available(toolchain).then_some(runtime)
+}
+
 /// Check if we're running inside a container
 pub fn is_in_container() -> bool {
     Path::new("/.dockerenv").exists()
@@ -47,119 +172,86 @@ pub fn get_mount_root() -> Result<std::path::PathBuf, String> {
         .map_err(|e| format!("Failed to get current directory: {}", e))
 }
 
-/// Ensure the build container is running with the correct mount point
-pub fn ensure_container() -> Result<(std::path::PathBuf, ContainerRuntime), String> {
-    let runtime = ContainerRuntime::detect()
-        .ok_or_else(|| "No container runtime found. Please install podman or docker.".to_string())?;
+/// Whether builds should go through the container. `config.toolchain` must
+/// already be resolved (see [`crate::toolchain::resolve`]).
+pub fn uses_container(config: &Config) -> bool {
+    config.toolchain.is_container() && !is_in_container()
+}
+
+/// Whether the running container `name` sees `host` at `path`
+fn is_mounted(runtime: &dyn ContainerRuntime, name: &str, host: &Path, path: &str) -> bool {
+    // Write a uniquely-named temp file, check if container can see it, then delete it
+    let marker_id = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let marker_name = format!(".gametank-check-{}", marker_id);
+    let marker_path = host.join(&marker_name);
+    let container_marker = format!("{}/{}", path, marker_name);
+
+    // Write the marker file
+    let _ = std::fs::write(&marker_path, "");
+
+    // Check if container can see it
+    let visible = Command::new(runtime.command())
+        .args(["exec", name, "test", "-f", &container_marker])
+        .status();
+
+    // Clean up marker file
+    let _ = std::fs::remove_file(&marker_path);
+
+    visible.map(|s| s.success()).unwrap_or(false)
+}
+
+/// Ensure the build container is running with the correct mount points:
+/// the workspace, and `[build] target-dir` if it's set, which is relative
+/// to `project_dir`
+pub fn ensure_container(config: &Config, project_dir: &Path) -> Result<(std::path::PathBuf, &'static dyn ContainerRuntime), String> {
+    let runtime = select_runtime(config.toolchain)
+        .ok_or_else(|| format!("{} is set in gtrom.toml but isn't installed", config.toolchain.name()))?;
     
     let mount_root = get_mount_root()?;
-    let cmd = runtime.as_str();
+    let name = config.container.name.as_str();
+
+    // a missing host directory would be created by the runtime, owned by root
+    let shared_target = config.target_dir(project_dir);
+    if let Some(dir) = &shared_target {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
     
-    // Check if container is already running
-    let output = Command::new(cmd)
-        .args(["ps", "--filter", "name=gametank", "--filter", "status=running", "--format", "{{.Names}}"])
-        .output()
-        .map_err(|e| format!("Failed to check container status: {}", e))?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    if stdout.contains("gametank") {
+    if runtime.is_running(name)? {
         // Container is running - verify it's mounted to this workspace
-        // Write a uniquely-named temp file, check if container can see it, then delete it
-        let marker_id = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_nanos())
-            .unwrap_or(0);
-        let marker_name = format!(".gametank-check-{}", marker_id);
-        let marker_path = mount_root.join(&marker_name);
-        let container_marker = format!("/workspace/{}", marker_name);
-        
-        // Write the marker file
-        let _ = std::fs::write(&marker_path, "");
-        
-        // Check if container can see it
-        let test_output = Command::new(cmd)
-            .args(["exec", "gametank", "test", "-f", &container_marker])
-            .status();
-        
-        // Clean up marker file
-        let _ = std::fs::remove_file(&marker_path);
-        
-        if test_output.map(|s| s.success()).unwrap_or(false) {
+        let mounted = is_mounted(runtime, name, &mount_root, "/workspace")
+            && shared_target.as_deref().is_none_or(|dir| is_mounted(runtime, name, dir, TARGET_MOUNT));
+        if mounted {
             return Ok((mount_root, runtime));
         }
         
         // Container can't see our workspace - recreate
         println!("Workspace changed, recreating container...");
-        let _ = Command::new(cmd)
-            .args(["rm", "-f", "gametank"])
-            .status();
+        runtime.remove(name);
     }
 
-    // Start the container
-    println!("Starting build container with {}...", cmd);
-
-    // Docker has no "--replace" equivalent so we need to stop and delete the old container
-    // Piping stdout to null here since docker complains if the container doesn't exist
-    if runtime == ContainerRuntime::Docker {
-        let _ = Command::new(cmd)
-            .args(["rm", "-f", "gametank"])
-            .stdout(Stdio::null())
-            .status();
-    }
-    
-    // Build volume mount arg - podman uses :z for SELinux, docker doesn't need it
-    let volume_arg = match runtime {
-        ContainerRuntime::Podman => format!("{}:/workspace:z", mount_root.display()),
-        ContainerRuntime::Docker => format!("{}:/workspace", mount_root.display()),
-    };
-
-    let mut start_args = vec![
-        "run", "-d", 
-        "--name", "gametank", 
-        "-v", &volume_arg
-    ];
-    
-    // Include --replace for the Podman runner
-    if runtime == ContainerRuntime::Podman {
-        start_args.push("--replace");
+    println!("Starting build container with {}...", runtime.command());
+    let mut volumes = vec![runtime.volume(&mount_root, "/workspace")];
+    if let Some(dir) = &shared_target {
+        volumes.push(runtime.volume(dir, TARGET_MOUNT));
     }
-
-    start_args.extend([
-        "docker.io/dwbrite/rust-mos:gte", 
-        "sleep", "infinity"
-    ]);
-    
-    let status = Command::new(cmd)
-        .args(start_args)
-        .status()
-        .map_err(|e| format!("Failed to start container: {}", e))?;
-
-    if status.success() {
+    if runtime.run(name, &config.container.image, &volumes)? {
         Ok((mount_root, runtime))
     } else {
         Err("Failed to start build container".to_string())
     }
 }
 
-/// Execute a command inside the container
-pub fn container_exec(runtime: ContainerRuntime, workdir: &str, args: &[&str]) -> Result<(), String> {
-    let cmd = runtime.as_str();
-    let status = Command::new(cmd)
-        .args(["exec", "-t", "-w", workdir, "gametank"])
-        .args(args)
-        .status()
-        .map_err(|e| format!("Failed to exec in container: {}", e))?;
-
-    if status.success() {
+/// Execute a command inside the build container
+pub fn container_exec(config: &Config, workdir: &str, args: &[&str]) -> Result<(), String> {
+    let runtime = select_runtime(config.toolchain)
+        .ok_or_else(|| "No container runtime found".to_string())?;
+    if runtime.exec(&config.container.name, workdir, args)? {
         Ok(())
     } else {
         Err(format!("Command failed: {:?}", args))
     }
 }
-
-/// Execute a command inside the container (convenience wrapper that detects runtime)
-pub fn podman_exec(workdir: &str, args: &[&str]) -> Result<(), String> {
-    let runtime = ContainerRuntime::detect()
-        .ok_or_else(|| "No container runtime found".to_string())?;
-    container_exec(runtime, workdir, args)
-}
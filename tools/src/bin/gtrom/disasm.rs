@@ -0,0 +1,213 @@
+//! ROM disassembly
+//!
+//! Lists a ROM's code as 65C02 assembly, for checking what the compiler made
+//! of a function or diffing two builds. Addresses are linked addresses, as
+//! in the `.sym` file and the debugger: banked code at `$8000 + bank *
+//! $10000`, the fixed bank at `$C000-$FFFF`. With the `.sym` file `gtrom
+//! build` writes next to the ROM (or an ELF's own symbols), functions are
+//! labelled, calls and jumps named and source lines noted.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+
+use gte_core::disasm::decode;
+use gte_core::elf_rom::{self, is_elf};
+use gte_core::rom_header::RomHeader;
+use gte_core::symbols::SymbolMap;
+
+const BANK_SIZE: usize = 0x4000;
+
+/// Where the vectors start, listed without symbols up to here
+const VECTORS: u32 = 0xFFFA;
+
+/// A ROM image to read linked addresses out of
+struct Image {
+    bytes: Vec<u8>,
+}
+
+impl Image {
+    /// The offset into the image of a linked address
+    fn offset(&self, address: u32) -> Option<usize> {
+        let len = self.bytes.len();
+        if len <= 0x8000 {
+            // unbanked ROMs end at $FFFF
+            let base = 0x10000 - len as u32;
+            return (base..0x10000).contains(&address).then(|| (address - base) as usize);
+        }
+        let in_bank = (address & 0x3FFF) as usize;
+        match address {
+            0xC000..=0xFFFF => Some(len - BANK_SIZE + in_bank),
+            _ if (0x8000..0xC000).contains(&(address & 0xFFFF)) => {
+                let offset = (address >> 16) as usize * BANK_SIZE + in_bank;
+                (offset < len - BANK_SIZE).then_some(offset)
+            }
+            _ => None,
+        }
+    }
+
+    /// The end of the bank `address` is in, as a linked address
+    fn bank_end(address: u32) -> u32 {
+        match address & 0xFFFF {
+            0xC000.. => 0x10000,
+            _ => (address & !0x3FFF) + BANK_SIZE as u32,
+        }
+    }
+}
+
+/// `$C123`, `0xC123` or `C123`
+fn parse_address(text: &str) -> Result<u32, String> {
+    let hex = text.strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid address {}", text))
+}
+
+/// The ROM image in `path` (a `.gtr`, raw image or ELF) and its symbols
+fn load(path: &str) -> Result<(Image, SymbolMap), String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let sym_path = Path::new(path).with_extension("sym");
+
+    let (rom, symbols) = if is_elf(&bytes) {
+        elf_rom::load(&bytes)?
+    } else {
+        let (_, rom) = RomHeader::split(&bytes);
+        (rom.to_vec(), SymbolMap::default())
+    };
+    // the .sym has source lines too, so it wins over an ELF's names
+    let symbols = match std::fs::read_to_string(&sym_path) {
+        Ok(text) => SymbolMap::parse(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", sym_path.display(), e))?,
+        Err(_) => symbols,
+    };
+    Ok((Image { bytes: rom }, symbols))
+}
+
+/// The linked address of a jump target from code at `from`. Banked targets
+/// are taken to be in the same bank; from the fixed bank there's no telling.
+fn linked_target(from: u32, target: u16) -> Option<u32> {
+    match target {
+        0x8000..=0xBFFF if from & 0xFFFF >= 0xC000 => None,
+        0x8000..=0xBFFF => Some((from & !0xFFFF) | target as u32),
+        _ => Some(target as u32),
+    }
+}
+
+/// Disassemble `start..end` onto `out`
+fn list(out: &mut String, image: &Image, symbols: &SymbolMap, start: u32, end: u32) -> Result<(), String> {
+    let first = image.offset(start)
+        .ok_or_else(|| format!("${:06X} isn't in the ROM", start))?;
+    let last = image.offset(end - 1)
+        .filter(|&last| last >= first && end - start == (last - first + 1) as u32)
+        .ok_or_else(|| format!("${:06X}-${:06X} isn't in one bank of the ROM", start, end - 1))?;
+    let bytes = &image.bytes[first..=last];
+
+    let mut source = None;
+    let mut at = 0;
+    while at < bytes.len() {
+        let address = start + at as u32;
+        if let Some((function, 0)) = symbols.function_at(address) {
+            let _ = writeln!(out, "{}:", function.name);
+        }
+        if let Some(line) = symbols.line_at(address) {
+            if source != Some((line.file, line.line)) {
+                let _ = writeln!(out, "    ; {}:{}", line.file, line.line);
+                source = Some((line.file, line.line));
+            }
+        }
+
+        let Some(instruction) = decode(&bytes[at..]) else {
+            // runs off the end: the rest is data
+            for &byte in &bytes[at..] {
+                let _ = writeln!(out, "  {:06X}  {:02X}        .byte ${:02X}", start + at as u32, byte, byte);
+                at += 1;
+            }
+            break;
+        };
+
+        let size = instruction.size();
+        let hex: Vec<String> = bytes[at..at + size].iter().map(|b| format!("{:02X}", b)).collect();
+        let pc = address as u16;
+        let mut line = format!("  {:06X}  {:<8}  {}", address, hex.join(" "), instruction.format(pc));
+        let current = symbols.function_at(address).map(|(f, _)| f.address);
+        if let Some(target) = instruction.target(pc).and_then(|t| linked_target(address, t)) {
+            // branches within the function are clear enough from the address
+            if symbols.function_at(target).map(|(f, _)| f.address) != current {
+                if let Some(name) = symbols.describe(target) {
+                    line = format!("{:<36}; {}", line, name);
+                }
+            }
+        }
+        let _ = writeln!(out, "{}", line.trim_end());
+        if instruction.ends_flow() {
+            out.push('\n');
+        }
+        at += size;
+    }
+    Ok(())
+}
+
+/// Disassemble a function, an address range, or with neither every function
+/// in the ROM (without symbols, the fixed bank), to `output` or stdout
+pub fn do_disasm(
+    path: &str,
+    function: Option<&str>,
+    start: Option<&str>,
+    end: Option<&str>,
+    output: Option<&str>,
+) -> Result<(), String> {
+    let (image, symbols) = load(path)?;
+
+    let ranges: Vec<(u32, u32)> = match (function, start) {
+        (Some(name), _) => {
+            let function = symbols.find(name).ok_or_else(|| match symbols.is_empty() {
+                true => format!("No symbols for {}: build it with gtrom to get a .sym", path),
+                false => format!("No function named {}", name),
+            })?;
+            vec![(function.address, function.address + function.size)]
+        }
+        (None, Some(start)) => {
+            let start = parse_address(start)?;
+            let end = match end {
+                Some(end) => parse_address(end)?,
+                None if (0xC000..VECTORS).contains(&start) => VECTORS,
+                None => Image::bank_end(start),
+            };
+            if end <= start {
+                return Err(format!("--end ${:06X} is before --start ${:06X}", end, start));
+            }
+            vec![(start, end)]
+        }
+        (None, None) if end.is_some() => return Err("--end needs a --start".to_string()),
+        (None, None) if symbols.is_empty() => {
+            let fixed = image.bytes.len().min(BANK_SIZE) as u32;
+            vec![(0x10000 - fixed, VECTORS)]
+        }
+        (None, None) => symbols.functions().iter()
+            .filter(|f| f.size > 0 && image.offset(f.address).is_some())
+            .map(|f| (f.address, f.address + f.size))
+            .collect(),
+    };
+
+    let mut out = String::new();
+    for (i, &(start, end)) in ranges.iter().enumerate() {
+        if i > 0 && !out.ends_with("\n\n") {
+            out.push('\n');
+        }
+        list(&mut out, &image, &symbols, start, end)?;
+    }
+
+    match output {
+        Some(output) => {
+            std::fs::write(output, &out)
+                .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+            println!("Wrote {}", output);
+        }
+        // ignore a closed pipe, for `| head`
+        None => {
+            let _ = std::io::stdout().write_all(out.as_bytes());
+        }
+    }
+    Ok(())
+}
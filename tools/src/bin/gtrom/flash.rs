@@ -0,0 +1,69 @@
+//! Cartridge flashing
+//!
+//! The programmer protocol lives in [`gametank_sdk::flash`], shared with
+//! gtgo. This draws its progress on the terminal.
+
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+
+use gametank_sdk::flash::{detect_port, flash, read_gtr, Progress};
+
+fn progress_bar(label: &str, done: usize, total: usize, bank: usize) {
+    const WIDTH: usize = 32;
+    let filled = done * WIDTH / total.max(1);
+    print!(
+        "\r  {:<9}[{}{}] {:>3}/{} (bank {:02X})",
+        label,
+        "#".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        done,
+        total,
+        bank,
+    );
+    let _ = std::io::stdout().flush();
+}
+
+/// Write a .gtr (with or without header) to a cartridge
+pub fn do_flash(path: &str, port: Option<&str>, verify: bool) -> Result<(), String> {
+    let (header, rom) = read_gtr(Path::new(path))?;
+    if let Some(header) = header {
+        println!("Flashing \"{}\"{}{}", header.title,
+            if header.rom_version.is_empty() { String::new() } else { format!(" {}", header.rom_version) },
+            if header.author.is_empty() { String::new() } else { format!(" by {}", header.author) });
+    }
+
+    let port_name = match port {
+        Some(p) => p.to_string(),
+        None => detect_port()?,
+    };
+    println!("Using programmer on {}", port_name);
+
+    let mut started = Instant::now();
+    flash(&rom, &port_name, verify, |progress| match progress {
+        Progress::Erasing => println!("Erasing cartridge..."),
+        Progress::Writing { done, total, bank } => {
+            if done == 0 {
+                println!("Writing {} bank(s)", total);
+                started = Instant::now();
+            }
+            progress_bar("write", done, total, bank);
+            if done == total {
+                println!(" {:.1}s", started.elapsed().as_secs_f32());
+            }
+        }
+        Progress::Verifying { done, total, bank } => {
+            progress_bar("verify", done, total, bank);
+            if done == total {
+                println!();
+            }
+        }
+    })?;
+
+    if verify {
+        println!("Flash complete, all banks verified");
+    } else {
+        println!("Skipping verification");
+    }
+    Ok(())
+}
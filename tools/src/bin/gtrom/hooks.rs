@@ -0,0 +1,129 @@
+//! Build hooks
+//!
+//! Runs the commands from `[hooks]` in gtrom.toml at their points in the
+//! build, from the ROM crate's directory, with the build's paths in
+//! `GTROM_*` variables. On the host they go through `sh -c` (`cmd /C` on
+//! Windows); with `in-container`, builds through podman or docker run them
+//! with `sh -c` in the build container, with the paths as it sees them.
+
+use std::path::Path;
+use std::process::Command;
+
+use gametank_project::config::{Config, HookCommands};
+
+use crate::container::{container_exec, TARGET_MOUNT};
+
+/// Where in the build a hook runs
+#[derive(Debug, Clone, Copy)]
+pub enum Stage {
+    PreBuild,
+    PostLink,
+    PostRom,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::PreBuild => "pre-build",
+            Stage::PostLink => "post-link",
+            Stage::PostRom => "post-rom",
+        }
+    }
+
+    fn commands(self, config: &Config) -> &HookCommands {
+        match self {
+            Stage::PreBuild => &config.hooks.pre_build,
+            Stage::PostLink => &config.hooks.post_link,
+            Stage::PostRom => &config.hooks.post_rom,
+        }
+    }
+}
+
+/// The paths a hook is told about
+pub struct HookPaths<'a> {
+    pub project_dir: &'a Path,
+    pub rom_dir: &'a Path,
+    /// cargo's output directory for the profile
+    pub target_dir: &'a Path,
+    pub elf: &'a Path,
+    pub rom: &'a Path,
+    pub rom_name: &'a str,
+    pub profile: &'a str,
+}
+
+impl HookPaths<'_> {
+    /// `GTROM_*` variables, with each path passed through `path`
+    fn vars(&self, path: impl Fn(&Path) -> String) -> Vec<(&'static str, String)> {
+        vec![
+            ("GTROM_PROJECT_DIR", path(self.project_dir)),
+            ("GTROM_ROM_DIR", path(self.rom_dir)),
+            ("GTROM_TARGET_DIR", path(self.target_dir)),
+            ("GTROM_ELF", path(self.elf)),
+            ("GTROM_ROM", path(self.rom)),
+            ("GTROM_ROM_NAME", self.rom_name.to_string()),
+            ("GTROM_PROFILE", self.profile.to_string()),
+        ]
+    }
+}
+
+fn shell() -> (&'static str, &'static str) {
+    if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") }
+}
+
+/// Run `stage`'s hooks, if it has any. `mount_root` is the container's
+/// /workspace mount, or `None` when building directly.
+pub fn run_hooks(stage: Stage, config: &Config, paths: &HookPaths, mount_root: Option<&Path>) -> Result<(), String> {
+    let commands = stage.commands(config);
+    if commands.is_empty() {
+        return Ok(());
+    }
+
+    let in_container = mount_root.filter(|_| config.hooks.in_container);
+    for command in commands.commands() {
+        println!("Running {} hook: {}", stage.name(), command);
+        match in_container {
+            None => run_on_host(stage, command, paths)?,
+            Some(mount_root) => run_in_container(stage, command, paths, mount_root, config)?,
+        }
+    }
+    Ok(())
+}
+
+fn run_on_host(stage: Stage, command: &str, paths: &HookPaths) -> Result<(), String> {
+    let (shell, flag) = shell();
+    let status = Command::new(shell)
+        .arg(flag)
+        .arg(command)
+        .current_dir(paths.rom_dir)
+        .envs(paths.vars(|p| p.to_string_lossy().to_string()))
+        .env("GTROM_HOOK", stage.name())
+        .status()
+        .map_err(|e| format!("Failed to run {} hook: {}", stage.name(), e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} hook failed: {}", stage.name(), command))
+    }
+}
+
+fn run_in_container(stage: Stage, command: &str, paths: &HookPaths, mount_root: &Path, config: &Config) -> Result<(), String> {
+    let shared_target = config.target_dir(paths.project_dir);
+    let in_workspace = |p: &Path| {
+        let (mount, rel) = match shared_target.as_deref().and_then(|dir| p.strip_prefix(dir).ok()) {
+            Some(rel) => (TARGET_MOUNT, rel),
+            None => ("/workspace", p.strip_prefix(mount_root).unwrap_or(p)),
+        };
+        format!("{}/{}", mount, rel.to_string_lossy().replace('\\', "/"))
+    };
+
+    // container_exec only takes a command line, so the variables go
+    // through `env`
+    let mut args = vec!["env".to_string(), format!("GTROM_HOOK={}", stage.name())];
+    args.extend(paths.vars(in_workspace).into_iter().map(|(name, value)| format!("{}={}", name, value)));
+    args.extend(["sh".to_string(), "-c".to_string(), command.to_string()]);
+
+    let args_ref: Vec<&str> = args.iter().map(String::as_str).collect();
+    container_exec(config, &in_workspace(paths.rom_dir), &args_ref)
+        .map_err(|_| format!("{} hook failed: {}", stage.name(), command))
+}
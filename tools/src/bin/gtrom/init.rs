@@ -1,9 +1,16 @@
 //! Project initialization
 //!
 //! Handles creating new GameTank projects from the embedded SDK template.
+//! The tarball holds the full demo project; other templates drop some of its
+//! files and lay their own (from `templates/`) over the top.
+//!
+//! Every project also gets the asset directories `gtrom build` converts
+//! from, a `.gitignore` for what the build leaves behind, and optionally a
+//! git repository.
 
 use std::io::Cursor;
 use std::path::Path;
+use std::process::Command;
 
 use flate2::read::GzDecoder;
 use tar::Archive;
@@ -11,8 +18,84 @@ use tar::Archive;
 // Embed the SDK template tarball at compile time
 static SDK_TEMPLATE: &[u8] = include_bytes!("../sdk-template.tar.gz");
 
+/// Placeholder replaced with the project's crate name in template files
+const CRATE_NAME_VAR: &str = "{{crate_name}}";
+
+/// Created in every project, so there's an obvious place for each kind of asset
+const ASSET_DIRS: &[&str] = &["assets/sprites", "assets/music", "assets/maps"];
+
+/// Build output (which includes converted assets and the asset cache) and ROMs
+const GITIGNORE: &str = "\
+# Build output, converted assets (target/assets) and the asset cache
+/target/
+
+# Built ROMs and their symbol files
+*.gtr
+*.sym
+";
+
+/// Starting point for a new project
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Template {
+    /// Empty game loop that draws a moving square
+    Minimal,
+    /// The full SDK demo: bouncing balls, banked assets and music
+    Demo,
+    /// Music playback with per-voice volume meters
+    AudioDemo,
+    /// Rust game loop calling into hand-written assembly
+    MixedAsm,
+}
+
+impl Template {
+    pub fn name(self) -> &'static str {
+        match self {
+            Template::Minimal => "minimal",
+            Template::Demo => "demo",
+            Template::AudioDemo => "audio-demo",
+            Template::MixedAsm => "mixed-asm",
+        }
+    }
+
+    /// Files and directories from the SDK tarball this template leaves out
+    fn skipped(self) -> &'static [&'static str] {
+        match self {
+            Template::Minimal | Template::MixedAsm => &["src/audio_demo.rs", "src/ball.rs", "src/asm/example.asm", "assets"],
+            Template::AudioDemo => &["src/ball.rs", "assets"],
+            Template::Demo => &[],
+        }
+    }
+
+    /// Files written over the SDK tarball, as (path, contents)
+    fn files(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Template::Minimal => &[
+                ("src/main.rs", include_str!("templates/minimal/src/main.rs")),
+            ],
+            Template::AudioDemo => &[
+                ("src/main.rs", include_str!("templates/audio-demo/src/main.rs")),
+            ],
+            Template::MixedAsm => &[
+                ("src/main.rs", include_str!("templates/mixed-asm/src/main.rs")),
+                ("src/asm/math.asm", include_str!("templates/mixed-asm/src/asm/math.asm")),
+            ],
+            Template::Demo => &[],
+        }
+    }
+
+    /// Binary files written as they are, as (path, contents)
+    fn assets(self) -> &'static [(&'static str, &'static [u8])] {
+        match self {
+            Template::Minimal => &[
+                ("assets/sprites/player.png", include_bytes!("templates/minimal/assets/sprites/player.png")),
+            ],
+            _ => &[],
+        }
+    }
+}
+
 /// Extract embedded SDK tarball to filesystem
-pub fn extract_sdk(base_target: &Path, include_audiofw_src: bool) -> Result<(), String> {
+pub fn extract_sdk(base_target: &Path, include_audiofw_src: bool, template: Template) -> Result<(), String> {
     let cursor = Cursor::new(SDK_TEMPLATE);
     let decoder = GzDecoder::new(cursor);
     let mut archive = Archive::new(decoder);
@@ -21,11 +104,17 @@ pub fn extract_sdk(base_target: &Path, include_audiofw_src: bool) -> Result<(),
         let mut entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
         let entry_path = entry.path().map_err(|e| format!("Invalid path: {}", e))?;
         
-        // Strip the leading "sdk/" from the path
-        let relative_path = entry_path.strip_prefix("sdk").unwrap_or(&entry_path);
+        // Strip the leading "./" (or "sdk/" from older tarballs) from the path
+        let relative_path = entry_path.strip_prefix(".")
+            .or_else(|_| entry_path.strip_prefix("sdk"))
+            .unwrap_or(&entry_path);
         
         // Skip audiofw-src if not requested
-        if !include_audiofw_src && relative_path.starts_with("audiofw-src") {
+        if !include_audiofw_src && relative_path.components().any(|c| c.as_os_str() == "audiofw-src") {
+            continue;
+        }
+
+        if template.skipped().iter().any(|skip| relative_path.starts_with(skip)) {
             continue;
         }
         
@@ -52,6 +141,91 @@ pub fn extract_sdk(base_target: &Path, include_audiofw_src: bool) -> Result<(),
     Ok(())
 }
 
+/// Write the template's own files, filling in the crate name
+fn write_template_files(base_target: &Path, template: Template, crate_name: &str) -> Result<(), String> {
+    for (path, contents) in template.files() {
+        let target_path = base_target.join(path);
+
+        if let Some(parent) = target_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create dir {:?}: {}", parent, e))?;
+        }
+
+        std::fs::write(&target_path, contents.replace(CRATE_NAME_VAR, crate_name))
+            .map_err(|e| format!("Failed to write {:?}: {}", target_path, e))?;
+    }
+
+    for (path, contents) in template.assets() {
+        let target_path = base_target.join(path);
+
+        if let Some(parent) = target_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create dir {:?}: {}", parent, e))?;
+        }
+
+        std::fs::write(&target_path, contents)
+            .map_err(|e| format!("Failed to write {:?}: {}", target_path, e))?;
+    }
+
+    Ok(())
+}
+
+/// Create the asset directories, with a `.gitkeep` in the empty ones so they
+/// survive a clone
+fn create_asset_dirs(base_target: &Path) -> Result<(), String> {
+    for dir in ASSET_DIRS {
+        let path = base_target.join(dir);
+        std::fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create dir {:?}: {}", path, e))?;
+
+        let empty = std::fs::read_dir(&path)
+            .map_err(|e| format!("Failed to read dir {:?}: {}", path, e))?
+            .next()
+            .is_none();
+        if empty {
+            std::fs::write(path.join(".gitkeep"), "")
+                .map_err(|e| format!("Failed to write {:?}: {}", path.join(".gitkeep"), e))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the `.gitignore`, unless there already is one
+fn write_gitignore(base_target: &Path) -> Result<(), String> {
+    let path = base_target.join(".gitignore");
+    if path.exists() {
+        return Ok(());
+    }
+    std::fs::write(&path, GITIGNORE)
+        .map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// `git init` the project, unless it's already inside a repository
+fn init_git(base_target: &Path) -> Result<(), String> {
+    let inside = Command::new("git")
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .current_dir(base_target)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if inside.status.success() {
+        println!("  Already inside a git repository, not creating one");
+        return Ok(());
+    }
+
+    let status = Command::new("git")
+        .args(["init", "--quiet"])
+        .current_dir(base_target)
+        .status()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !status.success() {
+        return Err(format!("git init failed with {}", status));
+    }
+    println!("  Initialized git repository");
+
+    Ok(())
+}
+
 /// Sanitize a string to be a valid Cargo crate name
 /// - lowercase
 /// - replace underscores and spaces with hyphens
@@ -87,7 +261,7 @@ fn sanitize_crate_name(name: &str) -> String {
 }
 
 /// Initialize a new GameTank project
-pub fn do_init(path: &str, name: Option<&str>, with_audiofw_src: bool, audio: &str) -> Result<(), String> {
+pub fn do_init(path: &str, name: Option<&str>, with_audiofw_src: bool, audio: &str, template: Template, git: bool) -> Result<(), String> {
     let target_dir = Path::new(path);
     
     // Derive project name from path if not specified, then sanitize
@@ -114,12 +288,13 @@ pub fn do_init(path: &str, name: Option<&str>, with_audiofw_src: bool, audio: &s
     
     if path == "." {
         // Check if current dir already has SDK files
-        if target_dir.join("rom").exists() {
+        if target_dir.join("rom").exists() || target_dir.join("Cargo.toml").exists() {
             return Err("Current directory already contains a GameTank project".to_string());
         }
     }
     
     println!("Creating new GameTank project: {}", project_name);
+    println!("  Template: {}", template.name());
     println!("  Audio firmware: {}", audio);
     if with_audiofw_src {
         println!("  Including audio firmware source");
@@ -130,10 +305,17 @@ pub fn do_init(path: &str, name: Option<&str>, with_audiofw_src: bool, audio: &s
         .map_err(|e| format!("Failed to create directory: {}", e))?;
     
     // Extract SDK template
-    extract_sdk(target_dir, with_audiofw_src)?;
+    extract_sdk(target_dir, with_audiofw_src, template)?;
+    write_template_files(target_dir, template, &project_name)?;
+    create_asset_dirs(target_dir)?;
+    write_gitignore(target_dir)?;
     
-    // Update project name in Cargo.toml
-    let cargo_toml_path = target_dir.join("rom/Cargo.toml");
+    // Update project name in Cargo.toml (at the root, or in rom/ for older layouts)
+    let cargo_toml_path = if target_dir.join("rom/Cargo.toml").exists() {
+        target_dir.join("rom/Cargo.toml")
+    } else {
+        target_dir.join("Cargo.toml")
+    };
     if cargo_toml_path.exists() {
         let content = std::fs::read_to_string(&cargo_toml_path)
             .map_err(|e| format!("Failed to read Cargo.toml: {}", e))?;
@@ -145,18 +327,19 @@ pub fn do_init(path: &str, name: Option<&str>, with_audiofw_src: bool, audio: &s
     }
     
     // Update audio feature if not default
-    if audio != "wavetable-8v" {
-        let cargo_toml_path = target_dir.join("rom/Cargo.toml");
-        if cargo_toml_path.exists() {
-            let content = std::fs::read_to_string(&cargo_toml_path)
-                .map_err(|e| format!("Failed to read Cargo.toml: {}", e))?;
-            let updated = content.replace(
-                "audio = [\"audio-wavetable-8v\"]",
-                &format!("audio = [\"audio-{}\"]", audio)
-            );
-            std::fs::write(&cargo_toml_path, updated)
-                .map_err(|e| format!("Failed to write Cargo.toml: {}", e))?;
-        }
+    if audio != "wavetable-8v" && cargo_toml_path.exists() {
+        let content = std::fs::read_to_string(&cargo_toml_path)
+            .map_err(|e| format!("Failed to read Cargo.toml: {}", e))?;
+        let updated = content.replace(
+            "audio = [\"audio-wavetable-8v\"]",
+            &format!("audio = [\"audio-{}\"]", audio)
+        );
+        std::fs::write(&cargo_toml_path, updated)
+            .map_err(|e| format!("Failed to write Cargo.toml: {}", e))?;
+    }
+    
+    if git {
+        init_git(target_dir)?;
     }
     
     println!("\nProject created successfully!");
@@ -0,0 +1,52 @@
+//! ROM inspection
+//!
+//! Prints and validates the `.gtr` header of a ROM file, or for a raw image,
+//! the header `gametank_rom!` built into it.
+
+use gte_core::cartridges::mapper::Mapper;
+use gte_core::rom_header::{RomHeader, HEADER_SIZE};
+
+/// Print a ROM's header and check it against the payload
+pub fn do_inspect(path: &str) -> Result<(), String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    let (header, rom) = RomHeader::split(&bytes);
+
+    let header = match header {
+        None => {
+            println!("{}: no .gtr header (raw image, {} bytes)", path, rom.len());
+            if let Some(info) = RomHeader::embedded(rom) {
+                println!("  {:<14}{}", "title", info.title);
+                println!("  {:<14}{}", "author", info.author);
+                println!("  {:<14}{}", "rom version", info.rom_version);
+            }
+            return Ok(());
+        }
+        Some(header) => header.map_err(|e| format!("Invalid header in {}: {}", path, e))?,
+    };
+
+    println!("{}", path);
+    println!("  {:<14}{}", "version", header.version);
+    println!("  {:<14}{}", "title", header.title);
+    println!("  {:<14}{}", "author", header.author);
+    if !header.rom_version.is_empty() {
+        println!("  {:<14}{}", "rom version", header.rom_version);
+    }
+    println!("  {:<14}{}", "banks", header.bank_count);
+    match Mapper::from_id(header.mapper) {
+        Some(mapper) => println!("  {:<14}{}", "mapper", mapper.name()),
+        None => println!("  {:<14}unknown ({})", "mapper", header.mapper),
+    }
+    if header.save_size != 0 {
+        println!("  {:<14}{} KB", "save size", header.save_size);
+    }
+    println!("  {:<14}${:04X}", "entry point", header.entry_point);
+    println!("  {:<14}{} bytes (+{} header)", "rom size", header.rom_size, HEADER_SIZE);
+    println!("  {:<14}{:08X}", "crc32", header.crc32);
+
+    header.validate(rom).map_err(|e| format!("Header does not match ROM: {}", e))?;
+    println!("Header OK");
+
+    Ok(())
+}
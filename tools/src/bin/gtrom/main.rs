@@ -3,23 +3,70 @@
 //! A unified CLI for building, running, and managing GameTank ROM projects.
 
 mod asm;
+mod aseprite;
+mod assets;
 mod audio;
+mod bundle;
 mod cargo;
+mod clean;
+mod compress;
+mod config;
 mod container;
+mod disasm;
+mod flash;
+mod hooks;
 mod init;
+mod inspect;
 mod rom_builder;
+mod scaffold;
+mod size_report;
+mod song;
+mod sprite;
+mod symbols;
+mod test;
+mod tiled;
+mod toolchain;
+mod watch;
+mod wav;
 
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use clap::{Parser, Subcommand};
+use gametank_project::config::{Config, Profile};
+use gametank_project::{get_crate_author, get_crate_name, RomTarget};
+use gametank_sdk::wav::{Cycle, ACP_SAMPLE_RATE};
+use gte_core::rom_header::crc32;
 
 use crate::asm::{build_asm, build_asm_in_container};
+use crate::aseprite::convert_aseprite;
+use crate::assets::{convert_assets, SHARED_DIR};
 use crate::audio::do_audio_build;
-use crate::cargo::{cargo_build, cargo_build_in_container, find_rom_dir, get_crate_name};
-use crate::container::{ensure_container, is_in_container};
-use crate::init::do_init;
+use crate::bundle::bundle_assets;
+use crate::clean::{do_clean, Clean};
+use crate::cargo::{cargo_build, cargo_build_in_container, find_rom_dir, find_roms, find_workspace};
+use crate::config::do_configure;
+use crate::container::{ensure_container, uses_container};
+use crate::disasm::do_disasm;
+use crate::flash::do_flash;
+use crate::hooks::{run_hooks, HookPaths, Stage};
+use crate::init::{do_init, Template};
+use crate::inspect::do_inspect;
 use crate::rom_builder::RomBuilder;
+use crate::scaffold::{new_asm, new_module};
+use crate::size_report::{check_limits, check_zero_page, measure, print_report, print_zero_page, zero_page};
+use crate::song::convert_gtt;
+use crate::sprite::{convert_png, Dither, SpriteFormat};
+use crate::symbols::write_symbols;
+use crate::test::{do_test, golden_dir, TestInput};
+use crate::tiled::convert_tmx;
+use crate::toolchain::resolve as resolve_toolchain;
+use crate::watch::{watch, watch_paths, watch_while};
+use crate::wav::{convert_wav, WavMode};
+
+/// Where gte listens for a debugger after `gtrom run --debug`
+const GDB_PORT: u16 = 1234;
 
 #[derive(Parser)]
 #[command(name = "gtrom")]
@@ -33,9 +80,64 @@ struct Cli {
 enum Commands {
     /// Build the ROM (handles container orchestration automatically)
     Build {
-        /// Build in release mode
-        #[arg(short, long, default_value_t = true)]
+        /// Build in release mode (the default unless gtrom.toml says otherwise)
+        #[arg(short, long)]
         release: bool,
+
+        /// Build in debug mode
+        #[arg(short, long, conflicts_with = "release")]
+        debug: bool,
+
+        /// Build with a named profile from gtrom.toml, or the built-in "size" or "speed"
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["release", "debug"])]
+        profile: Option<String>,
+
+        /// Rebuild whenever sources or assets change
+        #[arg(short, long)]
+        watch: bool,
+
+        /// Fail if a memory region is over its limit in gtrom.toml
+        #[arg(long)]
+        size_report: bool,
+
+        /// Build twice from scratch and fail unless both ROMs are byte-identical
+        #[arg(long, conflicts_with = "watch")]
+        verify_reproducible: bool,
+
+        /// Which of the workspace's ROMs to build, by its name in gtrom.toml
+        #[arg(long)]
+        rom: Option<String>,
+
+        /// Build every ROM in the workspace
+        #[arg(long, conflicts_with = "rom")]
+        all: bool,
+    },
+
+    /// Remove assembled objects, converted assets and built ROMs (all three unless some are picked)
+    Clean {
+        /// Remove converted assets and their caches
+        #[arg(long)]
+        assets: bool,
+
+        /// Remove assembled objects (target/asm)
+        #[arg(long)]
+        asm: bool,
+
+        /// Remove built ROMs and their symbol files
+        #[arg(long)]
+        roms: bool,
+
+        /// Remove each ROM's whole target directory, including cargo's build, and the ROMs
+        #[arg(long, conflicts_with_all = ["assets", "asm", "roms"])]
+        all: bool,
+
+        /// Also stop and remove the build container
+        #[arg(long)]
+        container: bool,
+
+        /// Which of the workspace's ROMs to clean (all of them by default)
+        #[arg(long)]
+        rom: Option<String>,
     },
 
     /// Build audio coprocessor firmware
@@ -44,14 +146,78 @@ enum Commands {
         path: String,
     },
 
-    /// Convert an ELF binary to a .gtr ROM file
+    /// Convert an ELF binary to a .gtr ROM file, a PNG to sprite RAM data, an Aseprite file to sprite RAM data and animations, a WAV to audio data, a Tiled .tmx to a tilemap, or a tracker project to a song
     Convert {
-        /// Path to the ELF binary
-        elf_path: String,
+        /// Path to the ELF binary, PNG image, Aseprite file, WAV file, Tiled map or .gtt tracker project
+        input: String,
 
-        /// Output .gtr file path
+        /// Output file path
         #[arg(short, long)]
         output: Option<String>,
+
+        /// Sprite output format (PNG input only)
+        #[arg(long, value_enum, default_value_t = SpriteFormat::Bin)]
+        format: SpriteFormat,
+
+        /// Dithering used when quantizing to the GameTank palette (PNG input only)
+        #[arg(long, value_enum, default_value_t = Dither::None)]
+        dither: Dither,
+
+        /// Report how far each color moved when quantizing, and write the original and quantized images side by side to a .preview.png (PNG input only)
+        #[arg(long)]
+        preview: bool,
+
+        /// What to make from a WAV (WAV input only)
+        #[arg(long, value_enum, default_value_t = WavMode::Wavetable)]
+        wav: WavMode,
+
+        /// Sample rate for `--wav sample` output (WAV input only)
+        #[arg(long, default_value_t = ACP_SAMPLE_RATE)]
+        rate: u32,
+
+        /// Take the whole file as one cycle instead of detecting its pitch (`--wav wavetable` only)
+        #[arg(long)]
+        whole: bool,
+
+        /// Put tile layers in auto-banked ROM sections (TMX input only)
+        #[arg(long)]
+        banked: bool,
+
+        /// ROM title stored in the .gtr header (defaults to the output file name)
+        #[arg(long)]
+        title: Option<String>,
+
+        /// ROM author stored in the .gtr header
+        #[arg(long, default_value = "")]
+        author: String,
+    },
+
+    /// Print and validate the header of a .gtr ROM
+    Inspect {
+        /// Path to the .gtr file
+        rom: String,
+    },
+
+    /// Disassemble a ROM, labelled from the .sym file next to it if there is one
+    Disasm {
+        /// Path to the .gtr, raw image or ELF
+        rom: String,
+
+        /// Only this function, by full path or any tail of it
+        #[arg(long, short = 'f', conflicts_with_all = ["start", "end"])]
+        function: Option<String>,
+
+        /// Linked address to start at, in hex (banked code is at $8000 + bank * $10000)
+        #[arg(long)]
+        start: Option<String>,
+
+        /// Linked address to stop before, in hex (defaults to the end of the bank)
+        #[arg(long)]
+        end: Option<String>,
+
+        /// Write the listing here instead of printing it
+        #[arg(long, short = 'o')]
+        output: Option<String>,
     },
 
     /// Initialize a new GameTank project
@@ -71,38 +237,186 @@ enum Commands {
         /// Audio firmware to use
         #[arg(long, default_value = "wavetable-8v")]
         audio: String,
+
+        /// Project template to start from
+        #[arg(short, long, value_enum, default_value_t = Template::Demo)]
+        template: Template,
+
+        /// Also create a git repository (a .gitignore is always written)
+        #[arg(long)]
+        git: bool,
+    },
+
+    /// Add src/asm/<NAME>.asm and a Rust binding for it, src/<NAME>.rs
+    NewAsm {
+        /// Function and file name, in snake_case
+        name: String,
+
+        /// ROM bank (0-126) to put it in, with a binding that switches to it (defaults to the fixed bank)
+        #[arg(long)]
+        bank: Option<u8>,
+
+        /// Which ROM in a workspace to add it to
+        #[arg(long)]
+        rom: Option<String>,
+    },
+
+    /// Add an empty game module, src/<NAME>.rs, and declare it in main.rs
+    NewModule {
+        /// Module name, in snake_case
+        name: String,
+
+        /// Which ROM in a workspace to add it to
+        #[arg(long)]
+        rom: Option<String>,
     },
 
     /// Build and run in the emulator (gte)
-    Run {},
+    Run {
+        /// Build in release mode (the default unless gtrom.toml says otherwise)
+        #[arg(short, long)]
+        release: bool,
+
+        /// Build in debug mode, and have gte wait for gdb or lldb on port 1234
+        #[arg(short, long, conflicts_with = "release")]
+        debug: bool,
+
+        /// Build with a named profile from gtrom.toml, or the built-in "size" or "speed"
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["release", "debug"])]
+        profile: Option<String>,
+
+        /// Run in RetroArch with this libretro core (`retroarch -L <CORE>`) instead of gte
+        #[arg(long, value_name = "CORE")]
+        libretro: Option<String>,
+
+        /// Keep the emulator open, rebuilding whenever sources or assets change and loading the new ROM
+        #[arg(long)]
+        hold: bool,
+
+        /// Run the ELF straight from cargo, skipping the .gtr and its layout checks, for faster iteration
+        #[arg(long)]
+        dev: bool,
+
+        /// Which of the workspace's ROMs to run, by its name in gtrom.toml
+        #[arg(long)]
+        rom: Option<String>,
+    },
+
+    /// Build and run headless in the emulator, checking the ROM's test hooks
+    Test {
+        /// Give up after this many frames
+        #[arg(short, long, default_value_t = 600)]
+        frames: usize,
+
+        /// Write the checked frames as the new golden checksums
+        #[arg(long)]
+        bless: bool,
+
+        /// Rhai script to drive the controllers and check RAM each frame
+        #[arg(short, long)]
+        script: Option<String>,
+
+        /// Play a .gtm movie on the controllers (runs at least as many frames as it has)
+        #[arg(short, long)]
+        movie: Option<String>,
+
+        /// Which of the workspace's ROMs to test, by its name in gtrom.toml
+        #[arg(long)]
+        rom: Option<String>,
+
+        /// Write a chrome://tracing JSON of frame times, blits, interrupts and bank switches
+        #[arg(long)]
+        trace: Option<String>,
+    },
 
-    /// Build and flash to cartridge via gtld
+    /// Write a ROM to a cartridge with the USB programmer (builds first if no ROM is given)
     Flash {
+        /// Path to the .gtr file
+        rom: Option<String>,
+
         /// Serial port (auto-detected if not specified)
         #[arg(short, long)]
         port: Option<String>,
+
+        /// Skip reading back the cartridge after writing
+        #[arg(long)]
+        no_verify: bool,
     },
 
     /// Build and open SDK documentation in your browser
     Docs {},
+
+    /// Show the project's gtrom.toml settings
+    Configure {
+        /// Write a gtrom.toml with the default settings
+        #[arg(long)]
+        init: bool,
+    },
+
+    /// Install the llvm-mos/rust-mos toolchain, or see what's installed
+    Toolchain {
+        #[command(subcommand)]
+        command: ToolchainCommand,
+    },
 }
 
-/// Convert ELF to GTR
-fn convert_elf_to_gtr(elf_path: &str, output: &str) -> Result<(), String> {
+#[derive(Subcommand)]
+enum ToolchainCommand {
+    /// Download a prebuilt toolchain and link it as rustup's `mos`, or get the build container image
+    Install {
+        /// Pull the container image for podman or docker (building it if that fails) instead
+        #[arg(long)]
+        container: bool,
+
+        /// Manifest of prebuilt toolchains, a URL or a file
+        #[arg(long, default_value = toolchain::MANIFEST_URL)]
+        manifest: String,
+
+        /// Install again even if it's already there
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Show which toolchains are installed and which one this project builds with
+    Status {},
+}
+
+/// The directory with the project's gtrom.toml: the workspace, the ROM
+/// crate, or failing those the current directory
+fn project_dir() -> Result<PathBuf, String> {
+    match find_workspace().map(|w| w.map(|(dir, _)| dir)) {
+        Ok(Some(workspace_dir)) => Ok(workspace_dir),
+        _ => match find_rom_dir() {
+            Ok((working_dir, _)) => Ok(working_dir),
+            Err(_) => std::env::current_dir().map_err(|e| format!("Failed to get current directory: {}", e)),
+        },
+    }
+}
+
+/// Convert ELF to GTR, with a symbol file for the emulator next to it
+fn convert_elf_to_gtr(elf_path: &str, output: &str, title: &str, author: &str) -> Result<(), String> {
     println!("Converting ELF to GTR: {} -> {}", elf_path, output);
-    RomBuilder::build(elf_path.to_string(), output.to_string());
+    RomBuilder::build(elf_path.to_string(), output.to_string(), title, author)?;
+
+    // the ROM is still usable without symbols, so don't fail the build over them
+    let sym_path = Path::new(output).with_extension("sym");
+    match write_symbols(Path::new(elf_path), &sym_path) {
+        Ok(count) => println!("Symbols: {} ({} functions)", sym_path.display(), count),
+        Err(e) => eprintln!("Warning: no symbol file: {}", e),
+    }
     Ok(())
 }
 
 /// Build and open SDK documentation
 fn do_docs() -> Result<(), String> {
-    let (_working_dir, rom_dir) = find_rom_dir()?;
+    let (_working_dir, _config, roms) = find_roms(None, false)?;
+    let rom_dir = &roms[0].dir;
     
     println!("Building documentation...");
     
     let status = Command::new("cargo")
         .args(["doc", "--document-private-items"])
-        .current_dir(&rom_dir)
+        .current_dir(rom_dir)
         .status()
         .map_err(|e| format!("Failed to run cargo doc: {}", e))?;
     
@@ -122,34 +436,338 @@ fn do_docs() -> Result<(), String> {
     Ok(())
 }
 
-/// Full build process
-fn do_build(release: bool) -> Result<PathBuf, String> {
-    let (working_dir, rom_dir) = find_rom_dir()?;
+/// Container mount root for a build, or `None` to build directly
+fn build_mount_root(working_dir: &Path, config: &Config) -> Result<Option<PathBuf>, String> {
+    if uses_container(config) {
+        Ok(Some(ensure_container(config, working_dir)?.0))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The project root, its settings with the toolchain resolved, and the
+/// ROMs to build (see [`find_roms`])
+fn load_project(rom: Option<&str>, all: bool) -> Result<(PathBuf, Config, Vec<RomTarget>), String> {
+    let (working_dir, mut config, roms) = find_roms(rom, all)?;
+    config.toolchain = resolve_toolchain(&config, &working_dir)?;
+    Ok((working_dir, config, roms))
+}
+
+/// The workspace's shared asset cache, if it has more than one ROM
+fn shared_assets(working_dir: &Path, config: &Config) -> Option<PathBuf> {
+    config.is_workspace().then(|| working_dir.join(SHARED_DIR))
+}
+
+/// Full build process for one ROM. `profile` overrides the one in gtrom.toml.
+fn do_build(rom: Option<&str>, profile: Option<Profile>, size_report: bool) -> Result<PathBuf, String> {
+    let (working_dir, config, roms) = load_project(rom, false)?;
+    let mount_root = build_mount_root(&working_dir, &config)?;
+    let profile = profile.unwrap_or_else(|| config.build.profile.clone());
+
+    build_rom(&working_dir, &roms[0], mount_root.as_deref(), &config, &profile, size_report)
+}
+
+/// Build every ROM in the workspace, carrying on past failures
+fn do_build_all(profile: Option<Profile>, size_report: bool) -> Result<(), String> {
+    let (working_dir, config, roms) = load_project(None, true)?;
+    let mount_root = build_mount_root(&working_dir, &config)?;
+    let profile = profile.unwrap_or_else(|| config.build.profile.clone());
+
+    let mut failed = vec![];
+    for rom in &roms {
+        println!("== {} ==", rom.name);
+        if let Err(e) = build_rom(&working_dir, rom, mount_root.as_deref(), &config, &profile, size_report) {
+            eprintln!("Error: {}", e);
+            failed.push(rom.name.as_str());
+        }
+    }
+
+    match failed.len() {
+        0 => {
+            println!("Built {} ROM(s)", roms.len());
+            Ok(())
+        }
+        _ => Err(format!("{} of {} ROM(s) failed: {}", failed.len(), roms.len(), failed.join(", "))),
+    }
+}
+
+/// Build twice from a clean target directory, and fail if the ROMs differ
+fn do_build_verify(rom: Option<&str>, all: bool, profile: Option<Profile>, size_report: bool) -> Result<(), String> {
+    let (working_dir, config, roms) = load_project(rom, all)?;
+    let mount_root = build_mount_root(&working_dir, &config)?;
+    let profile = profile.unwrap_or_else(|| config.build.profile.clone());
+
+    let remove = |path: PathBuf| -> Result<(), String> {
+        let removed = match path.is_dir() {
+            true => std::fs::remove_dir_all(&path),
+            false if path.exists() => std::fs::remove_file(&path),
+            false => Ok(()),
+        };
+        removed.map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+    };
+
+    let mut hashes = vec![];
+    for pass in 1..=2 {
+        println!("Reproducibility check: clean build {} of 2", pass);
+        if let Some(shared) = shared_assets(&working_dir, &config) {
+            remove(shared)?;
+        }
+
+        let mut pass_hashes = vec![];
+        for rom in &roms {
+            // a shared target-dir is cleared too, or the check proves nothing
+            remove(cargo_target(&working_dir, &config, &rom.dir).join("mos-unknown-none"))?;
+            for output in ["target/asm", "target/assets", "target/assets-cache"] {
+                remove(rom.dir.join(output))?;
+            }
+
+            let gtr_path = build_rom(&working_dir, rom, mount_root.as_deref(), &config, &profile, size_report)?;
+            let bytes = std::fs::read(&gtr_path)
+                .map_err(|e| format!("Failed to read {}: {}", gtr_path.display(), e))?;
+            pass_hashes.push((crc32(&bytes), bytes.len()));
+        }
+        hashes.push(pass_hashes);
+    }
+
+    for (rom, (&(first, first_len), &(second, second_len))) in roms.iter().zip(hashes[0].iter().zip(&hashes[1])) {
+        if (first, first_len) != (second, second_len) {
+            return Err(format!(
+                "{} is not reproducible: CRC32 {:08X} ({} bytes), then {:08X} ({} bytes)",
+                rom.name, first, first_len, second, second_len,
+            ));
+        }
+        println!("{} is reproducible: CRC32 {:08X} ({} bytes)", rom.name, first, first_len);
+    }
+    Ok(())
+}
+
+/// Build, then rebuild every time a watched file changes
+fn do_build_watch(rom: Option<&str>, all: bool, profile: Option<Profile>, size_report: bool) -> Result<(), String> {
+    let (working_dir, config, roms) = load_project(rom, all)?;
+    let profile = profile.unwrap_or_else(|| config.build.profile.clone());
+
+    // Only check the container once; re-checking on every change adds noticeable latency
+    let mount_root = build_mount_root(&working_dir, &config)?;
+
+    let build_all = || {
+        for rom in &roms {
+            if let Err(e) = build_rom(&working_dir, rom, mount_root.as_deref(), &config, &profile, size_report) {
+                eprintln!("Error: {}", e);
+            }
+        }
+    };
+    build_all();
 
-    if is_in_container() {
-        // Direct build inside container
-        let rom_dir_str = rom_dir.to_string_lossy().to_string();
-        build_asm(&rom_dir_str)?;
-        cargo_build(&rom_dir_str, release)?;
+    let mut paths = vec![];
+    for rom in &roms {
+        for path in watch_paths(&working_dir, &rom.dir, &config.assets.dirs) {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    watch(&paths, build_all)
+}
+
+/// The emulator command for a ROM: gte, or RetroArch with a libretro core.
+/// gte reloads the ROM itself with `--watch`.
+fn emulator_command(gtr_path: &Path, libretro: Option<&str>, watch: bool, gdb: bool) -> Command {
+    match libretro {
+        Some(core) => {
+            let mut command = Command::new("retroarch");
+            command.arg("-L").arg(core).arg(gtr_path);
+            command
+        }
+        None => {
+            let mut command = Command::new("gte");
+            command.arg(gtr_path);
+            if watch {
+                command.arg("--watch");
+            }
+            if gdb {
+                command.arg(format!("--gdb={}", GDB_PORT));
+            }
+            command
+        }
+    }
+}
+
+/// Build, then run the ROM. With `hold`, keep rebuilding on changes until the
+/// emulator is closed; gte picks up each new ROM, RetroArch is restarted.
+/// Debug builds in gte wait for a debugger to attach. With `dev`, the
+/// emulator runs the ELF and the .gtr isn't written.
+fn do_run(rom: Option<&str>, profile: Option<Profile>, libretro: Option<&str>, hold: bool, dev: bool) -> Result<(), String> {
+    let (working_dir, config, roms) = load_project(rom, false)?;
+    let mount_root = build_mount_root(&working_dir, &config)?;
+    let gdb = profile.as_ref().is_some_and(Profile::is_debug) && libretro.is_none();
+    let profile = profile.unwrap_or_else(|| config.build.profile.clone());
+    let rom = &roms[0];
+    let build = || if dev {
+        build_elf(&working_dir, rom, mount_root.as_deref(), &config, &profile)
     } else {
-        // Orchestrate from outside container
-        let (workspace_root, _runtime) = ensure_container()?;
-        build_asm_in_container(&rom_dir, &workspace_root)?;
-        cargo_build_in_container(&rom_dir, &workspace_root, release)?;
+        build_rom(&working_dir, rom, mount_root.as_deref(), &config, &profile, false)
+    };
+
+    let rom_path = build()?;
+    if gdb {
+        let elf = elf_path(&cargo_target(&working_dir, &config, &rom.dir), &profile, &get_crate_name(&rom.dir)?);
+        println!("gte is paused until a debugger attaches on localhost:{}:", GDB_PORT);
+        println!("  lldb {} -o \"gdb-remote {}\"", elf.display(), GDB_PORT);
+        println!("  gdb {} -ex \"target remote :{}\"", elf.display(), GDB_PORT);
+    }
+    let launch = || emulator_command(&rom_path, libretro, hold, gdb).spawn()
+        .map_err(|e| format!("Failed to launch {}: {}", if libretro.is_some() { "retroarch" } else { "gte" }, e));
+
+    println!("Launching emulator...");
+    let mut child = launch()?;
+    if !hold {
+        let status = child.wait().map_err(|e| format!("Failed to wait for the emulator: {}", e))?;
+        return if status.success() {
+            Ok(())
+        } else {
+            Err("Emulator exited with error".to_string())
+        };
+    }
+
+    let child = RefCell::new(child);
+    let running = || matches!(child.borrow_mut().try_wait(), Ok(None));
+    let rebuild = || {
+        if let Err(e) = build() {
+            eprintln!("Error: {}", e);
+            return;
+        }
+        if libretro.is_some() {
+            let _ = child.borrow_mut().kill();
+            match launch() {
+                Ok(new) => *child.borrow_mut() = new,
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        }
+    };
+
+    watch_while(&watch_paths(&working_dir, &rom.dir, &config.assets.dirs), running, rebuild)?;
+    println!("Emulator closed");
+    Ok(())
+}
+
+/// Build, then run the ROM headless with `gtrom test`
+fn do_build_test(rom: Option<&str>, frames: usize, bless: bool, input: TestInput) -> Result<(), String> {
+    let (working_dir, config, roms) = load_project(rom, false)?;
+    let mount_root = build_mount_root(&working_dir, &config)?;
+    let profile = config.build.profile.clone();
+    let rom = &roms[0];
+
+    let gtr_path = build_rom(&working_dir, rom, mount_root.as_deref(), &config, &profile, false)?;
+    let elf_path = elf_path(&cargo_target(&working_dir, &config, &rom.dir), &profile, &get_crate_name(&rom.dir)?);
+    // each ROM in a workspace has its own golden frames
+    let golden = golden_dir(if config.is_workspace() { &rom.dir } else { &working_dir });
+    do_test(&gtr_path, &elf_path, &golden, &rom.dir.join("target/test"), frames, bless, input)
+}
+
+/// cargo's target directory for a ROM: `[build] target-dir`, or the ROM's
+/// own target/
+fn cargo_target(working_dir: &Path, config: &Config, rom_dir: &Path) -> PathBuf {
+    config.target_dir(working_dir).unwrap_or_else(|| rom_dir.join("target"))
+}
+
+/// Where cargo puts `profile`'s build
+fn target_dir(cargo_target: &Path, profile: &Profile) -> PathBuf {
+    cargo_target.join("mos-unknown-none").join(profile.dir_name())
+}
+
+/// Where cargo leaves the linked ROM
+fn elf_path(cargo_target: &Path, profile: &Profile, crate_name: &str) -> PathBuf {
+    target_dir(cargo_target, profile).join(crate_name)
+}
+
+/// `[hooks]` paths for building `rom`
+fn hook_paths<'a>(working_dir: &'a Path, rom: &'a RomTarget, profile: &'a Profile, target_dir: &'a Path, elf: &'a Path) -> HookPaths<'a> {
+    HookPaths {
+        project_dir: working_dir,
+        rom_dir: &rom.dir,
+        target_dir,
+        elf,
+        rom: &rom.output,
+        rom_name: &rom.name,
+        profile: profile.name(),
     }
+}
 
-    let crate_name = get_crate_name(&rom_dir)?;
+/// Assemble and compile the ROM, returning its ELF. `[hooks]` run before
+/// and after linking.
+fn build_elf(working_dir: &Path, rom: &RomTarget, mount_root: Option<&Path>, config: &Config, profile: &Profile) -> Result<PathBuf, String> {
+    let settings = config.profile_config(profile)?;
+    let rom_dir = rom.dir.as_path();
+    let shared_target = config.target_dir(working_dir);
+    let cargo_target = cargo_target(working_dir, config, rom_dir);
+    let target_dir = target_dir(&cargo_target, profile);
+    let elf_path = elf_path(&cargo_target, profile, &get_crate_name(rom_dir)?);
+    let hook_paths = hook_paths(working_dir, rom, profile, &target_dir, &elf_path);
+    run_hooks(Stage::PreBuild, config, &hook_paths, mount_root)?;
+
+    // Converting assets only needs gtrom, so it runs on the host
+    let shared = shared_assets(working_dir, config);
+    convert_assets(working_dir, rom_dir, &config.assets.dirs, shared.as_deref())?;
+    bundle_assets(rom_dir)?;
+
+    match mount_root {
+        None => {
+            // Direct build inside container or with a local toolchain
+            let rom_dir_str = rom_dir.to_string_lossy().to_string();
+            build_asm(&rom_dir_str, &config.asm)?;
+            cargo_build(&rom_dir_str, config.toolchain, profile, &settings, shared_target.as_deref())?;
+        }
+        Some(workspace_root) => {
+            // Orchestrate from outside container
+            build_asm_in_container(rom_dir, workspace_root, config)?;
+            cargo_build_in_container(rom_dir, workspace_root, config, profile, &settings)?;
+        }
+    }
+
+    run_hooks(Stage::PostLink, config, &hook_paths, mount_root)?;
+    Ok(elf_path)
+}
+
+/// Assemble, compile, and convert the ROM.
+/// `mount_root` is the container's /workspace mount, or `None` to build directly.
+/// With `size_report`, regions over their gtrom.toml limit fail the build;
+/// zero page over its limit always does. `[hooks]` run before, after
+/// linking, and once the ROM is written.
+fn build_rom(working_dir: &Path, rom: &RomTarget, mount_root: Option<&Path>, config: &Config, profile: &Profile, size_report: bool) -> Result<PathBuf, String> {
+    let elf_path = build_elf(working_dir, rom, mount_root, config, profile)?;
+    let rom_dir = rom.dir.as_path();
+    let crate_name = get_crate_name(rom_dir)?;
+    let target_dir = target_dir(&cargo_target(working_dir, config, rom_dir), profile);
+    let gtr_path = rom.output.clone();
+    let author = get_crate_author(rom_dir).unwrap_or_default();
 
     // Convert to GTR (runs on host, doesn't need llvm)
-    let profile = if release { "release" } else { "debug" };
-    let elf_path = rom_dir.join(format!("target/mos-unknown-none/{}/{}", profile, crate_name));
-    let gtr_path = working_dir.join(format!("{}.gtr", crate_name));
+    if let Some(parent) = gtr_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
     
     convert_elf_to_gtr(
         elf_path.to_str().unwrap(),
         gtr_path.to_str().unwrap(),
+        &crate_name,
+        &author,
     )?;
 
+    let regions = measure(&elf_path, &config.size)?;
+    print_report(&regions);
+    let zp_symbols = zero_page(&elf_path)?;
+    print_zero_page(&zp_symbols);
+    let map_path = rom_dir.join("target/link.map");
+    if map_path.exists() {
+        println!("Linker map: {}", map_path.display());
+    }
+    check_zero_page(&regions, &zp_symbols)?;
+    if size_report {
+        check_limits(&regions)?;
+    }
+    run_hooks(Stage::PostRom, config, &hook_paths(working_dir, rom, profile, &target_dir, &elf_path), mount_root)?;
+
     println!("Build complete: {}", gtr_path.display());
     Ok(gtr_path)
 }
@@ -158,67 +776,119 @@ fn main() {
     let cli = Cli::parse();
 
     let result: Result<(), String> = match cli.command {
-        Commands::Build { release } => {
-            do_build(release).map(|_| ())
+        Commands::Build { release, debug, profile, watch, size_report, verify_reproducible, rom, all } => {
+            let profile = match (release, debug, profile) {
+                (true, ..) => Some(Profile::release()),
+                (_, true, _) => Some(Profile::debug()),
+                (.., name) => name.map(Profile::from),
+            };
+            if watch {
+                do_build_watch(rom.as_deref(), all, profile, size_report)
+            } else if verify_reproducible {
+                do_build_verify(rom.as_deref(), all, profile, size_report)
+            } else if all {
+                do_build_all(profile, size_report)
+            } else {
+                do_build(rom.as_deref(), profile, size_report).map(|_| ())
+            }
         }
         
+        Commands::Clean { assets, asm, roms, all, container, rom } => {
+            let clean = Clean { assets, asm, roms, all, container };
+            find_roms(rom.as_deref(), rom.is_none())
+                .and_then(|(working_dir, config, targets)| do_clean(&working_dir, &config, &targets, clean))
+        }
+
         Commands::Audio { path } => {
             do_audio_build(&path)
         }
         
-        Commands::Convert { elf_path, output } => {
-            let out = output.unwrap_or_else(|| "game.gtr".to_string());
-            convert_elf_to_gtr(&elf_path, &out)
+        Commands::Convert { input, output, format, dither, preview, wav, rate, whole, banked, title, author } => {
+            let lower = input.to_lowercase();
+            if lower.ends_with(".tmx") {
+                convert_tmx(&input, output.as_deref(), banked)
+            } else if lower.ends_with(".ase") || lower.ends_with(".aseprite") {
+                convert_aseprite(&input, output.as_deref())
+            } else if lower.ends_with(".png") {
+                convert_png(&input, output.as_deref(), format, dither, preview)
+            } else if lower.ends_with(".wav") {
+                let cycle = if whole { Cycle::Whole } else { Cycle::Detect };
+                convert_wav(&input, output.as_deref(), wav, rate, cycle)
+            } else if lower.ends_with(".gtt") {
+                convert_gtt(&input, output.as_deref())
+            } else {
+                let out = output.unwrap_or_else(|| "game.gtr".to_string());
+                let title = title.unwrap_or_else(|| {
+                    Path::new(&out).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+                });
+                convert_elf_to_gtr(&input, &out, &title, &author)
+            }
         }
 
-        Commands::Init { path, name, with_audiofw_src, audio } => {
-            do_init(&path, name.as_deref(), with_audiofw_src, &audio)
+        Commands::Inspect { rom } => {
+            do_inspect(&rom)
         }
-        
-        Commands::Run {} => {
-            do_build(true).and_then(|gtr_path| {
-                // Launch emulator
-                println!("Launching emulator...");
-                let status = Command::new("gte")
-                    .arg(&gtr_path)
-                    .status()
-                    .map_err(|e| format!("Failed to launch gte: {}", e))?;
-                
-                if status.success() {
-                    Ok(())
-                } else {
-                    Err("Emulator exited with error".to_string())
-                }
-            })
+
+        Commands::Disasm { rom, function, start, end, output } => {
+            do_disasm(&rom, function.as_deref(), start.as_deref(), end.as_deref(), output.as_deref())
+        }
+
+        Commands::Init { path, name, with_audiofw_src, audio, template, git } => {
+            do_init(&path, name.as_deref(), with_audiofw_src, &audio, template, git)
+        }
+
+        Commands::NewAsm { name, bank, rom } => {
+            find_roms(rom.as_deref(), false).and_then(|(_, _, roms)| new_asm(&roms[0].dir, &name, bank))
+        }
+
+        Commands::NewModule { name, rom } => {
+            find_roms(rom.as_deref(), false).and_then(|(_, _, roms)| new_module(&roms[0].dir, &name))
         }
         
-        Commands::Flash { port } => {
-            do_build(true).and_then(|gtr_path| {
-                // Flash via gtld
-                println!("Flashing to cartridge...");
-                let gtr_str = gtr_path.to_string_lossy().to_string();
-                let mut args = vec!["load".to_string(), gtr_str];
-                if let Some(ref p) = port {
-                    args.push("--port".to_string());
-                    args.push(p.clone());
-                }
-                
-                let status = Command::new("gtld")
-                    .args(&args)
-                    .status()
-                    .map_err(|e| format!("Failed to run gtld: {}", e))?;
-                
-                if status.success() {
-                    Ok(())
-                } else {
-                    Err("Flash failed".to_string())
-                }
-            })
+        Commands::Run { release, debug, profile, libretro, hold, dev, rom } => {
+            let profile = match (release, debug, profile) {
+                (true, ..) => Some(Profile::release()),
+                (_, true, _) => Some(Profile::debug()),
+                (.., name) => name.map(Profile::from),
+            };
+            do_run(rom.as_deref(), profile, libretro.as_deref(), hold, dev)
+        }
+
+        Commands::Test { frames, bless, script, movie, rom, trace } => {
+            let input = TestInput {
+                script: script.as_deref().map(Path::new),
+                movie: movie.as_deref().map(Path::new),
+                trace: trace.as_deref().map(Path::new),
+            };
+            do_build_test(rom.as_deref(), frames, bless, input)
+        }
+
+        Commands::Flash { rom, port, no_verify } => {
+            let rom = match rom {
+                Some(rom) => Ok(rom),
+                None => do_build(None, None, false).map(|p| p.to_string_lossy().to_string()),
+            };
+            rom.and_then(|rom| do_flash(&rom, port.as_deref(), !no_verify))
         }
 
         Commands::Docs {} => {
             do_docs()
         }
+
+        Commands::Configure { init } => {
+            project_dir().and_then(|dir| do_configure(&dir, init))
+        }
+
+        Commands::Toolchain { command } => {
+            project_dir().and_then(|dir| Config::load(&dir)).and_then(|config| match command {
+                ToolchainCommand::Install { container: true, force, .. } => toolchain::install_container(&config, force),
+                ToolchainCommand::Install { manifest, force, .. } => toolchain::install_prebuilt(&manifest, force),
+                ToolchainCommand::Status {} => {
+                    toolchain::print_status(&config);
+                    Ok(())
+                }
+            })
+        }
     };
 
     if let Err(e) = result {
@@ -1,120 +1,173 @@
 use std::{fs::File, io::Write};
 
-use elf::{ElfBytes, endian::AnyEndian};
+use elf::{abi::{SHF_ALLOC, SHT_NOBITS}, ElfBytes, endian::AnyEndian};
+use gte_core::elf_rom::{
+    bank_of_addr, read_bank_table, rom_image, rom_sections, static_sections, ElfSection, BANK_SIZE,
+    FIXED_BANK, LOADED_SECTIONS,
+};
+use gte_core::rom_header::{RomHeader, HEADER_SIZE, INFO_ADDRESS};
 use rustc_demangle::demangle;
 
-#[derive(Debug, Clone)]
-pub struct ElfSection {
-    _internal_name: String,
-    display_name: String,
-    bytes: Vec<u8>,
-    size: usize,
-    mem_loc: usize,
-    bank_loc: usize,
-    bank: u8,
+/// A section's name as it's shown in errors and the layout
+fn display_name(s: &ElfSection) -> String {
+    demangle(&s.name).to_string()
 }
 
-impl ElfSection {
-    fn from_static(section_name: String, elf: &ElfBytes<'_, AnyEndian>, bank: u8) -> Option<Self> {
-        let header = elf
-            .section_header_by_name(&section_name)
-            .expect("something failed")?;
-
-        let load_addr = header.sh_addr as usize;
-        let size = header.sh_size as usize;
-        let offset_in_bank = load_addr & 0x3FFF;
-
-        let (d, _ch) = elf.section_data(&header).expect("bruh");
-
-        Some(Self {
-            display_name: demangle(&section_name).to_string(),
-            _internal_name: section_name,
-            bytes: Vec::from(d),
-            size,
-            mem_loc: load_addr,
-            bank,
-            bank_loc: offset_in_bank,
-        })
+/// NMI, reset and IRQ, at the top of the fixed bank
+const VECTORS: usize = 0xFFFA;
+
+/// Where a bank's sections have to be linked
+fn bank_range(bank: u8) -> String {
+    match bank {
+        FIXED_BANK => format!("the fixed bank (${:04X}-${:04X})", 0xC000, VECTORS - 1),
+        n => {
+            let start = 0x8000 + ((n as usize) << 16);
+            format!("bank {} (${:06X}-${:06X})", n, start, start + BANK_SIZE - 1)
+        }
     }
+}
 
-    fn from_loaded(
-        section_name: String,
-        elf: &ElfBytes<'_, AnyEndian>,
-        load_symbol: String,
-    ) -> Option<Self> {
-        let header = elf.section_header_by_name(&section_name).ok().flatten()?;
-        let (bytes, _) = elf.section_data(&header).ok()?;
-
-        let (symtab, strtab) = elf.symbol_table().ok().flatten()?;
-        let load_sym = symtab.iter().find_map(|sym| {
-            let name = strtab.get(sym.st_name as usize).ok()?;
-            if name == load_symbol { Some(sym) } else { None }
-        })?;
-
-        let load_rom_addr = load_sym.st_value as usize; // where the section is in ROM
-        let mem_target_addr = header.sh_addr as usize; // where the section ends up in RAM
-
-        Some(Self {
-            display_name: demangle(&section_name).to_string(),
-            _internal_name: section_name,
-            bytes: bytes.to_vec(),
-            size: bytes.len(),
-            mem_loc: mem_target_addr,
-            bank: 127,
-            bank_loc: load_rom_addr & 0x3FFF,
-        })
+/// Problems with where the linker put each section, any of which would cut
+/// something out of the ROM or stop it booting
+fn check_placement(sections: &[ElfSection]) -> Vec<String> {
+    let mut problems = vec![];
+
+    if !sections.iter().any(|s| s.name == ".vector_table") {
+        problems.push("There's no .vector_table section, so the ROM can't boot (is the gametank crate's boot module linked in?)".to_string());
     }
+
+    for s in sections {
+        let end = s.rom_loc + s.size;
+        if s.name == ".rom_info" {
+            if s.rom_loc != INFO_ADDRESS as usize || s.size != HEADER_SIZE {
+                problems.push(format!(
+                    ".rom_info is linked at ${:04X}..${:04X}, but tools look for it in the {} bytes at ${:04X} (check the ROM_INFO region in build.rs)",
+                    s.rom_loc, end, HEADER_SIZE, INFO_ADDRESS
+                ));
+            }
+        } else if s.name == ".vector_table" {
+            if s.rom_loc != VECTORS || s.size != 6 {
+                problems.push(format!(
+                    ".vector_table is linked at ${:04X}..${:04X}, but the CPU reads its vectors from the 6 bytes at ${:04X} (check the VECTOR_TABLE region in build.rs)",
+                    s.rom_loc, end, VECTORS
+                ));
+            }
+        } else if bank_of_addr(s.rom_loc) != Some(s.bank) || (s.size > 0 && bank_of_addr(end - 1) != Some(s.bank)) {
+            problems.push(format!("{} is linked at ${:06X}..${:06X}, outside {}", display_name(s), s.rom_loc, end, bank_range(s.bank)));
+        } else if s.bank == FIXED_BANK && end > VECTORS {
+            problems.push(format!(
+                "{} ends at ${:04X}, {} bytes into the vector table; move some code or data out of the fixed bank",
+                display_name(s), end, end - VECTORS
+            ));
+        }
+    }
+
+    let mut placed: Vec<&ElfSection> = sections.iter().filter(|s| s.size > 0).collect();
+    placed.sort_by_key(|s| (s.bank, s.bank_loc));
+    for pair in placed.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if a.bank == b.bank && a.bank_loc + a.size > b.bank_loc {
+            problems.push(format!("{} and {} overlap in {}", display_name(a), display_name(b), bank_range(a.bank)));
+        }
+    }
+
+    problems
+}
+
+/// Loadable sections that aren't part of the ROM layout, and would be left
+/// out of the image
+fn check_unplaced(elf: &ElfBytes<'_, AnyEndian>, known: &[&str]) -> Vec<String> {
+    let Ok((Some(headers), Some(strtab))) = elf.section_headers_with_strtab() else {
+        return vec![];
+    };
+
+    headers.iter()
+        .filter(|h| h.sh_flags & SHF_ALLOC as u64 != 0 && h.sh_type != SHT_NOBITS && h.sh_size > 0)
+        .filter_map(|h| {
+            let name = strtab.get(h.sh_name as usize).ok()?;
+            if known.contains(&name) {
+                return None;
+            }
+            let past_end = [".text.bank", ".rodata.bank"].iter()
+                .filter_map(|prefix| name.strip_prefix(prefix)?.parse::<u32>().ok())
+                .any(|bank| bank >= FIXED_BANK as u32);
+            Some(if past_end {
+                format!("{} is past the last bank; a 2 MB cartridge has banks 0-126 and the fixed bank", name)
+            } else {
+                format!(
+                    "{} ({} bytes at ${:04X}) isn't part of the ROM layout and would be left out; put it in .text, .rodata, or a .text.bankN/.rodata.bankN section",
+                    name, h.sh_size, h.sh_addr
+                )
+            })
+        })
+        .collect()
+}
+
+/// The vectors have to point at code in the fixed bank, since it's the
+/// only one sure to be mapped in at power-on and when an interrupt comes
+fn check_vectors(fixed: &[u8], sections: &[ElfSection]) -> Vec<String> {
+    let code: Vec<(usize, usize)> = sections.iter()
+        .filter(|s| s.bank == FIXED_BANK && s.name == ".text")
+        .map(|s| (s.rom_loc, s.rom_loc + s.size))
+        .collect();
+
+    ["NMI", "reset", "IRQ"].iter().enumerate()
+        .filter_map(|(i, name)| {
+            let at = (VECTORS & 0x3FFF) + i * 2;
+            let addr = u16::from_le_bytes([fixed[at], fixed[at + 1]]) as usize;
+            let problem = if !(0xC000..VECTORS).contains(&addr) {
+                "outside the fixed bank"
+            } else if !code.iter().any(|(start, end)| (*start..*end).contains(&addr)) {
+                "which isn't code in .text"
+            } else {
+                return None;
+            };
+            Some(format!("The {} vector points at ${:04X}, {} (check _VECTOR_TABLE)", name, addr, problem))
+        })
+        .collect()
 }
 
 pub struct RomBuilder {}
 
 impl RomBuilder {
-    /// Build a .gtr ROM from an ELF file
-    pub fn build(elf_path: String, output_path: String) -> Self {
-        let file_data = std::fs::read(&elf_path).expect("Could not read ELF file.");
+    /// Build a .gtr ROM from an ELF file, refusing if its layout would give
+    /// a ROM that's missing pieces or can't boot. A title, author and
+    /// version from `gametank_rom!` take the place of `title` and `author`.
+    pub fn build(elf_path: String, output_path: String, title: &str, author: &str) -> Result<Self, String> {
+        let file_data = std::fs::read(&elf_path)
+            .map_err(|e| format!("Failed to read {}: {}", elf_path, e))?;
         let slice = file_data.as_slice();
-        let file = ElfBytes::<AnyEndian>::minimal_parse(slice).expect("Failed to parse ELF");
+        let file = ElfBytes::<AnyEndian>::minimal_parse(slice)
+            .map_err(|e| format!("Failed to parse {}: {}", elf_path, e))?;
         let elf = &file;
 
-        // 128 banks
-        let static_sections: [Vec<String>; 128] = std::array::from_fn(|i| match i {
-            0..=126 => vec![format!(".text.bank{}", i), format!(".rodata.bank{}", i)],
-            127 => vec![
-                ".text".to_string(),
-                ".rodata".to_string(),
-                ".vector_table".to_string(),
-            ],
-            _ => panic!("you fucked up"),
-        });
-
-        // loaded sections must be in the FIXED bank for crt0
-        let loaded_sections = [
-            (".data".to_string(), "__data_load".to_string()),
-            (".zp".to_string(), "__zp_load".to_string()),
-        ];
-
-        let map_sections: Vec<ElfSection> = static_sections
-            .iter()
-            .enumerate()
-            .flat_map(|(bank, names)| {
-                names
-                    .iter()
-                    .filter_map(move |name| ElfSection::from_static(name.clone(), elf, bank as u8))
-            })
-            .chain(loaded_sections.iter().filter_map(|(section, load_symbol)| {
-                ElfSection::from_loaded(section.clone(), elf, load_symbol.clone())
-            }))
+        let static_sections = static_sections();
+        let map_sections = rom_sections(elf);
+
+        let known: Vec<&str> = static_sections.iter().flatten()
+            .map(String::as_str)
+            .chain(LOADED_SECTIONS.iter().map(|(section, _)| *section))
             .collect();
+        let mut problems = check_placement(&map_sections);
+        problems.extend(check_unplaced(elf, &known));
+        for (section, load_symbol) in LOADED_SECTIONS {
+            let has_data = elf.section_header_by_name(section).ok().flatten().is_some_and(|h| h.sh_size > 0);
+            if has_data && !map_sections.iter().any(|s| s.name == section) {
+                problems.push(format!("{} has no {} symbol, so there's no telling where its starting values go in ROM", section, load_symbol));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(format!("{} doesn't make a working ROM:\n  {}", elf_path, problems.join("\n  ")));
+        }
 
         // ROM data - 128x 16k banks (2MB total)
-        // Use Box to allocate on heap - Windows has 1MB stack limit
-        let mut rom: Box<[[u8; 1 << 14]; 128]> = Box::new([[0x00u8; 1 << 14]; 128]);
+        let mut rom = rom_image(&map_sections);
+        let fixed = FIXED_BANK as usize * BANK_SIZE;
 
-        for s in map_sections {
-            rom[s.bank as usize][s.bank_loc..s.bank_loc + s.size].copy_from_slice(&s.bytes);
+        for s in &map_sections {
             println!(
                 "{:<24}bank {} @{:04X}..{:04X} ${:04X}",
-                s.display_name,
+                display_name(s),
                 s.bank,
                 s.bank_loc,
                 s.bank_loc + s.size,
@@ -122,12 +175,42 @@ impl RomBuilder {
             );
         }
 
-        let mut file = File::create(&output_path).expect("Failed to create output file");
-        let flat: &[u8; 2 * 1024 * 1024] = unsafe { core::mem::transmute(&*rom) };
-        file.write_all(flat).expect("Failed to write ROM data");
+        let problems = check_vectors(&rom[fixed..], &map_sections);
+        if !problems.is_empty() {
+            return Err(format!("{} doesn't make a working ROM:\n  {}", elf_path, problems.join("\n  ")));
+        }
+
+        for entry in read_bank_table(elf) {
+            match entry.bank {
+                Some(bank) if bank_of_addr(entry.entry_addr) == Some(FIXED_BANK) => {
+                    rom[fixed + (entry.entry_addr & 0x3FFF)] = bank;
+                    println!("{:<24}bank {}", entry.item, bank);
+                }
+                Some(_) => eprintln!("Warning: bank table entry for {} is not in the fixed bank", entry.item),
+                None => eprintln!("Warning: bank table entry for {} doesn't point at a ROM symbol (is it #[no_mangle]?)", entry.item),
+            }
+        }
+
+        let flat = rom.as_slice();
+        let mut header = RomHeader::new(title, author, flat);
+        if let Some(info) = RomHeader::embedded(flat) {
+            if !info.title.is_empty() {
+                header.title = info.title;
+            }
+            if !info.author.is_empty() {
+                header.author = info.author;
+            }
+            header.rom_version = info.rom_version;
+            println!("{:<24}\"{}\" {} by {}", ".rom_info", header.title, header.rom_version, header.author);
+        }
+        let mut file = File::create(&output_path)
+            .map_err(|e| format!("Failed to create {}: {}", output_path, e))?;
+        file.write_all(&header.to_bytes())
+            .and_then(|_| file.write_all(flat))
+            .map_err(|e| format!("Failed to write {}: {}", output_path, e))?;
 
-        println!("Created: {}", output_path);
+        println!("Created: {} (entry ${:04X}, CRC32 {:08X})", output_path, header.entry_point, header.crc32);
 
-        Self {}
+        Ok(Self {})
     }
 }
@@ -0,0 +1,239 @@
+//! Code generators
+//!
+//! `gtrom new-asm` writes an assembly file into `src/asm` with the section
+//! directives the linker script expects, and a Rust module declaring it
+//! `extern "C"`. `gtrom new-module` writes an empty game module. Either way
+//! the module is declared in `main.rs` after its last `mod`.
+//!
+//! Nothing is overwritten: a generator stops if any file it would write
+//! already exists.
+
+use std::path::Path;
+
+/// Words that can't name a module or function
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod",
+    "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+    "trait", "true", "type", "unsafe", "use", "where", "while", "abstract", "become", "box",
+    "do", "final", "macro", "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+    // already taken in every project
+    "main", "gametank",
+];
+
+/// Highest bank asm can be put in; 127 is the fixed bank
+const MAX_BANK: u8 = 126;
+
+/// Names have to work as a file, module and asm label alike
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && name != "_";
+    if !valid {
+        return Err(format!("{} isn't a valid name; use snake_case, like player_physics", name));
+    }
+    if KEYWORDS.contains(&name) {
+        return Err(format!("{} is a Rust keyword or already used by every project", name));
+    }
+    Ok(())
+}
+
+fn write_new(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    println!("Created {}", path.display());
+    Ok(())
+}
+
+fn ensure_missing(paths: &[&Path]) -> Result<(), String> {
+    match paths.iter().find(|p| p.exists()) {
+        Some(p) => Err(format!("{} already exists", p.display())),
+        None => Ok(()),
+    }
+}
+
+/// `main_rs` with `mod name;` after its last one-line `mod`, or after its
+/// inner attributes if it has none
+fn add_mod(main_rs: &str, name: &str) -> String {
+    let decl = format!("mod {};", name);
+    let lines: Vec<&str> = main_rs.lines().collect();
+    let is_mod = |line: &&str| {
+        let line = line.trim_end();
+        (line.starts_with("mod ") || line.starts_with("pub mod ")) && line.ends_with(';')
+    };
+
+    let (at, text) = match lines.iter().rposition(is_mod) {
+        Some(i) => (i + 1, decl),
+        None => {
+            let attrs = lines.iter().take_while(|l| l.starts_with("#![") || l.trim().is_empty()).count();
+            // back up over the blank lines after the attributes
+            let at = lines[..attrs].iter().rposition(|l| !l.trim().is_empty()).map_or(0, |i| i + 1);
+            let text = if at == 0 { format!("{}\n", decl) } else { format!("\n{}", decl) };
+            (at, text)
+        }
+    };
+
+    let mut out: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    out.insert(at, text);
+    let mut out = out.join("\n");
+    if main_rs.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Declare `name` in `src/main.rs`, unless it already is
+fn wire_into_main(rom_dir: &Path, name: &str) -> Result<(), String> {
+    let main_path = rom_dir.join("src/main.rs");
+    let main_rs = std::fs::read_to_string(&main_path)
+        .map_err(|e| format!("Failed to read {}: {}", main_path.display(), e))?;
+
+    let declared = main_rs.lines().any(|l| {
+        let l = l.trim();
+        l == format!("mod {};", name) || l == format!("pub mod {};", name)
+    });
+    if declared {
+        return Ok(());
+    }
+
+    std::fs::write(&main_path, add_mod(&main_rs, name))
+        .map_err(|e| format!("Failed to write {}: {}", main_path.display(), e))?;
+    println!("Added `mod {};` to {}", name, main_path.display());
+    Ok(())
+}
+
+fn asm_source(name: &str, bank: Option<u8>) -> String {
+    let section = match bank {
+        Some(bank) => format!(".text.bank{}", bank),
+        None => ".text".to_string(),
+    };
+    let placement = match bank {
+        Some(bank) => format!("; In ROM bank {}: switch to it before calling, as src/{}.rs does.\n", bank, name),
+        None => "; In the fixed bank, so it can be called from anywhere.\n".to_string(),
+    };
+
+    format!(
+        "; {name}, called from Rust through src/{name}.rs\n\
+         {placement}\
+         ;\n\
+         ; llvm-mos calling convention, roughly:\n\
+         ;   - 8-bit arguments go in A, then X, then imaginary registers __rc2...\n\
+         ;   - pointer arguments go in imaginary register pairs (__rc2/__rc3, ...)\n\
+         ;   - 8-bit return values come back in A\n\
+         ;   - __rc2-__rc19 may be clobbered freely; save __rc20 and up if used\n\
+         \n\
+         .section {section},\"ax\",@progbits\n\
+         .global {name}\n\
+         \n\
+         ; void {name}(void)\n\
+         {name}:\n    \
+         RTS\n"
+    )
+}
+
+fn asm_binding(name: &str, bank: Option<u8>) -> String {
+    let mut out = format!(
+        "//! Bindings for src/asm/{name}.asm\n\
+         //!\n\
+         //! Keep the signatures here in step with the registers the asm uses.\n"
+    );
+
+    match bank {
+        None => out.push_str(&format!(
+            "\n\
+             unsafe extern \"C\" {{\n    \
+             /// src/asm/{name}.asm\n    \
+             pub fn {name}();\n\
+             }}\n"
+        )),
+        Some(bank) => out.push_str(&format!(
+            "\n\
+             use gametank::console::Console;\n\
+             \n\
+             /// The ROM bank src/asm/{name}.asm is in\n\
+             pub const BANK: u8 = {bank};\n\
+             \n\
+             mod raw {{\n    \
+             unsafe extern \"C\" {{\n        \
+             pub fn {name}();\n    \
+             }}\n\
+             }}\n\
+             \n\
+             /// Call `{name}` in bank {bank}, then switch back. Call from the\n\
+             /// fixed bank (the default for Rust code).\n\
+             pub fn {name}(console: &mut Console) {{\n    \
+             let old = console.rom_bank();\n    \
+             console.set_rom_bank(BANK);\n    \
+             unsafe {{ raw::{name}() }};\n    \
+             console.set_rom_bank(old);\n\
+             }}\n"
+        )),
+    }
+    out
+}
+
+fn module_source(name: &str) -> String {
+    let type_name: String = name
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars).collect::<String>()
+        })
+        .collect();
+    let type_name = if type_name.is_empty() { "State".to_string() } else { type_name };
+
+    format!(
+        "//! {name}\n\
+         \n\
+         use gametank::video_dma::blitter::BlitterGuard;\n\
+         \n\
+         #[derive(Default)]\n\
+         pub struct {type_name} {{}}\n\
+         \n\
+         impl {type_name} {{\n    \
+         pub fn new() -> Self {{\n        \
+         Self {{}}\n    \
+         }}\n\
+         \n    \
+         /// Once a frame, before drawing\n    \
+         pub fn update(&mut self) {{}}\n\
+         \n    \
+         pub fn draw(&self, blitter: &mut BlitterGuard) {{\n        \
+         let _ = blitter;\n    \
+         }}\n\
+         }}\n"
+    )
+}
+
+/// Write `src/asm/<name>.asm` and its binding `src/<name>.rs`, in the fixed
+/// bank or `bank`
+pub fn new_asm(rom_dir: &Path, name: &str, bank: Option<u8>) -> Result<(), String> {
+    check_name(name)?;
+    if let Some(bank) = bank.filter(|b| *b > MAX_BANK) {
+        return Err(format!("Bank {} is out of range; asm can go in banks 0-{}, or leave out --bank for the fixed bank", bank, MAX_BANK));
+    }
+
+    let asm_path = rom_dir.join("src/asm").join(format!("{}.asm", name));
+    let rs_path = rom_dir.join("src").join(format!("{}.rs", name));
+    ensure_missing(&[&asm_path, &rs_path])?;
+
+    write_new(&asm_path, &asm_source(name, bank))?;
+    write_new(&rs_path, &asm_binding(name, bank))?;
+    wire_into_main(rom_dir, name)
+}
+
+/// Write an empty game module, `src/<name>.rs`
+pub fn new_module(rom_dir: &Path, name: &str) -> Result<(), String> {
+    check_name(name)?;
+
+    let rs_path = rom_dir.join("src").join(format!("{}.rs", name));
+    ensure_missing(&[&rs_path])?;
+
+    write_new(&rs_path, &module_source(name))?;
+    wire_into_main(rom_dir, name)
+}
@@ -0,0 +1,190 @@
+//! Memory usage report for a linked ROM
+//!
+//! Sizes come from the ELF section headers and are measured against the
+//! regions in the SDK's generated linker script. Zero page is broken down
+//! by symbol too, since it's the first thing to run out.
+
+use std::path::Path;
+
+use elf::{ElfBytes, endian::AnyEndian};
+use gametank_project::config::SizeConfig;
+use rustc_demangle::demangle;
+
+/// Zero page left over after the 64 `__rc` imaginary registers
+const ZP_SIZE: usize = 0xC0;
+/// General purpose RAM from $0400 (the soft stack shares it)
+const RAM_SIZE: usize = 0x1BFF;
+/// Fixed bank up to the ROM info and vector table
+const FIXED_SIZE: usize = 0x3F7A;
+const BANK_SIZE: usize = 0x4000;
+
+pub struct Region {
+    pub name: String,
+    pub used: usize,
+    pub size: usize,
+    /// Highest allowed usage, in percent
+    pub limit: u8,
+}
+
+impl Region {
+    fn new(name: impl Into<String>, size: usize, limit: u8) -> Self {
+        Self { name: name.into(), used: 0, size, limit }
+    }
+
+    pub fn percent(&self) -> f64 {
+        self.used as f64 * 100.0 / self.size as f64
+    }
+
+    pub fn over_limit(&self) -> bool {
+        self.percent() > self.limit as f64
+    }
+}
+
+/// A variable the linker put in zero page
+pub struct ZpSymbol {
+    pub address: u16,
+    pub size: usize,
+    pub name: String,
+}
+
+fn read_elf(elf_path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(elf_path).map_err(|e| format!("Failed to read {}: {}", elf_path.display(), e))
+}
+
+fn parse_elf<'a>(elf_path: &Path, data: &'a [u8]) -> Result<ElfBytes<'a, AnyEndian>, String> {
+    ElfBytes::<AnyEndian>::minimal_parse(data)
+        .map_err(|e| format!("Failed to parse {}: {}", elf_path.display(), e))
+}
+
+/// Add up each region's usage from the sections in `elf_path`
+pub fn measure(elf_path: &Path, limits: &SizeConfig) -> Result<Vec<Region>, String> {
+    let data = read_elf(elf_path)?;
+    let elf = parse_elf(elf_path, &data)?;
+    let (headers, strtab) = elf.section_headers_with_strtab()
+        .map_err(|e| format!("Failed to read section headers: {}", e))?;
+    let (Some(headers), Some(strtab)) = (headers, strtab) else {
+        return Err(format!("{} has no section headers", elf_path.display()));
+    };
+
+    let mut zp = Region::new("zero page", ZP_SIZE, limits.zp);
+    let mut ram = Region::new("RAM", RAM_SIZE, limits.ram);
+    let mut fixed = Region::new("fixed bank", FIXED_SIZE, limits.fixed);
+    let mut banks: Vec<Region> = (0..127).map(|i| Region::new(format!("bank {}", i), BANK_SIZE, limits.bank)).collect();
+
+    for header in headers.iter() {
+        let name = strtab.get(header.sh_name as usize)
+            .map_err(|e| format!("Failed to read section name: {}", e))?;
+        let size = header.sh_size as usize;
+
+        match name {
+            // .zp and .data are copied out of the fixed bank by crt0
+            ".zp" => {
+                zp.used += size;
+                fixed.used += size;
+            }
+            ".data" => {
+                ram.used += size;
+                fixed.used += size;
+            }
+            ".bss" => ram.used += size,
+            ".text" | ".rodata" | ".bank_table" => fixed.used += size,
+            _ => {
+                let bank = name.strip_prefix(".text.bank")
+                    .or_else(|| name.strip_prefix(".rodata.bank"))
+                    .and_then(|n| n.parse::<usize>().ok());
+                if let Some(bank) = bank.and_then(|b| banks.get_mut(b)) {
+                    bank.used += size;
+                }
+            }
+        }
+    }
+
+    let mut regions = vec![zp, ram, fixed];
+    regions.extend(banks.into_iter().filter(|b| b.used > 0));
+    Ok(regions)
+}
+
+/// The symbols in `.zp`, biggest first
+pub fn zero_page(elf_path: &Path) -> Result<Vec<ZpSymbol>, String> {
+    let data = read_elf(elf_path)?;
+    let elf = parse_elf(elf_path, &data)?;
+
+    let (headers, strtab) = elf.section_headers_with_strtab()
+        .map_err(|e| format!("Failed to read section headers: {}", e))?;
+    let (Some(headers), Some(strtab)) = (headers, strtab) else {
+        return Err(format!("{} has no section headers", elf_path.display()));
+    };
+    let Some(zp_index) = headers.iter().position(|h| strtab.get(h.sh_name as usize).is_ok_and(|name| name == ".zp")) else {
+        return Ok(vec![]);
+    };
+    let Some((symtab, names)) = elf.symbol_table().ok().flatten() else {
+        return Ok(vec![]);
+    };
+
+    let mut symbols: Vec<ZpSymbol> = symtab.iter()
+        .filter(|sym| sym.st_shndx as usize == zp_index && sym.st_size > 0)
+        .filter_map(|sym| {
+            let name = names.get(sym.st_name as usize).ok()?;
+            Some(ZpSymbol {
+                address: sym.st_value as u16,
+                size: sym.st_size as usize,
+                name: format!("{:#}", demangle(name)),
+            })
+        })
+        .collect();
+
+    symbols.sort_by(|a, b| b.size.cmp(&a.size).then(a.address.cmp(&b.address)));
+    Ok(symbols)
+}
+
+pub fn print_zero_page(symbols: &[ZpSymbol]) {
+    if symbols.is_empty() {
+        return;
+    }
+    println!("Zero page:");
+    for sym in symbols {
+        println!("  ${:02X} {:>4} bytes  {}", sym.address, sym.size, sym.name);
+    }
+}
+
+/// Fail if zero page is over its limit, naming what's using it. This is
+/// checked on every build, so a game nearing the end of zero page hears
+/// about it here rather than from the linker failing to fit `.zp`.
+pub fn check_zero_page(regions: &[Region], symbols: &[ZpSymbol]) -> Result<(), String> {
+    let Some(zp) = regions.iter().find(|r| r.name == "zero page").filter(|r| r.over_limit()) else {
+        return Ok(());
+    };
+    let biggest: Vec<String> = symbols.iter().take(5)
+        .map(|sym| format!("{} ({} bytes)", sym.name, sym.size))
+        .collect();
+    Err(format!(
+        "Zero page is {:.1}% full (limit {}%, {} of {} bytes). Biggest: {}",
+        zp.percent(), zp.limit, zp.used, zp.size,
+        if biggest.is_empty() { "unknown".to_string() } else { biggest.join(", ") },
+    ))
+}
+
+pub fn print_report(regions: &[Region]) {
+    println!("Memory usage:");
+    for r in regions {
+        let warning = if r.over_limit() { format!("  over {}% limit", r.limit) } else { String::new() };
+        println!(
+            "  {:<12}{:>6} / {:>5} bytes {:>6.1}%{}",
+            r.name, r.used, r.size, r.percent(), warning
+        );
+    }
+}
+
+/// Fail if any region is over its limit
+pub fn check_limits(regions: &[Region]) -> Result<(), String> {
+    let over: Vec<String> = regions.iter()
+        .filter(|r| r.over_limit())
+        .map(|r| format!("{} {:.1}% (limit {}%)", r.name, r.percent(), r.limit))
+        .collect();
+
+    if over.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Memory budget exceeded: {}", over.join(", ")))
+    }
+}
@@ -0,0 +1,83 @@
+//! Tracker project (.gtt) compilation
+//!
+//! Turns a gtgo tracker project into what the SDK's sequencer plays: the
+//! song's byte stream, exported the same way as gtgo's export, followed by
+//! its wavetables. The wavetables are the `.wav` files in a
+//! `<name>.wavetables` directory next to `<name>.gtt`, in name order, each
+//! converted like `gtrom convert --wav wavetable`; the sequencer copies them
+//! into slots 0 and up before playing.
+//!
+//! The module puts both in an auto-banked ROM section with a bank table
+//! entry, and declares a `Song` for them.
+
+use std::path::Path;
+
+use gametank_sdk::tracker::{export::export_song, project};
+use gametank_sdk::wav::load_wavetable;
+
+use crate::sprite::const_name;
+
+/// Compile a .gtt into `<output>.bin` and `<output>.rs`
+pub fn convert_gtt(input: &str, output: Option<&str>) -> Result<(), String> {
+    let input_path = Path::new(input);
+    let data = project::load(input_path)?;
+    let song = export_song(&data).map_err(|e| format!("{}: {}", input, e))?;
+
+    let bin_path = match output {
+        Some(out) => Path::new(out).with_extension("bin"),
+        None => input_path.with_extension("bin"),
+    };
+    let rs_path = bin_path.with_extension("rs");
+
+    let stem = rs_path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "song".to_string());
+    let name = const_name(&stem);
+    let bin_name = bin_path.file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Invalid output path: {}", bin_path.display()))?;
+
+    let wavetables = project::wavetable_files(input_path)?;
+    let mut bytes = song.clone();
+    let mut names = Vec::new();
+    for path in &wavetables {
+        let (table, _) = load_wavetable(path)?;
+        bytes.extend_from_slice(&table);
+        names.push(path.file_stem().unwrap_or_default().to_string_lossy().to_string());
+    }
+
+    println!(
+        "Compiling tracker project: {} ({} pattern(s), {} bytes, {} wavetable(s)) -> {}",
+        input, data.patterns.len(), song.len(), wavetables.len(), bin_path.display()
+    );
+
+    let section = format!("{}_song", name.to_lowercase());
+    let mut module = format!(
+        "// Generated by `gtrom convert` from {input}. Do not edit.\n\
+         \n\
+         /// The song, then its wavetables\n\
+         #[unsafe(no_mangle)]\n\
+         #[unsafe(link_section = \".rodata.auto.{section}\")]\n\
+         pub static {name}_DATA: [u8; {len}] = *include_bytes!(\"{bin_name}\");\n\
+         gametank::bank_table!(pub {name}_BANK => {name}_DATA);\n\
+         \n",
+        len = bytes.len(),
+    );
+    for (slot, table) in names.iter().enumerate() {
+        module.push_str(&format!("/// Wavetable slot of {}.wav\npub const {}_{}: usize = {};\n", table, name, const_name(table), slot));
+    }
+    module.push_str(&format!(
+        "\n/// For `Sequencer::for_song` or `music::play_song`\n\
+         pub const {name}: gametank::audio::sequencer::Song =\n    \
+         unsafe {{ gametank::audio::sequencer::Song::new(&{name}_BANK, &{name}_DATA, {song_len}) }};\n",
+        song_len = song.len(),
+    ));
+
+    std::fs::write(&bin_path, &bytes)
+        .map_err(|e| format!("Failed to write {}: {}", bin_path.display(), e))?;
+    std::fs::write(&rs_path, module)
+        .map_err(|e| format!("Failed to write {}: {}", rs_path.display(), e))?;
+    println!("Wrote Rust module: {}", rs_path.display());
+
+    Ok(())
+}
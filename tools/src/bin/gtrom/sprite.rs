@@ -0,0 +1,343 @@
+//! PNG to sprite sheet conversion
+//!
+//! Quantizes PNG images against the GameTank palette and lays them out as
+//! 128x128 sprite RAM quadrants, ready to be copied into sprite memory.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use clap::ValueEnum;
+use gte_core::color_map::COLOR_MAP;
+
+pub use gametank_project::manifest::Dither;
+
+/// Width and height of a single sprite RAM quadrant
+pub const QUADRANT_SIZE: u32 = 128;
+
+/// Bytes in a single sprite RAM quadrant
+pub const QUADRANT_BYTES: usize = (QUADRANT_SIZE * QUADRANT_SIZE) as usize;
+
+/// Pixels with alpha below this are written as color 0 (transparent)
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// 4x4 Bayer matrix used for ordered dithering
+const BAYER_4X4: [[f32; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+/// Strength of ordered dithering, in RGB units
+const ORDERED_SPREAD: f32 = 24.0;
+
+/// Rows of the quantization report, worst first
+const REPORT_ROWS: usize = 16;
+
+/// Output format for converted sprite data
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SpriteFormat {
+    /// Raw sprite RAM bytes
+    Bin,
+    /// Raw bytes plus a Rust module that `include_bytes!`s them
+    Rs,
+}
+
+/// Quantized sprite sheet, split into 128x128 quadrants
+pub struct SpriteSheet {
+    pub width: u32,
+    pub height: u32,
+    pub quadrants_x: u32,
+    pub quadrants_y: u32,
+    /// Quadrants in row-major order, each `QUADRANT_BYTES` long
+    pub data: Vec<u8>,
+}
+
+impl SpriteSheet {
+    /// Load a PNG and quantize it to GameTank colors
+    pub fn from_png(path: &Path, dither: Dither) -> Result<Self, String> {
+        Ok(Self::from_image(&load_png(path)?, dither))
+    }
+
+    /// Quantize an image that's already loaded
+    pub fn from_image(img: &image::RgbaImage, dither: Dither) -> Self {
+        let (width, height) = img.dimensions();
+        let indices = quantize(img, dither);
+
+        let quadrants_x = width.div_ceil(QUADRANT_SIZE);
+        let quadrants_y = height.div_ceil(QUADRANT_SIZE);
+        let mut data = vec![0u8; (quadrants_x * quadrants_y) as usize * QUADRANT_BYTES];
+
+        for y in 0..height {
+            for x in 0..width {
+                let quad = (y / QUADRANT_SIZE) * quadrants_x + (x / QUADRANT_SIZE);
+                let offset = quad as usize * QUADRANT_BYTES
+                    + ((y % QUADRANT_SIZE) * QUADRANT_SIZE + (x % QUADRANT_SIZE)) as usize;
+                data[offset] = indices[(y * width + x) as usize];
+            }
+        }
+
+        Self { width, height, quadrants_x, quadrants_y, data }
+    }
+
+    pub fn quadrant_count(&self) -> u32 {
+        self.quadrants_x * self.quadrants_y
+    }
+
+    /// The palette index at (x, y) in the source image
+    pub fn index_at(&self, x: u32, y: u32) -> u8 {
+        let quad = (y / QUADRANT_SIZE) * self.quadrants_x + (x / QUADRANT_SIZE);
+        self.data[quad as usize * QUADRANT_BYTES + ((y % QUADRANT_SIZE) * QUADRANT_SIZE + (x % QUADRANT_SIZE)) as usize]
+    }
+}
+
+fn load_png(path: &Path) -> Result<image::RgbaImage, String> {
+    let img = image::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?
+        .to_rgba8();
+
+    if img.width() == 0 || img.height() == 0 {
+        return Err(format!("{} is empty", path.display()));
+    }
+    Ok(img)
+}
+
+/// How one color of the source image came out in the palette
+pub struct ColorError {
+    pub rgb: [u8; 3],
+    pub pixels: u32,
+    /// The palette index most of its pixels became
+    pub index: u8,
+    /// Mean RGB distance from the palette colors its pixels became
+    pub error: f32,
+}
+
+/// Every opaque color in `img` and how far quantizing moved it, worst first
+pub fn quantization_errors(img: &image::RgbaImage, sheet: &SpriteSheet) -> Vec<ColorError> {
+    // per source color: pixels, summed error, and pixels per index
+    let mut colors: HashMap<[u8; 3], (u32, f32, HashMap<u8, u32>)> = HashMap::new();
+
+    for (x, y, p) in img.enumerate_pixels() {
+        if p[3] < ALPHA_THRESHOLD {
+            continue;
+        }
+        let index = sheet.index_at(x, y);
+        let (pr, pg, pb, _) = COLOR_MAP[index as usize];
+        let (dr, dg, db) = (p[0] as f32 - pr as f32, p[1] as f32 - pg as f32, p[2] as f32 - pb as f32);
+
+        let entry = colors.entry([p[0], p[1], p[2]]).or_default();
+        entry.0 += 1;
+        entry.1 += (dr * dr + dg * dg + db * db).sqrt();
+        *entry.2.entry(index).or_default() += 1;
+    }
+
+    let mut errors: Vec<ColorError> = colors.into_iter()
+        .map(|(rgb, (pixels, total, indices))| ColorError {
+            rgb,
+            pixels,
+            index: indices.into_iter().max_by_key(|&(index, count)| (count, index)).map_or(0, |(index, _)| index),
+            error: total / pixels as f32,
+        })
+        .collect();
+    errors.sort_by(|a, b| b.error.total_cmp(&a.error).then(b.pixels.cmp(&a.pixels)).then(a.rgb.cmp(&b.rgb)));
+    errors
+}
+
+fn print_quantization_report(errors: &[ColorError]) {
+    let pixels: u32 = errors.iter().map(|e| e.pixels).sum();
+    if pixels == 0 {
+        println!("Quantization: no opaque pixels");
+        return;
+    }
+    let mean = errors.iter().map(|e| e.error * e.pixels as f32).sum::<f32>() / pixels as f32;
+    let worst = errors.first().map_or(0.0, |e| e.error);
+    println!("Quantization: {} color(s), mean error {:.1}, worst {:.1}", errors.len(), mean, worst);
+
+    println!("  {:<9} {:>7} {:>6} {:>6}", "color", "pixels", "index", "error");
+    for e in errors.iter().take(REPORT_ROWS) {
+        println!(
+            "  #{:02x}{:02x}{:02x}   {:>7}   0x{:02x} {:>6.1}",
+            e.rgb[0], e.rgb[1], e.rgb[2], e.pixels, e.index, e.error
+        );
+    }
+    if errors.len() > REPORT_ROWS {
+        println!("  ... and {} more", errors.len() - REPORT_ROWS);
+    }
+}
+
+/// The original and quantized images side by side
+fn write_preview(img: &image::RgbaImage, sheet: &SpriteSheet, path: &Path) -> Result<(), String> {
+    let (width, height) = img.dimensions();
+    let mut preview = image::RgbaImage::new(width * 2, height);
+
+    for (x, y, &p) in img.enumerate_pixels() {
+        preview.put_pixel(x, y, p);
+        if p[3] >= ALPHA_THRESHOLD {
+            let (r, g, b, _) = COLOR_MAP[sheet.index_at(x, y) as usize];
+            preview.put_pixel(width + x, y, image::Rgba([r, g, b, 0xFF]));
+        }
+    }
+
+    preview.save(path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Find the nearest opaque palette entry. Index 0 is skipped since the
+/// blitter treats it as transparent.
+fn nearest_color(r: f32, g: f32, b: f32) -> u8 {
+    let mut best = 1;
+    let mut best_distance = f32::MAX;
+
+    for (i, &(pr, pg, pb, _)) in COLOR_MAP.iter().enumerate().skip(1) {
+        let dr = r - pr as f32;
+        let dg = g - pg as f32;
+        let db = b - pb as f32;
+        let distance = dr * dr + dg * dg + db * db;
+        if distance < best_distance {
+            best_distance = distance;
+            best = i;
+        }
+    }
+
+    best as u8
+}
+
+/// Map every pixel to a palette index
+fn quantize(img: &image::RgbaImage, dither: Dither) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let mut out = vec![0u8; (width * height) as usize];
+
+    // working copy so error diffusion can push values around
+    let mut work: Vec<[f32; 3]> = img.pixels()
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+        .collect();
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) as usize;
+            if img.get_pixel(x, y)[3] < ALPHA_THRESHOLD {
+                continue;
+            }
+
+            let [mut r, mut g, mut b] = work[i];
+
+            if dither == Dither::Ordered {
+                let t = (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] + 0.5) / 16.0 - 0.5;
+                r += t * ORDERED_SPREAD;
+                g += t * ORDERED_SPREAD;
+                b += t * ORDERED_SPREAD;
+            }
+
+            let idx = nearest_color(r, g, b);
+            out[i] = idx;
+
+            if dither == Dither::FloydSteinberg {
+                let (pr, pg, pb, _) = COLOR_MAP[idx as usize];
+                let err = [r - pr as f32, g - pg as f32, b - pb as f32];
+
+                let mut spread = |dx: i32, dy: i32, weight: f32| {
+                    let nx = x as i32 + dx;
+                    let ny = y as i32 + dy;
+                    if nx < 0 || nx >= width as i32 || ny >= height as i32 {
+                        return;
+                    }
+                    let n = (ny as u32 * width + nx as u32) as usize;
+                    for c in 0..3 {
+                        work[n][c] = (work[n][c] + err[c] * weight).clamp(0.0, 255.0);
+                    }
+                };
+
+                spread(1, 0, 7.0 / 16.0);
+                spread(-1, 1, 3.0 / 16.0);
+                spread(0, 1, 5.0 / 16.0);
+                spread(1, 1, 1.0 / 16.0);
+            }
+        }
+    }
+
+    out
+}
+
+/// Turn a file stem into a SCREAMING_SNAKE_CASE identifier
+pub fn const_name(stem: &str) -> String {
+    let mut name: String = stem.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+
+    name
+}
+
+/// Convert a PNG into sprite RAM data. With `preview`, also report how far
+/// each color moved and write the before and after side by side.
+pub fn convert_png(input: &str, output: Option<&str>, format: SpriteFormat, dither: Dither, preview: bool) -> Result<(), String> {
+    let input_path = Path::new(input);
+    let img = load_png(input_path)?;
+    let sheet = SpriteSheet::from_image(&img, dither);
+
+    let bin_path = match (output, format) {
+        (Some(out), SpriteFormat::Bin) => Path::new(out).to_path_buf(),
+        (Some(out), SpriteFormat::Rs) => Path::new(out).with_extension("bin"),
+        (None, _) => input_path.with_extension("bin"),
+    };
+
+    println!(
+        "Converting PNG to sprite data: {} ({}x{}, {} quadrant(s), dither: {:?}) -> {}",
+        input, sheet.width, sheet.height, sheet.quadrant_count(), dither, bin_path.display()
+    );
+
+    std::fs::write(&bin_path, &sheet.data)
+        .map_err(|e| format!("Failed to write {}: {}", bin_path.display(), e))?;
+
+    if preview {
+        print_quantization_report(&quantization_errors(&img, &sheet));
+        let preview_path = bin_path.with_extension("preview.png");
+        write_preview(&img, &sheet, &preview_path)?;
+        println!("Wrote preview: {}", preview_path.display());
+    }
+
+    if format == SpriteFormat::Rs {
+        let rs_path = match output {
+            Some(out) => Path::new(out).with_extension("rs"),
+            None => input_path.with_extension("rs"),
+        };
+
+        let stem = rs_path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "sprites".to_string());
+        let name = const_name(&stem);
+        let bin_name = bin_path.file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .ok_or_else(|| format!("Invalid output path: {}", bin_path.display()))?;
+
+        let module = format!(
+            "// Generated by `gtrom convert` from {input}. Do not edit.\n\
+             \n\
+             /// Source image width in pixels\n\
+             pub const {name}_WIDTH: u32 = {width};\n\
+             /// Source image height in pixels\n\
+             pub const {name}_HEIGHT: u32 = {height};\n\
+             /// Number of 128x128 quadrants in {name}\n\
+             pub const {name}_QUADRANTS: usize = {quads};\n\
+             \n\
+             /// Sprite RAM data, one 0x4000 byte quadrant after another (row-major)\n\
+             pub static {name}: &[u8; {len}] = include_bytes!(\"{bin_name}\");\n",
+            input = input,
+            name = name,
+            width = sheet.width,
+            height = sheet.height,
+            quads = sheet.quadrant_count(),
+            len = sheet.data.len(),
+            bin_name = bin_name,
+        );
+
+        std::fs::write(&rs_path, module)
+            .map_err(|e| format!("Failed to write {}: {}", rs_path.display(), e))?;
+        println!("Wrote Rust module: {}", rs_path.display());
+    }
+
+    Ok(())
+}
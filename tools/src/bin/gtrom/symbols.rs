@@ -0,0 +1,396 @@
+//! Debug symbol file for the emulator
+//!
+//! Written next to the .gtr as `<name>.sym`. Function names come from the ELF
+//! symbol table and source lines from `.debug_line`, so the debugger can set
+//! breakpoints by name and show where the CPU is in Rust terms.
+//!
+//! The format is plain text, one record per line, with addresses as linked
+//! (banked code at `$8000 + bank * $10000`, see `bank_of_addr`):
+//!
+//! ```text
+//! file 1 src/main.rs
+//! func 00C123 0042 game::update
+//! line 00C123 1 57
+//! ```
+//!
+//! A `line` with file 0 marks the end of a sequence: there's no source for
+//! the addresses after it.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use elf::{endian::AnyEndian, ElfBytes};
+use gte_core::elf_rom;
+
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_NEGATE_STMT: u8 = 6;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_DATA1: u64 = 0x0b;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_DATA16: u64 = 0x1e;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_UDATA: u64 = 0x0f;
+const DW_FORM_LINE_STRP: u64 = 0x1f;
+
+/// A row of the line table. `file` and `line` are 0 past the end of a sequence.
+struct Row {
+    address: u32,
+    file: u32,
+    line: u32,
+}
+
+/// Little-endian reader over a DWARF section
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.data.len())
+            .ok_or_else(|| "Unexpected end of .debug_line".to_string())?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, n: usize) -> Result<u64, String> {
+        Ok(self.bytes(n)?.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn uleb(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {
+                value |= ((b & 0x7F) as u64) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> Result<i64, String> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {
+                value |= ((b & 0x7F) as i64) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    fn cstr(&mut self) -> Result<&'a str, String> {
+        let len = self.data[self.pos..].iter().position(|&b| b == 0)
+            .ok_or_else(|| "Unterminated string in .debug_line".to_string())?;
+        let s = std::str::from_utf8(&self.data[self.pos..self.pos + len])
+            .map_err(|e| format!("Invalid string in .debug_line: {}", e))?;
+        self.pos += len + 1;
+        Ok(s)
+    }
+}
+
+/// Sections a v5 line table header can point into
+struct Strings<'a> {
+    debug_str: &'a [u8],
+    debug_line_str: &'a [u8],
+}
+
+impl Strings<'_> {
+    fn get(section: &[u8], offset: u64) -> Result<String, String> {
+        let mut reader = Reader::new(section);
+        reader.pos = usize::try_from(offset).ok().filter(|&o| o < section.len())
+            .ok_or_else(|| format!("String offset {:#X} is out of range", offset))?;
+        reader.cstr().map(str::to_string)
+    }
+}
+
+/// One value of a v5 directory or file entry. Only strings and indices matter.
+fn read_form(r: &mut Reader, form: u64, offset_size: usize, strings: &Strings) -> Result<Option<String>, String> {
+    match form {
+        DW_FORM_STRING => r.cstr().map(|s| Some(s.to_string())),
+        DW_FORM_LINE_STRP => Strings::get(strings.debug_line_str, r.uint(offset_size)?).map(Some),
+        DW_FORM_STRP => Strings::get(strings.debug_str, r.uint(offset_size)?).map(Some),
+        DW_FORM_UDATA => r.uleb().map(|v| Some(v.to_string())),
+        DW_FORM_DATA1 => r.uint(1).map(|v| Some(v.to_string())),
+        DW_FORM_DATA2 => r.uint(2).map(|v| Some(v.to_string())),
+        DW_FORM_DATA4 => r.uint(4).map(|v| Some(v.to_string())),
+        DW_FORM_DATA8 => r.uint(8).map(|v| Some(v.to_string())),
+        DW_FORM_DATA16 => r.bytes(16).map(|_| None),
+        DW_FORM_BLOCK => {
+            let len = r.uleb()? as usize;
+            r.bytes(len).map(|_| None)
+        }
+        _ => Err(format!("Unsupported form {:#X} in .debug_line header", form)),
+    }
+}
+
+/// Read a v5 directory or file name table: `(path, directory index)` pairs
+fn read_entries(r: &mut Reader, offset_size: usize, strings: &Strings) -> Result<Vec<(String, usize)>, String> {
+    let format_count = r.u8()?;
+    let format: Vec<(u64, u64)> = (0..format_count)
+        .map(|_| Ok((r.uleb()?, r.uleb()?)))
+        .collect::<Result<_, String>>()?;
+
+    let count = r.uleb()?;
+    (0..count).map(|_| {
+        let mut path = String::new();
+        let mut dir = 0;
+        for &(content, form) in &format {
+            let value = read_form(r, form, offset_size, strings)?;
+            match (content, value) {
+                (DW_LNCT_PATH, Some(v)) => path = v,
+                (DW_LNCT_DIRECTORY_INDEX, Some(v)) => dir = v.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+        Ok((path, dir))
+    }).collect()
+}
+
+/// Join a file name onto its directory unless it's already absolute
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() || Path::new(name).is_absolute() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir.trim_end_matches('/'), name)
+    }
+}
+
+/// Run every line number program in `.debug_line`, interning file paths into
+/// `files` (whose index + 1 is the file number in the .sym)
+fn read_line_tables(data: &[u8], strings: &Strings, files: &mut Vec<String>) -> Result<Vec<Row>, String> {
+    let mut ids: HashMap<String, u32> = HashMap::new();
+    let mut rows = Vec::new();
+    let mut r = Reader::new(data);
+
+    while r.pos < data.len() {
+        let (unit_length, offset_size) = match r.uint(4)? {
+            0xFFFF_FFFF => (r.uint(8)?, 8),
+            len => (len, 4),
+        };
+        let unit_end = r.pos + unit_length as usize;
+        if unit_end > data.len() {
+            return Err("Line table runs past the end of .debug_line".to_string());
+        }
+
+        let version = r.uint(2)?;
+        if !(2..=5).contains(&version) {
+            return Err(format!("Unsupported .debug_line version {}", version));
+        }
+        if version >= 5 {
+            r.bytes(2)?; // address and segment selector size
+        }
+        let header_length = r.uint(offset_size)? as usize;
+        let program_start = r.pos + header_length;
+
+        let min_inst_length = r.u8()? as u32;
+        if version >= 4 {
+            r.u8()?; // maximum operations per instruction, only for VLIW
+        }
+        let default_is_stmt = r.u8()? != 0;
+        let line_base = r.u8()? as i8 as i64;
+        let line_range = r.u8()?;
+        let opcode_base = r.u8()?;
+        let opcode_lengths = r.bytes(opcode_base.saturating_sub(1) as usize)?;
+        if line_range == 0 {
+            return Err("Line table has a line range of 0".to_string());
+        }
+
+        // file paths, in the order the program refers to them
+        let unit_files: Vec<String> = if version >= 5 {
+            // directory 0 is the compilation directory, and the others may be relative to it
+            let mut dirs: Vec<String> = read_entries(&mut r, offset_size, strings)?.into_iter()
+                .map(|(dir, _)| dir)
+                .collect();
+            if let Some((comp_dir, rest)) = dirs.split_first_mut() {
+                for dir in rest {
+                    *dir = join(comp_dir, dir);
+                }
+            }
+            read_entries(&mut r, offset_size, strings)?.into_iter()
+                .map(|(name, dir)| join(dirs.get(dir).map_or("", String::as_str), &name))
+                .collect()
+        } else {
+            let mut dirs = vec![String::new()];
+            loop {
+                let dir = r.cstr()?;
+                if dir.is_empty() {
+                    break;
+                }
+                dirs.push(dir.to_string());
+            }
+            // v2-4 files are numbered from 1
+            let mut names = vec![String::new()];
+            loop {
+                let name = r.cstr()?;
+                if name.is_empty() {
+                    break;
+                }
+                let dir = r.uleb()? as usize;
+                r.uleb()?; // modification time
+                r.uleb()?; // length
+                names.push(join(dirs.get(dir).map_or("", String::as_str), name));
+            }
+            names
+        };
+
+        let mut file_id = |index: u64| -> u32 {
+            let Some(path) = unit_files.get(index as usize) else { return 0 };
+            *ids.entry(path.clone()).or_insert_with(|| {
+                files.push(path.clone());
+                files.len() as u32
+            })
+        };
+
+        r.pos = program_start;
+        let mut address = 0u64;
+        let mut file = 1u64;
+        let mut line = 1i64;
+        let mut is_stmt = default_is_stmt;
+        // the linker points sequences for discarded code at 0 (or all ones)
+        let mut discarded = false;
+
+        while r.pos < unit_end {
+            let opcode = r.u8()?;
+            let mut emit = false;
+
+            if opcode >= opcode_base {
+                let adjusted = opcode - opcode_base;
+                address += (adjusted / line_range) as u64 * min_inst_length as u64;
+                line += line_base + (adjusted % line_range) as i64;
+                emit = true;
+            } else {
+                match opcode {
+                    0 => {
+                        let len = r.uleb()? as usize;
+                        let body = r.bytes(len)?;
+                        match body.first() {
+                            Some(&DW_LNE_END_SEQUENCE) => {
+                                if !discarded {
+                                    rows.push(Row { address: address as u32, file: 0, line: 0 });
+                                }
+                                discarded = false;
+                                address = 0;
+                                file = 1;
+                                line = 1;
+                                is_stmt = default_is_stmt;
+                            }
+                            Some(&DW_LNE_SET_ADDRESS) => {
+                                let operand = &body[1..];
+                                address = operand.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64);
+                                discarded = address == 0 || operand.iter().all(|&b| b == 0xFF);
+                            }
+                            _ => {}
+                        }
+                    }
+                    DW_LNS_COPY => emit = true,
+                    DW_LNS_ADVANCE_PC => address += r.uleb()? * min_inst_length as u64,
+                    DW_LNS_ADVANCE_LINE => line += r.sleb()?,
+                    DW_LNS_SET_FILE => file = r.uleb()?,
+                    DW_LNS_NEGATE_STMT => is_stmt = !is_stmt,
+                    DW_LNS_CONST_ADD_PC => {
+                        address += ((255 - opcode_base) / line_range) as u64 * min_inst_length as u64;
+                    }
+                    DW_LNS_FIXED_ADVANCE_PC => address += r.uint(2)?,
+                    _ => {
+                        for _ in 0..opcode_lengths[opcode as usize - 1] {
+                            r.uleb()?;
+                        }
+                    }
+                }
+            }
+
+            if emit && is_stmt && !discarded {
+                rows.push(Row { address: address as u32, file: file_id(file), line: line.max(0) as u32 });
+            }
+        }
+
+        r.pos = unit_end;
+    }
+
+    Ok(rows)
+}
+
+fn section<'a>(elf: &ElfBytes<'a, AnyEndian>, name: &str) -> Result<&'a [u8], String> {
+    match elf.section_header_by_name(name) {
+        Ok(Some(header)) => elf.section_data(&header)
+            .map(|(data, _)| data)
+            .map_err(|e| format!("Failed to read {}: {}", name, e)),
+        _ => Ok(&[]),
+    }
+}
+
+/// Write the symbol file for `elf_path`. Returns how many functions were found.
+pub fn write_symbols(elf_path: &Path, output: &Path) -> Result<usize, String> {
+    let data = std::fs::read(elf_path)
+        .map_err(|e| format!("Failed to read {}: {}", elf_path.display(), e))?;
+    let elf = ElfBytes::<AnyEndian>::minimal_parse(&data)
+        .map_err(|e| format!("Failed to parse {}: {}", elf_path.display(), e))?;
+
+    let functions = elf_rom::functions(&elf);
+
+    let strings = Strings {
+        debug_str: section(&elf, ".debug_str")?,
+        debug_line_str: section(&elf, ".debug_line_str")?,
+    };
+    let mut files = Vec::new();
+    let mut rows = read_line_tables(section(&elf, ".debug_line")?, &strings, &mut files)?;
+    // where one sequence ends and the next begins, the end goes first
+    rows.sort_by_key(|r| (r.address, r.file != 0));
+
+    let mut out = String::from("# gtrom symbols\n");
+
+    for (i, file) in files.iter().enumerate() {
+        let _ = writeln!(out, "file {} {}", i + 1, file);
+    }
+    for f in &functions {
+        let _ = writeln!(out, "func {:06X} {:04X} {}", f.address, f.size, f.name);
+    }
+    let mut last = None;
+    for row in &rows {
+        if last != Some((row.file, row.line)) {
+            let _ = writeln!(out, "line {:06X} {} {}", row.address, row.file, row.line);
+            last = Some((row.file, row.line));
+        }
+    }
+
+    std::fs::write(output, out)
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    Ok(functions.len())
+}
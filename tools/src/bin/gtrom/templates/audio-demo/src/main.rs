@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+#![allow(static_mut_refs)]
+
+use gametank::{audio::{voices, MAX_VOLUME}, boot::wait, console::Console};
+
+mod audio_demo;
+
+// {{crate_name}}: plays the demo chord progression and shows each voice's volume.
+
+const BACKGROUND: u8 = 0b000_00_001;
+const BAR: u8 = 0b111_11_100;
+
+#[unsafe(no_mangle)]
+fn main(console: &mut Console) {
+    let mut sequencer = audio_demo::init_demo();
+
+    loop {
+        unsafe {
+            wait();
+        }
+
+        console.flip_framebuffers();
+
+        let mut blitter = console.blitter().unwrap();
+        blitter.draw_square(0, 0, 127, 127, !BACKGROUND);
+
+        // the sequencer runs while the background blit is in flight
+        sequencer.tick();
+
+        blitter.wait_blit();
+
+        for (i, voice) in voices().iter().enumerate() {
+            let height = (voice.get_volume().min(MAX_VOLUME) as u16 * 64 / (MAX_VOLUME as u16 + 1)) as u8 + 1;
+            blitter.draw_square(8 + i as u8 * 14, 100 - height, 10, height, !BAR);
+            blitter.wait_blit();
+        }
+
+        blitter.draw_letterbox();
+        blitter.wait_blit();
+    }
+}
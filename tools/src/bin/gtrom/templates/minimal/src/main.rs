@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+#![allow(static_mut_refs)]
+
+use gametank::{boot::wait, console::Console};
+
+// {{crate_name}}: a blank GameTank project. Start drawing here!
+
+// `gtrom build` converts everything in assets/ into target/assets first.
+// assets.rs has a module per asset with its size: sprites/player.png is
+// `assets::sprites_player`.
+#[allow(dead_code)]
+mod assets {
+    include!("../target/assets/assets.rs");
+}
+
+use assets::sprites_player::{PLAYER_HEIGHT, PLAYER_WIDTH};
+
+/// The converted sprite sheet, kept in a ROM bank: a whole quadrant is too
+/// big for the fixed bank
+#[unsafe(link_section = ".rodata.bank0")]
+static PLAYER_SHEET: [u8; 0x4000] = *include_bytes!("../target/assets/sprites/player.bin");
+
+const BACKGROUND: u8 = 0b101_01_010;
+
+fn load_sprites(console: &mut Console) {
+    console.set_rom_bank(0);
+    if let Some(mut sm) = console.dma.sprite_mem(&mut console.video_flags) {
+        sm.bytes().copy_from_slice(&PLAYER_SHEET);
+    }
+}
+
+#[unsafe(no_mangle)]
+fn main(console: &mut Console) {
+    load_sprites(console);
+
+    let mut x: u8 = 60;
+
+    loop {
+        unsafe {
+            wait();
+        }
+
+        console.flip_framebuffers();
+
+        let mut blitter = console.blitter().unwrap();
+
+        blitter.draw_square(0, 0, 127, 127, !BACKGROUND);
+        blitter.wait_blit();
+
+        x = x.wrapping_add(1) % (128 - PLAYER_WIDTH as u8);
+        blitter.draw_sprite(0, 0, x, 56, PLAYER_WIDTH as u8, PLAYER_HEIGHT as u8);
+        blitter.wait_blit();
+
+        blitter.draw_letterbox();
+        blitter.wait_blit();
+    }
+}
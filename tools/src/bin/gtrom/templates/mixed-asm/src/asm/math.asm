@@ -0,0 +1,34 @@
+; Example routines called from Rust (see src/main.rs)
+;
+; llvm-mos calling convention, roughly:
+;   - 8-bit arguments go in A, then X, then imaginary registers __rc2...
+;   - pointer arguments go in imaginary register pairs (__rc2/__rc3, ...)
+;   - 8-bit return values come back in A
+;   - __rc2-__rc19 may be clobbered freely
+
+.section .text
+.global add_wrapping, fill_bytes
+
+; u8 add_wrapping(u8 a, u8 b)
+;   A = a, X = b
+add_wrapping:
+    STX __rc4
+    CLC
+    ADC __rc4
+    RTS
+
+; void fill_bytes(u8 *dst, u8 value, u8 len)
+;   __rc2/__rc3 = dst, A = value, X = len
+fill_bytes:
+    CPX #0
+    BEQ .Ldone
+    PHA
+    TXA
+    TAY
+    PLA
+.Lloop:
+    DEY
+    STA (__rc2),Y
+    BNE .Lloop
+.Ldone:
+    RTS
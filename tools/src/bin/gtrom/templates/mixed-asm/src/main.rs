@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+#![allow(static_mut_refs)]
+
+use gametank::{boot::wait, console::Console};
+
+// {{crate_name}}: Rust game loop with hot paths in assembly.
+//
+// Every .asm file in src/asm/ is assembled into target/asm/libasm.a and
+// linked in. Functions use the llvm-mos calling convention: the first
+// 8-bit arguments arrive in A and X, and an 8-bit result is returned in A.
+unsafe extern "C" {
+    /// Adds two bytes, wrapping on overflow (src/asm/math.asm)
+    fn add_wrapping(a: u8, b: u8) -> u8;
+
+    /// Fills `len` bytes at `dst` with `value` (src/asm/math.asm)
+    fn fill_bytes(dst: *mut u8, value: u8, len: u8);
+}
+
+const BACKGROUND: u8 = 0b100_01_010;
+const PLAYER: u8 = 0b001_11_110;
+const TRAIL_COLOR: u8 = 0b001_01_100;
+
+/// Recent player x positions, oldest overwritten first
+static mut TRAIL: [u8; 16] = [0; 16];
+
+#[unsafe(no_mangle)]
+fn main(console: &mut Console) {
+    let mut x: u8 = 0;
+    let mut frame: u8 = 0;
+
+    unsafe {
+        fill_bytes(TRAIL.as_mut_ptr(), x, TRAIL.len() as u8);
+    }
+
+    loop {
+        unsafe {
+            wait();
+        }
+
+        console.flip_framebuffers();
+
+        let mut blitter = console.blitter().unwrap();
+        blitter.draw_square(0, 0, 127, 127, !BACKGROUND);
+        blitter.wait_blit();
+
+        x = unsafe { add_wrapping(x, 1) } % 120;
+        frame = frame.wrapping_add(1);
+        unsafe {
+            TRAIL[frame as usize % TRAIL.len()] = x;
+            for &tx in TRAIL.iter() {
+                blitter.draw_square(tx, 62, 4, 4, !TRAIL_COLOR);
+                blitter.wait_blit();
+            }
+        }
+
+        blitter.draw_square(x, 60, 8, 8, !PLAYER);
+        blitter.wait_blit();
+
+        blitter.draw_letterbox();
+        blitter.wait_blit();
+    }
+}
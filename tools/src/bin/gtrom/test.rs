@@ -0,0 +1,307 @@
+//! Headless test runs
+//!
+//! `gtrom test` runs the ROM in gte-core without a window, as fast as it
+//! goes, and watches the hook bytes from the SDK's `testing` module:
+//!
+//! - `GT_TEST_STATUS` set to passed or failed ends the run
+//! - `GT_TEST_CODE` is the code the ROM failed with
+//! - `GT_TEST_FRAME` asks for the displayed frame to be checked; its CRC32
+//!   is compared against `tests/golden/frame-<id>.crc`
+//!
+//! The hooks are found by name in the ELF's symbol table. A ROM without them
+//! passes if it runs for the whole frame limit without the CPU stopping.
+//!
+//! A ROM built with the SDK's `watchdog` also has `GT_FRAME_OVERRUNS`, and
+//! the run ends by saying how many frames overran.
+//!
+//! What the ROM prints with the SDK's `debug::log!` is shown as it runs.
+//!
+//! `--script` adds a Rhai script (see `gte_core::script`) that presses
+//! buttons and checks RAM each frame. It can pass or fail the run too, and
+//! its `sym` looks up the ROM's RAM symbols from the ELF.
+//!
+//! `--movie` plays a `.gtm` recorded in gtgo or the libretro core (see
+//! `gte_core::movie`) on the controllers, and runs for at least as long as
+//! it lasts.
+
+use std::path::{Path, PathBuf};
+
+use elf::{endian::AnyEndian, ElfBytes};
+use rustc_demangle::demangle;
+use gametank_sdk::capture::save_png;
+use gte_core::emulator::{Emulator, TimeDaemon};
+use gte_core::movie::Movie;
+use gte_core::rom_header::crc32;
+use gte_core::script::{Script, ScriptOutcome};
+
+const STATUS_SYMBOL: &str = "GT_TEST_STATUS";
+const CODE_SYMBOL: &str = "GT_TEST_CODE";
+const FRAME_SYMBOL: &str = "GT_TEST_FRAME";
+/// Frames the SDK's `watchdog` saw overrun, a `u16`
+const OVERRUNS_SYMBOL: &str = "GT_FRAME_OVERRUNS";
+
+/// Values of `GT_TEST_STATUS`, as in the SDK
+const PASSED: u8 = 1;
+const FAILED: u8 = 2;
+
+/// Tests don't run against the wall clock
+struct NoClock;
+
+impl TimeDaemon for NoClock {
+    fn get_now_ms(&self) -> f64 {
+        0.0
+    }
+}
+
+/// RAM addresses of the test hooks
+struct Hooks {
+    status: u16,
+    code: u16,
+    frame: u16,
+}
+
+/// Every symbol in the ELF's symbol table, demangled, with its address
+fn read_symbols(elf_path: &Path) -> Result<Vec<(String, u64)>, String> {
+    let data = std::fs::read(elf_path)
+        .map_err(|e| format!("Failed to read {}: {}", elf_path.display(), e))?;
+    let elf = ElfBytes::<AnyEndian>::minimal_parse(&data)
+        .map_err(|e| format!("Failed to parse {}: {}", elf_path.display(), e))?;
+    let Some((symtab, strtab)) = elf.symbol_table().ok().flatten() else {
+        return Ok(vec![]);
+    };
+
+    Ok(symtab.iter()
+        .filter_map(|sym| {
+            let name = strtab.get(sym.st_name as usize).ok().filter(|n| !n.is_empty())?;
+            Some((format!("{:#}", demangle(name)), sym.st_value))
+        })
+        .collect())
+}
+
+impl Hooks {
+    /// Look the hooks up in the symbols. `None` if the ROM doesn't use them.
+    fn find(symbols: &[(String, u64)]) -> Result<Option<Self>, String> {
+        let address = |wanted: &str| -> Result<Option<u16>, String> {
+            let Some(&(_, value)) = symbols.iter().find(|(name, _)| name == wanted) else {
+                return Ok(None);
+            };
+            if value >= 0x2000 {
+                return Err(format!("{} is at ${:04X}, outside RAM", wanted, value));
+            }
+            Ok(Some(value as u16))
+        };
+
+        match (address(STATUS_SYMBOL)?, address(CODE_SYMBOL)?, address(FRAME_SYMBOL)?) {
+            (Some(status), Some(code), Some(frame)) => Ok(Some(Self { status, code, frame })),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// How a test run ended
+enum Outcome {
+    Passed,
+    Failed(String),
+}
+
+struct TestRun<'a> {
+    emulator: Emulator<NoClock>,
+    hooks: Option<Hooks>,
+    script: Option<Script>,
+    golden_dir: &'a Path,
+    out_dir: &'a Path,
+    bless: bool,
+    frame: usize,
+}
+
+impl TestRun<'_> {
+    /// Hook bytes live in RAM bank 0, where the SDK keeps its statics
+    fn peek(&self, address: u16) -> u8 {
+        self.emulator.cpu_bus.ram_banks[0][address as usize]
+    }
+
+    fn poke(&mut self, address: u16, value: u8) {
+        self.emulator.cpu_bus.ram_banks[0][address as usize] = value;
+    }
+
+    /// Check the displayed frame against `frame-<id>.crc`, or write that
+    /// file when blessing
+    fn check_frame(&self, id: u8) -> Result<(), String> {
+        let framebuffer = self.emulator.screenshot();
+        let actual = crc32(&framebuffer);
+        let golden_path = self.golden_dir.join(format!("frame-{}.crc", id));
+
+        if self.bless {
+            std::fs::create_dir_all(self.golden_dir)
+                .map_err(|e| format!("Failed to create {}: {}", self.golden_dir.display(), e))?;
+            std::fs::write(&golden_path, format!("{:08X}\n", actual))
+                .map_err(|e| format!("Failed to write {}: {}", golden_path.display(), e))?;
+            println!("  frame {} blessed ({:08X})", id, actual);
+            return Ok(());
+        }
+
+        let text = std::fs::read_to_string(&golden_path)
+            .map_err(|e| format!("Failed to read {} (run with --bless to create it): {}", golden_path.display(), e))?;
+        let expected = u32::from_str_radix(text.trim(), 16)
+            .map_err(|e| format!("Invalid checksum in {}: {}", golden_path.display(), e))?;
+
+        if actual == expected {
+            println!("  frame {} ok", id);
+            return Ok(());
+        }
+
+        let png_path = self.out_dir.join(format!("frame-{}.png", id));
+        save_png(&framebuffer, &png_path)?;
+        Err(format!(
+            "frame {} is {:08X}, expected {:08X} (see {})",
+            id, actual, expected, png_path.display(),
+        ))
+    }
+
+    /// Run one frame, then look at the hooks
+    fn step(&mut self) -> Option<Outcome> {
+        if let Some(script) = &mut self.script {
+            let result = script.before_frame(&mut self.emulator);
+            for line in script.take_log() {
+                println!("  script: {}", line);
+            }
+            if let Err(e) = result {
+                return Some(Outcome::Failed(e));
+            }
+            match script.outcome() {
+                Some(ScriptOutcome::Passed) => return Some(Outcome::Passed),
+                Some(ScriptOutcome::Failed(reason)) => {
+                    return Some(Outcome::Failed(format!("script failed on frame {}: {}", self.frame, reason)));
+                }
+                None => {}
+            }
+        }
+
+        self.emulator.run_frame();
+        self.frame += 1;
+        for line in self.emulator.take_debug_messages() {
+            println!("  rom: {}", line);
+        }
+
+        if self.emulator.is_stopped() {
+            let pc = self.emulator.cpu.get_pc();
+            return Some(Outcome::Failed(format!("CPU stopped at ${:04X} on frame {}", pc, self.frame)));
+        }
+
+        let Some(hooks) = &self.hooks else { return None };
+        let (status, code, frame) = (hooks.status, hooks.code, hooks.frame);
+
+        let id = self.peek(frame);
+        if id != 0 {
+            self.poke(frame, 0);
+            if let Err(e) = self.check_frame(id) {
+                return Some(Outcome::Failed(e));
+            }
+        }
+
+        match self.peek(status) {
+            PASSED => Some(Outcome::Passed),
+            FAILED => Some(Outcome::Failed(format!("failed with code {} on frame {}", self.peek(code), self.frame))),
+            _ => None,
+        }
+    }
+}
+
+/// Where `gtrom test` keeps golden frame checksums
+pub fn golden_dir(working_dir: &Path) -> PathBuf {
+    working_dir.join("tests/golden")
+}
+
+/// What drives the controllers during a test run, and what else to record
+pub struct TestInput<'a> {
+    /// Rhai script, see `gte_core::script`
+    pub script: Option<&'a Path>,
+    /// `.gtm` movie, see `gte_core::movie`
+    pub movie: Option<&'a Path>,
+    /// Where to write a Chrome trace of the run, see `gte_core::trace`
+    pub trace: Option<&'a Path>,
+}
+
+/// Run `gtr_path` for up to `frames` frames, using the hooks in `elf_path`
+/// and the script and movie in `input`. Mismatched frames are written as
+/// PNGs to `out_dir`.
+pub fn do_test(gtr_path: &Path, elf_path: &Path, golden_dir: &Path, out_dir: &Path, frames: usize, bless: bool, input: TestInput) -> Result<(), String> {
+    let rom = std::fs::read(gtr_path)
+        .map_err(|e| format!("Failed to read {}: {}", gtr_path.display(), e))?;
+    let symbols = read_symbols(elf_path)?;
+    let hooks = Hooks::find(&symbols)?;
+    let overruns = symbols.iter()
+        .find(|(name, address)| name == OVERRUNS_SYMBOL && *address < 0x2000)
+        .map(|&(_, address)| address as u16);
+
+    let script = match input.script {
+        Some(path) => {
+            let source = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let mut script = Script::compile(&source)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            script.set_symbols(symbols.iter()
+                .filter(|(_, address)| *address < 0x2000)
+                .map(|(name, address)| (name.clone(), *address as u16)));
+            Some(script)
+        }
+        None => None,
+    };
+
+    let mut emulator = Emulator::init(NoClock, 44100.0);
+    emulator.load_rom(&rom);
+
+    let mut frames = frames;
+    if let Some(path) = input.movie {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let movie = Movie::from_bytes(&bytes)
+            .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+        println!("  playing {} ({} frames)", path.display(), movie.len());
+        frames = frames.max(movie.len());
+        emulator.play_movie(movie)
+            .map_err(|e| format!("Failed to play {}: {}", path.display(), e))?;
+    }
+
+    if input.trace.is_some() {
+        emulator.start_trace();
+    }
+
+    println!("Testing {} for up to {} frames", gtr_path.display(), frames);
+    if hooks.is_none() {
+        println!("  no test hooks in the ROM; checking that it runs");
+    }
+
+    let mut run = TestRun { emulator, hooks, script, golden_dir, out_dir, bless, frame: 0 };
+    let outcome = loop {
+        if let Some(outcome) = run.step() {
+            break outcome;
+        }
+        if run.frame >= frames {
+            break match run.hooks {
+                Some(_) => Outcome::Failed(format!("timed out after {} frames without passing", frames)),
+                None => Outcome::Passed,
+            };
+        }
+    };
+
+    // written whether or not the test passed, to see why it didn't
+    if let (Some(path), Some(trace)) = (input.trace, run.emulator.stop_trace()) {
+        std::fs::write(path, trace.to_chrome_json())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        println!("  wrote a trace of {} frames to {}", trace.frames(), path.display());
+    }
+
+    if let Some(address) = overruns {
+        let count = u16::from_le_bytes([run.peek(address), run.peek(address + 1)]);
+        println!("  {} frame(s) overran their vblank", count);
+    }
+
+    match outcome {
+        Outcome::Passed => {
+            println!("Test passed after {} frames", run.frame);
+            Ok(())
+        }
+        Outcome::Failed(reason) => Err(format!("Test failed: {}", reason)),
+    }
+}
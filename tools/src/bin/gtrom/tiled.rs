@@ -0,0 +1,377 @@
+//! Tiled map (.tmx) conversion
+//!
+//! Turns a map from the [Tiled](https://www.mapeditor.org/) editor into
+//! what the SDK's `gfx::tilemap` engine draws:
+//!
+//! - the tileset image, cut into 16x16 tiles and laid out 8 to a row as
+//!   sprite RAM data (`<name>_tiles.bin`), so tile `n` is `Tileset::source(n)`
+//! - a `[[u8; W]; H]` of tile indices for each tile layer
+//! - a `&[Spawn]` table from the object layers, with a `<NAME>_KIND_*`
+//!   constant for each object class
+//!
+//! Maps must be orthogonal, finite, use one tileset of at most 256 16x16
+//! tiles, and store tile layers as CSV or XML (Tiled's default is CSV).
+//! Empty cells become tile 0.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use roxmltree::{Document, Node};
+
+use crate::sprite::{const_name, Dither, SpriteSheet};
+
+/// Tile size the SDK's tilemap engine draws
+const TILE_SIZE: u32 = 16;
+/// Tiles per row in the converted tileset, one quadrant wide
+const SHEET_COLUMNS: u32 = 8;
+/// Tile indices are bytes
+const MAX_TILES: u32 = 256;
+
+/// Tiled keeps flip and rotation flags in the top bits of a GID
+const GID_FLAGS: u32 = 0xF000_0000;
+
+struct Tileset {
+    first_gid: u32,
+    columns: u32,
+    count: u32,
+    spacing: u32,
+    margin: u32,
+    image: PathBuf,
+}
+
+struct Layer {
+    name: String,
+    gids: Vec<u32>,
+}
+
+struct Object {
+    kind: String,
+    x: f64,
+    y: f64,
+}
+
+struct Map {
+    width: usize,
+    height: usize,
+    tileset: Tileset,
+    layers: Vec<Layer>,
+    objects: Vec<Object>,
+}
+
+fn attr<T: FromStr>(node: Node, name: &str, file: &Path) -> Result<T, String> {
+    let value = node.attribute(name)
+        .ok_or_else(|| format!("{}: <{}> has no {}", file.display(), node.tag_name().name(), name))?;
+    value.parse()
+        .map_err(|_| format!("{}: invalid {} \"{}\" on <{}>", file.display(), name, value, node.tag_name().name()))
+}
+
+fn attr_or<T: FromStr>(node: Node, name: &str, default: T, file: &Path) -> Result<T, String> {
+    match node.attribute(name) {
+        Some(_) => attr(node, name, file),
+        None => Ok(default),
+    }
+}
+
+fn read_xml(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+fn parse_xml<'a>(text: &'a str, path: &Path) -> Result<Document<'a>, String> {
+    Document::parse(text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+impl Tileset {
+    /// Read a `<tileset>`, following it to a .tsx if it's external
+    fn parse(node: Node, map_path: &Path) -> Result<Self, String> {
+        let first_gid = attr(node, "firstgid", map_path)?;
+
+        match node.attribute("source") {
+            Some(source) => {
+                let tsx_path = relative_to(map_path, source);
+                let text = read_xml(&tsx_path)?;
+                let doc = parse_xml(&text, &tsx_path)?;
+                Self::parse_inline(doc.root_element(), first_gid, &tsx_path)
+            }
+            None => Self::parse_inline(node, first_gid, map_path),
+        }
+    }
+
+    fn parse_inline(node: Node, first_gid: u32, path: &Path) -> Result<Self, String> {
+        let (width, height): (u32, u32) = (attr(node, "tilewidth", path)?, attr(node, "tileheight", path)?);
+        if (width, height) != (TILE_SIZE, TILE_SIZE) {
+            return Err(format!("{}: tiles are {}x{}, but the tilemap engine uses {}x{}", path.display(), width, height, TILE_SIZE, TILE_SIZE));
+        }
+
+        let image = node.children().find(|n| n.has_tag_name("image"))
+            .ok_or_else(|| format!("{}: tilesets made of separate images aren't supported", path.display()))?;
+
+        let tileset = Self {
+            first_gid,
+            columns: attr(node, "columns", path)?,
+            count: attr(node, "tilecount", path)?,
+            spacing: attr_or(node, "spacing", 0, path)?,
+            margin: attr_or(node, "margin", 0, path)?,
+            image: relative_to(path, &attr::<String>(image, "source", path)?),
+        };
+        if tileset.count > MAX_TILES {
+            return Err(format!("{}: {} tiles, but a tileset can have at most {}", path.display(), tileset.count, MAX_TILES));
+        }
+        Ok(tileset)
+    }
+
+    /// The tiles, 8 to a row, quantized to sprite RAM data
+    fn to_sprite_sheet(&self) -> Result<SpriteSheet, String> {
+        let source = image::open(&self.image)
+            .map_err(|e| format!("Failed to open {}: {}", self.image.display(), e))?
+            .to_rgba8();
+
+        let rows = self.count.div_ceil(SHEET_COLUMNS).max(1);
+        let mut sheet = image::RgbaImage::new(SHEET_COLUMNS * TILE_SIZE, rows * TILE_SIZE);
+
+        for tile in 0..self.count {
+            let sx = self.margin + (tile % self.columns.max(1)) * (TILE_SIZE + self.spacing);
+            let sy = self.margin + (tile / self.columns.max(1)) * (TILE_SIZE + self.spacing);
+            let (dx, dy) = ((tile % SHEET_COLUMNS) * TILE_SIZE, (tile / SHEET_COLUMNS) * TILE_SIZE);
+            if sx + TILE_SIZE > source.width() || sy + TILE_SIZE > source.height() {
+                return Err(format!("{}: tile {} is outside the image", self.image.display(), tile));
+            }
+
+            for y in 0..TILE_SIZE {
+                for x in 0..TILE_SIZE {
+                    sheet.put_pixel(dx + x, dy + y, *source.get_pixel(sx + x, sy + y));
+                }
+            }
+        }
+
+        Ok(SpriteSheet::from_image(&sheet, Dither::None))
+    }
+
+    /// Tile index of a layer cell, 0 for an empty one
+    fn tile_of(&self, gid: u32) -> Result<u8, String> {
+        let gid = gid & !GID_FLAGS;
+        if gid == 0 {
+            return Ok(0);
+        }
+        match gid.checked_sub(self.first_gid) {
+            Some(tile) if tile < self.count => Ok(tile as u8),
+            _ => Err(format!("tile {} isn't in the tileset", gid)),
+        }
+    }
+}
+
+/// A path in a Tiled file is relative to that file
+fn relative_to(file: &Path, path: &str) -> PathBuf {
+    file.parent().unwrap_or(Path::new("")).join(path)
+}
+
+fn parse_layer(node: Node, width: usize, height: usize, path: &Path) -> Result<Layer, String> {
+    let name: String = attr_or(node, "name", "layer".to_string(), path)?;
+    let data = node.children().find(|n| n.has_tag_name("data"))
+        .ok_or_else(|| format!("{}: layer \"{}\" has no data", path.display(), name))?;
+
+    let gids: Vec<u32> = match data.attribute("encoding") {
+        Some("csv") => data.text().unwrap_or_default()
+            .split(',')
+            .map(|cell| cell.trim().parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("{}: layer \"{}\" has invalid CSV: {}", path.display(), name, e))?,
+        None if data.children().any(|n| n.has_tag_name("chunk")) => {
+            return Err(format!("{}: infinite maps aren't supported", path.display()));
+        }
+        None => data.children()
+            .filter(|n| n.has_tag_name("tile"))
+            .map(|tile| attr_or(tile, "gid", 0, path))
+            .collect::<Result<_, _>>()?,
+        Some(encoding) => {
+            return Err(format!(
+                "{}: layer \"{}\" is {}; set Tile Layer Format to CSV in the map properties",
+                path.display(), name, encoding,
+            ));
+        }
+    };
+
+    if gids.len() != width * height {
+        return Err(format!("{}: layer \"{}\" has {} tiles, expected {}", path.display(), name, gids.len(), width * height));
+    }
+    Ok(Layer { name, gids })
+}
+
+fn parse_objects(node: Node, path: &Path, objects: &mut Vec<Object>) -> Result<(), String> {
+    for object in node.children().filter(|n| n.has_tag_name("object")) {
+        let kind = object.attribute("class")
+            .or(object.attribute("type"))
+            .filter(|k| !k.is_empty())
+            .or(object.attribute("name"))
+            .unwrap_or("object")
+            .to_string();
+
+        let x: f64 = attr_or(object, "x", 0.0, path)?;
+        let mut y: f64 = attr_or(object, "y", 0.0, path)?;
+        // tile objects hang up from their position
+        if object.attribute("gid").is_some() {
+            y -= attr_or(object, "height", 0.0, path)?;
+        }
+        objects.push(Object { kind, x, y });
+    }
+    Ok(())
+}
+
+/// Collect tile and object layers in order, looking inside groups
+fn parse_layers(node: Node, map: &mut Map, path: &Path) -> Result<(), String> {
+    for child in node.children() {
+        match child.tag_name().name() {
+            "layer" => map.layers.push(parse_layer(child, map.width, map.height, path)?),
+            "objectgroup" => parse_objects(child, path, &mut map.objects)?,
+            "group" => parse_layers(child, map, path)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl Map {
+    fn load(path: &Path) -> Result<Self, String> {
+        let text = read_xml(path)?;
+        let doc = parse_xml(&text, path)?;
+        let root = doc.root_element();
+
+        if root.attribute("orientation") != Some("orthogonal") {
+            return Err(format!("{}: only orthogonal maps are supported", path.display()));
+        }
+        if root.attribute("infinite") == Some("1") {
+            return Err(format!("{}: infinite maps aren't supported", path.display()));
+        }
+
+        let tilesets: Vec<Node> = root.children().filter(|n| n.has_tag_name("tileset")).collect();
+        let tileset = match tilesets.as_slice() {
+            [one] => Tileset::parse(*one, path)?,
+            [] => return Err(format!("{}: map has no tileset", path.display())),
+            _ => return Err(format!("{}: map uses {} tilesets; merge them into one", path.display(), tilesets.len())),
+        };
+
+        let mut map = Map {
+            width: attr(root, "width", path)?,
+            height: attr(root, "height", path)?,
+            tileset,
+            layers: vec![],
+            objects: vec![],
+        };
+        parse_layers(root, &mut map, path)?;
+        Ok(map)
+    }
+}
+
+/// Rust array literal for a layer, one map row per line
+fn layer_rows(map: &Map, layer: &Layer) -> Result<String, String> {
+    let mut rows = String::new();
+    for row in layer.gids.chunks(map.width) {
+        let tiles = row.iter()
+            .map(|&gid| map.tileset.tile_of(gid).map(|t| t.to_string()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("layer \"{}\": {}", layer.name, e))?;
+        rows.push_str(&format!("    [{}],\n", tiles.join(", ")));
+    }
+    Ok(rows)
+}
+
+/// Convert a .tmx into `<output>.rs` and `<output>_tiles.bin`. With
+/// `banked`, layers go in auto-banked ROM sections with bank table entries.
+pub fn convert_tmx(input: &str, output: Option<&str>, banked: bool) -> Result<(), String> {
+    let input_path = Path::new(input);
+    let map = Map::load(input_path)?;
+
+    let rs_path = match output {
+        Some(out) => Path::new(out).with_extension("rs"),
+        None => input_path.with_extension("rs"),
+    };
+    let stem = rs_path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "map".to_string());
+    let name = const_name(&stem);
+    let bin_path = rs_path.with_file_name(format!("{}_tiles.bin", stem));
+    let bin_name = format!("{}_tiles.bin", stem);
+
+    println!(
+        "Converting Tiled map: {} ({}x{} tiles, {} layer(s), {} object(s)) -> {}",
+        input, map.width, map.height, map.layers.len(), map.objects.len(), rs_path.display()
+    );
+
+    let sheet = map.tileset.to_sprite_sheet()?;
+    std::fs::write(&bin_path, &sheet.data)
+        .map_err(|e| format!("Failed to write {}: {}", bin_path.display(), e))?;
+
+    let mut module = format!(
+        "// Generated by `gtrom convert` from {input}. Do not edit.\n\
+         \n\
+         /// Map width in tiles\n\
+         pub const {name}_WIDTH: usize = {width};\n\
+         /// Map height in tiles\n\
+         pub const {name}_HEIGHT: usize = {height};\n\
+         \n\
+         /// The tileset as sprite RAM data, 8 tiles to a row. Load it with\n\
+         /// `SpriteSheet` and draw from it with `Tileset::new(page)`.\n\
+         pub static {name}_TILES: &[u8; {len}] = include_bytes!(\"{bin_name}\");\n",
+        width = map.width,
+        height = map.height,
+        len = sheet.data.len(),
+    );
+
+    for layer in &map.layers {
+        let layer_name = format!("{}_{}", name, const_name(&layer.name));
+        let rows = layer_rows(&map, layer).map_err(|e| format!("{}: {}", input, e))?;
+        let ty = format!("[[u8; {}]; {}]", map.width, map.height);
+
+        module.push_str(&format!("\n/// Tile layer \"{}\"\n", layer.name));
+        if banked {
+            module.push_str(&format!(
+                "#[unsafe(no_mangle)]\n\
+                 #[unsafe(link_section = \".rodata.auto.{section}\")]\n\
+                 pub static {layer_name}: {ty} = [\n{rows}];\n\
+                 gametank::bank_table!(pub {layer_name}_BANK => {layer_name});\n",
+                section = layer_name.to_lowercase(),
+            ));
+        } else {
+            module.push_str(&format!("pub const {layer_name}: {ty} = [\n{rows}];\n"));
+        }
+    }
+
+    let kinds: Vec<&str> = map.objects.iter()
+        .map(|o| o.kind.as_str())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    if kinds.len() > 256 {
+        return Err(format!("{}: {} object classes, but kinds are bytes", input, kinds.len()));
+    }
+
+    if !map.objects.is_empty() {
+        module.push('\n');
+        for (i, kind) in kinds.iter().enumerate() {
+            module.push_str(&format!("/// Objects of class \"{}\"\npub const {}_KIND_{}: u8 = {};\n", kind, name, const_name(kind), i));
+        }
+    }
+
+    module.push_str(&format!(
+        "\n/// Objects from the map's object layers, in pixels from its top-left\n\
+         pub static {name}_SPAWNS: &[gametank::gfx::tilemap::Spawn] = &[\n",
+    ));
+    for object in &map.objects {
+        module.push_str(&format!(
+            "    gametank::gfx::tilemap::Spawn {{ kind: {}_KIND_{}, x: {}, y: {} }},\n",
+            name,
+            const_name(&object.kind),
+            object.x.round().clamp(0.0, u16::MAX as f64) as u16,
+            object.y.round().clamp(0.0, u16::MAX as f64) as u16,
+        ));
+    }
+    module.push_str("];\n");
+
+    std::fs::write(&rs_path, module)
+        .map_err(|e| format!("Failed to write {}: {}", rs_path.display(), e))?;
+    println!("Wrote tileset ({} tiles): {}", map.tileset.count, bin_path.display());
+    println!("Wrote Rust module: {}", rs_path.display());
+
+    Ok(())
+}
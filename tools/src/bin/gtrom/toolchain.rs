@@ -0,0 +1,358 @@
+//! Toolchain detection
+//!
+//! With `toolchain = "auto"`, the first of these that works is used:
+//!
+//! 1. the `mos` rustup toolchain (`cargo +mos`)
+//! 2. llvm-mos on PATH, with a `cargo` that can target `mos-unknown-none`
+//! 3. podman
+//! 4. docker
+//!
+//! The choice is saved to gtrom.toml so later builds skip the probing.
+//!
+//! `gtrom toolchain install` sets one up: a prebuilt rust-mos (which
+//! carries llvm-mos) downloaded to `~/.gametank/toolchains` and linked as
+//! the `mos` rustup toolchain, or with `--container`, the build image.
+//! The prebuilt archives are listed in a manifest with their SHA-256, and a
+//! download that doesn't match is thrown away.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use flate2::read::GzDecoder;
+use gametank_project::config::{record_toolchain, Config, Toolchain};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::container::{is_in_container, select_runtime};
+
+/// Where `gtrom toolchain install` looks for prebuilt toolchains by default
+pub const MANIFEST_URL: &str = "https://github.com/dwbrite/gametank-sdk/releases/download/toolchain/manifest.toml";
+
+/// Built into the container image when pulling it fails
+const CONTAINERFILE: &str = include_str!("../../../rust-mos-container/Containerfile");
+
+/// Bytes downloaded between progress updates
+const PROGRESS_STEP: u64 = 1 << 20;
+
+/// Run `cmd args` and return its stdout, if it ran and succeeded
+fn output_of(cmd: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(cmd).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn has_rustup_mos() -> bool {
+    output_of("rustup", &["toolchain", "list"])
+        .is_some_and(|list| list.lines().any(|l| l.split_whitespace().next() == Some("mos")))
+}
+
+fn has_llvm_mos() -> bool {
+    // llvm-mc lists the targets it was built with
+    let assembles_mos = output_of("llvm-mc", &["--version"])
+        .is_some_and(|v| v.split_whitespace().any(|w| w == "mos"));
+    let compiles_mos = output_of("rustc", &["--print", "target-list"])
+        .is_some_and(|targets| targets.lines().any(|t| t == "mos-unknown-none"));
+    assembles_mos && compiles_mos
+}
+
+fn is_installed(cmd: &str) -> bool {
+    output_of(cmd, &["--version"]).is_some()
+}
+
+/// Whether `toolchain` looks usable on this machine
+pub fn is_available(toolchain: Toolchain) -> bool {
+    match toolchain {
+        Toolchain::Auto => false,
+        Toolchain::RustupMos => has_rustup_mos(),
+        Toolchain::LlvmMos => has_llvm_mos(),
+        Toolchain::Podman => is_installed("podman"),
+        Toolchain::Docker => is_installed("docker"),
+    }
+}
+
+/// The first usable toolchain, in the order listed in the module docs
+pub fn detect() -> Option<Toolchain> {
+    [Toolchain::RustupMos, Toolchain::LlvmMos, Toolchain::Podman, Toolchain::Docker]
+        .into_iter()
+        .find(|&t| is_available(t))
+}
+
+/// The toolchain to build with. Resolves `auto` by probing, and saves what
+/// it found to gtrom.toml in `project_dir`.
+pub fn resolve(config: &Config, project_dir: &Path) -> Result<Toolchain, String> {
+    if config.toolchain != Toolchain::Auto {
+        return Ok(config.toolchain);
+    }
+
+    // The build image has the mos toolchain. Don't save it: the project
+    // directory is shared with the host, which has to go through the container.
+    if is_in_container() {
+        return Ok(Toolchain::RustupMos);
+    }
+
+    let toolchain = detect().ok_or_else(|| {
+        "No toolchain found. Install the mos rustup toolchain, llvm-mos, podman or docker.".to_string()
+    })?;
+
+    record_toolchain(project_dir, toolchain)?;
+    println!("Using the {} toolchain (saved to gtrom.toml)", toolchain.name());
+    Ok(toolchain)
+}
+
+/// The prebuilt toolchains on offer
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    /// Installed to a directory of this name, so versions sit side by side
+    version: String,
+    #[serde(default)]
+    archive: Vec<Archive>,
+}
+
+/// A `.tar.gz` with a `rust-mos` directory that rustup can link
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Archive {
+    /// `<arch>-<os>`, as Rust names them (`x86_64-linux`, `aarch64-macos`)
+    host: String,
+    /// A URL, or a path on this machine
+    url: String,
+    sha256: String,
+}
+
+/// This machine, as [`Archive::host`] names it
+fn host() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Where installed toolchains live: `$GAMETANK_HOME/toolchains`, or
+/// `~/.gametank/toolchains`
+pub fn install_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("GAMETANK_HOME").map(PathBuf::from).or_else(|| {
+        std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).map(|home| PathBuf::from(home).join(".gametank"))
+    })?;
+    Some(home.join("toolchains"))
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("https://") || location.starts_with("http://")
+}
+
+/// A reader for `location`, a URL or a file, and its length if known
+fn open(location: &str) -> Result<(Box<dyn Read>, Option<u64>), String> {
+    if is_url(location) {
+        let response = ureq::get(location).call()
+            .map_err(|e| format!("Failed to download {}: {}", location, e))?;
+        let len = response.header("Content-Length").and_then(|len| len.parse().ok());
+        Ok((Box::new(response.into_reader()), len))
+    } else {
+        let file = File::open(location)
+            .map_err(|e| format!("Failed to open {}: {}", location, e))?;
+        let len = file.metadata().ok().map(|m| m.len());
+        Ok((Box::new(file), len))
+    }
+}
+
+fn fetch_manifest(location: &str) -> Result<Manifest, String> {
+    let (mut reader, _) = open(location)?;
+    let mut text = String::new();
+    reader.read_to_string(&mut text)
+        .map_err(|e| format!("Failed to read {}: {}", location, e))?;
+    toml::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", location, e))
+}
+
+/// Copy `location` to `dest`, showing progress, and check it hashes to
+/// `sha256`. A mismatched download is deleted.
+fn download(location: &str, dest: &Path, sha256: &str) -> Result<(), String> {
+    let (mut reader, len) = open(location)?;
+    let mut file = File::create(dest)
+        .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let (mut done, mut shown) = (0u64, 0u64);
+
+    loop {
+        let n = reader.read(&mut buf)
+            .map_err(|e| format!("Failed to download {}: {}", location, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        file.write_all(&buf[..n])
+            .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+        done += n as u64;
+        if done - shown >= PROGRESS_STEP {
+            shown = done;
+            print_progress(done, len);
+        }
+    }
+    print_progress(done, len);
+    println!();
+
+    let hash: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    if !hash.eq_ignore_ascii_case(sha256.trim()) {
+        let _ = std::fs::remove_file(dest);
+        return Err(format!("Checksum mismatch for {}: expected {}, got {}", location, sha256, hash));
+    }
+    println!("  checksum ok");
+    Ok(())
+}
+
+fn print_progress(done: u64, len: Option<u64>) {
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    match len {
+        Some(len) if len > 0 => print!("\r  {:.1} / {:.1} MB ({}%)", mb(done), mb(len), done * 100 / len),
+        _ => print!("\r  {:.1} MB", mb(done)),
+    }
+    let _ = std::io::stdout().flush();
+}
+
+/// `rustup toolchain link mos`, so `cargo +mos` uses `dir`
+fn link_rustup(dir: &Path) -> Result<(), String> {
+    let status = Command::new("rustup")
+        .args(["toolchain", "link", "mos"])
+        .arg(dir)
+        .status()
+        .map_err(|e| format!("Failed to run rustup (install it from https://rustup.rs): {}", e))?;
+    if !status.success() {
+        return Err("Failed to link the toolchain with rustup".to_string());
+    }
+    Ok(())
+}
+
+/// Download and link the prebuilt toolchain for this machine from the
+/// manifest at `manifest`, a URL or a file
+pub fn install_prebuilt(manifest: &str, force: bool) -> Result<(), String> {
+    println!("Reading {}", manifest);
+    let manifest = fetch_manifest(manifest)?;
+    let host = host();
+    let archive = manifest.archive.iter().find(|a| a.host == host).ok_or_else(|| {
+        format!("No prebuilt toolchain for {}; try `gtrom toolchain install --container`", host)
+    })?;
+
+    let root = install_dir().ok_or("Can't find a home directory to install to; set GAMETANK_HOME")?;
+    let dir = root.join(&manifest.version);
+    let toolchain = dir.join("rust-mos");
+
+    if toolchain.exists() && !force {
+        println!("Toolchain {} is already in {}", manifest.version, dir.display());
+    } else {
+        std::fs::create_dir_all(&root)
+            .map_err(|e| format!("Failed to create {}: {}", root.display(), e))?;
+        let tarball = root.join(format!("{}.tar.gz", manifest.version));
+        println!("Downloading toolchain {} for {}", manifest.version, host);
+        download(&archive.url, &tarball, &archive.sha256)?;
+
+        // unpack beside the old one and swap, so a failure leaves it working
+        let partial = root.join(format!("{}.partial", manifest.version));
+        let _ = std::fs::remove_dir_all(&partial);
+        println!("Unpacking to {}", dir.display());
+        let file = File::open(&tarball)
+            .map_err(|e| format!("Failed to open {}: {}", tarball.display(), e))?;
+        tar::Archive::new(GzDecoder::new(file)).unpack(&partial)
+            .map_err(|e| format!("Failed to unpack {}: {}", tarball.display(), e))?;
+        if !partial.join("rust-mos").is_dir() {
+            return Err(format!("{} has no rust-mos directory", archive.url));
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::rename(&partial, &dir)
+            .map_err(|e| format!("Failed to move {} to {}: {}", partial.display(), dir.display(), e))?;
+        let _ = std::fs::remove_file(&tarball);
+    }
+
+    link_rustup(&toolchain)?;
+    println!("Installed: `cargo +mos` now uses {}", toolchain.display());
+    Ok(())
+}
+
+/// Get the build image for podman or docker: pull it, or build it from the
+/// SDK's Containerfile if that fails
+pub fn install_container(config: &Config, force: bool) -> Result<(), String> {
+    let toolchain = if config.toolchain.is_container() {
+        config.toolchain
+    } else {
+        [Toolchain::Podman, Toolchain::Docker].into_iter()
+            .find(|&t| is_available(t))
+            .ok_or("Neither podman nor docker is installed")?
+    };
+    let runtime = select_runtime(toolchain)
+        .ok_or_else(|| format!("{} isn't installed", toolchain.name()))?;
+    let image = &config.container.image;
+
+    if runtime.has_image(image) && !force {
+        println!("{} already has {}", runtime.command(), image);
+        return Ok(());
+    }
+
+    println!("Pulling {} with {}", image, runtime.command());
+    // the registry checks each layer against its digest as it comes down
+    if runtime.pull(image)? {
+        println!("Installed {}", image);
+        return Ok(());
+    }
+
+    println!("Pulling failed; building {} from the SDK's Containerfile instead", image);
+    let dir = std::env::temp_dir().join("gtrom-container");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let containerfile = dir.join("Containerfile");
+    std::fs::write(&containerfile, CONTAINERFILE)
+        .map_err(|e| format!("Failed to write {}: {}", containerfile.display(), e))?;
+    let built = runtime.build(image, &containerfile, &dir)?;
+    let _ = std::fs::remove_dir_all(&dir);
+    if !built {
+        return Err(format!("Failed to build {}", image));
+    }
+    println!("Installed {}", image);
+    Ok(())
+}
+
+/// Where rustup's `mos` toolchain lives, if there is one
+fn rustup_mos_path() -> Option<String> {
+    output_of("rustup", &["toolchain", "list", "-v"])?.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        (words.next() == Some("mos")).then(|| words.last().unwrap_or("").to_string())
+    })
+}
+
+/// `gtrom toolchain status`: what's installed, and what `config` builds with
+pub fn print_status(config: &Config) {
+    let found = |available: bool| if available { "found" } else { "not found" };
+
+    println!("Toolchains on this machine:");
+    match rustup_mos_path() {
+        Some(path) => println!("  rustup-mos  found at {}", path),
+        None => println!("  rustup-mos  not found"),
+    }
+    println!("  llvm-mos    {}", found(has_llvm_mos()));
+    for toolchain in [Toolchain::Podman, Toolchain::Docker] {
+        let status = match select_runtime(toolchain) {
+            Some(runtime) if runtime.has_image(&config.container.image) => format!("found, with {}", config.container.image),
+            Some(_) => format!("found, without {} (`gtrom toolchain install --container` gets it)", config.container.image),
+            None => "not found".to_string(),
+        };
+        println!("  {:<10}  {}", toolchain.name(), status);
+    }
+
+    if let Some(root) = install_dir() {
+        let mut versions: Vec<String> = std::fs::read_dir(&root).into_iter().flatten().flatten()
+            .filter(|entry| entry.path().join("rust-mos").is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        versions.sort();
+        if !versions.is_empty() {
+            println!("Installed by gtrom in {}: {}", root.display(), versions.join(", "));
+        }
+    }
+
+    let using = match config.toolchain {
+        Toolchain::Auto => match detect() {
+            Some(found) => format!("auto (would use {})", found.name()),
+            None => "auto (nothing found; run `gtrom toolchain install`)".to_string(),
+        },
+        t if is_available(t) => t.name().to_string(),
+        t => format!("{} (not found; run `gtrom toolchain install`)", t.name()),
+    };
+    println!("This project builds with: {}", using);
+}
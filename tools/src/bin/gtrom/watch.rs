@@ -0,0 +1,116 @@
+//! File watching for `gtrom build --watch` and `gtrom run --hold`
+//!
+//! Watches source and asset directories and invokes a rebuild callback
+//! whenever something changes. Bursts of events are debounced so a single
+//! save only triggers one rebuild.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use gametank_project::manifest::MANIFEST_FILE;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::bundle::GENERATED_FILE;
+
+/// How long the filesystem must stay quiet before rebuilding
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Collect the paths worth watching for a project: `src` plus the asset
+/// directories from gtrom.toml, relative to the ROM dir or project root
+pub fn watch_paths(working_dir: &Path, rom_dir: &Path, asset_dirs: &[String]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let dirs = std::iter::once("src").chain(asset_dirs.iter().map(|d| d.as_str()));
+
+    for base in [rom_dir, working_dir] {
+        for dir in dirs.clone() {
+            let path = base.join(dir);
+            if path.is_dir() && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    // build.rs, Cargo.toml and assets.toml changes also need a rebuild
+    for file in ["Cargo.toml", "build.rs", MANIFEST_FILE] {
+        let path = rom_dir.join(file);
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+
+    paths
+}
+
+/// Ignore events we'd generate ourselves (build output, editor swap files)
+fn is_relevant(event: &Event) -> bool {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+
+    event.paths.iter().any(|p| {
+        let in_target = p.components().any(|c| c.as_os_str() == "target");
+        let name = p.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        let is_temp = name.starts_with(".#") || name.ends_with('~') || name.ends_with(".swp");
+        let is_generated = p.ends_with(GENERATED_FILE);
+        !in_target && !is_temp && !is_generated
+    })
+}
+
+/// How often [`watch_while`] checks whether to keep going
+const POLL: Duration = Duration::from_millis(250);
+
+/// Block forever, calling `rebuild` after every batch of changes
+pub fn watch(paths: &[PathBuf], rebuild: impl FnMut()) -> Result<(), String> {
+    watch_while(paths, || true, rebuild)
+}
+
+/// Like [`watch`], but return once `running` says to stop
+pub fn watch_while(paths: &[PathBuf], mut running: impl FnMut() -> bool, mut rebuild: impl FnMut()) -> Result<(), String> {
+    let (tx, rx) = mpsc::channel();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        if let Ok(event) = res {
+            let _ = tx.send(event);
+        }
+    }).map_err(|e| format!("Failed to create file watcher: {}", e))?;
+
+    for path in paths {
+        let mode = if path.is_dir() { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        watcher.watch(path, mode)
+            .map_err(|e| format!("Failed to watch {}: {}", path.display(), e))?;
+        println!("  Watching {}", path.display());
+    }
+
+    println!("Waiting for changes (Ctrl+C to stop)...");
+
+    while running() {
+        let event = match rx.recv_timeout(POLL) {
+            Ok(event) => event,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(e) => return Err(format!("File watcher stopped: {}", e)),
+        };
+        if !is_relevant(&event) {
+            continue;
+        }
+
+        let mut changed: Vec<PathBuf> = event.paths;
+
+        // drain the rest of the burst
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            if is_relevant(&event) {
+                changed.extend(event.paths);
+            }
+        }
+
+        changed.sort();
+        changed.dedup();
+        for path in &changed {
+            println!("Changed: {}", path.display());
+        }
+
+        rebuild();
+        println!("Waiting for changes (Ctrl+C to stop)...");
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! `gtrom convert` for WAV files
+//!
+//! Writes a wavetable or sample made by [`gametank_sdk::wav`], plus a Rust
+//! module that `include_bytes!`s it.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use gametank_sdk::wav::{Audio, Cycle};
+
+use crate::sprite::const_name;
+
+/// What to make from a WAV file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WavMode {
+    /// One pitch-detected cycle, resampled to 256 bytes
+    Wavetable,
+    /// The whole file, resampled to `--rate`
+    Sample,
+}
+
+/// Convert a WAV into a wavetable or sample, plus a Rust module that
+/// `include_bytes!`s it
+pub fn convert_wav(input: &str, output: Option<&str>, mode: WavMode, rate: u32, cycle: Cycle) -> Result<(), String> {
+    let input_path = Path::new(input);
+    let audio = Audio::load(input_path)?;
+
+    let bin_path = match output {
+        Some(out) => Path::new(out).with_extension("bin"),
+        None => input_path.with_extension("bin"),
+    };
+    let rs_path = bin_path.with_extension("rs");
+
+    let stem = rs_path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "sound".to_string());
+    let name = const_name(&stem);
+    let bin_name = bin_path.file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Invalid output path: {}", bin_path.display()))?;
+
+    let (data, module) = match mode {
+        WavMode::Wavetable => {
+            let (data, pitch) = audio.wavetable(cycle)?;
+            println!("Converting WAV to wavetable: {} (detected {:.1} Hz) -> {}", input, pitch, bin_path.display());
+
+            let module = format!(
+                "// Generated by `gtrom convert` from {input}. Do not edit.\n\
+                 \n\
+                 /// Pitch detected in the source recording, in Hz\n\
+                 pub const {name}_SOURCE_HZ: u16 = {pitch};\n\
+                 \n\
+                 /// Single-cycle waveform for a wavetable slot (unsigned, centered on 0x80)\n\
+                 pub static {name}: &[u8; {len}] = include_bytes!(\"{bin_name}\");\n",
+                pitch = pitch.round() as u16,
+                len = data.len(),
+            );
+            (data, module)
+        }
+        WavMode::Sample => {
+            let data = audio.sample(rate);
+            println!(
+                "Converting WAV to sample: {} ({} Hz -> {} Hz, {} bytes) -> {}",
+                input, audio.rate(), rate, data.len(), bin_path.display()
+            );
+
+            let module = format!(
+                "// Generated by `gtrom convert` from {input}. Do not edit.\n\
+                 \n\
+                 /// Playback rate in Hz\n\
+                 pub const {name}_RATE: u32 = {rate};\n\
+                 \n\
+                 /// 8-bit unsigned samples, centered on 0x80\n\
+                 pub static {name}: &[u8; {len}] = include_bytes!(\"{bin_name}\");\n",
+                len = data.len(),
+            );
+            (data, module)
+        }
+    };
+
+    std::fs::write(&bin_path, &data)
+        .map_err(|e| format!("Failed to write {}: {}", bin_path.display(), e))?;
+    std::fs::write(&rs_path, module)
+        .map_err(|e| format!("Failed to write {}: {}", rs_path.display(), e))?;
+    println!("Wrote Rust module: {}", rs_path.display());
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! Saving screenshots and captures from the emulator
+//!
+//! Frames come out of gte-core as palette indices, so both PNG and GIF store
+//! them indexed with the GameTank's 256 colors as the palette: nothing is
+//! lost to quantizing, and the files stay small.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use gte_core::color_map::COLOR_MAP;
+use gte_core::emulator::{CapturedFrame, HEIGHT, WIDTH};
+
+/// Frames per second of the capture
+const FRAME_RATE: u32 = 60;
+
+/// The palette as RGB triples
+fn palette() -> Vec<u8> {
+    COLOR_MAP.iter().flat_map(|&(r, g, b, _)| [r, g, b]).collect()
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>, String> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))
+}
+
+fn png_encoder<'a>(file: BufWriter<File>) -> png::Encoder<'a, BufWriter<File>> {
+    let mut encoder = png::Encoder::new(file, WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette());
+    encoder
+}
+
+/// Write one frame (from `Emulator::screenshot`) as a PNG
+pub fn save_png(pixels: &[u8], path: &Path) -> Result<(), String> {
+    let err = |e: png::EncodingError| format!("Failed to write {}: {}", path.display(), e);
+
+    let mut writer = png_encoder(create(path)?).write_header().map_err(err)?;
+    writer.write_image_data(pixels).map_err(err)?;
+    writer.finish().map_err(err)
+}
+
+/// Write a capture as an animated PNG, with exact 60Hz timing
+fn save_apng(frames: &[CapturedFrame], path: &Path) -> Result<(), String> {
+    let err = |e: png::EncodingError| format!("Failed to write {}: {}", path.display(), e);
+
+    let mut encoder = png_encoder(create(path)?);
+    encoder.set_animated(frames.len() as u32, 0).map_err(err)?;
+    let mut writer = encoder.write_header().map_err(err)?;
+    for frame in frames {
+        writer.set_frame_delay(frame.frames.min(u16::MAX as u32) as u16, FRAME_RATE as u16).map_err(err)?;
+        writer.write_image_data(&frame.pixels[..]).map_err(err)?;
+    }
+    writer.finish().map_err(err)
+}
+
+/// Write a capture as a GIF. GIF delays are in hundredths of a second, so
+/// each frame's delay is rounded with the remainder carried to the next,
+/// keeping the whole thing in time.
+fn save_gif(frames: &[CapturedFrame], path: &Path) -> Result<(), String> {
+    let err = |e: gif::EncodingError| format!("Failed to write {}: {}", path.display(), e);
+
+    let palette = palette();
+    let mut encoder = gif::Encoder::new(create(path)?, WIDTH as u16, HEIGHT as u16, &palette).map_err(err)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(err)?;
+
+    let mut elapsed = 0u64;
+    let mut written_cs = 0u64;
+    for frame in frames {
+        elapsed += frame.frames as u64;
+        let end_cs = elapsed * 100 / FRAME_RATE as u64;
+        let delay = (end_cs - written_cs).min(u16::MAX as u64) as u16;
+        written_cs = end_cs;
+
+        let gif_frame = gif::Frame {
+            width: WIDTH as u16,
+            height: HEIGHT as u16,
+            delay,
+            buffer: Cow::Borrowed(&frame.pixels[..]),
+            ..Default::default()
+        };
+        encoder.write_frame(&gif_frame).map_err(err)?;
+    }
+    Ok(())
+}
+
+/// Write a capture, as a GIF if `path` ends in `.gif` and otherwise as an
+/// animated PNG
+pub fn save_capture(frames: &[CapturedFrame], path: &Path) -> Result<(), String> {
+    if frames.is_empty() {
+        return Err("Nothing was captured".to_string());
+    }
+    let is_gif = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    if is_gif {
+        save_gif(frames, path)
+    } else {
+        save_apng(frames, path)
+    }
+}
+
+/// `<dir>/<stem>-<n>.<ext>` with the first `n` not already taken
+pub fn numbered_path(dir: &Path, stem: &str, ext: &str) -> PathBuf {
+    (1..)
+        .map(|n| dir.join(format!("{}-{}.{}", stem, n, ext)))
+        .find(|path| !path.exists())
+        .unwrap_or_default()
+}
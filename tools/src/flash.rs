@@ -0,0 +1,257 @@
+//! Cartridge flashing, shared by `gtrom flash` and gtgo's ROM flasher
+//!
+//! Talks to the USB cartridge programmer over serial, using the same
+//! line-based command protocol as `gtld`. The ROM is written bank by bank
+//! into the top of the cartridge (bank 127 is always the last bank of the
+//! image), then every bank is read back by checksum to verify it.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use gte_core::rom_header::{crc32, RomHeader};
+use serialport::{SerialPort, SerialPortType};
+
+const BAUD_RATE: u32 = 115_200;
+const BANK_SIZE: usize = 0x4000;
+pub const BANK_COUNT: usize = 128;
+const CHUNK_SIZE: usize = 0x1000;
+
+/// How long to wait for the programmer before giving up on a command
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(20);
+/// Chip erase takes a while on larger flash parts
+const ERASE_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long a port gets to answer during auto-detection
+const PROBE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// How far along [`flash`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// Erasing the whole chip, which takes a few seconds
+    Erasing,
+    /// `done` of `total` banks written, `bank` being the one up next (or
+    /// the last one, once done)
+    Writing { done: usize, total: usize, bank: usize },
+    /// `done` of `total` banks checked
+    Verifying { done: usize, total: usize, bank: usize },
+}
+
+struct Programmer {
+    port: Box<dyn SerialPort>,
+}
+
+impl Programmer {
+    fn open(port_name: &str) -> Result<Self, String> {
+        let port = serialport::new(port_name, BAUD_RATE)
+            .timeout(Duration::from_millis(100))
+            .open()
+            .map_err(|e| format!("Failed to open {}: {}", port_name, e))?;
+
+        Ok(Self { port })
+    }
+
+    fn send(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.port.write_all(bytes)
+            .and_then(|_| self.port.flush())
+            .map_err(|e| format!("Failed to write to programmer: {}", e))
+    }
+
+    fn command(&mut self, cmd: &str) -> Result<(), String> {
+        self.send(format!("{}\r", cmd).as_bytes())
+    }
+
+    /// Discard anything the programmer has already printed
+    fn drain(&mut self) {
+        let mut buf = [0u8; 1024];
+        while matches!(self.port.read(&mut buf), Ok(n) if n > 0) {}
+    }
+
+    /// Read lines until one contains `needle`, returning that line
+    fn wait_for(&mut self, needle: &str, timeout: Duration) -> Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+
+        while Instant::now() < deadline {
+            match self.port.read(&mut byte) {
+                Ok(1) if byte[0] == b'\n' => {
+                    let text = String::from_utf8_lossy(&line).trim().to_string();
+                    if text.contains(needle) {
+                        return Ok(text);
+                    }
+                    line.clear();
+                }
+                Ok(1) => line.push(byte[0]),
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(format!("Failed to read from programmer: {}", e)),
+            }
+        }
+
+        Err(format!("Timed out waiting for \"{}\" from programmer", needle))
+    }
+
+    fn select_bank(&mut self, bank: usize) -> Result<(), String> {
+        self.command(&format!("shift {:X}", bank))?;
+        sleep(Duration::from_millis(20));
+        self.drain();
+        Ok(())
+    }
+
+    fn write_bank(&mut self, data: &[u8]) -> Result<(), String> {
+        for (i, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+            self.command(&format!("writeMulti {:X} {:X}", i * CHUNK_SIZE, chunk.len()))?;
+            sleep(Duration::from_millis(50));
+            self.send(chunk)?;
+            sleep(Duration::from_millis(20));
+            self.wait_for("ACK", COMMAND_TIMEOUT)?;
+        }
+        Ok(())
+    }
+
+    fn bank_matches(&mut self, expected: u32) -> Result<bool, String> {
+        self.command(&format!("checksum 0 {:X}", BANK_SIZE))?;
+        let line = self.wait_for("CRC32", COMMAND_TIMEOUT)?;
+        // the value is the first hex word after "CRC32"
+        let crc = line.split_once("CRC32")
+            .and_then(|(_, rest)| {
+                rest.split(|c: char| c.is_whitespace() || c == ':' || c == '=')
+                    .map(|word| word.trim_start_matches("0x").trim_start_matches("0X"))
+                    .find_map(|word| u32::from_str_radix(word, 16).ok())
+            })
+            .ok_or_else(|| format!("Unexpected checksum reply from programmer: {}", line))?;
+        Ok(crc == expected)
+    }
+}
+
+/// Ask a port whether it's a cartridge programmer
+pub fn probe(port_name: &str) -> bool {
+    let Ok(mut programmer) = Programmer::open(port_name) else { return false };
+    programmer.drain();
+    programmer.command("").is_ok() && programmer.wait_for(">", PROBE_TIMEOUT).is_ok()
+}
+
+/// The connected USB serial devices, any of which could be the programmer
+pub fn usb_ports() -> Result<Vec<String>, String> {
+    let ports = serialport::available_ports()
+        .map_err(|e| format!("Failed to list serial ports: {}", e))?;
+
+    Ok(ports.into_iter()
+        .filter(|p| matches!(p.port_type, SerialPortType::UsbPort(_)))
+        .map(|p| p.port_name)
+        .collect())
+}
+
+/// Find the programmer among the connected USB serial devices
+pub fn detect_port() -> Result<String, String> {
+    let candidates = usb_ports()?;
+
+    match candidates.as_slice() {
+        [] => Err("No USB serial ports found. Is the programmer plugged in (and are you in the dialout group)?".to_string()),
+        [only] => Ok(only.clone()),
+        _ => {
+            candidates.iter()
+                .find(|p| probe(p))
+                .cloned()
+                .ok_or_else(|| format!(
+                    "No programmer answered on {}; pass --port to choose one",
+                    candidates.join(", "),
+                ))
+        }
+    }
+}
+
+/// Read a .gtr, with or without header, checking it against its header if
+/// it has one. Returns the header, or for a raw image the one
+/// `gametank_rom!` built into it, and the ROM after it.
+pub fn read_gtr(path: &Path) -> Result<(Option<RomHeader>, Vec<u8>), String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let (header, rom) = RomHeader::split(&bytes);
+    let header = match header {
+        Some(header) => {
+            let header = header.map_err(|e| format!("Invalid header in {}: {}", path.display(), e))?;
+            header.validate(rom).map_err(|e| format!("{} does not match its header: {}", path.display(), e))?;
+            Some(header)
+        }
+        None => RomHeader::embedded(rom),
+    };
+
+    Ok((header, rom.to_vec()))
+}
+
+/// Pad a ROM image to whole banks. Padding goes at the front so the reset
+/// vectors stay at the end of bank 127.
+fn to_banks(rom: &[u8]) -> Result<Vec<u8>, String> {
+    if rom.is_empty() {
+        return Err("ROM is empty".to_string());
+    }
+    if rom.len() > BANK_SIZE * BANK_COUNT {
+        return Err(format!("ROM is {} bytes, larger than a 2MB cartridge", rom.len()));
+    }
+
+    let padding = (BANK_SIZE - rom.len() % BANK_SIZE) % BANK_SIZE;
+    let mut image = vec![0xFF; padding];
+    image.extend_from_slice(rom);
+    Ok(image)
+}
+
+/// Erase the cartridge on `port_name` and write `rom` (without its header)
+/// to it, then read it back if `verify` is set. Blocks until done, calling
+/// `progress` along the way.
+pub fn flash(rom: &[u8], port_name: &str, verify: bool, mut progress: impl FnMut(Progress)) -> Result<(), String> {
+    let image = to_banks(rom)?;
+    let banks: Vec<(usize, &[u8])> = image.chunks(BANK_SIZE)
+        .enumerate()
+        .map(|(i, data)| (BANK_COUNT - image.len() / BANK_SIZE + i, data))
+        .collect();
+
+    let mut programmer = Programmer::open(port_name)?;
+    programmer.drain();
+
+    programmer.command("mode f")?;
+    programmer.wait_for("FLASH", COMMAND_TIMEOUT)?;
+    programmer.command("reset")?;
+    programmer.wait_for("OK", COMMAND_TIMEOUT)?;
+
+    progress(Progress::Erasing);
+    programmer.command("eraseChip")?;
+    programmer.wait_for("Done", ERASE_TIMEOUT)?;
+
+    // erased banks already hold 0xFF, so only write the rest; the ones
+    // skipped are still verified, against 0xFF
+    let to_write: Vec<&(usize, &[u8])> = banks.iter()
+        .filter(|(_, data)| data.iter().any(|&b| b != 0xFF))
+        .collect();
+
+    let total = to_write.len();
+    for (done, (bank, data)) in to_write.iter().enumerate() {
+        progress(Progress::Writing { done, total, bank: *bank });
+        programmer.select_bank(*bank)?;
+        programmer.write_bank(data)?;
+    }
+    progress(Progress::Writing { done: total, total, bank: BANK_COUNT - 1 });
+
+    if !verify {
+        return Ok(());
+    }
+
+    let total = banks.len();
+    let mut bad = vec![];
+    for (done, (bank, data)) in banks.iter().enumerate() {
+        progress(Progress::Verifying { done, total, bank: *bank });
+        programmer.select_bank(*bank)?;
+        if !programmer.bank_matches(crc32(data))? {
+            bad.push(format!("{:02X}", bank));
+        }
+    }
+    progress(Progress::Verifying { done: total, total, bank: BANK_COUNT - 1 });
+
+    if !bad.is_empty() {
+        return Err(format!("Verification failed for bank(s) {}; try flashing again", bad.join(", ")));
+    }
+
+    Ok(())
+}
@@ -5,3 +5,22 @@
 //! - gtrom: ROM build tool
 //! - gtgo: TUI toolkit
 //! - gtld: Cartridge loader
+//!
+//! Code more than one of the tools needs lives here:
+//! - [`flash`]: writing ROMs to cartridges, for `gtrom flash` and gtgo
+//! - [`capture`]: screenshots and captures as PNG, APNG and GIF, for gte,
+//!   gtgo and `gtrom test`
+//! - [`link`]: netplay over the emulated link port, for gte and gtgo
+//! - [`tracker`]: tracker songs and `.gtt` projects, for gtgo's tracker and
+//!   `gtrom build`
+//! - [`wav`]: wavetables and samples from WAV files, for `gtrom convert` and
+//!   gtgo's instrument editor, so both make the same bytes
+//!
+//! Finding projects and reading their settings is in the `gametank-project`
+//! crate, for gtrom and gtgo.
+
+pub mod capture;
+pub mod flash;
+pub mod link;
+pub mod tracker;
+pub mod wav;
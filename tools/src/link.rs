@@ -0,0 +1,136 @@
+//! Netplay over the link port: two emulators joined by a TCP socket, as if
+//! by a cable between their expansion ports
+//!
+//! One side hosts and the other joins. Each frame, [`NetLink::pump`] sends
+//! the bytes the game wrote to the port and hands over the ones that came
+//! back. Bytes are sent as they are, with nothing else on the wire.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use gte_core::gametank_bus::LinkPort;
+
+pub const DEFAULT_PORT: u16 = 6502;
+
+/// How long joining waits for the host to answer
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub enum NetLink {
+    /// Hosting, until the other side joins
+    Waiting(TcpListener),
+    Connected(TcpStream),
+}
+
+impl NetLink {
+    /// Wait for the other side on `port`, on every interface so it can be
+    /// on another machine
+    pub fn host(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        listener.set_nonblocking(true)
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        Ok(Self::Waiting(listener))
+    }
+
+    /// Connect to a host at `address`, a `host:port` or just a host
+    pub fn join(address: &str) -> Result<Self, String> {
+        let address = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, DEFAULT_PORT) };
+        let resolved = address.to_socket_addrs()
+            .map_err(|e| format!("Failed to resolve {}: {}", address, e))?
+            .next()
+            .ok_or_else(|| format!("Failed to resolve {}", address))?;
+        let stream = TcpStream::connect_timeout(&resolved, CONNECT_TIMEOUT)
+            .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+        Self::connected(stream)
+    }
+
+    /// Host or join as `--link-host[=PORT]` or `--link-join[=ADDRESS]` say,
+    /// if either is given
+    pub fn from_args() -> Option<Result<Self, String>> {
+        std::env::args().find_map(|arg| {
+            if let Some(port) = arg.strip_prefix("--link-host") {
+                let port = match port.strip_prefix('=') {
+                    Some(port) => port.parse().map_err(|e| format!("Failed to parse link port {}: {}", port, e)),
+                    None if port.is_empty() => Ok(DEFAULT_PORT),
+                    None => return None,
+                };
+                Some(port.and_then(Self::host))
+            } else if let Some(address) = arg.strip_prefix("--link-join") {
+                match address.strip_prefix('=') {
+                    Some(address) => Some(Self::join(address)),
+                    None if address.is_empty() => Some(Self::join("127.0.0.1")),
+                    None => None,
+                }
+            } else {
+                None
+            }
+        })
+    }
+
+    fn connected(stream: TcpStream) -> Result<Self, String> {
+        stream.set_nonblocking(true)
+            .and_then(|_| stream.set_nodelay(true))
+            .map_err(|e| format!("Failed to set up link: {}", e))?;
+        Ok(Self::Connected(stream))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self, Self::Connected(_))
+    }
+
+    /// What's going on, for showing in a status line
+    pub fn status(&self) -> String {
+        match self {
+            Self::Waiting(listener) => match listener.local_addr() {
+                Ok(address) => format!("waiting on port {}", address.port()),
+                Err(_) => "waiting".to_string(),
+            },
+            Self::Connected(stream) => match stream.peer_addr() {
+                Ok(address) => format!("linked to {}", address),
+                Err(_) => "linked".to_string(),
+            },
+        }
+    }
+
+    /// Accept the other side if it's arrived, and trade bytes with it. Call
+    /// once a frame. An error means the link is gone, and `port` has been
+    /// unplugged.
+    pub fn pump(&mut self, port: &mut LinkPort) -> Result<(), String> {
+        let result = self.trade(port);
+        if result.is_err() {
+            port.disconnect();
+        }
+        result
+    }
+
+    fn trade(&mut self, port: &mut LinkPort) -> Result<(), String> {
+        if let Self::Waiting(listener) = self {
+            match listener.accept() {
+                Ok((stream, _)) => *self = Self::connected(stream)?,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(format!("Failed to accept link: {}", e)),
+            }
+        }
+        let Self::Connected(stream) = self else { return Ok(()) };
+        port.connected = true;
+
+        if !port.outgoing.is_empty() {
+            let (first, second) = port.outgoing.as_slices();
+            stream.write_all(first)
+                .and_then(|_| stream.write_all(second))
+                .map_err(|e| format!("Failed to send over link: {}", e))?;
+            port.outgoing.clear();
+        }
+
+        let mut buf = [0u8; 256];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return Err("the other side disconnected".to_string()),
+                Ok(n) => buf[..n].iter().for_each(|&byte| port.receive(byte)),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(format!("Failed to receive over link: {}", e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Song export
+//!
+//! Serializes `TrackerData` into the byte stream played by the SDK's
+//! `gametank::audio::sequencer` module. See that module for the format.
+
+use crate::tracker::{Beat, ChannelCmd, Pattern, SequencerCmd, TrackerData};
+
+pub const MAGIC: [u8; 4] = *b"GTS\x02";
+
+mod op {
+    pub const END: u8 = 0x00;
+    pub const WAIT: u8 = 0x01;
+
+    pub const NOTE: u8 = 0x10;
+    pub const VOLUME: u8 = 0x20;
+    pub const WAVETABLE: u8 = 0x30;
+    pub const PHASE: u8 = 0x40;
+    pub const TREMOLO: u8 = 0x50;
+    pub const VIBRATO: u8 = 0x60;
+    pub const SLIDE_VOL: u8 = 0x70;
+    pub const STOP_VSLIDE: u8 = 0x80;
+    pub const SLIDE_PITCH: u8 = 0x90;
+    pub const STOP_PSLIDE: u8 = 0xA0;
+
+    pub const TEMPO: u8 = 0xF1;
+    pub const LOAD: u8 = 0xF2;
+    pub const PATTERN: u8 = 0xF3;
+    pub const BEAT: u8 = 0xF4;
+    pub const ADVANCE: u8 = 0xF5;
+    pub const STOP: u8 = 0xF6;
+}
+
+fn push_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn encode_channel_cmd(out: &mut Vec<u8>, ch: u8, cmd: &ChannelCmd) {
+    match *cmd {
+        ChannelCmd::Note(n) => out.extend_from_slice(&[op::NOTE | ch, n & 0x7F]),
+        ChannelCmd::Volume(v) => out.extend_from_slice(&[op::VOLUME | ch, v]),
+        ChannelCmd::Wavetable(addr) => { out.push(op::WAVETABLE | ch); push_u16(out, addr); }
+        ChannelCmd::Phase(phase) => { out.push(op::PHASE | ch); push_u16(out, phase); }
+        ChannelCmd::Tremolo(depth, speed) => out.extend_from_slice(&[op::TREMOLO | ch, depth, speed]),
+        ChannelCmd::Vibrato(depth, speed) => out.extend_from_slice(&[op::VIBRATO | ch, depth, speed]),
+        ChannelCmd::SlideVol(rows, delta) => { out.extend_from_slice(&[op::SLIDE_VOL | ch, rows]); push_u16(out, delta as u16); }
+        ChannelCmd::StopVSlide => out.push(op::STOP_VSLIDE | ch),
+        ChannelCmd::SlidePitch(rows, delta) => { out.extend_from_slice(&[op::SLIDE_PITCH | ch, rows]); push_u16(out, delta as u16); }
+        ChannelCmd::StopPSlide => out.push(op::STOP_PSLIDE | ch),
+    }
+}
+
+fn encode_sequencer_cmd(out: &mut Vec<u8>, cmd: &SequencerCmd) {
+    match *cmd {
+        SequencerCmd::Tempo(bpm) => out.extend_from_slice(&[op::TEMPO, bpm]),
+        SequencerCmd::Load(slot, addr) => { out.extend_from_slice(&[op::LOAD, slot]); push_u16(out, addr); }
+        SequencerCmd::Pattern(p) => out.extend_from_slice(&[op::PATTERN, p]),
+        SequencerCmd::Beat(b) => out.extend_from_slice(&[op::BEAT, b]),
+        SequencerCmd::Advance => out.push(op::ADVANCE),
+        SequencerCmd::Stop => out.push(op::STOP),
+    }
+}
+
+fn row_is_empty(pattern: &Pattern, row: usize) -> bool {
+    pattern.iter().all(|lane| {
+        let Beat { cmd_list, sqc_list } = &lane[row];
+        cmd_list.is_empty() && sqc_list.is_empty()
+    })
+}
+
+/// The row `row` jumps to, if its first change of flow is a `Beat`
+fn beat_target(pattern: &Pattern, row: usize) -> Option<usize> {
+    match pattern[0][row].sqc_list.iter().find(|cmd| !matches!(cmd, SequencerCmd::Tempo(_) | SequencerCmd::Load(..)))? {
+        SequencerCmd::Beat(b) => Some(*b as usize),
+        _ => None,
+    }
+}
+
+/// Check that no `Beat` leads back to itself before a row passes; the
+/// player would run those rows over and over within one frame
+fn check_beats(pattern: &Pattern, index: usize) -> Result<(), String> {
+    let rows = pattern[0].len();
+    for row in 0..rows {
+        let mut seen = vec![row];
+        let mut current = row;
+        while let Some(target) = beat_target(pattern, current) {
+            // past the end or onto an empty row, it waits before going on
+            if target >= rows || row_is_empty(pattern, target) {
+                break;
+            }
+            if seen.contains(&target) {
+                return Err(format!(
+                    "pattern {:02X} row {:02X} jumps to row {:02X}, which leads straight back to it",
+                    index, row, target,
+                ));
+            }
+            seen.push(target);
+            current = target;
+        }
+    }
+    Ok(())
+}
+
+/// Encode one pattern as an event stream
+fn encode_pattern(pattern: &Pattern) -> Vec<u8> {
+    let rows = pattern[0].len();
+    let mut out = vec![];
+    let mut row = 0;
+
+    while row < rows {
+        // channel lanes first so notes land before any jump on the same row
+        for (ch, lane) in pattern.iter().enumerate().skip(1) {
+            for cmd in &lane[row].cmd_list {
+                encode_channel_cmd(&mut out, (ch - 1) as u8, cmd);
+            }
+        }
+        for cmd in &pattern[0][row].sqc_list {
+            encode_sequencer_cmd(&mut out, cmd);
+        }
+
+        let mut next = row + 1;
+        while next < rows && row_is_empty(pattern, next) {
+            next += 1;
+        }
+
+        out.extend_from_slice(&[op::WAIT, (next - row) as u8]);
+        row = next;
+    }
+
+    out.push(op::END);
+    out
+}
+
+/// Serialize tracker data into a sequencer song
+pub fn export_song(data: &TrackerData) -> Result<Vec<u8>, String> {
+    if data.patterns.is_empty() || data.patterns.len() > u8::MAX as usize {
+        return Err(format!("can't export {} patterns (1-255 supported)", data.patterns.len()));
+    }
+
+    // entries past the loop end never play, so they're left out
+    let order = data.song();
+    if order.len() > u8::MAX as usize {
+        return Err("order list is longer than 255 entries".to_string());
+    }
+    if let Some(entry) = order.iter().find(|e| e.pattern >= data.patterns.len()) {
+        return Err(format!("order list refers to missing pattern {:02X}", entry.pattern));
+    }
+
+    for (i, pattern) in data.patterns.iter().enumerate() {
+        check_beats(pattern, i)?;
+    }
+
+    let patterns: Vec<Vec<u8>> = data.patterns.iter().map(encode_pattern).collect();
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&MAGIC);
+    out.push(data.tempo);
+    out.push(patterns.len() as u8);
+    out.push(order.len() as u8);
+    out.push(data.loop_start.min(order.len() - 1) as u8);
+    for entry in order {
+        out.extend_from_slice(&[entry.pattern as u8, entry.rows]);
+    }
+
+    let mut offset = out.len() + patterns.len() * 2;
+    for pattern in &patterns {
+        let o = u16::try_from(offset).map_err(|_| "song is larger than 64K".to_string())?;
+        push_u16(&mut out, o);
+        offset += pattern.len();
+    }
+
+    for pattern in &patterns {
+        out.extend_from_slice(pattern);
+    }
+
+    Ok(out)
+}
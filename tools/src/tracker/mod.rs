@@ -0,0 +1,165 @@
+//! Tracker songs
+//!
+//! What gtgo's tracker edits: patterns of channel and sequencer commands,
+//! played in the order list's order. [`project`] reads and writes `.gtt`
+//! project files, and [`export`] turns a project into the byte stream the
+//! SDK's sequencer plays, for gtgo's export and `gtrom build`.
+
+pub mod export;
+pub mod project;
+
+pub type Pattern = [[Beat; 64]; 9];
+
+pub fn empty_pattern() -> Pattern {
+    std::array::from_fn(|_| std::array::from_fn(|_| Beat::default()))
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Beat {
+    pub cmd_list: Vec<ChannelCmd>,
+    pub sqc_list: Vec<SequencerCmd>
+}
+
+
+#[derive(Debug, Clone)]
+pub enum SequencerCmd {
+    Tempo(u8), // 0 - 256 in bpm. 60hz * 60s = 3600 / tempo = tick counter.
+    Load(u8, u16), // load a wavetable from a pointer?
+    Pattern(u8), // change to pattern #
+    Beat(u8), // set next beat to beat #
+    Advance, // continues to the next pattern in the sequence
+    Stop, // stops the sequencer
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ChannelCmd {
+    Tremolo(u8, u8), // volume
+    Vibrato(u8, u8), // pitch
+    Wavetable(u16), // set wavetable
+    Phase(u16), // set phase
+    Note(u8), // set note (freq)
+    Volume(u8), // volume index (0..=16)
+    SlideVol(u8, i16), // how many beats, delta
+    StopVSlide,
+    SlidePitch(u8, i16), // how many beats, delta
+    StopPSlide,
+}
+
+
+
+/// Rows per minute until the song says otherwise
+pub const DEFAULT_TEMPO: u8 = 240;
+
+/// Longest order list a song can export
+pub const MAX_ORDER: usize = 255;
+
+/// Voices the firmware mixes, one per pattern lane after the sequencer's
+pub const CHANNELS: usize = 8;
+
+/// How a channel is heard and shown while editing. Saved with the project,
+/// but not exported.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ChannelFlags {
+    pub muted: bool,
+    pub solo: bool,
+    /// Shown as just its notes, to fit narrow terminals
+    pub collapsed: bool,
+}
+
+/// One step of the song: a pattern, cut off after `rows` rows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderEntry {
+    pub pattern: usize,
+    pub rows: u8, // 1..=64
+}
+
+impl OrderEntry {
+    /// All of `pattern`
+    pub fn new(pattern: usize) -> Self {
+        Self { pattern, rows: 64 }
+    }
+}
+
+pub struct TrackerData {
+    pub beat: u8,
+    pub pattern: u8,
+    pub sequence: u8,
+    pub tempo: u8,
+
+    pub order: Vec<OrderEntry>, // never empty
+    pub loop_start: usize, // where playback goes after loop_end
+    pub loop_end: usize,
+    pub patterns: Vec<Pattern>,
+    pub channels: [ChannelFlags; CHANNELS],
+}
+
+impl TrackerData {
+    /// The order entries that get played; anything past the loop end never is
+    pub fn song(&self) -> &[OrderEntry] {
+        &self.order[..=self.loop_end.min(self.order.len() - 1)]
+    }
+
+    /// Where playback goes after `pos`
+    pub fn next_order(&self, pos: usize) -> usize {
+        if pos + 1 < self.song().len() { pos + 1 } else { self.loop_start.min(self.loop_end) }
+    }
+
+    /// Put `entry` at `pos`, keeping the loop points on the same entries. A
+    /// loop ending on the last entry stretches over entries added after it.
+    pub fn insert_order(&mut self, pos: usize, entry: OrderEntry) -> Result<(), String> {
+        if self.order.len() >= MAX_ORDER {
+            return Err(format!("the order list is full ({} entries)", MAX_ORDER));
+        }
+
+        let pos = pos.min(self.order.len());
+        let follow_end = pos == self.order.len() && self.loop_end == pos - 1;
+        self.order.insert(pos, entry);
+
+        if self.loop_start >= pos {
+            self.loop_start += 1;
+        }
+        if self.loop_end >= pos || follow_end {
+            self.loop_end += 1;
+        }
+        Ok(())
+    }
+
+    /// Remove the entry at `pos`, unless it's the only one
+    pub fn remove_order(&mut self, pos: usize) -> Result<OrderEntry, String> {
+        if self.order.len() <= 1 {
+            return Err("the song needs at least one order entry".to_string());
+        }
+        if pos >= self.order.len() {
+            return Err(format!("no order entry {:02X}", pos));
+        }
+
+        let entry = self.order.remove(pos);
+        if self.loop_start > pos {
+            self.loop_start -= 1;
+        }
+        if self.loop_end >= pos {
+            self.loop_end = self.loop_end.saturating_sub(1);
+        }
+        self.clamp_loop();
+        Ok(entry)
+    }
+
+    /// Whether the preview plays `ch`: only soloed channels while any are,
+    /// otherwise every channel that isn't muted
+    pub fn audible(&self, ch: usize) -> bool {
+        let flags = self.channels[ch];
+        if self.channels.iter().any(|c| c.solo) { flags.solo } else { !flags.muted }
+    }
+
+    /// Whether no pattern has anything on `ch`
+    pub fn is_unused(&self, ch: usize) -> bool {
+        self.patterns.iter().all(|p| p[ch + 1].iter().all(|beat| beat.cmd_list.is_empty()))
+    }
+
+    /// Keep the loop points on the order list, in order
+    pub fn clamp_loop(&mut self) {
+        let last = self.order.len() - 1;
+        self.loop_end = self.loop_end.min(last);
+        self.loop_start = self.loop_start.min(self.loop_end);
+    }
+}
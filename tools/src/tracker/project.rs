@@ -0,0 +1,290 @@
+//! Tracker project files
+//!
+//! A `.gtt` file holds everything needed to pick up editing where you left
+//! off. All integers are little-endian.
+//!
+//! - magic `GTT\x03`, tempo (u8), pattern count (u16)
+//! - order list length, loop start and loop end (u16 each)
+//! - the order list, each entry a u16 pattern index and a u8 row count
+//! - a flags byte per channel: bit 0 muted, bit 1 solo, bit 2 collapsed
+//! - each pattern as 9 lanes x 64 beats, where a beat is a u8 channel
+//!   command count, the channel commands, a u8 sequencer command count and
+//!   the sequencer commands
+//!
+//! Commands are a tag byte followed by their arguments.
+//!
+//! Version 2 files had no channel flags, and open with every channel shown
+//! and playing.
+//!
+//! A song's wavetables aren't in the file: they're the `.wav` files in a
+//! `<name>.wavetables` directory next to `<name>.gtt` (see
+//! [`wavetable_files`]), which `gtrom build` converts into the ROM with the
+//! song.
+//!
+//! Version 1 files had a 256-entry table of u16 pattern indices in place of
+//! the order list. They still open, with the table up to its last non-zero
+//! entry as the order list, every entry a whole pattern, looping the lot.
+
+use std::path::{Path, PathBuf};
+
+use crate::tracker::{empty_pattern, Beat, ChannelCmd, ChannelFlags, OrderEntry, Pattern, SequencerCmd, TrackerData, CHANNELS, MAX_ORDER};
+
+pub const MAGIC: [u8; 4] = *b"GTT\x03";
+const MAGIC_V2: [u8; 4] = *b"GTT\x02";
+const MAGIC_V1: [u8; 4] = *b"GTT\x01";
+pub const EXTENSION: &str = "gtt";
+/// Suffix of the directory holding a song's wavetables
+pub const WAVETABLES_SUFFIX: &str = ".wavetables";
+
+const MUTED: u8 = 1 << 0;
+const SOLO: u8 = 1 << 1;
+const COLLAPSED: u8 = 1 << 2;
+
+fn push_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn write_channel_cmd(out: &mut Vec<u8>, cmd: &ChannelCmd) {
+    match *cmd {
+        ChannelCmd::Tremolo(depth, speed) => out.extend_from_slice(&[0, depth, speed]),
+        ChannelCmd::Vibrato(depth, speed) => out.extend_from_slice(&[1, depth, speed]),
+        ChannelCmd::Wavetable(addr) => { out.push(2); push_u16(out, addr); }
+        ChannelCmd::Phase(phase) => { out.push(3); push_u16(out, phase); }
+        ChannelCmd::Note(n) => out.extend_from_slice(&[4, n]),
+        ChannelCmd::Volume(v) => out.extend_from_slice(&[5, v]),
+        ChannelCmd::SlideVol(rows, delta) => { out.extend_from_slice(&[6, rows]); push_u16(out, delta as u16); }
+        ChannelCmd::StopVSlide => out.push(7),
+        ChannelCmd::SlidePitch(rows, delta) => { out.extend_from_slice(&[8, rows]); push_u16(out, delta as u16); }
+        ChannelCmd::StopPSlide => out.push(9),
+    }
+}
+
+fn write_sequencer_cmd(out: &mut Vec<u8>, cmd: &SequencerCmd) {
+    match *cmd {
+        SequencerCmd::Tempo(bpm) => out.extend_from_slice(&[0, bpm]),
+        SequencerCmd::Load(slot, addr) => { out.extend_from_slice(&[1, slot]); push_u16(out, addr); }
+        SequencerCmd::Pattern(p) => out.extend_from_slice(&[2, p]),
+        SequencerCmd::Beat(b) => out.extend_from_slice(&[3, b]),
+        SequencerCmd::Advance => out.push(4),
+        SequencerCmd::Stop => out.push(5),
+    }
+}
+
+/// Serialize a project
+pub fn to_bytes(data: &TrackerData) -> Result<Vec<u8>, String> {
+    let pattern_count = u16::try_from(data.patterns.len())
+        .map_err(|_| format!("too many patterns ({})", data.patterns.len()))?;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&MAGIC);
+    out.push(data.tempo);
+    push_u16(&mut out, pattern_count);
+
+    push_u16(&mut out, data.order.len() as u16);
+    push_u16(&mut out, data.loop_start as u16);
+    push_u16(&mut out, data.loop_end as u16);
+    for entry in &data.order {
+        push_u16(&mut out, entry.pattern.min(u16::MAX as usize) as u16);
+        out.push(entry.rows);
+    }
+
+    for ch in &data.channels {
+        let flag = |set: bool, bit: u8| if set { bit } else { 0 };
+        out.push(flag(ch.muted, MUTED) | flag(ch.solo, SOLO) | flag(ch.collapsed, COLLAPSED));
+    }
+
+    for pattern in &data.patterns {
+        for lane in pattern {
+            for Beat { cmd_list, sqc_list } in lane {
+                if cmd_list.len() > u8::MAX as usize || sqc_list.len() > u8::MAX as usize {
+                    return Err("a beat has more than 255 commands".to_string());
+                }
+
+                out.push(cmd_list.len() as u8);
+                for cmd in cmd_list {
+                    write_channel_cmd(&mut out, cmd);
+                }
+                out.push(sqc_list.len() as u8);
+                for cmd in sqc_list {
+                    write_sequencer_cmd(&mut out, cmd);
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn u8(&mut self) -> Result<u8, String> {
+        let b = *self.bytes.get(self.pos).ok_or("unexpected end of file")?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn channel_cmd(&mut self) -> Result<ChannelCmd, String> {
+        Ok(match self.u8()? {
+            0 => ChannelCmd::Tremolo(self.u8()?, self.u8()?),
+            1 => ChannelCmd::Vibrato(self.u8()?, self.u8()?),
+            2 => ChannelCmd::Wavetable(self.u16()?),
+            3 => ChannelCmd::Phase(self.u16()?),
+            4 => ChannelCmd::Note(self.u8()?),
+            5 => ChannelCmd::Volume(self.u8()?),
+            6 => ChannelCmd::SlideVol(self.u8()?, self.u16()? as i16),
+            7 => ChannelCmd::StopVSlide,
+            8 => ChannelCmd::SlidePitch(self.u8()?, self.u16()? as i16),
+            9 => ChannelCmd::StopPSlide,
+            tag => return Err(format!("unknown channel command {:#04x} at byte {}", tag, self.pos - 1)),
+        })
+    }
+
+    fn sequencer_cmd(&mut self) -> Result<SequencerCmd, String> {
+        Ok(match self.u8()? {
+            0 => SequencerCmd::Tempo(self.u8()?),
+            1 => SequencerCmd::Load(self.u8()?, self.u16()?),
+            2 => SequencerCmd::Pattern(self.u8()?),
+            3 => SequencerCmd::Beat(self.u8()?),
+            4 => SequencerCmd::Advance,
+            5 => SequencerCmd::Stop,
+            tag => return Err(format!("unknown sequencer command {:#04x} at byte {}", tag, self.pos - 1)),
+        })
+    }
+
+    fn order(&mut self) -> Result<(Vec<OrderEntry>, usize, usize), String> {
+        let len = self.u16()? as usize;
+        let loop_start = self.u16()? as usize;
+        let loop_end = self.u16()? as usize;
+        if len == 0 || len > MAX_ORDER {
+            return Err(format!("order list has {} entries (1-{} supported)", len, MAX_ORDER));
+        }
+
+        let order = (0..len).map(|_| Ok(OrderEntry {
+            pattern: self.u16()? as usize,
+            rows: self.u8()?.clamp(1, 64),
+        })).collect::<Result<Vec<_>, String>>()?;
+        Ok((order, loop_start, loop_end))
+    }
+
+    /// The version 1 sequence table, as an order list
+    fn v1_order(&mut self) -> Result<(Vec<OrderEntry>, usize, usize), String> {
+        let table = (0..256).map(|_| self.u16()).collect::<Result<Vec<_>, _>>()?;
+        let len = table.iter().rposition(|&p| p != 0).map_or(1, |i| i + 1).min(MAX_ORDER);
+        let order: Vec<_> = table[..len].iter().map(|&p| OrderEntry::new(p as usize)).collect();
+        Ok((order, 0, len - 1))
+    }
+
+    fn channels(&mut self) -> Result<[ChannelFlags; CHANNELS], String> {
+        let mut channels = [ChannelFlags::default(); CHANNELS];
+        for ch in channels.iter_mut() {
+            let flags = self.u8()?;
+            *ch = ChannelFlags {
+                muted: flags & MUTED != 0,
+                solo: flags & SOLO != 0,
+                collapsed: flags & COLLAPSED != 0,
+            };
+        }
+        Ok(channels)
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        let mut pattern = empty_pattern();
+        for lane in pattern.iter_mut() {
+            for beat in lane.iter_mut() {
+                for _ in 0..self.u8()? {
+                    beat.cmd_list.push(self.channel_cmd()?);
+                }
+                for _ in 0..self.u8()? {
+                    beat.sqc_list.push(self.sequencer_cmd()?);
+                }
+            }
+        }
+        Ok(pattern)
+    }
+}
+
+/// Deserialize a project
+pub fn from_bytes(bytes: &[u8]) -> Result<TrackerData, String> {
+    let version = match bytes.get(0..4) {
+        Some(magic) if magic == MAGIC => 3,
+        Some(magic) if magic == MAGIC_V2 => 2,
+        Some(magic) if magic == MAGIC_V1 => 1,
+        _ => return Err("not a .gtt project".to_string()),
+    };
+
+    let mut r = Reader { bytes, pos: 4 };
+    let tempo = r.u8()?;
+    let pattern_count = r.u16()?;
+    if pattern_count == 0 || pattern_count > u8::MAX as u16 {
+        return Err(format!("project has {} patterns (1-255 supported)", pattern_count));
+    }
+
+    let (order, loop_start, loop_end) = if version == 1 { r.v1_order()? } else { r.order()? };
+    let channels = if version >= 3 { r.channels()? } else { Default::default() };
+
+    let patterns = (0..pattern_count).map(|_| r.pattern()).collect::<Result<Vec<_>, _>>()?;
+    if let Some(entry) = order.iter().find(|entry| entry.pattern >= patterns.len()) {
+        return Err(format!("order list refers to missing pattern {:02X}", entry.pattern));
+    }
+
+    let mut data = TrackerData {
+        beat: 0,
+        pattern: 0,
+        sequence: 0,
+        tempo,
+        order,
+        loop_start,
+        loop_end,
+        patterns,
+        channels,
+    };
+    data.clamp_loop();
+    Ok(data)
+}
+
+pub fn save(data: &TrackerData, path: &Path) -> Result<(), String> {
+    let bytes = to_bytes(data)?;
+    std::fs::write(path, bytes)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub fn load(path: &Path) -> Result<TrackerData, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    from_bytes(&bytes).map_err(|e| format!("Failed to load {}: {}", path.display(), e))
+}
+
+/// The directory holding the wavetables of the song at `gtt`
+pub fn wavetables_dir(gtt: &Path) -> PathBuf {
+    let stem = gtt.file_stem().unwrap_or_default().to_string_lossy();
+    gtt.with_file_name(format!("{}{}", stem, WAVETABLES_SUFFIX))
+}
+
+/// The `.wav` files that go with the song at `gtt`, in the order they're
+/// loaded into slots, or none without a wavetables directory.
+pub fn wavetable_files(gtt: &Path) -> Result<Vec<PathBuf>, String> {
+    let dir = wavetables_dir(gtt);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let entries = std::fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?.path();
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav")) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
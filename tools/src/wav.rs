@@ -0,0 +1,223 @@
+//! WAV to wavetable/sample conversion
+//!
+//! Turns a short recording into either a single-cycle 256-byte wavetable for
+//! the wavetable firmware, or a raw 8-bit sample blob at the ACP's playback
+//! rate. Both are unsigned and centered on 0x80, like the firmware's own
+//! tables.
+//!
+//! A mono 8-bit WAV of exactly 256 samples is taken to be a wavetable
+//! already and used byte for byte. That's what [`write_wavetable`] writes,
+//! so a table made in gtgo's instrument editor builds into the ROM as it
+//! was previewed.
+
+use std::path::Path;
+
+/// Bytes in one wavetable slot
+pub const WAVETABLE_SIZE: usize = 256;
+
+/// ACP sample rate with the default `set_audio(0xFF)`: the CPU clock / 255
+pub const ACP_SAMPLE_RATE: u32 = 14_037;
+
+/// Pitch search range for wavetables
+const MIN_PITCH_HZ: f32 = 20.0;
+const MAX_PITCH_HZ: f32 = 2_000.0;
+
+/// Dips in the normalized difference function below this count as a period
+const PITCH_THRESHOLD: f32 = 0.15;
+
+/// Samples below this level at the start are skipped as silence
+const SILENCE: f32 = 0.02;
+
+/// Where a wavetable's cycle comes from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cycle {
+    /// One period at the pitch detected in the recording
+    #[default]
+    Detect,
+    /// The whole recording, for files that are a single cycle already
+    Whole,
+}
+
+/// Mono samples in -1.0..=1.0
+pub struct Audio {
+    samples: Vec<f32>,
+    rate: u32,
+    /// The bytes of a file that's a wavetable already
+    table: Option<Vec<u8>>,
+}
+
+impl Audio {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut reader = hound::WavReader::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let spec = reader.spec();
+
+        let interleaved: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+            hound::SampleFormat::Int => {
+                let scale = (1u32 << (spec.bits_per_sample - 1)) as f32;
+                reader.samples::<i32>().map(|s| s.map(|s| s as f32 / scale)).collect::<Result<_, _>>()
+            }
+        }.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        let channels = spec.channels.max(1) as usize;
+        let samples: Vec<f32> = interleaved.chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+
+        if samples.is_empty() {
+            return Err(format!("{} has no samples", path.display()));
+        }
+
+        let is_table = spec.sample_format == hound::SampleFormat::Int
+            && spec.bits_per_sample == 8
+            && channels == 1
+            && samples.len() == WAVETABLE_SIZE;
+        // 8-bit samples came in as (byte - 128) / 128
+        let table = is_table.then(|| samples.iter().map(|s| (s * 128.0 + 128.0) as u8).collect());
+
+        Ok(Self { samples, rate: spec.sample_rate, table })
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Linearly interpolated sample at a fractional position
+    fn at(&self, pos: f32) -> f32 {
+        let i = pos.floor() as usize;
+        let frac = pos - pos.floor();
+        let a = self.samples.get(i).copied().unwrap_or(0.0);
+        let b = self.samples.get(i + 1).copied().unwrap_or(a);
+        a + (b - a) * frac
+    }
+
+    /// Index of the first sample louder than silence
+    fn start(&self) -> usize {
+        self.samples.iter().position(|s| s.abs() > SILENCE).unwrap_or(0)
+    }
+
+    /// Fundamental period in samples, found with the normalized difference
+    /// function from the YIN pitch detector
+    fn detect_period(&self, from: usize) -> Option<f32> {
+        let min_lag = (self.rate as f32 / MAX_PITCH_HZ) as usize;
+        let max_lag = (self.rate as f32 / MIN_PITCH_HZ) as usize;
+        let window = max_lag;
+
+        let x = self.samples.get(from..)?;
+        let max_lag = max_lag.min(x.len().saturating_sub(window));
+        if max_lag <= min_lag.max(2) {
+            return None;
+        }
+
+        let diff: Vec<f32> = (0..=max_lag)
+            .map(|lag| (0..window).map(|i| (x[i] - x[i + lag]).powi(2)).sum())
+            .collect();
+
+        // cumulative mean normalized difference
+        let mut cmnd = vec![1.0; diff.len()];
+        let mut running = 0.0;
+        for lag in 1..diff.len() {
+            running += diff[lag];
+            cmnd[lag] = if running > 0.0 { diff[lag] * lag as f32 / running } else { 1.0 };
+        }
+
+        // first dip under the threshold, else the deepest one
+        let lag = (min_lag.max(1)..max_lag)
+            .find(|&lag| cmnd[lag] < PITCH_THRESHOLD && cmnd[lag] <= cmnd[lag + 1])
+            .or_else(|| (min_lag.max(1)..max_lag).min_by(|&a, &b| cmnd[a].total_cmp(&cmnd[b])))?;
+
+        // parabolic interpolation for a fractional period
+        let (a, b, c) = (cmnd[lag - 1], cmnd[lag], cmnd[lag + 1]);
+        let denom = a - 2.0 * b + c;
+        let offset = if denom.abs() > f32::EPSILON { 0.5 * (a - c) / denom } else { 0.0 };
+        Some(lag as f32 + offset.clamp(-0.5, 0.5))
+    }
+
+    /// First rising zero crossing at or after `from`, so the cycle loops cleanly
+    fn rising_zero_crossing(&self, from: usize) -> usize {
+        (from..self.samples.len().saturating_sub(1))
+            .find(|&i| self.samples[i] <= 0.0 && self.samples[i + 1] > 0.0)
+            .unwrap_or(from)
+    }
+
+    /// One cycle of the recording, DC-free and normalized, and its pitch
+    pub fn wavetable(&self, cycle: Cycle) -> Result<(Vec<u8>, f32), String> {
+        if let Some(table) = &self.table {
+            return Ok((table.clone(), self.rate as f32 / WAVETABLE_SIZE as f32));
+        }
+
+        let (cycle_start, period) = match cycle {
+            Cycle::Detect => {
+                let start = self.start();
+                let period = self.detect_period(start)
+                    .ok_or("Couldn't detect a pitch; the file is too short or not periodic")?;
+                (self.rising_zero_crossing(start), period)
+            }
+            Cycle::Whole => (0, self.samples.len() as f32),
+        };
+
+        let mut cycle: Vec<f32> = (0..WAVETABLE_SIZE)
+            .map(|i| self.at(cycle_start as f32 + i as f32 * period / WAVETABLE_SIZE as f32))
+            .collect();
+
+        let mean = cycle.iter().sum::<f32>() / cycle.len() as f32;
+        let peak = cycle.iter().map(|s| (s - mean).abs()).fold(0.0, f32::max);
+        let gain = if peak > 0.0 { 1.0 / peak } else { 0.0 };
+        for s in &mut cycle {
+            *s = (*s - mean) * gain;
+        }
+
+        Ok((cycle.into_iter().map(to_u8).collect(), self.rate as f32 / period))
+    }
+
+    /// The whole recording at `rate`. Each output sample averages the input
+    /// it covers, which keeps aliasing down when going to a lower rate.
+    pub fn sample(&self, rate: u32) -> Vec<u8> {
+        let step = self.rate as f32 / rate as f32;
+        let len = (self.samples.len() as f32 / step).ceil() as usize;
+
+        (0..len)
+            .map(|i| {
+                let from = i as f32 * step;
+                if step <= 1.0 {
+                    return to_u8(self.at(from));
+                }
+                let window = &self.samples[from as usize..((from + step) as usize).min(self.samples.len())];
+                to_u8(window.iter().sum::<f32>() / window.len().max(1) as f32)
+            })
+            .collect()
+    }
+}
+
+/// 8-bit unsigned, centered on 0x80
+fn to_u8(sample: f32) -> u8 {
+    (128.0 + sample.clamp(-1.0, 1.0) * 127.0).round() as u8
+}
+
+/// A WAV file as a wavetable, with the pitch detected in it
+pub fn load_wavetable(path: &Path) -> Result<(Vec<u8>, f32), String> {
+    Audio::load(path)?.wavetable(Cycle::Detect).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// A WAV file as a sample at `rate`
+pub fn load_sample(path: &Path, rate: u32) -> Result<Vec<u8>, String> {
+    Ok(Audio::load(path)?.sample(rate))
+}
+
+/// Write a wavetable as a WAV that [`load_wavetable`] reads back byte for
+/// byte. The sample rate is set so it plays one cycle at `pitch`.
+pub fn write_wavetable(path: &Path, table: &[u8], pitch: f32) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: (pitch * WAVETABLE_SIZE as f32).round().max(1.0) as u32,
+        bits_per_sample: 8,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let fail = |e: hound::Error| format!("Failed to write {}: {}", path.display(), e);
+    let mut writer = hound::WavWriter::create(path, spec).map_err(fail)?;
+    for &byte in table {
+        writer.write_sample(byte.wrapping_sub(0x80) as i8).map_err(fail)?;
+    }
+    writer.finalize().map_err(fail)
+}